@@ -0,0 +1,239 @@
+//! Compares the crate's structures against their std counterparts.
+//! Run with `cargo bench --bench structures`, optionally setting `BENCH_SIZES` to a
+//! comma-separated list of element counts (the default is `1000,100000`).
+//! Each measurement is the fastest of several runs, reported per operation.
+
+use std::{
+    collections::{BTreeMap, BinaryHeap, HashMap},
+    hint::black_box,
+    time::{Duration, Instant},
+};
+
+use rastd::{
+    avl_tree::AVLTree, bs_tree::BSTree, hash_trie::HashTrie, heap::Heap, lru_cache::LRUCache,
+};
+
+const RUNS: usize = 5;
+
+fn main() {
+    let sizes = std::env::var("BENCH_SIZES")
+        .map(|s| {
+            s.split(',')
+                .map(|n| n.trim().parse().expect("sizes must be integers"))
+                .collect()
+        })
+        .unwrap_or_else(|_| vec![1000, 100_000]);
+    for size in sizes {
+        println!("n = {}", size);
+        ordered_maps(size);
+        tries(size);
+        heaps(size);
+        lru_cache(size);
+    }
+}
+
+/// Times `run` on fresh input from `setup`, returning the fastest time per element.
+fn bench<S, T>(name: &str, n: usize, mut setup: impl FnMut() -> S, mut run: impl FnMut(S) -> T) {
+    let mut best = Duration::MAX;
+    for _ in 0..RUNS {
+        let input = setup();
+        let start = Instant::now();
+        black_box(run(input));
+        best = best.min(start.elapsed());
+    }
+    println!(
+        "  {:<28} {:>10.1} ns/op",
+        name,
+        best.as_nanos() as f64 / n as f64
+    );
+}
+
+/// The keys `0..n` in a fixed pseudo-random order, so unbalanced trees stay shallow.
+fn shuffled(n: usize) -> Vec<u64> {
+    let mut keys = (0..n as u64).collect::<Vec<_>>();
+    let mut rng = 0x2545_f491_4f6c_dd1d_u64;
+    for i in (1..keys.len()).rev() {
+        rng ^= rng << 13;
+        rng ^= rng >> 7;
+        rng ^= rng << 17;
+        keys.swap(i, rng as usize % (i + 1));
+    }
+    keys
+}
+
+fn ordered_maps(n: usize) {
+    let keys = shuffled(n);
+    let avl = || {
+        let mut tree = AVLTree::new();
+        keys.iter().for_each(|&k| tree.insert(k, k));
+        tree
+    };
+    let bst = || {
+        let mut tree = BSTree::new();
+        keys.iter().for_each(|&k| {
+            tree.insert(k);
+        });
+        tree
+    };
+    let btree = || keys.iter().map(|&k| (k, k)).collect::<BTreeMap<_, _>>();
+
+    bench("AVLTree insert", n, || (), |_| avl());
+    bench("BSTree insert", n, || (), |_| bst());
+    bench("BTreeMap insert", n, || (), |_| btree());
+
+    let (a, b, c) = (avl(), bst(), btree());
+    bench(
+        "AVLTree get",
+        n,
+        || (),
+        |_| keys.iter().filter(|k| a.get(k).is_some()).count(),
+    );
+    bench(
+        "BSTree get",
+        n,
+        || (),
+        |_| keys.iter().filter(|&&k| b.search(k).is_some()).count(),
+    );
+    bench(
+        "BTreeMap get",
+        n,
+        || (),
+        |_| keys.iter().filter(|k| c.contains_key(k)).count(),
+    );
+
+    bench(
+        "AVLTree iterate",
+        n,
+        || (),
+        |_| a.iter().fold(0, |acc, (k, _)| acc ^ k),
+    );
+    bench(
+        "BSTree iterate",
+        n,
+        || (),
+        |_| b.iter().fold(0, |acc, k| acc ^ k),
+    );
+    bench(
+        "BTreeMap iterate",
+        n,
+        || (),
+        |_| c.keys().fold(0, |acc, k| acc ^ k),
+    );
+    // Many short scans, where setting up the iterator dominates
+    bench(
+        "AVLTree range (8 keys)",
+        n,
+        || (),
+        |_| {
+            keys.iter()
+                .map(|k| a.range(k..).take(8).count())
+                .sum::<usize>()
+        },
+    );
+    bench(
+        "BSTree iterate (8 keys)",
+        n,
+        || (),
+        |_| keys.iter().map(|_| b.iter().take(8).count()).sum::<usize>(),
+    );
+    bench(
+        "BTreeMap range (8 keys)",
+        n,
+        || (),
+        |_| {
+            keys.iter()
+                .map(|k| c.range(k..).take(8).count())
+                .sum::<usize>()
+        },
+    );
+
+    bench("AVLTree remove", n, avl, |mut t| {
+        keys.iter().map(|k| t.remove(k)).count()
+    });
+    bench("BSTree remove", n, bst, |mut t| {
+        keys.iter().map(|&k| t.remove(k)).count()
+    });
+    bench("BTreeMap remove", n, btree, |mut t| {
+        keys.iter().map(|k| t.remove(k)).count()
+    });
+}
+
+fn tries(n: usize) {
+    let keys = shuffled(n)
+        .into_iter()
+        .map(|k| k.to_string().into_bytes())
+        .collect::<Vec<_>>();
+    let trie = || {
+        let mut trie = HashTrie::new();
+        keys.iter().for_each(|k| {
+            trie.insert(k, ());
+        });
+        trie
+    };
+    let map = || {
+        keys.iter()
+            .map(|k| (k.clone(), ()))
+            .collect::<HashMap<_, _>>()
+    };
+
+    bench("HashTrie insert", n, || (), |_| trie());
+    bench("HashMap insert", n, || (), |_| map());
+
+    let (t, m) = (trie(), map());
+    bench(
+        "HashTrie get",
+        n,
+        || (),
+        |_| keys.iter().filter(|k| t.get(k).is_some()).count(),
+    );
+    bench(
+        "HashMap get",
+        n,
+        || (),
+        |_| keys.iter().filter(|k| m.contains_key(*k)).count(),
+    );
+
+    bench("HashTrie remove", n, trie, |mut t| {
+        keys.iter().map(|k| t.remove(k)).count()
+    });
+    bench("HashMap remove", n, map, |mut m| {
+        keys.iter().map(|k| m.remove(k)).count()
+    });
+}
+
+fn heaps(n: usize) {
+    let keys = shuffled(n);
+    let heap = || {
+        let mut heap = Heap::new();
+        keys.iter().for_each(|&k| heap.push(k));
+        heap
+    };
+    let binary = || {
+        keys.iter()
+            .map(|&k| std::cmp::Reverse(k))
+            .collect::<BinaryHeap<_>>()
+    };
+
+    bench("Heap push", n, || (), |_| heap());
+    bench("BinaryHeap push", n, || (), |_| binary());
+    bench("Heap pop", n, heap, |mut h| {
+        std::iter::from_fn(|| h.pop()).count()
+    });
+    bench("BinaryHeap pop", n, binary, |mut h| {
+        std::iter::from_fn(|| h.pop()).count()
+    });
+}
+
+fn lru_cache(n: usize) {
+    let keys = shuffled(n);
+    // A cache holding a tenth of the keys, so most inserts evict
+    let cache = || {
+        let mut cache = LRUCache::new(n / 10 + 1);
+        keys.iter().for_each(|&k| cache.insert(k, k));
+        cache
+    };
+    bench("LRUCache insert", n, || (), |_| cache());
+    bench("LRUCache get", n, cache, |mut c| {
+        keys.iter().filter(|k| c.get(k).is_some()).count()
+    });
+}
@@ -0,0 +1,132 @@
+//! The allocator interface used by the node-based structures.
+//! With the `nightly` feature this is the unstable `std::alloc::Allocator`, so any allocator
+//! written for std works. On stable it is a copy of that trait with the same signatures,
+//! which custom allocators can implement directly until it is stabilized.
+
+use std::{alloc::Layout, ptr::NonNull};
+
+#[cfg(feature = "nightly")]
+pub use std::alloc::{AllocError, Allocator, Global};
+
+/// The error returned when an allocator is out of memory.
+#[cfg(not(feature = "nightly"))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AllocError;
+
+/// A stable stand-in for `std::alloc::Allocator`.
+///
+/// # Safety
+/// Memory returned by `allocate` must stay valid until it is passed to `deallocate`, even if
+/// the allocator is moved, and clones of an allocator must be able to free each other's memory.
+#[cfg(not(feature = "nightly"))]
+pub unsafe trait Allocator {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError>;
+
+    /// # Safety
+    /// `ptr` must have been returned by `allocate` on this allocator, or a clone of it,
+    /// with the same layout.
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout);
+}
+
+/// The global allocator.
+#[cfg(not(feature = "nightly"))]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Global;
+
+#[cfg(not(feature = "nightly"))]
+unsafe impl Allocator for Global {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        if layout.size() == 0 {
+            let dangling = NonNull::new(layout.align() as *mut u8).unwrap();
+            return Ok(NonNull::slice_from_raw_parts(dangling, 0));
+        }
+        let ptr = NonNull::new(unsafe { std::alloc::alloc(layout) }).ok_or(AllocError)?;
+        Ok(NonNull::slice_from_raw_parts(ptr, layout.size()))
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        if layout.size() != 0 {
+            std::alloc::dealloc(ptr.as_ptr(), layout);
+        }
+    }
+}
+
+#[cfg(not(feature = "nightly"))]
+unsafe impl<A: Allocator + ?Sized> Allocator for &A {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        (**self).allocate(layout)
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        (**self).deallocate(ptr, layout)
+    }
+}
+
+/// Moves the value into memory from the allocator, like `Box::new_in`.
+#[cfg_attr(not(feature = "avl_tree"), allow(dead_code))]
+pub(crate) fn allocate_value<T, A: Allocator>(alloc: &A, value: T) -> NonNull<T> {
+    let layout = Layout::new::<T>();
+    let ptr = match alloc.allocate(layout) {
+        Ok(ptr) => ptr.cast::<T>(),
+        Err(_) => std::alloc::handle_alloc_error(layout),
+    };
+    unsafe { ptr.as_ptr().write(value) };
+    ptr
+}
+
+/// Drops the value and returns its memory to the allocator.
+///
+/// # Safety
+/// `ptr` must have come from `allocate_value` with the same allocator, or a clone of it,
+/// and must not be used afterwards.
+#[cfg_attr(not(feature = "avl_tree"), allow(dead_code))]
+pub(crate) unsafe fn deallocate_value<T, A: Allocator>(alloc: &A, ptr: NonNull<T>) {
+    std::ptr::drop_in_place(ptr.as_ptr());
+    alloc.deallocate(ptr.cast(), Layout::new::<T>());
+}
+
+/// Moves the value out and returns its memory to the allocator.
+///
+/// # Safety
+/// The same as for `deallocate_value`.
+#[cfg_attr(not(feature = "avl_tree"), allow(dead_code))]
+pub(crate) unsafe fn take_value<T, A: Allocator>(alloc: &A, ptr: NonNull<T>) -> T {
+    let value = ptr.as_ptr().read();
+    alloc.deallocate(ptr.cast(), Layout::new::<T>());
+    value
+}
+
+/// Counts the live allocations made through it.
+#[cfg(test)]
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Counting(pub(crate) std::rc::Rc<std::cell::Cell<isize>>);
+
+#[cfg(test)]
+unsafe impl Allocator for Counting {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        self.0.set(self.0.get() + 1);
+        Global.allocate(layout)
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        self.0.set(self.0.get() - 1);
+        Global.deallocate(ptr, layout)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{allocate_value, deallocate_value, Counting, Global};
+
+    #[test]
+    fn round_trip() {
+        let alloc = Counting::default();
+        let ptr = allocate_value(&alloc, String::from("value"));
+        assert_eq!(unsafe { ptr.as_ref() }, "value");
+        assert_eq!(alloc.0.get(), 1);
+        unsafe { deallocate_value(&alloc, ptr) };
+        assert_eq!(alloc.0.get(), 0);
+        let unit = allocate_value(&Global, ());
+        unsafe { deallocate_value(&Global, unit) };
+    }
+}
@@ -0,0 +1,205 @@
+use std::{
+    cell::RefCell,
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+const DEFAULT_CHUNK: usize = 8;
+
+// Gives every arena a distinct id so that handles can't be used with the wrong arena
+static NEXT_ARENA_ID: AtomicU32 = AtomicU32::new(0);
+
+/// A handle to a value inserted into an `Arena`. Handles are invalidated by `reset`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Handle {
+    arena: u32,
+    generation: u32,
+    index: u32,
+}
+
+/// A typed bump arena. Values are allocated into chunks which never move, so references
+/// to them stay valid while more values are allocated, and everything is freed at once
+/// when the arena is dropped or reset. Each chunk is twice the size of the one before.
+/// Values can be accessed by reference through `alloc` or by `Handle` through `insert`.
+#[derive(Debug)]
+pub struct Arena<T> {
+    chunks: RefCell<Vec<Vec<T>>>,
+    first_chunk: usize,
+    id: u32,
+    generation: u32,
+}
+
+impl<T> Arena<T> {
+    pub fn new() -> Self {
+        Arena::with_capacity(DEFAULT_CHUNK)
+    }
+
+    /// Creates an arena whose first chunk holds `capacity` values.
+    pub fn with_capacity(capacity: usize) -> Self {
+        let first_chunk = capacity.max(1);
+        Arena {
+            chunks: RefCell::new(vec![Vec::with_capacity(first_chunk)]),
+            first_chunk,
+            id: NEXT_ARENA_ID.fetch_add(1, Ordering::Relaxed),
+            generation: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        let chunks = self.chunks.borrow();
+        let last = chunks.len() - 1;
+        self.chunk_start(last) + chunks[last].len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Moves the value into the arena, returning a reference which lives as long as the arena.
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc(&self, value: T) -> &mut T {
+        self.push(value).0
+    }
+
+    /// Moves the value into the arena, returning a handle to it.
+    pub fn insert(&mut self, value: T) -> Handle {
+        let index = self.push(value).1;
+        Handle {
+            arena: self.id,
+            generation: self.generation,
+            index: index as u32,
+        }
+    }
+
+    pub fn get(&self, handle: Handle) -> Option<&T> {
+        let (chunk, offset) = self.locate(handle)?;
+        let chunks = self.chunks.borrow();
+        let value = chunks.get(chunk)?.get(offset)?;
+        // Chunks never reallocate, so the value outlives the borrow of the chunk list
+        Some(unsafe { &*(value as *const T) })
+    }
+
+    pub fn get_mut(&mut self, handle: Handle) -> Option<&mut T> {
+        let (chunk, offset) = self.locate(handle)?;
+        self.chunks.get_mut().get_mut(chunk)?.get_mut(offset)
+    }
+
+    /// Iterates over the values in allocation order.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.chunks.get_mut().iter_mut().flatten()
+    }
+
+    /// Drops every value and invalidates every handle, keeping the largest chunk for reuse.
+    pub fn reset(&mut self) {
+        let chunks = self.chunks.get_mut();
+        // The kept chunk becomes the first, so the chunk sizes still double from it
+        self.first_chunk <<= chunks.len() - 1;
+        let mut largest = chunks.pop().unwrap();
+        largest.clear();
+        chunks.clear();
+        chunks.push(largest);
+        self.generation = self.generation.wrapping_add(1);
+    }
+
+    pub fn into_vec(self) -> Vec<T> {
+        self.chunks.into_inner().into_iter().flatten().collect()
+    }
+
+    #[allow(clippy::mut_from_ref)]
+    fn push(&self, value: T) -> (&mut T, usize) {
+        let mut chunks = self.chunks.borrow_mut();
+        let mut last = chunks.len() - 1;
+        if chunks[last].len() == self.chunk_capacity(last) {
+            last += 1;
+            chunks.push(Vec::with_capacity(self.chunk_capacity(last)));
+        }
+        let chunk = &mut chunks[last];
+        let index = self.chunk_start(last) + chunk.len();
+        // Chunks are never pushed past their reserved capacity, so they never reallocate
+        // and the value keeps its address for the lifetime of the arena
+        chunk.push(value);
+        let value = chunk.last_mut().unwrap() as *mut T;
+        (unsafe { &mut *value }, index)
+    }
+
+    fn chunk_capacity(&self, chunk: usize) -> usize {
+        self.first_chunk << chunk
+    }
+
+    fn chunk_start(&self, chunk: usize) -> usize {
+        self.first_chunk * ((1 << chunk) - 1)
+    }
+
+    fn locate(&self, handle: Handle) -> Option<(usize, usize)> {
+        if handle.arena != self.id || handle.generation != self.generation {
+            return None;
+        }
+        let index = handle.index as usize;
+        let chunk = (index / self.first_chunk + 1).ilog2() as usize;
+        Some((chunk, index - self.chunk_start(chunk)))
+    }
+}
+
+impl<T> Default for Arena<T> {
+    fn default() -> Self {
+        Arena::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::Cell;
+
+    use super::Arena;
+
+    struct Node<'a> {
+        value: i32,
+        next: Cell<Option<&'a Node<'a>>>,
+    }
+
+    #[test]
+    fn stable_references() {
+        let arena = Arena::with_capacity(1);
+        let first = arena.alloc(Node {
+            value: 0,
+            next: Cell::new(None),
+        });
+        let mut prev = &*first;
+        for value in 1..100 {
+            let node = arena.alloc(Node {
+                value,
+                next: Cell::new(None),
+            });
+            prev.next.set(Some(node));
+            prev = node;
+        }
+        let mut sum = 0;
+        let mut node = Some(&*first);
+        while let Some(n) = node {
+            sum += n.value;
+            node = n.next.get();
+        }
+        assert_eq!(sum, 4950);
+        assert_eq!(arena.len(), 100);
+    }
+
+    #[test]
+    fn handles() {
+        let mut arena = Arena::new();
+        let handles = (0..50).map(|i| arena.insert(i)).collect::<Vec<_>>();
+        assert!(handles
+            .iter()
+            .enumerate()
+            .all(|(i, h)| arena.get(*h) == Some(&i)));
+        *arena.get_mut(handles[20]).unwrap() += 100;
+        assert_eq!(arena.get(handles[20]), Some(&120));
+
+        let other = Arena::<usize>::new();
+        assert_eq!(other.get(handles[0]), None);
+        arena.reset();
+        assert!(arena.is_empty());
+        assert_eq!(arena.get(handles[0]), None);
+        let h = arena.insert(7);
+        assert_eq!(arena.get(h), Some(&7));
+        assert_eq!(arena.into_vec(), vec![7]);
+    }
+}
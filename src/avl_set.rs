@@ -0,0 +1,211 @@
+use std::{
+    fmt::{self, Debug},
+    ops::RangeBounds,
+};
+
+use crate::{
+    avl_tree::AVLTree,
+    from_sorted::FromSorted,
+    merge::{merge_join, EitherOrBoth},
+};
+
+/// An ordered set backed by an `AVLTree`.
+/// Unions, intersections and differences walk both sets in order and build the result
+/// bottom up, so they take O(n + m) time rather than inserting element by element.
+#[derive(Clone)]
+pub struct AVLSet<T> {
+    tree: AVLTree<T, ()>,
+    len: usize,
+}
+
+impl<T> AVLSet<T> {
+    pub fn new() -> Self {
+        AVLSet {
+            tree: AVLTree::new(),
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl<T: Ord> AVLSet<T> {
+    /// Adds the value, returning whether it was absent.
+    pub fn insert(&mut self, value: T) -> bool {
+        if self.contains(&value) {
+            return false;
+        }
+        self.tree.insert(value, ());
+        self.len += 1;
+        true
+    }
+
+    /// Removes the value, returning whether it was present.
+    pub fn remove(&mut self, value: &T) -> bool {
+        let removed = self.tree.remove(value).is_some();
+        if removed {
+            self.len -= 1;
+        }
+        removed
+    }
+
+    pub fn contains(&self, value: &T) -> bool {
+        self.tree.get(value).is_some()
+    }
+
+    pub fn first(&self) -> Option<&T> {
+        self.tree.first()
+    }
+
+    pub fn last(&self) -> Option<&T> {
+        self.tree.last()
+    }
+
+    /// Returns an iterator over the values in ascending order.
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.tree.iter().map(|(k, _)| k)
+    }
+
+    /// Returns an iterator over the values within `range`, in ascending order.
+    pub fn range<R: RangeBounds<T>>(&self, range: R) -> impl Iterator<Item = &T> {
+        self.tree.range(range).map(|(k, _)| k)
+    }
+
+    /// Returns the values in either set.
+    pub fn union_with(&self, other: &Self) -> Self
+    where
+        T: Clone,
+    {
+        let merged = merge_join(self.iter(), other.iter()).map(|e| e.reduce(|l, _| l));
+        AVLSet::from_sorted_iter(merged.cloned())
+    }
+
+    /// Returns the values in both sets.
+    pub fn intersect_with(&self, other: &Self) -> Self
+    where
+        T: Clone,
+    {
+        let merged = merge_join(self.iter(), other.iter()).filter_map(|e| e.both());
+        AVLSet::from_sorted_iter(merged.map(|(l, _)| l.clone()))
+    }
+
+    /// Returns the values in this set but not in `other`.
+    pub fn difference_with(&self, other: &Self) -> Self
+    where
+        T: Clone,
+    {
+        let merged = merge_join(self.iter(), other.iter()).filter_map(EitherOrBoth::left_only);
+        AVLSet::from_sorted_iter(merged.cloned())
+    }
+
+    /// Panics if the tree is out of order or unbalanced, or the length is wrong.
+    #[cfg(any(test, feature = "testing"))]
+    pub fn check_invariants(&self) {
+        self.tree.check_invariants();
+        assert_eq!(self.iter().count(), self.len, "length is out of date");
+    }
+}
+
+impl<T> Default for AVLSet<T> {
+    fn default() -> Self {
+        AVLSet::new()
+    }
+}
+
+impl<T: Debug + Ord> Debug for AVLSet<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+impl<T: Ord> PartialEq for AVLSet<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len && self.iter().eq(other.iter())
+    }
+}
+
+impl<T: Ord> Eq for AVLSet<T> {}
+
+impl<T: Ord> FromSorted<T> for AVLSet<T> {
+    fn from_sorted_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut len = 0;
+        let tree = AVLTree::from_sorted_iter(iter.into_iter().map(|value| {
+            len += 1;
+            (value, ())
+        }));
+        AVLSet { tree, len }
+    }
+}
+
+impl<T: Ord> FromIterator<T> for AVLSet<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut set = AVLSet::new();
+        set.extend(iter);
+        set
+    }
+}
+
+impl<T: Ord> Extend<T> for AVLSet<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for value in iter {
+            self.insert(value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeSet;
+
+    use quickcheck::quickcheck;
+
+    use super::AVLSet;
+
+    #[test]
+    fn insert_remove() {
+        let mut set = AVLSet::new();
+        assert!(set.insert(3));
+        assert!(set.insert(1));
+        assert!(!set.insert(3));
+        assert_eq!(set.len(), 2);
+        assert!(set.remove(&3));
+        assert!(!set.remove(&3));
+        assert_eq!(set.iter().collect::<Vec<_>>(), vec![&1]);
+        assert_eq!(format!("{:?}", set), "{1}");
+    }
+
+    #[test]
+    fn set_operations() {
+        let a = [1, 2, 3, 5].into_iter().collect::<AVLSet<_>>();
+        let b = [2, 4, 5].into_iter().collect::<AVLSet<_>>();
+        let values = |s: AVLSet<i32>| s.iter().copied().collect::<Vec<_>>();
+        assert_eq!(values(a.union_with(&b)), vec![1, 2, 3, 4, 5]);
+        assert_eq!(values(a.intersect_with(&b)), vec![2, 5]);
+        assert_eq!(values(a.difference_with(&b)), vec![1, 3]);
+        assert_eq!(a.union_with(&AVLSet::new()), a);
+    }
+
+    #[test]
+    fn prop_set_operations() {
+        fn p(left: BTreeSet<u8>, right: BTreeSet<u8>) -> bool {
+            let a = left.iter().copied().collect::<AVLSet<_>>();
+            let b = right.iter().copied().collect::<AVLSet<_>>();
+            let results = [
+                (a.union_with(&b), left.union(&right).collect::<Vec<_>>()),
+                (a.intersect_with(&b), left.intersection(&right).collect()),
+                (a.difference_with(&b), left.difference(&right).collect()),
+            ];
+            results.into_iter().all(|(set, expected)| {
+                set.check_invariants();
+                set.iter().collect::<Vec<_>>() == expected
+            })
+        }
+        quickcheck(p as fn(BTreeSet<u8>, BTreeSet<u8>) -> bool);
+    }
+}
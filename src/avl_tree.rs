@@ -1,64 +1,126 @@
-use std::{cmp::Ordering, fmt::Debug, ptr::NonNull};
+use std::{
+    cmp::Ordering,
+    fmt::{self, Debug},
+    hash::{Hash, Hasher},
+    marker::PhantomData,
+    mem::ManuallyDrop,
+    ops::{Bound, RangeBounds},
+    ptr::{self, NonNull},
+};
+
+#[cfg(feature = "parallel")]
+use crate::sort::par_sort_by;
+#[cfg(feature = "viz")]
+use crate::viz::{self, Visualize};
+use crate::{
+    allocator::{allocate_value, deallocate_value, take_value, Allocator, Global},
+    debug::DebugStructure,
+    footprint::MemoryFootprint,
+    from_sorted::{collect_sorted, FromSorted},
+    merge::{merge_join, EitherOrBoth},
+    seek::Seekable,
+    small_vec::SmallVec,
+    traversal::TraversalStack,
+};
 
 /// An AVL tree is a self-balancing binary search tree.
 /// Invariant: for any node N, the heights of both children of N may differ by no more than 1.
-#[derive(Debug)]
-pub enum AVLTree<K, V> {
-    Node(Node<K, V>),
-    Nil,
+/// Nodes are allocated with `A`, a copy of which is kept in every subtree.
+pub enum AVLTree<K, V, A: Allocator = Global> {
+    Node(Node<K, V, A>),
+    Nil(A),
 }
 
 impl<K, V> AVLTree<K, V> {
     pub fn new() -> Self {
-        Self::Nil
+        Self::Nil(Global)
+    }
+}
+
+impl<K, V> Default for AVLTree<K, V> {
+    fn default() -> Self {
+        AVLTree::new()
+    }
+}
+
+impl<K, V, A: Allocator> AVLTree<K, V, A> {
+    /// Creates an empty tree whose nodes are allocated with `alloc`.
+    pub fn new_in(alloc: A) -> Self {
+        Self::Nil(alloc)
+    }
+
+    pub fn allocator(&self) -> &A {
+        match self {
+            AVLTree::Node(node) => &node.alloc,
+            AVLTree::Nil(alloc) => alloc,
+        }
     }
 
     fn is_nil(&self) -> bool {
         match self {
             AVLTree::Node(_) => false,
-            AVLTree::Nil => true,
+            AVLTree::Nil(_) => true,
         }
     }
 
     fn take_value(&mut self) -> Option<V> {
         match self {
             AVLTree::Node(node) => Some(node.entry.value.take().unwrap()),
-            AVLTree::Nil => None,
+            AVLTree::Nil(_) => None,
         }
     }
 
-    fn node_mut(&mut self) -> Option<&mut Node<K, V>> {
+    fn node_mut(&mut self) -> Option<&mut Node<K, V, A>> {
         match self {
             AVLTree::Node(node) => Some(node),
-            AVLTree::Nil => None,
+            AVLTree::Nil(_) => None,
         }
     }
 
     pub fn balance_factor(&self) -> isize {
         match self {
             AVLTree::Node(node) => node.balance(),
-            AVLTree::Nil => 0,
+            AVLTree::Nil(_) => 0,
         }
     }
 
     pub fn height(&self) -> usize {
         match self {
             AVLTree::Node(node) => node.height_m,
-            AVLTree::Nil => 0,
+            AVLTree::Nil(_) => 0,
+        }
+    }
+
+    /// The number of entries, which every node keeps for its subtree alongside its height.
+    pub fn len(&self) -> usize {
+        match self {
+            AVLTree::Node(node) => node.len_m,
+            AVLTree::Nil(_) => 0,
         }
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.is_nil()
+    }
+
+    /// The depth of the deepest node, counting the root as one. Balancing keeps it below
+    /// 1.45 log2(n + 2), and as it's the stored height, it's found in constant time.
+    pub fn max_depth(&self) -> usize {
+        self.height()
+    }
+
     pub fn update_height(&mut self) {
         match self {
             AVLTree::Node(node) => node.update_height(),
-            AVLTree::Nil => {}
+            AVLTree::Nil(_) => {}
         }
     }
 }
 
-impl<K, V> AVLTree<K, V>
+impl<K, V, A> AVLTree<K, V, A>
 where
     K: Ord,
+    A: Allocator + Clone,
 {
     pub fn get(&self, k: &K) -> Option<&V> {
         match self {
@@ -69,7 +131,64 @@ where
                     Ordering::Greater => node.right.as_ref().get(k),
                 }
             },
-            AVLTree::Nil => None,
+            AVLTree::Nil(_) => None,
+        }
+    }
+
+    /// Looks up a batch of keys, returning their values in the order of `keys`.
+    /// The keys are sorted and looked up in a single descent, so each node is visited at
+    /// most once per batch rather than once per key.
+    pub fn get_many(&self, keys: &[&K]) -> Vec<Option<&V>> {
+        let mut out = vec![None; keys.len()];
+        self.get_sorted(&sort_keys(keys), &mut out);
+        out
+    }
+
+    /// Like `get_many`, but returns mutable references. Panics if any two keys are equal.
+    pub fn get_many_mut(&mut self, keys: &[&K]) -> Vec<Option<&mut V>> {
+        let sorted = sort_keys(keys);
+        assert!(
+            sorted.windows(2).all(|w| w[0].1 != w[1].1),
+            "keys must be distinct"
+        );
+        let mut out = (0..keys.len()).map(|_| None).collect::<Vec<_>>();
+        self.get_sorted_mut(&sorted, &mut out);
+        out
+    }
+
+    /// Splits keys in ascending order into those below, equal to and above the node's key.
+    fn split_sorted<'k>(
+        node: &Node<K, V, A>,
+        keys: &'k [(usize, &'k K)],
+    ) -> [&'k [(usize, &'k K)]; 3] {
+        let lo = keys.partition_point(|(_, k)| *k < &node.entry.key);
+        let hi = lo + keys[lo..].partition_point(|(_, k)| *k == &node.entry.key);
+        [&keys[..lo], &keys[lo..hi], &keys[hi..]]
+    }
+
+    fn get_sorted<'a>(&'a self, keys: &[(usize, &K)], out: &mut [Option<&'a V>]) {
+        if let (AVLTree::Node(node), false) = (self, keys.is_empty()) {
+            let [less, equal, greater] = Self::split_sorted(node, keys);
+            for &(position, _) in equal {
+                out[position] = node.entry.value.as_ref();
+            }
+            node.left_node().get_sorted(less, out);
+            node.right_node().get_sorted(greater, out);
+        }
+    }
+
+    fn get_sorted_mut<'a>(&'a mut self, keys: &[(usize, &K)], out: &mut [Option<&'a mut V>]) {
+        if let (AVLTree::Node(node), false) = (self, keys.is_empty()) {
+            let [less, equal, greater] = Self::split_sorted(node, keys);
+            let (mut left, mut right) = (node.left, node.right);
+            if let Some(&(position, _)) = equal.first() {
+                out[position] = node.entry.value.as_mut();
+            }
+            // The subtrees are disjoint from each other and from this node's entry
+            unsafe {
+                left.as_mut().get_sorted_mut(less, out);
+                right.as_mut().get_sorted_mut(greater, out);
+            }
         }
     }
 
@@ -85,16 +204,14 @@ where
                     self.update_height();
                     self.rebalance();
                 }
-                AVLTree::Nil => {
+                AVLTree::Nil(alloc) => {
                     let node = Node {
                         entry: Entry::new(k, v),
-                        left: NonNull::new_unchecked(Box::into_raw(Box::new(
-                            AVLTree::<K, V>::new(),
-                        ))),
-                        right: NonNull::new_unchecked(Box::into_raw(Box::new(
-                            AVLTree::<K, V>::new(),
-                        ))),
+                        left: allocate_value(alloc, AVLTree::Nil(alloc.clone())),
+                        right: allocate_value(alloc, AVLTree::Nil(alloc.clone())),
                         height_m: 1,
+                        len_m: 1,
+                        alloc: alloc.clone(),
                     };
                     *self = AVLTree::Node(node);
                 }
@@ -113,7 +230,8 @@ where
                         if !right.is_nil() {
                             Some(right.delete_promote_leftmost(self))
                         } else {
-                            let mut replace = std::mem::take(node.left.as_mut());
+                            let nil = AVLTree::Nil(node.alloc.clone());
+                            let mut replace = std::mem::replace(node.left.as_mut(), nil);
                             std::mem::swap(self, &mut replace);
                             Some(replace.take_value().unwrap())
                         }
@@ -124,21 +242,22 @@ where
                 self.rebalance();
                 out
             },
-            AVLTree::Nil => None,
+            AVLTree::Nil(_) => None,
         }
     }
 
-    fn delete_promote_leftmost(&mut self, target: &mut AVLTree<K, V>) -> V {
+    fn delete_promote_leftmost(&mut self, target: &mut AVLTree<K, V, A>) -> V {
         match self {
             AVLTree::Node(node) => unsafe {
                 let out = if node.left.as_ref().is_nil() {
-                    let replace = node.right.as_mut();
-                    std::mem::swap(replace, self);
-                    std::mem::swap(
-                        &mut replace.node_mut().unwrap().entry,
-                        &mut target.node_mut().unwrap().entry,
-                    );
-                    replace.node_mut().unwrap().entry.value.take().unwrap()
+                    // Replace this node with its right subtree, then drop it along with
+                    // the target's entry
+                    let nil = AVLTree::Nil(node.alloc.clone());
+                    let right = std::mem::replace(node.right.as_mut(), nil);
+                    let mut removed = std::mem::replace(self, right);
+                    let removed = removed.node_mut().unwrap();
+                    std::mem::swap(&mut removed.entry, &mut target.node_mut().unwrap().entry);
+                    removed.entry.value.take().unwrap()
                     // Technically we don't need to update the height here because it doesn't change
                 } else {
                     node.left.as_mut().delete_promote_leftmost(target)
@@ -147,7 +266,7 @@ where
                 self.rebalance();
                 out
             },
-            AVLTree::Nil => panic!("should never be called"),
+            AVLTree::Nil(_) => panic!("should never be called"),
         }
     }
 
@@ -155,6 +274,8 @@ where
         match self {
             AVLTree::Node(node) => match node.balance() {
                 -2 => unsafe {
+                    #[cfg(feature = "tracing")]
+                    tracing::trace!(height = node.height_m, "rotating right");
                     let left_ref = node.left.as_mut();
                     if left_ref.balance_factor() <= 0 {
                         self.unsafe_rotate_right()
@@ -164,6 +285,8 @@ where
                     }
                 },
                 2 => unsafe {
+                    #[cfg(feature = "tracing")]
+                    tracing::trace!(height = node.height_m, "rotating left");
                     let right_ref = node.right.as_mut();
                     if right_ref.balance_factor() >= 0 {
                         self.unsafe_rotate_left()
@@ -172,22 +295,22 @@ where
                         self.unsafe_rotate_left();
                     }
                 },
-                -1 | 0 | 1 => {}
+                -1..=1 => {}
                 _ => panic!("illegal balance factor"),
             },
-            AVLTree::Nil => {}
+            AVLTree::Nil(_) => {}
         }
     }
 
     unsafe fn unsafe_rotate_right(&mut self) {
-        let child = self.node_mut().unwrap().left.as_mut();
-        let grandchild = child.node_mut().unwrap().right.as_mut();
+        let child = self.node_mut().unwrap().left.as_ptr();
+        let grandchild = (*child).node_mut().unwrap().right.as_ptr();
         rotate(self, child, grandchild);
     }
 
     unsafe fn unsafe_rotate_left(&mut self) {
-        let child = self.node_mut().unwrap().right.as_mut();
-        let grandchild = child.node_mut().unwrap().left.as_mut();
+        let child = self.node_mut().unwrap().right.as_ptr();
+        let grandchild = (*child).node_mut().unwrap().left.as_ptr();
         rotate(self, child, grandchild);
     }
 
@@ -201,7 +324,7 @@ where
                     left.first()
                 }
             }
-            AVLTree::Nil => None,
+            AVLTree::Nil(_) => None,
         }
     }
 
@@ -215,62 +338,433 @@ where
                     right.last()
                 }
             }
-            AVLTree::Nil => None,
+            AVLTree::Nil(_) => None,
+        }
+    }
+
+    /// Returns an iterator over the entries of the tree in ascending key order.
+    pub fn iter(&self) -> Iter<'_, K, V, A> {
+        self.range(..)
+    }
+
+    /// Returns an iterator over the entries of the tree in ascending key order, with mutable
+    /// references to the values.
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V, A> {
+        let mut iter = IterMut {
+            stack: SmallVec::new(),
+            marker: PhantomData,
+        };
+        iter.push_left_edge(NonNull::from(self));
+        iter
+    }
+
+    /// Returns the keys in ascending order.
+    pub fn keys(&self) -> Keys<'_, K, V, A> {
+        Keys { iter: self.iter() }
+    }
+
+    /// Returns the values in ascending order of their keys.
+    pub fn values(&self) -> Values<'_, K, V, A> {
+        Values { iter: self.iter() }
+    }
+
+    /// Returns mutable references to the values in ascending order of their keys.
+    pub fn values_mut(&mut self) -> ValuesMut<'_, K, V, A> {
+        ValuesMut {
+            iter: self.iter_mut(),
+        }
+    }
+
+    /// Clones the entries into a vector in ascending key order, in a single traversal
+    /// into a vector allocated up front for the tree's length.
+    pub fn to_sorted_vec(&self) -> Vec<(K, V)>
+    where
+        K: Clone,
+        V: Clone,
+    {
+        let mut entries = Vec::with_capacity(self.len());
+        entries.extend(self.iter().map(|(k, v)| (k.clone(), v.clone())));
+        entries
+    }
+
+    /// Like `to_sorted_vec`.
+    pub fn to_vec(&self) -> Vec<(K, V)>
+    where
+        K: Clone,
+        V: Clone,
+    {
+        self.to_sorted_vec()
+    }
+
+    /// Returns an iterator over the entries whose keys fall within `range`, in ascending order.
+    pub fn range<R: RangeBounds<K>>(&self, range: R) -> Iter<'_, K, V, A> {
+        let mut iter = Iter {
+            stack: SmallVec::new(),
+            back: SmallVec::new(),
+            first: None,
+            last: None,
+        };
+        iter.push_left_edge(self, range.start_bound());
+        iter.push_right_edge(self, range.end_bound());
+        iter.first = iter.stack.last().copied();
+        iter.last = iter.back.last().copied();
+        iter
+    }
+
+    /// Returns the changes which turn this tree into `other`, in ascending key order.
+    /// Both trees are walked in step, so this takes linear time.
+    pub fn diff<'a, B>(
+        &'a self,
+        other: &'a AVLTree<K, V, B>,
+    ) -> impl Iterator<Item = Diff<&'a K, &'a V>> + 'a
+    where
+        V: PartialEq,
+        B: Allocator + Clone,
+    {
+        merge_join(self.iter(), other.iter()).filter_map(|e| match e {
+            EitherOrBoth::Left((k, v)) => Some(Diff::Removed(k, v)),
+            EitherOrBoth::Right((k, v)) => Some(Diff::Added(k, v)),
+            EitherOrBoth::Both((k, old), (_, new)) if old != new => {
+                Some(Diff::Changed(k, old, new))
+            }
+            EitherOrBoth::Both(..) => None,
+        })
+    }
+
+    /// Panics if the keys are out of order, a stored height is wrong, or the tree
+    /// is unbalanced.
+    #[cfg(any(test, feature = "testing"))]
+    pub fn check_invariants(&self) {
+        let mut iter = self.iter();
+        if let Some((mut prev, _)) = iter.next() {
+            for (k, _) in iter {
+                assert!(prev < k, "keys are out of order");
+                prev = k;
+            }
+        }
+        self.checked_height();
+    }
+
+    /// Checks every node's height after both of its subtrees', with an explicit stack so
+    /// that a tree broken into a long chain can't overflow the call stack.
+    #[cfg(any(test, feature = "testing"))]
+    fn checked_height(&self) -> usize {
+        let mut heights = TraversalStack::<usize>::new();
+        let mut stack = TraversalStack::new();
+        stack.push((self, false));
+        while let Some((tree, visited)) = stack.pop() {
+            match tree {
+                AVLTree::Node(node) if visited => {
+                    let right = heights.pop().unwrap();
+                    let left = heights.pop().unwrap();
+                    assert!(left.abs_diff(right) <= 1, "unbalanced node");
+                    assert_eq!(node.height_m, 1 + left.max(right), "wrong height");
+                    let len = 1 + node.left_node().len() + node.right_node().len();
+                    assert_eq!(node.len_m, len, "wrong length");
+                    heights.push(node.height_m);
+                }
+                AVLTree::Node(node) => {
+                    stack.push((tree, true));
+                    stack.push((node.right_node(), false));
+                    stack.push((node.left_node(), false));
+                }
+                AVLTree::Nil(_) => heights.push(0),
+            }
         }
+        heights.pop().unwrap()
     }
+}
 
-    // pub fn iter() -> Iter<_, K, V> {
+/// Builds a tree whose subtrees differ in size by at most one, so no rebalancing is needed.
+impl<K: Ord, V> FromSorted<(K, V)> for AVLTree<K, V> {
+    fn from_sorted_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let entries = collect_sorted(iter, true, |(k, _)| k);
+        AVLTree::build_sorted(&Global, entries.len(), &mut entries.into_iter())
+    }
+}
 
-    // }
+#[cfg(feature = "parallel")]
+impl<K: Ord + Send, V: Send> AVLTree<K, V> {
+    /// Builds a tree from entries in any order, sorting them on every available core and
+    /// then building the tree bottom-up in linear time. As with inserting the entries one at
+    /// a time, the first entry with a given key is kept and later ones are ignored.
+    pub fn from_iter_par<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut entries = iter.into_iter().collect::<Vec<_>>();
+        // The sort is stable, so the first entry with each key leads its run
+        par_sort_by(&mut entries, |a, b| a.0.cmp(&b.0));
+        entries.dedup_by(|later, first| later.0 == first.0);
+        AVLTree::build_sorted(&Global, entries.len(), &mut entries.into_iter())
+    }
+}
+
+impl<K, V, A: Allocator + Clone> AVLTree<K, V, A> {
+    /// Builds a tree from the next `n` entries, placing the middle one at the root.
+    fn build_sorted(alloc: &A, n: usize, entries: &mut impl Iterator<Item = (K, V)>) -> Self {
+        if n == 0 {
+            return AVLTree::Nil(alloc.clone());
+        }
+        let left = AVLTree::build_sorted(alloc, n / 2, entries);
+        let (k, v) = entries.next().unwrap();
+        let right = AVLTree::build_sorted(alloc, n - n / 2 - 1, entries);
+        AVLTree::Node(Node {
+            entry: Entry::new(k, v),
+            height_m: 1 + std::cmp::max(left.height(), right.height()),
+            len_m: n,
+            left: allocate_value(alloc, left),
+            right: allocate_value(alloc, right),
+            alloc: alloc.clone(),
+        })
+    }
 }
 
 /// Performs a left or right rotation.
 /// Given a parent, child, and grandchild, perform a rotation
 /// such that the parent and child swap positions and exchange the grandchild.
-fn rotate<K, V>(
-    parent: &mut AVLTree<K, V>,
-    child: &mut AVLTree<K, V>,
-    grandchild: &mut AVLTree<K, V>,
+/// The child and grandchild are reachable through the parent, so they are passed as raw
+/// pointers; three live `&mut` to nested trees would alias.
+unsafe fn rotate<K, V, A: Allocator + Clone>(
+    parent: *mut AVLTree<K, V, A>,
+    child: *mut AVLTree<K, V, A>,
+    grandchild: *mut AVLTree<K, V, A>,
 ) {
-    let mut temp = std::mem::take(grandchild);
-    std::mem::swap(&mut temp, child); // temp has child now, grandchild has child now
-    std::mem::swap(&mut temp, parent); // parent is child now, temp has old parent
-    std::mem::swap(&mut temp, grandchild); // move old parent into new parent child
-    grandchild.node_mut().unwrap().update_height();
-    parent.node_mut().unwrap().update_height();
+    let nil = AVLTree::Nil((*parent).allocator().clone());
+    let mut temp = std::ptr::replace(grandchild, nil);
+    std::ptr::swap(&mut temp, child); // temp has child now, grandchild has child now
+    std::ptr::swap(&mut temp, parent); // parent is child now, temp has old parent
+    std::ptr::swap(&mut temp, grandchild); // move old parent into new parent child
+    (*grandchild).node_mut().unwrap().update_height();
+    (*parent).node_mut().unwrap().update_height();
 }
 
-impl<K, V> Default for AVLTree<K, V> {
-    fn default() -> Self {
-        AVLTree::Nil
+impl<K, V, A> Clone for AVLTree<K, V, A>
+where
+    K: Clone,
+    V: Clone,
+    A: Allocator + Clone,
+{
+    fn clone(&self) -> Self {
+        match self {
+            AVLTree::Node(node) => AVLTree::Node(Node {
+                entry: Entry {
+                    key: node.entry.key.clone(),
+                    value: node.entry.value.clone(),
+                },
+                left: allocate_value(&node.alloc, node.left_node().clone()),
+                right: allocate_value(&node.alloc, node.right_node().clone()),
+                height_m: node.height_m,
+                len_m: node.len_m,
+                alloc: node.alloc.clone(),
+            }),
+            AVLTree::Nil(alloc) => AVLTree::Nil(alloc.clone()),
+        }
     }
 }
 
-impl<K, V> Drop for AVLTree<K, V> {
+// Every node is uniquely owned by its parent, so the tree is
+// thread-safe whenever its keys and values are.
+unsafe impl<K: Send, V: Send, A: Allocator + Send> Send for AVLTree<K, V, A> {}
+unsafe impl<K: Sync, V: Sync, A: Allocator + Sync> Sync for AVLTree<K, V, A> {}
+
+impl<K, V, A: Allocator> Drop for AVLTree<K, V, A> {
     fn drop(&mut self) {
         match self {
             AVLTree::Node(node) => unsafe {
-                Box::from_raw(node.left.as_ptr());
-                Box::from_raw(node.right.as_ptr());
+                deallocate_value(&node.alloc, node.left);
+                deallocate_value(&node.alloc, node.right);
             },
-            AVLTree::Nil => {}
+            AVLTree::Nil(_) => {}
         }
     }
 }
 
-#[derive(Debug)]
-pub struct Node<K, V> {
+impl<K, V, A: Allocator> AVLTree<K, V, A> {
+    /// Returns the keys in ascending order, consuming the tree.
+    pub fn into_keys(self) -> impl Iterator<Item = K> {
+        self.into_iter().map(|(k, _)| k)
+    }
+
+    /// Returns the values in ascending order of their keys, consuming the tree.
+    pub fn into_values(self) -> impl Iterator<Item = V> {
+        self.into_iter().map(|(_, v)| v)
+    }
+
+    /// Moves the entries into a vector in ascending key order, preallocated like
+    /// `to_sorted_vec`.
+    pub fn into_sorted_vec(self) -> Vec<(K, V)> {
+        let mut entries = Vec::with_capacity(self.len());
+        entries.extend(self);
+        entries
+    }
+
+    /// Takes the root apart into its entry and subtrees, or returns `None` if the tree is
+    /// empty.
+    fn into_parts(self) -> Option<(K, V, Self, Self)> {
+        let mut tree = ManuallyDrop::new(self);
+        match &mut *tree {
+            // The tree's destructor won't run, so every field is either moved out or freed
+            AVLTree::Node(node) => unsafe {
+                let value = node.entry.value.take().unwrap();
+                let key = ptr::read(&node.entry.key);
+                let alloc = ptr::read(&node.alloc);
+                let left = take_value(&alloc, node.left);
+                let right = take_value(&alloc, node.right);
+                Some((key, value, left, right))
+            },
+            AVLTree::Nil(alloc) => {
+                unsafe { ptr::drop_in_place(alloc) };
+                None
+            }
+        }
+    }
+
+    /// Adds the entries to the map in order, without needing `K: Ord` like `iter` does.
+    fn debug_entries(&self, map: &mut fmt::DebugMap<'_, '_>)
+    where
+        K: Debug,
+        V: Debug,
+    {
+        let mut stack = TraversalStack::new();
+        let mut tree = self;
+        loop {
+            while let AVLTree::Node(node) = tree {
+                stack.push(node);
+                tree = node.left_node();
+            }
+            let Some(node) = stack.pop() else {
+                return;
+            };
+            map.entry(&node.entry.key, node.entry.value.as_ref().unwrap());
+            tree = node.right_node();
+        }
+    }
+}
+
+/// Prints the entries in ascending key order.
+impl<K: Debug, V: Debug, A: Allocator> Debug for AVLTree<K, V, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut map = f.debug_map();
+        self.debug_entries(&mut map);
+        map.finish()
+    }
+}
+
+/// Prints every node with its height, including the empty subtrees.
+impl<K: Debug, V: Debug, A: Allocator> Debug for DebugStructure<'_, AVLTree<K, V, A>> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            AVLTree::Node(node) => node.fmt(f),
+            AVLTree::Nil(_) => f.write_str("Nil"),
+        }
+    }
+}
+
+/// Trees are compared by their entries in key order, regardless of their shape.
+impl<K, V, A> PartialEq for AVLTree<K, V, A>
+where
+    K: Ord,
+    V: PartialEq,
+    A: Allocator + Clone,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.iter().eq(other.iter())
+    }
+}
+
+impl<K: Ord, V: Eq, A: Allocator + Clone> Eq for AVLTree<K, V, A> {}
+
+impl<K, V, A> PartialOrd for AVLTree<K, V, A>
+where
+    K: Ord,
+    V: PartialOrd,
+    A: Allocator + Clone,
+{
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.iter().partial_cmp(other.iter())
+    }
+}
+
+impl<K: Ord, V: Ord, A: Allocator + Clone> Ord for AVLTree<K, V, A> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.iter().cmp(other.iter())
+    }
+}
+
+impl<K, V, A> Hash for AVLTree<K, V, A>
+where
+    K: Ord + Hash,
+    V: Hash,
+    A: Allocator + Clone,
+{
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.len().hash(state);
+        self.iter().for_each(|entry| entry.hash(state));
+    }
+}
+
+impl<K, V, A: Allocator> MemoryFootprint for AVLTree<K, V, A> {
+    /// Every node allocates both of its subtrees, including the empty ones.
+    fn heap_bytes(&self) -> usize {
+        match self {
+            AVLTree::Node(node) => unsafe {
+                2 * std::mem::size_of::<Self>()
+                    + node.left.as_ref().heap_bytes()
+                    + node.right.as_ref().heap_bytes()
+            },
+            AVLTree::Nil(_) => 0,
+        }
+    }
+
+    fn node_count(&self) -> usize {
+        self.len()
+    }
+}
+
+#[cfg(feature = "viz")]
+impl<K, V, A> Visualize for AVLTree<K, V, A>
+where
+    K: Ord + Debug,
+    A: Allocator,
+{
+    type Key = K;
+
+    /// Labels each node with its key and height.
+    fn view(&self, search: Option<&K>) -> Option<viz::Node> {
+        let node = match self {
+            AVLTree::Node(node) => node,
+            AVLTree::Nil(_) => return None,
+        };
+        let (left, right) = viz::descend(search, &node.entry.key);
+        Some(viz::Node {
+            label: format!("{:?} (h={})", node.entry.key, node.height_m),
+            highlighted: search.is_some(),
+            children: vec![node.left_node().view(left), node.right_node().view(right)],
+        })
+    }
+}
+
+/// Pairs each key with its position and sorts them by key.
+fn sort_keys<'k, K: Ord>(keys: &[&'k K]) -> Vec<(usize, &'k K)> {
+    let mut sorted = keys.iter().copied().enumerate().collect::<Vec<_>>();
+    sorted.sort_unstable_by(|a, b| a.1.cmp(b.1));
+    sorted
+}
+
+pub struct Node<K, V, A: Allocator = Global> {
     entry: Entry<K, V>,
-    left: NonNull<AVLTree<K, V>>,
-    right: NonNull<AVLTree<K, V>>,
+    left: NonNull<AVLTree<K, V, A>>,
+    right: NonNull<AVLTree<K, V, A>>,
     height_m: usize,
+    len_m: usize,
+    alloc: A,
 }
 
-impl<K, V> Node<K, V> {
+impl<K, V, A: Allocator> Node<K, V, A> {
+    /// Recomputes the height and length from the children's.
     fn update_height(&mut self) {
         unsafe {
-            self.height_m =
-                1 + std::cmp::max(self.left.as_ref().height(), self.right.as_ref().height())
+            let (left, right) = (self.left.as_ref(), self.right.as_ref());
+            self.height_m = 1 + std::cmp::max(left.height(), right.height());
+            self.len_m = 1 + left.len() + right.len();
         }
     }
 
@@ -278,15 +772,27 @@ impl<K, V> Node<K, V> {
         unsafe { (self.right.as_ref().height() as isize) - (self.left.as_ref().height() as isize) }
     }
 
-    fn left_node(&self) -> &AVLTree<K, V> {
+    fn left_node(&self) -> &AVLTree<K, V, A> {
         unsafe { self.left.as_ref() }
     }
 
-    fn right_node(&self) -> &AVLTree<K, V> {
+    fn right_node(&self) -> &AVLTree<K, V, A> {
         unsafe { self.right.as_ref() }
     }
 }
 
+impl<K: Debug, V: Debug, A: Allocator> Debug for Node<K, V, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Node")
+            .field("key", &self.entry.key)
+            .field("value", self.entry.value.as_ref().unwrap())
+            .field("height", &self.height_m)
+            .field("left", &DebugStructure(self.left_node()))
+            .field("right", &DebugStructure(self.right_node()))
+            .finish()
+    }
+}
+
 #[derive(Debug)]
 pub struct Entry<K, V> {
     key: K,
@@ -302,12 +808,302 @@ impl<K, V> Entry<K, V> {
     }
 }
 
+/// A difference between two trees, as returned by `AVLTree::diff`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Diff<K, V> {
+    /// The key is only present in the other tree.
+    Added(K, V),
+    /// The key is only present in this tree.
+    Removed(K, V),
+    /// The key is present in both trees with the old and new values.
+    Changed(K, V, V),
+}
+
+// An AVL tree of this height holds billions of entries, so the iterator stack never spills
+const STACK_DEPTH: usize = 48;
+
+/// An in-order iterator over the entries of an `AVLTree`, which can also be walked from the
+/// largest key down. Ancestors which have yet to be visited are kept on an inline stack for
+/// each end, so iterating doesn't allocate.
+#[derive(Debug)]
+pub struct Iter<'a, K, V, A: Allocator = Global> {
+    stack: SmallVec<&'a Node<K, V, A>, STACK_DEPTH>,
+    back: SmallVec<&'a Node<K, V, A>, STACK_DEPTH>,
+    // The next nodes to yield from the front and the back, which are `None` once the ends
+    // have met
+    first: Option<&'a Node<K, V, A>>,
+    last: Option<&'a Node<K, V, A>>,
+}
+
+impl<'a, K, V, A> Iter<'a, K, V, A>
+where
+    K: Ord,
+    A: Allocator,
+{
+    /// Pushes every node along the path to the smallest key within the lower bound.
+    fn push_left_edge(&mut self, mut tree: &'a AVLTree<K, V, A>, bound: Bound<&K>) {
+        while let AVLTree::Node(node) = tree {
+            let within = match bound {
+                Bound::Included(k) => node.entry.key >= *k,
+                Bound::Excluded(k) => node.entry.key > *k,
+                Bound::Unbounded => true,
+            };
+            if within {
+                self.stack.push(node);
+                tree = node.left_node();
+            } else {
+                tree = node.right_node();
+            }
+        }
+    }
+
+    /// Pushes every node along the path to the largest key within the upper bound.
+    fn push_right_edge(&mut self, mut tree: &'a AVLTree<K, V, A>, bound: Bound<&K>) {
+        while let AVLTree::Node(node) = tree {
+            let within = match bound {
+                Bound::Included(k) => node.entry.key <= *k,
+                Bound::Excluded(k) => node.entry.key < *k,
+                Bound::Unbounded => true,
+            };
+            if within {
+                self.back.push(node);
+                tree = node.right_node();
+            } else {
+                tree = node.left_node();
+            }
+        }
+    }
+
+    fn finish(&mut self) {
+        self.stack.clear();
+        self.back.clear();
+        self.first = None;
+        self.last = None;
+    }
+}
+
+impl<'a, K, V, A> Iterator for Iter<'a, K, V, A>
+where
+    K: Ord,
+    A: Allocator,
+{
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.stack.pop()?;
+        let last = self.last?;
+        if node.entry.key > last.entry.key {
+            self.finish();
+            return None;
+        }
+        if std::ptr::eq(node, last) {
+            self.finish();
+        } else {
+            self.push_left_edge(node.right_node(), Bound::Unbounded);
+            self.first = self.stack.last().copied();
+        }
+        Some((&node.entry.key, node.entry.value.as_ref().unwrap()))
+    }
+}
+
+impl<K, V, A> DoubleEndedIterator for Iter<'_, K, V, A>
+where
+    K: Ord,
+    A: Allocator,
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let node = self.back.pop()?;
+        let first = self.first?;
+        if node.entry.key < first.entry.key {
+            self.finish();
+            return None;
+        }
+        if std::ptr::eq(node, first) {
+            self.finish();
+        } else {
+            self.push_right_edge(node.left_node(), Bound::Unbounded);
+            self.last = self.back.last().copied();
+        }
+        Some((&node.entry.key, node.entry.value.as_ref().unwrap()))
+    }
+}
+
+/// An in-order iterator over the entries of an `AVLTree` with mutable references to the
+/// values, as returned by `AVLTree::iter_mut`.
+/// Nodes are held as raw pointers, since a node's value is lent out while its right subtree
+/// is still to be walked. Each node is yielded once and only its key and value are
+/// borrowed, so the references handed out never alias.
+#[derive(Debug)]
+pub struct IterMut<'a, K, V, A: Allocator = Global> {
+    stack: SmallVec<NonNull<Node<K, V, A>>, STACK_DEPTH>,
+    marker: PhantomData<&'a mut AVLTree<K, V, A>>,
+}
+
+unsafe impl<K: Sync, V: Send, A: Allocator + Sync> Send for IterMut<'_, K, V, A> {}
+unsafe impl<K: Sync, V: Sync, A: Allocator + Sync> Sync for IterMut<'_, K, V, A> {}
+
+impl<K, V, A: Allocator> IterMut<'_, K, V, A> {
+    fn push_left_edge(&mut self, tree: NonNull<AVLTree<K, V, A>>) {
+        let mut tree = tree.as_ptr();
+        // The subtrees reached here haven't been yielded from yet
+        while let AVLTree::Node(node) = unsafe { &mut *tree } {
+            self.stack.push(NonNull::from(&mut *node));
+            tree = node.left.as_ptr();
+        }
+    }
+}
+
+impl<'a, K, V, A: Allocator> Iterator for IterMut<'a, K, V, A> {
+    type Item = (&'a K, &'a mut V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.stack.pop()?.as_ptr();
+        unsafe {
+            self.push_left_edge((*node).right);
+            let entry = &mut (*node).entry;
+            Some((&entry.key, entry.value.as_mut().unwrap()))
+        }
+    }
+}
+
+/// An iterator over the keys of an `AVLTree` in ascending order.
+#[derive(Debug)]
+pub struct Keys<'a, K, V, A: Allocator = Global> {
+    iter: Iter<'a, K, V, A>,
+}
+
+impl<'a, K: Ord, V, A: Allocator> Iterator for Keys<'a, K, V, A> {
+    type Item = &'a K;
+
+    fn next(&mut self) -> Option<&'a K> {
+        self.iter.next().map(|(k, _)| k)
+    }
+}
+
+impl<'a, K: Ord, V, A: Allocator> DoubleEndedIterator for Keys<'a, K, V, A> {
+    fn next_back(&mut self) -> Option<&'a K> {
+        self.iter.next_back().map(|(k, _)| k)
+    }
+}
+
+/// An iterator over the values of an `AVLTree` in ascending order of their keys.
+#[derive(Debug)]
+pub struct Values<'a, K, V, A: Allocator = Global> {
+    iter: Iter<'a, K, V, A>,
+}
+
+impl<'a, K: Ord, V, A: Allocator> Iterator for Values<'a, K, V, A> {
+    type Item = &'a V;
+
+    fn next(&mut self) -> Option<&'a V> {
+        self.iter.next().map(|(_, v)| v)
+    }
+}
+
+impl<'a, K: Ord, V, A: Allocator> DoubleEndedIterator for Values<'a, K, V, A> {
+    fn next_back(&mut self) -> Option<&'a V> {
+        self.iter.next_back().map(|(_, v)| v)
+    }
+}
+
+/// An iterator over mutable references to the values of an `AVLTree` in ascending order of
+/// their keys.
+#[derive(Debug)]
+pub struct ValuesMut<'a, K, V, A: Allocator = Global> {
+    iter: IterMut<'a, K, V, A>,
+}
+
+impl<'a, K, V, A: Allocator> Iterator for ValuesMut<'a, K, V, A> {
+    type Item = &'a mut V;
+
+    fn next(&mut self) -> Option<&'a mut V> {
+        self.iter.next().map(|(_, v)| v)
+    }
+}
+
+impl<K, V, A: Allocator> IntoIterator for AVLTree<K, V, A> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V, A>;
+
+    fn into_iter(self) -> IntoIter<K, V, A> {
+        let mut iter = IntoIter { stack: vec![] };
+        iter.push_left_edge(self);
+        iter
+    }
+}
+
+/// An iterator which moves the entries out of an `AVLTree` in ascending key order, freeing
+/// each node as it goes.
+#[derive(Debug)]
+pub struct IntoIter<K, V, A: Allocator = Global> {
+    // Entries which have yet to be yielded, each with the subtree that follows it
+    stack: Vec<(K, V, AVLTree<K, V, A>)>,
+}
+
+impl<K, V, A: Allocator> IntoIter<K, V, A> {
+    fn push_left_edge(&mut self, mut tree: AVLTree<K, V, A>) {
+        while let Some((k, v, left, right)) = tree.into_parts() {
+            self.stack.push((k, v, right));
+            tree = left;
+        }
+    }
+}
+
+impl<K, V, A: Allocator> Iterator for IntoIter<K, V, A> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<(K, V)> {
+        let (k, v, right) = self.stack.pop()?;
+        self.push_left_edge(right);
+        Some((k, v))
+    }
+}
+
+impl<K, V, A> Seekable for Iter<'_, K, V, A>
+where
+    K: Ord,
+    A: Allocator,
+{
+    type Key = K;
+
+    fn key_of<'i>(item: &'i (&K, &V)) -> &'i K {
+        item.0
+    }
+
+    fn seek(&mut self, key: &K) {
+        // Every node below the top of the stack is greater than the top's right subtree
+        while let Some(&node) = self.stack.last() {
+            if node.entry.key >= *key {
+                self.first = Some(node);
+                return;
+            }
+            self.stack.pop();
+            if self.last.is_some_and(|last| std::ptr::eq(node, last)) {
+                break;
+            }
+            self.push_left_edge(node.right_node(), Bound::Included(key));
+        }
+        self.finish();
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use quickcheck::quickcheck;
-    use std::collections::HashSet;
+    use std::{
+        collections::{hash_map::DefaultHasher, BTreeMap, HashSet},
+        hash::{Hash, Hasher},
+        ops::Bound,
+    };
 
-    use crate::avl_tree::AVLTree;
+    use crate::{
+        allocator::Counting,
+        avl_tree::{AVLTree, Diff},
+        debug::DebugStructure,
+        footprint::MemoryFootprint,
+        from_sorted::FromSorted,
+        seek::seeks_match,
+    };
 
     impl<K, V> AVLTree<K, V> {
         fn height_internal(&self) -> usize {
@@ -319,7 +1115,7 @@ mod tests {
                             node.right.as_ref().height_internal(),
                         )
                     }
-                    AVLTree::Nil => 0,
+                    AVLTree::Nil(_) => 0,
                 }
             }
         }
@@ -337,7 +1133,7 @@ mod tests {
                                 .abs()
                                 <= 1
                     }
-                    AVLTree::Nil => true,
+                    AVLTree::Nil(_) => true,
                 }
             }
         }
@@ -466,6 +1262,293 @@ mod tests {
     }
 
     #[test]
+    fn clone_independent() {
+        let mut tree = AVLTree::new();
+        tree.insert_same(5);
+        tree.insert_same(4);
+        tree.insert_same(6);
+        let mut copy = tree.clone();
+        copy.remove(&4);
+        copy.insert_same(7);
+        assert_eq!(tree.get(&4), Some(&4));
+        assert_eq!(tree.get(&7), None);
+        assert_eq!(copy.get(&4), None);
+        assert_eq!(copy.get(&7), Some(&7));
+        assert!(copy.balanced_internal());
+    }
+
+    #[test]
+    fn iter_ascending() {
+        let mut tree = AVLTree::new();
+        for i in [5, 2, 8, 1, 9, 3] {
+            tree.insert(i, i * 10);
+        }
+        assert_eq!(
+            tree.iter().collect::<Vec<_>>(),
+            vec![
+                (&1, &10),
+                (&2, &20),
+                (&3, &30),
+                (&5, &50),
+                (&8, &80),
+                (&9, &90)
+            ]
+        );
+        assert_eq!(AVLTree::<i32, i32>::new().iter().next(), None);
+    }
+
+    #[test]
+    fn iter_mut() {
+        let mut tree = AVLTree::new();
+        for i in [5, 2, 8, 1, 9, 3] {
+            tree.insert(i, i * 10);
+        }
+        // Hold every reference at once, as the borrow checker allows
+        let entries = tree.iter_mut().collect::<Vec<_>>();
+        let mut previous = None;
+        for (k, v) in entries {
+            assert!(previous < Some(*k));
+            previous = Some(*k);
+            *v += 1;
+        }
+        assert!(tree.iter().all(|(k, v)| *v == k * 10 + 1));
+        tree.check_invariants();
+        assert_eq!(AVLTree::<i32, i32>::new().iter_mut().next(), None);
+    }
+
+    #[test]
+    fn keys_and_values() {
+        let mut tree = AVLTree::new();
+        for (k, v) in [(3, 'c'), (1, 'a'), (2, 'b')] {
+            tree.insert(k, v);
+        }
+        assert_eq!(tree.keys().collect::<Vec<_>>(), vec![&1, &2, &3]);
+        assert_eq!(tree.values().collect::<Vec<_>>(), vec![&'a', &'b', &'c']);
+        for v in tree.values_mut() {
+            *v = v.to_ascii_uppercase();
+        }
+        assert_eq!(tree.values().collect::<String>(), "ABC");
+        assert_eq!(AVLTree::<i32, i32>::new().keys().next(), None);
+    }
+
+    #[test]
+    fn sorted_vecs() {
+        let mut tree = AVLTree::new();
+        for i in [4, 1, 3, 0, 2] {
+            tree.insert(i, i.to_string());
+        }
+        let expected = (0..5).map(|i| (i, i.to_string())).collect::<Vec<_>>();
+        assert_eq!(tree.len(), 5);
+        let cloned = tree.to_sorted_vec();
+        assert_eq!(cloned, expected);
+        assert_eq!(cloned.capacity(), 5);
+        assert_eq!(tree.to_vec(), expected);
+        let moved = tree.into_sorted_vec();
+        assert_eq!(moved, expected);
+        assert_eq!(moved.capacity(), 5);
+        assert!(AVLTree::<i32, i32>::new().into_sorted_vec().is_empty());
+    }
+
+    #[test]
+    fn from_sorted() {
+        for n in [0, 1, 2, 10, 1000] {
+            let tree = AVLTree::from_sorted_iter((0..n).map(|i| (i, i * 2)));
+            tree.check_invariants();
+            assert_eq!(tree.max_depth(), tree.height_internal());
+            assert!(tree
+                .iter()
+                .map(|(k, v)| (*k, *v))
+                .eq((0..n).map(|i| (i, i * 2))));
+        }
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn from_iter_par() {
+        let n = 100_000;
+        // Every key appears twice, and the first value is kept as it would be by `insert`
+        let entries = (0..2 * n).map(|i| ((i * 7919) % n, i));
+        let tree = AVLTree::from_iter_par(entries.clone());
+        tree.check_invariants();
+        let mut inserted = AVLTree::new();
+        for (k, v) in entries {
+            inserted.insert(k, v);
+        }
+        assert!(tree.iter().eq(inserted.iter()));
+        assert_eq!(tree.iter().count(), n);
+    }
+
+    #[test]
+    #[should_panic]
+    fn from_sorted_duplicate() {
+        AVLTree::from_sorted_iter([(1, ()), (1, ())]);
+    }
+
+    #[test]
+    fn iter_stays_inline() {
+        let mut tree = AVLTree::new();
+        for i in 0..10_000 {
+            tree.insert(i, ());
+        }
+        let mut iter = tree.iter();
+        assert!(iter.by_ref().map(|(k, _)| *k).eq(0..10_000));
+        assert!(!iter.stack.spilled());
+    }
+
+    #[test]
+    fn range_bounds() {
+        let mut tree = AVLTree::new();
+        for i in 0..10 {
+            tree.insert_same(i);
+        }
+        let keys = |r: Vec<(&i32, &i32)>| r.into_iter().map(|e| *e.0).collect::<Vec<_>>();
+        assert_eq!(keys(tree.range(3..6).collect()), vec![3, 4, 5]);
+        assert_eq!(keys(tree.range(3..=6).collect()), vec![3, 4, 5, 6]);
+        assert_eq!(keys(tree.range(..2).collect()), vec![0, 1]);
+        assert_eq!(keys(tree.range(8..).collect()), vec![8, 9]);
+        assert_eq!(keys(tree.range(20..).collect()), vec![]);
+        assert_eq!(
+            keys(
+                tree.range((Bound::Excluded(5), Bound::Excluded(6)))
+                    .collect()
+            ),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn double_ended() {
+        let mut tree = AVLTree::new();
+        for i in 0..10 {
+            tree.insert_same(i);
+        }
+        let keys = tree.iter().rev().map(|(k, _)| *k).collect::<Vec<_>>();
+        assert_eq!(keys, (0..10).rev().collect::<Vec<_>>());
+        assert_eq!(
+            tree.keys().rev().take(3).collect::<Vec<_>>(),
+            vec![&9, &8, &7]
+        );
+        // The ends stop where they meet
+        let mut range = tree.range(2..6);
+        assert_eq!(range.next_back(), Some((&5, &5)));
+        assert_eq!(range.next(), Some((&2, &2)));
+        assert_eq!(range.next_back(), Some((&4, &4)));
+        assert_eq!(range.next(), Some((&3, &3)));
+        assert_eq!(range.next(), None);
+        assert_eq!(range.next_back(), None);
+        assert_eq!(
+            tree.range((Bound::Excluded(5), Bound::Excluded(6)))
+                .next_back(),
+            None
+        );
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn prop_double_ended_matches_btree_map() {
+        fn p(input: HashSet<i8>, from_back: Vec<bool>, bounds: (i8, i8)) -> bool {
+            let mut tree = AVLTree::new();
+            for &i in input.iter() {
+                tree.insert(i, ());
+            }
+            let model = input.iter().map(|&i| (i, ())).collect::<BTreeMap<_, _>>();
+            let (lo, hi) = (bounds.0.min(bounds.1), bounds.0.max(bounds.1));
+            let mut iter = tree.range(lo..hi);
+            let mut expected = model.range(lo..hi);
+            from_back.into_iter().chain([false; 2]).all(|back| {
+                if back {
+                    iter.next_back() == expected.next_back()
+                } else {
+                    iter.next() == expected.next()
+                }
+            })
+        }
+        quickcheck(p as fn(HashSet<i8>, Vec<bool>, (i8, i8)) -> bool)
+    }
+
+    #[test]
+    fn get_many() {
+        let mut tree = AVLTree::new();
+        for i in 0..50 {
+            tree.insert(i * 2, i);
+        }
+        assert_eq!(
+            tree.get_many(&[&10, &3, &10, &0]),
+            vec![Some(&5), None, Some(&5), Some(&0)]
+        );
+        for v in tree.get_many_mut(&[&98, &1, &4]).into_iter().flatten() {
+            *v *= 100;
+        }
+        assert_eq!(tree.get(&98), Some(&4900));
+        assert_eq!(tree.get(&4), Some(&200));
+        assert_eq!(tree.get(&6), Some(&3));
+    }
+
+    #[test]
+    #[should_panic(expected = "keys must be distinct")]
+    fn get_many_mut_overlapping() {
+        let mut tree = AVLTree::new();
+        tree.insert(1, 1);
+        tree.get_many_mut(&[&1, &2, &1]);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn prop_get_many() {
+        fn p(input: Vec<i8>, keys: Vec<i8>) -> bool {
+            let mut tree = AVLTree::new();
+            let model = input
+                .iter()
+                .map(|&i| (i, i as i32))
+                .collect::<BTreeMap<_, _>>();
+            for (&k, &v) in model.iter() {
+                tree.insert(k, v);
+            }
+            let keys = keys.iter().collect::<Vec<_>>();
+            tree.get_many(&keys) == keys.iter().map(|k| model.get(k)).collect::<Vec<_>>()
+        }
+        quickcheck(p as fn(Vec<i8>, Vec<i8>) -> bool)
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn prop_seek() {
+        fn p(input: HashSet<i8>, ops: Vec<Option<i8>>, bounds: (i8, i8)) -> bool {
+            let mut tree = AVLTree::new();
+            for &i in input.iter() {
+                tree.insert(i, ());
+            }
+            let mut keys = input.into_iter().collect::<Vec<_>>();
+            keys.sort();
+            let (lo, hi) = (bounds.0.min(bounds.1), bounds.0.max(bounds.1));
+            let in_range = keys
+                .iter()
+                .copied()
+                .filter(|k| (lo..=hi).contains(k))
+                .collect::<Vec<_>>();
+            seeks_match(tree.iter(), &keys, &ops)
+                && seeks_match(tree.range(lo..=hi), &in_range, &ops)
+        }
+        quickcheck(p as fn(HashSet<i8>, Vec<Option<i8>>, (i8, i8)) -> bool)
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn prop_iter_sorted() {
+        fn p(input: HashSet<i32>) -> bool {
+            let mut tree = AVLTree::new();
+            for i in input.iter() {
+                tree.insert(*i, *i);
+            }
+            let mut expected = input.into_iter().collect::<Vec<_>>();
+            expected.sort();
+            tree.iter().map(|e| *e.0).collect::<Vec<_>>() == expected
+        }
+        quickcheck(p as fn(HashSet<i32>) -> bool)
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
     fn prop_insertion() {
         fn p(input: HashSet<i32>) -> bool {
             let mut tree = AVLTree::new();
@@ -478,6 +1561,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg_attr(miri, ignore)]
     fn prop_balance() {
         fn p(input: HashSet<i32>) -> bool {
             let mut tree = AVLTree::new();
@@ -490,6 +1574,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg_attr(miri, ignore)]
     fn prop_removal() {
         fn p(input: HashSet<i32>) -> bool {
             let seq = input.into_iter().collect::<Vec<_>>();
@@ -506,4 +1591,158 @@ mod tests {
         }
         quickcheck(p as fn(HashSet<i32>) -> bool)
     }
+
+    #[test]
+    fn custom_allocator() {
+        let alloc = Counting::default();
+        let mut tree = AVLTree::new_in(alloc.clone());
+        for i in 0..100 {
+            tree.insert(i, i);
+        }
+        // Each node owns two child slots
+        assert_eq!(alloc.0.get(), 200);
+        for i in 0..50 {
+            tree.remove(&i);
+        }
+        let copy = tree.clone();
+        assert_eq!(alloc.0.get(), 200);
+        assert!(copy.iter().map(|(k, _)| *k).eq(50..100));
+        drop(tree);
+        drop(copy);
+        assert_eq!(alloc.0.get(), 0);
+    }
+
+    #[test]
+    fn into_iterator_frees_nodes() {
+        let alloc = Counting::default();
+        let mut tree = AVLTree::new_in(alloc.clone());
+        for i in 0..100 {
+            tree.insert(i, i.to_string());
+        }
+        tree.remove(&10);
+        let mut iter = tree.into_iter();
+        assert_eq!(iter.next(), Some((0, String::from("0"))));
+        assert!(iter
+            .by_ref()
+            .take(20)
+            .map(|(k, _)| k)
+            .eq((1..10).chain(11..22)));
+        // Dropping the iterator part way frees the entries it has yet to yield
+        drop(iter);
+        assert_eq!(alloc.0.get(), 0);
+    }
+
+    #[test]
+    fn prop_into_iter_sorted() {
+        fn p(keys: Vec<i32>) -> bool {
+            let mut tree = AVLTree::new();
+            let mut model = BTreeMap::new();
+            for k in keys {
+                tree.insert(k, k.to_string());
+                model.insert(k, k.to_string());
+            }
+            let copy = tree.clone();
+            tree.into_iter().eq(model.clone())
+                && copy.clone().into_keys().eq(model.keys().copied())
+                && copy.into_values().eq(model.into_values())
+        }
+        quickcheck(p as fn(Vec<i32>) -> bool)
+    }
+
+    #[test]
+    fn footprint_matches_allocations() {
+        let alloc = Counting::default();
+        let mut tree = AVLTree::new_in(alloc.clone());
+        assert_eq!(tree.heap_bytes(), 0);
+        for i in 0..100 {
+            tree.insert(i, i);
+        }
+        tree.remove(&7);
+        assert_eq!(tree.node_count(), 99);
+        assert_eq!(
+            tree.heap_bytes(),
+            alloc.0.get() as usize * std::mem::size_of::<AVLTree<i32, i32, Counting>>()
+        );
+    }
+
+    #[test]
+    fn debug() {
+        let mut tree = AVLTree::new();
+        for (k, v) in [(2, 'b'), (3, 'c'), (1, 'a')] {
+            tree.insert(k, v);
+        }
+        assert_eq!(format!("{:?}", tree), "{1: 'a', 2: 'b', 3: 'c'}");
+        let mut single = AVLTree::new();
+        single.insert(1, 'a');
+        assert_eq!(
+            format!("{:?}", DebugStructure(&single)),
+            "Node { key: 1, value: 'a', height: 1, left: Nil, right: Nil }"
+        );
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn prop_compares_by_content() {
+        fn p(a: Vec<(u8, u8)>, b: Vec<(u8, u8)>) -> bool {
+            let build = |pairs: &[(u8, u8)]| {
+                let mut tree = AVLTree::new();
+                pairs.iter().for_each(|&(k, v)| tree.insert(k, v));
+                tree
+            };
+            let (x, y) = (build(&a), build(&b));
+            // The tree keeps the first value inserted for a key
+            let (bx, by) = (
+                a.iter().rev().copied().collect::<BTreeMap<_, _>>(),
+                b.iter().rev().copied().collect::<BTreeMap<_, _>>(),
+            );
+            // Inserting in sorted order gives the same entries in a different shape
+            let sorted = build(&bx.iter().map(|(k, v)| (*k, *v)).collect::<Vec<_>>());
+            let hash = |tree: &AVLTree<u8, u8>| {
+                let mut hasher = DefaultHasher::new();
+                tree.hash(&mut hasher);
+                hasher.finish()
+            };
+            x.cmp(&y) == bx.cmp(&by)
+                && (x == y) == (bx == by)
+                && x == sorted
+                && hash(&x) == hash(&sorted)
+        }
+        quickcheck(p as fn(Vec<(u8, u8)>, Vec<(u8, u8)>) -> bool)
+    }
+
+    // Owned values let Miri check each move, rotation and drop through the raw node links
+    #[test]
+    fn owned_values() {
+        let mut tree = AVLTree::new();
+        for i in [5, 2, 8, 1, 3, 7, 9, 4, 6] {
+            tree.insert(i, i.to_string());
+        }
+        for i in [2, 8, 5] {
+            assert_eq!(tree.remove(&i), Some(i.to_string()));
+        }
+        let copy = tree.clone();
+        drop(tree);
+        assert!(copy.iter().map(|(k, _)| *k).eq([1, 3, 4, 6, 7, 9]));
+    }
+
+    #[test]
+    fn diff() {
+        let mut old = AVLTree::new();
+        let mut new = AVLTree::new();
+        for (k, v) in [("host", "a"), ("port", "80"), ("user", "root")] {
+            old.insert(k, v);
+        }
+        for (k, v) in [("debug", "1"), ("host", "a"), ("port", "8080")] {
+            new.insert(k, v);
+        }
+        assert_eq!(
+            old.diff(&new).collect::<Vec<_>>(),
+            vec![
+                Diff::Added(&"debug", &"1"),
+                Diff::Changed(&"port", &"80", &"8080"),
+                Diff::Removed(&"user", &"root"),
+            ]
+        );
+        assert_eq!(old.diff(&old).count(), 0);
+    }
 }
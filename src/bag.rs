@@ -0,0 +1,259 @@
+use std::{
+    borrow::Borrow,
+    collections::{hash_map::RandomState, HashMap},
+    hash::{BuildHasher, Hash},
+};
+
+use crate::small_vec::SmallVec;
+
+/// A multiset stored in a vector, supporting constant time insertion, removal of a
+/// uniformly random element, and lookups through a hash table of each element's positions.
+/// Removal moves the last element into the vacated slot, so the order is arbitrary.
+/// Elements are stored twice, once in each structure, so they must be `Clone`.
+#[derive(Debug, Clone)]
+pub struct Bag<T, S = RandomState> {
+    items: Vec<T>,
+    positions: HashMap<T, SmallVec<usize, 1>, S>,
+    rng: u64,
+}
+
+impl<T> Bag<T> {
+    pub fn new() -> Self {
+        Bag::with_hasher(RandomState::new())
+    }
+}
+
+impl<T, S> Bag<T, S> {
+    pub fn with_hasher(hasher: S) -> Self {
+        Bag {
+            items: vec![],
+            positions: HashMap::with_hasher(hasher),
+            rng: 0x2545_f491_4f6c_dd1d,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Reseeds the generator used by `choose` and `remove_random`.
+    pub fn reseed(&mut self, seed: u64) {
+        // xorshift gets stuck at zero
+        self.rng = seed.max(1);
+    }
+
+    pub fn get_index(&self, index: usize) -> Option<&T> {
+        self.items.get(index)
+    }
+
+    /// Returns a uniformly random element.
+    pub fn choose(&mut self) -> Option<&T> {
+        if self.items.is_empty() {
+            return None;
+        }
+        let index = self.random_index();
+        self.items.get(index)
+    }
+
+    /// Returns an iterator over the elements in an arbitrary order.
+    pub fn iter(&self) -> std::slice::Iter<'_, T> {
+        self.items.iter()
+    }
+
+    pub fn clear(&mut self) {
+        self.items.clear();
+        self.positions.clear();
+    }
+
+    fn random_index(&mut self) -> usize {
+        // xorshift64
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        (self.rng % self.items.len() as u64) as usize
+    }
+}
+
+impl<T, S: Default> Default for Bag<T, S> {
+    fn default() -> Self {
+        Bag::with_hasher(S::default())
+    }
+}
+
+impl<T, S> Bag<T, S>
+where
+    T: Eq + Hash + Clone,
+    S: BuildHasher,
+{
+    pub fn insert(&mut self, t: T) {
+        self.positions
+            .entry(t.clone())
+            .or_default()
+            .push(self.items.len());
+        self.items.push(t);
+    }
+
+    pub fn contains<Q>(&self, t: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.positions.contains_key(t)
+    }
+
+    /// The number of occurrences of the element.
+    pub fn count<Q>(&self, t: &Q) -> usize
+    where
+        T: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.positions.get(t).map_or(0, |p| p.len())
+    }
+
+    /// Removes one occurrence of the element, returning whether it was present.
+    pub fn remove<Q>(&mut self, t: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        match self.positions.get(t).and_then(|p| p.last()) {
+            Some(&index) => {
+                self.swap_remove_index(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Removes and returns a uniformly random element.
+    pub fn remove_random(&mut self) -> Option<T> {
+        if self.items.is_empty() {
+            return None;
+        }
+        let index = self.random_index();
+        Some(self.swap_remove_index(index))
+    }
+
+    /// Removes the element at `index`, moving the last element into its place.
+    /// Panics if the index is out of bounds.
+    pub fn swap_remove_index(&mut self, index: usize) -> T {
+        let last = self.items.len() - 1;
+        let positions = self.positions.get_mut(&self.items[index]).unwrap();
+        let i = positions.iter().position(|&p| p == index).unwrap();
+        positions.remove(i);
+        if positions.is_empty() {
+            self.positions.remove(&self.items[index]);
+        }
+        if index != last {
+            let moved = self.positions.get_mut(&self.items[last]).unwrap();
+            *moved.iter_mut().find(|p| **p == last).unwrap() = index;
+        }
+        self.items.swap_remove(index)
+    }
+}
+
+impl<T, S> FromIterator<T> for Bag<T, S>
+where
+    T: Eq + Hash + Clone,
+    S: BuildHasher + Default,
+{
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut bag = Bag::default();
+        bag.extend(iter);
+        bag
+    }
+}
+
+impl<T, S> Extend<T> for Bag<T, S>
+where
+    T: Eq + Hash + Clone,
+    S: BuildHasher,
+{
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for t in iter {
+            self.insert(t);
+        }
+    }
+}
+
+impl<'a, T, S> IntoIterator for &'a Bag<T, S> {
+    type Item = &'a T;
+    type IntoIter = std::slice::Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use quickcheck::quickcheck;
+
+    use super::Bag;
+
+    #[test]
+    fn counts() {
+        let mut bag = "abacab".chars().collect::<Bag<_>>();
+        assert_eq!(bag.count(&'a'), 3);
+        assert!(bag.remove(&'a'));
+        assert!(bag.remove(&'c'));
+        assert!(!bag.remove(&'c'));
+        assert!(!bag.contains(&'c'));
+        assert_eq!(bag.len(), 4);
+    }
+
+    #[test]
+    fn remove_random() {
+        let mut bag = (0..100).collect::<Bag<_>>();
+        bag.reseed(7);
+        let mut removed = (0..100)
+            .map(|_| bag.remove_random().unwrap())
+            .collect::<Vec<_>>();
+        assert!(bag.is_empty() && bag.remove_random().is_none());
+        assert_ne!(removed, (0..100).collect::<Vec<_>>());
+        removed.sort();
+        assert_eq!(removed, (0..100).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn roughly_uniform() {
+        let mut bag = (0..4).collect::<Bag<_>>();
+        let mut counts = [0; 4];
+        for _ in 0..4000 {
+            counts[*bag.choose().unwrap()] += 1;
+        }
+        assert!(counts.iter().all(|&c| c > 800), "{:?}", counts);
+    }
+
+    #[test]
+    fn prop_positions_agree() {
+        fn p(input: Vec<u8>, removals: Vec<Option<u8>>) -> bool {
+            let mut bag = input.iter().copied().collect::<Bag<_>>();
+            let mut model = HashMap::new();
+            for i in input {
+                *model.entry(i).or_insert(0) += 1;
+            }
+            for r in removals {
+                let removed = match r {
+                    Some(r) => bag.remove(&r).then_some(r),
+                    None => bag.remove_random(),
+                };
+                if let Some(r) = removed {
+                    *model.get_mut(&r).unwrap() -= 1;
+                }
+            }
+            model.iter().all(|(k, &c)| bag.count(k) == c)
+                && bag
+                    .iter()
+                    .enumerate()
+                    .all(|(i, t)| bag.positions[t].contains(&i))
+        }
+        quickcheck(p as fn(Vec<u8>, Vec<Option<u8>>) -> bool)
+    }
+}
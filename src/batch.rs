@@ -0,0 +1,187 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use crate::clock::{Clock, SystemClock};
+
+/// A queue which hands out its items in batches. A batch is ready once `max_size` items
+/// are waiting, or, with a maximum age, once the oldest item has waited that long, and
+/// `flush` takes whatever is waiting regardless. Batches hold at most `max_size` items and
+/// preserve the order the items were pushed in.
+/// Arrival times are read from a `Clock`, which tests can replace with a `MockClock`.
+#[derive(Debug, Clone)]
+pub struct BatchQueue<T, C = SystemClock> {
+    items: VecDeque<(T, Instant)>,
+    max_size: usize,
+    max_age: Option<Duration>,
+    clock: C,
+}
+
+impl<T> BatchQueue<T> {
+    /// Creates a queue which releases batches of `max_size` items. Panics if `max_size` is
+    /// zero.
+    pub fn new(max_size: usize) -> Self {
+        BatchQueue::with_clock(max_size, SystemClock)
+    }
+
+    /// Creates a queue which also releases a smaller batch once its oldest item has waited
+    /// for `max_age`.
+    pub fn with_max_age(max_size: usize, max_age: Duration) -> Self {
+        BatchQueue::with_max_age_and_clock(max_size, max_age, SystemClock)
+    }
+}
+
+impl<T, C: Clock> BatchQueue<T, C> {
+    /// Creates a queue which releases batches of `max_size` items and measures time with
+    /// `clock`. Panics if `max_size` is zero.
+    pub fn with_clock(max_size: usize, clock: C) -> Self {
+        assert!(max_size > 0, "batch size must be positive");
+        BatchQueue {
+            items: VecDeque::new(),
+            max_size,
+            max_age: None,
+            clock,
+        }
+    }
+
+    pub fn with_max_age_and_clock(max_size: usize, max_age: Duration, clock: C) -> Self {
+        BatchQueue {
+            max_age: Some(max_age),
+            ..BatchQueue::with_clock(max_size, clock)
+        }
+    }
+
+    pub fn push(&mut self, item: T) {
+        self.push_at(item, self.clock.now());
+    }
+
+    /// Takes the next batch if it is full or has waited too long.
+    pub fn pop_batch(&mut self) -> Option<Vec<T>> {
+        self.pop_batch_at(self.clock.now())
+    }
+
+    /// The time on the queue's clock.
+    pub fn now(&self) -> Instant {
+        self.clock.now()
+    }
+}
+
+impl<T, C> BatchQueue<T, C> {
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn max_size(&self) -> usize {
+        self.max_size
+    }
+
+    pub fn max_age(&self) -> Option<Duration> {
+        self.max_age
+    }
+
+    /// Pushes an item as though it arrived at `now`, which must not be before the arrival
+    /// of the items already waiting.
+    pub fn push_at(&mut self, item: T, now: Instant) {
+        self.items.push_back((item, now));
+    }
+
+    /// Takes the next batch if it is full or has waited too long as of `now`.
+    pub fn pop_batch_at(&mut self, now: Instant) -> Option<Vec<T>> {
+        let full = self.items.len() >= self.max_size;
+        let expired = self.deadline().is_some_and(|deadline| deadline <= now);
+        if full || expired {
+            self.flush()
+        } else {
+            None
+        }
+    }
+
+    /// Takes up to `max_size` of the waiting items, whether or not a batch is ready.
+    pub fn flush(&mut self) -> Option<Vec<T>> {
+        if self.items.is_empty() {
+            return None;
+        }
+        let len = self.items.len().min(self.max_size);
+        Some(self.items.drain(..len).map(|(item, _)| item).collect())
+    }
+
+    /// When the next batch becomes ready by age, if there is a maximum age and an item
+    /// waiting. A full batch is ready immediately, whatever its deadline.
+    pub fn deadline(&self) -> Option<Instant> {
+        let (_, arrived) = self.items.front()?;
+        Some(*arrived + self.max_age?)
+    }
+}
+
+impl<T, C: Clock> Extend<T> for BatchQueue<T, C> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        let now = self.clock.now();
+        for item in iter {
+            self.push_at(item, now);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use super::BatchQueue;
+    use crate::clock::{Clock, MockClock};
+
+    #[test]
+    fn batches_by_size() {
+        let mut queue = BatchQueue::new(3);
+        queue.extend(0..7);
+        assert_eq!(queue.pop_batch(), Some(vec![0, 1, 2]));
+        assert_eq!(queue.pop_batch(), Some(vec![3, 4, 5]));
+        assert_eq!(queue.pop_batch(), None);
+        assert_eq!(queue.deadline(), None);
+        assert_eq!(queue.flush(), Some(vec![6]));
+        assert_eq!(queue.flush(), None);
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn batches_by_age() {
+        let second = Duration::from_secs(1);
+        let start = Instant::now();
+        let mut queue = BatchQueue::with_max_age(10, second);
+        queue.push_at('a', start);
+        queue.push_at('b', start + second / 2);
+        assert_eq!(queue.deadline(), Some(start + second));
+        assert_eq!(queue.pop_batch_at(start + second / 2), None);
+        assert_eq!(queue.pop_batch_at(start + second), Some(vec!['a', 'b']));
+        queue.push_at('c', start + second * 2);
+        assert_eq!(queue.deadline(), Some(start + second * 3));
+        assert_eq!(queue.pop_batch_at(start + second * 2), None);
+    }
+
+    #[test]
+    fn ages_by_clock() {
+        let clock = MockClock::new();
+        let mut queue = BatchQueue::with_max_age_and_clock(10, Duration::from_secs(1), &clock);
+        queue.push('a');
+        clock.advance(Duration::from_millis(500));
+        queue.extend(['b', 'c']);
+        assert_eq!(
+            queue.deadline(),
+            Some(clock.now() + Duration::from_millis(500))
+        );
+        assert_eq!(queue.pop_batch(), None);
+        clock.advance(Duration::from_millis(500));
+        assert_eq!(queue.pop_batch(), Some(vec!['a', 'b', 'c']));
+        assert_eq!(queue.deadline(), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "batch size must be positive")]
+    fn zero_size() {
+        BatchQueue::<()>::new(0);
+    }
+}
@@ -0,0 +1,262 @@
+use std::{
+    borrow::Borrow,
+    collections::{hash_map::RandomState, HashMap},
+    hash::{BuildHasher, Hash},
+};
+
+/// A one-to-one map which can be looked up from either side.
+/// Every left value is paired with exactly one right value and vice versa.
+/// Both sides are stored in each direction, so they must be `Clone`.
+#[derive(Debug, Clone)]
+pub struct BiMap<L, R, S = RandomState> {
+    left_to_right: HashMap<L, R, S>,
+    right_to_left: HashMap<R, L, S>,
+}
+
+/// The pairs which were removed to make room for a pair inserted with `BiMap::insert`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Overwritten<L, R> {
+    /// Neither value was present.
+    Neither,
+    /// The left value was paired with another right value.
+    Left(L, R),
+    /// The right value was paired with another left value.
+    Right(L, R),
+    /// The exact pair was already present.
+    Pair(L, R),
+    /// Both values were present in two different pairs.
+    Both((L, R), (L, R)),
+}
+
+impl<L, R> BiMap<L, R> {
+    pub fn new() -> Self {
+        BiMap::with_hasher(RandomState::new())
+    }
+}
+
+impl<L, R, S> BiMap<L, R, S> {
+    pub fn with_hasher(hasher: S) -> Self
+    where
+        S: Clone,
+    {
+        BiMap {
+            left_to_right: HashMap::with_hasher(hasher.clone()),
+            right_to_left: HashMap::with_hasher(hasher),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.left_to_right.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.left_to_right.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&L, &R)> {
+        self.left_to_right.iter()
+    }
+
+    pub fn clear(&mut self) {
+        self.left_to_right.clear();
+        self.right_to_left.clear();
+    }
+}
+
+impl<L, R, S: Clone + Default> Default for BiMap<L, R, S> {
+    fn default() -> Self {
+        BiMap::with_hasher(S::default())
+    }
+}
+
+impl<L, R, S> BiMap<L, R, S>
+where
+    L: Eq + Hash + Clone,
+    R: Eq + Hash + Clone,
+    S: BuildHasher + Clone,
+{
+    /// Panics if the two directions disagree.
+    #[cfg(any(test, feature = "testing"))]
+    pub fn check_invariants(&self) {
+        assert_eq!(
+            self.left_to_right.len(),
+            self.right_to_left.len(),
+            "lengths differ"
+        );
+        for (l, r) in self.left_to_right.iter() {
+            assert!(self.right_to_left.get(r) == Some(l), "directions disagree");
+        }
+    }
+
+    /// Inserts the pair, removing any existing pairs which contain either value.
+    pub fn insert(&mut self, l: L, r: R) -> Overwritten<L, R> {
+        let by_left = self.remove_by_left(&l);
+        let by_right = self.remove_by_right(&r);
+        self.insert_unchecked(l, r);
+        match (by_left, by_right) {
+            (None, None) => Overwritten::Neither,
+            (Some(pair), None) => {
+                if pair.1 == self.left_to_right[&pair.0] {
+                    Overwritten::Pair(pair.0, pair.1)
+                } else {
+                    Overwritten::Left(pair.0, pair.1)
+                }
+            }
+            (None, Some(pair)) => Overwritten::Right(pair.0, pair.1),
+            (Some(a), Some(b)) => Overwritten::Both(a, b),
+        }
+    }
+
+    /// Inserts the pair only if neither value is already present.
+    /// Otherwise the map is unchanged and the pair is returned.
+    pub fn try_insert(&mut self, l: L, r: R) -> Result<(), (L, R)> {
+        if self.left_to_right.contains_key(&l) || self.right_to_left.contains_key(&r) {
+            return Err((l, r));
+        }
+        self.insert_unchecked(l, r);
+        Ok(())
+    }
+
+    fn insert_unchecked(&mut self, l: L, r: R) {
+        self.left_to_right.insert(l.clone(), r.clone());
+        self.right_to_left.insert(r, l);
+    }
+
+    pub fn get_by_left<Q>(&self, l: &Q) -> Option<&R>
+    where
+        L: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.left_to_right.get(l)
+    }
+
+    pub fn get_by_right<Q>(&self, r: &Q) -> Option<&L>
+    where
+        R: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.right_to_left.get(r)
+    }
+
+    pub fn contains_left<Q>(&self, l: &Q) -> bool
+    where
+        L: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.left_to_right.contains_key(l)
+    }
+
+    pub fn contains_right<Q>(&self, r: &Q) -> bool
+    where
+        R: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.right_to_left.contains_key(r)
+    }
+
+    pub fn remove_by_left<Q>(&mut self, l: &Q) -> Option<(L, R)>
+    where
+        L: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let (l, r) = self.left_to_right.remove_entry(l)?;
+        self.right_to_left.remove(&r);
+        Some((l, r))
+    }
+
+    pub fn remove_by_right<Q>(&mut self, r: &Q) -> Option<(L, R)>
+    where
+        R: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let (r, l) = self.right_to_left.remove_entry(r)?;
+        self.left_to_right.remove(&l);
+        Some((l, r))
+    }
+}
+
+impl<L, R, S> PartialEq for BiMap<L, R, S>
+where
+    L: Eq + Hash,
+    R: PartialEq,
+    S: BuildHasher,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.left_to_right == other.left_to_right
+    }
+}
+
+impl<L, R, S> Eq for BiMap<L, R, S>
+where
+    L: Eq + Hash,
+    R: Eq,
+    S: BuildHasher,
+{
+}
+
+impl<L, R, S> FromIterator<(L, R)> for BiMap<L, R, S>
+where
+    L: Eq + Hash + Clone,
+    R: Eq + Hash + Clone,
+    S: BuildHasher + Default + Clone,
+{
+    /// Later pairs overwrite earlier ones which share a value.
+    fn from_iter<I: IntoIterator<Item = (L, R)>>(iter: I) -> Self {
+        let mut map = BiMap::default();
+        for (l, r) in iter {
+            map.insert(l, r);
+        }
+        map
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use quickcheck::quickcheck;
+
+    use super::{BiMap, Overwritten};
+
+    #[test]
+    fn lookups() {
+        let mut map = BiMap::new();
+        map.insert(1, "one");
+        map.insert(2, "two");
+        assert_eq!(map.get_by_left(&1), Some(&"one"));
+        assert_eq!(map.get_by_right("two"), Some(&2));
+        assert_eq!(map.remove_by_right("one"), Some((1, "one")));
+        assert!(!map.contains_left(&1));
+        assert_eq!(map.len(), 1);
+    }
+
+    #[test]
+    fn overwrite() {
+        let mut map = BiMap::new();
+        assert_eq!(map.insert(1, 'a'), Overwritten::Neither);
+        assert_eq!(map.insert(1, 'a'), Overwritten::Pair(1, 'a'));
+        assert_eq!(map.insert(1, 'b'), Overwritten::Left(1, 'a'));
+        assert_eq!(map.insert(2, 'b'), Overwritten::Right(1, 'b'));
+        map.insert(3, 'c');
+        assert_eq!(map.insert(2, 'c'), Overwritten::Both((2, 'b'), (3, 'c')));
+        assert_eq!(map.len(), 1);
+        assert_eq!(map.get_by_right(&'c'), Some(&2));
+    }
+
+    #[test]
+    fn reject() {
+        let mut map = BiMap::new();
+        assert_eq!(map.try_insert(1, 'a'), Ok(()));
+        assert_eq!(map.try_insert(1, 'b'), Err((1, 'b')));
+        assert_eq!(map.try_insert(2, 'a'), Err((2, 'a')));
+        assert_eq!(map.get_by_left(&1), Some(&'a'));
+    }
+
+    #[test]
+    fn prop_directions_agree() {
+        fn p(pairs: Vec<(u8, u8)>) -> bool {
+            let map = pairs.into_iter().collect::<BiMap<_, _>>();
+            map.iter().all(|(l, r)| map.get_by_right(r) == Some(l))
+                && map.right_to_left.len() == map.len()
+        }
+        quickcheck(p as fn(Vec<(u8, u8)>) -> bool)
+    }
+}
@@ -0,0 +1,391 @@
+use std::ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, BitXorAssign, Not, Shl, Shr};
+
+const WORD_BITS: usize = u64::BITS as usize;
+
+/// A set of small non-negative integers stored as a packed bit vector.
+/// The set has a length in bits, which grows automatically when a bit past the end is set.
+/// Binary operations between sets of different lengths treat the missing bits as cleared
+/// and produce a set as long as the longer operand.
+/// Invariant: every bit at or past `len` in the backing words is zero.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct BitSet {
+    words: Vec<u64>,
+    len: usize,
+}
+
+impl BitSet {
+    /// Panics if the words don't match the length or a bit past the end is set.
+    #[cfg(any(test, feature = "testing"))]
+    pub fn check_invariants(&self) {
+        assert_eq!(
+            self.words.len(),
+            self.len.div_ceil(WORD_BITS),
+            "wrong number of words"
+        );
+        let used = self.len % WORD_BITS;
+        if used != 0 {
+            assert_eq!(
+                self.words[self.words.len() - 1] >> used,
+                0,
+                "bit set past the end"
+            );
+        }
+    }
+
+    pub fn new() -> Self {
+        BitSet::default()
+    }
+
+    /// Creates a set of `len` cleared bits.
+    pub fn with_len(len: usize) -> Self {
+        BitSet {
+            words: vec![0; len.div_ceil(WORD_BITS)],
+            len,
+        }
+    }
+
+    /// The number of bits, set or not.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Grows or truncates the set to `len` bits. New bits are cleared.
+    pub fn resize(&mut self, len: usize) {
+        self.words.resize(len.div_ceil(WORD_BITS), 0);
+        self.len = len;
+        self.clear_unused_bits();
+    }
+
+    /// Sets bit `i`, growing the set if needed.
+    /// Returns whether the bit was previously cleared.
+    pub fn set(&mut self, i: usize) -> bool {
+        if i >= self.len {
+            self.resize(i + 1);
+        }
+        let (word, mask) = (i / WORD_BITS, 1 << (i % WORD_BITS));
+        let was_clear = self.words[word] & mask == 0;
+        self.words[word] |= mask;
+        was_clear
+    }
+
+    /// Clears bit `i`, returning whether it was previously set.
+    pub fn clear(&mut self, i: usize) -> bool {
+        if i >= self.len {
+            return false;
+        }
+        let (word, mask) = (i / WORD_BITS, 1 << (i % WORD_BITS));
+        let was_set = self.words[word] & mask != 0;
+        self.words[word] &= !mask;
+        was_set
+    }
+
+    pub fn test(&self, i: usize) -> bool {
+        i < self.len && self.words[i / WORD_BITS] & (1 << (i % WORD_BITS)) != 0
+    }
+
+    /// Flips bit `i`, growing the set if needed.
+    pub fn toggle(&mut self, i: usize) {
+        if !self.set(i) {
+            self.clear(i);
+        }
+    }
+
+    /// Clears every bit without changing the length.
+    pub fn clear_all(&mut self) {
+        self.words.iter_mut().for_each(|w| *w = 0);
+    }
+
+    pub fn count_ones(&self) -> usize {
+        self.words.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    pub fn count_zeros(&self) -> usize {
+        self.len - self.count_ones()
+    }
+
+    /// Returns an iterator over the indices of the set bits in ascending order.
+    pub fn iter(&self) -> Iter<'_> {
+        Iter {
+            words: &self.words,
+            index: 0,
+            current: self.words.first().copied().unwrap_or(0),
+        }
+    }
+
+    pub fn is_subset(&self, other: &BitSet) -> bool {
+        self.words
+            .iter()
+            .enumerate()
+            .all(|(i, w)| w & !other.words.get(i).copied().unwrap_or(0) == 0)
+    }
+
+    pub fn is_disjoint(&self, other: &BitSet) -> bool {
+        self.words
+            .iter()
+            .zip(other.words.iter())
+            .all(|(a, b)| a & b == 0)
+    }
+
+    fn clear_unused_bits(&mut self) {
+        let used = self.len % WORD_BITS;
+        if used != 0 {
+            if let Some(last) = self.words.last_mut() {
+                *last &= (1 << used) - 1;
+            }
+        }
+    }
+
+    fn combine_with<F: Fn(u64, u64) -> u64>(&mut self, other: &BitSet, f: F) {
+        if other.len > self.len {
+            self.resize(other.len);
+        }
+        for (i, word) in self.words.iter_mut().enumerate() {
+            *word = f(*word, other.words.get(i).copied().unwrap_or(0));
+        }
+    }
+}
+
+impl FromIterator<usize> for BitSet {
+    fn from_iter<I: IntoIterator<Item = usize>>(iter: I) -> Self {
+        let mut set = BitSet::new();
+        for i in iter {
+            set.set(i);
+        }
+        set
+    }
+}
+
+impl<'a> IntoIterator for &'a BitSet {
+    type Item = usize;
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// An iterator over the indices of the set bits of a `BitSet`.
+#[derive(Debug, Clone)]
+pub struct Iter<'a> {
+    words: &'a [u64],
+    index: usize,
+    // The remaining bits of the current word
+    current: u64,
+}
+
+impl Iterator for Iter<'_> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        while self.current == 0 {
+            self.index += 1;
+            self.current = *self.words.get(self.index)?;
+        }
+        let bit = self.current.trailing_zeros() as usize;
+        // Clear the lowest set bit
+        self.current &= self.current - 1;
+        Some(self.index * WORD_BITS + bit)
+    }
+}
+
+impl BitAndAssign<&BitSet> for BitSet {
+    fn bitand_assign(&mut self, rhs: &BitSet) {
+        self.combine_with(rhs, |a, b| a & b);
+    }
+}
+
+impl BitOrAssign<&BitSet> for BitSet {
+    fn bitor_assign(&mut self, rhs: &BitSet) {
+        self.combine_with(rhs, |a, b| a | b);
+    }
+}
+
+impl BitXorAssign<&BitSet> for BitSet {
+    fn bitxor_assign(&mut self, rhs: &BitSet) {
+        self.combine_with(rhs, |a, b| a ^ b);
+    }
+}
+
+impl BitAnd for &BitSet {
+    type Output = BitSet;
+
+    fn bitand(self, rhs: &BitSet) -> BitSet {
+        let mut out = self.clone();
+        out &= rhs;
+        out
+    }
+}
+
+impl BitOr for &BitSet {
+    type Output = BitSet;
+
+    fn bitor(self, rhs: &BitSet) -> BitSet {
+        let mut out = self.clone();
+        out |= rhs;
+        out
+    }
+}
+
+impl BitXor for &BitSet {
+    type Output = BitSet;
+
+    fn bitxor(self, rhs: &BitSet) -> BitSet {
+        let mut out = self.clone();
+        out ^= rhs;
+        out
+    }
+}
+
+/// Flips every bit within the length of the set.
+impl Not for &BitSet {
+    type Output = BitSet;
+
+    fn not(self) -> BitSet {
+        let mut out = BitSet {
+            words: self.words.iter().map(|w| !w).collect(),
+            len: self.len,
+        };
+        out.clear_unused_bits();
+        out
+    }
+}
+
+/// Moves every bit to a higher index. Bits shifted past the length are dropped.
+impl Shl<usize> for &BitSet {
+    type Output = BitSet;
+
+    fn shl(self, n: usize) -> BitSet {
+        let mut out = BitSet::with_len(self.len);
+        let (word_shift, bit_shift) = (n / WORD_BITS, n % WORD_BITS);
+        for i in (word_shift..out.words.len()).rev() {
+            let src = i - word_shift;
+            let mut word = self.words[src] << bit_shift;
+            if bit_shift > 0 && src > 0 {
+                word |= self.words[src - 1] >> (WORD_BITS - bit_shift);
+            }
+            out.words[i] = word;
+        }
+        out.clear_unused_bits();
+        out
+    }
+}
+
+/// Moves every bit to a lower index. Bits shifted below zero are dropped.
+impl Shr<usize> for &BitSet {
+    type Output = BitSet;
+
+    fn shr(self, n: usize) -> BitSet {
+        let mut out = BitSet::with_len(self.len);
+        let (word_shift, bit_shift) = (n / WORD_BITS, n % WORD_BITS);
+        for i in 0..out.words.len().saturating_sub(word_shift) {
+            let src = i + word_shift;
+            let mut word = self.words[src] >> bit_shift;
+            if bit_shift > 0 && src + 1 < self.words.len() {
+                word |= self.words[src + 1] << (WORD_BITS - bit_shift);
+            }
+            out.words[i] = word;
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeSet;
+
+    use quickcheck::quickcheck;
+
+    use super::BitSet;
+
+    #[test]
+    fn set_clear_test() {
+        let mut set = BitSet::with_len(10);
+        assert!(set.set(3));
+        assert!(!set.set(3));
+        assert!(set.test(3));
+        assert!(!set.test(4));
+        assert!(set.clear(3));
+        assert!(!set.test(3));
+        assert_eq!(set.len(), 10);
+    }
+
+    #[test]
+    fn grows() {
+        let mut set = BitSet::new();
+        set.set(130);
+        assert_eq!(set.len(), 131);
+        assert!(set.test(130));
+        assert!(!set.test(1000));
+    }
+
+    #[test]
+    fn iterate() {
+        let set = [0, 5, 63, 64, 200].into_iter().collect::<BitSet>();
+        assert_eq!(set.iter().collect::<Vec<_>>(), vec![0, 5, 63, 64, 200]);
+        assert_eq!(set.count_ones(), 5);
+        assert_eq!(BitSet::with_len(100).iter().next(), None);
+    }
+
+    #[test]
+    fn bitwise() {
+        let a = [1, 2, 3].into_iter().collect::<BitSet>();
+        let b = [3, 4, 100].into_iter().collect::<BitSet>();
+        assert_eq!((&a & &b).iter().collect::<Vec<_>>(), vec![3]);
+        assert_eq!((&a | &b).iter().collect::<Vec<_>>(), vec![1, 2, 3, 4, 100]);
+        assert_eq!((&a ^ &b).iter().collect::<Vec<_>>(), vec![1, 2, 4, 100]);
+        assert_eq!((&a & &b).len(), 101);
+    }
+
+    #[test]
+    fn not() {
+        let mut set = BitSet::with_len(5);
+        set.set(1);
+        let inverted = !&set;
+        assert_eq!(inverted.iter().collect::<Vec<_>>(), vec![0, 2, 3, 4]);
+        assert_eq!(inverted.count_zeros(), 1);
+    }
+
+    #[test]
+    fn shifts() {
+        let mut set = BitSet::with_len(130);
+        set.set(0);
+        set.set(63);
+        set.set(129);
+        assert_eq!((&set << 1).iter().collect::<Vec<_>>(), vec![1, 64]);
+        assert_eq!((&set << 66).iter().collect::<Vec<_>>(), vec![66, 129]);
+        assert_eq!((&set >> 1).iter().collect::<Vec<_>>(), vec![62, 128]);
+        assert_eq!((&set >> 65).iter().collect::<Vec<_>>(), vec![64]);
+    }
+
+    #[test]
+    fn subset_and_disjoint() {
+        let a = [1, 2].into_iter().collect::<BitSet>();
+        let b = [1, 2, 300].into_iter().collect::<BitSet>();
+        let c = [5].into_iter().collect::<BitSet>();
+        assert!(a.is_subset(&b));
+        assert!(!b.is_subset(&a));
+        assert!(a.is_disjoint(&c));
+    }
+
+    #[test]
+    fn prop_matches_btree_set() {
+        fn p(a: Vec<u8>, b: Vec<u8>) -> bool {
+            let (sa, sb) = (
+                a.iter().map(|&i| i as usize).collect::<BTreeSet<_>>(),
+                b.iter().map(|&i| i as usize).collect::<BTreeSet<_>>(),
+            );
+            let (ba, bb) = (
+                sa.iter().copied().collect::<BitSet>(),
+                sb.iter().copied().collect::<BitSet>(),
+            );
+            (&ba & &bb).iter().eq(sa.intersection(&sb).copied())
+                && (&ba | &bb).iter().eq(sa.union(&sb).copied())
+                && (&ba ^ &bb).iter().eq(sa.symmetric_difference(&sb).copied())
+        }
+        quickcheck(p as fn(Vec<u8>, Vec<u8>) -> bool)
+    }
+}
@@ -0,0 +1,119 @@
+//! A common interface for caches which hold a bounded number of entries and evict them
+//! according to some policy.
+
+use crate::Error;
+
+/// A map which holds at most `capacity` entries, evicting one chosen by its policy to make
+/// room for a new key. Code written against this trait can be tested with each policy.
+pub trait BoundedMap<K, V> {
+    fn capacity(&self) -> usize;
+
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn is_full(&self) -> bool {
+        self.len() >= self.capacity()
+    }
+
+    /// How many more entries fit before inserting evicts one.
+    fn remaining_capacity(&self) -> usize {
+        self.capacity().saturating_sub(self.len())
+    }
+
+    /// Whether the key is present, without counting as a use.
+    fn contains_key(&self, k: &K) -> bool;
+
+    /// Looks up the key, counting as a use for the eviction policy.
+    fn get(&mut self, k: &K) -> Option<&V>;
+
+    /// Inserts the entry, replacing the value of an existing key. Returns the entry evicted
+    /// to make room, which is the new entry itself if the capacity is zero.
+    fn insert_evicting(&mut self, k: K, v: V) -> Option<(K, V)>;
+
+    fn remove(&mut self, k: &K) -> Option<V>;
+
+    /// The key which the policy would evict next, if the map is not empty.
+    fn victim(&self) -> Option<&K>;
+
+    /// Inserts the entry only if the key is absent and there is room, so nothing is
+    /// overwritten or evicted. Otherwise the entry is handed back in the error.
+    fn try_insert(&mut self, k: K, v: V) -> Result<(), Error<(K, V)>> {
+        if self.contains_key(&k) {
+            return Err(Error::Exists((k, v)));
+        }
+        if self.is_full() {
+            return Err(Error::Full((k, v)));
+        }
+        self.insert_evicting(k, v);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::BoundedMap;
+    use crate::Error;
+
+    /// Checks the behaviour shared by every policy on a map with a capacity of two.
+    #[cfg_attr(
+        not(any(feature = "lfu_cache", feature = "lru_cache")),
+        allow(dead_code)
+    )]
+    fn check_contract<M: BoundedMap<u32, u32>>(mut map: M) {
+        assert_eq!(map.capacity(), 2);
+        assert_eq!(map.remaining_capacity(), 2);
+        assert!(map.is_empty());
+        assert_eq!(map.victim(), None);
+        assert_eq!(map.insert_evicting(1, 10), None);
+        assert_eq!(map.insert_evicting(1, 11), None);
+        assert_eq!(map.try_insert(1, 12), Err(Error::Exists((1, 12))));
+        assert_eq!(map.try_insert(2, 20), Ok(()));
+        assert!(map.is_full());
+        assert_eq!(map.remaining_capacity(), 0);
+        assert_eq!(map.try_insert(3, 30), Err(Error::Full((3, 30))));
+        let victim = *map.victim().unwrap();
+        let (evicted, _) = map.insert_evicting(3, 30).unwrap();
+        assert_eq!(evicted, victim);
+        assert!(!map.contains_key(&victim));
+        assert_eq!(map.get(&3), Some(&30));
+        assert_eq!(map.remove(&3), Some(30));
+        assert_eq!(map.remove(&3), None);
+        assert_eq!(map.len(), 1);
+        assert_eq!(map.try_insert(4, 40), Ok(()));
+    }
+
+    #[cfg(feature = "lru_cache")]
+    #[test]
+    fn lru_cache() {
+        use crate::lru_cache::LRUCache;
+
+        check_contract(LRUCache::new(2));
+        let mut cache = LRUCache::new(2);
+        cache.insert(1, ());
+        cache.insert(2, ());
+        cache.get(&1);
+        assert_eq!(cache.victim(), Some(&2));
+        assert_eq!(LRUCache::new(0).insert_evicting(1, ()), Some((1, ())));
+    }
+
+    #[cfg(feature = "lfu_cache")]
+    #[test]
+    fn lfu_cache() {
+        use crate::lfu_cache::LFUCache;
+
+        check_contract(LFUCache::new(2));
+        let mut cache = LFUCache::new(2);
+        cache.insert(1, ());
+        cache.insert(2, ());
+        cache.get(&2);
+        assert_eq!(cache.victim(), Some(&1));
+        cache.get(&1);
+        cache.get(&1);
+        assert_eq!(BoundedMap::remove(&mut cache, &2), Some(()));
+        assert_eq!(cache.victim(), Some(&1));
+        assert_eq!(LFUCache::new(0).insert_evicting(1, ()), Some((1, ())));
+    }
+}
@@ -1,24 +1,48 @@
-use std::cmp::Ordering;
+use std::{
+    cmp::Ordering,
+    fmt::{self, Debug},
+    ops::{Deref, DerefMut},
+};
+
+#[cfg(feature = "viz")]
+use crate::viz::{self, Visualize};
+use crate::{
+    from_sorted::{collect_sorted, FromSorted},
+    seek::Seekable,
+    small_vec::SmallVec,
+    traversal::TraversalStack,
+};
 
 /// An unbalanced binary search tree.
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub enum BSTree<A> {
     Node {
         value: A,
-        left: Box<BSTree<A>>,
-        right: Box<BSTree<A>>,
+        left: Subtree<A>,
+        right: Subtree<A>,
     },
+    #[default]
     Nil,
 }
 
-impl<A> BSTree<A> {
-    pub fn new() -> Self {
-        BSTree::Nil
+/// A child of a `BSTree` node, which derefs to the subtree it owns.
+/// Dropping it detaches the nodes below onto a stack before freeing them, so that dropping
+/// a degenerate tree doesn't recurse once per level.
+#[derive(Clone)]
+pub struct Subtree<A>(Box<BSTree<A>>);
+
+impl<A> Subtree<A> {
+    pub fn new(tree: BSTree<A>) -> Self {
+        Subtree(Box::new(tree))
+    }
+
+    pub fn into_inner(mut self) -> BSTree<A> {
+        std::mem::take(&mut *self.0)
     }
 }
 
-impl<A> Default for BSTree<A> {
-    fn default() -> Self {
+impl<A> BSTree<A> {
+    pub fn new() -> Self {
         BSTree::Nil
     }
 }
@@ -28,32 +52,32 @@ where
     A: Ord,
 {
     pub fn search(&self, a: A) -> Option<&BSTree<A>> {
-        match self {
-            BSTree::Node { value, left, right } => match a.cmp(value) {
-                Ordering::Less => left.search(a),
-                Ordering::Equal => Some(&self),
-                Ordering::Greater => right.search(a),
-            },
-            BSTree::Nil => None,
+        let mut tree = self;
+        while let BSTree::Node { value, left, right } = tree {
+            tree = match a.cmp(value) {
+                Ordering::Less => left,
+                Ordering::Equal => return Some(tree),
+                Ordering::Greater => right,
+            };
         }
+        None
     }
 
     pub fn insert(&mut self, a: A) -> bool {
-        match self {
-            BSTree::Node { value, left, right } => match a.cmp(value) {
-                Ordering::Less => left.insert(a),
-                Ordering::Equal => true,
-                Ordering::Greater => right.insert(a),
-            },
-            BSTree::Nil => {
-                *self = BSTree::Node {
-                    value: a,
-                    left: Box::new(BSTree::Nil),
-                    right: Box::new(BSTree::Nil),
-                };
-                false
-            }
+        let mut tree = self;
+        while let BSTree::Node { value, left, right } = tree {
+            tree = match a.cmp(value) {
+                Ordering::Less => left,
+                Ordering::Equal => return true,
+                Ordering::Greater => right,
+            };
         }
+        *tree = BSTree::Node {
+            value: a,
+            left: Subtree::default(),
+            right: Subtree::default(),
+        };
+        false
     }
 
     pub fn is_node(&self) -> bool {
@@ -68,61 +92,71 @@ where
     }
 
     pub fn remove(&mut self, a: A) -> bool {
-        match self {
-            BSTree::Node { value, left, right } => match a.cmp(value) {
-                Ordering::Less => left.remove(a),
-                Ordering::Equal => {
-                    match (left.is_node(), right.is_node()) {
-                        (true, true) => right.swap_leftmost(value), // Swap the current node with its immediate successor
-                        (true, false) => *self = std::mem::take(left), // Promote the left subtree
-                        (false, true) => *self = std::mem::take(right), // Promote the right subtree
-                        (false, false) => {
-                            // Clear out the current node
-                            std::mem::take(self);
-                        }
-                    }
-                    true
+        let mut tree = self;
+        while let Some(value) = tree.value() {
+            tree = match (a.cmp(value), tree) {
+                (Ordering::Equal, found) => {
+                    found.remove_root();
+                    return true;
                 }
-                Ordering::Greater => right.remove(a),
-            },
-            BSTree::Nil => false,
+                (Ordering::Less, BSTree::Node { left, .. }) => left,
+                (_, BSTree::Node { right, .. }) => right,
+                (_, BSTree::Nil) => unreachable!(),
+            };
         }
+        false
     }
 
-    fn swap_leftmost(&mut self, to: &mut A) {
-        match self {
-            BSTree::Node { value, left, right } => {
-                if !left.is_node() {
-                    std::mem::swap(value, to);
-                    *self = std::mem::take(right);
-                } else {
-                    left.swap_leftmost(to);
+    fn remove_root(&mut self) {
+        if let BSTree::Node { value, left, right } = self {
+            match (left.is_node(), right.is_node()) {
+                (true, true) => right.swap_leftmost(value), // Swap the current node with its immediate successor
+                (true, false) => *self = std::mem::take(&mut **left), // Promote the left subtree
+                (false, true) => *self = std::mem::take(&mut **right), // Promote the right subtree
+                (false, false) => {
+                    // Clear out the current node
+                    std::mem::take(self);
                 }
             }
-            BSTree::Nil => {}
         }
     }
 
+    fn swap_leftmost(&mut self, to: &mut A) {
+        let mut tree = self;
+        while matches!(tree, BSTree::Node { left, .. } if left.is_node()) {
+            if let BSTree::Node { left, .. } = tree {
+                tree = left;
+            }
+        }
+        if let BSTree::Node { value, right, .. } = tree {
+            std::mem::swap(value, to);
+            *tree = std::mem::take(&mut **right);
+        }
+    }
+
+    /// The number of nodes on the longest path from the root, which for a tree this isn't
+    /// balanced can be as many as it has values. It's found without recursing.
     pub fn height(&self) -> usize {
-        match self {
-            BSTree::Node {
-                value: _,
-                left,
-                right,
-            } => std::cmp::max(left.height(), right.height()) + 1,
-            BSTree::Nil => 0,
+        let mut height = 0;
+        let mut stack = TraversalStack::new();
+        stack.push((self, 0));
+        while let Some((tree, depth)) = stack.pop() {
+            if let BSTree::Node { left, right, .. } = tree {
+                height = height.max(depth + 1);
+                stack.push((left, depth + 1));
+                stack.push((right, depth + 1));
+            }
         }
+        height
+    }
+
+    /// The depth of the deepest node, counting the root as one, which is the tree's height.
+    pub fn max_depth(&self) -> usize {
+        self.height()
     }
 
     pub fn size(&self) -> usize {
-        match self {
-            BSTree::Node {
-                value: _,
-                left,
-                right,
-            } => left.size() + right.size() + 1,
-            BSTree::Nil => 0,
-        }
+        self.iter().count()
     }
 
     pub fn balance(&self) -> i16 {
@@ -150,69 +184,193 @@ where
     /// Returns an iterator that traverses the keys of the tree in ascending order.
     /// This corresponds to an in-order traveral of the tree.
     pub fn iter<'a>(&'a self) -> Iter<'a, A> {
-        Iter {
-            state: IterState::Left,
-            tree: self,
-            parent: None,
+        let mut iter = Iter {
+            stack: SmallVec::new(),
+        };
+        iter.push_left_edge(self);
+        iter
+    }
+}
+
+/// Copies the tree node by node from the root down, without recursing.
+impl<A: Clone> Clone for BSTree<A> {
+    fn clone(&self) -> Self {
+        let mut root = BSTree::Nil;
+        let mut stack = TraversalStack::new();
+        stack.push((self, &mut root));
+        while let Some((tree, copy)) = stack.pop() {
+            if let BSTree::Node { value, left, right } = tree {
+                *copy = BSTree::Node {
+                    value: value.clone(),
+                    left: Subtree::default(),
+                    right: Subtree::default(),
+                };
+                if let BSTree::Node {
+                    left: left_copy,
+                    right: right_copy,
+                    ..
+                } = copy
+                {
+                    stack.push((left, left_copy));
+                    stack.push((right, right_copy));
+                }
+            }
         }
+        root
     }
 }
 
-#[derive(Debug)]
-pub struct Iter<'a, A> {
-    state: IterState,
-    tree: &'a BSTree<A>,
-    parent: Option<Box<Iter<'a, A>>>,
+impl<A> Default for Subtree<A> {
+    fn default() -> Self {
+        Subtree::new(BSTree::Nil)
+    }
 }
 
-impl<'a, A> Iter<'a, A>
-where
-    A: Ord,
-{
-    fn continue_to_parent(&mut self) -> Option<&'a A> {
-        match self.parent.take() {
-            Some(mut p) => {
-                std::mem::swap(self, &mut p);
-                self.next()
+impl<A> Deref for Subtree<A> {
+    type Target = BSTree<A>;
+
+    fn deref(&self) -> &BSTree<A> {
+        &self.0
+    }
+}
+
+impl<A> DerefMut for Subtree<A> {
+    fn deref_mut(&mut self) -> &mut BSTree<A> {
+        &mut self.0
+    }
+}
+
+impl<A> From<BSTree<A>> for Subtree<A> {
+    fn from(tree: BSTree<A>) -> Self {
+        Subtree::new(tree)
+    }
+}
+
+impl<A: Debug> Debug for Subtree<A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl<A> Drop for Subtree<A> {
+    fn drop(&mut self) {
+        if let BSTree::Nil = *self.0 {
+            return;
+        }
+        let mut stack = TraversalStack::new();
+        stack.push(std::mem::take(&mut *self.0));
+        // Each node is dropped with its children already taken, so the empty subtrees left
+        // behind return straight away
+        while let Some(tree) = stack.pop() {
+            if let BSTree::Node { left, right, .. } = tree {
+                for mut child in [left, right] {
+                    if let BSTree::Node { .. } = *child.0 {
+                        stack.push(std::mem::take(&mut *child.0));
+                    }
+                }
             }
-            None => None,
         }
     }
 }
 
+/// Builds a perfectly balanced tree.
+impl<A: Ord> FromSorted<A> for BSTree<A> {
+    fn from_sorted_iter<I: IntoIterator<Item = A>>(iter: I) -> Self {
+        let values = collect_sorted(iter, true, |a| a);
+        BSTree::build_sorted(values.len(), &mut values.into_iter())
+    }
+}
+
+impl<A> BSTree<A> {
+    /// Builds a tree from the next `n` values, placing the middle one at the root.
+    fn build_sorted(n: usize, values: &mut impl Iterator<Item = A>) -> Self {
+        if n == 0 {
+            return BSTree::Nil;
+        }
+        let left = BSTree::build_sorted(n / 2, values);
+        let value = values.next().unwrap();
+        let right = BSTree::build_sorted(n - n / 2 - 1, values);
+        BSTree::Node {
+            value,
+            left: Subtree::new(left),
+            right: Subtree::new(right),
+        }
+    }
+}
+
+// Deep enough for a tree of millions of random insertions before the stack spills to the heap
+const STACK_DEPTH: usize = 48;
+
+/// An in-order iterator over a `BSTree`.
+/// Ancestors which have yet to be visited are kept on an inline stack, so iterating
+/// doesn't allocate unless the tree is very deep.
 #[derive(Debug)]
-enum IterState {
-    Left,
-    Node,
-    Right,
+pub struct Iter<'a, A> {
+    stack: SmallVec<(&'a A, &'a BSTree<A>), STACK_DEPTH>,
 }
 
-impl<'a, A> Iterator for Iter<'a, A>
-where
-    A: Ord,
-{
+impl<'a, A> Iter<'a, A> {
+    /// Pushes every node along the path to the smallest value of `tree`, along with its right subtree.
+    fn push_left_edge(&mut self, mut tree: &'a BSTree<A>) {
+        while let BSTree::Node { value, left, right } = tree {
+            self.stack.push((value, right));
+            tree = left;
+        }
+    }
+}
+
+impl<'a, A> Iterator for Iter<'a, A> {
     type Item = &'a A;
 
     fn next(&mut self) -> Option<Self::Item> {
-        match self.tree {
-            BSTree::Node { value, left, right } => match self.state {
-                IterState::Left => {
-                    self.state = IterState::Node;
-                    let mut new_parent = left.iter();
-                    std::mem::swap(self, &mut new_parent);
-                    self.parent = Some(Box::new(new_parent));
-                    self.next()
-                }
-                IterState::Node => {
-                    self.state = IterState::Right;
-                    let mut new_parent = right.iter();
-                    std::mem::swap(self, &mut new_parent);
-                    self.parent = Some(Box::new(new_parent));
-                    Some(value)
+        let (value, right) = self.stack.pop()?;
+        self.push_left_edge(right);
+        Some(value)
+    }
+}
+
+impl<A: Ord> Seekable for Iter<'_, A> {
+    type Key = A;
+
+    fn key_of<'i>(item: &'i &A) -> &'i A {
+        item
+    }
+
+    fn seek(&mut self, key: &A) {
+        // Every value below the top of the stack is greater than the top's right subtree
+        while let Some(&(value, right)) = self.stack.last() {
+            if value >= key {
+                return;
+            }
+            self.stack.pop();
+            let mut tree = right;
+            while let BSTree::Node { value, left, right } = tree {
+                if value >= key {
+                    self.stack.push((value, right));
+                    tree = left;
+                } else {
+                    tree = right;
                 }
-                IterState::Right => self.continue_to_parent(),
-            },
-            BSTree::Nil => self.continue_to_parent(),
+            }
+        }
+    }
+}
+
+#[cfg(feature = "viz")]
+impl<A: Ord + std::fmt::Debug> Visualize for BSTree<A> {
+    type Key = A;
+
+    fn view(&self, search: Option<&A>) -> Option<viz::Node> {
+        match self {
+            BSTree::Node { value, left, right } => {
+                let (l, r) = viz::descend(search, value);
+                Some(viz::Node {
+                    label: format!("{:?}", value),
+                    highlighted: search.is_some(),
+                    children: vec![left.view(l), right.view(r)],
+                })
+            }
+            BSTree::Nil => None,
         }
     }
 }
@@ -222,6 +380,7 @@ mod test {
     use std::collections::HashSet;
 
     use super::BSTree;
+    use crate::{from_sorted::FromSorted, seek::seeks_match};
     use quickcheck::quickcheck;
 
     #[test]
@@ -239,7 +398,7 @@ mod test {
         tree.insert(3);
         tree.insert(4);
         assert!(tree.search(3).is_some());
-        assert_eq!(tree.remove(4), true);
+        assert!(tree.remove(4));
     }
 
     #[test]
@@ -283,6 +442,20 @@ mod test {
         assert_eq!(iter.next(), None);
     }
 
+    #[test]
+    fn prop_seek() {
+        fn p(input: HashSet<i8>, ops: Vec<Option<i8>>) -> bool {
+            let mut tree = BSTree::new();
+            for &i in input.iter() {
+                tree.insert(i);
+            }
+            let mut keys = input.into_iter().collect::<Vec<_>>();
+            keys.sort();
+            seeks_match(tree.iter(), &keys, &ops)
+        }
+        quickcheck(p as fn(HashSet<i8>, Vec<Option<i8>>) -> bool)
+    }
+
     #[test]
     fn prop_iter_ascending_order() {
         fn p(input: Vec<i32>) -> bool {
@@ -303,6 +476,57 @@ mod test {
         quickcheck(p as fn(Vec<i32>) -> bool)
     }
 
+    #[test]
+    fn from_sorted() {
+        let tree = BSTree::from_sorted_iter(0..100);
+        assert!(tree.iter().copied().eq(0..100));
+        assert_eq!(tree.height(), 7);
+    }
+
+    #[test]
+    fn iter_degenerate_tree() {
+        // Sorted insertions make a tree deeper than the inline stack, which spills onto the heap
+        let mut tree = BSTree::new();
+        for i in (0..200).rev() {
+            tree.insert(i);
+        }
+        assert!(tree.iter().copied().eq(0..200));
+    }
+
+    #[test]
+    fn destructure() {
+        let mut tree = BSTree::new();
+        tree.insert(2);
+        tree.insert(1);
+        let BSTree::Node { value, left, .. } = tree else {
+            panic!("empty tree");
+        };
+        assert_eq!(value, 2);
+        assert_eq!(left.into_inner().value(), Some(&1));
+    }
+
+    #[test]
+    fn deep_tree() {
+        // A chain a million nodes deep would overflow the stack if any of these recursed
+        let n = 1_000_000;
+        let mut tree = BSTree::new();
+        for i in 0..n {
+            tree = BSTree::Node {
+                value: i,
+                left: tree.into(),
+                right: BSTree::Nil.into(),
+            };
+        }
+        let mut copy = tree.clone();
+        assert_eq!(copy.height(), n);
+        assert_eq!(copy.max_depth(), n);
+        assert_eq!(copy.size(), n);
+        assert!(copy.search(0).is_some());
+        assert!(copy.remove(0));
+        assert!(!copy.insert(0));
+        assert!(copy.iter().copied().eq(tree.iter().copied()));
+    }
+
     #[test]
     fn prop_tree_size() {
         // HashSet because the tree only stores unique values
@@ -0,0 +1,80 @@
+//! Budgets for the bounded structures, so that they report how much room is left in the same
+//! way and producers can apply backpressure without knowing which structure they feed.
+
+/// How much a bounded structure may hold, either counted in entries or weighed in bytes.
+/// A byte budget weighs each entry with a function supplied by the structure's owner.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Capacity {
+    /// At most this many entries.
+    Entries(usize),
+    /// Entries whose weights sum to at most this many bytes.
+    Bytes(usize),
+}
+
+impl Capacity {
+    /// A budget which never runs out.
+    pub const UNBOUNDED: Capacity = Capacity::Entries(usize::MAX);
+
+    /// The size of the budget, in entries or bytes.
+    pub fn limit(self) -> usize {
+        match self {
+            Capacity::Entries(limit) | Capacity::Bytes(limit) => limit,
+        }
+    }
+
+    /// How much an entry counts against the budget: one for an entry budget, and otherwise
+    /// the weight `weigh` measures, which is only called for a byte budget.
+    pub fn weigh(self, weigh: impl FnOnce() -> usize) -> usize {
+        match self {
+            Capacity::Entries(_) => 1,
+            Capacity::Bytes(_) => weigh(),
+        }
+    }
+
+    /// How much is left once `used` has been spent, or zero if the budget is overspent.
+    pub fn remaining(self, used: usize) -> usize {
+        self.limit().saturating_sub(used)
+    }
+
+    /// Whether `weight` more fits once `used` has been spent. A total which overflows
+    /// doesn't fit.
+    pub fn fits(self, used: usize, weight: usize) -> bool {
+        self.checked_spend(used, weight).is_some()
+    }
+
+    /// Spends `weight` on top of `used`, returning the new total, or `None` if it would
+    /// exceed the budget.
+    pub fn checked_spend(self, used: usize, weight: usize) -> Option<usize> {
+        used.checked_add(weight)
+            .filter(|&total| total <= self.limit())
+    }
+}
+
+impl From<usize> for Capacity {
+    /// A budget of this many entries.
+    fn from(entries: usize) -> Self {
+        Capacity::Entries(entries)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Capacity;
+
+    #[test]
+    fn arithmetic() {
+        let entries = Capacity::from(3);
+        assert_eq!(entries.weigh(|| unreachable!()), 1);
+        assert_eq!(entries.remaining(1), 2);
+        assert_eq!(entries.remaining(5), 0);
+        assert_eq!(entries.checked_spend(2, 1), Some(3));
+        assert!(!entries.fits(3, 1));
+
+        let bytes = Capacity::Bytes(usize::MAX - 1);
+        assert_eq!(bytes.weigh(|| 40), 40);
+        assert!(bytes.fits(usize::MAX - 2, 1));
+        assert!(!bytes.fits(usize::MAX - 1, 1));
+        assert!(!bytes.fits(2, usize::MAX));
+        assert!(Capacity::UNBOUNDED.fits(usize::MAX - 1, 1));
+    }
+}
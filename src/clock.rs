@@ -0,0 +1,93 @@
+//! Sources of time for the time-based structures, so that tests can drive them by advancing
+//! a mock clock rather than by sleeping.
+
+use std::{
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// A source of the current time, and of waiting for it to pass.
+pub trait Clock {
+    fn now(&self) -> Instant;
+
+    /// Waits until `duration` has passed on this clock.
+    fn sleep(&self, duration: Duration) {
+        thread::sleep(duration);
+    }
+}
+
+/// The system's monotonic clock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// A clock which only moves when it is advanced. Clones share the same time, so a test can
+/// keep one and advance the one it handed to a structure. Sleeping advances the clock
+/// instead of blocking.
+#[derive(Debug, Clone)]
+pub struct MockClock {
+    now: Arc<Mutex<Instant>>,
+}
+
+impl MockClock {
+    /// Creates a clock stopped at the current time.
+    pub fn new() -> Self {
+        MockClock {
+            now: Arc::new(Mutex::new(Instant::now())),
+        }
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        *self.now.lock().unwrap() += duration;
+    }
+}
+
+impl Default for MockClock {
+    fn default() -> Self {
+        MockClock::new()
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Instant {
+        *self.now.lock().unwrap()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        self.advance(duration);
+    }
+}
+
+impl<C: Clock + ?Sized> Clock for &C {
+    fn now(&self) -> Instant {
+        (**self).now()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        (**self).sleep(duration);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::{Clock, MockClock};
+
+    #[test]
+    fn mock_clones_share_time() {
+        let clock = MockClock::new();
+        let shared = clock.clone();
+        let start = clock.now();
+        shared.advance(Duration::from_secs(5));
+        assert_eq!(clock.now(), start + Duration::from_secs(5));
+        clock.sleep(Duration::from_secs(1));
+        assert_eq!(shared.now(), start + Duration::from_secs(6));
+    }
+}
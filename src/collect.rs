@@ -0,0 +1,49 @@
+//! Aggregations of iterators into the crate's maps.
+
+use std::hash::Hash;
+
+use crate::{multi_map::MultiMap, multi_set::Counter};
+
+/// Groups the items under the key `key` computes for each. Within a group the items keep
+/// the order they came in. Collect pairs into a `MultiMap` directly for another hasher.
+pub fn group_by<I, K, F>(iter: I, mut key: F) -> MultiMap<K, I::Item>
+where
+    I: IntoIterator,
+    K: Eq + Hash,
+    F: FnMut(&I::Item) -> K,
+{
+    iter.into_iter().map(|item| (key(&item), item)).collect()
+}
+
+/// Counts how many times each item occurs.
+pub fn counts<I>(iter: I) -> Counter<I::Item>
+where
+    I: IntoIterator,
+    I::Item: Eq + Hash,
+{
+    iter.into_iter().collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{counts, group_by};
+
+    #[test]
+    fn groups_in_order() {
+        let words = ["apple", "bean", "avocado", "beet", "cherry"];
+        let groups = group_by(words, |word| word.as_bytes()[0]);
+        assert_eq!(groups.key_count(), 3);
+        assert_eq!(groups.get(&b'a'), &["apple", "avocado"]);
+        assert_eq!(groups.get(&b'b'), &["bean", "beet"]);
+        assert!(group_by(Vec::<u8>::new(), |&x| x).is_empty());
+    }
+
+    #[test]
+    fn counts_occurrences() {
+        let counter = counts("mississippi".chars());
+        assert_eq!(counter.len(), 11);
+        assert_eq!(counter.count(&'s'), 4);
+        assert_eq!(counter.count(&'p'), 2);
+        assert_eq!(counter.distinct_len(), 4);
+    }
+}
@@ -0,0 +1,462 @@
+use std::io;
+
+use crate::{
+    from_sorted::FromSorted,
+    layout::{Buf, LoadError, Reader, Writer},
+    seek::Seekable,
+    sort::radix_sort_by_key,
+};
+
+const WORD_BITS: usize = u64::BITS as usize;
+// How many ones or zeros of the high bits lie between consecutive select samples
+const SAMPLE_RATE: usize = 256;
+
+/// An immutable set of `u64`s compressed with Elias-Fano encoding, taking about
+/// `2 + log2(max / len)` bits per element.
+/// Each key is split into low bits, which are packed into an array, and high bits, which
+/// are stored in unary as gaps between ones in a bit vector. Sampled positions of the ones
+/// and zeros make `select`, `rank` and `contains` close to constant time.
+/// The set can be written with `write_to` and loaded back with `load`, which borrows the
+/// arrays from the buffer for the lifetime `'a`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StaticSortedSetView<'a> {
+    low: Buf<'a, u64>,
+    low_bits: usize,
+    high: Buf<'a, u64>,
+    // The word index and number of ones before it for every `SAMPLE_RATE`th one
+    one_samples: Buf<'a, [u64; 2]>,
+    // Likewise for every `SAMPLE_RATE`th zero
+    zero_samples: Buf<'a, [u64; 2]>,
+    // The number of buckets of keys sharing their high bits
+    buckets: u64,
+    len: usize,
+}
+
+/// A `StaticSortedSetView` which owns its arrays, as every set does unless it was loaded.
+pub type StaticSortedSet = StaticSortedSetView<'static>;
+
+impl<'a> StaticSortedSetView<'a> {
+    /// Builds the set from strictly ascending keys.
+    /// Panics if the keys are not strictly ascending.
+    pub fn from_sorted(keys: &[u64]) -> Self {
+        assert!(
+            keys.windows(2).all(|w| w[0] < w[1]),
+            "keys must be strictly ascending"
+        );
+        let len = keys.len();
+        let max = keys.last().copied().unwrap_or(0);
+        let low_bits = match max / (len.max(1) as u64) {
+            0 => 0,
+            ratio => ratio.ilog2() as usize,
+        };
+        let buckets = (max >> low_bits) + 1;
+        let high_len = len + buckets as usize;
+        let mut low = vec![0; (len * low_bits).div_ceil(WORD_BITS)];
+        let mut high = vec![0; high_len.div_ceil(WORD_BITS)];
+        for (i, &key) in keys.iter().enumerate() {
+            set_low(&mut low, low_bits, i, key);
+            // The ith key's one follows the zeros ending each bucket before its own
+            let pos = (key >> low_bits) as usize + i;
+            high[pos / WORD_BITS] |= 1 << (pos % WORD_BITS);
+        }
+        StaticSortedSetView {
+            low: low.into(),
+            low_bits,
+            one_samples: samples(&high, |w| w).into(),
+            zero_samples: samples(&high, |w| !w).into(),
+            high: high.into(),
+            buckets,
+            len,
+        }
+    }
+
+    /// Writes the set in the format described in `layout`.
+    pub fn write_to<W: io::Write>(&self, out: W) -> io::Result<()> {
+        let mut writer = Writer::new(out, *b"EFSS")?;
+        writer.word(self.len as u64)?;
+        writer.word(self.low_bits as u64)?;
+        writer.word(self.buckets)?;
+        writer.array(&self.low)?;
+        writer.array(&self.high)?;
+        writer.array(&self.one_samples)?;
+        writer.array(&self.zero_samples)
+    }
+
+    /// Loads a set written by `write_to`, borrowing its arrays from `bytes` where possible.
+    pub fn load(bytes: &'a [u8]) -> Result<Self, LoadError> {
+        let mut reader = Reader::new(bytes, *b"EFSS")?;
+        let len = reader.size()?;
+        let low_bits = reader.size()?;
+        let buckets = reader.word()?;
+        let set = StaticSortedSetView {
+            low: reader.array()?,
+            low_bits,
+            high: reader.array()?,
+            one_samples: reader.array()?,
+            zero_samples: reader.array()?,
+            buckets,
+            len,
+        };
+        if low_bits >= WORD_BITS {
+            return Err(LoadError::Corrupt("too many low bits"));
+        }
+        let low_len = len
+            .checked_mul(low_bits)
+            .map(|bits| bits.div_ceil(WORD_BITS));
+        let high_len = usize::try_from(buckets)
+            .ok()
+            .and_then(|buckets| buckets.checked_add(len))
+            .map(|bits| bits.div_ceil(WORD_BITS));
+        if low_len != Some(set.low.len()) || high_len != Some(set.high.len()) {
+            return Err(LoadError::Corrupt("bit vectors don't match the length"));
+        }
+        let ones = set
+            .high
+            .iter()
+            .map(|w| w.count_ones() as usize)
+            .sum::<usize>();
+        if ones != len || set.one_samples.len() != len.div_ceil(SAMPLE_RATE) {
+            return Err(LoadError::Corrupt("samples don't match the length"));
+        }
+        Ok(set)
+    }
+
+    /// Copies any arrays borrowed from a loaded buffer, so the set can outlive it.
+    pub fn into_owned(self) -> StaticSortedSet {
+        StaticSortedSetView {
+            low: self.low.into_owned(),
+            high: self.high.into_owned(),
+            one_samples: self.one_samples.into_owned(),
+            zero_samples: self.zero_samples.into_owned(),
+            ..self
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The approximate heap memory used by the set.
+    pub fn size_in_bytes(&self) -> usize {
+        (self.low.len() + self.high.len()) * std::mem::size_of::<u64>()
+            + (self.one_samples.len() + self.zero_samples.len()) * std::mem::size_of::<[u64; 2]>()
+    }
+
+    /// Returns the `i`th smallest key.
+    pub fn select(&self, i: usize) -> Option<u64> {
+        if i >= self.len {
+            return None;
+        }
+        let pos = select(&self.high, &self.one_samples, i, |w| w);
+        Some((((pos - i) as u64) << self.low_bits) | self.low(i))
+    }
+
+    /// Returns the number of keys less than `key`.
+    pub fn rank(&self, key: u64) -> usize {
+        self.find(key).0
+    }
+
+    pub fn contains(&self, key: u64) -> bool {
+        self.find(key).1
+    }
+
+    /// Returns the keys in ascending order.
+    pub fn iter(&self) -> Iter<'_> {
+        Iter {
+            set: self,
+            word: 0,
+            current: self.high.first().copied().unwrap_or(0),
+            index: 0,
+        }
+    }
+
+    /// Returns the number of keys less than `key` and whether `key` is present.
+    fn find(&self, key: u64) -> (usize, bool) {
+        let bucket = key >> self.low_bits;
+        if bucket >= self.buckets {
+            return (self.len, false);
+        }
+        // The bucket starts just after the zero which ends the previous one
+        let mut pos = match bucket {
+            0 => 0,
+            b => select(&self.high, &self.zero_samples, b as usize - 1, |w| !w) + 1,
+        };
+        let mut index = pos - bucket as usize;
+        let low = key & self.low_mask();
+        while self.high_bit(pos) {
+            let l = self.low(index);
+            if l >= low {
+                return (index, l == low);
+            }
+            index += 1;
+            pos += 1;
+        }
+        (index, false)
+    }
+
+    fn high_bit(&self, pos: usize) -> bool {
+        self.high
+            .get(pos / WORD_BITS)
+            .is_some_and(|w| w & (1 << (pos % WORD_BITS)) != 0)
+    }
+
+    fn low_mask(&self) -> u64 {
+        (1 << self.low_bits) - 1
+    }
+
+    fn low(&self, i: usize) -> u64 {
+        if self.low_bits == 0 {
+            return 0;
+        }
+        let pos = i * self.low_bits;
+        let (word, bit) = (pos / WORD_BITS, pos % WORD_BITS);
+        let mut value = self.low[word] >> bit;
+        if bit + self.low_bits > WORD_BITS {
+            value |= self.low[word + 1] << (WORD_BITS - bit);
+        }
+        value & self.low_mask()
+    }
+}
+
+/// Packs the low bits of the `i`th key into `low`.
+fn set_low(low: &mut [u64], low_bits: usize, i: usize, key: u64) {
+    if low_bits == 0 {
+        return;
+    }
+    let value = key & ((1 << low_bits) - 1);
+    let pos = i * low_bits;
+    let (word, bit) = (pos / WORD_BITS, pos % WORD_BITS);
+    low[word] |= value << bit;
+    if bit + low_bits > WORD_BITS {
+        low[word + 1] |= value >> (WORD_BITS - bit);
+    }
+}
+
+/// Records the word containing every `SAMPLE_RATE`th bit counted by `f`.
+fn samples(words: &[u64], f: impl Fn(u64) -> u64) -> Vec<[u64; 2]> {
+    let mut samples = vec![];
+    let mut before = 0;
+    for (i, &w) in words.iter().enumerate() {
+        let count = f(w).count_ones() as usize;
+        while samples.len() * SAMPLE_RATE < before + count {
+            samples.push([i as u64, before as u64]);
+        }
+        before += count;
+    }
+    samples
+}
+
+/// Returns the position of the `k`th bit counted by `f`, which must exist.
+fn select(words: &[u64], samples: &[[u64; 2]], k: usize, f: impl Fn(u64) -> u64) -> usize {
+    let [i, before] = samples[k / SAMPLE_RATE];
+    let (mut i, mut before) = (i as usize, before as usize);
+    loop {
+        let mut word = f(words[i]);
+        let count = word.count_ones() as usize;
+        if before + count > k {
+            for _ in 0..k - before {
+                // Clear the lowest set bit
+                word &= word - 1;
+            }
+            return i * WORD_BITS + word.trailing_zeros() as usize;
+        }
+        before += count;
+        i += 1;
+    }
+}
+
+impl FromIterator<u64> for StaticSortedSetView<'_> {
+    fn from_iter<I: IntoIterator<Item = u64>>(iter: I) -> Self {
+        let mut keys = iter.into_iter().collect::<Vec<_>>();
+        radix_sort_by_key(&mut keys, |&k| k);
+        keys.dedup();
+        StaticSortedSet::from_sorted(&keys)
+    }
+}
+
+impl FromSorted<u64> for StaticSortedSetView<'_> {
+    fn from_sorted_iter<I: IntoIterator<Item = u64>>(iter: I) -> Self {
+        StaticSortedSet::from_sorted(&iter.into_iter().collect::<Vec<_>>())
+    }
+}
+
+impl<'a> IntoIterator for &'a StaticSortedSetView<'_> {
+    type Item = u64;
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// An iterator over the keys of a `StaticSortedSet` in ascending order.
+#[derive(Debug, Clone)]
+pub struct Iter<'a> {
+    set: &'a StaticSortedSetView<'a>,
+    word: usize,
+    // The remaining ones of the current high word
+    current: u64,
+    index: usize,
+}
+
+impl Iterator for Iter<'_> {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        if self.index >= self.set.len {
+            return None;
+        }
+        while self.current == 0 {
+            self.word += 1;
+            self.current = self.set.high[self.word];
+        }
+        let pos = self.word * WORD_BITS + self.current.trailing_zeros() as usize;
+        self.current &= self.current - 1;
+        let high = (pos - self.index) as u64;
+        let key = (high << self.set.low_bits) | self.set.low(self.index);
+        self.index += 1;
+        Some(key)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.set.len - self.index;
+        (remaining, Some(remaining))
+    }
+}
+
+impl ExactSizeIterator for Iter<'_> {}
+
+impl Seekable for Iter<'_> {
+    type Key = u64;
+
+    fn key_of(item: &u64) -> &u64 {
+        item
+    }
+
+    fn seek(&mut self, key: &u64) {
+        let index = self.set.rank(*key);
+        if index <= self.index {
+            return;
+        }
+        self.index = index;
+        if index < self.set.len {
+            // Resume from the key's one, dropping the ones before it in its word
+            let pos = select(&self.set.high, &self.set.one_samples, index, |w| w);
+            self.word = pos / WORD_BITS;
+            self.current = self.set.high[self.word] & (u64::MAX << (pos % WORD_BITS));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeSet;
+
+    use quickcheck::quickcheck;
+
+    use super::{StaticSortedSet, StaticSortedSetView};
+    use crate::{layout::LoadError, seek::seeks_match};
+
+    #[test]
+    fn lookups() {
+        let set = StaticSortedSet::from_sorted(&[2, 3, 5, 7, 11, 13, 24]);
+        assert_eq!(set.len(), 7);
+        assert!(set.contains(11));
+        assert!(!set.contains(12));
+        assert!(!set.contains(100));
+        assert_eq!(set.rank(0), 0);
+        assert_eq!(set.rank(12), 5);
+        assert_eq!(set.rank(100), 7);
+        assert_eq!(set.select(6), Some(24));
+        assert_eq!(set.select(7), None);
+        assert_eq!(set.iter().collect::<Vec<_>>(), vec![2, 3, 5, 7, 11, 13, 24]);
+    }
+
+    #[test]
+    fn extremes() {
+        let set = [0, u64::MAX].into_iter().collect::<StaticSortedSet>();
+        assert!(set.contains(0) && set.contains(u64::MAX));
+        assert_eq!(set.rank(u64::MAX), 1);
+        let empty = StaticSortedSet::from_sorted(&[]);
+        assert!(!empty.contains(0));
+        assert_eq!(empty.iter().next(), None);
+    }
+
+    #[test]
+    #[should_panic]
+    fn unsorted() {
+        StaticSortedSet::from_sorted(&[2, 1]);
+    }
+
+    #[test]
+    fn compresses() {
+        let keys = (0..100_000u64)
+            .map(|i| i * 1000 + i % 7)
+            .collect::<Vec<_>>();
+        let set = StaticSortedSet::from_sorted(&keys);
+        // Around 12 bits per key rather than 64
+        assert!(set.size_in_bytes() * 4 < keys.len() * 8);
+        assert!(set.iter().eq(keys.iter().copied()));
+        assert_eq!(set.select(54321), Some(keys[54321]));
+    }
+
+    #[test]
+    fn write_and_load() {
+        let keys = (0..10_000u64).map(|i| i * i).collect::<Vec<_>>();
+        let set = StaticSortedSet::from_sorted(&keys);
+        let mut bytes = vec![];
+        set.write_to(&mut bytes).unwrap();
+        let loaded = StaticSortedSetView::load(&bytes).unwrap();
+        assert_eq!(loaded, set);
+        assert_eq!(loaded.select(9999), Some(9999 * 9999));
+        assert_eq!(loaded.rank(50), 8);
+        assert!(loaded.iter().eq(keys));
+        let owned: StaticSortedSet = loaded.into_owned();
+        assert_eq!(owned, set);
+        // The length is the first word after the header
+        let mut corrupt = bytes.clone();
+        corrupt[16] ^= 1;
+        assert!(matches!(
+            StaticSortedSetView::load(&corrupt),
+            Err(LoadError::Corrupt(_))
+        ));
+    }
+
+    #[test]
+    fn prop_matches_btree_set() {
+        fn p(keys: BTreeSet<u64>, probes: Vec<u64>, scale: u8) -> bool {
+            // Shrink the keys to vary the density of the set
+            let keys = keys
+                .into_iter()
+                .map(|k| k >> (scale % 64))
+                .collect::<BTreeSet<_>>();
+            let set = keys.iter().copied().collect::<StaticSortedSet>();
+            set.iter().eq(keys.iter().copied())
+                && keys
+                    .iter()
+                    .enumerate()
+                    .all(|(i, &k)| set.select(i) == Some(k) && set.rank(k) == i)
+                && probes.into_iter().map(|k| k >> (scale % 64)).all(|k| {
+                    set.contains(k) == keys.contains(&k) && set.rank(k) == keys.range(..k).count()
+                })
+        }
+        quickcheck(p as fn(BTreeSet<u64>, Vec<u64>, u8) -> bool)
+    }
+
+    #[test]
+    fn prop_seek() {
+        fn p(keys: BTreeSet<u64>, ops: Vec<Option<u64>>, scale: u8) -> bool {
+            let shrink = |k: u64| k >> (scale % 64);
+            let keys = keys.into_iter().map(shrink).collect::<BTreeSet<_>>();
+            let set = keys.iter().copied().collect::<StaticSortedSet>();
+            let keys = keys.into_iter().collect::<Vec<_>>();
+            let ops = ops.into_iter().map(|op| op.map(shrink)).collect::<Vec<_>>();
+            seeks_match(set.iter(), &keys, &ops)
+        }
+        quickcheck(p as fn(BTreeSet<u64>, Vec<Option<u64>>, u8) -> bool)
+    }
+}
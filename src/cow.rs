@@ -0,0 +1,120 @@
+use std::{ops::Deref, sync::Arc};
+
+/// A copy-on-write wrapper around any cloneable collection.
+/// Cloning the wrapper only bumps a reference count, so snapshots are cheap and can be
+/// shared between threads. The collection is copied on the first mutation made through
+/// a wrapper whose contents are shared, leaving every other snapshot unchanged.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct CowMap<M> {
+    inner: Arc<M>,
+}
+
+impl<M> CowMap<M> {
+    pub fn new(map: M) -> Self {
+        CowMap {
+            inner: Arc::new(map),
+        }
+    }
+
+    /// Returns a snapshot which is unaffected by later mutations of either copy.
+    pub fn snapshot(&self) -> Self {
+        CowMap {
+            inner: Arc::clone(&self.inner),
+        }
+    }
+
+    /// Whether other snapshots share these contents, so the next mutation will copy them.
+    pub fn is_shared(&self) -> bool {
+        Arc::strong_count(&self.inner) > 1
+    }
+
+    /// Whether both wrappers share the same contents.
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+    }
+}
+
+impl<M: Clone> CowMap<M> {
+    /// Returns a mutable reference to the collection, copying it first if it is shared.
+    pub fn make_mut(&mut self) -> &mut M {
+        Arc::make_mut(&mut self.inner)
+    }
+
+    /// Applies `f` to the collection, copying it first if it is shared.
+    /// Use this to batch several mutations.
+    pub fn update<F, R>(&mut self, f: F) -> R
+    where
+        F: FnOnce(&mut M) -> R,
+    {
+        f(self.make_mut())
+    }
+
+    /// Returns the collection, copying it if it is shared.
+    pub fn into_inner(self) -> M {
+        Arc::unwrap_or_clone(self.inner)
+    }
+}
+
+impl<M> Clone for CowMap<M> {
+    fn clone(&self) -> Self {
+        self.snapshot()
+    }
+}
+
+impl<M> Deref for CowMap<M> {
+    type Target = M;
+
+    fn deref(&self) -> &M {
+        &self.inner
+    }
+}
+
+impl<M> From<M> for CowMap<M> {
+    fn from(map: M) -> Self {
+        CowMap::new(map)
+    }
+}
+
+impl<M, T> FromIterator<T> for CowMap<M>
+where
+    M: FromIterator<T>,
+{
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        CowMap::new(iter.into_iter().collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        collections::{BTreeMap, HashMap},
+        thread,
+    };
+
+    use super::CowMap;
+
+    #[test]
+    fn copies_on_write() {
+        let mut map = (0..3).map(|i| (i, i)).collect::<CowMap<HashMap<_, _>>>();
+        assert!(!map.is_shared());
+        let snapshot = map.snapshot();
+        assert!(map.ptr_eq(&snapshot));
+        map.make_mut().insert(3, 3);
+        assert!(!map.ptr_eq(&snapshot) && !snapshot.is_shared());
+        assert_eq!(map.len(), 4);
+        assert_eq!(snapshot.len(), 3);
+        // Unshared contents are mutated in place
+        let ptr = &*map as *const _;
+        map.update(|m| m.insert(4, 4));
+        assert_eq!(ptr, &*map as *const _);
+    }
+
+    #[test]
+    fn shared_between_threads() {
+        let map = CowMap::new(BTreeMap::from([(1, "one")]));
+        let snapshot = map.snapshot();
+        let handle = thread::spawn(move || snapshot.get(&1).copied());
+        assert_eq!(handle.join().unwrap(), Some("one"));
+        assert_eq!(map.into_inner().get(&1), Some(&"one"));
+    }
+}
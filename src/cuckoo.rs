@@ -0,0 +1,235 @@
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hash},
+    marker::PhantomData,
+};
+
+const BUCKET_SIZE: usize = 4;
+const MAX_KICKS: usize = 500;
+const EMPTY: u8 = 0;
+
+/// A cuckoo filter is a probabilistic set which, unlike a Bloom filter, supports removal.
+/// Each item is reduced to an 8-bit fingerprint stored in one of two candidate buckets,
+/// so `contains` may report false positives (roughly 3% at full load) but never false negatives.
+/// Only items which were previously inserted should be removed.
+#[derive(Debug, Clone)]
+pub struct CuckooFilter<T: ?Sized, S = RandomState> {
+    buckets: Vec<[u8; BUCKET_SIZE]>,
+    // A fingerprint which was evicted and could not be placed after a failed insertion
+    victim: Option<(usize, u8)>,
+    len: usize,
+    hasher: S,
+    rng: u64,
+    _marker: PhantomData<fn(&T)>,
+}
+
+impl<T: ?Sized> CuckooFilter<T> {
+    /// Creates a filter with room for at least `capacity` items.
+    pub fn with_capacity(capacity: usize) -> Self {
+        CuckooFilter::with_capacity_and_hasher(capacity, RandomState::new())
+    }
+}
+
+impl<T: ?Sized, S> CuckooFilter<T, S> {
+    pub fn with_capacity_and_hasher(capacity: usize, hasher: S) -> Self {
+        let buckets = capacity.div_ceil(BUCKET_SIZE).next_power_of_two().max(1);
+        CuckooFilter {
+            buckets: vec![[EMPTY; BUCKET_SIZE]; buckets],
+            victim: None,
+            len: 0,
+            hasher,
+            rng: 0x2545_f491_4f6c_dd1d,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.buckets.len() * BUCKET_SIZE
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn alt_index(&self, index: usize, fingerprint: u8) -> usize {
+        // Setting the low bit guarantees the two buckets differ whenever there is more than one
+        let mixed = (fingerprint as u64).wrapping_mul(0x5bd1_e995) | 1;
+        (index ^ mixed as usize) & (self.buckets.len() - 1)
+    }
+
+    fn next_random(&mut self) -> usize {
+        // xorshift64
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        self.rng as usize
+    }
+
+    fn try_place(&mut self, index: usize, fingerprint: u8) -> bool {
+        match self.buckets[index].iter_mut().find(|f| **f == EMPTY) {
+            Some(slot) => {
+                *slot = fingerprint;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl<T, S> CuckooFilter<T, S>
+where
+    T: Hash + ?Sized,
+    S: BuildHasher,
+{
+    /// Returns the fingerprint of the item and its primary bucket.
+    fn locate(&self, item: &T) -> (usize, u8) {
+        let hash = self.hasher.hash_one(item);
+        let fingerprint = match (hash >> 56) as u8 {
+            EMPTY => 1,
+            f => f,
+        };
+        let index = hash as usize & (self.buckets.len() - 1);
+        (index, fingerprint)
+    }
+
+    /// Adds the item to the filter.
+    /// Returns false if the filter is too full to accept it.
+    pub fn insert(&mut self, item: &T) -> bool {
+        if self.victim.is_some() {
+            return false;
+        }
+        let (i1, fingerprint) = self.locate(item);
+        let i2 = self.alt_index(i1, fingerprint);
+        if self.try_place(i1, fingerprint) || self.try_place(i2, fingerprint) {
+            self.len += 1;
+            return true;
+        }
+
+        // Both buckets are full, so relocate existing fingerprints to their alternate buckets
+        let mut index = if self.next_random().is_multiple_of(2) {
+            i1
+        } else {
+            i2
+        };
+        let mut fingerprint = fingerprint;
+        for _ in 0..MAX_KICKS {
+            let slot = self.next_random() % BUCKET_SIZE;
+            std::mem::swap(&mut fingerprint, &mut self.buckets[index][slot]);
+            index = self.alt_index(index, fingerprint);
+            if self.try_place(index, fingerprint) {
+                self.len += 1;
+                return true;
+            }
+        }
+        // The item itself has been stored, but the last evicted fingerprint has no home
+        self.victim = Some((index, fingerprint));
+        self.len += 1;
+        true
+    }
+
+    pub fn contains(&self, item: &T) -> bool {
+        let (i1, fingerprint) = self.locate(item);
+        let i2 = self.alt_index(i1, fingerprint);
+        self.buckets[i1].contains(&fingerprint)
+            || self.buckets[i2].contains(&fingerprint)
+            || self
+                .victim
+                .is_some_and(|(i, f)| f == fingerprint && (i == i1 || i == i2))
+    }
+
+    /// Removes one copy of the item from the filter, returning whether it was found.
+    pub fn remove(&mut self, item: &T) -> bool {
+        let (i1, fingerprint) = self.locate(item);
+        let i2 = self.alt_index(i1, fingerprint);
+        if let Some((i, f)) = self.victim {
+            if f == fingerprint && (i == i1 || i == i2) {
+                self.victim = None;
+                self.len -= 1;
+                return true;
+            }
+        }
+        for index in [i1, i2] {
+            if let Some(slot) = self.buckets[index].iter_mut().find(|f| **f == fingerprint) {
+                *slot = EMPTY;
+                self.len -= 1;
+                // Now that there is room, try to find a home for the victim again
+                if let Some((i, f)) = self.victim.take() {
+                    if !self.try_place(i, f) {
+                        self.victim = Some((i, f));
+                    }
+                }
+                return true;
+            }
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        collections::{hash_map::DefaultHasher, HashSet},
+        hash::BuildHasherDefault,
+    };
+
+    use quickcheck::quickcheck;
+
+    use super::CuckooFilter;
+
+    #[test]
+    fn insert_and_contains() {
+        let mut filter = CuckooFilter::with_capacity(100);
+        assert!(filter.insert("foo"));
+        assert!(filter.contains("foo"));
+        assert_eq!(filter.len(), 1);
+    }
+
+    #[test]
+    fn remove() {
+        // A fixed hasher keeps the two fingerprints from colliding by chance
+        let mut filter = CuckooFilter::with_capacity_and_hasher(
+            100,
+            BuildHasherDefault::<DefaultHasher>::default(),
+        );
+        filter.insert(&1);
+        filter.insert(&2);
+        assert!(filter.remove(&1));
+        assert!(!filter.contains(&1));
+        assert!(filter.contains(&2));
+        assert_eq!(filter.len(), 1);
+    }
+
+    #[test]
+    fn fills_up() {
+        let mut filter = CuckooFilter::with_capacity(16);
+        let inserted = (0..1000).take_while(|i| filter.insert(i)).count();
+        assert!(inserted >= 8 && inserted <= filter.capacity() + 1);
+        assert!(!filter.insert(&1000));
+    }
+
+    #[test]
+    fn false_positive_rate() {
+        let mut filter = CuckooFilter::with_capacity(10_000);
+        for i in 0..8000 {
+            assert!(filter.insert(&i));
+        }
+        let false_positives = (8000..18000).filter(|i| filter.contains(i)).count();
+        assert!(false_positives < 500, "{} false positives", false_positives);
+    }
+
+    #[test]
+    fn prop_no_false_negatives() {
+        fn p(input: HashSet<u32>) -> bool {
+            let mut filter = CuckooFilter::with_capacity(input.len() * 2);
+            for i in input.iter() {
+                filter.insert(i);
+            }
+            input.iter().all(|i| filter.contains(i))
+        }
+        quickcheck(p as fn(HashSet<u32>) -> bool)
+    }
+}
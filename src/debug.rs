@@ -0,0 +1,8 @@
+//! Formatting of a collection's internal layout.
+//! The collections' own `Debug` impls print only their logical contents, so that output
+//! doesn't change when the internals do. Wrapping one in `DebugStructure` prints its
+//! nodes instead, which helps when debugging the structure itself.
+
+/// Formats the internal layout of the wrapped collection, e.g. `{:#?}` of
+/// `DebugStructure(&tree)` prints every node of an `AVLTree` along with its height.
+pub struct DebugStructure<'a, T: ?Sized>(pub &'a T);
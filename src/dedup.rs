@@ -0,0 +1,202 @@
+use std::{
+    borrow::Borrow,
+    collections::{hash_map::RandomState, HashMap},
+    hash::{BuildHasher, Hash},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    clock::{Clock, SystemClock},
+    index_list::{IndexList, IndexListHandle},
+};
+
+/// A set which remembers only recently inserted items, for de-duplicating streams.
+/// Items are forgotten once more than `capacity` newer items have been inserted,
+/// or once they are older than `max_age`, whichever limits are set.
+/// Seeing an item again does not extend how long it is remembered.
+/// Ages are measured with a `Clock`, which tests can replace with a `MockClock`.
+#[derive(Debug)]
+pub struct RecentSet<T, S = RandomState, C = SystemClock> {
+    seen: HashMap<T, Seen, S>,
+    // Items from newest at the head to oldest at the tail
+    order: IndexList<(T, Instant)>,
+    capacity: Option<usize>,
+    max_age: Option<Duration>,
+    clock: C,
+}
+
+#[derive(Debug)]
+struct Seen {
+    inserted: Instant,
+    handle: IndexListHandle,
+}
+
+impl<T> RecentSet<T> {
+    /// Creates a set which remembers the last `capacity` items.
+    pub fn new(capacity: usize) -> Self {
+        RecentSet::with_limits(Some(capacity), None, RandomState::new())
+    }
+
+    /// Creates a set which remembers the items inserted within the last `max_age`.
+    pub fn with_max_age(max_age: Duration) -> Self {
+        RecentSet::with_limits(None, Some(max_age), RandomState::new())
+    }
+}
+
+impl<T, S> RecentSet<T, S> {
+    pub fn with_limits(capacity: Option<usize>, max_age: Option<Duration>, hasher: S) -> Self {
+        RecentSet::with_limits_and_clock(capacity, max_age, hasher, SystemClock)
+    }
+}
+
+impl<T, S, C> RecentSet<T, S, C> {
+    pub fn with_limits_and_clock(
+        capacity: Option<usize>,
+        max_age: Option<Duration>,
+        hasher: S,
+        clock: C,
+    ) -> Self {
+        RecentSet {
+            seen: HashMap::with_hasher(hasher),
+            order: IndexList::new(),
+            capacity,
+            max_age,
+            clock,
+        }
+    }
+
+    /// The number of items remembered, including expired ones which have not been evicted yet.
+    pub fn len(&self) -> usize {
+        self.seen.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.seen.is_empty()
+    }
+}
+
+impl<T, S, C> RecentSet<T, S, C>
+where
+    T: Eq + Hash + Clone,
+    S: BuildHasher,
+    C: Clock,
+{
+    /// Inserts the item, returning false if it was already remembered.
+    pub fn insert_if_new(&mut self, t: T) -> bool {
+        let now = self.clock.now();
+        self.evict_expired(now);
+        if self.seen.contains_key(&t) {
+            return false;
+        }
+        let handle = self.order.push_head((t.clone(), now));
+        self.seen.insert(
+            t,
+            Seen {
+                inserted: now,
+                handle,
+            },
+        );
+        if self.capacity.is_some_and(|c| self.seen.len() > c) {
+            self.pop_oldest();
+        }
+        true
+    }
+
+    pub fn contains<Q>(&self, t: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.seen
+            .get(t)
+            .is_some_and(|seen| !self.is_expired(seen.inserted, self.clock.now()))
+    }
+
+    /// Forgets the item, returning whether it was remembered.
+    pub fn remove<Q>(&mut self, t: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        match self.seen.remove(t) {
+            Some(seen) => {
+                self.order.remove(seen.handle);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        while self.pop_oldest().is_some() {}
+    }
+
+    fn is_expired(&self, inserted: Instant, now: Instant) -> bool {
+        self.max_age
+            .is_some_and(|age| now.duration_since(inserted) > age)
+    }
+
+    fn evict_expired(&mut self, now: Instant) {
+        while self
+            .order
+            .tail()
+            .is_some_and(|(_, inserted)| self.is_expired(*inserted, now))
+        {
+            self.pop_oldest();
+        }
+    }
+
+    fn pop_oldest(&mut self) -> Option<T> {
+        let (t, _) = self.order.pop_tail()?;
+        self.seen.remove(&t);
+        Some(t)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{collections::hash_map::RandomState, thread, time::Duration};
+
+    use super::RecentSet;
+    use crate::clock::MockClock;
+
+    #[test]
+    fn remembers_last_n() {
+        let mut set = RecentSet::new(2);
+        assert!(set.insert_if_new(1));
+        assert!(!set.insert_if_new(1));
+        assert!(set.insert_if_new(2));
+        assert!(set.insert_if_new(3));
+        assert!(!set.contains(&1));
+        assert!(set.insert_if_new(1));
+        assert!(!set.contains(&2));
+        assert!(set.remove(&3));
+        assert_eq!(set.len(), 1);
+    }
+
+    #[test]
+    fn moves_between_threads() {
+        let mut set = RecentSet::new(2);
+        set.insert_if_new(1);
+        let seen = thread::spawn(move || set.contains(&1));
+        assert!(seen.join().unwrap());
+    }
+
+    #[test]
+    fn forgets_old_items() {
+        let clock = MockClock::new();
+        let mut set = RecentSet::with_limits_and_clock(
+            None,
+            Some(Duration::from_millis(20)),
+            RandomState::new(),
+            clock.clone(),
+        );
+        assert!(set.insert_if_new("a"));
+        clock.advance(Duration::from_millis(20));
+        assert!(!set.insert_if_new("a"));
+        clock.advance(Duration::from_millis(1));
+        assert!(!set.contains("a"));
+        assert!(set.insert_if_new("a"));
+        assert_eq!(set.len(), 1);
+    }
+}
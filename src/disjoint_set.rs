@@ -0,0 +1,251 @@
+use std::{collections::HashMap, hash::Hash};
+
+/// A disjoint-set forest over the elements `0..len`, using path compression and union by rank.
+/// Every operation runs in amortized near-constant time.
+#[derive(Debug, Clone, Default)]
+pub struct UnionFind {
+    parent: Vec<usize>,
+    rank: Vec<u8>,
+    size: Vec<usize>,
+    sets: usize,
+}
+
+impl UnionFind {
+    pub fn new() -> Self {
+        UnionFind::default()
+    }
+
+    /// Creates `n` singleton sets, numbered `0..n`.
+    pub fn with_len(n: usize) -> Self {
+        let mut uf = UnionFind::new();
+        for _ in 0..n {
+            uf.make_set();
+        }
+        uf
+    }
+
+    /// Adds a new singleton set and returns its element.
+    pub fn make_set(&mut self) -> usize {
+        let x = self.parent.len();
+        self.parent.push(x);
+        self.rank.push(0);
+        self.size.push(1);
+        self.sets += 1;
+        x
+    }
+
+    /// The total number of elements.
+    pub fn len(&self) -> usize {
+        self.parent.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.parent.is_empty()
+    }
+
+    /// The number of disjoint sets.
+    pub fn set_count(&self) -> usize {
+        self.sets
+    }
+
+    /// Returns the representative of the set containing `x`.
+    pub fn find(&mut self, x: usize) -> usize {
+        let mut root = x;
+        while self.parent[root] != root {
+            root = self.parent[root];
+        }
+        // Point every node along the path directly at the root
+        let mut curr = x;
+        while self.parent[curr] != root {
+            let next = self.parent[curr];
+            self.parent[curr] = root;
+            curr = next;
+        }
+        root
+    }
+
+    /// Merges the sets containing `x` and `y`.
+    /// Returns false if they were already in the same set.
+    pub fn union(&mut self, x: usize, y: usize) -> bool {
+        let mut x = self.find(x);
+        let mut y = self.find(y);
+        if x == y {
+            return false;
+        }
+        if self.rank[x] < self.rank[y] {
+            std::mem::swap(&mut x, &mut y);
+        }
+        // x now has the higher rank and becomes the root
+        self.parent[y] = x;
+        self.size[x] += self.size[y];
+        if self.rank[x] == self.rank[y] {
+            self.rank[x] += 1;
+        }
+        self.sets -= 1;
+        true
+    }
+
+    pub fn connected(&mut self, x: usize, y: usize) -> bool {
+        self.find(x) == self.find(y)
+    }
+
+    /// The number of elements in the set containing `x`.
+    pub fn set_size(&mut self, x: usize) -> usize {
+        let root = self.find(x);
+        self.size[root]
+    }
+}
+
+/// A disjoint-set forest over arbitrary hashable keys.
+#[derive(Debug, Clone)]
+pub struct KeyedUnionFind<K> {
+    indices: HashMap<K, usize>,
+    sets: UnionFind,
+}
+
+impl<K> KeyedUnionFind<K> {
+    pub fn new() -> Self {
+        KeyedUnionFind {
+            indices: HashMap::new(),
+            sets: UnionFind::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.sets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sets.is_empty()
+    }
+
+    pub fn set_count(&self) -> usize {
+        self.sets.set_count()
+    }
+}
+
+impl<K> Default for KeyedUnionFind<K> {
+    fn default() -> Self {
+        KeyedUnionFind::new()
+    }
+}
+
+impl<K> KeyedUnionFind<K>
+where
+    K: Eq + Hash,
+{
+    /// Adds `k` as a singleton set. Returns false if it was already present.
+    pub fn make_set(&mut self, k: K) -> bool {
+        if self.indices.contains_key(&k) {
+            return false;
+        }
+        let index = self.sets.make_set();
+        self.indices.insert(k, index);
+        true
+    }
+
+    pub fn contains(&self, k: &K) -> bool {
+        self.indices.contains_key(k)
+    }
+
+    /// Returns the index of the representative of the set containing `k`, if present.
+    /// Two keys are in the same set exactly when their representatives are equal.
+    pub fn find(&mut self, k: &K) -> Option<usize> {
+        let index = *self.indices.get(k)?;
+        Some(self.sets.find(index))
+    }
+
+    /// Merges the sets containing `a` and `b`, adding either key as a singleton first if absent.
+    /// Returns false if they were already in the same set.
+    pub fn union(&mut self, a: K, b: K) -> bool {
+        let a = self.index_or_insert(a);
+        let b = self.index_or_insert(b);
+        self.sets.union(a, b)
+    }
+
+    pub fn connected(&mut self, a: &K, b: &K) -> bool {
+        match (self.indices.get(a), self.indices.get(b)) {
+            (Some(&a), Some(&b)) => self.sets.connected(a, b),
+            _ => false,
+        }
+    }
+
+    pub fn set_size(&mut self, k: &K) -> Option<usize> {
+        let index = *self.indices.get(k)?;
+        Some(self.sets.set_size(index))
+    }
+
+    fn index_or_insert(&mut self, k: K) -> usize {
+        match self.indices.get(&k) {
+            Some(&index) => index,
+            None => {
+                let index = self.sets.make_set();
+                self.indices.insert(k, index);
+                index
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use quickcheck::quickcheck;
+
+    use super::{KeyedUnionFind, UnionFind};
+
+    #[test]
+    fn singletons() {
+        let mut uf = UnionFind::with_len(3);
+        assert_eq!(uf.set_count(), 3);
+        assert!(!uf.connected(0, 1));
+        assert_eq!(uf.set_size(2), 1);
+    }
+
+    #[test]
+    fn union_and_find() {
+        let mut uf = UnionFind::with_len(5);
+        assert!(uf.union(0, 1));
+        assert!(uf.union(3, 4));
+        assert!(uf.union(1, 4));
+        assert!(!uf.union(0, 3));
+        assert!(uf.connected(0, 4));
+        assert!(!uf.connected(0, 2));
+        assert_eq!(uf.set_size(3), 4);
+        assert_eq!(uf.set_count(), 2);
+    }
+
+    #[test]
+    fn keyed() {
+        let mut uf = KeyedUnionFind::new();
+        assert!(uf.make_set("a"));
+        assert!(!uf.make_set("a"));
+        uf.union("a", "b");
+        uf.union("c", "d");
+        assert!(uf.connected(&"a", &"b"));
+        assert!(!uf.connected(&"a", &"c"));
+        assert!(!uf.connected(&"a", &"z"));
+        assert_eq!(uf.set_size(&"d"), Some(2));
+        assert_eq!(uf.set_size(&"z"), None);
+        assert_eq!(uf.set_count(), 2);
+    }
+
+    #[test]
+    fn prop_matches_naive_labels() {
+        fn p(unions: Vec<(u8, u8)>) -> bool {
+            let n = 16;
+            let mut uf = UnionFind::with_len(n);
+            let mut labels = (0..n).collect::<Vec<_>>();
+            for (a, b) in unions {
+                let (a, b) = (a as usize % n, b as usize % n);
+                uf.union(a, b);
+                let (from, to) = (labels[b], labels[a]);
+                labels
+                    .iter_mut()
+                    .filter(|l| **l == from)
+                    .for_each(|l| *l = to);
+            }
+            (0..n).all(|a| (0..n).all(|b| uf.connected(a, b) == (labels[a] == labels[b])))
+        }
+        quickcheck(p as fn(Vec<(u8, u8)>) -> bool)
+    }
+}
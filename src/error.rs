@@ -0,0 +1,40 @@
+//! The error returned by the fallible `try_` methods of the capacity-bounded structures.
+
+use std::fmt;
+
+/// Why a `try_` operation was rejected. The rejected value is handed back so it isn't lost.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error<T> {
+    /// The structure is at capacity.
+    Full(T),
+    /// The key is already present.
+    Exists(T),
+}
+
+impl<T> Error<T> {
+    /// Returns the value which was rejected.
+    pub fn into_inner(self) -> T {
+        match self {
+            Error::Full(t) | Error::Exists(t) => t,
+        }
+    }
+
+    pub fn is_full(&self) -> bool {
+        matches!(self, Error::Full(_))
+    }
+
+    pub fn is_exists(&self) -> bool {
+        matches!(self, Error::Exists(_))
+    }
+}
+
+impl<T> fmt::Display for Error<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Full(_) => write!(f, "structure is full"),
+            Error::Exists(_) => write!(f, "key already exists"),
+        }
+    }
+}
+
+impl<T: fmt::Debug> std::error::Error for Error<T> {}
@@ -0,0 +1,299 @@
+use std::{
+    borrow::Borrow,
+    cmp::Ordering,
+    collections::{hash_map::RandomState, HashMap},
+    hash::{BuildHasher, Hash},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    clock::{Clock, SystemClock},
+    heap::Heap,
+};
+
+/// A hash map whose entries expire after a time to live. Expired entries are invisible
+/// to lookups, and are collected from a heap of deadlines by `expire_now`. Replaced and
+/// removed entries leave stale deadlines in the heap which are skipped when they come due,
+/// and the heap is rebuilt if they come to outnumber the live entries.
+/// Time is read from a `Clock`, which tests can replace with a `MockClock`.
+#[derive(Debug)]
+pub struct ExpiryMap<K, V, S = RandomState, C = SystemClock> {
+    entries: HashMap<K, Entry<V>, S>,
+    deadlines: Heap<Deadline<K>>,
+    // Tells an entry's current deadline apart from stale deadlines of the same key
+    next_id: u64,
+    clock: C,
+}
+
+#[derive(Debug)]
+struct Entry<V> {
+    value: V,
+    expires_at: Instant,
+    id: u64,
+}
+
+#[derive(Debug)]
+struct Deadline<K> {
+    at: Instant,
+    id: u64,
+    key: K,
+}
+
+impl<K> PartialEq for Deadline<K> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<K> Eq for Deadline<K> {}
+
+impl<K> PartialOrd for Deadline<K> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<K> Ord for Deadline<K> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.at, self.id).cmp(&(other.at, other.id))
+    }
+}
+
+impl<K, V> ExpiryMap<K, V> {
+    pub fn new() -> Self {
+        ExpiryMap::with_hasher(RandomState::new())
+    }
+}
+
+impl<K, V, C: Clock> ExpiryMap<K, V, RandomState, C> {
+    pub fn with_clock(clock: C) -> Self {
+        ExpiryMap::with_hasher_and_clock(RandomState::new(), clock)
+    }
+}
+
+impl<K, V, S> ExpiryMap<K, V, S> {
+    pub fn with_hasher(hasher: S) -> Self {
+        ExpiryMap::with_hasher_and_clock(hasher, SystemClock)
+    }
+}
+
+impl<K, V, S, C> ExpiryMap<K, V, S, C> {
+    pub fn with_hasher_and_clock(hasher: S, clock: C) -> Self {
+        ExpiryMap {
+            entries: HashMap::with_hasher(hasher),
+            deadlines: Heap::new(),
+            next_id: 0,
+            clock,
+        }
+    }
+
+    /// The number of entries, including expired ones which have not been collected yet.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl<K, V, S, C> ExpiryMap<K, V, S, C>
+where
+    K: Eq + Hash + Clone,
+    S: BuildHasher,
+    C: Clock,
+{
+    /// Inserts a value which expires after `ttl`, returning the old value if it was still live.
+    pub fn insert(&mut self, k: K, v: V, ttl: Duration) -> Option<V> {
+        self.insert_until(k, v, self.clock.now() + ttl)
+    }
+
+    /// Inserts a value which expires at `deadline`.
+    pub fn insert_until(&mut self, k: K, v: V, deadline: Instant) -> Option<V> {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.deadlines.push(Deadline {
+            at: deadline,
+            id,
+            key: k.clone(),
+        });
+        let entry = Entry {
+            value: v,
+            expires_at: deadline,
+            id,
+        };
+        let old = self.entries.insert(k, entry);
+        if self.deadlines.size() > 2 * self.entries.len() + 16 {
+            self.rebuild_deadlines();
+        }
+        old.filter(|e| e.expires_at > self.clock.now())
+            .map(|e| e.value)
+    }
+
+    pub fn get<Q>(&self, k: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let entry = self.entries.get(k)?;
+        (entry.expires_at > self.clock.now()).then_some(&entry.value)
+    }
+
+    pub fn get_mut<Q>(&mut self, k: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let entry = self.entries.get_mut(k)?;
+        (entry.expires_at > self.clock.now()).then_some(&mut entry.value)
+    }
+
+    pub fn contains_key<Q>(&self, k: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.get(k).is_some()
+    }
+
+    /// The time left before the key expires.
+    pub fn ttl<Q>(&self, k: &Q) -> Option<Duration>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let entry = self.entries.get(k)?;
+        entry.expires_at.checked_duration_since(self.clock.now())
+    }
+
+    /// Removes the key, returning its value if it was still live.
+    pub fn remove<Q>(&mut self, k: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let entry = self.entries.remove(k)?;
+        (entry.expires_at > self.clock.now()).then_some(entry.value)
+    }
+
+    /// Removes and returns every entry which has expired, in order of expiry.
+    pub fn expire_now(&mut self) -> Vec<(K, V)> {
+        self.expire_at(self.clock.now())
+    }
+
+    /// Removes and returns every entry which expires at or before `now`.
+    pub fn expire_at(&mut self, now: Instant) -> Vec<(K, V)> {
+        let mut expired = vec![];
+        while self.deadlines.peek().is_some_and(|d| d.at <= now) {
+            let deadline = self.deadlines.pop().unwrap();
+            if self
+                .entries
+                .get(&deadline.key)
+                .is_some_and(|e| e.id == deadline.id)
+            {
+                let entry = self.entries.remove(&deadline.key).unwrap();
+                expired.push((deadline.key, entry.value));
+            }
+        }
+        expired
+    }
+
+    /// The earliest deadline among the entries, live or expired.
+    pub fn next_deadline(&mut self) -> Option<Instant> {
+        // Discard stale deadlines until the earliest one belongs to a current entry
+        loop {
+            let deadline = self.deadlines.peek()?;
+            if self
+                .entries
+                .get(&deadline.key)
+                .is_some_and(|e| e.id == deadline.id)
+            {
+                return Some(deadline.at);
+            }
+            self.deadlines.pop();
+        }
+    }
+
+    fn rebuild_deadlines(&mut self) {
+        self.deadlines = Heap::new();
+        for (k, entry) in &self.entries {
+            self.deadlines.push(Deadline {
+                at: entry.expires_at,
+                id: entry.id,
+                key: k.clone(),
+            });
+        }
+    }
+}
+
+impl<K, V, S: Default, C: Default> Default for ExpiryMap<K, V, S, C> {
+    fn default() -> Self {
+        ExpiryMap::with_hasher_and_clock(S::default(), C::default())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use super::ExpiryMap;
+    use crate::clock::{Clock, MockClock};
+
+    const HOUR: Duration = Duration::from_secs(3600);
+
+    #[test]
+    fn get_ignores_expired() {
+        let mut map = ExpiryMap::new();
+        map.insert("live", 1, HOUR);
+        map.insert("dead", 2, Duration::ZERO);
+        assert_eq!(map.get("live"), Some(&1));
+        assert_eq!(map.get("dead"), None);
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.expire_now(), vec![("dead", 2)]);
+        assert_eq!(map.len(), 1);
+        assert_eq!(map.remove("live"), Some(1));
+    }
+
+    #[test]
+    fn expires_in_order() {
+        let now = Instant::now();
+        let mut map = ExpiryMap::new();
+        for (i, secs) in [30, 10, 20, 40].into_iter().enumerate() {
+            map.insert_until(i, secs, now + Duration::from_secs(secs));
+        }
+        // Extending a key's deadline leaves its old deadline behind in the heap
+        map.insert_until(1, 50, now + Duration::from_secs(50));
+        assert_eq!(map.next_deadline(), Some(now + Duration::from_secs(20)));
+        assert_eq!(
+            map.expire_at(now + Duration::from_secs(35)),
+            vec![(2, 20), (0, 30)]
+        );
+        assert_eq!(map.expire_at(now + HOUR), vec![(3, 40), (1, 50)]);
+        assert!(map.is_empty());
+        assert_eq!(map.next_deadline(), None);
+    }
+
+    #[test]
+    fn mock_clock() {
+        let clock = MockClock::new();
+        let mut map = ExpiryMap::with_clock(clock.clone());
+        map.insert("token", 1, HOUR);
+        clock.advance(HOUR / 2);
+        assert_eq!(map.ttl("token"), Some(HOUR / 2));
+        assert!(map.expire_now().is_empty());
+        clock.advance(HOUR / 2);
+        assert_eq!(map.get("token"), None);
+        assert_eq!(map.next_deadline(), Some(clock.now()));
+        assert_eq!(map.expire_now(), vec![("token", 1)]);
+    }
+
+    #[test]
+    fn stale_deadlines_are_bounded() {
+        let mut map = ExpiryMap::new();
+        for i in 0..1000 {
+            map.insert("session", i, HOUR);
+        }
+        assert!(map.deadlines.size() <= 18);
+        assert_eq!(map.get("session"), Some(&999));
+    }
+}
@@ -0,0 +1,211 @@
+use std::vec;
+
+use crate::{
+    heap::Heap,
+    merge::{kmerge, KMerge},
+};
+
+/// Where an `ExternalSorter` spills its sorted runs, such as a directory of temporary files.
+pub trait RunStore<T> {
+    /// A handle to a run which has been written.
+    type Run;
+    /// Reads a run back in the order it was written.
+    type Reader: Iterator<Item = T>;
+    type Error;
+
+    /// Writes a run of items in ascending order.
+    fn write_run(&mut self, items: Vec<T>) -> Result<Self::Run, Self::Error>;
+
+    fn read_run(&mut self, run: Self::Run) -> Result<Self::Reader, Self::Error>;
+}
+
+/// Sorts more items than fit in memory. Items are buffered in a heap until `run_capacity`
+/// of them have been pushed, then spilled to the store as a sorted run. `finish` merges
+/// every run, along with whatever is still buffered, into one ascending iterator.
+/// The sort is not stable.
+#[derive(Debug)]
+pub struct ExternalSorter<T, S: RunStore<T>> {
+    store: S,
+    buffer: Heap<T>,
+    run_capacity: usize,
+    runs: Vec<S::Run>,
+}
+
+impl<T: Ord, S: RunStore<T>> ExternalSorter<T, S> {
+    /// Creates a sorter which holds at most `run_capacity` items in memory before spilling
+    /// them. Panics if `run_capacity` is zero.
+    pub fn new(store: S, run_capacity: usize) -> Self {
+        assert!(run_capacity > 0, "run capacity must be positive");
+        ExternalSorter {
+            store,
+            buffer: Heap::new(),
+            run_capacity,
+            runs: vec![],
+        }
+    }
+
+    /// The number of runs which have been spilled.
+    pub fn runs(&self) -> usize {
+        self.runs.len()
+    }
+
+    pub fn push(&mut self, item: T) -> Result<(), S::Error> {
+        self.buffer.push(item);
+        if self.buffer.size() >= self.run_capacity {
+            self.spill()?;
+        }
+        Ok(())
+    }
+
+    pub fn push_all<I: IntoIterator<Item = T>>(&mut self, iter: I) -> Result<(), S::Error> {
+        for item in iter {
+            self.push(item)?;
+        }
+        Ok(())
+    }
+
+    /// Returns every item pushed, in ascending order.
+    pub fn finish(mut self) -> Result<Sorted<T, S::Reader>, S::Error> {
+        let buffered = self.drain_buffer();
+        let mut sources = Vec::with_capacity(self.runs.len() + 1);
+        for run in self.runs {
+            sources.push(Source::Spilled(self.store.read_run(run)?));
+        }
+        sources.push(Source::Buffered(buffered.into_iter()));
+        Ok(Sorted(kmerge(sources)))
+    }
+
+    fn spill(&mut self) -> Result<(), S::Error> {
+        let items = self.drain_buffer();
+        self.runs.push(self.store.write_run(items)?);
+        Ok(())
+    }
+
+    fn drain_buffer(&mut self) -> Vec<T> {
+        let mut items = Vec::with_capacity(self.buffer.size());
+        while let Some(item) = self.buffer.pop() {
+            items.push(item);
+        }
+        items
+    }
+}
+
+/// The items of an `ExternalSorter` in ascending order.
+#[derive(Debug)]
+pub struct Sorted<T, R: Iterator<Item = T>>(KMerge<Source<T, R>>);
+
+#[derive(Debug)]
+enum Source<T, R> {
+    Spilled(R),
+    Buffered(vec::IntoIter<T>),
+}
+
+impl<T, R: Iterator<Item = T>> Iterator for Source<T, R> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        match self {
+            Source::Spilled(reader) => reader.next(),
+            Source::Buffered(items) => items.next(),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self {
+            Source::Spilled(reader) => reader.size_hint(),
+            Source::Buffered(items) => items.size_hint(),
+        }
+    }
+}
+
+impl<T: Ord, R: Iterator<Item = T>> Iterator for Sorted<T, R> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.0.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{convert::Infallible, vec};
+
+    use quickcheck::quickcheck;
+
+    use super::{ExternalSorter, RunStore};
+
+    /// Keeps runs in memory, recording how large each one was.
+    #[derive(Default)]
+    struct MemoryStore {
+        runs: Vec<Vec<u32>>,
+        sizes: Vec<usize>,
+    }
+
+    impl RunStore<u32> for MemoryStore {
+        type Run = usize;
+        type Reader = vec::IntoIter<u32>;
+        type Error = Infallible;
+
+        fn write_run(&mut self, items: Vec<u32>) -> Result<usize, Infallible> {
+            assert!(items.windows(2).all(|w| w[0] <= w[1]), "run out of order");
+            self.sizes.push(items.len());
+            self.runs.push(items);
+            Ok(self.runs.len() - 1)
+        }
+
+        fn read_run(&mut self, run: usize) -> Result<Self::Reader, Infallible> {
+            Ok(std::mem::take(&mut self.runs[run]).into_iter())
+        }
+    }
+
+    /// Fails to write any run.
+    struct FullDisk;
+
+    impl RunStore<u32> for FullDisk {
+        type Run = ();
+        type Reader = vec::IntoIter<u32>;
+        type Error = &'static str;
+
+        fn write_run(&mut self, _: Vec<u32>) -> Result<(), &'static str> {
+            Err("disk full")
+        }
+
+        fn read_run(&mut self, _: ()) -> Result<Self::Reader, &'static str> {
+            unreachable!()
+        }
+    }
+
+    #[test]
+    fn spills_and_merges() {
+        let mut sorter = ExternalSorter::new(MemoryStore::default(), 3);
+        sorter.push_all([5, 3, 9, 1, 1, 8, 2]).unwrap();
+        assert_eq!(sorter.runs(), 2);
+        assert_eq!(sorter.store.sizes, vec![3, 3]);
+        let sorted = sorter.finish().unwrap();
+        assert_eq!(sorted.size_hint(), (7, Some(7)));
+        assert_eq!(sorted.collect::<Vec<_>>(), vec![1, 1, 2, 3, 5, 8, 9]);
+    }
+
+    #[test]
+    fn write_errors() {
+        let mut sorter = ExternalSorter::new(FullDisk, 2);
+        assert_eq!(sorter.push(1), Ok(()));
+        assert_eq!(sorter.push(2), Err("disk full"));
+    }
+
+    #[test]
+    fn prop_sorts() {
+        fn p(items: Vec<u32>, capacity: u8) -> bool {
+            let mut sorter = ExternalSorter::new(MemoryStore::default(), capacity as usize + 1);
+            sorter.push_all(items.iter().copied()).unwrap();
+            let mut expected = items;
+            expected.sort();
+            sorter.finish().unwrap().collect::<Vec<_>>() == expected
+        }
+        quickcheck(p as fn(Vec<u32>, u8) -> bool);
+    }
+}
@@ -0,0 +1,23 @@
+//! Memory usage introspection for the node-based structures.
+
+use std::{collections::HashMap, mem::size_of};
+
+/// Reports how much memory a structure has allocated for itself. Memory owned by the keys
+/// and values, such as the buffer of a `String`, isn't counted, and hash tables are
+/// estimated from their capacity since std doesn't expose their exact layout.
+pub trait MemoryFootprint {
+    /// The number of bytes allocated on the heap.
+    fn heap_bytes(&self) -> usize;
+
+    /// The number of nodes holding entries.
+    fn node_count(&self) -> usize;
+}
+
+/// Estimates a hash map's table as one slot plus one control byte per unit of capacity.
+#[cfg_attr(
+    not(any(feature = "hash_trie", feature = "lru_cache")),
+    allow(dead_code)
+)]
+pub(crate) fn hash_map_bytes<K, V, S>(map: &HashMap<K, V, S>) -> usize {
+    map.capacity() * (size_of::<(K, V)>() + 1)
+}
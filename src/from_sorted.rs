@@ -0,0 +1,39 @@
+//! Bulk construction of ordered structures from sorted input.
+
+/// An ordered structure which can be built in linear time from items in ascending order,
+/// rather than inserting them one at a time.
+pub trait FromSorted<T>: Sized {
+    /// Builds the structure from items in ascending order.
+    /// Panics if the items are out of order, or if a structure which holds distinct keys is
+    /// given a duplicate.
+    fn from_sorted_iter<I: IntoIterator<Item = T>>(iter: I) -> Self;
+}
+
+/// Collects the items, panicking unless their keys are ascending.
+/// With `strict`, equal keys are rejected as well.
+#[cfg_attr(
+    not(any(feature = "avl_tree", feature = "bs_tree", feature = "sorted_list")),
+    allow(dead_code)
+)]
+pub(crate) fn collect_sorted<T, K, I, F>(iter: I, strict: bool, key: F) -> Vec<T>
+where
+    I: IntoIterator<Item = T>,
+    K: Ord + ?Sized,
+    F: Fn(&T) -> &K,
+{
+    let items = iter.into_iter().collect::<Vec<_>>();
+    let sorted = items.windows(2).all(|w| {
+        let (a, b) = (key(&w[0]), key(&w[1]));
+        if strict {
+            a < b
+        } else {
+            a <= b
+        }
+    });
+    if strict {
+        assert!(sorted, "keys must be strictly ascending");
+    } else {
+        assert!(sorted, "keys must be ascending");
+    }
+    items
+}
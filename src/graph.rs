@@ -0,0 +1,435 @@
+use std::collections::{HashSet, VecDeque};
+
+/// Identifies a node in a `Graph`. Ids are never reused after a node is removed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NodeId(usize);
+
+/// Identifies an edge in a `Graph`. Ids are never reused after an edge is removed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct EdgeId(usize);
+
+/// A graph stored as adjacency lists, with a weight of type `N` on each node and `E` on each edge.
+/// Parallel edges and self loops are allowed. In an undirected graph every edge
+/// appears in the adjacency lists of both of its endpoints.
+#[derive(Debug, Clone)]
+pub struct Graph<N, E> {
+    nodes: Vec<Option<Node<N>>>,
+    edges: Vec<Option<Edge<E>>>,
+    directed: bool,
+    node_count: usize,
+    edge_count: usize,
+}
+
+#[derive(Debug, Clone)]
+struct Node<N> {
+    weight: N,
+    // Edges leaving this node, or every incident edge in an undirected graph
+    outgoing: Vec<EdgeId>,
+    incoming: Vec<EdgeId>,
+}
+
+#[derive(Debug, Clone)]
+struct Edge<E> {
+    weight: E,
+    source: NodeId,
+    target: NodeId,
+}
+
+impl<N, E> Graph<N, E> {
+    pub fn directed() -> Self {
+        Graph::new(true)
+    }
+
+    pub fn undirected() -> Self {
+        Graph::new(false)
+    }
+
+    fn new(directed: bool) -> Self {
+        Graph {
+            nodes: vec![],
+            edges: vec![],
+            directed,
+            node_count: 0,
+            edge_count: 0,
+        }
+    }
+
+    pub fn is_directed(&self) -> bool {
+        self.directed
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.node_count
+    }
+
+    pub fn edge_count(&self) -> usize {
+        self.edge_count
+    }
+
+    pub fn add_node(&mut self, weight: N) -> NodeId {
+        self.nodes.push(Some(Node {
+            weight,
+            outgoing: vec![],
+            incoming: vec![],
+        }));
+        self.node_count += 1;
+        NodeId(self.nodes.len() - 1)
+    }
+
+    /// Adds an edge from `source` to `target`. Panics if either node is absent.
+    pub fn add_edge(&mut self, source: NodeId, target: NodeId, weight: E) -> EdgeId {
+        assert!(
+            self.contains_node(source) && self.contains_node(target),
+            "edge endpoints must be in the graph"
+        );
+        let id = EdgeId(self.edges.len());
+        self.edges.push(Some(Edge {
+            weight,
+            source,
+            target,
+        }));
+        self.node_mut(source).outgoing.push(id);
+        if self.directed {
+            self.node_mut(target).incoming.push(id);
+        } else if source != target {
+            self.node_mut(target).outgoing.push(id);
+        }
+        self.edge_count += 1;
+        id
+    }
+
+    /// Removes the node and every edge incident to it.
+    pub fn remove_node(&mut self, id: NodeId) -> Option<N> {
+        let node = self.nodes.get_mut(id.0)?.take()?;
+        for edge in node.outgoing.iter().chain(node.incoming.iter()) {
+            self.remove_edge(*edge);
+        }
+        self.node_count -= 1;
+        Some(node.weight)
+    }
+
+    pub fn remove_edge(&mut self, id: EdgeId) -> Option<E> {
+        let edge = self.edges.get_mut(id.0)?.take()?;
+        // Either endpoint may already be gone if this is part of removing a node
+        if let Some(Some(source)) = self.nodes.get_mut(edge.source.0) {
+            source.outgoing.retain(|e| *e != id);
+        }
+        if let Some(Some(target)) = self.nodes.get_mut(edge.target.0) {
+            target.incoming.retain(|e| *e != id);
+            target.outgoing.retain(|e| *e != id);
+        }
+        self.edge_count -= 1;
+        Some(edge.weight)
+    }
+
+    pub fn contains_node(&self, id: NodeId) -> bool {
+        matches!(self.nodes.get(id.0), Some(Some(_)))
+    }
+
+    pub fn node_weight(&self, id: NodeId) -> Option<&N> {
+        self.nodes.get(id.0)?.as_ref().map(|n| &n.weight)
+    }
+
+    pub fn node_weight_mut(&mut self, id: NodeId) -> Option<&mut N> {
+        self.nodes.get_mut(id.0)?.as_mut().map(|n| &mut n.weight)
+    }
+
+    pub fn edge_weight(&self, id: EdgeId) -> Option<&E> {
+        self.edges.get(id.0)?.as_ref().map(|e| &e.weight)
+    }
+
+    pub fn edge_weight_mut(&mut self, id: EdgeId) -> Option<&mut E> {
+        self.edges.get_mut(id.0)?.as_mut().map(|e| &mut e.weight)
+    }
+
+    pub fn endpoints(&self, id: EdgeId) -> Option<(NodeId, NodeId)> {
+        self.edges.get(id.0)?.as_ref().map(|e| (e.source, e.target))
+    }
+
+    pub fn nodes(&self) -> impl Iterator<Item = NodeId> + '_ {
+        self.nodes
+            .iter()
+            .enumerate()
+            .filter(|(_, n)| n.is_some())
+            .map(|(i, _)| NodeId(i))
+    }
+
+    pub fn edges(&self) -> impl Iterator<Item = EdgeId> + '_ {
+        self.edges
+            .iter()
+            .enumerate()
+            .filter(|(_, e)| e.is_some())
+            .map(|(i, _)| EdgeId(i))
+    }
+
+    /// Returns the nodes reachable from `id` by one edge, following edge direction.
+    /// A node is repeated once for each parallel edge.
+    pub fn neighbors(&self, id: NodeId) -> impl Iterator<Item = NodeId> + '_ {
+        self.nodes
+            .get(id.0)
+            .and_then(|n| n.as_ref())
+            .into_iter()
+            .flat_map(move |n| n.outgoing.iter().map(move |e| self.opposite(*e, id)))
+    }
+
+    /// Returns an iterator visiting every node reachable from `start` in breadth-first order.
+    pub fn bfs(&self, start: NodeId) -> Bfs<'_, N, E> {
+        let mut visited = HashSet::new();
+        let mut queue = VecDeque::new();
+        if self.contains_node(start) {
+            visited.insert(start);
+            queue.push_back(start);
+        }
+        Bfs {
+            graph: self,
+            visited,
+            queue,
+        }
+    }
+
+    /// Returns an iterator visiting every node reachable from `start` in depth-first preorder.
+    pub fn dfs(&self, start: NodeId) -> Dfs<'_, N, E> {
+        let stack = if self.contains_node(start) {
+            vec![start]
+        } else {
+            vec![]
+        };
+        Dfs {
+            graph: self,
+            visited: HashSet::new(),
+            stack,
+        }
+    }
+
+    /// Orders the nodes of a directed graph so that every edge points forward.
+    /// Returns None if the graph has a cycle or is undirected.
+    pub fn topological_sort(&self) -> Option<Vec<NodeId>> {
+        if !self.directed {
+            return None;
+        }
+        // Kahn's algorithm
+        let mut in_degree = self
+            .nodes
+            .iter()
+            .map(|n| n.as_ref().map_or(0, |n| n.incoming.len()))
+            .collect::<Vec<_>>();
+        let mut ready = self
+            .nodes()
+            .filter(|n| in_degree[n.0] == 0)
+            .collect::<VecDeque<_>>();
+        let mut order = Vec::with_capacity(self.node_count);
+        while let Some(id) = ready.pop_front() {
+            order.push(id);
+            for next in self.neighbors(id) {
+                in_degree[next.0] -= 1;
+                if in_degree[next.0] == 0 {
+                    ready.push_back(next);
+                }
+            }
+        }
+        (order.len() == self.node_count).then_some(order)
+    }
+
+    /// Groups the nodes into connected components, ignoring edge direction.
+    pub fn connected_components(&self) -> Vec<Vec<NodeId>> {
+        let mut visited = HashSet::new();
+        let mut components = vec![];
+        for start in self.nodes() {
+            if !visited.insert(start) {
+                continue;
+            }
+            let mut component = vec![];
+            let mut stack = vec![start];
+            while let Some(id) = stack.pop() {
+                component.push(id);
+                let node = self.node(id);
+                for edge in node.outgoing.iter().chain(node.incoming.iter()) {
+                    let next = self.opposite(*edge, id);
+                    if visited.insert(next) {
+                        stack.push(next);
+                    }
+                }
+            }
+            components.push(component);
+        }
+        components
+    }
+
+    fn node(&self, id: NodeId) -> &Node<N> {
+        self.nodes[id.0].as_ref().unwrap()
+    }
+
+    fn node_mut(&mut self, id: NodeId) -> &mut Node<N> {
+        self.nodes[id.0].as_mut().unwrap()
+    }
+
+    /// The endpoint of the edge which is not `from`.
+    fn opposite(&self, edge: EdgeId, from: NodeId) -> NodeId {
+        let edge = self.edges[edge.0].as_ref().unwrap();
+        if edge.source == from {
+            edge.target
+        } else {
+            edge.source
+        }
+    }
+}
+
+/// A breadth-first traversal of a `Graph`.
+pub struct Bfs<'a, N, E> {
+    graph: &'a Graph<N, E>,
+    visited: HashSet<NodeId>,
+    queue: VecDeque<NodeId>,
+}
+
+impl<N, E> Iterator for Bfs<'_, N, E> {
+    type Item = NodeId;
+
+    fn next(&mut self) -> Option<NodeId> {
+        let id = self.queue.pop_front()?;
+        for next in self.graph.neighbors(id) {
+            if self.visited.insert(next) {
+                self.queue.push_back(next);
+            }
+        }
+        Some(id)
+    }
+}
+
+/// A depth-first traversal of a `Graph`.
+pub struct Dfs<'a, N, E> {
+    graph: &'a Graph<N, E>,
+    visited: HashSet<NodeId>,
+    stack: Vec<NodeId>,
+}
+
+impl<N, E> Iterator for Dfs<'_, N, E> {
+    type Item = NodeId;
+
+    fn next(&mut self) -> Option<NodeId> {
+        loop {
+            let id = self.stack.pop()?;
+            if !self.visited.insert(id) {
+                continue;
+            }
+            // Push in reverse so that neighbors are visited in insertion order
+            let neighbors = self.graph.neighbors(id).collect::<Vec<_>>();
+            self.stack.extend(
+                neighbors
+                    .into_iter()
+                    .rev()
+                    .filter(|n| !self.visited.contains(n)),
+            );
+            return Some(id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use quickcheck::quickcheck;
+
+    use super::Graph;
+
+    #[test]
+    fn add_and_remove() {
+        let mut graph = Graph::directed();
+        let a = graph.add_node("a");
+        let b = graph.add_node("b");
+        let c = graph.add_node("c");
+        let ab = graph.add_edge(a, b, 1);
+        graph.add_edge(b, c, 2);
+        graph.add_edge(c, a, 3);
+        assert_eq!(graph.neighbors(a).collect::<Vec<_>>(), vec![b]);
+        assert_eq!(graph.edge_weight(ab), Some(&1));
+        assert_eq!(graph.remove_node(b), Some("b"));
+        assert_eq!(graph.edge_count(), 1);
+        assert_eq!(graph.neighbors(a).count(), 0);
+        assert_eq!(graph.neighbors(c).collect::<Vec<_>>(), vec![a]);
+        assert_eq!(graph.edge_weight(ab), None);
+    }
+
+    #[test]
+    fn undirected_neighbors() {
+        let mut graph = Graph::undirected();
+        let a = graph.add_node(());
+        let b = graph.add_node(());
+        graph.add_edge(a, b, ());
+        assert_eq!(graph.neighbors(b).collect::<Vec<_>>(), vec![a]);
+        assert_eq!(graph.topological_sort(), None);
+    }
+
+    #[test]
+    fn traversals() {
+        //   0 -> 1 -> 3
+        //   |         ^
+        //   +--> 2 ---+
+        let mut graph = Graph::directed();
+        let n = (0..4).map(|i| graph.add_node(i)).collect::<Vec<_>>();
+        graph.add_edge(n[0], n[1], ());
+        graph.add_edge(n[0], n[2], ());
+        graph.add_edge(n[1], n[3], ());
+        graph.add_edge(n[2], n[3], ());
+        assert_eq!(
+            graph.bfs(n[0]).collect::<Vec<_>>(),
+            vec![n[0], n[1], n[2], n[3]]
+        );
+        assert_eq!(
+            graph.dfs(n[0]).collect::<Vec<_>>(),
+            vec![n[0], n[1], n[3], n[2]]
+        );
+        assert_eq!(graph.bfs(n[3]).collect::<Vec<_>>(), vec![n[3]]);
+    }
+
+    #[test]
+    fn topological_sort() {
+        let mut graph = Graph::directed();
+        let a = graph.add_node(());
+        let b = graph.add_node(());
+        let c = graph.add_node(());
+        graph.add_edge(c, b, ());
+        graph.add_edge(b, a, ());
+        assert_eq!(graph.topological_sort(), Some(vec![c, b, a]));
+        graph.add_edge(a, c, ());
+        assert_eq!(graph.topological_sort(), None);
+    }
+
+    #[test]
+    fn components() {
+        let mut graph = Graph::directed();
+        let n = (0..5).map(|i| graph.add_node(i)).collect::<Vec<_>>();
+        graph.add_edge(n[1], n[0], ());
+        graph.add_edge(n[3], n[4], ());
+        let mut components = graph.connected_components();
+        components.iter_mut().for_each(|c| c.sort());
+        assert_eq!(
+            components,
+            vec![vec![n[0], n[1]], vec![n[2]], vec![n[3], n[4]]]
+        );
+    }
+
+    #[test]
+    fn prop_topological_order_respects_edges() {
+        // Edges only point from lower to higher nodes, so the graph is acyclic
+        fn p(edges: Vec<(u8, u8)>) -> bool {
+            let mut graph = Graph::directed();
+            let n = (0..16).map(|i| graph.add_node(i)).collect::<Vec<_>>();
+            for (a, b) in edges {
+                let (a, b) = (a as usize % 16, b as usize % 16);
+                if a < b {
+                    graph.add_edge(n[a], n[b], ());
+                }
+            }
+            let order = graph.topological_sort().unwrap();
+            let mut position = [0; 16];
+            for (i, id) in order.iter().enumerate() {
+                position[*graph.node_weight(*id).unwrap()] = i;
+            }
+            let respected = graph.edges().all(|e| {
+                let (a, b) = graph.endpoints(e).unwrap();
+                position[*graph.node_weight(a).unwrap()] < position[*graph.node_weight(b).unwrap()]
+            });
+            respected
+        }
+        quickcheck(p as fn(Vec<(u8, u8)>) -> bool)
+    }
+}
@@ -0,0 +1,865 @@
+use std::{
+    borrow::Borrow,
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hash},
+};
+
+const MIN_SLOTS: usize = 8;
+
+/// The fraction of a table's slots which may be occupied, in (0, 1].
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct LoadFactor(f64);
+
+impl LoadFactor {
+    pub const DEFAULT: LoadFactor = LoadFactor(0.875);
+
+    /// Panics unless `load_factor` is in (0, 1].
+    pub fn new(load_factor: f64) -> Self {
+        assert!(
+            load_factor > 0.0 && load_factor <= 1.0,
+            "max load factor must be in (0, 1]"
+        );
+        LoadFactor(load_factor)
+    }
+
+    pub fn get(self) -> f64 {
+        self.0
+    }
+}
+
+impl Default for LoadFactor {
+    fn default() -> Self {
+        LoadFactor::DEFAULT
+    }
+}
+
+/// Whether a table gives memory back as entries are removed.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ShrinkPolicy {
+    /// The table only grows, until it is shrunk explicitly.
+    #[default]
+    Never,
+    /// The table shrinks to fit its entries once its load falls below the given factor,
+    /// which must be less than half the max load factor so that it doesn't grow again
+    /// straight away.
+    BelowLoad(LoadFactor),
+}
+
+/// How a hash table is sized and hashed, built up from the defaults one setting at a time.
+#[derive(Debug, Clone)]
+pub struct HashConfig<S = RandomState> {
+    capacity: usize,
+    max_load_factor: LoadFactor,
+    shrink_policy: ShrinkPolicy,
+    hasher: S,
+}
+
+impl HashConfig {
+    pub fn new() -> Self {
+        HashConfig::default()
+    }
+}
+
+impl<S: Default> Default for HashConfig<S> {
+    fn default() -> Self {
+        HashConfig {
+            capacity: 0,
+            max_load_factor: LoadFactor::DEFAULT,
+            shrink_policy: ShrinkPolicy::Never,
+            hasher: S::default(),
+        }
+    }
+}
+
+impl<S> HashConfig<S> {
+    /// Sizes the table to hold `capacity` entries without resizing. The table never
+    /// shrinks below this size.
+    pub fn with_capacity(self, capacity: usize) -> Self {
+        HashConfig { capacity, ..self }
+    }
+
+    /// Sets the load at which the table doubles in size. Higher loads save memory at the
+    /// cost of longer probe sequences.
+    pub fn with_load_factor(self, max_load_factor: LoadFactor) -> Self {
+        HashConfig {
+            max_load_factor,
+            ..self
+        }
+    }
+
+    pub fn with_hasher<T>(self, hasher: T) -> HashConfig<T> {
+        HashConfig {
+            capacity: self.capacity,
+            max_load_factor: self.max_load_factor,
+            shrink_policy: self.shrink_policy,
+            hasher,
+        }
+    }
+
+    pub fn shrink_policy(self, shrink_policy: ShrinkPolicy) -> Self {
+        HashConfig {
+            shrink_policy,
+            ..self
+        }
+    }
+}
+
+/// A hash map which stores entries inline in a single table using open addressing.
+/// Collisions are resolved with Robin Hood linear probing: an entry which has probed
+/// further than the resident of a slot takes that slot, which keeps probe lengths short.
+/// Removal shifts the following entries back instead of leaving tombstones.
+#[derive(Debug, Clone)]
+pub struct FlatHashMap<K, V, S = RandomState> {
+    slots: Vec<Option<Bucket<K, V>>>,
+    len: usize,
+    max_load_factor: LoadFactor,
+    shrink_policy: ShrinkPolicy,
+    // The table is never shrunk below the size which holds this many entries
+    min_capacity: usize,
+    hasher: S,
+}
+
+#[derive(Debug, Clone)]
+struct Bucket<K, V> {
+    hash: u64,
+    key: K,
+    value: V,
+}
+
+impl<K, V> FlatHashMap<K, V> {
+    pub fn new() -> Self {
+        FlatHashMap::with_hasher(RandomState::new())
+    }
+
+    /// Creates a map which can hold `capacity` entries without resizing.
+    pub fn with_capacity(capacity: usize) -> Self {
+        FlatHashMap::with_capacity_and_hasher(capacity, RandomState::new())
+    }
+}
+
+impl<K, V, S> FlatHashMap<K, V, S> {
+    pub fn with_hasher(hasher: S) -> Self {
+        FlatHashMap {
+            slots: vec![],
+            len: 0,
+            max_load_factor: LoadFactor::DEFAULT,
+            shrink_policy: ShrinkPolicy::Never,
+            min_capacity: 0,
+            hasher,
+        }
+    }
+
+    pub fn with_capacity_and_hasher(capacity: usize, hasher: S) -> Self {
+        FlatHashMap::with_config(
+            HashConfig::new()
+                .with_capacity(capacity)
+                .with_hasher(hasher),
+        )
+    }
+
+    /// Creates a map sized and hashed as configured. Panics if the shrink policy's load
+    /// factor isn't less than half the max load factor.
+    pub fn with_config(config: HashConfig<S>) -> Self {
+        if let ShrinkPolicy::BelowLoad(shrink) = config.shrink_policy {
+            assert!(
+                shrink.get() < config.max_load_factor.get() / 2.0,
+                "shrink load factor must be less than half the max load factor"
+            );
+        }
+        let mut map = FlatHashMap {
+            max_load_factor: config.max_load_factor,
+            shrink_policy: config.shrink_policy,
+            min_capacity: config.capacity,
+            ..FlatHashMap::with_hasher(config.hasher)
+        };
+        if config.capacity > 0 {
+            map.resize(map.slots_for(config.capacity));
+        }
+        map
+    }
+
+    pub fn hasher(&self) -> &S {
+        &self.hasher
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The number of entries the map can hold before it resizes.
+    pub fn capacity(&self) -> usize {
+        (self.slots.len() as f64 * self.max_load_factor.get()) as usize
+    }
+
+    /// The fraction of slots which are occupied.
+    pub fn load_factor(&self) -> f64 {
+        if self.slots.is_empty() {
+            0.0
+        } else {
+            self.len as f64 / self.slots.len() as f64
+        }
+    }
+
+    pub fn max_load_factor(&self) -> f64 {
+        self.max_load_factor.get()
+    }
+
+    /// Sets the load factor at which the table doubles in size, resizing now if it is exceeded.
+    /// Higher values save memory at the cost of longer probe sequences.
+    pub fn set_max_load_factor(&mut self, max_load_factor: f64) {
+        self.max_load_factor = LoadFactor::new(max_load_factor);
+        if self.len > self.capacity() {
+            self.resize(self.slots_for(self.len));
+        }
+    }
+
+    pub fn shrink_policy(&self) -> ShrinkPolicy {
+        self.shrink_policy
+    }
+
+    /// Shrinks the table to the smallest size which holds its entries under the max load
+    /// factor, but no smaller than the configured capacity.
+    pub fn shrink_to_fit(&mut self) {
+        let slots = self.slots_for(self.len.max(self.min_capacity));
+        if slots < self.slots.len() {
+            self.resize(slots);
+        }
+    }
+
+    /// Shrinks the table if the shrink policy calls for it.
+    fn shrink_if_sparse(&mut self) {
+        if let ShrinkPolicy::BelowLoad(shrink) = self.shrink_policy {
+            if self.load_factor() < shrink.get() {
+                self.shrink_to_fit();
+            }
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.slots.iter().flatten().map(|b| (&b.key, &b.value))
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (&K, &mut V)> {
+        self.slots
+            .iter_mut()
+            .flatten()
+            .map(|b| (&b.key, &mut b.value))
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.iter().map(|(k, _)| k)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        self.iter().map(|(_, v)| v)
+    }
+
+    pub fn clear(&mut self) {
+        self.slots.iter_mut().for_each(|slot| *slot = None);
+        self.len = 0;
+        self.shrink_if_sparse();
+    }
+
+    fn mask(&self) -> usize {
+        self.slots.len() - 1
+    }
+
+    /// How far the entry in slot `index` is from the slot its hash points at.
+    fn probe_distance(&self, index: usize, hash: u64) -> usize {
+        index.wrapping_sub(hash as usize) & self.mask()
+    }
+
+    /// The number of slots needed to hold `len` entries under the max load factor.
+    fn slots_for(&self, len: usize) -> usize {
+        ((len as f64 / self.max_load_factor.get()).ceil() as usize)
+            .max(MIN_SLOTS)
+            .next_power_of_two()
+    }
+
+    fn resize(&mut self, slots: usize) {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!(
+            "rehash",
+            from = self.slots.len(),
+            to = slots,
+            len = self.len
+        )
+        .entered();
+        let old = std::mem::replace(&mut self.slots, (0..slots).map(|_| None).collect());
+        for bucket in old.into_iter().flatten() {
+            self.place(bucket);
+        }
+    }
+
+    /// Places a bucket whose key is known to be absent, displacing entries closer to home.
+    /// Returns the slot it landed in.
+    fn place(&mut self, mut bucket: Bucket<K, V>) -> usize {
+        let mask = self.mask();
+        let mut index = bucket.hash as usize & mask;
+        let mut distance = 0;
+        // Set once the bucket has displaced a resident, which is carried on in its place
+        let mut placed = None;
+        loop {
+            match &mut self.slots[index] {
+                None => {
+                    self.slots[index] = Some(bucket);
+                    return placed.unwrap_or(index);
+                }
+                Some(resident) => {
+                    let resident_distance = index.wrapping_sub(resident.hash as usize) & mask;
+                    if resident_distance < distance {
+                        std::mem::swap(resident, &mut bucket);
+                        distance = resident_distance;
+                        placed.get_or_insert(index);
+                    }
+                }
+            }
+            index = (index + 1) & mask;
+            distance += 1;
+        }
+    }
+}
+
+impl<K, V, S: Default> Default for FlatHashMap<K, V, S> {
+    fn default() -> Self {
+        FlatHashMap::with_hasher(S::default())
+    }
+}
+
+impl<K, V, S> FlatHashMap<K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{
+    /// Inserts a value, returning the old one if the key was present.
+    pub fn insert(&mut self, k: K, v: V) -> Option<V> {
+        let hash = self.hasher.hash_one(&k);
+        if let Some(index) = self.find(hash, &k) {
+            let bucket = self.slots[index].as_mut().unwrap();
+            return Some(std::mem::replace(&mut bucket.value, v));
+        }
+        if self.len + 1 > self.capacity() {
+            self.resize(self.slots_for(self.len + 1));
+        }
+        self.place(Bucket {
+            hash,
+            key: k,
+            value: v,
+        });
+        self.len += 1;
+        None
+    }
+
+    /// Looks up the entry for a borrowed key, so that an owned key is only made from it
+    /// if a value is inserted.
+    pub fn entry_ref<'a, 'q, Q>(&'a mut self, k: &'q Q) -> EntryRef<'a, 'q, K, Q, V, S>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let hash = self.hasher.hash_one(k);
+        match self.find(hash, k) {
+            Some(index) => EntryRef::Occupied(OccupiedEntryRef {
+                map: self,
+                key: k,
+                index,
+            }),
+            None => EntryRef::Vacant(VacantEntryRef {
+                map: self,
+                key: k,
+                hash,
+            }),
+        }
+    }
+
+    pub fn get<Q>(&self, k: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let index = self.find(self.hasher.hash_one(k), k)?;
+        self.slots[index].as_ref().map(|b| &b.value)
+    }
+
+    pub fn get_mut<Q>(&mut self, k: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let index = self.find(self.hasher.hash_one(k), k)?;
+        self.slots[index].as_mut().map(|b| &mut b.value)
+    }
+
+    pub fn contains_key<Q>(&self, k: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.find(self.hasher.hash_one(k), k).is_some()
+    }
+
+    pub fn remove<Q>(&mut self, k: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let mut index = self.find(self.hasher.hash_one(k), k)?;
+        let removed = self.slots[index].take().unwrap();
+        // Shift the rest of the probe sequence back so that lookups never see a gap
+        loop {
+            let next = (index + 1) & self.mask();
+            match &self.slots[next] {
+                Some(b) if self.probe_distance(next, b.hash) > 0 => {
+                    self.slots[index] = self.slots[next].take();
+                    index = next;
+                }
+                _ => break,
+            }
+        }
+        self.len -= 1;
+        self.shrink_if_sparse();
+        Some(removed.value)
+    }
+
+    /// Looks up a batch of keys, returning their values in the order of `keys`.
+    /// The keys are probed in order of their home slots, so the table is walked from front
+    /// to back rather than visited at random.
+    pub fn get_many<Q>(&self, keys: &[&Q]) -> Vec<Option<&V>>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let mut out = vec![None; keys.len()];
+        for (index, position) in self.find_many(keys) {
+            out[position] = self.slots[index].as_ref().map(|b| &b.value);
+        }
+        out
+    }
+
+    /// Like `get_many`, but returns mutable references.
+    /// Panics if two of the keys refer to the same entry.
+    pub fn get_many_mut<Q>(&mut self, keys: &[&Q]) -> Vec<Option<&mut V>>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let mut found = self.find_many(keys);
+        found.sort_unstable();
+        assert!(
+            found.windows(2).all(|w| w[0].0 != w[1].0),
+            "keys must be distinct"
+        );
+        let mut out = (0..keys.len()).map(|_| None).collect::<Vec<_>>();
+        let slots = self.slots.as_mut_ptr();
+        for (index, position) in found {
+            // Every index is distinct, so the references don't alias
+            out[position] = unsafe { (*slots.add(index)).as_mut() }.map(|b| &mut b.value);
+        }
+        out
+    }
+
+    /// Returns the slot and position in `keys` of every key which is present.
+    fn find_many<Q>(&self, keys: &[&Q]) -> Vec<(usize, usize)>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        if self.slots.is_empty() {
+            return vec![];
+        }
+        let mut hashes = keys
+            .iter()
+            .enumerate()
+            .map(|(position, k)| (self.hasher.hash_one(k), position))
+            .collect::<Vec<_>>();
+        let mask = self.mask() as u64;
+        hashes.sort_unstable_by_key(|&(hash, _)| hash & mask);
+        hashes
+            .into_iter()
+            .filter_map(|(hash, position)| Some((self.find(hash, keys[position])?, position)))
+            .collect()
+    }
+
+    /// Returns the slot holding the key, if present.
+    fn find<Q>(&self, hash: u64, k: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+    {
+        if self.slots.is_empty() {
+            return None;
+        }
+        let mut index = hash as usize & self.mask();
+        let mut distance = 0;
+        loop {
+            match &self.slots[index] {
+                None => return None,
+                Some(b) => {
+                    // The key would have displaced any entry closer to its home slot
+                    if self.probe_distance(index, b.hash) < distance {
+                        return None;
+                    }
+                    if b.hash == hash && b.key.borrow() == k {
+                        return Some(index);
+                    }
+                }
+            }
+            index = (index + 1) & self.mask();
+            distance += 1;
+        }
+    }
+}
+
+impl<K, V, S> FromIterator<(K, V)> for FlatHashMap<K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher + Default,
+{
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut map = FlatHashMap::default();
+        map.extend(iter);
+        map
+    }
+}
+
+impl<K, V, S> Extend<(K, V)> for FlatHashMap<K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        for (k, v) in iter {
+            self.insert(k, v);
+        }
+    }
+}
+
+/// An entry of a `FlatHashMap` looked up by a borrowed key, as returned by `entry_ref`.
+pub enum EntryRef<'a, 'q, K, Q: ?Sized, V, S = RandomState> {
+    Occupied(OccupiedEntryRef<'a, 'q, K, Q, V, S>),
+    Vacant(VacantEntryRef<'a, 'q, K, Q, V, S>),
+}
+
+impl<'a, 'q, K, Q, V, S> EntryRef<'a, 'q, K, Q, V, S>
+where
+    K: Eq + Hash + Borrow<Q> + From<&'q Q>,
+    Q: Eq + Hash + ?Sized,
+    S: BuildHasher,
+{
+    pub fn key(&self) -> &'q Q {
+        match self {
+            EntryRef::Occupied(entry) => entry.key,
+            EntryRef::Vacant(entry) => entry.key,
+        }
+    }
+
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        self.or_insert_with(|| default)
+    }
+
+    pub fn or_insert_with<F: FnOnce() -> V>(self, default: F) -> &'a mut V {
+        match self {
+            EntryRef::Occupied(entry) => entry.into_mut(),
+            EntryRef::Vacant(entry) => entry.insert(default()),
+        }
+    }
+
+    pub fn or_default(self) -> &'a mut V
+    where
+        V: Default,
+    {
+        self.or_insert_with(V::default)
+    }
+
+    pub fn and_modify<F: FnOnce(&mut V)>(mut self, f: F) -> Self {
+        if let EntryRef::Occupied(entry) = &mut self {
+            f(entry.get_mut());
+        }
+        self
+    }
+}
+
+pub struct OccupiedEntryRef<'a, 'q, K, Q: ?Sized, V, S = RandomState> {
+    map: &'a mut FlatHashMap<K, V, S>,
+    key: &'q Q,
+    index: usize,
+}
+
+impl<'a, K, Q, V, S> OccupiedEntryRef<'a, '_, K, Q, V, S>
+where
+    K: Eq + Hash + Borrow<Q>,
+    Q: Eq + Hash + ?Sized,
+    S: BuildHasher,
+{
+    /// The key stored in the map.
+    pub fn key(&self) -> &K {
+        &self.bucket().key
+    }
+
+    pub fn get(&self) -> &V {
+        &self.bucket().value
+    }
+
+    pub fn get_mut(&mut self) -> &mut V {
+        &mut self.map.slots[self.index].as_mut().unwrap().value
+    }
+
+    pub fn into_mut(self) -> &'a mut V {
+        &mut self.map.slots[self.index].as_mut().unwrap().value
+    }
+
+    /// Replaces the value, returning the old one.
+    pub fn insert(&mut self, v: V) -> V {
+        std::mem::replace(self.get_mut(), v)
+    }
+
+    pub fn remove(self) -> V {
+        self.map.remove(self.key).unwrap()
+    }
+
+    fn bucket(&self) -> &Bucket<K, V> {
+        self.map.slots[self.index].as_ref().unwrap()
+    }
+}
+
+pub struct VacantEntryRef<'a, 'q, K, Q: ?Sized, V, S = RandomState> {
+    map: &'a mut FlatHashMap<K, V, S>,
+    key: &'q Q,
+    hash: u64,
+}
+
+impl<'a, 'q, K, Q, V, S> VacantEntryRef<'a, 'q, K, Q, V, S>
+where
+    K: From<&'q Q>,
+    Q: ?Sized,
+{
+    pub fn key(&self) -> &'q Q {
+        self.key
+    }
+
+    /// Makes the owned key and inserts the value, growing the table if needed.
+    pub fn insert(self, v: V) -> &'a mut V {
+        let map = self.map;
+        if map.len + 1 > map.capacity() {
+            map.resize(map.slots_for(map.len + 1));
+        }
+        let index = map.place(Bucket {
+            hash: self.hash,
+            key: K::from(self.key),
+            value: v,
+        });
+        map.len += 1;
+        &mut map.slots[index].as_mut().unwrap().value
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        collections::{hash_map::DefaultHasher, HashMap},
+        hash::BuildHasherDefault,
+    };
+
+    use quickcheck::quickcheck;
+
+    use super::{EntryRef, FlatHashMap, HashConfig, LoadFactor, ShrinkPolicy};
+
+    #[test]
+    fn fixed_hasher_is_deterministic() {
+        type Fixed = BuildHasherDefault<DefaultHasher>;
+        let a = (0..100)
+            .map(|i| (i, i))
+            .collect::<FlatHashMap<_, _, Fixed>>();
+        let mut b = FlatHashMap::with_hasher(Fixed::default());
+        for i in 0..100 {
+            b.insert(i, i);
+        }
+        assert!(a.iter().eq(b.iter()));
+    }
+
+    #[test]
+    fn get_many() {
+        let mut map = (0..100).map(|i| (i, i * 10)).collect::<FlatHashMap<_, _>>();
+        assert_eq!(
+            map.get_many(&[&5, &200, &5, &0]),
+            vec![Some(&50), None, Some(&50), Some(&0)]
+        );
+        for v in map.get_many_mut(&[&7, &300, &3]).into_iter().flatten() {
+            *v += 1;
+        }
+        assert_eq!(map.get(&7), Some(&71));
+        assert_eq!(map.get(&3), Some(&31));
+        assert_eq!(FlatHashMap::<u8, u8>::new().get_many(&[&1]), vec![None]);
+    }
+
+    #[test]
+    fn entry_ref() {
+        let mut map = FlatHashMap::<String, usize>::new();
+        // Enough new keys to grow the table while inserting through vacant entries
+        for word in "a b a c a b d e f g h i j".split(' ') {
+            *map.entry_ref(word).or_default() += 1;
+        }
+        assert_eq!(map.get("a"), Some(&3));
+        assert_eq!(map.get("j"), Some(&1));
+        assert_eq!(map.len(), 10);
+        match map.entry_ref("b") {
+            EntryRef::Occupied(mut entry) => {
+                assert_eq!(entry.key(), "b");
+                assert_eq!(entry.insert(5), 2);
+                assert_eq!(entry.remove(), 5);
+            }
+            EntryRef::Vacant(_) => panic!("b is present"),
+        }
+        assert!(!map.contains_key("b"));
+        map.entry_ref("z").and_modify(|v| *v = 0).or_insert(26);
+        assert_eq!(map.get("z"), Some(&26));
+    }
+
+    #[test]
+    fn prop_entry_ref_counts() {
+        fn p(keys: Vec<Vec<u8>>) -> bool {
+            let mut map = FlatHashMap::<Vec<u8>, usize>::new();
+            let mut expected = HashMap::new();
+            for k in &keys {
+                *map.entry_ref(k.as_slice()).or_insert(0) += 1;
+                *expected.entry(k.clone()).or_insert(0) += 1;
+            }
+            map.len() == expected.len() && expected.iter().all(|(k, v)| map.get(k) == Some(v))
+        }
+        quickcheck(p as fn(Vec<Vec<u8>>) -> bool);
+    }
+
+    #[test]
+    #[should_panic(expected = "keys must be distinct")]
+    fn get_many_mut_overlapping() {
+        let mut map = (0..10).map(|i| (i, i)).collect::<FlatHashMap<_, _>>();
+        map.get_many_mut(&[&1, &2, &1]);
+    }
+
+    #[test]
+    fn insert_get_remove() {
+        let mut map = FlatHashMap::new();
+        assert_eq!(map.insert("a", 1), None);
+        assert_eq!(map.insert("b", 2), None);
+        assert_eq!(map.insert("a", 3), Some(1));
+        assert_eq!(map.get("a"), Some(&3));
+        assert_eq!(map.remove("a"), Some(3));
+        assert_eq!(map.remove("a"), None);
+        assert_eq!(map.get("b"), Some(&2));
+        assert_eq!(map.len(), 1);
+    }
+
+    #[test]
+    fn grows() {
+        let mut map = FlatHashMap::with_capacity(4);
+        for i in 0..1000 {
+            map.insert(i, i * 2);
+        }
+        assert!((0..1000).all(|i| map.get(&i) == Some(&(i * 2))));
+        assert!(map.load_factor() <= map.max_load_factor());
+    }
+
+    #[test]
+    fn load_factor_control() {
+        let mut map = (0..100).map(|i| (i, i)).collect::<FlatHashMap<_, _>>();
+        map.set_max_load_factor(0.25);
+        assert!(map.load_factor() <= 0.25);
+        assert!(map.capacity() >= 100);
+        assert!((0..100).all(|i| map.contains_key(&i)));
+    }
+
+    #[test]
+    fn configured() {
+        let config = HashConfig::new()
+            .with_capacity(100)
+            .with_load_factor(LoadFactor::new(0.5))
+            .shrink_policy(ShrinkPolicy::BelowLoad(LoadFactor::new(0.1)))
+            .with_hasher(BuildHasherDefault::<DefaultHasher>::default());
+        let mut map = FlatHashMap::with_config(config);
+        let initial = map.capacity();
+        assert!(initial >= 100);
+        map.extend((0..100).map(|i| (i, i)));
+        assert_eq!(map.capacity(), initial);
+        map.extend((100..10_000).map(|i| (i, i)));
+        assert!(map.load_factor() <= 0.5);
+        for i in 10..10_000 {
+            map.remove(&i);
+        }
+        // Shrinking stops at the configured capacity
+        assert_eq!(map.capacity(), initial);
+        assert!((0..10).all(|i| map.get(&i) == Some(&i)));
+        map.clear();
+        assert_eq!(map.capacity(), initial);
+    }
+
+    #[test]
+    fn shrinks_explicitly() {
+        let mut map = (0..1000).map(|i| (i, i)).collect::<FlatHashMap<_, _>>();
+        assert_eq!(map.shrink_policy(), ShrinkPolicy::Never);
+        for i in 10..1000 {
+            map.remove(&i);
+        }
+        let grown = map.capacity();
+        map.shrink_to_fit();
+        assert!(map.capacity() < grown);
+        assert!(map.capacity() >= 10);
+        assert!((0..10).all(|i| map.contains_key(&i)));
+    }
+
+    #[test]
+    #[should_panic(expected = "shrink load factor must be less than half the max load factor")]
+    fn shrink_load_too_high() {
+        FlatHashMap::<u8, u8>::with_config(
+            HashConfig::new().shrink_policy(ShrinkPolicy::BelowLoad(LoadFactor::new(0.5))),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "max load factor must be in (0, 1]")]
+    fn invalid_load_factor() {
+        LoadFactor::new(1.5);
+    }
+
+    #[test]
+    fn prop_shrinking_matches_hash_map() {
+        fn p(ops: Vec<(u8, Option<u16>)>) -> bool {
+            let shrink = ShrinkPolicy::BelowLoad(LoadFactor::new(0.2));
+            let mut map = FlatHashMap::with_config(HashConfig::new().shrink_policy(shrink));
+            let mut model = HashMap::new();
+            for (k, v) in ops {
+                let agree = match v {
+                    Some(v) => map.insert(k, v) == model.insert(k, v),
+                    None => map.remove(&k) == model.remove(&k),
+                };
+                if !agree || map.load_factor() > map.max_load_factor() {
+                    return false;
+                }
+            }
+            map.len() == model.len() && model.iter().all(|(k, v)| map.get(k) == Some(v))
+        }
+        quickcheck(p as fn(Vec<(u8, Option<u16>)>) -> bool)
+    }
+
+    #[test]
+    fn prop_matches_hash_map() {
+        fn p(ops: Vec<(u8, Option<u16>)>) -> bool {
+            let mut map = FlatHashMap::new();
+            let mut model = HashMap::new();
+            for (k, v) in ops {
+                let agree = match v {
+                    Some(v) => map.insert(k, v) == model.insert(k, v),
+                    None => map.remove(&k) == model.remove(&k),
+                };
+                if !agree {
+                    return false;
+                }
+            }
+            map.len() == model.len() && model.iter().all(|(k, v)| map.get(k) == Some(v))
+        }
+        quickcheck(p as fn(Vec<(u8, Option<u16>)>) -> bool)
+    }
+}
@@ -1,206 +1,730 @@
-use core::hash::Hash;
-use std::collections::HashMap;
+use core::hash::{BuildHasher, Hash};
+use std::{
+    collections::{
+        hash_map::{self, RandomState},
+        HashMap,
+    },
+    fmt::{self, Debug},
+    slice,
+};
+
+#[cfg(feature = "viz")]
+use std::fmt::Write;
+
+#[cfg(feature = "viz")]
+use crate::viz::{self, Visualize};
+use crate::{
+    debug::DebugStructure,
+    footprint::{hash_map_bytes, MemoryFootprint},
+    sketch::BloomFilter,
+    traversal::TraversalStack,
+};
+
+// The fewest keys a `FilteredHashTrie` sizes its filter for
+const MIN_FILTER_CAPACITY: usize = 16;
 
 /// A trie that indexes keys by the hash of its constituent elements.
-#[derive(Debug, Clone)]
-pub struct HashTrie<K, V> {
+/// Every node hashes its children with a clone of the trie's `BuildHasher`.
+/// By default children are visited in hash order, which varies from one `RandomState` to
+/// the next; a trie created with insertion order also remembers the order each node's
+/// children were created in, and visits them in that order.
+/// Every operation walks the trie with loops or an explicit stack, so keys may be longer
+/// than the call stack is deep.
+pub struct HashTrie<K, V, S = RandomState> {
     key: Vec<K>,
     value: Option<V>,
-    children: HashMap<K, HashTrie<K, V>>,
+    children: HashMap<K, HashTrie<K, V, S>, S>,
+    // The keys of the children in the order they were created, if the trie keeps it
+    order: Option<Vec<K>>,
 }
 
 impl<K, V> HashTrie<K, V> {
     pub fn new() -> Self {
         HashTrie::default()
     }
+
+    /// Creates a trie which iterates in a deterministic order, visiting each node's children
+    /// in the order they were created. Removing a child costs time linear in the number of
+    /// its siblings.
+    pub fn with_insertion_order() -> Self {
+        HashTrie::with_insertion_order_and_hasher(RandomState::new())
+    }
 }
 
-impl<K, V> Default for HashTrie<K, V> {
-    fn default() -> Self {
+impl<K, V, S> HashTrie<K, V, S> {
+    pub fn with_hasher(hasher: S) -> Self {
         Self {
             key: vec![],
             value: None,
-            children: HashMap::new(),
+            children: HashMap::with_hasher(hasher),
+            order: None,
+        }
+    }
+
+    pub fn with_insertion_order_and_hasher(hasher: S) -> Self {
+        let mut trie = HashTrie::with_hasher(hasher);
+        trie.order = Some(vec![]);
+        trie
+    }
+
+    /// Whether the children are visited in the order they were created.
+    pub fn keeps_insertion_order(&self) -> bool {
+        self.order.is_some()
+    }
+
+    fn children(&self) -> Children<'_, K, V, S> {
+        match &self.order {
+            Some(order) => Children::Ordered(order.iter(), &self.children),
+            None => Children::Hashed(self.children.values()),
+        }
+    }
+
+    /// Calls `f` with every node of this subtrie, parents before their children.
+    fn for_each_node<'a>(&'a self, mut f: impl FnMut(&'a HashTrie<K, V, S>)) {
+        let mut stack = TraversalStack::new();
+        stack.push(self);
+        while let Some(trie) = stack.pop() {
+            f(trie);
+            stack.extend(trie.children.values());
         }
     }
+
+    /// The length of the longest path from this node down to another, which is as long as
+    /// the longest key below it once this node's own key is cut off.
+    pub fn max_depth(&self) -> usize {
+        let mut depth = 0;
+        self.for_each_node(|trie| depth = depth.max(trie.key.len()));
+        depth - self.key.len()
+    }
 }
 
-impl<K, V> HashTrie<K, V>
+impl<K, V, S> HashTrie<K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{
+    /// Returns the keys with the same ordering guarantee as `iter`, consuming the trie.
+    pub fn into_keys(self) -> impl Iterator<Item = Vec<K>> {
+        self.into_iter().map(|(k, _)| k)
+    }
+
+    /// Returns the values with the same ordering guarantee as `iter`, consuming the trie.
+    pub fn into_values(self) -> impl Iterator<Item = V> {
+        self.into_iter().map(|(_, v)| v)
+    }
+}
+
+impl<K, V, S: Default> Default for HashTrie<K, V, S> {
+    fn default() -> Self {
+        HashTrie::with_hasher(S::default())
+    }
+}
+
+impl<K, V, S> HashTrie<K, V, S>
 where
     K: Eq + Hash + Clone,
+    S: BuildHasher + Clone,
 {
     pub fn insert<P: AsRef<[K]>>(&mut self, key: P, value: V) -> Option<V> {
-        match key.as_ref() {
-            [first, rest @ ..] => match self.children.get_mut(first) {
-                Some(child) => child.insert(rest, value),
-                None => {
-                    let mut child = HashTrie::<K, V>::new();
-                    let mut child_key = self.key.clone();
-                    child_key.push(first.clone());
+        // Every node hashes with a clone of the same hasher
+        let hasher = self.children.hasher().clone();
+        let mut trie = self;
+        for k in key.as_ref() {
+            trie = match trie.children.entry(k.clone()) {
+                hash_map::Entry::Occupied(child) => child.into_mut(),
+                hash_map::Entry::Vacant(slot) => {
+                    let mut child = HashTrie::with_hasher(hasher.clone());
+                    let mut child_key = trie.key.clone();
+                    child_key.push(k.clone());
                     child.key = child_key;
-                    let ret = child.insert(rest, value);
-                    self.children.insert(first.clone(), child);
-                    ret
+                    if let Some(order) = &mut trie.order {
+                        order.push(k.clone());
+                        child.order = Some(vec![]);
+                    }
+                    slot.insert(child)
                 }
-            },
-            [] => self.value.replace(value),
+            };
         }
+        trie.value.replace(value)
     }
 
     pub fn get<P: AsRef<[K]>>(&self, key: P) -> Option<&V> {
-        match key.as_ref() {
-            [first, rest @ ..] => match self.children.get(first) {
-                Some(child) => child.get(rest),
-                None => None,
-            },
-            [] => self.value.as_ref(),
+        self.descendant(key.as_ref())?.value.as_ref()
+    }
+
+    /// Panics if a node's key isn't its parent's followed by the element leading to it, a
+    /// branch below the root holds no values, or a remembered order disagrees with the
+    /// children.
+    #[cfg(any(test, feature = "testing"))]
+    pub fn check_invariants(&self) {
+        let mut stack = TraversalStack::new();
+        stack.push(self);
+        while let Some(trie) = stack.pop() {
+            if let Some(order) = &trie.order {
+                assert_eq!(order.len(), trie.children.len(), "order is out of date");
+                assert!(
+                    trie.children.keys().all(|k| order.contains(k)),
+                    "order is out of date"
+                );
+            }
+            for (k, child) in &trie.children {
+                let (last, parent) = child.key.split_last().expect("child has an empty key");
+                assert!(
+                    last == k && parent == &trie.key[..],
+                    "child's key doesn't extend its parent's"
+                );
+                assert!(
+                    child.value.is_some() || !child.children.is_empty(),
+                    "branch holds no values"
+                );
+                assert_eq!(
+                    child.order.is_some(),
+                    trie.order.is_some(),
+                    "child disagrees about keeping insertion order"
+                );
+            }
+            stack.extend(trie.children.values());
         }
     }
 
+    /// The subtrie reached by following the key.
+    fn descendant(&self, key: &[K]) -> Option<&HashTrie<K, V, S>> {
+        key.iter().try_fold(self, |trie, k| trie.children.get(k))
+    }
+
+    /// The subtrie holding the keys which continue with the element.
+    pub fn child(&self, k: &K) -> Option<&HashTrie<K, V, S>> {
+        self.children.get(k)
+    }
+
+    /// The value stored at the root of this subtrie.
+    pub fn value(&self) -> Option<&V> {
+        self.value.as_ref()
+    }
+
     pub fn remove<P: AsRef<[K]>>(&mut self, key: P) -> Option<V> {
-        self.remove_internal(key).0
+        let key = key.as_ref();
+        let mut trie = &mut *self;
+        for k in key {
+            trie = trie.children.get_mut(k)?;
+        }
+        let removed = trie.value.take()?;
+        if trie.children.is_empty() && !key.is_empty() {
+            self.prune(key);
+        }
+        Some(removed)
     }
 
-    // TODO: is there a way to test that we are clearing out memory without creating a brittle test?
-    fn remove_internal<P: AsRef<[K]>>(&mut self, key: P) -> (Option<V>, bool) {
-        match key.as_ref() {
-            [first, rest @ ..] => match self.children.get_mut(first) {
-                Some(child) => {
-                    let (removed, empty) = child.remove_internal(rest);
-                    if empty {
-                        self.children.remove(first);
-                    }
-                    (removed, self.children.is_empty() && self.value.is_none())
-                }
-                None => (None, false),
-            },
-            [] => (self.value.take(), self.children.is_empty()),
+    /// Cuts off the branch of nodes left without values or other children at the end of
+    /// `key`, which must lead to a node with neither.
+    fn prune(&mut self, key: &[K]) {
+        // The branch hangs from the deepest node on the path with another reason to exist
+        let mut cut = 0;
+        let mut trie = &*self;
+        for (depth, k) in key.iter().enumerate() {
+            if trie.value.is_some() || trie.children.len() > 1 {
+                cut = depth;
+            }
+            trie = &trie.children[k];
+        }
+        let mut parent = self;
+        for k in &key[..cut] {
+            parent = parent.children.get_mut(k).unwrap();
+        }
+        parent.children.remove(&key[cut]);
+        if let Some(order) = &mut parent.order {
+            order.retain(|k| *k != key[cut]);
         }
     }
 
     /// This iterator provides only one ordering guarantee:
     /// Given A and B are the keys of two entries in the trie,
     /// A appears strictly before B if and only if A is a strict prefix of B.
-    pub fn iter<'a>(&'a self) -> Iter<'a, K, V> {
+    /// If the trie keeps insertion order, the order is also deterministic: entries are
+    /// visited depth first, each node's children in the order they were created.
+    pub fn iter<'a>(&'a self) -> Iter<'a, K, V, S> {
+        self.iter_from(0)
+    }
+
+    /// Iterates over this subtrie, cutting the first `skip` elements from every key.
+    fn iter_from<'a>(&'a self, skip: usize) -> Iter<'a, K, V, S> {
         Iter {
-            key: &self.key,
+            key: &self.key[skip..],
             value: self.value.as_ref(),
-            children: self.children.iter(),
+            children: Some(self.children()),
+            skip,
             parent: None,
         }
     }
 
-    pub fn keys<'a>(&'a self) -> Keys<'a, K, V> {
+    pub fn keys<'a>(&'a self) -> Keys<'a, K, V, S> {
         Keys { iter: self.iter() }
     }
 
-    pub fn values<'a>(&'a self) -> Values<'a, K, V> {
+    pub fn values<'a>(&'a self) -> Values<'a, K, V, S> {
         Values { iter: self.iter() }
     }
 
-    // TODO: convert to an iterator
-    pub fn keys_with_prefix<P: AsRef<[K]>>(&mut self, key: P) -> Vec<Vec<K>> {
-        self.entries_with_prefix(key)
-            .into_iter()
-            .map(|e| e.0)
-            .collect()
+    /// Returns an iterator over the entries whose keys start with `prefix`, yielding each
+    /// key without the prefix. It has the same ordering guarantee as `iter`.
+    pub fn entries_with_prefix<P: AsRef<[K]>>(&self, prefix: P) -> Iter<'_, K, V, S> {
+        let prefix = prefix.as_ref();
+        match self.descendant(prefix) {
+            Some(subtrie) => subtrie.iter_from(self.key.len() + prefix.len()),
+            None => Iter {
+                key: &[],
+                value: None,
+                children: None,
+                skip: 0,
+                parent: None,
+            },
+        }
     }
 
-    pub fn values_with_prefix<P: AsRef<[K]>>(&mut self, key: P) -> Vec<&V> {
-        self.entries_with_prefix(key)
-            .into_iter()
-            .map(|e| e.1)
-            .collect()
+    /// Returns the keys which start with `prefix`, without the prefix.
+    pub fn keys_with_prefix<P: AsRef<[K]>>(&self, prefix: P) -> Keys<'_, K, V, S> {
+        Keys {
+            iter: self.entries_with_prefix(prefix),
+        }
+    }
+
+    pub fn values_with_prefix<P: AsRef<[K]>>(&self, prefix: P) -> Values<'_, K, V, S> {
+        Values {
+            iter: self.entries_with_prefix(prefix),
+        }
+    }
+}
+
+/// A `HashTrie` with a Bloom filter over its keys in front of exact lookups, so that most
+/// lookups of absent keys are answered without walking the trie. The filter is rebuilt at
+/// twice the size once the keys outgrow it, and rebuilt once removed keys, which it cannot
+/// forget, outnumber the live ones. Prefix queries go through `trie`, unfiltered.
+#[derive(Debug)]
+pub struct FilteredHashTrie<K, V, S = RandomState> {
+    trie: HashTrie<K, V, S>,
+    filter: BloomFilter,
+    false_positive_rate: f64,
+    capacity: usize,
+    len: usize,
+    // Keys which have been removed from the trie but are still in the filter
+    removed: usize,
+}
+
+impl<K, V> FilteredHashTrie<K, V> {
+    /// Creates a trie whose lookups of absent keys reach the trie at most about
+    /// `false_positive_rate` of the time.
+    pub fn new(false_positive_rate: f64) -> Self {
+        FilteredHashTrie::with_hasher(false_positive_rate, RandomState::new())
+    }
+}
+
+impl<K, V, S> FilteredHashTrie<K, V, S> {
+    pub fn with_hasher(false_positive_rate: f64, hasher: S) -> Self {
+        FilteredHashTrie {
+            trie: HashTrie::with_hasher(hasher),
+            filter: BloomFilter::new(MIN_FILTER_CAPACITY, false_positive_rate),
+            false_positive_rate,
+            capacity: MIN_FILTER_CAPACITY,
+            len: 0,
+            removed: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn trie(&self) -> &HashTrie<K, V, S> {
+        &self.trie
+    }
+
+    pub fn filter(&self) -> &BloomFilter {
+        &self.filter
+    }
+
+    pub fn into_trie(self) -> HashTrie<K, V, S> {
+        self.trie
+    }
+}
+
+impl<K, V, S> Clone for FilteredHashTrie<K, V, S>
+where
+    K: Eq + Hash + Clone,
+    V: Clone,
+    S: BuildHasher + Clone,
+{
+    fn clone(&self) -> Self {
+        FilteredHashTrie {
+            trie: self.trie.clone(),
+            filter: self.filter.clone(),
+            false_positive_rate: self.false_positive_rate,
+            capacity: self.capacity,
+            len: self.len,
+            removed: self.removed,
+        }
+    }
+}
+
+impl<K, V, S> FilteredHashTrie<K, V, S>
+where
+    K: Eq + Hash + Clone,
+    S: BuildHasher + Clone,
+{
+    pub fn insert<P: AsRef<[K]>>(&mut self, key: P, value: V) -> Option<V> {
+        let key = key.as_ref();
+        let old = self.trie.insert(key, value);
+        if old.is_none() {
+            self.len += 1;
+            if self.len + self.removed > self.capacity {
+                self.rebuild_filter();
+            } else {
+                self.filter.insert(key);
+            }
+        }
+        old
+    }
+
+    pub fn get<P: AsRef<[K]>>(&self, key: P) -> Option<&V> {
+        let key = key.as_ref();
+        if !self.filter.contains(key) {
+            return None;
+        }
+        self.trie.get(key)
+    }
+
+    pub fn contains_key<P: AsRef<[K]>>(&self, key: P) -> bool {
+        self.get(key).is_some()
+    }
+
+    pub fn remove<P: AsRef<[K]>>(&mut self, key: P) -> Option<V> {
+        let key = key.as_ref();
+        if !self.filter.contains(key) {
+            return None;
+        }
+        let removed = self.trie.remove(key)?;
+        self.len -= 1;
+        self.removed += 1;
+        if self.removed > self.len {
+            self.rebuild_filter();
+        }
+        Some(removed)
+    }
+
+    fn rebuild_filter(&mut self) {
+        while self.len > self.capacity {
+            self.capacity *= 2;
+        }
+        self.filter = BloomFilter::new(self.capacity, self.false_positive_rate);
+        for (key, _) in self.trie.iter() {
+            self.filter.insert(key);
+        }
+        self.removed = 0;
+    }
+}
+
+impl<K, V, S> HashTrie<K, V, S> {
+    fn collect_entries<'a>(&'a self, acc: &mut Vec<(&'a [K], &'a V)>) {
+        self.for_each_node(|trie| {
+            if let Some(value) = &trie.value {
+                acc.push((&trie.key, value));
+            }
+        });
+    }
+
+    /// Copies this node's key, value and order, without its children.
+    fn clone_node(&self) -> Self
+    where
+        K: Clone,
+        V: Clone,
+        S: Clone,
+    {
+        HashTrie {
+            key: self.key.clone(),
+            value: self.value.clone(),
+            children: HashMap::with_capacity_and_hasher(
+                self.children.len(),
+                self.children.hasher().clone(),
+            ),
+            order: self.order.clone(),
+        }
+    }
+}
+
+/// Copies the trie a level at a time from the root down, without recursing.
+impl<K, V, S> Clone for HashTrie<K, V, S>
+where
+    K: Eq + Hash + Clone,
+    V: Clone,
+    S: BuildHasher + Clone,
+{
+    fn clone(&self) -> Self {
+        let mut root = self.clone_node();
+        let mut stack = TraversalStack::new();
+        stack.push((self, &mut root));
+        while let Some((trie, copy)) = stack.pop() {
+            let children = trie.children.iter();
+            copy.children
+                .extend(children.map(|(k, child)| (k.clone(), child.clone_node())));
+            stack.extend(
+                copy.children
+                    .iter_mut()
+                    .map(|(k, child_copy)| (&trie.children[k], child_copy)),
+            );
+        }
+        root
+    }
+}
+
+/// Detaches the children onto a stack before freeing them, so that dropping a trie with
+/// long keys doesn't recurse once per element.
+impl<K, V, S> Drop for HashTrie<K, V, S> {
+    fn drop(&mut self) {
+        let mut stack = TraversalStack::new();
+        stack.extend(self.children.drain().map(|(_, child)| child));
+        // Each subtrie is dropped with its children already detached
+        while let Some(mut trie) = stack.pop() {
+            stack.extend(trie.children.drain().map(|(_, child)| child));
+        }
     }
+}
 
-    pub fn entries_with_prefix<P: AsRef<[K]>>(&mut self, key: P) -> Vec<(Vec<K>, &V)> {
+/// Prints the entries as a map from keys to values. Keys are ordered by their formatted
+/// elements, so that the output doesn't depend on the hasher.
+impl<K: Debug, V: Debug, S> Debug for HashTrie<K, V, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let mut entries = vec![];
-        self.entries_with_prefix_internal(key.as_ref(), &mut entries);
+        self.collect_entries(&mut entries);
         entries
+            .sort_by_cached_key(|(k, _)| k.iter().map(|e| format!("{:?}", e)).collect::<Vec<_>>());
+        f.debug_map().entries(entries).finish()
     }
+}
 
-    fn entries_with_prefix_internal<'a>(&'a self, key: &[K], acc: &mut Vec<(Vec<K>, &'a V)>) {
-        match key {
-            [first, rest @ ..] => match self.children.get(first) {
-                Some(child) => {
-                    if let Some(value) = &self.value {
-                        acc.push((self.key.clone(), value));
-                    }
-                    child.entries_with_prefix_internal(rest, acc);
-                }
-                None => {}
-            },
-            [] => {
-                if let Some(value) = &self.value {
-                    acc.push((self.key.clone(), value));
-                }
-                for (key, child) in self.children.iter() {
-                    child.entries_with_prefix_internal(&[], acc);
-                }
-            }
-            _ => {}
+/// Prints every node with its key, value and children, including nodes without a value.
+impl<K: Debug, V: Debug, S> Debug for DebugStructure<'_, HashTrie<K, V, S>> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut children = self.0.children.iter().collect::<Vec<_>>();
+        children.sort_by_cached_key(|(k, _)| format!("{:?}", k));
+        let children = children
+            .into_iter()
+            .map(|(_, child)| DebugStructure(child))
+            .collect::<Vec<_>>();
+        f.debug_struct("HashTrie")
+            .field("key", &self.0.key)
+            .field("value", &self.0.value)
+            .field("children", &children)
+            .finish()
+    }
+}
+
+impl<K, V, S> MemoryFootprint for HashTrie<K, V, S> {
+    fn heap_bytes(&self) -> usize {
+        let mut bytes = 0;
+        self.for_each_node(|trie| {
+            bytes += (trie.key.capacity() + trie.order.as_ref().map_or(0, Vec::capacity))
+                * std::mem::size_of::<K>()
+                + hash_map_bytes(&trie.children);
+        });
+        bytes
+    }
+
+    /// Counts the nodes below the root, one for each distinct key prefix.
+    fn node_count(&self) -> usize {
+        let mut count = 0;
+        self.for_each_node(|_| count += 1);
+        count - 1
+    }
+}
+
+/// Labels each node with the last element of its key and any value stored there.
+/// Siblings are sorted by label so that the output doesn't depend on the hasher.
+#[cfg(feature = "viz")]
+impl<K, V, S> Visualize for HashTrie<K, V, S>
+where
+    K: Eq + Hash + std::fmt::Debug,
+    V: std::fmt::Debug,
+    S: BuildHasher,
+{
+    type Key = [K];
+
+    fn view(&self, search: Option<&[K]>) -> Option<viz::Node> {
+        if self.value.is_none() && self.children.is_empty() {
+            return None;
+        }
+        Some(self.view_node(search))
+    }
+}
+
+#[cfg(feature = "viz")]
+impl<K, V, S> HashTrie<K, V, S>
+where
+    K: Eq + Hash + std::fmt::Debug,
+    V: std::fmt::Debug,
+    S: BuildHasher,
+{
+    fn view_node(&self, search: Option<&[K]>) -> viz::Node {
+        let mut label = match self.key.last() {
+            Some(k) => format!("{:?}", k),
+            None => "(root)".to_string(),
+        };
+        if let Some(value) = &self.value {
+            write!(label, " = {:?}", value).unwrap();
+        }
+        let mut children = self
+            .children
+            .iter()
+            .map(|(k, child)| {
+                let search = match search {
+                    Some([first, rest @ ..]) if first == k => Some(rest),
+                    _ => None,
+                };
+                child.view_node(search)
+            })
+            .collect::<Vec<_>>();
+        children.sort_by(|a, b| a.label.cmp(&b.label));
+        viz::Node {
+            label,
+            highlighted: search.is_some(),
+            children: children.into_iter().map(Some).collect(),
         }
     }
 }
 
-pub struct Iter<'a, K, V> {
-    key: &'a Vec<K>,
+pub struct Iter<'a, K, V, S = RandomState> {
+    key: &'a [K],
     value: Option<&'a V>,
-    children: std::collections::hash_map::Iter<'a, K, HashTrie<K, V>>,
+    children: Option<Children<'a, K, V, S>>,
+    // The number of leading elements cut from each key
+    skip: usize,
     // Forms a stack leading to the root of the trie
-    parent: Option<Box<Iter<'a, K, V>>>,
+    parent: Option<Box<Iter<'a, K, V, S>>>,
 }
 
-impl<'a, K, V> Iterator for Iter<'a, K, V>
+impl<'a, K, V, S> Iterator for Iter<'a, K, V, S>
 where
     K: Eq + Hash + Clone,
+    S: BuildHasher + Clone,
 {
-    type Item = (&'a Vec<K>, &'a V);
+    type Item = (&'a [K], &'a V);
 
     fn next(&mut self) -> Option<Self::Item> {
-        match self.value.take() {
-            Some(v) => Some((&self.key, v)),
-            None => match self.children.next() {
-                Some((_, child)) => {
-                    let mut parent = child.iter();
+        loop {
+            if let Some(v) = self.value.take() {
+                return Some((self.key, v));
+            }
+            match self.children.as_mut().and_then(Iterator::next) {
+                Some(child) => {
+                    let mut parent = child.iter_from(self.skip);
                     std::mem::swap(&mut parent, self);
                     self.parent = Some(Box::new(parent));
-                    self.next()
                 }
-                None => match self.parent.take() {
-                    Some(mut p) => {
-                        std::mem::swap(p.as_mut(), self);
-                        self.next()
-                    }
-                    None => None,
-                },
-            },
+                None => {
+                    let mut parent = self.parent.take()?;
+                    std::mem::swap(parent.as_mut(), self);
+                }
+            }
+        }
+    }
+}
+
+/// The children of a node, in the order the trie visits them.
+enum Children<'a, K, V, S> {
+    Hashed(hash_map::Values<'a, K, HashTrie<K, V, S>>),
+    Ordered(slice::Iter<'a, K>, &'a HashMap<K, HashTrie<K, V, S>, S>),
+}
+
+impl<'a, K, V, S> Iterator for Children<'a, K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{
+    type Item = &'a HashTrie<K, V, S>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            Children::Hashed(values) => values.next(),
+            Children::Ordered(keys, children) => keys.next().map(|k| &children[k]),
+        }
+    }
+}
+
+impl<K, V, S> IntoIterator for HashTrie<K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{
+    type Item = (Vec<K>, V);
+    type IntoIter = IntoIter<K, V, S>;
+
+    fn into_iter(self) -> IntoIter<K, V, S> {
+        IntoIter { stack: vec![self] }
+    }
+}
+
+/// An iterator which moves the entries out of a `HashTrie`, with the same ordering
+/// guarantee as `iter`.
+pub struct IntoIter<K, V, S = RandomState> {
+    // Subtries which have yet to be visited
+    stack: Vec<HashTrie<K, V, S>>,
+}
+
+impl<K, V, S> Iterator for IntoIter<K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{
+    type Item = (Vec<K>, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let mut trie = self.stack.pop()?;
+            // Children are only visited after their parent has been yielded, and are pushed
+            // in reverse so that the first comes off the stack first
+            match trie.order.take() {
+                Some(order) => {
+                    let children = order.iter().rev().filter_map(|k| trie.children.remove(k));
+                    self.stack.extend(children);
+                }
+                None => {
+                    let children = trie.children.drain().map(|(_, child)| child);
+                    self.stack.extend(children);
+                }
+            }
+            if let Some(value) = trie.value.take() {
+                return Some((std::mem::take(&mut trie.key), value));
+            }
         }
     }
 }
 
-pub struct Keys<'a, K, V> {
-    iter: Iter<'a, K, V>,
+pub struct Keys<'a, K, V, S = RandomState> {
+    iter: Iter<'a, K, V, S>,
 }
 
-impl<'a, K, V> Iterator for Keys<'a, K, V>
+impl<'a, K, V, S> Iterator for Keys<'a, K, V, S>
 where
     K: Eq + Hash + Clone,
+    S: BuildHasher + Clone,
 {
-    type Item = &'a Vec<K>;
+    type Item = &'a [K];
 
     fn next(&mut self) -> Option<Self::Item> {
         self.iter.next().map(|x| x.0)
     }
 }
 
-pub struct Values<'a, K, V> {
-    iter: Iter<'a, K, V>,
+pub struct Values<'a, K, V, S = RandomState> {
+    iter: Iter<'a, K, V, S>,
 }
 
-impl<'a, K, V> Iterator for Values<'a, K, V>
+impl<'a, K, V, S> Iterator for Values<'a, K, V, S>
 where
     K: Eq + Hash + Clone,
+    S: BuildHasher + Clone,
 {
     type Item = &'a V;
 
@@ -211,9 +735,24 @@ where
 
 #[cfg(test)]
 mod test {
-    use std::collections::HashSet;
+    use std::{
+        collections::{hash_map::DefaultHasher, HashMap, HashSet},
+        hash::BuildHasherDefault,
+    };
+
+    use quickcheck::quickcheck;
 
-    use super::HashTrie;
+    use super::{FilteredHashTrie, HashTrie};
+    use crate::{debug::DebugStructure, footprint::MemoryFootprint};
+
+    #[test]
+    fn trie_with_hasher() {
+        let mut trie = HashTrie::with_hasher(BuildHasherDefault::<DefaultHasher>::default());
+        trie.insert("foo", 3);
+        trie.insert("foobar", 4);
+        assert_eq!(trie.get("foobar"), Some(&4));
+        assert_eq!(trie.remove("foo"), Some(3));
+    }
 
     #[test]
     fn trie_absent() {
@@ -259,6 +798,7 @@ mod test {
         trie.insert("foobar", 4);
         trie.remove("foobar");
         assert_eq!(trie.get("foo"), Some(&3));
+        trie.check_invariants();
     }
 
     #[test]
@@ -268,9 +808,87 @@ mod test {
         trie.insert("foobar", 4);
 
         let mut iter = trie.iter();
-        assert_eq!(iter.next(), Some((&"foo".to_string().into_bytes(), &3)));
-        assert_eq!(iter.next(), Some((&"foobar".to_string().into_bytes(), &4)));
+        assert_eq!(iter.next(), Some((&b"foo"[..], &3)));
+        assert_eq!(iter.next(), Some((&b"foobar"[..], &4)));
         assert_eq!(iter.next(), None);
+        let child = trie.child(&b'f').unwrap();
+        assert_eq!(child.keys().next(), Some(&b"foo"[..]));
+    }
+
+    #[test]
+    fn trie_into_iterator() {
+        let mut trie = HashTrie::new();
+        trie.insert("foo", 3);
+        trie.insert("foobar", 4);
+        trie.insert("bar", 5);
+        let entries = trie.clone().into_iter().collect::<Vec<_>>();
+        let position = |key: &[u8]| entries.iter().position(|(k, _)| k == key).unwrap();
+        assert!(position(b"foo") < position(b"foobar"));
+        assert_eq!(
+            entries.into_iter().collect::<HashMap<_, _>>(),
+            trie.iter().map(|(k, v)| (k.to_vec(), *v)).collect()
+        );
+        assert_eq!(
+            trie.clone().into_keys().collect::<HashSet<_>>(),
+            HashSet::from([b"foo".to_vec(), b"foobar".to_vec(), b"bar".to_vec()])
+        );
+        let mut values = trie.into_values().collect::<Vec<_>>();
+        values.sort();
+        assert_eq!(values, vec![3, 4, 5]);
+    }
+
+    #[test]
+    fn trie_insertion_order() {
+        let mut trie = HashTrie::with_insertion_order();
+        for key in ["b", "ba", "a", "bc", "c", "bb"] {
+            trie.insert(key, ());
+        }
+        let keys = |trie: &HashTrie<u8, ()>| {
+            trie.keys()
+                .map(|k| String::from_utf8(k.to_vec()).unwrap())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(keys(&trie), ["b", "ba", "bc", "bb", "a", "c"]);
+        // A child emptied by removal is forgotten, so it comes last once recreated
+        trie.remove("ba");
+        trie.insert("ba", ());
+        assert_eq!(keys(&trie), ["b", "bc", "bb", "ba", "a", "c"]);
+        assert_eq!(
+            trie.keys_with_prefix("b").collect::<Vec<_>>(),
+            [&b""[..], b"c", b"b", b"a"]
+        );
+        assert!(trie
+            .into_keys()
+            .eq(["b", "bc", "bb", "ba", "a", "c"].map(|k| k.as_bytes().to_vec())));
+    }
+
+    #[test]
+    fn prop_insertion_order_is_deterministic() {
+        fn p(ops: Vec<(bool, Vec<u8>)>) -> bool {
+            let mut a = HashTrie::with_insertion_order();
+            let mut b = HashTrie::with_insertion_order();
+            let mut model = HashMap::new();
+            for (insert, key) in ops {
+                if insert {
+                    a.insert(&key, key.len());
+                    b.insert(&key, key.len());
+                    model.insert(key.clone(), key.len());
+                } else {
+                    a.remove(&key);
+                    b.remove(&key);
+                    model.remove(&key);
+                }
+            }
+            a.check_invariants();
+            let entries = a.iter().map(|(k, v)| (k.to_vec(), *v)).collect::<Vec<_>>();
+            entries
+                .iter()
+                .cloned()
+                .eq(b.iter().map(|(k, v)| (k.to_vec(), *v)))
+                && entries.iter().cloned().eq(b)
+                && entries.into_iter().collect::<HashMap<_, _>>() == model
+        }
+        quickcheck(p as fn(Vec<(bool, Vec<u8>)>) -> bool);
     }
 
     #[test]
@@ -280,16 +898,125 @@ mod test {
         trie.insert("foobar", 4);
         trie.insert("foobaz", 5);
         assert_eq!(
-            trie.entries_with_prefix("foo")
-                .into_iter()
-                .collect::<HashSet<_>>(),
-            vec![
-                ("foo".to_string().into_bytes(), &3),
-                ("foobar".to_string().into_bytes(), &4),
-                ("foobaz".to_string().into_bytes(), &5)
-            ]
-            .into_iter()
-            .collect::<HashSet<_>>()
-        )
+            trie.entries_with_prefix("foo").collect::<HashSet<_>>(),
+            HashSet::from([(&b""[..], &3), (&b"bar"[..], &4), (&b"baz"[..], &5)])
+        );
+        // Keys are relative to the prefix, so shorter keys on the way to it are left out
+        assert_eq!(
+            trie.keys_with_prefix("foob").collect::<HashSet<_>>(),
+            HashSet::from([&b"ar"[..], &b"az"[..]])
+        );
+        let child = trie.child(&b'f').unwrap();
+        assert_eq!(
+            child.values_with_prefix("oobar").collect::<Vec<_>>(),
+            vec![&4]
+        );
+        assert_eq!(trie.entries_with_prefix("bar").next(), None);
+    }
+
+    #[test]
+    fn long_keys() {
+        let long = vec![7u8; 4096];
+        let mut trie = HashTrie::with_insertion_order();
+        trie.insert(&long, 1);
+        trie.insert(&long[..10], 2);
+        trie.insert([1, 2], 3);
+        assert_eq!(trie.max_depth(), long.len());
+        assert_eq!(trie.child(&7).unwrap().max_depth(), long.len() - 1);
+        let copy = trie.clone();
+        assert!(copy.iter().eq(trie.iter()));
+        assert_eq!(copy.node_count(), trie.node_count());
+        // Removing the long key cuts its branch back to the node holding the shorter one
+        assert_eq!(trie.remove(&long), Some(1));
+        assert_eq!(trie.max_depth(), 10);
+        assert_eq!(trie.node_count(), 12);
+        assert_eq!(trie.remove([1, 2]), Some(3));
+        assert_eq!(trie.node_count(), 10);
+        assert_eq!(copy.get(&long), Some(&1));
+    }
+
+    #[test]
+    fn footprint() {
+        let mut trie = HashTrie::new();
+        assert_eq!(trie.node_count(), 0);
+        trie.insert("tea", 1);
+        trie.insert("ten", 2);
+        trie.insert("to", 3);
+        // t, te, tea, ten, to
+        assert_eq!(trie.node_count(), 5);
+        let before = trie.heap_bytes();
+        trie.remove("to");
+        assert!(trie.heap_bytes() <= before);
+    }
+
+    #[test]
+    fn debug() {
+        let mut trie = HashTrie::new();
+        trie.insert(['b'], 2);
+        trie.insert(['a', 'b'], 3);
+        trie.insert(['a'], 1);
+        assert_eq!(format!("{:?}", trie), "{['a']: 1, ['a', 'b']: 3, ['b']: 2}");
+        let mut single = HashTrie::new();
+        single.insert(['a'], 1);
+        assert_eq!(
+            format!("{:?}", DebugStructure(&single)),
+            "HashTrie { key: [], value: None, children: \
+             [HashTrie { key: ['a'], value: Some(1), children: [] }] }"
+        );
+    }
+
+    #[test]
+    fn filtered_lookups() {
+        let mut trie = FilteredHashTrie::new(0.01);
+        for i in 0..1000u32 {
+            assert_eq!(trie.insert(i.to_be_bytes(), i), None);
+        }
+        assert_eq!(trie.insert(7u32.to_be_bytes(), 70), Some(7));
+        assert_eq!(trie.len(), 1000);
+        assert_eq!(trie.get(7u32.to_be_bytes()), Some(&70));
+        // Nearly every absent key is turned away by the filter
+        let reached = (1000..11_000u32)
+            .filter(|i| trie.filter().contains(&i.to_be_bytes()[..]))
+            .count();
+        assert!(reached < 200, "{} lookups reached the trie", reached);
+        assert!(!trie.contains_key(5000u32.to_be_bytes()));
+        // Prefix queries use the trie itself
+        assert_eq!(trie.trie().keys_with_prefix([0, 0, 0]).count(), 256);
+    }
+
+    #[test]
+    fn filtered_removal_rebuilds() {
+        let mut trie = FilteredHashTrie::new(0.01);
+        for i in 0..100u8 {
+            trie.insert([i], i);
+        }
+        for i in 0..60u8 {
+            assert_eq!(trie.remove([i]), Some(i));
+        }
+        assert_eq!(trie.remove([0]), None);
+        assert_eq!(trie.len(), 40);
+        // The filter was rebuilt once the removed keys outnumbered the rest
+        assert!(trie.filter().len() < 60);
+        assert!((60..100).all(|i| trie.get([i]) == Some(&i)));
+    }
+
+    #[test]
+    fn prop_filtered_matches_hash_map() {
+        fn p(ops: Vec<(bool, Vec<u8>)>) -> bool {
+            let mut trie = FilteredHashTrie::new(0.1);
+            let mut model = HashMap::new();
+            for (i, (insert, key)) in ops.iter().enumerate() {
+                let matches = if *insert {
+                    trie.insert(key, i) == model.insert(key.clone(), i)
+                } else {
+                    trie.remove(key) == model.remove(key)
+                };
+                if !matches || trie.len() != model.len() {
+                    return false;
+                }
+            }
+            ops.iter().all(|(_, key)| trie.get(key) == model.get(key))
+        }
+        quickcheck(p as fn(Vec<(bool, Vec<u8>)>) -> bool)
     }
 }
@@ -1,5 +1,10 @@
 use std::{cmp::Ordering, collections::VecDeque};
 
+use crate::capacity::Capacity;
+
+#[cfg(feature = "viz")]
+use crate::viz::{self, Visualize};
+
 #[derive(Debug, PartialEq, Clone, Default)]
 pub struct Heap<A> {
     inner: VecDeque<A>,
@@ -11,16 +16,38 @@ impl<A> Heap<A> {
             inner: VecDeque::new(),
         }
     }
+
+    /// Returns an iterator over the elements in an arbitrary order.
+    pub fn iter(&self) -> impl Iterator<Item = &A> {
+        self.inner.iter()
+    }
 }
 
 impl<A> Heap<A>
 where
     A: Ord,
 {
+    /// Panics if a child is smaller than its parent.
+    #[cfg(any(test, feature = "testing"))]
+    pub fn check_invariants(&self) {
+        for i in 1..self.inner.len() {
+            assert!(
+                self.inner[(i - 1) / 2] <= self.inner[i],
+                "heap property broken at {}",
+                i
+            );
+        }
+    }
+
     pub fn size(&self) -> usize {
         self.inner.len()
     }
 
+    /// Returns the smallest element without removing it.
+    pub fn peek(&self) -> Option<&A> {
+        self.inner.front()
+    }
+
     pub fn pop(&mut self) -> Option<A> {
         let head = self.inner.swap_remove_back(0);
         if head.is_some() {
@@ -35,67 +62,238 @@ where
     }
 
     fn sift_down(&mut self) {
-        if self.inner.len() <= 1 {
+        // Children compare as `Less` to a larger parent, so the smallest element rises
+        sift_down_by(self.inner.make_contiguous(), 0, &mut |a, b| a > b);
+    }
+
+    fn sift_up(&mut self) {
+        let len = self.inner.len();
+        if len <= 1 {
             return;
         }
 
-        let mut index = 0;
-        loop {
-            let mut lowest = self.inner.get(index).unwrap();
-            let mut new_index = index;
-            let first_child = 2 * index + 1;
-            let second_child = 2 * index + 1;
-            if let Some(value) = self.inner.get(first_child) {
-                if value.cmp(lowest) == Ordering::Less {
-                    lowest = value;
-                    new_index = first_child;
-                }
-            }
-            if let Some(value) = self.inner.get(second_child) {
-                if value.cmp(lowest) == Ordering::Less {
-                    new_index = second_child;
-                }
-            }
-
-            if new_index != index {
-                self.inner.swap(new_index, index);
-                index = new_index;
+        let mut index = len - 1;
+        while index > 0 {
+            let parent = (index - 1) / 2;
+            if self.inner[index].cmp(&self.inner[parent]) == Ordering::Less {
+                self.inner.swap(parent, index);
+                index = parent;
             } else {
                 break;
             }
         }
     }
+}
 
-    fn sift_up(&mut self) {
-        let len = self.inner.len();
-        if len <= 1 {
-            return;
+/// A heap which keeps only the largest elements that fit within its capacity, dropping the
+/// smallest to make room, so that pushing a stream through it selects the top elements.
+/// `peek` and `pop` return the smallest element kept.
+/// Under a byte budget, room is made greedily: smaller elements are dropped until the new
+/// one fits or the smallest left is at least as large as it, in which case the new one is
+/// dropped too.
+#[derive(Debug, Clone)]
+pub struct BoundedHeap<A> {
+    heap: Heap<Weighted<A>>,
+    capacity: Capacity,
+    // The total weight of the elements, which is their number under an entry budget
+    used: usize,
+    weigher: fn(&A) -> usize,
+}
+
+// An element with its weight, so that it isn't weighed again when it's popped. Ordered by
+// the element alone.
+#[derive(Debug, Clone)]
+struct Weighted<A> {
+    value: A,
+    weight: usize,
+}
+
+impl<A: PartialEq> PartialEq for Weighted<A> {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+    }
+}
+
+impl<A: Eq> Eq for Weighted<A> {}
+
+impl<A: Ord> PartialOrd for Weighted<A> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<A: Ord> Ord for Weighted<A> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.value.cmp(&other.value)
+    }
+}
+
+impl<A: Ord> BoundedHeap<A> {
+    /// Creates a heap which keeps the `capacity` largest elements.
+    pub fn new(capacity: usize) -> Self {
+        BoundedHeap::with_budget(Capacity::Entries(capacity), |_| 1)
+    }
+
+    /// Creates a heap which keeps the largest elements whose weights, as measured by
+    /// `weigher`, sum to at most `max_bytes`.
+    pub fn weighted(max_bytes: usize, weigher: fn(&A) -> usize) -> Self {
+        BoundedHeap::with_budget(Capacity::Bytes(max_bytes), weigher)
+    }
+
+    fn with_budget(capacity: Capacity, weigher: fn(&A) -> usize) -> Self {
+        BoundedHeap {
+            heap: Heap::new(),
+            capacity,
+            used: 0,
+            weigher,
         }
+    }
 
-        let mut index = len - 1;
-        loop {
-            let current = self.inner.get(index).unwrap();
-            let mut new_index = index;
-            let parent = index / 2;
-            if let Some(value) = self.inner.get(parent) {
-                if current.cmp(value) == Ordering::Less {
-                    new_index = parent;
+    pub fn len(&self) -> usize {
+        self.heap.size()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.heap.size() == 0
+    }
+
+    /// The size of the budget, in elements or bytes.
+    pub fn capacity(&self) -> usize {
+        self.capacity.limit()
+    }
+
+    pub fn budget(&self) -> Capacity {
+        self.capacity
+    }
+
+    /// How much of the budget is left before pushing drops anything.
+    pub fn remaining_capacity(&self) -> usize {
+        self.capacity.remaining(self.used)
+    }
+
+    /// Returns the smallest element kept.
+    pub fn peek(&self) -> Option<&A> {
+        self.heap.peek().map(|entry| &entry.value)
+    }
+
+    pub fn pop(&mut self) -> Option<A> {
+        let entry = self.heap.pop()?;
+        self.used -= entry.weight;
+        Some(entry.value)
+    }
+
+    /// Adds the element, returning the elements dropped to stay within the budget from
+    /// smallest to largest. That includes the new element if it's too small to be kept.
+    pub fn push(&mut self, a: A) -> Vec<A> {
+        let weight = self.capacity.weigh(|| (self.weigher)(&a));
+        // Too heavy to fit even in an empty heap, so there's no use dropping anything
+        if !self.capacity.fits(0, weight) {
+            return vec![a];
+        }
+        let mut dropped = vec![];
+        while !self.capacity.fits(self.used, weight) {
+            match self.peek() {
+                Some(smallest) if *smallest < a => dropped.push(self.pop().unwrap()),
+                _ => {
+                    dropped.push(a);
+                    return dropped;
                 }
             }
+        }
+        self.used += weight;
+        self.heap.push(Weighted { value: a, weight });
+        dropped
+    }
 
-            if new_index != index {
-                self.inner.swap(new_index, index);
-                index = new_index;
-            } else {
-                break;
-            }
+    /// Returns the elements kept from largest to smallest.
+    pub fn into_sorted_vec(mut self) -> Vec<A> {
+        let mut sorted = std::iter::from_fn(|| self.pop()).collect::<Vec<_>>();
+        sorted.reverse();
+        sorted
+    }
+}
+
+/// Restores the max-heap property, as ordered by `is_less`, to the implicit binary tree
+/// under `root`, whose subtrees must already be heaps. The element at `root` is sifted
+/// bottom-up: the path of larger children is followed to a leaf and then climbed back to
+/// where the element belongs, which takes about half the comparisons of sifting top-down
+/// since that element usually belongs near the bottom.
+pub(crate) fn sift_down_by<T>(v: &mut [T], root: usize, is_less: &mut impl FnMut(&T, &T) -> bool) {
+    if root >= v.len() {
+        return;
+    }
+    let mut leaf = root;
+    loop {
+        let mut child = 2 * leaf + 1;
+        if child >= v.len() {
+            break;
         }
+        if child + 1 < v.len() && is_less(&v[child], &v[child + 1]) {
+            child += 1;
+        }
+        leaf = child;
+    }
+    while is_less(&v[leaf], &v[root]) {
+        leaf = (leaf - 1) / 2;
+    }
+    // Shift the path between them up a level, putting the root's element in its place
+    let mut index = leaf;
+    while index > root {
+        v.swap(root, index);
+        index = (index - 1) / 2;
+    }
+}
+
+#[cfg(feature = "viz")]
+impl<A: Ord + std::fmt::Debug> Heap<A> {
+    /// Finds an index holding `a`, skipping subtrees whose root is already larger.
+    fn position(&self, a: &A, index: usize) -> Option<usize> {
+        match self.inner.get(index)?.cmp(a) {
+            Ordering::Equal => Some(index),
+            Ordering::Greater => None,
+            Ordering::Less => self
+                .position(a, 2 * index + 1)
+                .or_else(|| self.position(a, 2 * index + 2)),
+        }
+    }
+
+    fn view_at(&self, index: usize, path: &[usize]) -> viz::Node {
+        viz::Node {
+            label: format!("{:?}", self.inner[index]),
+            highlighted: path.contains(&index),
+            children: [2 * index + 1, 2 * index + 2]
+                .into_iter()
+                .filter(|&child| child < self.inner.len())
+                .map(|child| Some(self.view_at(child, path)))
+                .collect(),
+        }
+    }
+}
+
+/// Draws the implicit binary tree, where the children of index `i` are `2i + 1` and `2i + 2`.
+/// The search path leads from the root to an element equal to the key.
+#[cfg(feature = "viz")]
+impl<A: Ord + std::fmt::Debug> Visualize for Heap<A> {
+    type Key = A;
+
+    fn view(&self, search: Option<&A>) -> Option<viz::Node> {
+        if self.inner.is_empty() {
+            return None;
+        }
+        let mut path = vec![];
+        let mut found = search.and_then(|a| self.position(a, 0));
+        while let Some(index) = found {
+            path.push(index);
+            found = index.checked_sub(1).map(|i| i / 2);
+        }
+        Some(self.view_at(0, &path))
     }
 }
 
 #[cfg(test)]
 mod test {
-    use super::Heap;
+    use super::{BoundedHeap, Heap};
+    use quickcheck::quickcheck;
 
     #[test]
     fn push_and_pop() {
@@ -133,6 +331,79 @@ mod test {
         assert_eq!(heap.size(), 1);
     }
 
+    #[test]
+    fn prop_pop_ascending_order() {
+        fn p(input: Vec<i32>) -> bool {
+            let mut heap = Heap::new();
+            for i in input.iter() {
+                heap.push(*i);
+            }
+            let mut sorted = input;
+            sorted.sort();
+            let mut popped = vec![];
+            while let Some(i) = heap.pop() {
+                popped.push(i);
+            }
+            popped == sorted
+        }
+        quickcheck(p as fn(Vec<i32>) -> bool)
+    }
+
+    #[test]
+    fn bounded_keeps_largest() {
+        let mut heap = BoundedHeap::new(3);
+        assert_eq!(heap.remaining_capacity(), 3);
+        for i in [5, 1, 4] {
+            assert!(heap.push(i).is_empty());
+        }
+        assert_eq!(heap.push(2), vec![1]);
+        assert_eq!(heap.push(0), vec![0]);
+        assert_eq!(heap.remaining_capacity(), 0);
+        assert_eq!(heap.peek(), Some(&2));
+        assert_eq!(heap.into_sorted_vec(), vec![5, 4, 2]);
+        assert_eq!(BoundedHeap::new(0).push(1), vec![1]);
+    }
+
+    #[test]
+    fn bounded_by_weight() {
+        let mut heap = BoundedHeap::weighted(10, |s: &String| s.len());
+        heap.push("b".repeat(4));
+        heap.push("a".repeat(4));
+        assert_eq!(heap.remaining_capacity(), 2);
+        // Both shorter strings sort before it, so both go to make room
+        assert_eq!(heap.push("c".repeat(7)), vec!["a".repeat(4), "b".repeat(4)]);
+        assert_eq!(heap.push("0".repeat(5)), vec!["0".repeat(5)]);
+        assert_eq!(heap.pop(), Some("c".repeat(7)));
+        assert_eq!(heap.remaining_capacity(), 10);
+    }
+
+    #[test]
+    fn bounded_drops_oversized() {
+        let mut heap = BoundedHeap::weighted(10, |s: &String| s.len());
+        heap.push("a".repeat(4));
+        heap.push("b".repeat(4));
+        // Larger than everything kept, but it can't fit even alone, so nothing is dropped
+        assert_eq!(heap.push("z".repeat(11)), vec!["z".repeat(11)]);
+        assert_eq!(heap.len(), 2);
+        assert_eq!(heap.remaining_capacity(), 2);
+    }
+
+    #[test]
+    fn prop_bounded_matches_sort() {
+        fn p(input: Vec<i32>, capacity: u8) -> bool {
+            let capacity = capacity as usize % 16;
+            let mut heap = BoundedHeap::new(capacity);
+            for &i in &input {
+                heap.push(i);
+            }
+            let mut sorted = input;
+            sorted.sort_by(|a, b| b.cmp(a));
+            sorted.truncate(capacity);
+            heap.into_sorted_vec() == sorted
+        }
+        quickcheck(p as fn(Vec<i32>, u8) -> bool)
+    }
+
     ///////////////////////
     // PRIVATE API TESTS //
     ///////////////////////
@@ -0,0 +1,510 @@
+use std::{
+    cmp::Ordering,
+    fmt::{self, Debug},
+    hash::{Hash, Hasher},
+};
+
+use crate::{debug::DebugStructure, footprint::MemoryFootprint};
+
+// Marks the absence of a link, so no node can live at this index
+const NIL: u32 = u32::MAX;
+
+/// A doubly linked list whose nodes live in a single `Vec` and link to each other by `u32`
+/// index, rather than being allocated one by one. It supports the same constant time
+/// operations as `LinkedList`, through handles which are checked against the slot's
+/// generation, so a stale handle is never mistaken for a later node in the same slot.
+/// The slots of removed nodes are reused, and the list holds at most `u32::MAX - 1` nodes.
+#[derive(Clone)]
+pub struct IndexList<A> {
+    slots: Vec<Slot<A>>,
+    head: u32,
+    tail: u32,
+    // The first empty slot, whose `next` leads to the rest of them
+    free: u32,
+    len: usize,
+}
+
+#[derive(Clone)]
+struct Slot<A> {
+    value: Option<A>,
+    prev: u32,
+    next: u32,
+    // Bumped every time the slot is emptied
+    generation: u32,
+}
+
+/// A handle to a particular node in an `IndexList`, for removing it or moving it to the
+/// head in constant time. It is stale once the node is removed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct IndexListHandle {
+    index: u32,
+    generation: u32,
+}
+
+impl<A> IndexList<A> {
+    pub fn new() -> Self {
+        IndexList::with_capacity(0)
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        IndexList {
+            slots: Vec::with_capacity(capacity),
+            head: NIL,
+            tail: NIL,
+            free: NIL,
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the newest element, which `pop_head` would remove.
+    pub fn head(&self) -> Option<&A> {
+        self.value(self.head)
+    }
+
+    /// Returns the oldest element, which `pop_tail` would remove.
+    pub fn tail(&self) -> Option<&A> {
+        self.value(self.tail)
+    }
+
+    pub fn get(&self, handle: IndexListHandle) -> Option<&A> {
+        let index = self.resolve(handle)?;
+        self.value(index)
+    }
+
+    pub fn get_mut(&mut self, handle: IndexListHandle) -> Option<&mut A> {
+        let index = self.resolve(handle)?;
+        self.slots[index as usize].value.as_mut()
+    }
+
+    /// Whether the handle's node is still in the list.
+    pub fn contains(&self, handle: IndexListHandle) -> bool {
+        self.resolve(handle).is_some()
+    }
+
+    pub fn push_head(&mut self, a: A) -> IndexListHandle {
+        let handle = self.allocate(a);
+        self.link_head(handle.index);
+        handle
+    }
+
+    pub fn push_tail(&mut self, a: A) -> IndexListHandle {
+        let handle = self.allocate(a);
+        let index = handle.index;
+        self.slots[index as usize].prev = self.tail;
+        match self.tail {
+            NIL => self.head = index,
+            tail => self.slots[tail as usize].next = index,
+        }
+        self.tail = index;
+        handle
+    }
+
+    pub fn pop_head(&mut self) -> Option<A> {
+        self.remove_at(self.head)
+    }
+
+    pub fn pop_tail(&mut self) -> Option<A> {
+        self.remove_at(self.tail)
+    }
+
+    /// Removes the handle's node, returning its element, or `None` if the handle is stale.
+    pub fn remove(&mut self, handle: IndexListHandle) -> Option<A> {
+        let index = self.resolve(handle)?;
+        self.remove_at(index)
+    }
+
+    /// Moves the handle's node to the head, keeping the handle valid. Returns `false` if the
+    /// handle is stale.
+    pub fn move_to_head(&mut self, handle: IndexListHandle) -> bool {
+        match self.resolve(handle) {
+            Some(index) => {
+                if index != self.head {
+                    self.unlink(index);
+                    self.link_head(index);
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        while self.pop_tail().is_some() {}
+    }
+
+    /// Calls `f` on each element from head to tail.
+    pub fn for_each(&self, f: impl FnMut(&A)) {
+        self.iter().for_each(f);
+    }
+
+    /// Returns an iterator over the elements from head to tail.
+    pub fn iter(&self) -> Iter<'_, A> {
+        Iter {
+            list: self,
+            front: self.head,
+            back: self.tail,
+            len: self.len,
+        }
+    }
+
+    fn value(&self, index: u32) -> Option<&A> {
+        self.slots.get(index as usize)?.value.as_ref()
+    }
+
+    fn resolve(&self, handle: IndexListHandle) -> Option<u32> {
+        let node = self.slots.get(handle.index as usize)?;
+        (node.generation == handle.generation && node.value.is_some()).then_some(handle.index)
+    }
+
+    /// Puts the element in an empty slot, without linking it into the list.
+    fn allocate(&mut self, a: A) -> IndexListHandle {
+        self.len += 1;
+        let index = match self.free {
+            NIL => {
+                assert!(
+                    self.slots.len() < NIL as usize,
+                    "index list cannot hold more than u32::MAX - 1 elements"
+                );
+                self.slots.push(Slot {
+                    value: None,
+                    prev: NIL,
+                    next: NIL,
+                    generation: 0,
+                });
+                (self.slots.len() - 1) as u32
+            }
+            free => {
+                self.free = self.slots[free as usize].next;
+                free
+            }
+        };
+        let node = &mut self.slots[index as usize];
+        node.value = Some(a);
+        node.prev = NIL;
+        node.next = NIL;
+        IndexListHandle {
+            index,
+            generation: node.generation,
+        }
+    }
+
+    fn link_head(&mut self, index: u32) {
+        let node = &mut self.slots[index as usize];
+        node.prev = NIL;
+        node.next = self.head;
+        match self.head {
+            NIL => self.tail = index,
+            head => self.slots[head as usize].prev = index,
+        }
+        self.head = index;
+    }
+
+    fn unlink(&mut self, index: u32) {
+        let Slot { prev, next, .. } = self.slots[index as usize];
+        match prev {
+            NIL => self.head = next,
+            prev => self.slots[prev as usize].next = next,
+        }
+        match next {
+            NIL => self.tail = prev,
+            next => self.slots[next as usize].prev = prev,
+        }
+    }
+
+    fn remove_at(&mut self, index: u32) -> Option<A> {
+        if index == NIL {
+            return None;
+        }
+        self.unlink(index);
+        self.len -= 1;
+        let node = &mut self.slots[index as usize];
+        node.generation = node.generation.wrapping_add(1);
+        node.next = self.free;
+        self.free = index;
+        node.value.take()
+    }
+
+    /// Panics if the links disagree with each other, the length or the free list.
+    #[cfg(any(test, feature = "testing"))]
+    pub fn check_invariants(&self) {
+        let mut prev = NIL;
+        let mut index = self.head;
+        let mut linked = 0;
+        while index != NIL {
+            let node = &self.slots[index as usize];
+            assert!(node.value.is_some(), "slot {} is linked but empty", index);
+            assert_eq!(
+                node.prev, prev,
+                "slot {} links back to the wrong node",
+                index
+            );
+            prev = index;
+            index = node.next;
+            linked += 1;
+        }
+        assert_eq!(self.tail, prev, "tail is out of date");
+        assert_eq!(linked, self.len, "length is out of date");
+        let mut index = self.free;
+        let mut free = 0;
+        while index != NIL {
+            assert!(self.slots[index as usize].value.is_none());
+            index = self.slots[index as usize].next;
+            free += 1;
+        }
+        assert_eq!(
+            free + self.len,
+            self.slots.len(),
+            "free list is out of date"
+        );
+    }
+}
+
+impl<A> Default for IndexList<A> {
+    fn default() -> Self {
+        IndexList::new()
+    }
+}
+
+impl<A> Extend<A> for IndexList<A> {
+    /// Pushes the elements onto the tail in order.
+    fn extend<I: IntoIterator<Item = A>>(&mut self, iter: I) {
+        for a in iter {
+            self.push_tail(a);
+        }
+    }
+}
+
+impl<A> FromIterator<A> for IndexList<A> {
+    fn from_iter<I: IntoIterator<Item = A>>(iter: I) -> Self {
+        let mut list = IndexList::new();
+        list.extend(iter);
+        list
+    }
+}
+
+impl<'a, A> IntoIterator for &'a IndexList<A> {
+    type Item = &'a A;
+    type IntoIter = Iter<'a, A>;
+
+    fn into_iter(self) -> Iter<'a, A> {
+        self.iter()
+    }
+}
+
+/// An iterator over the elements of an `IndexList` from head to tail.
+pub struct Iter<'a, A> {
+    list: &'a IndexList<A>,
+    front: u32,
+    back: u32,
+    // The elements between `front` and `back` inclusive
+    len: usize,
+}
+
+impl<'a, A> Iterator for Iter<'a, A> {
+    type Item = &'a A;
+
+    fn next(&mut self) -> Option<&'a A> {
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+        let node = &self.list.slots[self.front as usize];
+        self.front = node.next;
+        node.value.as_ref()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<A> DoubleEndedIterator for Iter<'_, A> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+        let node = &self.list.slots[self.back as usize];
+        self.back = node.prev;
+        node.value.as_ref()
+    }
+}
+
+impl<A> ExactSizeIterator for Iter<'_, A> {}
+
+impl<A> MemoryFootprint for IndexList<A> {
+    /// Every slot is counted, including those which are free to be reused.
+    fn heap_bytes(&self) -> usize {
+        self.slots.capacity() * std::mem::size_of::<Slot<A>>()
+    }
+
+    fn node_count(&self) -> usize {
+        self.len
+    }
+}
+
+/// Prints the elements from head to tail.
+impl<A: Debug> Debug for IndexList<A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+/// Lists are compared by their elements from head to tail.
+impl<A: PartialEq> PartialEq for IndexList<A> {
+    fn eq(&self, other: &Self) -> bool {
+        self.iter().eq(other.iter())
+    }
+}
+
+impl<A: Eq> Eq for IndexList<A> {}
+
+impl<A: PartialOrd> PartialOrd for IndexList<A> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.iter().partial_cmp(other.iter())
+    }
+}
+
+impl<A: Ord> Ord for IndexList<A> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.iter().cmp(other.iter())
+    }
+}
+
+impl<A: Hash> Hash for IndexList<A> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // Prefixing the length keeps nested lists from colliding
+        self.len.hash(state);
+        self.iter().for_each(|a| a.hash(state));
+    }
+}
+
+/// Prints every node from head to tail along with the elements its links point to, in the
+/// same form as a `LinkedList`.
+impl<A: Debug> Debug for DebugStructure<'_, IndexList<A>> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let list = self.0;
+        let mut nodes = f.debug_list();
+        let mut index = list.head;
+        while index != NIL {
+            nodes.entry(&NodeRef { list, index });
+            index = list.slots[index as usize].next;
+        }
+        nodes.finish()
+    }
+}
+
+struct NodeRef<'a, A> {
+    list: &'a IndexList<A>,
+    index: u32,
+}
+
+impl<A: Debug> Debug for NodeRef<'_, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let slot = &self.list.slots[self.index as usize];
+        f.debug_struct("Node")
+            .field("key", &slot.value.as_ref().unwrap())
+            .field("prev", &self.list.value(slot.prev))
+            .field("next", &self.list.value(slot.next))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::VecDeque;
+
+    use quickcheck::quickcheck;
+
+    use super::IndexList;
+    use crate::debug::DebugStructure;
+
+    #[test]
+    fn handles_survive_moves() {
+        let mut list = IndexList::new();
+        let a = list.push_head('a');
+        let b = list.push_head('b');
+        list.push_tail('c');
+        assert!(list.iter().eq(&['b', 'a', 'c']));
+        assert!(list.move_to_head(a));
+        assert!(list.iter().rev().eq(&['c', 'b', 'a']));
+        assert_eq!(list.remove(b), Some('b'));
+        // The freed slot is reused, but the old handle stays stale
+        let d = list.push_head('d');
+        assert_eq!(list.remove(b), None);
+        assert!(!list.move_to_head(b));
+        assert_eq!(list.get(d), Some(&'d'));
+        assert_eq!(list.pop_tail(), Some('c'));
+        assert_eq!(list.pop_head(), Some('d'));
+        assert_eq!(format!("{:?}", list), "['a']");
+        list.check_invariants();
+    }
+
+    #[test]
+    fn debug_structure() {
+        let list = IndexList::from_iter([1, 2]);
+        assert_eq!(
+            format!("{:?}", DebugStructure(&list)),
+            "[Node { key: 1, prev: None, next: Some(2) }, \
+             Node { key: 2, prev: Some(1), next: None }]"
+        );
+    }
+
+    #[test]
+    fn prop_matches_vec_deque() {
+        fn p(ops: Vec<(u8, u8)>) -> bool {
+            let mut list = IndexList::new();
+            let mut model = VecDeque::new();
+            // Every element is distinct, so the model can find the one a handle refers to
+            let mut handles = vec![];
+            for (id, (op, pick)) in ops.into_iter().enumerate() {
+                let picked = handles.get(pick as usize % (handles.len() + 1)).copied();
+                let agrees = match (op % 6, picked) {
+                    (0, _) => {
+                        handles.push((list.push_head(id), id));
+                        model.push_front(id);
+                        true
+                    }
+                    (1, _) => {
+                        handles.push((list.push_tail(id), id));
+                        model.push_back(id);
+                        true
+                    }
+                    (2, _) => list.pop_head() == model.pop_front(),
+                    (3, _) => list.pop_tail() == model.pop_back(),
+                    (4, Some((handle, id))) => {
+                        let live = model.contains(&id);
+                        model.retain(|&x| x != id);
+                        list.remove(handle) == live.then_some(id)
+                    }
+                    (_, Some((handle, id))) => {
+                        let live = model.contains(&id);
+                        if live {
+                            model.retain(|&x| x != id);
+                            model.push_front(id);
+                        }
+                        list.move_to_head(handle) == live
+                    }
+                    _ => true,
+                };
+                if !agrees {
+                    return false;
+                }
+                list.check_invariants();
+                if !list.iter().eq(model.iter()) || !list.iter().rev().eq(model.iter().rev()) {
+                    return false;
+                }
+            }
+            true
+        }
+        quickcheck(p as fn(Vec<(u8, u8)>) -> bool);
+    }
+}
@@ -0,0 +1,637 @@
+use std::{
+    borrow::Borrow,
+    collections::{hash_map::RandomState, HashMap},
+    hash::{BuildHasher, Hash},
+    ops::Index,
+};
+
+/// A hash map which iterates in insertion order. Entries live in a vector and a
+/// hash table maps each key to its position, so entries can also be addressed by index.
+/// Keys are stored twice, once in each structure, so they must be `Clone`.
+#[derive(Debug, Clone)]
+pub struct IndexMap<K, V, S = RandomState> {
+    indices: HashMap<K, usize, S>,
+    entries: Vec<(K, V)>,
+}
+
+impl<K, V> IndexMap<K, V> {
+    pub fn new() -> Self {
+        IndexMap::with_hasher(RandomState::new())
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        IndexMap::with_capacity_and_hasher(capacity, RandomState::new())
+    }
+}
+
+impl<K, V, S> IndexMap<K, V, S> {
+    pub fn with_hasher(hasher: S) -> Self {
+        IndexMap {
+            indices: HashMap::with_hasher(hasher),
+            entries: vec![],
+        }
+    }
+
+    pub fn with_capacity_and_hasher(capacity: usize, hasher: S) -> Self {
+        IndexMap {
+            indices: HashMap::with_capacity_and_hasher(capacity, hasher),
+            entries: Vec::with_capacity(capacity),
+        }
+    }
+
+    pub fn hasher(&self) -> &S {
+        self.indices.hasher()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn get_index(&self, index: usize) -> Option<(&K, &V)> {
+        self.entries.get(index).map(|(k, v)| (k, v))
+    }
+
+    pub fn get_index_mut(&mut self, index: usize) -> Option<(&K, &mut V)> {
+        self.entries.get_mut(index).map(|(k, v)| (&*k, v))
+    }
+
+    pub fn first(&self) -> Option<(&K, &V)> {
+        self.get_index(0)
+    }
+
+    pub fn last(&self) -> Option<(&K, &V)> {
+        self.entries.last().map(|(k, v)| (k, v))
+    }
+
+    /// Returns an iterator over the entries in insertion order.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = (&K, &V)> + ExactSizeIterator {
+        self.entries.iter().map(|(k, v)| (k, v))
+    }
+
+    pub fn iter_mut(
+        &mut self,
+    ) -> impl DoubleEndedIterator<Item = (&K, &mut V)> + ExactSizeIterator {
+        self.entries.iter_mut().map(|(k, v)| (&*k, v))
+    }
+
+    pub fn keys(&self) -> impl DoubleEndedIterator<Item = &K> + ExactSizeIterator {
+        self.entries.iter().map(|(k, _)| k)
+    }
+
+    pub fn values(&self) -> impl DoubleEndedIterator<Item = &V> + ExactSizeIterator {
+        self.entries.iter().map(|(_, v)| v)
+    }
+
+    pub fn into_keys(self) -> impl DoubleEndedIterator<Item = K> + ExactSizeIterator {
+        self.entries.into_iter().map(|(k, _)| k)
+    }
+
+    pub fn into_values(self) -> impl DoubleEndedIterator<Item = V> + ExactSizeIterator {
+        self.entries.into_iter().map(|(_, v)| v)
+    }
+
+    pub fn clear(&mut self) {
+        self.indices.clear();
+        self.entries.clear();
+    }
+}
+
+impl<K, V, S: Default> Default for IndexMap<K, V, S> {
+    fn default() -> Self {
+        IndexMap::with_hasher(S::default())
+    }
+}
+
+impl<K, V, S> IndexMap<K, V, S>
+where
+    K: Eq + Hash + Clone,
+    S: BuildHasher + Clone,
+{
+    /// Panics if the hash table doesn't map every key to its position.
+    #[cfg(any(test, feature = "testing"))]
+    pub fn check_invariants(&self) {
+        assert_eq!(self.indices.len(), self.entries.len(), "lengths differ");
+        for (i, (k, _)) in self.entries.iter().enumerate() {
+            assert_eq!(self.indices.get(k), Some(&i), "index out of date");
+        }
+    }
+
+    /// Inserts a value, returning the old one if the key was present.
+    /// Replacing a value keeps the key's original position.
+    pub fn insert(&mut self, k: K, v: V) -> Option<V> {
+        self.insert_full(k, v).1
+    }
+
+    /// Like `insert`, but also returns the index of the entry.
+    pub fn insert_full(&mut self, k: K, v: V) -> (usize, Option<V>) {
+        match self.indices.get(&k) {
+            Some(&index) => (
+                index,
+                Some(std::mem::replace(&mut self.entries[index].1, v)),
+            ),
+            None => {
+                let index = self.entries.len();
+                self.indices.insert(k.clone(), index);
+                self.entries.push((k, v));
+                (index, None)
+            }
+        }
+    }
+
+    /// Looks up the entry for a borrowed key, so that an owned key is only made from it
+    /// if a value is inserted.
+    pub fn entry_ref<'a, 'q, Q>(&'a mut self, k: &'q Q) -> EntryRef<'a, 'q, K, Q, V, S>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        match self.indices.get(k) {
+            Some(&index) => EntryRef::Occupied(OccupiedEntryRef {
+                map: self,
+                key: k,
+                index,
+            }),
+            None => EntryRef::Vacant(VacantEntryRef { map: self, key: k }),
+        }
+    }
+
+    pub fn get<Q>(&self, k: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let index = *self.indices.get(k)?;
+        Some(&self.entries[index].1)
+    }
+
+    pub fn get_mut<Q>(&mut self, k: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let index = *self.indices.get(k)?;
+        Some(&mut self.entries[index].1)
+    }
+
+    pub fn get_index_of<Q>(&self, k: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.indices.get(k).copied()
+    }
+
+    pub fn contains_key<Q>(&self, k: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.indices.contains_key(k)
+    }
+
+    /// Looks up a batch of keys, returning their values in the order of `keys`.
+    /// The entries are read in index order once every key has been resolved.
+    pub fn get_many<Q>(&self, keys: &[&Q]) -> Vec<Option<&V>>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let mut out = vec![None; keys.len()];
+        for (index, position) in self.find_many(keys) {
+            out[position] = Some(&self.entries[index].1);
+        }
+        out
+    }
+
+    /// Like `get_many`, but returns mutable references.
+    /// Panics if two of the keys refer to the same entry.
+    pub fn get_many_mut<Q>(&mut self, keys: &[&Q]) -> Vec<Option<&mut V>>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let found = self.find_many(keys);
+        assert!(
+            found.windows(2).all(|w| w[0].0 != w[1].0),
+            "keys must be distinct"
+        );
+        let mut out = (0..keys.len()).map(|_| None).collect::<Vec<_>>();
+        let entries = self.entries.as_mut_ptr();
+        for (index, position) in found {
+            // Every index is distinct, so the references don't alias
+            out[position] = Some(unsafe { &mut (*entries.add(index)).1 });
+        }
+        out
+    }
+
+    /// Returns the index and position in `keys` of every key which is present, by index.
+    fn find_many<Q>(&self, keys: &[&Q]) -> Vec<(usize, usize)>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let mut found = keys
+            .iter()
+            .enumerate()
+            .filter_map(|(position, k)| Some((*self.indices.get(*k)?, position)))
+            .collect::<Vec<_>>();
+        found.sort_unstable();
+        found
+    }
+
+    /// Removes the entry in O(1) by moving the last entry into its place.
+    /// This disturbs the order of the map.
+    pub fn swap_remove<Q>(&mut self, k: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let index = self.indices.remove(k)?;
+        let (_, v) = self.entries.swap_remove(index);
+        if let Some((moved, _)) = self.entries.get(index) {
+            *self.indices.get_mut::<K>(moved).unwrap() = index;
+        }
+        Some(v)
+    }
+
+    /// Removes the entry in O(n) by shifting every later entry down.
+    /// This preserves the order of the map.
+    pub fn shift_remove<Q>(&mut self, k: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let index = self.indices.remove(k)?;
+        let (_, v) = self.entries.remove(index);
+        for (i, (k, _)) in self.entries.iter().enumerate().skip(index) {
+            *self.indices.get_mut::<K>(k).unwrap() = i;
+        }
+        Some(v)
+    }
+
+    pub fn pop(&mut self) -> Option<(K, V)> {
+        let (k, v) = self.entries.pop()?;
+        self.indices.remove(&k);
+        Some((k, v))
+    }
+
+    /// Sorts the entries by key, keeping lookups valid.
+    pub fn sort_keys(&mut self)
+    where
+        K: Ord,
+    {
+        self.entries.sort_by(|a, b| a.0.cmp(&b.0));
+        self.reindex();
+    }
+
+    /// Keeps only the entries for which the predicate returns true, preserving order.
+    pub fn retain<F: FnMut(&K, &mut V) -> bool>(&mut self, mut f: F) {
+        self.entries.retain_mut(|(k, v)| f(k, v));
+        self.reindex();
+    }
+
+    fn reindex(&mut self) {
+        let hasher = self.indices.hasher().clone();
+        let mut indices = HashMap::with_capacity_and_hasher(self.entries.len(), hasher);
+        for (i, (k, _)) in self.entries.iter().enumerate() {
+            indices.insert(k.clone(), i);
+        }
+        self.indices = indices;
+    }
+}
+
+impl<K, V, S> Index<usize> for IndexMap<K, V, S> {
+    type Output = V;
+
+    fn index(&self, index: usize) -> &V {
+        &self.entries[index].1
+    }
+}
+
+impl<K, V, S> PartialEq for IndexMap<K, V, S>
+where
+    K: Eq + Hash,
+    V: PartialEq,
+    S: BuildHasher,
+{
+    /// Maps are equal when they hold the same entries, regardless of order.
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len()
+            && self.entries.iter().all(|(k, v)| {
+                other
+                    .indices
+                    .get(k)
+                    .is_some_and(|&i| other.entries[i].1 == *v)
+            })
+    }
+}
+
+impl<K, V, S> Eq for IndexMap<K, V, S>
+where
+    K: Eq + Hash,
+    V: Eq,
+    S: BuildHasher,
+{
+}
+
+impl<K, V, S> FromIterator<(K, V)> for IndexMap<K, V, S>
+where
+    K: Eq + Hash + Clone,
+    S: BuildHasher + Clone + Default,
+{
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut map = IndexMap::default();
+        map.extend(iter);
+        map
+    }
+}
+
+impl<K, V, S> Extend<(K, V)> for IndexMap<K, V, S>
+where
+    K: Eq + Hash + Clone,
+    S: BuildHasher + Clone,
+{
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        for (k, v) in iter {
+            self.insert(k, v);
+        }
+    }
+}
+
+impl<K, V, S> IntoIterator for IndexMap<K, V, S> {
+    type Item = (K, V);
+    type IntoIter = std::vec::IntoIter<(K, V)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.into_iter()
+    }
+}
+
+/// An entry of an `IndexMap` looked up by a borrowed key, as returned by `entry_ref`.
+pub enum EntryRef<'a, 'q, K, Q: ?Sized, V, S = RandomState> {
+    Occupied(OccupiedEntryRef<'a, 'q, K, Q, V, S>),
+    Vacant(VacantEntryRef<'a, 'q, K, Q, V, S>),
+}
+
+impl<'a, 'q, K, Q, V, S> EntryRef<'a, 'q, K, Q, V, S>
+where
+    K: Eq + Hash + Clone + Borrow<Q> + From<&'q Q>,
+    Q: Eq + Hash + ?Sized,
+    S: BuildHasher + Clone,
+{
+    pub fn key(&self) -> &'q Q {
+        match self {
+            EntryRef::Occupied(entry) => entry.key,
+            EntryRef::Vacant(entry) => entry.key,
+        }
+    }
+
+    /// The position the entry has, or will have once inserted.
+    pub fn index(&self) -> usize {
+        match self {
+            EntryRef::Occupied(entry) => entry.index,
+            EntryRef::Vacant(entry) => entry.index(),
+        }
+    }
+
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        self.or_insert_with(|| default)
+    }
+
+    pub fn or_insert_with<F: FnOnce() -> V>(self, default: F) -> &'a mut V {
+        match self {
+            EntryRef::Occupied(entry) => entry.into_mut(),
+            EntryRef::Vacant(entry) => entry.insert(default()),
+        }
+    }
+
+    pub fn or_default(self) -> &'a mut V
+    where
+        V: Default,
+    {
+        self.or_insert_with(V::default)
+    }
+
+    pub fn and_modify<F: FnOnce(&mut V)>(mut self, f: F) -> Self {
+        if let EntryRef::Occupied(entry) = &mut self {
+            f(entry.get_mut());
+        }
+        self
+    }
+}
+
+pub struct OccupiedEntryRef<'a, 'q, K, Q: ?Sized, V, S = RandomState> {
+    map: &'a mut IndexMap<K, V, S>,
+    key: &'q Q,
+    index: usize,
+}
+
+impl<'a, K, Q, V, S> OccupiedEntryRef<'a, '_, K, Q, V, S>
+where
+    K: Eq + Hash + Clone + Borrow<Q>,
+    Q: Eq + Hash + ?Sized,
+    S: BuildHasher + Clone,
+{
+    /// The key stored in the map.
+    pub fn key(&self) -> &K {
+        &self.map.entries[self.index].0
+    }
+
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    pub fn get(&self) -> &V {
+        &self.map.entries[self.index].1
+    }
+
+    pub fn get_mut(&mut self) -> &mut V {
+        &mut self.map.entries[self.index].1
+    }
+
+    pub fn into_mut(self) -> &'a mut V {
+        &mut self.map.entries[self.index].1
+    }
+
+    /// Replaces the value, returning the old one. The entry keeps its position.
+    pub fn insert(&mut self, v: V) -> V {
+        std::mem::replace(self.get_mut(), v)
+    }
+
+    /// Removes the entry like `IndexMap::swap_remove`.
+    pub fn swap_remove(self) -> V {
+        self.map.swap_remove(self.key).unwrap()
+    }
+
+    /// Removes the entry like `IndexMap::shift_remove`.
+    pub fn shift_remove(self) -> V {
+        self.map.shift_remove(self.key).unwrap()
+    }
+}
+
+pub struct VacantEntryRef<'a, 'q, K, Q: ?Sized, V, S = RandomState> {
+    map: &'a mut IndexMap<K, V, S>,
+    key: &'q Q,
+}
+
+impl<'a, 'q, K, Q, V, S> VacantEntryRef<'a, 'q, K, Q, V, S>
+where
+    K: Eq + Hash + Clone + From<&'q Q>,
+    Q: ?Sized,
+    S: BuildHasher,
+{
+    pub fn key(&self) -> &'q Q {
+        self.key
+    }
+
+    /// The position the entry will have once inserted, at the end of the map.
+    pub fn index(&self) -> usize {
+        self.map.entries.len()
+    }
+
+    /// Makes the owned key and appends the entry.
+    pub fn insert(self, v: V) -> &'a mut V {
+        let index = self.map.entries.len();
+        let k = K::from(self.key);
+        self.map.indices.insert(k.clone(), index);
+        self.map.entries.push((k, v));
+        &mut self.map.entries[index].1
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use quickcheck::quickcheck;
+
+    use super::{EntryRef, IndexMap};
+
+    #[test]
+    fn insertion_order() {
+        let mut map = IndexMap::new();
+        map.insert("c", 1);
+        map.insert("a", 2);
+        map.insert("b", 3);
+        assert_eq!(map.insert("a", 4), Some(2));
+        assert_eq!(map.keys().copied().collect::<Vec<_>>(), vec!["c", "a", "b"]);
+        assert_eq!(map.get_index(1), Some((&"a", &4)));
+        assert_eq!(map.get_index_of("b"), Some(2));
+        assert_eq!(map[0], 1);
+    }
+
+    #[test]
+    fn into_keys_and_values() {
+        let map = [("c", 1), ("a", 2), ("b", 3)]
+            .into_iter()
+            .collect::<IndexMap<_, _>>();
+        assert_eq!(
+            map.clone().into_keys().rev().collect::<Vec<_>>(),
+            vec!["b", "a", "c"]
+        );
+        assert_eq!(map.into_values().collect::<Vec<_>>(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn get_many() {
+        let mut map = (0..10).map(|i| (i, i * 10)).collect::<IndexMap<_, _>>();
+        assert_eq!(
+            map.get_many(&[&9, &20, &1]),
+            vec![Some(&90), None, Some(&10)]
+        );
+        if let [Some(a), Some(b)] = &mut map.get_many_mut(&[&2, &3])[..] {
+            std::mem::swap(*a, *b);
+        }
+        assert_eq!(map.get(&2), Some(&30));
+        assert_eq!(map.get(&3), Some(&20));
+    }
+
+    #[test]
+    #[should_panic(expected = "keys must be distinct")]
+    fn get_many_mut_overlapping() {
+        let mut map = (0..10).map(|i| (i, i)).collect::<IndexMap<_, _>>();
+        map.get_many_mut(&[&1, &1]);
+    }
+
+    #[test]
+    fn swap_remove() {
+        let mut map = (0..5).map(|i| (i, i)).collect::<IndexMap<_, _>>();
+        assert_eq!(map.swap_remove(&1), Some(1));
+        assert_eq!(map.keys().copied().collect::<Vec<_>>(), vec![0, 4, 2, 3]);
+        assert_eq!(map.get(&4), Some(&4));
+        assert_eq!(map.get_index_of(&4), Some(1));
+        assert_eq!(map.swap_remove(&1), None);
+    }
+
+    #[test]
+    fn shift_remove() {
+        let mut map = (0..5).map(|i| (i, i)).collect::<IndexMap<_, _>>();
+        assert_eq!(map.shift_remove(&1), Some(1));
+        assert_eq!(map.keys().copied().collect::<Vec<_>>(), vec![0, 2, 3, 4]);
+        assert_eq!(map.get_index_of(&4), Some(3));
+    }
+
+    #[test]
+    fn entry_ref() {
+        let mut map = IndexMap::<String, usize>::new();
+        for word in ["b", "a", "b", "c", "b"] {
+            *map.entry_ref(word).or_default() += 1;
+        }
+        assert_eq!(map.keys().cloned().collect::<Vec<_>>(), ["b", "a", "c"]);
+        assert_eq!(map.get("b"), Some(&3));
+        assert_eq!(map.entry_ref("d").index(), 3);
+        match map.entry_ref("a") {
+            EntryRef::Occupied(mut entry) => {
+                assert_eq!((entry.key().as_str(), entry.index()), ("a", 1));
+                assert_eq!(entry.insert(10), 1);
+                assert_eq!(entry.shift_remove(), 10);
+            }
+            EntryRef::Vacant(_) => panic!("a is present"),
+        }
+        assert_eq!(map.get_index_of("c"), Some(1));
+        map.check_invariants();
+    }
+
+    #[test]
+    fn sort_and_retain() {
+        let mut map = [(3, 'c'), (1, 'a'), (2, 'b')]
+            .into_iter()
+            .collect::<IndexMap<_, _>>();
+        map.sort_keys();
+        assert_eq!(map.values().copied().collect::<String>(), "abc");
+        map.retain(|k, _| *k != 2);
+        assert_eq!(map.get_index_of(&3), Some(1));
+    }
+
+    #[test]
+    fn prop_matches_hash_map() {
+        fn p(ops: Vec<(u8, Option<u8>)>) -> bool {
+            let mut map = IndexMap::new();
+            let mut model = HashMap::new();
+            let mut order = vec![];
+            for (k, v) in ops {
+                match v {
+                    Some(v) => {
+                        if model.insert(k, v).is_none() {
+                            order.push(k);
+                        }
+                        map.insert(k, v);
+                    }
+                    None => {
+                        order.retain(|o| *o != k);
+                        if map.shift_remove(&k) != model.remove(&k) {
+                            return false;
+                        }
+                    }
+                }
+            }
+            map.keys().copied().eq(order.iter().copied())
+                && order.iter().all(|k| map.get(k) == model.get(k))
+        }
+        quickcheck(p as fn(Vec<(u8, Option<u8>)>) -> bool)
+    }
+}
@@ -0,0 +1,393 @@
+use std::{cmp::Ordering, ops::Range};
+
+/// An interval tree stores possibly overlapping half-open ranges and finds every range
+/// containing a point or overlapping another range in O(log n + k) time.
+/// It is an AVL tree ordered by range start, where each node is augmented with
+/// the largest range end in its subtree so that queries can skip whole subtrees.
+type Link<K, V> = Option<Box<Node<K, V>>>;
+
+#[derive(Debug, Clone)]
+pub struct IntervalTree<K, V> {
+    root: Link<K, V>,
+    len: usize,
+}
+
+#[derive(Debug, Clone)]
+struct Node<K, V> {
+    range: Range<K>,
+    value: V,
+    max_end: K,
+    height: usize,
+    left: Link<K, V>,
+    right: Link<K, V>,
+}
+
+impl<K, V> IntervalTree<K, V> {
+    pub fn new() -> Self {
+        IntervalTree { root: None, len: 0 }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl<K, V> Default for IntervalTree<K, V> {
+    fn default() -> Self {
+        IntervalTree::new()
+    }
+}
+
+impl<K, V> IntervalTree<K, V>
+where
+    K: Ord + Clone,
+{
+    /// Adds a range to the tree. Equal ranges may be inserted more than once.
+    pub fn insert(&mut self, range: Range<K>, value: V) {
+        self.root = Some(insert(self.root.take(), range, value));
+        self.len += 1;
+    }
+
+    /// Removes one occurrence of exactly this range, returning its value.
+    pub fn remove(&mut self, range: &Range<K>) -> Option<V> {
+        let (root, removed) = remove(self.root.take(), range);
+        self.root = root;
+        if removed.is_some() {
+            self.len -= 1;
+        }
+        removed
+    }
+
+    /// Returns every range which contains the point, ordered by range start.
+    pub fn query_point(&self, point: &K) -> Overlaps<'_, K, V> {
+        self.overlaps_internal(point.clone(), None)
+    }
+
+    /// Returns every range which overlaps the query range, ordered by range start.
+    pub fn query_overlaps(&self, range: &Range<K>) -> Overlaps<'_, K, V> {
+        self.overlaps_internal(range.start.clone(), Some(range.end.clone()))
+    }
+
+    fn overlaps_internal(&self, start: K, end: Option<K>) -> Overlaps<'_, K, V> {
+        Overlaps {
+            stack: vec![],
+            cursor: self.root.as_deref(),
+            start,
+            end,
+        }
+    }
+
+    /// Returns every range in the tree, ordered by range start.
+    pub fn iter(&self) -> impl Iterator<Item = (&Range<K>, &V)> {
+        let mut stack = vec![];
+        let mut cursor = self.root.as_deref();
+        std::iter::from_fn(move || {
+            while let Some(node) = cursor {
+                stack.push(node);
+                cursor = node.left.as_deref();
+            }
+            let node = stack.pop()?;
+            cursor = node.right.as_deref();
+            Some((&node.range, &node.value))
+        })
+    }
+}
+
+/// An in-order iterator over the ranges overlapping a query.
+#[derive(Debug)]
+pub struct Overlaps<'a, K, V> {
+    stack: Vec<&'a Node<K, V>>,
+    cursor: Option<&'a Node<K, V>>,
+    start: K,
+    // A point query is represented as an inclusive query with no end
+    end: Option<K>,
+}
+
+impl<K, V> Overlaps<'_, K, V>
+where
+    K: Ord,
+{
+    /// Whether a range starting at `start` begins at or after the end of the query.
+    fn past_end(&self, start: &K) -> bool {
+        match &self.end {
+            Some(end) => start >= end,
+            None => *start > self.start,
+        }
+    }
+}
+
+impl<'a, K, V> Iterator for Overlaps<'a, K, V>
+where
+    K: Ord,
+{
+    type Item = (&'a Range<K>, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            while let Some(node) = self.cursor {
+                // Nothing in this subtree ends after the query starts
+                if node.max_end <= self.start {
+                    break;
+                }
+                self.stack.push(node);
+                self.cursor = node.left.as_deref();
+            }
+            self.cursor = None;
+
+            let node = self.stack.pop()?;
+            if self.past_end(&node.range.start) {
+                // Every remaining node starts even later
+                self.stack.clear();
+                return None;
+            }
+            self.cursor = node.right.as_deref();
+            if node.range.end > self.start && node.range.start < node.range.end {
+                return Some((&node.range, &node.value));
+            }
+        }
+    }
+}
+
+fn height<K, V>(node: &Link<K, V>) -> usize {
+    node.as_ref().map_or(0, |n| n.height)
+}
+
+fn compare<K: Ord>(a: &Range<K>, b: &Range<K>) -> Ordering {
+    a.start.cmp(&b.start).then_with(|| a.end.cmp(&b.end))
+}
+
+impl<K, V> Node<K, V>
+where
+    K: Ord + Clone,
+{
+    fn new(range: Range<K>, value: V) -> Self {
+        Node {
+            max_end: range.end.clone(),
+            range,
+            value,
+            height: 1,
+            left: None,
+            right: None,
+        }
+    }
+
+    /// Recomputes the height and max end from the children.
+    fn update(&mut self) {
+        self.height = 1 + std::cmp::max(height(&self.left), height(&self.right));
+        let mut max_end = &self.range.end;
+        for child in [&self.left, &self.right].into_iter().flatten() {
+            if child.max_end > *max_end {
+                max_end = &child.max_end;
+            }
+        }
+        self.max_end = max_end.clone();
+    }
+
+    fn balance(&self) -> isize {
+        height(&self.right) as isize - height(&self.left) as isize
+    }
+}
+
+fn rotate_left<K: Ord + Clone, V>(mut node: Box<Node<K, V>>) -> Box<Node<K, V>> {
+    let mut child = node.right.take().unwrap();
+    node.right = child.left.take();
+    node.update();
+    child.left = Some(node);
+    child.update();
+    child
+}
+
+fn rotate_right<K: Ord + Clone, V>(mut node: Box<Node<K, V>>) -> Box<Node<K, V>> {
+    let mut child = node.left.take().unwrap();
+    node.left = child.right.take();
+    node.update();
+    child.right = Some(node);
+    child.update();
+    child
+}
+
+fn rebalance<K: Ord + Clone, V>(mut node: Box<Node<K, V>>) -> Box<Node<K, V>> {
+    node.update();
+    match node.balance() {
+        -2 => {
+            if node.left.as_ref().unwrap().balance() > 0 {
+                node.left = Some(rotate_left(node.left.take().unwrap()));
+            }
+            rotate_right(node)
+        }
+        2 => {
+            if node.right.as_ref().unwrap().balance() < 0 {
+                node.right = Some(rotate_right(node.right.take().unwrap()));
+            }
+            rotate_left(node)
+        }
+        _ => node,
+    }
+}
+
+fn insert<K: Ord + Clone, V>(node: Link<K, V>, range: Range<K>, value: V) -> Box<Node<K, V>> {
+    match node {
+        Some(mut node) => {
+            if compare(&range, &node.range) == Ordering::Less {
+                node.left = Some(insert(node.left.take(), range, value));
+            } else {
+                node.right = Some(insert(node.right.take(), range, value));
+            }
+            rebalance(node)
+        }
+        None => Box::new(Node::new(range, value)),
+    }
+}
+
+fn remove<K: Ord + Clone, V>(node: Link<K, V>, range: &Range<K>) -> (Link<K, V>, Option<V>) {
+    let mut node = match node {
+        Some(node) => node,
+        None => return (None, None),
+    };
+    let removed = match compare(range, &node.range) {
+        Ordering::Less => {
+            let (left, removed) = remove(node.left.take(), range);
+            node.left = left;
+            removed
+        }
+        Ordering::Greater => {
+            let (right, removed) = remove(node.right.take(), range);
+            node.right = right;
+            removed
+        }
+        Ordering::Equal => {
+            let Node {
+                value, left, right, ..
+            } = *node;
+            return match (left, right) {
+                (None, None) => (None, Some(value)),
+                (Some(child), None) | (None, Some(child)) => (Some(child), Some(value)),
+                (Some(left), Some(right)) => {
+                    // Replace this node with its in-order successor
+                    let (right, mut successor) = remove_min(right);
+                    successor.left = Some(left);
+                    successor.right = right;
+                    (Some(rebalance(successor)), Some(value))
+                }
+            };
+        }
+    };
+    (Some(rebalance(node)), removed)
+}
+
+/// Detaches the leftmost node of the subtree, returning the remaining subtree and the node.
+fn remove_min<K: Ord + Clone, V>(mut node: Box<Node<K, V>>) -> (Link<K, V>, Box<Node<K, V>>) {
+    match node.left.take() {
+        Some(left) => {
+            let (left, min) = remove_min(left);
+            node.left = left;
+            (Some(rebalance(node)), min)
+        }
+        None => (node.right.take(), node),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::ops::Range;
+
+    use quickcheck::quickcheck;
+
+    use super::{height, IntervalTree, Link};
+
+    fn balanced<K, V>(node: &Link<K, V>) -> bool {
+        match node {
+            Some(node) => {
+                (height(&node.left) as isize - height(&node.right) as isize).abs() <= 1
+                    && balanced(&node.left)
+                    && balanced(&node.right)
+            }
+            None => true,
+        }
+    }
+
+    fn ranges<'a>(iter: impl Iterator<Item = (&'a Range<i32>, &'a ())>) -> Vec<Range<i32>> {
+        iter.map(|(r, _)| r.clone()).collect()
+    }
+
+    #[test]
+    fn query_point() {
+        let mut tree = IntervalTree::new();
+        tree.insert(0..10, ());
+        tree.insert(5..15, ());
+        tree.insert(20..30, ());
+        assert_eq!(ranges(tree.query_point(&7)), vec![0..10, 5..15]);
+        assert_eq!(ranges(tree.query_point(&10)), vec![5..15]);
+        assert_eq!(ranges(tree.query_point(&17)), vec![]);
+        assert_eq!(ranges(tree.query_point(&20)), vec![20..30]);
+    }
+
+    #[test]
+    fn query_overlaps() {
+        let mut tree = IntervalTree::new();
+        tree.insert(0..10, ());
+        tree.insert(5..15, ());
+        tree.insert(20..30, ());
+        assert_eq!(ranges(tree.query_overlaps(&(12..21))), vec![5..15, 20..30]);
+        assert_eq!(ranges(tree.query_overlaps(&(15..20))), vec![]);
+    }
+
+    #[test]
+    fn duplicates_and_remove() {
+        let mut tree = IntervalTree::new();
+        tree.insert(1..3, 'a');
+        tree.insert(1..3, 'b');
+        tree.insert(2..4, 'c');
+        assert_eq!(tree.len(), 3);
+        assert!(tree.remove(&(1..3)).is_some());
+        assert_eq!(tree.query_point(&1).count(), 1);
+        assert_eq!(tree.remove(&(5..6)), None);
+        assert_eq!(tree.len(), 2);
+    }
+
+    #[test]
+    fn prop_matches_linear_scan() {
+        fn p(input: Vec<(u8, u8)>, point: u8) -> bool {
+            let input = input
+                .into_iter()
+                .map(|(a, b)| (a.min(b) as i32)..(a.max(b) as i32))
+                .collect::<Vec<_>>();
+            let mut tree = IntervalTree::new();
+            for r in input.iter() {
+                tree.insert(r.clone(), ());
+            }
+            let point = point as i32;
+            let mut expected = input
+                .iter()
+                .filter(|r| r.contains(&point))
+                .cloned()
+                .collect::<Vec<_>>();
+            expected.sort_by_key(|r| (r.start, r.end));
+            balanced(&tree.root) && ranges(tree.query_point(&point)) == expected
+        }
+        quickcheck(p as fn(Vec<(u8, u8)>, u8) -> bool)
+    }
+
+    #[test]
+    fn prop_removal() {
+        fn p(input: Vec<(u8, u8)>) -> bool {
+            let input = input
+                .into_iter()
+                .map(|(a, b)| (a.min(b) as i32)..(a.max(b) as i32 + 1))
+                .collect::<Vec<_>>();
+            let mut tree = IntervalTree::new();
+            for r in input.iter() {
+                tree.insert(r.clone(), ());
+            }
+            input
+                .iter()
+                .all(|r| tree.remove(r).is_some() && balanced(&tree.root))
+                && tree.is_empty()
+        }
+        quickcheck(p as fn(Vec<(u8, u8)>) -> bool)
+    }
+}
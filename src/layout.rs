@@ -0,0 +1,366 @@
+//! A versioned binary format for the immutable structures, `EytzingerSearch`,
+//! `StaticSortedSet` and `StaticTrie`, so they can be built offline and loaded at startup
+//! from memory such as an mmap without copying.
+//!
+//! A buffer starts with a 16 byte header: the magic bytes `RSTD`, four bytes naming the
+//! structure, the format version and four reserved bytes. Every integer is little-endian.
+//! Scalars follow as `u64`s, and each array as its length and element size in `u64`s and
+//! then its elements, padded to a multiple of eight bytes so that every array starts
+//! eight-byte aligned. Loading borrows the arrays straight from the buffer when it is
+//! eight-byte aligned and the target is little-endian, and copies them otherwise.
+//! Loading checks that the arrays fit together, but not that their contents are in order,
+//! so a corrupt buffer may give wrong answers or panic, though never cause undefined
+//! behaviour.
+
+use std::{fmt, io, mem, ops::Deref, ptr, slice};
+
+/// The version of the format written by `write_to` and accepted by `load`.
+pub const VERSION: u32 = 1;
+
+const MAGIC: [u8; 4] = *b"RSTD";
+const ALIGN: usize = 8;
+
+/// Why a buffer couldn't be loaded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoadError {
+    /// The buffer doesn't start with the magic bytes.
+    NotLayout,
+    /// The buffer holds a different structure, named by these bytes.
+    WrongKind([u8; 4]),
+    /// The buffer was written with an unsupported version of the format.
+    Version(u32),
+    /// The buffer ends before the structure does.
+    Truncated,
+    /// An array's elements are a different size than the type it's loaded as.
+    ElementSize { expected: usize, found: u64 },
+    /// The arrays don't fit together.
+    Corrupt(&'static str),
+    /// The structure's elements are zero-sized, like `[u8; 0]`, which can't be loaded.
+    ZeroSized,
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::NotLayout => write!(f, "buffer doesn't hold a serialized structure"),
+            LoadError::WrongKind(kind) => {
+                write!(f, "buffer holds a {}", String::from_utf8_lossy(kind))
+            }
+            LoadError::Version(version) => write!(f, "unsupported format version {}", version),
+            LoadError::Truncated => write!(f, "buffer is truncated"),
+            LoadError::ElementSize { expected, found } => {
+                write!(f, "expected {} byte elements, found {}", expected, found)
+            }
+            LoadError::Corrupt(why) => write!(f, "buffer is corrupt: {}", why),
+            LoadError::ZeroSized => write!(f, "elements are zero-sized"),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+/// A type which is stored by copying its bytes, so that it can be borrowed straight out of
+/// a loaded buffer.
+///
+/// # Safety
+///
+/// Every bit pattern must be a valid value, and the type must have no padding bytes, a
+/// nonzero size and an alignment of at most eight. It must be `Sync`, as every structure
+/// loaded from the same buffer shares its elements.
+pub unsafe trait Plain: Copy + Sync + 'static {
+    /// Converts between native and little-endian byte order, which is the same conversion
+    /// in both directions.
+    fn swap_le(self) -> Self;
+}
+
+macro_rules! plain_integers {
+    ($($t:ty),*) => {
+        $(unsafe impl Plain for $t {
+            fn swap_le(self) -> Self {
+                self.to_le()
+            }
+        })*
+    };
+}
+
+plain_integers!(u8, u16, u32, u64, i8, i16, i32, i64);
+
+unsafe impl Plain for f32 {
+    fn swap_le(self) -> Self {
+        f32::from_bits(self.to_bits().to_le())
+    }
+}
+
+unsafe impl Plain for f64 {
+    fn swap_le(self) -> Self {
+        f64::from_bits(self.to_bits().to_le())
+    }
+}
+
+// `[T; 0]` breaks the nonzero size rule, so `Reader::array` refuses it rather than dividing
+// by its size
+unsafe impl<T: Plain, const N: usize> Plain for [T; N] {
+    fn swap_le(self) -> Self {
+        self.map(T::swap_le)
+    }
+}
+
+/// An array which is either owned or borrowed from a loaded buffer.
+pub(crate) enum Buf<'a, T> {
+    Owned(Vec<T>),
+    // The bytes of `Plain` elements, aligned for `T`. They're untyped so that an owned
+    // `Buf<'static, T>` doesn't need `T: 'static`.
+    Borrowed(&'a [u8]),
+}
+
+impl<T> Deref for Buf<'_, T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        match self {
+            Buf::Owned(items) => items,
+            // Only `Reader::array` borrows, after checking the bytes can be read as `T`s
+            Buf::Borrowed(bytes) => unsafe {
+                slice::from_raw_parts(bytes.as_ptr().cast(), bytes.len() / mem::size_of::<T>())
+            },
+        }
+    }
+}
+
+impl<T: Clone> Buf<'_, T> {
+    /// Copies the array out of the buffer if it's borrowed.
+    pub(crate) fn into_owned(self) -> Buf<'static, T> {
+        match self {
+            Buf::Borrowed(_) => Buf::Owned(self.to_vec()),
+            Buf::Owned(items) => Buf::Owned(items),
+        }
+    }
+}
+
+impl<T> From<Vec<T>> for Buf<'_, T> {
+    fn from(items: Vec<T>) -> Self {
+        Buf::Owned(items)
+    }
+}
+
+impl<T: Clone> Clone for Buf<'_, T> {
+    fn clone(&self) -> Self {
+        match self {
+            Buf::Owned(items) => Buf::Owned(items.clone()),
+            Buf::Borrowed(items) => Buf::Borrowed(items),
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Buf<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+impl<T: PartialEq> PartialEq for Buf<'_, T> {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl<T: Eq> Eq for Buf<'_, T> {}
+
+/// Writes a structure's header, scalars and arrays.
+pub(crate) struct Writer<W> {
+    out: W,
+}
+
+impl<W: io::Write> Writer<W> {
+    pub(crate) fn new(mut out: W, kind: [u8; 4]) -> io::Result<Self> {
+        out.write_all(&MAGIC)?;
+        out.write_all(&kind)?;
+        out.write_all(&VERSION.to_le_bytes())?;
+        out.write_all(&[0; 4])?;
+        Ok(Writer { out })
+    }
+
+    pub(crate) fn word(&mut self, word: u64) -> io::Result<()> {
+        self.out.write_all(&word.to_le_bytes())
+    }
+
+    pub(crate) fn array<T: Plain>(&mut self, items: &[T]) -> io::Result<()> {
+        self.word(items.len() as u64)?;
+        self.word(mem::size_of::<T>() as u64)?;
+        if cfg!(target_endian = "little") {
+            self.out.write_all(bytes_of(items))?;
+        } else {
+            for item in items {
+                self.out.write_all(bytes_of(&[item.swap_le()]))?;
+            }
+        }
+        let padding = mem::size_of_val(items).next_multiple_of(ALIGN) - mem::size_of_val(items);
+        self.out.write_all(&[0; ALIGN][..padding])
+    }
+}
+
+fn bytes_of<T: Plain>(items: &[T]) -> &[u8] {
+    // Plain types have no padding, so every byte is initialized
+    unsafe { slice::from_raw_parts(items.as_ptr().cast(), mem::size_of_val(items)) }
+}
+
+/// Reads a structure's scalars and arrays in the order they were written, borrowing the
+/// arrays from the buffer where it can.
+pub(crate) struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    /// Checks the header, which must name `kind`.
+    pub(crate) fn new(bytes: &'a [u8], kind: [u8; 4]) -> Result<Self, LoadError> {
+        let mut reader = Reader { bytes };
+        let header = reader.take(16).map_err(|_| LoadError::NotLayout)?;
+        if header[..4] != MAGIC {
+            return Err(LoadError::NotLayout);
+        }
+        if header[4..8] != kind {
+            return Err(LoadError::WrongKind(header[4..8].try_into().unwrap()));
+        }
+        let version = u32::from_le_bytes(header[8..12].try_into().unwrap());
+        if version != VERSION {
+            return Err(LoadError::Version(version));
+        }
+        Ok(reader)
+    }
+
+    fn take(&mut self, n: usize) -> Result<&'a [u8], LoadError> {
+        if n > self.bytes.len() {
+            return Err(LoadError::Truncated);
+        }
+        let (taken, rest) = self.bytes.split_at(n);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    pub(crate) fn word(&mut self) -> Result<u64, LoadError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    /// Reads a word which must fit in a `usize`.
+    pub(crate) fn size(&mut self) -> Result<usize, LoadError> {
+        usize::try_from(self.word()?).map_err(|_| LoadError::Corrupt("size overflows usize"))
+    }
+
+    pub(crate) fn array<T: Plain>(&mut self) -> Result<Buf<'a, T>, LoadError> {
+        if mem::size_of::<T>() == 0 {
+            return Err(LoadError::ZeroSized);
+        }
+        let len = self.size()?;
+        let size = self.word()?;
+        if size != mem::size_of::<T>() as u64 {
+            return Err(LoadError::ElementSize {
+                expected: mem::size_of::<T>(),
+                found: size,
+            });
+        }
+        let bytes_len = len
+            .checked_mul(mem::size_of::<T>())
+            .ok_or(LoadError::Truncated)?;
+        let padded = bytes_len
+            .checked_next_multiple_of(ALIGN)
+            .ok_or(LoadError::Truncated)?;
+        let bytes = &self.take(padded)?[..bytes_len];
+        if cfg!(target_endian = "little") && bytes.as_ptr().cast::<T>().is_aligned() {
+            // The bytes are aligned, in native order and valid for any bit pattern
+            Ok(Buf::Borrowed(bytes))
+        } else {
+            let items = bytes
+                .chunks_exact(mem::size_of::<T>())
+                .map(|chunk| unsafe { ptr::read_unaligned(chunk.as_ptr().cast::<T>()) }.swap_le())
+                .collect();
+            Ok(Buf::Owned(items))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Buf, LoadError, Reader, Writer};
+
+    fn written(f: impl FnOnce(&mut Writer<&mut Vec<u8>>)) -> Vec<u8> {
+        let mut bytes = vec![];
+        let mut writer = Writer::new(&mut bytes, *b"TEST").unwrap();
+        f(&mut writer);
+        bytes
+    }
+
+    #[test]
+    fn round_trip() {
+        let bytes = written(|w| {
+            w.word(7).unwrap();
+            w.array(&[1u8, 2, 3]).unwrap();
+            w.array(&[[4u32, 5]]).unwrap();
+        });
+        // Each array is padded to keep the next one aligned
+        assert_eq!(bytes.len(), 16 + 8 + (16 + 8) + (16 + 8));
+        // A `Vec<u64>` is aligned well enough for every array to be borrowed
+        let words = bytes
+            .chunks(8)
+            .map(|c| u64::from_ne_bytes(c.try_into().unwrap()))
+            .collect::<Vec<_>>();
+        let aligned =
+            unsafe { std::slice::from_raw_parts(words.as_ptr().cast::<u8>(), bytes.len()) };
+        let mut reader = Reader::new(aligned, *b"TEST").unwrap();
+        assert_eq!(reader.word(), Ok(7));
+        let small = reader.array::<u8>().unwrap();
+        assert_eq!(*small, [1, 2, 3]);
+        let pairs = reader.array::<[u32; 2]>().unwrap();
+        assert_eq!(*pairs, [[4, 5]]);
+        if cfg!(target_endian = "little") {
+            assert!(matches!(pairs, Buf::Borrowed(_)));
+        }
+        assert_eq!(reader.word(), Err(LoadError::Truncated));
+    }
+
+    #[test]
+    fn misaligned_buffers_are_copied() {
+        let bytes = written(|w| w.array(&[u64::MAX - 1]).unwrap());
+        let mut shifted = vec![0];
+        shifted.extend(&bytes);
+        let mut reader = Reader::new(&shifted[1..], *b"TEST").unwrap();
+        let items = reader.array::<u64>().unwrap();
+        assert!(matches!(items, Buf::Owned(_)));
+        assert_eq!(*items, [u64::MAX - 1]);
+    }
+
+    #[test]
+    fn rejects_bad_headers() {
+        let bytes = written(|w| w.array(&[1u16]).unwrap());
+        assert_eq!(
+            Reader::new(&bytes, *b"ELSE").err(),
+            Some(LoadError::WrongKind(*b"TEST"))
+        );
+        assert_eq!(
+            Reader::new(&bytes[1..], *b"TEST").err(),
+            Some(LoadError::NotLayout)
+        );
+        let mut future = bytes.clone();
+        future[8] = 2;
+        assert_eq!(
+            Reader::new(&future, *b"TEST").err(),
+            Some(LoadError::Version(2))
+        );
+        let mut reader = Reader::new(&bytes, *b"TEST").unwrap();
+        assert_eq!(
+            reader.array::<u32>().err(),
+            Some(LoadError::ElementSize {
+                expected: 4,
+                found: 2
+            })
+        );
+        let mut reader = Reader::new(&bytes[..bytes.len() - 8], *b"TEST").unwrap();
+        assert_eq!(reader.array::<u16>().err(), Some(LoadError::Truncated));
+    }
+
+    #[test]
+    fn rejects_zero_sized_elements() {
+        let bytes = written(|w| w.array(&[[0u32; 0]; 3]).unwrap());
+        let mut reader = Reader::new(&bytes, *b"TEST").unwrap();
+        assert_eq!(reader.array::<[u32; 0]>().err(), Some(LoadError::ZeroSized));
+    }
+}
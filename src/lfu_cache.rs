@@ -0,0 +1,318 @@
+use std::{
+    collections::{hash_map::RandomState, HashMap},
+    fmt::Debug,
+    hash::{BuildHasher, Hash},
+};
+
+use crate::{
+    bounded_map::BoundedMap,
+    index_list::{IndexList, IndexListHandle},
+    Error,
+};
+
+/// A cache which evicts the least frequently used entry once it is full.
+/// Keys are kept in one list per access count, so promoting a key and finding the
+/// eviction candidate are both O(1). Ties are broken by evicting the least recently used key.
+#[derive(Debug, Clone)]
+pub struct LFUCache<K, V, S = RandomState> {
+    entries: HashMap<K, Entry<V>, S>,
+    frequencies: HashMap<usize, IndexList<K>, S>,
+    min_frequency: usize,
+    capacity: usize,
+}
+
+#[derive(Debug, Clone)]
+struct Entry<V> {
+    value: V,
+    frequency: usize,
+    handle: IndexListHandle,
+}
+
+impl<K, V> LFUCache<K, V>
+where
+    K: Clone,
+{
+    pub fn new(capacity: usize) -> Self {
+        LFUCache::with_hasher(capacity, RandomState::new())
+    }
+}
+
+impl<K, V, S> LFUCache<K, V, S>
+where
+    K: Clone,
+    S: Clone,
+{
+    pub fn with_hasher(capacity: usize, hasher: S) -> Self {
+        LFUCache {
+            entries: HashMap::with_hasher(hasher.clone()),
+            frequencies: HashMap::with_hasher(hasher),
+            min_frequency: 0,
+            capacity,
+        }
+    }
+}
+
+impl<K, V, S> LFUCache<K, V, S>
+where
+    K: Eq + Hash + Clone,
+    S: BuildHasher,
+{
+    /// Inserts a value, replacing the value of an existing key without counting it as a use.
+    pub fn insert(&mut self, k: K, v: V) {
+        BoundedMap::insert_evicting(self, k, v);
+    }
+
+    fn insert_new(&mut self, k: K, v: V) {
+        let handle = self.frequencies.entry(1).or_default().push_head(k.clone());
+        self.min_frequency = 1;
+        self.entries.insert(
+            k,
+            Entry {
+                value: v,
+                frequency: 1,
+                handle,
+            },
+        );
+    }
+
+    /// Inserts the entry only if the key is absent and the cache has room, so nothing is
+    /// overwritten or evicted. Otherwise the entry is handed back in the error.
+    pub fn try_insert(&mut self, k: K, v: V) -> Result<(), Error<(K, V)>> {
+        if self.entries.contains_key(&k) {
+            return Err(Error::Exists((k, v)));
+        }
+        if self.entries.len() == self.capacity {
+            return Err(Error::Full((k, v)));
+        }
+        self.insert(k, v);
+        Ok(())
+    }
+
+    pub fn get(&mut self, k: &K) -> Option<&V> {
+        self.touch(k)?;
+        self.entries.get(k).map(|e| &e.value)
+    }
+
+    pub fn get_mut(&mut self, k: &K) -> Option<&mut V> {
+        self.touch(k)?;
+        self.entries.get_mut(k).map(|e| &mut e.value)
+    }
+
+    /// The number of times the key has been inserted or accessed since it entered the cache.
+    pub fn frequency(&self, k: &K) -> Option<usize> {
+        self.entries.get(k).map(|e| e.frequency)
+    }
+
+    pub fn contains_key(&self, k: &K) -> bool {
+        self.entries.contains_key(k)
+    }
+
+    pub fn remove(&mut self, k: &K) -> Option<V> {
+        let entry = self.entries.remove(k)?;
+        self.unlink(entry.frequency, entry.handle);
+        Some(entry.value)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns an iterator over the entries in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.entries.iter().map(|(k, entry)| (k, &entry.value))
+    }
+
+    /// Moves the key into the list for its next access count.
+    fn touch(&mut self, k: &K) -> Option<()> {
+        let mut entry = self.entries.remove(k)?;
+        let bucket = self.frequencies.get_mut(&entry.frequency).unwrap();
+        bucket.remove(entry.handle);
+        if bucket.is_empty() {
+            self.frequencies.remove(&entry.frequency);
+            if self.min_frequency == entry.frequency {
+                self.min_frequency += 1;
+            }
+        }
+        entry.frequency += 1;
+        entry.handle = self
+            .frequencies
+            .entry(entry.frequency)
+            .or_default()
+            .push_head(k.clone());
+        self.entries.insert(k.clone(), entry);
+        Some(())
+    }
+
+    /// Removes a key from the list for its access count.
+    fn unlink(&mut self, frequency: usize, handle: IndexListHandle) {
+        let bucket = self.frequencies.get_mut(&frequency).unwrap();
+        bucket.remove(handle);
+        if bucket.is_empty() {
+            self.frequencies.remove(&frequency);
+            if self.min_frequency == frequency {
+                // Removal can empty any list, so the next lowest count has to be searched for
+                self.min_frequency = self.frequencies.keys().copied().min().unwrap_or(0);
+            }
+        }
+    }
+}
+
+/// Evicts the least frequently used entry, breaking ties by recency.
+impl<K, V, S> BoundedMap<K, V> for LFUCache<K, V, S>
+where
+    K: Eq + Hash + Clone,
+    S: BuildHasher,
+{
+    fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    fn contains_key(&self, k: &K) -> bool {
+        self.entries.contains_key(k)
+    }
+
+    fn get(&mut self, k: &K) -> Option<&V> {
+        LFUCache::get(self, k)
+    }
+
+    fn insert_evicting(&mut self, k: K, v: V) -> Option<(K, V)> {
+        if let Some(entry) = self.entries.get_mut(&k) {
+            entry.value = v;
+            return None;
+        }
+        if self.capacity == 0 {
+            return Some((k, v));
+        }
+
+        let mut evicted = None;
+        if self.entries.len() == self.capacity {
+            #[cfg(feature = "tracing")]
+            tracing::debug!(
+                capacity = self.capacity,
+                frequency = self.min_frequency,
+                "evicting least frequently used entry"
+            );
+            let bucket = self.frequencies.get_mut(&self.min_frequency).unwrap();
+            let removed = bucket.pop_tail().unwrap();
+            if bucket.is_empty() {
+                self.frequencies.remove(&self.min_frequency);
+            }
+            let entry = self.entries.remove(&removed).unwrap();
+            evicted = Some((removed, entry.value));
+        }
+        self.insert_new(k, v);
+        evicted
+    }
+
+    fn remove(&mut self, k: &K) -> Option<V> {
+        LFUCache::remove(self, k)
+    }
+
+    fn victim(&self) -> Option<&K> {
+        self.frequencies.get(&self.min_frequency)?.tail()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{collections::HashMap, thread};
+
+    use quickcheck::quickcheck;
+
+    use super::LFUCache;
+    use crate::bounded_map::BoundedMap;
+
+    #[test]
+    fn cache_evict_least_frequent() {
+        let mut cache = LFUCache::new(2);
+        cache.insert(1, 101);
+        cache.insert(2, 102);
+        cache.get(&1);
+        cache.get(&1);
+        cache.get(&2);
+        cache.insert(3, 103);
+        assert_eq!(cache.get(&2), None);
+        assert_eq!(cache.get(&1), Some(&101));
+        assert_eq!(cache.get(&3), Some(&103));
+        assert_eq!(cache.frequency(&1), Some(4));
+    }
+
+    #[test]
+    fn cache_ties_evict_least_recent() {
+        let mut cache = LFUCache::new(2);
+        cache.insert(1, 101);
+        cache.insert(2, 102);
+        cache.insert(3, 103);
+        assert_eq!(cache.get(&1), None);
+        assert_eq!(cache.get(&2), Some(&102));
+        *cache.get_mut(&3).unwrap() += 1;
+        assert_eq!(cache.get(&3), Some(&104));
+    }
+
+    #[test]
+    fn cache_moves_between_threads() {
+        let mut cache = LFUCache::new(2);
+        cache.insert(1, 101);
+        let hit = thread::spawn(move || cache.get(&1).copied());
+        assert_eq!(hit.join().unwrap(), Some(101));
+    }
+
+    #[test]
+    fn cache_zero_capacity() {
+        let mut cache = LFUCache::new(0);
+        cache.insert(1, 101);
+        assert_eq!(cache.get(&1), None);
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn prop_matches_model() {
+        // The model scans every entry for the lowest (frequency, last use)
+        fn p(ops: Vec<(u8, u8)>) -> bool {
+            let mut cache = LFUCache::new(4);
+            let mut model: HashMap<u8, (usize, usize)> = HashMap::new();
+            for (time, (op, k)) in ops.into_iter().enumerate() {
+                let k = k % 8;
+                let victim = model.iter().min_by_key(|(_, v)| **v).map(|(k, _)| k);
+                if cache.victim() != victim {
+                    return false;
+                }
+                if op % 3 == 2 {
+                    if cache.remove(&k).is_some() != model.remove(&k).is_some() {
+                        return false;
+                    }
+                } else if op % 3 == 0 {
+                    let hit = cache.get(&k) == Some(&k);
+                    if hit != model.contains_key(&k) {
+                        return false;
+                    }
+                    if let Some((frequency, last)) = model.get_mut(&k) {
+                        *frequency += 1;
+                        *last = time;
+                    }
+                } else if !model.contains_key(&k) {
+                    cache.insert(k, k);
+                    if model.len() == 4 {
+                        let victim = *model.iter().min_by_key(|(_, v)| **v).unwrap().0;
+                        model.remove(&victim);
+                    }
+                    model.insert(k, (1, time));
+                }
+            }
+            cache.len() == model.len()
+        }
+        quickcheck(p as fn(Vec<(u8, u8)>) -> bool)
+    }
+}
@@ -1,7 +1,144 @@
+//! Data structures and algorithms, each behind a feature of the same name as its module.
+//! Every feature is enabled by default through `full`, and the `prelude` re-exports the
+//! main type of each enabled module.
+
+#![cfg_attr(feature = "nightly", feature(allocator_api))]
+
+pub mod allocator;
+#[cfg(feature = "arena")]
+pub mod arena;
+#[cfg(feature = "avl_set")]
+pub mod avl_set;
+#[cfg(feature = "avl_tree")]
 pub mod avl_tree;
+#[cfg(feature = "bag")]
+pub mod bag;
+#[cfg(feature = "batch")]
+pub mod batch;
+#[cfg(feature = "bimap")]
+pub mod bimap;
+#[cfg(feature = "bitset")]
+pub mod bitset;
+pub mod bounded_map;
+#[cfg(feature = "bs_tree")]
 pub mod bs_tree;
+pub mod capacity;
+pub mod clock;
+#[cfg(feature = "collect")]
+pub mod collect;
+#[cfg(feature = "compact")]
+pub mod compact;
+#[cfg(feature = "cow")]
+pub mod cow;
+#[cfg(feature = "cuckoo")]
+pub mod cuckoo;
+pub mod debug;
+#[cfg(feature = "dedup")]
+pub mod dedup;
+#[cfg(feature = "disjoint_set")]
+pub mod disjoint_set;
+pub mod error;
+#[cfg(feature = "expiry_map")]
+pub mod expiry_map;
+#[cfg(feature = "extsort")]
+pub mod extsort;
+pub mod footprint;
+pub mod from_sorted;
+#[cfg(feature = "graph")]
+pub mod graph;
+#[cfg(feature = "hash_map")]
+pub mod hash_map;
+#[cfg(feature = "hash_trie")]
 pub mod hash_trie;
+#[cfg(feature = "heap")]
 pub mod heap;
+#[cfg(feature = "index_list")]
+pub mod index_list;
+#[cfg(feature = "index_map")]
+pub mod index_map;
+#[cfg(feature = "interval_tree")]
+pub mod interval_tree;
+#[cfg(any(feature = "compact", feature = "static_tree", feature = "static_trie"))]
+pub mod layout;
+#[cfg(feature = "lfu_cache")]
+pub mod lfu_cache;
+#[cfg(feature = "linked_list")]
 pub mod linked_list;
+#[cfg(feature = "llrb")]
+pub mod llrb;
+#[cfg(feature = "lru_cache")]
 pub mod lru_cache;
+#[cfg(feature = "merge")]
+pub mod merge;
+#[cfg(feature = "merkle")]
+pub mod merkle;
+#[cfg(feature = "min_stack")]
+pub mod min_stack;
+#[cfg(feature = "monotonic")]
+pub mod monotonic;
+#[cfg(feature = "multi_index")]
+pub mod multi_index;
+#[cfg(feature = "multi_map")]
+pub mod multi_map;
+#[cfg(feature = "multi_set")]
+pub mod multi_set;
+#[cfg(feature = "persistent")]
+pub mod persistent;
+pub mod prelude;
+#[cfg(feature = "priority_map")]
+pub mod priority_map;
+#[cfg(feature = "range_map")]
+pub mod range_map;
+#[cfg(feature = "router")]
+pub mod router;
+#[cfg(feature = "sampling")]
+pub mod sampling;
+#[cfg(feature = "scheduler")]
+pub mod scheduler;
+pub mod seek;
+#[cfg(feature = "sketch")]
+pub mod sketch;
+#[cfg(feature = "slab")]
+pub mod slab;
+#[cfg(feature = "small_vec")]
+pub mod small_vec;
+#[cfg(feature = "sort")]
+pub mod sort;
+#[cfg(feature = "sorted_list")]
+pub mod sorted_list;
+#[cfg(feature = "spatial")]
+pub mod spatial;
+#[cfg(feature = "static_tree")]
+pub mod static_tree;
+#[cfg(feature = "static_trie")]
+pub mod static_trie;
+#[cfg(feature = "stats")]
+pub mod stats;
+#[cfg(feature = "suffix")]
+pub mod suffix;
+#[cfg(feature = "sync")]
 pub mod sync;
+#[cfg(feature = "testing")]
+pub mod testing;
+#[cfg(feature = "tiered_map")]
+pub mod tiered_map;
+#[cfg(feature = "timer_wheel")]
+pub mod timer_wheel;
+#[cfg(feature = "tinylfu")]
+pub mod tinylfu;
+#[cfg(any(feature = "avl_tree", feature = "bs_tree", feature = "hash_trie"))]
+mod traversal;
+#[cfg(feature = "txn")]
+pub mod txn;
+#[cfg(feature = "veb")]
+pub mod veb;
+#[cfg(feature = "viz")]
+pub mod viz;
+#[cfg(feature = "weak_map")]
+pub mod weak_map;
+#[cfg(feature = "weighted")]
+pub mod weighted;
+#[cfg(feature = "window")]
+pub mod window;
+
+pub use error::Error;
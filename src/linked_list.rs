@@ -1,11 +1,16 @@
 use std::{
     borrow::{Borrow, BorrowMut},
     cell::RefCell,
+    cmp::Ordering,
+    fmt::{self, Debug},
+    hash::{Hash, Hasher},
     rc::{Rc, Weak},
 };
 
+use crate::{debug::DebugStructure, footprint::MemoryFootprint};
+
 /// A doubly linked list which support constant time head insertion, tail deletion, and random deletion.
-#[derive(Debug, Default)]
+#[derive(Default)]
 pub struct LinkedList<A> {
     head: Option<Rc<Node<A>>>,
     tail: Option<Rc<Node<A>>>,
@@ -19,6 +24,28 @@ impl<A> LinkedList<A> {
         }
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.head.is_none()
+    }
+
+    /// Returns the oldest element, which `pop_tail` would remove.
+    pub fn tail(&self) -> Option<&A> {
+        self.tail.as_ref().map(|node| &node.key)
+    }
+
+    /// Calls `f` on each element from head to tail.
+    pub fn for_each(&self, f: impl FnMut(&A)) {
+        self.iter().for_each(f);
+    }
+
+    fn for_each_node(&self, mut f: impl FnMut(&Node<A>)) {
+        let mut node = self.head.clone();
+        while let Some(n) = node {
+            f(&n);
+            node = n.next.borrow().clone();
+        }
+    }
+
     pub fn push_head(&mut self, k: A) -> LinkedListHandle<A> {
         if let Some(old_head) = self.head.take() {
             let new_head = Rc::new(Node::new(k, None, Some(old_head.clone())));
@@ -67,46 +94,134 @@ impl<A> LinkedList<A> {
         }
     }
 
-    // pub fn iter<'a>(&'a self) -> Iter<'a, A> {
-    //     Iter { head: self.head.as_ref().map(|n| n.as_ref()), tail: self.tail.as_ref().map(|n| n.as_ref()) }
-    // }
-}
-
-// pub struct Iter<A> {
-//     head: Option<AsRef<Node<A>>,
-//     tail: Option<AsRef<Node<A>>,
-// }
-
-// impl<A> Iterator for Iter<A> {
-//     type Item = &A;
-
-//     fn next(&mut self) -> Option<Self::Item> {
-//         if let Some(head) = self.head.take() {
-//             let item = Ref::map(head, |n| &n.key);
-
-//             // Invariant: if there is a head, there must be a tail
-//             let tail = self.tail.unwrap();
-//             if std::ptr::eq(head, tail) {
-//                 self.head = None;
-//                 self.tail = None;
-//             } else {
-//                 // We have a tail element next
-//                 let next_head = head.next.borrow();
-//                 let x = Ref::map(next_head, |n| &n.unwrap());
-//                 self.head = Some(next_head);
-//             }
-//             Some(item)
-//         } else {
-//             None
-//         }
-//     }
-// }
-
-// impl<'a, A> DoubleEndedIterator for Iter<'a, A> {
-//     fn next_back(&mut self) -> Option<Self::Item> {
-//         todo!()
-//     }
-// }
+    /// Returns an iterator over the elements from head to tail.
+    pub fn iter(&self) -> Iter<'_, A> {
+        Iter {
+            next: self.head.as_deref(),
+        }
+    }
+
+    /// Panics if a node's links disagree with its neighbours' or the tail is out of date.
+    #[cfg(any(test, feature = "testing"))]
+    pub fn check_invariants(&self) {
+        let mut prev: Option<Rc<Node<A>>> = None;
+        let mut node = self.head.clone();
+        while let Some(n) = node {
+            assert!(
+                n.prev.borrow().as_ref().map(Rc::as_ptr) == prev.as_ref().map(Rc::as_ptr),
+                "node links back to the wrong node"
+            );
+            node = n.next.borrow().clone();
+            prev = Some(n);
+        }
+        assert!(
+            self.tail.as_ref().map(Rc::as_ptr) == prev.as_ref().map(Rc::as_ptr),
+            "tail is out of date"
+        );
+    }
+}
+
+/// An iterator over the elements of a `LinkedList` from head to tail.
+#[derive(Debug)]
+pub struct Iter<'a, A> {
+    next: Option<&'a Node<A>>,
+}
+
+impl<'a, A> Iterator for Iter<'a, A> {
+    type Item = &'a A;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.next?;
+        // The links are only modified through `&mut LinkedList`, so they can't change
+        // while the list is borrowed by this iterator
+        self.next = unsafe { (*node.next.as_ptr()).as_deref() };
+        Some(&node.key)
+    }
+}
+
+/// Copies the elements into new nodes, keeping their order.
+impl<A: Clone> Clone for LinkedList<A> {
+    fn clone(&self) -> Self {
+        let mut list = LinkedList::new();
+        let elements = self.iter().collect::<Vec<_>>();
+        for a in elements.into_iter().rev() {
+            list.push_head(a.clone());
+        }
+        list
+    }
+}
+
+// Neighbouring nodes hold strong references to each other, so the links have to be
+// broken explicitly or every node would be leaked
+impl<A> Drop for LinkedList<A> {
+    fn drop(&mut self) {
+        while self.pop_tail().is_some() {}
+    }
+}
+
+impl<A> MemoryFootprint for LinkedList<A> {
+    /// Each node is a separate `Rc` allocation, which also holds its reference counts.
+    fn heap_bytes(&self) -> usize {
+        self.node_count() * (2 * std::mem::size_of::<usize>() + std::mem::size_of::<Node<A>>())
+    }
+
+    fn node_count(&self) -> usize {
+        let mut count = 0;
+        self.for_each_node(|_| count += 1);
+        count
+    }
+}
+
+/// Prints the elements from head to tail.
+impl<A: Debug> Debug for LinkedList<A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut list = f.debug_list();
+        self.for_each(|a| {
+            list.entry(a);
+        });
+        list.finish()
+    }
+}
+
+/// Lists are compared by their elements from head to tail.
+impl<A: PartialEq> PartialEq for LinkedList<A> {
+    fn eq(&self, other: &Self) -> bool {
+        self.iter().eq(other.iter())
+    }
+}
+
+impl<A: Eq> Eq for LinkedList<A> {}
+
+impl<A: PartialOrd> PartialOrd for LinkedList<A> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.iter().partial_cmp(other.iter())
+    }
+}
+
+impl<A: Ord> Ord for LinkedList<A> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.iter().cmp(other.iter())
+    }
+}
+
+impl<A: Hash> Hash for LinkedList<A> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // Prefixing the length keeps nested lists from colliding
+        self.iter().count().hash(state);
+        self.iter().for_each(|a| a.hash(state));
+    }
+}
+
+/// Prints every node from head to tail along with the elements its links point to.
+impl<A: Debug> Debug for DebugStructure<'_, LinkedList<A>> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut list = f.debug_list();
+        self.0.for_each_node(|node| {
+            list.entry(node);
+        });
+        list.finish()
+    }
+}
 
 /// A handle to a particular node in a LinkedList. This is useful for
 /// random deletions. This handle will be rendered stale if the referenced
@@ -114,13 +229,23 @@ impl<A> LinkedList<A> {
 #[derive(Debug)]
 pub struct LinkedListHandle<K>(Weak<Node<K>>);
 
-#[derive(Debug)]
 struct Node<K> {
     key: K,
     prev: RefCell<Option<Rc<Node<K>>>>,
     next: RefCell<Option<Rc<Node<K>>>>,
 }
 
+// Following the links would recurse forever, so only the neighbouring elements are printed
+impl<K: Debug> Debug for Node<K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Node")
+            .field("key", &self.key)
+            .field("prev", &self.prev.borrow().as_ref().map(|n| &n.key))
+            .field("next", &self.next.borrow().as_ref().map(|n| &n.key))
+            .finish()
+    }
+}
+
 impl<K> Node<K> {
     pub fn new(key: K, prev: Option<Rc<Node<K>>>, next: Option<Rc<Node<K>>>) -> Self {
         Node {
@@ -130,3 +255,50 @@ impl<K> Node<K> {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::LinkedList;
+
+    #[test]
+    fn iter_and_compare() {
+        let mut list = LinkedList::new();
+        let handle = list.push_head(3);
+        list.push_head(2);
+        list.push_head(1);
+        assert_eq!(list.iter().collect::<Vec<_>>(), vec![&1, &2, &3]);
+        let mut other = LinkedList::new();
+        other.push_head(2);
+        other.push_head(1);
+        assert!(list > other);
+        list.remove(handle);
+        assert!(list == other);
+        assert_eq!(format!("{:?}", list), "[1, 2]");
+        list.check_invariants();
+        let copy = list.clone();
+        copy.check_invariants();
+        assert!(copy == list);
+    }
+
+    #[test]
+    fn owned_values() {
+        let mut list = LinkedList::new();
+        let a = list.push_head(String::from("a"));
+        list.push_head(String::from("b"));
+        list.push_head(String::from("c"));
+        list.remove(a);
+        assert_eq!(list.pop_tail(), Some(String::from("b")));
+        assert!(list.iter().eq(["c"]));
+    }
+
+    #[test]
+    fn drop_releases_nodes() {
+        let counter = std::rc::Rc::new(());
+        let mut list = LinkedList::new();
+        for _ in 0..3 {
+            list.push_head(counter.clone());
+        }
+        drop(list);
+        assert_eq!(std::rc::Rc::strong_count(&counter), 1);
+    }
+}
@@ -0,0 +1,475 @@
+use std::{
+    cmp::Ordering,
+    hash::{Hash, Hasher},
+    ops::{Bound, RangeBounds},
+};
+
+type Link<T> = Option<Box<Node<T>>>;
+
+/// An ordered set backed by a left-leaning red-black tree, which encodes a 2-3 tree as a
+/// binary tree by colouring the left link of each 3-node red. The method names follow
+/// `AVLTree` so the two can be swapped in comparisons.
+#[derive(Debug, Clone)]
+pub struct LLRBSet<T> {
+    root: Link<T>,
+    len: usize,
+}
+
+#[derive(Debug, Clone)]
+struct Node<T> {
+    key: T,
+    // Whether the link from the parent to this node is red
+    red: bool,
+    left: Link<T>,
+    right: Link<T>,
+}
+
+impl<T> LLRBSet<T> {
+    pub fn new() -> Self {
+        LLRBSet { root: None, len: 0 }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn height(&self) -> usize {
+        fn height<T>(link: &Link<T>) -> usize {
+            link.as_ref()
+                .map_or(0, |n| 1 + height(&n.left).max(height(&n.right)))
+        }
+        height(&self.root)
+    }
+
+    pub fn first(&self) -> Option<&T> {
+        let mut node = self.root.as_ref()?;
+        while let Some(left) = &node.left {
+            node = left;
+        }
+        Some(&node.key)
+    }
+
+    pub fn last(&self) -> Option<&T> {
+        let mut node = self.root.as_ref()?;
+        while let Some(right) = &node.right {
+            node = right;
+        }
+        Some(&node.key)
+    }
+
+    pub fn clear(&mut self) {
+        self.root = None;
+        self.len = 0;
+    }
+}
+
+impl<T: Ord> LLRBSet<T> {
+    pub fn contains(&self, key: &T) -> bool {
+        let mut link = &self.root;
+        while let Some(node) = link {
+            link = match key.cmp(&node.key) {
+                Ordering::Less => &node.left,
+                Ordering::Greater => &node.right,
+                Ordering::Equal => return true,
+            };
+        }
+        false
+    }
+
+    /// Inserts the key, returning whether it was absent.
+    pub fn insert(&mut self, key: T) -> bool {
+        let (mut root, inserted) = insert(self.root.take(), key);
+        root.red = false;
+        self.root = Some(root);
+        self.len += inserted as usize;
+        inserted
+    }
+
+    /// Removes the key, returning whether it was present.
+    pub fn remove(&mut self, key: &T) -> bool {
+        if !self.contains(key) {
+            return false;
+        }
+        let mut root = self.root.take().unwrap();
+        if !is_red(&root.left) && !is_red(&root.right) {
+            root.red = true;
+        }
+        self.root = remove(root, key);
+        if let Some(root) = &mut self.root {
+            root.red = false;
+        }
+        self.len -= 1;
+        true
+    }
+
+    /// Returns an iterator over the keys in ascending order.
+    pub fn iter(&self) -> Iter<'_, T> {
+        self.range(..)
+    }
+
+    /// Returns an iterator over the keys within `range`, in ascending order.
+    pub fn range<R: RangeBounds<T>>(&self, range: R) -> Iter<'_, T> {
+        let mut iter = Iter {
+            stack: vec![],
+            last: self.last_node_within(range.end_bound()),
+        };
+        iter.push_left_edge(&self.root, range.start_bound());
+        iter
+    }
+
+    /// Panics if the keys are out of order, the length is wrong, or a red-black
+    /// invariant is broken.
+    #[cfg(any(test, feature = "testing"))]
+    pub fn check_invariants(&self) {
+        let mut iter = self.iter();
+        let mut len = 0;
+        if let Some(mut prev) = iter.next() {
+            len += 1;
+            for key in iter {
+                assert!(prev < key, "keys are out of order");
+                prev = key;
+                len += 1;
+            }
+        }
+        assert_eq!(len, self.len, "length is out of date");
+        assert!(!is_red(&self.root), "the root is red");
+        assert!(
+            black_height(&self.root).is_some(),
+            "red-black invariant broken"
+        );
+    }
+
+    /// Finds the node with the largest key that is within the upper bound.
+    fn last_node_within(&self, bound: Bound<&T>) -> Option<&Node<T>> {
+        let mut link = &self.root;
+        let mut last = None;
+        while let Some(node) = link {
+            let within = match bound {
+                Bound::Included(k) => node.key <= *k,
+                Bound::Excluded(k) => node.key < *k,
+                Bound::Unbounded => true,
+            };
+            if within {
+                last = Some(&**node);
+                link = &node.right;
+            } else {
+                link = &node.left;
+            }
+        }
+        last
+    }
+}
+
+fn is_red<T>(link: &Link<T>) -> bool {
+    link.as_ref().is_some_and(|n| n.red)
+}
+
+/// Returns the black height of a subtree, or none if a red node has a red child,
+/// a right child is red, or two paths have different black heights.
+#[cfg(any(test, feature = "testing"))]
+fn black_height<T>(link: &Link<T>) -> Option<usize> {
+    let Some(node) = link else {
+        return Some(0);
+    };
+    if is_red(&node.right) || (node.red && is_red(&node.left)) {
+        return None;
+    }
+    let left = black_height(&node.left)?;
+    let right = black_height(&node.right)?;
+    (left == right).then_some(left + !node.red as usize)
+}
+
+fn rotate_left<T>(mut h: Box<Node<T>>) -> Box<Node<T>> {
+    let mut x = h.right.take().unwrap();
+    h.right = x.left.take();
+    x.red = h.red;
+    h.red = true;
+    x.left = Some(h);
+    x
+}
+
+fn rotate_right<T>(mut h: Box<Node<T>>) -> Box<Node<T>> {
+    let mut x = h.left.take().unwrap();
+    h.left = x.right.take();
+    x.red = h.red;
+    h.red = true;
+    x.right = Some(h);
+    x
+}
+
+/// Splits or merges a 4-node by flipping the colours of a node and its children.
+fn flip_colors<T>(h: &mut Node<T>) {
+    h.red = !h.red;
+    for child in [&mut h.left, &mut h.right].into_iter().flatten() {
+        child.red = !child.red;
+    }
+}
+
+/// Restores the left-leaning invariants on the way back up the tree.
+fn balance<T>(mut h: Box<Node<T>>) -> Box<Node<T>> {
+    if is_red(&h.right) && !is_red(&h.left) {
+        h = rotate_left(h);
+    }
+    if is_red(&h.left) && is_red(&h.left.as_ref().unwrap().left) {
+        h = rotate_right(h);
+    }
+    if is_red(&h.left) && is_red(&h.right) {
+        flip_colors(&mut h);
+    }
+    h
+}
+
+fn insert<T: Ord>(link: Link<T>, key: T) -> (Box<Node<T>>, bool) {
+    let Some(mut h) = link else {
+        let node = Node {
+            key,
+            red: true,
+            left: None,
+            right: None,
+        };
+        return (Box::new(node), true);
+    };
+    let inserted = match key.cmp(&h.key) {
+        Ordering::Less => {
+            let (left, inserted) = insert(h.left.take(), key);
+            h.left = Some(left);
+            inserted
+        }
+        Ordering::Greater => {
+            let (right, inserted) = insert(h.right.take(), key);
+            h.right = Some(right);
+            inserted
+        }
+        Ordering::Equal => false,
+    };
+    (balance(h), inserted)
+}
+
+/// Borrows from the right sibling so that the left child is not a 2-node.
+fn move_red_left<T>(mut h: Box<Node<T>>) -> Box<Node<T>> {
+    flip_colors(&mut h);
+    if is_red(&h.right.as_ref().unwrap().left) {
+        h.right = Some(rotate_right(h.right.take().unwrap()));
+        h = rotate_left(h);
+        flip_colors(&mut h);
+    }
+    h
+}
+
+/// Borrows from the left sibling so that the right child is not a 2-node.
+fn move_red_right<T>(mut h: Box<Node<T>>) -> Box<Node<T>> {
+    flip_colors(&mut h);
+    if is_red(&h.left.as_ref().unwrap().left) {
+        h = rotate_right(h);
+        flip_colors(&mut h);
+    }
+    h
+}
+
+/// Removes the smallest key in the subtree, returning the new subtree and the key.
+fn remove_min<T>(mut h: Box<Node<T>>) -> (Link<T>, T) {
+    let Some(left) = &h.left else {
+        return (None, h.key);
+    };
+    if !left.red && !is_red(&left.left) {
+        h = move_red_left(h);
+    }
+    let (left, min) = remove_min(h.left.take().unwrap());
+    h.left = left;
+    (Some(balance(h)), min)
+}
+
+/// Removes a key which is known to be in the subtree.
+fn remove<T: Ord>(mut h: Box<Node<T>>, key: &T) -> Link<T> {
+    if *key < h.key {
+        if !is_red(&h.left) && !is_red(&h.left.as_ref().unwrap().left) {
+            h = move_red_left(h);
+        }
+        h.left = remove(h.left.take().unwrap(), key);
+    } else {
+        if is_red(&h.left) {
+            h = rotate_right(h);
+        }
+        if *key == h.key && h.right.is_none() {
+            return None;
+        }
+        if !is_red(&h.right) && !is_red(&h.right.as_ref().unwrap().left) {
+            h = move_red_right(h);
+        }
+        if *key == h.key {
+            let (right, min) = remove_min(h.right.take().unwrap());
+            h.key = min;
+            h.right = right;
+        } else {
+            h.right = remove(h.right.take().unwrap(), key);
+        }
+    }
+    Some(balance(h))
+}
+
+impl<T> Default for LLRBSet<T> {
+    fn default() -> Self {
+        LLRBSet::new()
+    }
+}
+
+/// Sets are compared by their elements in ascending order, regardless of their shape.
+impl<T: Ord> PartialEq for LLRBSet<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len && self.iter().eq(other.iter())
+    }
+}
+
+impl<T: Ord> Eq for LLRBSet<T> {}
+
+impl<T: Ord> PartialOrd for LLRBSet<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T: Ord> Ord for LLRBSet<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.iter().cmp(other.iter())
+    }
+}
+
+impl<T: Ord + Hash> Hash for LLRBSet<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.len.hash(state);
+        self.iter().for_each(|t| t.hash(state));
+    }
+}
+
+impl<T: Ord> FromIterator<T> for LLRBSet<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut set = LLRBSet::new();
+        set.extend(iter);
+        set
+    }
+}
+
+impl<T: Ord> Extend<T> for LLRBSet<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for key in iter {
+            self.insert(key);
+        }
+    }
+}
+
+/// An in-order iterator over the keys of an `LLRBSet`.
+#[derive(Debug)]
+pub struct Iter<'a, T> {
+    stack: Vec<&'a Node<T>>,
+    // The final node to yield
+    last: Option<&'a Node<T>>,
+}
+
+impl<'a, T: Ord> Iter<'a, T> {
+    /// Pushes every node along the path to the smallest key within the lower bound.
+    fn push_left_edge(&mut self, mut link: &'a Link<T>, bound: Bound<&T>) {
+        while let Some(node) = link {
+            let within = match bound {
+                Bound::Included(k) => node.key >= *k,
+                Bound::Excluded(k) => node.key > *k,
+                Bound::Unbounded => true,
+            };
+            if within {
+                self.stack.push(node);
+                link = &node.left;
+            } else {
+                link = &node.right;
+            }
+        }
+    }
+}
+
+impl<'a, T: Ord> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.stack.pop()?;
+        let last = self.last?;
+        if node.key > last.key {
+            self.stack.clear();
+            return None;
+        }
+        if std::ptr::eq(node, last) {
+            self.stack.clear();
+            self.last = None;
+        } else {
+            self.push_left_edge(&node.right, Bound::Unbounded);
+        }
+        Some(&node.key)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeSet;
+
+    use quickcheck::quickcheck;
+
+    use super::LLRBSet;
+
+    #[test]
+    fn insert_remove() {
+        let mut set = (0..100).collect::<LLRBSet<_>>();
+        assert!(!set.insert(5));
+        assert!(set.remove(&5));
+        assert!(!set.remove(&5));
+        assert!(!set.contains(&5));
+        assert_eq!(set.len(), 99);
+        assert_eq!(set.first(), Some(&0));
+        assert_eq!(set.last(), Some(&99));
+        assert!(set.height() <= 2 * 7);
+        assert_eq!(
+            set.range(3..8).copied().collect::<Vec<_>>(),
+            vec![3, 4, 6, 7]
+        );
+    }
+
+    #[test]
+    fn prop_matches_btree_set() {
+        fn p(ops: Vec<(bool, u8)>) -> bool {
+            let mut set = LLRBSet::new();
+            let mut model = BTreeSet::new();
+            for (insert, k) in ops {
+                let agree = if insert {
+                    set.insert(k) == model.insert(k)
+                } else {
+                    set.remove(&k) == model.remove(&k)
+                };
+                set.check_invariants();
+                if !agree {
+                    return false;
+                }
+            }
+            set.len() == model.len()
+                && set.iter().eq(model.iter())
+                && set.range(10..=20).eq(model.range(10..=20))
+        }
+        quickcheck(p as fn(Vec<(bool, u8)>) -> bool)
+    }
+
+    #[test]
+    fn prop_compares_like_btree_set() {
+        fn p(a: Vec<u8>, b: Vec<u8>) -> bool {
+            let (x, y) = (
+                a.iter().collect::<LLRBSet<_>>(),
+                b.iter().collect::<LLRBSet<_>>(),
+            );
+            let (bx, by) = (
+                a.iter().collect::<BTreeSet<_>>(),
+                b.iter().collect::<BTreeSet<_>>(),
+            );
+            let reversed = a.iter().rev().collect::<LLRBSet<_>>();
+            x.cmp(&y) == bx.cmp(&by) && (x == y) == (bx == by) && x == reversed
+        }
+        quickcheck(p as fn(Vec<u8>, Vec<u8>) -> bool)
+    }
+}
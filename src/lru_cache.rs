@@ -1,14 +1,32 @@
-use std::{collections::HashMap, fmt::Debug, hash::Hash};
+use std::{
+    borrow::Borrow,
+    collections::{hash_map::RandomState, HashMap},
+    fmt::{self, Debug},
+    hash::{BuildHasher, Hash},
+};
 
-use crate::linked_list::{LinkedList, LinkedListHandle};
+use crate::{
+    bounded_map::BoundedMap,
+    capacity::Capacity,
+    debug::DebugStructure,
+    footprint::{hash_map_bytes, MemoryFootprint},
+    index_list::{IndexList, IndexListHandle},
+    Error,
+};
 
-#[derive(Debug)]
-pub struct LRUCache<K, V> {
-    entries: HashMap<K, V>,
-    recent: HashMap<K, LinkedListHandle<K>>,
-    list: LinkedList<K>,
+/// A cache which evicts the least recently used entries once it holds `capacity` entries,
+/// or, if it's `weighted`, once the weights of its entries would sum to more than a budget.
+#[derive(Clone)]
+pub struct LRUCache<K, V, S = RandomState> {
+    entries: HashMap<K, V, S>,
+    // Each key's node in the recency list and the weight it was inserted with
+    recent: HashMap<K, (IndexListHandle, usize), S>,
+    list: IndexList<K>,
     size: usize,
-    capacity: usize,
+    capacity: Capacity,
+    // The total weight of the entries, which is `size` under an entry budget
+    used: usize,
+    weigher: fn(&K, &V) -> usize,
 }
 
 impl<K, V> LRUCache<K, V>
@@ -16,58 +34,476 @@ where
     K: Clone,
 {
     pub fn new(capacity: usize) -> Self {
+        LRUCache::with_hasher(capacity, RandomState::new())
+    }
+
+    /// Creates a cache holding entries whose weights, as measured by `weigher` when each is
+    /// inserted, sum to at most `max_bytes`.
+    pub fn weighted(max_bytes: usize, weigher: fn(&K, &V) -> usize) -> Self {
+        LRUCache::weighted_with_hasher(max_bytes, weigher, RandomState::new())
+    }
+}
+
+impl<K, V, S> LRUCache<K, V, S>
+where
+    K: Clone,
+    S: Clone,
+{
+    pub fn with_hasher(capacity: usize, hasher: S) -> Self {
+        LRUCache::with_budget(Capacity::Entries(capacity), |_, _| 1, hasher)
+    }
+
+    pub fn weighted_with_hasher(max_bytes: usize, weigher: fn(&K, &V) -> usize, hasher: S) -> Self {
+        LRUCache::with_budget(Capacity::Bytes(max_bytes), weigher, hasher)
+    }
+
+    fn with_budget(capacity: Capacity, weigher: fn(&K, &V) -> usize, hasher: S) -> Self {
         LRUCache {
-            entries: HashMap::new(),
-            recent: HashMap::new(),
-            list: LinkedList::new(),
+            entries: HashMap::with_hasher(hasher.clone()),
+            recent: HashMap::with_hasher(hasher),
+            list: IndexList::new(),
             size: 0,
             capacity,
+            used: 0,
+            weigher,
         }
     }
 }
 
-impl<K, V> LRUCache<K, V>
+impl<K, V, S> LRUCache<K, V, S>
 where
     K: Eq + Hash + Clone,
+    S: BuildHasher,
 {
     pub fn insert(&mut self, k: K, v: V) {
-        if let Some(value) = self.entries.get_mut(&k) {
-            *value = v;
-            return;
+        self.insert_evicting_all(k, v);
+    }
+
+    /// Inserts the entry, replacing the value of an existing key, and returns the entries
+    /// evicted to stay within the budget from least to most recently used. That is the new
+    /// entry itself if it weighs more than the whole budget.
+    /// A replaced value keeps its place in the recency order unless its new weight forces
+    /// an eviction, in which case it's reinserted as the most recently used entry.
+    pub fn insert_evicting_all(&mut self, k: K, v: V) -> Vec<(K, V)> {
+        let weight = self.weigh(&k, &v);
+        if let Some(&(_, old)) = self.recent.get(&k) {
+            if self.capacity.fits(self.used - old, weight) {
+                self.recent.get_mut(&k).unwrap().1 = weight;
+                self.used = self.used - old + weight;
+                *self.entries.get_mut(&k).unwrap() = v;
+                return vec![];
+            }
+            self.remove(&k);
+        }
+        if !self.capacity.fits(0, weight) {
+            return vec![(k, v)];
         }
 
-        if self.size < self.capacity {
-            self.size += 1;
-        } else {
-            let removed = self.list.pop_tail().unwrap();
-            self.recent.remove(&removed);
-            self.entries.remove(&removed);
+        let mut evicted = vec![];
+        while !self.capacity.fits(self.used, weight) {
+            #[cfg(feature = "tracing")]
+            tracing::debug!(
+                capacity = self.capacity.limit(),
+                "evicting least recently used entry"
+            );
+            evicted.push(self.pop_least_recent().unwrap());
         }
+        self.size += 1;
+        self.used += weight;
 
         let handle = self.list.push_head(k.clone());
-        self.recent.insert(k.clone(), handle);
+        self.recent.insert(k.clone(), (handle, weight));
 
         self.entries.insert(k, v);
+        evicted
     }
 
-    pub fn get(&mut self, k: &K) -> Option<&V> {
-        let handle = self.recent.remove(k);
-        if let Some(handle) = handle {
-            self.list.remove(handle);
+    /// Inserts the entry only if the key is absent and the cache has room, so nothing is
+    /// overwritten or evicted. Otherwise the entry is handed back in the error.
+    pub fn try_insert(&mut self, k: K, v: V) -> Result<(), Error<(K, V)>> {
+        if self.entries.contains_key(&k) {
+            return Err(Error::Exists((k, v)));
         }
-        let new_handle = self.list.push_head(k.clone());
-        self.recent.insert(k.clone(), new_handle);
+        if !self.capacity.fits(self.used, self.weigh(&k, &v)) {
+            return Err(Error::Full((k, v)));
+        }
+        self.insert(k, v);
+        Ok(())
+    }
+
+    pub fn get(&mut self, k: &K) -> Option<&V> {
+        self.list.move_to_head(self.recent.get(k)?.0);
         self.entries.get(k)
     }
 
+    /// The entry keeps the weight it was inserted with, however the value is changed.
     pub fn get_mut(&mut self, k: &K) -> Option<&mut V> {
         self.entries.get_mut(k)
     }
+
+    /// Looks up the entry for a borrowed key, so that an owned key is only made from it
+    /// if a value is inserted. Finding the key counts as a use of it, like `get`.
+    pub fn entry_ref<'a, 'q, Q>(&'a mut self, k: &'q Q) -> EntryRef<'a, 'q, K, Q, V, S>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        match self.recent.get(k) {
+            Some(&(handle, _)) => {
+                self.list.move_to_head(handle);
+                EntryRef::Occupied(OccupiedEntryRef {
+                    cache: self,
+                    key: k,
+                })
+            }
+            None => EntryRef::Vacant(VacantEntryRef {
+                cache: self,
+                key: k,
+            }),
+        }
+    }
+
+    pub fn contains_key(&self, k: &K) -> bool {
+        self.entries.contains_key(k)
+    }
+
+    pub fn remove(&mut self, k: &K) -> Option<V> {
+        let (handle, weight) = self.recent.remove(k)?;
+        self.list.remove(handle);
+        self.size -= 1;
+        self.used -= weight;
+        self.entries.remove(k)
+    }
+
+    /// Returns the keys from least to most recently used, consuming the cache.
+    pub fn into_keys(self) -> impl Iterator<Item = K> {
+        self.into_iter().map(|(k, _)| k)
+    }
+
+    /// Returns the values from least to most recently used, consuming the cache.
+    pub fn into_values(self) -> impl Iterator<Item = V> {
+        self.into_iter().map(|(_, v)| v)
+    }
+
+    fn pop_least_recent(&mut self) -> Option<(K, V)> {
+        let k = self.list.pop_tail()?;
+        let (_, weight) = self.recent.remove(&k).unwrap();
+        self.size -= 1;
+        self.used -= weight;
+        let v = self.entries.remove(&k).unwrap();
+        Some((k, v))
+    }
+
+    fn weigh(&self, k: &K, v: &V) -> usize {
+        self.capacity.weigh(|| (self.weigher)(k, v))
+    }
+}
+
+impl<K, V, S> LRUCache<K, V, S> {
+    pub fn len(&self) -> usize {
+        self.size
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    /// The size of the budget, in entries or, for a weighted cache, bytes.
+    pub fn capacity(&self) -> usize {
+        self.capacity.limit()
+    }
+
+    pub fn budget(&self) -> Capacity {
+        self.capacity
+    }
+
+    /// The total weight of the entries, which is their number unless the cache is weighted.
+    pub fn weight(&self) -> usize {
+        self.used
+    }
+
+    /// How much of the budget is left before inserting evicts anything.
+    pub fn remaining_capacity(&self) -> usize {
+        self.capacity.remaining(self.used)
+    }
+}
+
+/// Evicts the least recently used entry. A weighted cache may need to evict several to make
+/// room, of which `insert_evicting` only returns the first; `insert_evicting_all` returns
+/// them all.
+impl<K, V, S> BoundedMap<K, V> for LRUCache<K, V, S>
+where
+    K: Eq + Hash + Clone,
+    S: BuildHasher,
+{
+    fn capacity(&self) -> usize {
+        self.capacity.limit()
+    }
+
+    fn remaining_capacity(&self) -> usize {
+        LRUCache::remaining_capacity(self)
+    }
+
+    fn is_full(&self) -> bool {
+        self.remaining_capacity() == 0
+    }
+
+    fn len(&self) -> usize {
+        self.size
+    }
+
+    fn contains_key(&self, k: &K) -> bool {
+        self.entries.contains_key(k)
+    }
+
+    fn get(&mut self, k: &K) -> Option<&V> {
+        LRUCache::get(self, k)
+    }
+
+    fn insert_evicting(&mut self, k: K, v: V) -> Option<(K, V)> {
+        self.insert_evicting_all(k, v).into_iter().next()
+    }
+
+    fn remove(&mut self, k: &K) -> Option<V> {
+        LRUCache::remove(self, k)
+    }
+
+    fn try_insert(&mut self, k: K, v: V) -> Result<(), Error<(K, V)>> {
+        LRUCache::try_insert(self, k, v)
+    }
+
+    fn victim(&self) -> Option<&K> {
+        self.list.tail()
+    }
+}
+
+impl<K, V, S> IntoIterator for LRUCache<K, V, S>
+where
+    K: Eq + Hash + Clone,
+    S: BuildHasher,
+{
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V, S>;
+
+    fn into_iter(self) -> IntoIter<K, V, S> {
+        IntoIter { cache: self }
+    }
+}
+
+/// An iterator which moves the entries out of an `LRUCache` in the order they would have
+/// been evicted, from least to most recently used.
+pub struct IntoIter<K, V, S = RandomState> {
+    cache: LRUCache<K, V, S>,
+}
+
+impl<K, V, S> Iterator for IntoIter<K, V, S>
+where
+    K: Eq + Hash + Clone,
+    S: BuildHasher,
+{
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<(K, V)> {
+        self.cache.pop_least_recent()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.cache.size, Some(self.cache.size))
+    }
+}
+
+impl<K, V, S> ExactSizeIterator for IntoIter<K, V, S>
+where
+    K: Eq + Hash + Clone,
+    S: BuildHasher,
+{
+}
+
+/// Prints the entries from most to least recently used.
+impl<K, V, S> Debug for LRUCache<K, V, S>
+where
+    K: Eq + Hash + Debug,
+    V: Debug,
+    S: BuildHasher,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut map = f.debug_map();
+        self.list.for_each(|k| {
+            map.entry(k, &self.entries[k]);
+        });
+        map.finish()
+    }
+}
+
+/// Prints the capacity and the nodes of the recency list alongside the entries.
+impl<K, V, S> Debug for DebugStructure<'_, LRUCache<K, V, S>>
+where
+    K: Eq + Hash + Debug,
+    V: Debug,
+    S: BuildHasher,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LRUCache")
+            .field("size", &self.0.size)
+            .field("capacity", &self.0.capacity.limit())
+            .field("entries", self.0)
+            .field("list", &DebugStructure(&self.0.list))
+            .finish()
+    }
+}
+
+impl<K, V, S> MemoryFootprint for LRUCache<K, V, S> {
+    fn heap_bytes(&self) -> usize {
+        hash_map_bytes(&self.entries) + hash_map_bytes(&self.recent) + self.list.heap_bytes()
+    }
+
+    /// The number of nodes in the recency list, one per cached entry.
+    fn node_count(&self) -> usize {
+        self.list.node_count()
+    }
+}
+
+/// An entry of an `LRUCache` looked up by a borrowed key, as returned by `entry_ref`.
+pub enum EntryRef<'a, 'q, K, Q: ?Sized, V, S = RandomState> {
+    Occupied(OccupiedEntryRef<'a, 'q, K, Q, V, S>),
+    Vacant(VacantEntryRef<'a, 'q, K, Q, V, S>),
+}
+
+impl<'a, 'q, K, Q, V, S> EntryRef<'a, 'q, K, Q, V, S>
+where
+    K: Eq + Hash + Clone + Borrow<Q> + From<&'q Q>,
+    Q: Eq + Hash + ?Sized,
+    S: BuildHasher,
+{
+    pub fn key(&self) -> &'q Q {
+        match self {
+            EntryRef::Occupied(entry) => entry.key,
+            EntryRef::Vacant(entry) => entry.key,
+        }
+    }
+
+    /// Panics if the key is absent and the entry weighs more than the cache's capacity.
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        self.or_insert_with(|| default)
+    }
+
+    /// Panics if the key is absent and the entry weighs more than the cache's capacity.
+    pub fn or_insert_with<F: FnOnce() -> V>(self, default: F) -> &'a mut V {
+        match self {
+            EntryRef::Occupied(entry) => entry.into_mut(),
+            EntryRef::Vacant(entry) => entry.insert(default()),
+        }
+    }
+
+    /// Panics if the key is absent and the entry weighs more than the cache's capacity.
+    pub fn or_default(self) -> &'a mut V
+    where
+        V: Default,
+    {
+        self.or_insert_with(V::default)
+    }
+
+    pub fn and_modify<F: FnOnce(&mut V)>(mut self, f: F) -> Self {
+        if let EntryRef::Occupied(entry) = &mut self {
+            f(entry.get_mut());
+        }
+        self
+    }
+}
+
+pub struct OccupiedEntryRef<'a, 'q, K, Q: ?Sized, V, S = RandomState> {
+    cache: &'a mut LRUCache<K, V, S>,
+    key: &'q Q,
+}
+
+impl<'a, K, Q, V, S> OccupiedEntryRef<'a, '_, K, Q, V, S>
+where
+    K: Eq + Hash + Borrow<Q>,
+    Q: Eq + Hash + ?Sized,
+    S: BuildHasher,
+{
+    /// The key stored in the cache.
+    pub fn key(&self) -> &K {
+        self.cache.entries.get_key_value(self.key).unwrap().0
+    }
+
+    pub fn get(&self) -> &V {
+        &self.cache.entries[self.key]
+    }
+
+    pub fn get_mut(&mut self) -> &mut V {
+        self.cache.entries.get_mut(self.key).unwrap()
+    }
+
+    pub fn into_mut(self) -> &'a mut V {
+        self.cache.entries.get_mut(self.key).unwrap()
+    }
+
+    /// Replaces the value, returning the old one. The entry is reweighed, and if it no
+    /// longer fits alongside the others, the least recently used of them are evicted and
+    /// dropped.
+    /// Panics if the entry weighs more than the cache's whole capacity, like inserting a
+    /// vacant entry.
+    pub fn insert(&mut self, v: V) -> V
+    where
+        K: Clone,
+    {
+        let cache = &mut *self.cache;
+        let weight = cache.weigh(cache.entries.get_key_value(self.key).unwrap().0, &v);
+        assert!(cache.capacity.fits(0, weight), "cache has no capacity");
+        // Finding the entry made it the most recently used, so it's evicted last, and the
+        // loop stops before that as it fits on its own
+        let old = cache.recent[self.key].1;
+        while !cache.capacity.fits(cache.used - old, weight) {
+            cache.pop_least_recent();
+        }
+        cache.recent.get_mut(self.key).unwrap().1 = weight;
+        cache.used = cache.used - old + weight;
+        std::mem::replace(cache.entries.get_mut(self.key).unwrap(), v)
+    }
+
+    pub fn remove(self) -> V {
+        let cache = self.cache;
+        let (handle, weight) = cache.recent.remove(self.key).unwrap();
+        cache.list.remove(handle);
+        cache.size -= 1;
+        cache.used -= weight;
+        cache.entries.remove(self.key).unwrap()
+    }
+}
+
+pub struct VacantEntryRef<'a, 'q, K, Q: ?Sized, V, S = RandomState> {
+    cache: &'a mut LRUCache<K, V, S>,
+    key: &'q Q,
+}
+
+impl<'a, 'q, K, Q, V, S> VacantEntryRef<'a, 'q, K, Q, V, S>
+where
+    K: Eq + Hash + Clone + Borrow<Q> + From<&'q Q>,
+    Q: Eq + Hash + ?Sized,
+    S: BuildHasher,
+{
+    pub fn key(&self) -> &'q Q {
+        self.key
+    }
+
+    /// Makes the owned key and inserts the value as the most recently used entry,
+    /// evicting the least recently used ones if the cache is full.
+    /// Panics if the entry weighs more than the cache's whole capacity, such as when the
+    /// capacity is zero, since the entry couldn't be kept.
+    pub fn insert(self, v: V) -> &'a mut V {
+        let k = K::from(self.key);
+        let weight = self.cache.weigh(&k, &v);
+        assert!(self.cache.capacity.fits(0, weight), "cache has no capacity");
+        self.cache.insert(k, v);
+        self.cache.entries.get_mut(self.key).unwrap()
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use super::LRUCache;
+    use super::{EntryRef, LRUCache};
+    use crate::{debug::DebugStructure, footprint::MemoryFootprint, Error};
 
     #[test]
     fn cache_retrieve() {
@@ -76,6 +512,34 @@ mod test {
         assert_eq!(cache.get(&1), Some(&100));
     }
 
+    #[test]
+    fn entry_ref() {
+        let mut cache = LRUCache::<String, usize>::new(2);
+        *cache.entry_ref("a").or_default() += 1;
+        *cache.entry_ref("b").or_default() += 1;
+        // Finding `a` makes it the most recently used, so `b` is evicted for `c`
+        *cache.entry_ref("a").or_default() += 1;
+        cache.entry_ref("c").or_insert(7);
+        assert_eq!(cache.get(&"a".to_string()), Some(&2));
+        assert!(!cache.contains_key(&"b".to_string()));
+        match cache.entry_ref("c") {
+            EntryRef::Occupied(mut entry) => {
+                assert_eq!(entry.key(), "c");
+                assert_eq!(entry.insert(8), 7);
+                assert_eq!(entry.remove(), 8);
+            }
+            EntryRef::Vacant(_) => panic!("c is present"),
+        }
+        assert_eq!(cache.len(), 1);
+        assert!(cache.into_keys().eq(["a".to_string()]));
+    }
+
+    #[test]
+    #[should_panic(expected = "cache has no capacity")]
+    fn entry_ref_without_capacity() {
+        LRUCache::<String, u8>::new(0).entry_ref("a").or_insert(1);
+    }
+
     #[test]
     fn cache_evict() {
         let mut cache = LRUCache::new(2);
@@ -98,4 +562,149 @@ mod test {
         assert_eq!(cache.get(&2), None);
         assert_eq!(cache.get(&3), Some(&103));
     }
+
+    #[test]
+    fn weighted() {
+        let mut cache = LRUCache::<u32, String>::weighted(10, |_, v| v.len());
+        cache.insert(1, "aaaa".to_string());
+        cache.insert(2, "bbb".to_string());
+        assert_eq!(cache.weight(), 7);
+        assert_eq!(cache.remaining_capacity(), 3);
+        cache.get(&1);
+        // Making room for six bytes evicts both of the others, least recent first
+        let evicted = cache.insert_evicting_all(3, "cccccc".to_string());
+        assert_eq!(evicted, vec![(2, "bbb".to_string())]);
+        let evicted = cache.insert_evicting_all(4, "dddddd".to_string());
+        assert_eq!(
+            evicted,
+            vec![(1, "aaaa".to_string()), (3, "cccccc".to_string())]
+        );
+        assert_eq!(cache.len(), 1);
+        // Too heavy for the whole budget
+        let heavy = "e".repeat(11);
+        assert_eq!(
+            cache.insert_evicting_all(5, heavy.clone()),
+            vec![(5, heavy.clone())]
+        );
+        assert_eq!(
+            cache.try_insert(5, "eeeee".to_string()),
+            Err(Error::Full((5, "eeeee".to_string())))
+        );
+        // Growing a value in place reweighs it
+        cache.insert(4, "d".to_string());
+        assert_eq!(cache.weight(), 1);
+        assert_eq!(cache.remove(&4), Some("d".to_string()));
+        assert_eq!(cache.remaining_capacity(), 10);
+    }
+
+    #[test]
+    fn weighted_entry_ref() {
+        let mut cache = LRUCache::<String, String>::weighted(10, |_, v| v.len());
+        cache.insert("a".to_string(), "aaa".to_string());
+        cache.insert("b".to_string(), "bbb".to_string());
+        cache.insert("c".to_string(), "cc".to_string());
+        let EntryRef::Occupied(mut entry) = cache.entry_ref("c") else {
+            panic!("c is present");
+        };
+        // Growing `c` to six bytes evicts `a`, the least recently used
+        assert_eq!(entry.insert("cccccc".to_string()), "cc");
+        assert_eq!(entry.get(), "cccccc");
+        assert_eq!(cache.weight(), 9);
+        assert!(!cache.contains_key(&"a".to_string()));
+        let EntryRef::Occupied(mut entry) = cache.entry_ref("c") else {
+            panic!("c is present");
+        };
+        entry.insert("c".to_string());
+        assert_eq!(cache.weight(), 4);
+        assert_eq!(cache.remaining_capacity(), 6);
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "cache has no capacity")]
+    fn weighted_entry_ref_too_heavy() {
+        let mut cache = LRUCache::<String, String>::weighted(4, |_, v| v.len());
+        cache.insert("a".to_string(), "a".to_string());
+        if let EntryRef::Occupied(mut entry) = cache.entry_ref("a") {
+            entry.insert("aaaaa".to_string());
+        }
+    }
+
+    #[test]
+    fn cache_into_iterator() {
+        let mut cache = LRUCache::new(3);
+        cache.insert(1, String::from("a"));
+        cache.insert(2, String::from("b"));
+        cache.insert(3, String::from("c"));
+        cache.get(&1);
+        let mut iter = cache.into_iter();
+        assert_eq!(iter.len(), 3);
+        assert_eq!(iter.next(), Some((2, String::from("b"))));
+        assert_eq!(iter.len(), 2);
+        assert_eq!(
+            iter.collect::<Vec<_>>(),
+            vec![(3, String::from("c")), (1, String::from("a"))]
+        );
+
+        let fill = || {
+            let mut cache = LRUCache::new(2);
+            cache.insert('x', 1);
+            cache.insert('y', 2);
+            cache.insert('z', 3);
+            cache
+        };
+        assert_eq!(fill().into_keys().collect::<Vec<_>>(), vec!['y', 'z']);
+        assert_eq!(fill().into_values().collect::<Vec<_>>(), vec![2, 3]);
+    }
+
+    #[test]
+    fn cache_get_absent() {
+        let mut cache = LRUCache::new(2);
+        cache.insert(1, 101);
+        cache.insert(2, 102);
+        assert_eq!(cache.get(&3), None);
+        cache.insert(4, 104);
+        cache.insert(5, 105);
+        cache.insert(6, 106);
+        assert_eq!(cache.get(&4), None);
+        assert_eq!(cache.get(&5), Some(&105));
+        assert_eq!(cache.get(&6), Some(&106));
+    }
+
+    #[test]
+    fn try_insert() {
+        let mut cache = LRUCache::new(2);
+        assert_eq!(cache.try_insert(1, 101), Ok(()));
+        assert_eq!(cache.try_insert(1, 201), Err(Error::Exists((1, 201))));
+        assert_eq!(cache.try_insert(2, 102), Ok(()));
+        assert_eq!(cache.try_insert(3, 103), Err(Error::Full((3, 103))));
+        assert_eq!(cache.get(&1), Some(&101));
+        assert_eq!(cache.get(&3), None);
+    }
+
+    #[test]
+    fn footprint() {
+        let mut cache = LRUCache::new(2);
+        assert_eq!(cache.node_count(), 0);
+        cache.insert(1, 101);
+        cache.insert(2, 102);
+        cache.insert(3, 103);
+        assert_eq!(cache.node_count(), 2);
+        assert!(cache.heap_bytes() > 0);
+    }
+
+    #[test]
+    fn debug() {
+        let mut cache = LRUCache::new(2);
+        cache.insert(1, 101);
+        cache.insert(2, 102);
+        cache.get(&1);
+        assert_eq!(format!("{:?}", cache), "{1: 101, 2: 102}");
+        assert_eq!(
+            format!("{:?}", DebugStructure(&cache)),
+            "LRUCache { size: 2, capacity: 2, entries: {1: 101, 2: 102}, list: \
+             [Node { key: 1, prev: None, next: Some(2) }, \
+             Node { key: 2, prev: Some(1), next: None }] }"
+        );
+    }
 }
@@ -0,0 +1,530 @@
+//! Merge joins over sorted iterators, such as those of `AVLTree`, `BSTree` and `SortedList`.
+//! Walking both inputs in lockstep gives unions, intersections and differences of sorted
+//! collections in linear time, without building any intermediate collection.
+//! `kmerge` merges any number of sorted iterators with a heap.
+
+use std::{
+    cmp::Ordering,
+    fmt::{self, Debug},
+    iter::{FusedIterator, Peekable},
+};
+
+use crate::{heap::Heap, seek::Seekable};
+
+/// An element from the left input, the right input, or an equal pair from both.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EitherOrBoth<L, R> {
+    Left(L),
+    Right(R),
+    Both(L, R),
+}
+
+impl<L, R> EitherOrBoth<L, R> {
+    /// Returns the left element, if there is one.
+    pub fn left(self) -> Option<L> {
+        match self {
+            EitherOrBoth::Left(l) | EitherOrBoth::Both(l, _) => Some(l),
+            EitherOrBoth::Right(_) => None,
+        }
+    }
+
+    /// Returns the right element, if there is one.
+    pub fn right(self) -> Option<R> {
+        match self {
+            EitherOrBoth::Right(r) | EitherOrBoth::Both(_, r) => Some(r),
+            EitherOrBoth::Left(_) => None,
+        }
+    }
+
+    /// Returns the pair if the element was present in both inputs.
+    pub fn both(self) -> Option<(L, R)> {
+        match self {
+            EitherOrBoth::Both(l, r) => Some((l, r)),
+            _ => None,
+        }
+    }
+
+    /// Returns the left element only if it had no match on the right.
+    pub fn left_only(self) -> Option<L> {
+        match self {
+            EitherOrBoth::Left(l) => Some(l),
+            _ => None,
+        }
+    }
+
+    /// Returns the right element only if it had no match on the left.
+    pub fn right_only(self) -> Option<R> {
+        match self {
+            EitherOrBoth::Right(r) => Some(r),
+            _ => None,
+        }
+    }
+}
+
+impl<T> EitherOrBoth<T, T> {
+    /// Collapses the element into one, combining a pair with `f`.
+    pub fn reduce<F: FnOnce(T, T) -> T>(self, f: F) -> T {
+        match self {
+            EitherOrBoth::Left(t) | EitherOrBoth::Right(t) => t,
+            EitherOrBoth::Both(l, r) => f(l, r),
+        }
+    }
+}
+
+/// The items of sorted iterators, which are ordered by a key.
+/// Set iterators yield the key itself, while map iterators yield a key and value pair.
+pub trait SortKey {
+    type Key: Ord + ?Sized;
+
+    fn sort_key(&self) -> &Self::Key;
+}
+
+impl<T: Ord + ?Sized> SortKey for &T {
+    type Key = T;
+
+    fn sort_key(&self) -> &T {
+        self
+    }
+}
+
+impl<K: Ord + ?Sized, V: ?Sized> SortKey for (&K, &V) {
+    type Key = K;
+
+    fn sort_key(&self) -> &K {
+        self.0
+    }
+}
+
+impl<K: Ord + ?Sized, V: ?Sized> SortKey for (&K, &mut V) {
+    type Key = K;
+
+    fn sort_key(&self) -> &K {
+        self.0
+    }
+}
+
+/// An iterator which merges two sorted iterators, pairing up the elements which compare equal.
+/// Elements are yielded in ascending order. Each element is matched at most once, so
+/// duplicates within an input are paired off one by one like a multiset.
+pub struct MergeJoin<A: Iterator, B: Iterator, F> {
+    left: Peekable<A>,
+    right: Peekable<B>,
+    cmp: F,
+}
+
+impl<A, B, F> Debug for MergeJoin<A, B, F>
+where
+    A: Iterator + Debug,
+    B: Iterator + Debug,
+    A::Item: Debug,
+    B::Item: Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MergeJoin")
+            .field("left", &self.left)
+            .field("right", &self.right)
+            .finish()
+    }
+}
+
+impl<A, B, F> Clone for MergeJoin<A, B, F>
+where
+    A: Iterator + Clone,
+    B: Iterator + Clone,
+    A::Item: Clone,
+    B::Item: Clone,
+    F: Clone,
+{
+    fn clone(&self) -> Self {
+        MergeJoin {
+            left: self.left.clone(),
+            right: self.right.clone(),
+            cmp: self.cmp.clone(),
+        }
+    }
+}
+
+/// The iterator returned by `merge_join`, which compares elements by their `SortKey`.
+pub type MergeJoinByKey<A, B> =
+    MergeJoin<A, B, fn(&<A as Iterator>::Item, &<B as Iterator>::Item) -> Ordering>;
+
+/// Merges two iterators which are sorted by the same key, like the iterators of two ordered
+/// maps or sets. A map can be joined against a set of its keys.
+pub fn merge_join<A, B, K>(left: A, right: B) -> MergeJoinByKey<A::IntoIter, B::IntoIter>
+where
+    A: IntoIterator,
+    B: IntoIterator,
+    A::Item: SortKey<Key = K>,
+    B::Item: SortKey<Key = K>,
+    K: Ord + ?Sized,
+{
+    merge_join_by(left, right, |l, r| l.sort_key().cmp(r.sort_key()))
+}
+
+/// Merges two iterators which are both sorted in the order given by `cmp`.
+pub fn merge_join_by<A, B, F>(left: A, right: B, cmp: F) -> MergeJoin<A::IntoIter, B::IntoIter, F>
+where
+    A: IntoIterator,
+    B: IntoIterator,
+    F: FnMut(&A::Item, &B::Item) -> Ordering,
+{
+    MergeJoin {
+        left: left.into_iter().peekable(),
+        right: right.into_iter().peekable(),
+        cmp,
+    }
+}
+
+impl<A, B, F> Iterator for MergeJoin<A, B, F>
+where
+    A: Iterator,
+    B: Iterator,
+    F: FnMut(&A::Item, &B::Item) -> Ordering,
+{
+    type Item = EitherOrBoth<A::Item, B::Item>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let ordering = match (self.left.peek(), self.right.peek()) {
+            (Some(l), Some(r)) => (self.cmp)(l, r),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => return None,
+        };
+        Some(match ordering {
+            Ordering::Less => EitherOrBoth::Left(self.left.next()?),
+            Ordering::Greater => EitherOrBoth::Right(self.right.next()?),
+            Ordering::Equal => EitherOrBoth::Both(self.left.next()?, self.right.next()?),
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (left_lower, left_upper) = self.left.size_hint();
+        let (right_lower, right_upper) = self.right.size_hint();
+        let upper = match (left_upper, right_upper) {
+            (Some(l), Some(r)) => l.checked_add(r),
+            _ => None,
+        };
+        (left_lower.max(right_lower), upper)
+    }
+}
+
+impl<A, B, F> FusedIterator for MergeJoin<A, B, F>
+where
+    A: Iterator,
+    B: Iterator,
+    F: FnMut(&A::Item, &B::Item) -> Ordering,
+{
+}
+
+/// An iterator which merges any number of ascending iterators into one ascending iterator.
+/// The next element of every input is kept in a heap, so each step is O(log k) for k inputs.
+/// Equal elements are yielded in the order of their inputs, so the merge is stable.
+pub struct KMerge<I: Iterator> {
+    heads: Heap<Head<I::Item>>,
+    iters: Vec<I>,
+}
+
+/// The next element of the input at `source`.
+struct Head<T> {
+    item: T,
+    source: usize,
+}
+
+impl<T: Ord> PartialEq for Head<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<T: Ord> Eq for Head<T> {}
+
+impl<T: Ord> PartialOrd for Head<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T: Ord> Ord for Head<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.item
+            .cmp(&other.item)
+            .then(self.source.cmp(&other.source))
+    }
+}
+
+impl<I> Debug for KMerge<I>
+where
+    I: Iterator + Debug,
+    I::Item: Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KMerge")
+            .field(
+                "heads",
+                &self.heads.iter().map(|h| &h.item).collect::<Vec<_>>(),
+            )
+            .field("iters", &self.iters)
+            .finish()
+    }
+}
+
+/// Merges iterators which are each sorted in ascending order.
+pub fn kmerge<I>(iters: I) -> KMerge<<I::Item as IntoIterator>::IntoIter>
+where
+    I: IntoIterator,
+    I::Item: IntoIterator,
+    <I::Item as IntoIterator>::Item: Ord,
+{
+    let mut iters = iters
+        .into_iter()
+        .map(IntoIterator::into_iter)
+        .collect::<Vec<_>>();
+    let mut heads = Heap::new();
+    for (source, iter) in iters.iter_mut().enumerate() {
+        if let Some(item) = iter.next() {
+            heads.push(Head { item, source });
+        }
+    }
+    KMerge { heads, iters }
+}
+
+impl<I> Iterator for KMerge<I>
+where
+    I: Iterator,
+    I::Item: Ord,
+{
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let Head { item, source } = self.heads.pop()?;
+        if let Some(next) = self.iters[source].next() {
+            self.heads.push(Head { item: next, source });
+        }
+        Some(item)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iters.iter().fold(
+            (self.heads.size(), Some(self.heads.size())),
+            |(lo, hi), iter| {
+                let (iter_lo, iter_hi) = iter.size_hint();
+                let hi = match (hi, iter_hi) {
+                    (Some(a), Some(b)) => a.checked_add(b),
+                    _ => None,
+                };
+                (lo.saturating_add(iter_lo), hi)
+            },
+        )
+    }
+}
+
+impl<I> FusedIterator for KMerge<I>
+where
+    I: Iterator,
+    I::Item: Ord,
+{
+}
+
+/// Seeks every input whose next element is behind the key, which must order the elements
+/// the same way they compare.
+impl<I> Seekable for KMerge<I>
+where
+    I: Seekable,
+    I::Item: Ord,
+{
+    type Key = I::Key;
+
+    fn key_of(item: &I::Item) -> &I::Key {
+        I::key_of(item)
+    }
+
+    fn seek(&mut self, key: &I::Key) {
+        while let Some(head) = self.heads.peek() {
+            if I::key_of(&head.item) >= key {
+                return;
+            }
+            let Head { source, .. } = self.heads.pop().unwrap();
+            let iter = &mut self.iters[source];
+            iter.seek(key);
+            if let Some(item) = iter.next() {
+                self.heads.push(Head { item, source });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use quickcheck::quickcheck;
+    use std::collections::BTreeSet;
+
+    use super::{kmerge, merge_join, merge_join_by, EitherOrBoth};
+
+    #[test]
+    fn join() {
+        let left = [1, 3, 4, 4];
+        let right = [2, 3, 4];
+        assert_eq!(
+            merge_join(&left, &right).collect::<Vec<_>>(),
+            vec![
+                EitherOrBoth::Left(&1),
+                EitherOrBoth::Right(&2),
+                EitherOrBoth::Both(&3, &3),
+                EitherOrBoth::Both(&4, &4),
+                EitherOrBoth::Left(&4),
+            ]
+        );
+        let descending = merge_join_by([3, 1], [2, 1], |l, r| r.cmp(l));
+        assert_eq!(
+            descending
+                .filter_map(EitherOrBoth::both)
+                .collect::<Vec<_>>(),
+            vec![(1, 1)]
+        );
+    }
+
+    #[test]
+    fn size_hint() {
+        let join = merge_join(&[1, 2, 3], &[4, 5]);
+        assert_eq!(join.size_hint(), (3, Some(5)));
+        assert_eq!(join.count(), 5);
+    }
+
+    #[test]
+    fn set_operations() {
+        fn p(left: BTreeSet<u8>, right: BTreeSet<u8>) -> bool {
+            let join = || merge_join(&left, &right);
+            join().map(|e| e.reduce(|l, _| l)).eq(left.union(&right))
+                && join()
+                    .filter_map(EitherOrBoth::both)
+                    .map(|(l, _)| l)
+                    .eq(left.intersection(&right))
+                && join()
+                    .filter_map(EitherOrBoth::left_only)
+                    .eq(left.difference(&right))
+                && join()
+                    .filter(|e| !matches!(e, EitherOrBoth::Both(..)))
+                    .map(|e| e.reduce(|l, _| l))
+                    .eq(left.symmetric_difference(&right))
+        }
+        quickcheck(p as fn(BTreeSet<u8>, BTreeSet<u8>) -> bool);
+    }
+
+    #[test]
+    fn kmerge_is_stable() {
+        // Compares by key alone, so the tag shows which input an element came from
+        #[derive(Debug)]
+        struct Tagged(u8, char);
+        impl PartialEq for Tagged {
+            fn eq(&self, other: &Self) -> bool {
+                self.0 == other.0
+            }
+        }
+        impl Eq for Tagged {}
+        impl PartialOrd for Tagged {
+            fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+                Some(self.cmp(other))
+            }
+        }
+        impl Ord for Tagged {
+            fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+                self.0.cmp(&other.0)
+            }
+        }
+
+        let runs = vec![
+            vec![Tagged(1, 'a'), Tagged(3, 'a')],
+            vec![],
+            vec![Tagged(1, 'c'), Tagged(2, 'c')],
+        ];
+        let merged = kmerge(runs);
+        assert_eq!(merged.size_hint(), (4, Some(4)));
+        assert_eq!(
+            merged.map(|t| (t.0, t.1)).collect::<Vec<_>>(),
+            vec![(1, 'a'), (1, 'c'), (2, 'c'), (3, 'a')]
+        );
+    }
+
+    #[test]
+    fn prop_kmerge_sorts() {
+        fn p(mut runs: Vec<Vec<u8>>) -> bool {
+            for run in runs.iter_mut() {
+                run.sort();
+            }
+            let mut expected = runs.concat();
+            expected.sort();
+            kmerge(runs).collect::<Vec<_>>() == expected
+        }
+        quickcheck(p as fn(Vec<Vec<u8>>) -> bool);
+    }
+
+    #[cfg(feature = "avl_tree")]
+    #[test]
+    fn avl_tree() {
+        use crate::avl_tree::AVLTree;
+
+        let mut prices = AVLTree::new();
+        let mut stock = AVLTree::new();
+        for (k, v) in [("apple", 3), ("pear", 5), ("plum", 2)] {
+            prices.insert(k, v);
+        }
+        for (k, v) in [("apple", 10), ("fig", 4), ("plum", 0)] {
+            stock.insert(k, v);
+        }
+        let value: i32 = merge_join(prices.iter(), stock.iter())
+            .filter_map(EitherOrBoth::both)
+            .map(|((_, price), (_, count))| price * count)
+            .sum();
+        assert_eq!(value, 30);
+        let keys = ["fig", "plum"];
+        let unpriced = merge_join(stock.iter(), &keys)
+            .filter_map(EitherOrBoth::left_only)
+            .map(|(k, _)| *k)
+            .collect::<Vec<_>>();
+        assert_eq!(unpriced, vec!["apple"]);
+    }
+
+    #[cfg(feature = "avl_tree")]
+    #[test]
+    fn prop_kmerge_seek() {
+        use crate::{avl_tree::AVLTree, seek::seeks_match};
+
+        fn p(sets: Vec<BTreeSet<u8>>, ops: Vec<Option<u8>>) -> bool {
+            let trees = sets
+                .iter()
+                .map(|set| {
+                    let mut tree = AVLTree::new();
+                    for &k in set {
+                        tree.insert(k, ());
+                    }
+                    tree
+                })
+                .collect::<Vec<_>>();
+            let mut expected = sets.into_iter().flatten().collect::<Vec<_>>();
+            expected.sort();
+            seeks_match(kmerge(trees.iter().map(AVLTree::iter)), &expected, &ops)
+        }
+        quickcheck(p as fn(Vec<BTreeSet<u8>>, Vec<Option<u8>>) -> bool);
+    }
+
+    #[cfg(all(feature = "bs_tree", feature = "sorted_list"))]
+    #[test]
+    fn bs_tree_and_sorted_list() {
+        use crate::{bs_tree::BSTree, sorted_list::SortedList};
+
+        let mut tree = BSTree::new();
+        let mut list = SortedList::new();
+        for i in [5, 1, 3] {
+            tree.insert(i);
+        }
+        for i in [3, 4, 5, 5] {
+            list.insert(i);
+        }
+        assert_eq!(
+            merge_join(tree.iter(), list.iter())
+                .filter_map(EitherOrBoth::right_only)
+                .collect::<Vec<_>>(),
+            vec![&4, &5]
+        );
+    }
+}
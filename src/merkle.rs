@@ -0,0 +1,185 @@
+use std::{fmt::Debug, hash::Hasher};
+
+/// A hash function for building a `MerkleTree`.
+/// Leaves and interior nodes are hashed differently so that a leaf can't be passed off
+/// as an interior node. Cryptographic uses need a collision-resistant implementation.
+pub trait MerkleHasher {
+    type Hash: Clone + Eq + Debug;
+
+    fn hash_leaf(&self, data: &[u8]) -> Self::Hash;
+
+    fn hash_nodes(&self, left: &Self::Hash, right: &Self::Hash) -> Self::Hash;
+}
+
+/// A fast, non-cryptographic `MerkleHasher` built on std's SipHash with fixed keys.
+/// It detects accidental corruption but not deliberate tampering.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SipMerkleHasher;
+
+impl MerkleHasher for SipMerkleHasher {
+    type Hash = u64;
+
+    fn hash_leaf(&self, data: &[u8]) -> u64 {
+        #[allow(deprecated)]
+        let mut hasher = std::hash::SipHasher::new();
+        hasher.write_u8(0);
+        hasher.write(data);
+        hasher.finish()
+    }
+
+    fn hash_nodes(&self, left: &u64, right: &u64) -> u64 {
+        #[allow(deprecated)]
+        let mut hasher = std::hash::SipHasher::new();
+        hasher.write_u8(1);
+        hasher.write_u64(*left);
+        hasher.write_u64(*right);
+        hasher.finish()
+    }
+}
+
+/// A binary hash tree over a sequence of leaves. The root hash commits to every leaf,
+/// and an inclusion proof of O(log n) hashes shows that a leaf is part of the tree.
+/// When a level has an odd number of nodes, the last one is promoted to the next level unchanged.
+#[derive(Debug, Clone)]
+pub struct MerkleTree<H: MerkleHasher = SipMerkleHasher> {
+    // Each level of the tree, from the leaf hashes up to the root
+    levels: Vec<Vec<H::Hash>>,
+    hasher: H,
+}
+
+/// Evidence that a leaf is at a position in a `MerkleTree` with a given root.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Proof<T> {
+    pub index: usize,
+    /// The sibling hashes from the leaf up to the root, and whether each sibling is on the left.
+    /// Levels where the node had no sibling are skipped.
+    pub siblings: Vec<(T, bool)>,
+}
+
+impl MerkleTree<SipMerkleHasher> {
+    pub fn new<L: AsRef<[u8]>>(leaves: &[L]) -> Self {
+        MerkleTree::with_hasher(leaves, SipMerkleHasher)
+    }
+}
+
+impl<H: MerkleHasher> MerkleTree<H> {
+    pub fn with_hasher<L: AsRef<[u8]>>(leaves: &[L], hasher: H) -> Self {
+        let mut levels = vec![leaves
+            .iter()
+            .map(|l| hasher.hash_leaf(l.as_ref()))
+            .collect::<Vec<_>>()];
+        while levels.last().unwrap().len() > 1 {
+            let next = levels
+                .last()
+                .unwrap()
+                .chunks(2)
+                .map(|pair| match pair {
+                    [left, right] => hasher.hash_nodes(left, right),
+                    [single] => single.clone(),
+                    _ => unreachable!(),
+                })
+                .collect();
+            levels.push(next);
+        }
+        MerkleTree { levels, hasher }
+    }
+
+    pub fn len(&self) -> usize {
+        self.levels[0].len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.levels[0].is_empty()
+    }
+
+    /// The root hash, or None if the tree has no leaves.
+    pub fn root(&self) -> Option<&H::Hash> {
+        self.levels.last().unwrap().first()
+    }
+
+    pub fn leaf(&self, index: usize) -> Option<&H::Hash> {
+        self.levels[0].get(index)
+    }
+
+    /// Builds the proof that the leaf at `index` is in the tree.
+    pub fn proof(&self, index: usize) -> Option<Proof<H::Hash>> {
+        if index >= self.len() {
+            return None;
+        }
+        let mut siblings = vec![];
+        let mut i = index;
+        for level in &self.levels[..self.levels.len() - 1] {
+            let sibling = i ^ 1;
+            if let Some(hash) = level.get(sibling) {
+                siblings.push((hash.clone(), sibling < i));
+            }
+            i /= 2;
+        }
+        Some(Proof { index, siblings })
+    }
+
+    /// Checks that the leaf data is in a tree with the given root according to the proof.
+    pub fn verify(&self, root: &H::Hash, leaf: &[u8], proof: &Proof<H::Hash>) -> bool {
+        verify(&self.hasher, root, leaf, proof)
+    }
+}
+
+/// Checks a proof without access to the tree, e.g. on a peer which only knows the root.
+pub fn verify<H: MerkleHasher>(
+    hasher: &H,
+    root: &H::Hash,
+    leaf: &[u8],
+    proof: &Proof<H::Hash>,
+) -> bool {
+    let mut hash = hasher.hash_leaf(leaf);
+    for (sibling, is_left) in &proof.siblings {
+        hash = if *is_left {
+            hasher.hash_nodes(sibling, &hash)
+        } else {
+            hasher.hash_nodes(&hash, sibling)
+        };
+    }
+    hash == *root
+}
+
+#[cfg(test)]
+mod test {
+    use quickcheck::quickcheck;
+
+    use super::{verify, MerkleTree, SipMerkleHasher};
+
+    #[test]
+    fn root_changes_with_leaves() {
+        let a = MerkleTree::new(&["a", "b", "c"]);
+        let b = MerkleTree::new(&["a", "b", "d"]);
+        let c = MerkleTree::new(&["a", "b", "c"]);
+        assert_ne!(a.root(), b.root());
+        assert_eq!(a.root(), c.root());
+        assert_eq!(MerkleTree::new::<&[u8]>(&[]).root(), None);
+    }
+
+    #[test]
+    fn proofs() {
+        let leaves = ["a", "b", "c", "d", "e"];
+        let tree = MerkleTree::new(&leaves);
+        let root = tree.root().unwrap();
+        for (i, leaf) in leaves.iter().enumerate() {
+            let proof = tree.proof(i).unwrap();
+            assert!(tree.verify(root, leaf.as_bytes(), &proof));
+            assert!(!tree.verify(root, b"x", &proof));
+        }
+        assert!(tree.proof(5).is_none());
+    }
+
+    #[test]
+    fn prop_proofs_verify() {
+        fn p(leaves: Vec<Vec<u8>>) -> bool {
+            let tree = MerkleTree::new(&leaves);
+            leaves.iter().enumerate().all(|(i, leaf)| {
+                let proof = tree.proof(i).unwrap();
+                verify(&SipMerkleHasher, tree.root().unwrap(), leaf, &proof)
+            })
+        }
+        quickcheck(p as fn(Vec<Vec<u8>>) -> bool)
+    }
+}
@@ -0,0 +1,127 @@
+use crate::monotonic::Stack;
+
+/// A stack which reports its minimum and maximum elements in O(1).
+/// Every entry records where the minimum and maximum were when it was pushed,
+/// so popping restores them without a search.
+#[derive(Debug, Clone)]
+pub struct MinMaxStack<T> {
+    stack: Stack<T>,
+}
+
+impl<T> MinMaxStack<T> {
+    pub fn new() -> Self {
+        MinMaxStack {
+            stack: Stack::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.stack.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stack.len() == 0
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        self.stack.pop()
+    }
+
+    /// Returns the top of the stack.
+    pub fn peek(&self) -> Option<&T> {
+        self.stack.top()
+    }
+
+    pub fn min(&self) -> Option<&T> {
+        self.stack.min()
+    }
+
+    pub fn max(&self) -> Option<&T> {
+        self.stack.max()
+    }
+
+    /// Returns an iterator from the bottom of the stack to the top.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &T> {
+        self.stack.iter()
+    }
+
+    pub fn clear(&mut self) {
+        self.stack.clear();
+    }
+}
+
+impl<T: Ord> MinMaxStack<T> {
+    pub fn push(&mut self, value: T) {
+        self.stack.push(value);
+    }
+}
+
+impl<T> Default for MinMaxStack<T> {
+    fn default() -> Self {
+        MinMaxStack::new()
+    }
+}
+
+impl<T: Ord> FromIterator<T> for MinMaxStack<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut stack = MinMaxStack::new();
+        stack.extend(iter);
+        stack
+    }
+}
+
+impl<T: Ord> Extend<T> for MinMaxStack<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for value in iter {
+            self.push(value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use quickcheck::quickcheck;
+
+    use super::MinMaxStack;
+
+    #[test]
+    fn duplicates() {
+        let mut stack = [3, 1, 1, 5, 5].into_iter().collect::<MinMaxStack<_>>();
+        assert_eq!((stack.min(), stack.max()), (Some(&1), Some(&5)));
+        stack.pop();
+        assert_eq!(stack.max(), Some(&5));
+        stack.pop();
+        stack.pop();
+        assert_eq!((stack.min(), stack.max()), (Some(&1), Some(&3)));
+        assert_eq!(stack.iter().copied().collect::<Vec<_>>(), vec![3, 1]);
+        stack.clear();
+        assert_eq!(stack.min(), None);
+    }
+
+    #[test]
+    fn prop_matches_vec() {
+        fn p(ops: Vec<Option<i32>>) -> bool {
+            let mut stack = MinMaxStack::new();
+            let mut model = vec![];
+            for op in ops {
+                let agree = match op {
+                    Some(x) => {
+                        stack.push(x);
+                        model.push(x);
+                        true
+                    }
+                    None => stack.pop() == model.pop(),
+                };
+                if !agree
+                    || stack.min() != model.iter().min()
+                    || stack.max() != model.iter().max()
+                    || stack.peek() != model.last()
+                {
+                    return false;
+                }
+            }
+            stack.iter().eq(model.iter())
+        }
+        quickcheck(p as fn(Vec<Option<i32>>) -> bool)
+    }
+}
@@ -0,0 +1,268 @@
+/// A double-ended queue which reports its minimum and maximum elements in O(1).
+/// It is made of two stacks facing away from each other, where every stack entry records
+/// the positions of the minimum and maximum at or below it. Popping from an empty side
+/// moves half of the other side across, so every operation is amortized O(1).
+#[derive(Debug, Clone)]
+pub struct MinMaxDeque<T> {
+    // The top of `front` is the front of the deque and the top of `back` is the back
+    front: Stack<T>,
+    back: Stack<T>,
+}
+
+/// A stack which tracks its minimum and maximum, shared with `MinMaxStack`.
+#[derive(Debug, Clone)]
+pub(crate) struct Stack<T> {
+    entries: Vec<Entry<T>>,
+}
+
+#[derive(Debug, Clone)]
+struct Entry<T> {
+    value: T,
+    // Indices of the minimum and maximum entries from the bottom of the stack up to this one
+    min: usize,
+    max: usize,
+}
+
+impl<T> Stack<T> {
+    pub(crate) fn new() -> Self {
+        Stack { entries: vec![] }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub(crate) fn top(&self) -> Option<&T> {
+        self.entries.last().map(|e| &e.value)
+    }
+
+    pub(crate) fn pop(&mut self) -> Option<T> {
+        self.entries.pop().map(|e| e.value)
+    }
+
+    pub(crate) fn min(&self) -> Option<&T> {
+        let top = self.entries.last()?;
+        Some(&self.entries[top.min].value)
+    }
+
+    pub(crate) fn max(&self) -> Option<&T> {
+        let top = self.entries.last()?;
+        Some(&self.entries[top.max].value)
+    }
+
+    pub(crate) fn iter(&self) -> impl DoubleEndedIterator<Item = &T> {
+        self.entries.iter().map(|e| &e.value)
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+impl<T: Ord> Stack<T> {
+    pub(crate) fn push(&mut self, value: T) {
+        let index = self.entries.len();
+        let (min, max) = match self.entries.last() {
+            Some(top) => (
+                if value < self.entries[top.min].value {
+                    index
+                } else {
+                    top.min
+                },
+                if value > self.entries[top.max].value {
+                    index
+                } else {
+                    top.max
+                },
+            ),
+            None => (index, index),
+        };
+        self.entries.push(Entry { value, min, max });
+    }
+}
+
+impl<T> MinMaxDeque<T> {
+    pub fn new() -> Self {
+        MinMaxDeque {
+            front: Stack::new(),
+            back: Stack::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.front.len() + self.back.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn front(&self) -> Option<&T> {
+        self.front
+            .top()
+            .or_else(|| self.back.entries.first().map(|e| &e.value))
+    }
+
+    pub fn back(&self) -> Option<&T> {
+        self.back
+            .top()
+            .or_else(|| self.front.entries.first().map(|e| &e.value))
+    }
+
+    /// Returns an iterator from the front of the deque to the back.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &T> {
+        self.front.iter().rev().chain(self.back.iter())
+    }
+
+    pub fn clear(&mut self) {
+        self.front.clear();
+        self.back.clear();
+    }
+}
+
+impl<T: Ord> MinMaxDeque<T> {
+    pub fn push_front(&mut self, value: T) {
+        self.front.push(value);
+    }
+
+    pub fn push_back(&mut self, value: T) {
+        self.back.push(value);
+    }
+
+    pub fn pop_front(&mut self) -> Option<T> {
+        if self.front.len() == 0 {
+            Self::rebalance(&mut self.back, &mut self.front);
+        }
+        self.front.pop()
+    }
+
+    pub fn pop_back(&mut self) -> Option<T> {
+        if self.back.len() == 0 {
+            Self::rebalance(&mut self.front, &mut self.back);
+        }
+        self.back.pop()
+    }
+
+    pub fn min(&self) -> Option<&T> {
+        match (self.front.min(), self.back.min()) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
+
+    pub fn max(&self) -> Option<&T> {
+        match (self.front.max(), self.back.max()) {
+            (Some(a), Some(b)) => Some(a.max(b)),
+            (a, b) => a.or(b),
+        }
+    }
+
+    /// Moves the bottom half of `from` onto the empty stack `to`.
+    fn rebalance(from: &mut Stack<T>, to: &mut Stack<T>) {
+        let mut values = std::mem::take(&mut from.entries)
+            .into_iter()
+            .map(|e| e.value)
+            .collect::<Vec<_>>();
+        // The bottom of `from` is the end of the deque which `to` serves, so it goes on top
+        let keep = values.split_off(values.len().div_ceil(2));
+        for value in values.into_iter().rev() {
+            to.push(value);
+        }
+        for value in keep {
+            from.push(value);
+        }
+    }
+}
+
+impl<T> Default for MinMaxDeque<T> {
+    fn default() -> Self {
+        MinMaxDeque::new()
+    }
+}
+
+impl<T: Ord> FromIterator<T> for MinMaxDeque<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut deque = MinMaxDeque::new();
+        for value in iter {
+            deque.push_back(value);
+        }
+        deque
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::VecDeque;
+
+    use quickcheck::quickcheck;
+
+    use super::MinMaxDeque;
+
+    #[test]
+    fn sliding_window() {
+        let data = [4, 2, 12, 3, 8, 1, 7];
+        let mut window = MinMaxDeque::new();
+        let mut mins = vec![];
+        for x in data {
+            window.push_back(x);
+            if window.len() > 3 {
+                window.pop_front();
+            }
+            mins.push(*window.min().unwrap());
+        }
+        assert_eq!(mins, vec![4, 2, 2, 2, 3, 1, 1]);
+        assert_eq!(window.max(), Some(&8));
+    }
+
+    #[test]
+    fn both_ends() {
+        let mut deque = MinMaxDeque::new();
+        deque.push_back(2);
+        deque.push_front(1);
+        deque.push_back(3);
+        assert_eq!(deque.iter().copied().collect::<Vec<_>>(), vec![1, 2, 3]);
+        assert_eq!(deque.front(), Some(&1));
+        assert_eq!(deque.back(), Some(&3));
+        assert_eq!(deque.pop_back(), Some(3));
+        assert_eq!(deque.max(), Some(&2));
+        assert_eq!(deque.pop_back(), Some(2));
+        assert_eq!(deque.pop_back(), Some(1));
+        assert_eq!(deque.pop_back(), None);
+        assert_eq!(deque.min(), None);
+    }
+
+    #[test]
+    fn prop_matches_vec_deque() {
+        // 0 and 1 push to either end, 2 and 3 pop from either end
+        fn p(ops: Vec<(u8, i32)>) -> bool {
+            let mut deque = MinMaxDeque::new();
+            let mut model = VecDeque::new();
+            for (op, x) in ops {
+                let agree = match op % 4 {
+                    0 => {
+                        deque.push_front(x);
+                        model.push_front(x);
+                        true
+                    }
+                    1 => {
+                        deque.push_back(x);
+                        model.push_back(x);
+                        true
+                    }
+                    2 => deque.pop_front() == model.pop_front(),
+                    _ => deque.pop_back() == model.pop_back(),
+                };
+                if !agree
+                    || deque.min() != model.iter().min()
+                    || deque.max() != model.iter().max()
+                    || deque.front() != model.front()
+                    || deque.back() != model.back()
+                {
+                    return false;
+                }
+            }
+            deque.iter().eq(model.iter())
+        }
+        quickcheck(p as fn(Vec<(u8, i32)>) -> bool)
+    }
+}
@@ -0,0 +1,393 @@
+use std::{
+    any::Any,
+    collections::{BTreeMap, HashMap},
+    fmt::{self, Debug},
+    hash::Hash,
+    marker::PhantomData,
+    ops::RangeBounds,
+};
+
+use crate::{
+    slab::{Key, SlotMap},
+    Error,
+};
+
+/// A collection of items which can be looked up through any number of indexes, each keyed
+/// by a different function of the item. Inserting, modifying or removing an item updates
+/// every index at once, so they can't drift apart.
+/// Hashed indexes are unique, while ordered indexes allow duplicates and keep equal keys
+/// in insertion order.
+pub struct MultiIndexMap<T> {
+    items: SlotMap<T>,
+    indexes: Vec<Box<dyn Index<T>>>,
+}
+
+/// A handle to a unique hashed index of a `MultiIndexMap`, for looking up items by key.
+/// Handles are only meaningful to the map which created them.
+#[derive(Debug)]
+pub struct Hashed<K>(usize, PhantomData<fn() -> K>);
+
+/// A handle to an ordered index of a `MultiIndexMap`, for iterating over items in key order.
+/// Handles are only meaningful to the map which created them.
+#[derive(Debug)]
+pub struct Ordered<K>(usize, PhantomData<fn() -> K>);
+
+// Deriving these would require `K` to implement them too
+impl<K> Clone for Hashed<K> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<K> Copy for Hashed<K> {}
+
+impl<K> Clone for Ordered<K> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<K> Copy for Ordered<K> {}
+
+/// Keeps one index in step with the items.
+trait Index<T> {
+    /// Whether inserting the item would break the index's uniqueness.
+    fn conflicts(&self, item: &T) -> bool;
+
+    fn insert(&mut self, item: &T, key: Key);
+
+    fn remove(&mut self, item: &T, key: Key);
+
+    fn as_any(&self) -> &dyn Any;
+}
+
+struct HashedIndex<T, K> {
+    key_fn: Box<dyn Fn(&T) -> K>,
+    keys: HashMap<K, Key>,
+}
+
+struct OrderedIndex<T, K> {
+    key_fn: Box<dyn Fn(&T) -> K>,
+    keys: BTreeMap<K, Vec<Key>>,
+}
+
+impl<T: 'static, K: Eq + Hash + 'static> Index<T> for HashedIndex<T, K> {
+    fn conflicts(&self, item: &T) -> bool {
+        self.keys.contains_key(&(self.key_fn)(item))
+    }
+
+    fn insert(&mut self, item: &T, key: Key) {
+        self.keys.insert((self.key_fn)(item), key);
+    }
+
+    fn remove(&mut self, item: &T, _: Key) {
+        self.keys.remove(&(self.key_fn)(item));
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl<T: 'static, K: Ord + 'static> Index<T> for OrderedIndex<T, K> {
+    fn conflicts(&self, _: &T) -> bool {
+        false
+    }
+
+    fn insert(&mut self, item: &T, key: Key) {
+        self.keys.entry((self.key_fn)(item)).or_default().push(key);
+    }
+
+    fn remove(&mut self, item: &T, key: Key) {
+        let k = (self.key_fn)(item);
+        let keys = self.keys.get_mut(&k).unwrap();
+        keys.retain(|&other| other != key);
+        if keys.is_empty() {
+            self.keys.remove(&k);
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl<T: 'static> MultiIndexMap<T> {
+    pub fn new() -> Self {
+        MultiIndexMap {
+            items: SlotMap::new(),
+            indexes: vec![],
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Adds a unique index keyed by `key_fn`, indexing any items already present.
+    /// Panics if two items already present have the same key.
+    pub fn add_hashed_index<K, F>(&mut self, key_fn: F) -> Hashed<K>
+    where
+        K: Eq + Hash + 'static,
+        F: Fn(&T) -> K + 'static,
+    {
+        let index = HashedIndex {
+            key_fn: Box::new(key_fn),
+            keys: HashMap::new(),
+        };
+        Hashed(self.add_index(Box::new(index)), PhantomData)
+    }
+
+    /// Adds an index which orders the items by `key_fn`, indexing any items already present.
+    pub fn add_ordered_index<K, F>(&mut self, key_fn: F) -> Ordered<K>
+    where
+        K: Ord + 'static,
+        F: Fn(&T) -> K + 'static,
+    {
+        let index = OrderedIndex {
+            key_fn: Box::new(key_fn),
+            keys: BTreeMap::new(),
+        };
+        Ordered(self.add_index(Box::new(index)), PhantomData)
+    }
+
+    fn add_index(&mut self, mut index: Box<dyn Index<T>>) -> usize {
+        for (key, item) in self.items.iter() {
+            assert!(!index.conflicts(item), "duplicate key in unique index");
+            index.insert(item, key);
+        }
+        self.indexes.push(index);
+        self.indexes.len() - 1
+    }
+
+    /// Inserts the item into every index. If it has the same key as another item in a
+    /// hashed index, nothing is changed and the item is handed back in the error.
+    pub fn insert(&mut self, item: T) -> Result<(), Error<T>> {
+        if self.indexes.iter().any(|index| index.conflicts(&item)) {
+            return Err(Error::Exists(item));
+        }
+        let key = self.items.insert(item);
+        for index in self.indexes.iter_mut() {
+            index.insert(&self.items[key], key);
+        }
+        Ok(())
+    }
+
+    pub fn get_by<K: Eq + Hash + 'static>(&self, index: Hashed<K>, k: &K) -> Option<&T> {
+        self.find(index, k).map(|key| &self.items[key])
+    }
+
+    pub fn contains_by<K: Eq + Hash + 'static>(&self, index: Hashed<K>, k: &K) -> bool {
+        self.find(index, k).is_some()
+    }
+
+    pub fn remove_by<K: Eq + Hash + 'static>(&mut self, index: Hashed<K>, k: &K) -> Option<T> {
+        let key = self.find(index, k)?;
+        let item = self.items.remove(key).unwrap();
+        for index in self.indexes.iter_mut() {
+            index.remove(&item, key);
+        }
+        Some(item)
+    }
+
+    /// Modifies the item with the key `k` and reindexes it, returning whether it was found.
+    /// If the modified item clashes with another item in a hashed index, it is removed
+    /// and handed back in the error.
+    pub fn modify_by<K, F>(&mut self, index: Hashed<K>, k: &K, f: F) -> Result<bool, Error<T>>
+    where
+        K: Eq + Hash + 'static,
+        F: FnOnce(&mut T),
+    {
+        let Some(key) = self.find(index, k) else {
+            return Ok(false);
+        };
+        for index in self.indexes.iter_mut() {
+            index.remove(&self.items[key], key);
+        }
+        f(&mut self.items[key]);
+        if self
+            .indexes
+            .iter()
+            .any(|index| index.conflicts(&self.items[key]))
+        {
+            return Err(Error::Exists(self.items.remove(key).unwrap()));
+        }
+        for index in self.indexes.iter_mut() {
+            index.insert(&self.items[key], key);
+        }
+        Ok(true)
+    }
+
+    /// Returns an iterator over the items in the order of the index.
+    pub fn iter_by<K: Ord + 'static>(
+        &self,
+        index: Ordered<K>,
+    ) -> impl DoubleEndedIterator<Item = &T> + '_ {
+        self.range_by(index, ..)
+    }
+
+    /// Returns an iterator over the items whose keys fall within `range`, in the order of
+    /// the index.
+    pub fn range_by<K, R>(
+        &self,
+        index: Ordered<K>,
+        range: R,
+    ) -> impl DoubleEndedIterator<Item = &T> + '_
+    where
+        K: Ord + 'static,
+        R: RangeBounds<K>,
+    {
+        self.ordered(index)
+            .keys
+            .range(range)
+            .flat_map(|(_, keys)| keys.iter())
+            .map(|&key| &self.items[key])
+    }
+
+    /// Returns an iterator over the items in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.items.iter().map(|(_, item)| item)
+    }
+
+    fn find<K: Eq + Hash + 'static>(&self, index: Hashed<K>, k: &K) -> Option<Key> {
+        let index = self.indexes[index.0]
+            .as_any()
+            .downcast_ref::<HashedIndex<T, K>>()
+            .expect("index handle belongs to another map");
+        index.keys.get(k).copied()
+    }
+
+    fn ordered<K: Ord + 'static>(&self, index: Ordered<K>) -> &OrderedIndex<T, K> {
+        self.indexes[index.0]
+            .as_any()
+            .downcast_ref::<OrderedIndex<T, K>>()
+            .expect("index handle belongs to another map")
+    }
+}
+
+impl<T: 'static> Default for MultiIndexMap<T> {
+    fn default() -> Self {
+        MultiIndexMap::new()
+    }
+}
+
+/// Prints the items in no particular order.
+impl<T: Debug> Debug for MultiIndexMap<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.items.iter().map(|(_, item)| item))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use quickcheck::quickcheck;
+
+    use super::MultiIndexMap;
+    use crate::Error;
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Event {
+        id: u32,
+        at: u64,
+        name: &'static str,
+    }
+
+    fn event(id: u32, at: u64, name: &'static str) -> Event {
+        Event { id, at, name }
+    }
+
+    #[test]
+    fn indexes_stay_in_sync() {
+        let mut events = MultiIndexMap::new();
+        let by_id = events.add_hashed_index(|e: &Event| e.id);
+        let by_time = events.add_ordered_index(|e: &Event| e.at);
+        events.insert(event(1, 30, "c")).unwrap();
+        events.insert(event(2, 10, "a")).unwrap();
+        events.insert(event(3, 20, "b")).unwrap();
+        events.insert(event(4, 20, "b2")).unwrap();
+        assert_eq!(
+            events.insert(event(1, 0, "dup")),
+            Err(Error::Exists(event(1, 0, "dup")))
+        );
+        assert_eq!(events.get_by(by_id, &3).unwrap().name, "b");
+        let names = |events: &MultiIndexMap<Event>| {
+            events.iter_by(by_time).map(|e| e.name).collect::<Vec<_>>()
+        };
+        assert_eq!(names(&events), vec!["a", "b", "b2", "c"]);
+        assert_eq!(
+            events
+                .range_by(by_time, 15..25)
+                .rev()
+                .map(|e| e.id)
+                .collect::<Vec<_>>(),
+            vec![4, 3]
+        );
+
+        assert_eq!(events.remove_by(by_id, &3), Some(event(3, 20, "b")));
+        assert!(!events.contains_by(by_id, &3));
+        assert_eq!(names(&events), vec!["a", "b2", "c"]);
+
+        assert_eq!(events.modify_by(by_id, &2, |e| e.at = 40), Ok(true));
+        assert_eq!(names(&events), vec!["b2", "c", "a"]);
+        assert_eq!(events.modify_by(by_id, &9, |e| e.at = 0), Ok(false));
+        assert_eq!(
+            events.modify_by(by_id, &2, |e| e.id = 1),
+            Err(Error::Exists(event(1, 40, "a")))
+        );
+        assert_eq!(names(&events), vec!["b2", "c"]);
+        assert_eq!(events.len(), 2);
+    }
+
+    #[test]
+    fn index_added_later() {
+        let mut events = MultiIndexMap::new();
+        events.insert(event(1, 20, "b")).unwrap();
+        events.insert(event(2, 10, "a")).unwrap();
+        let by_name = events.add_hashed_index(|e: &Event| e.name);
+        assert_eq!(events.get_by(by_name, &"a").unwrap().id, 2);
+    }
+
+    #[test]
+    #[should_panic]
+    fn unique_index_over_duplicates() {
+        let mut events = MultiIndexMap::new();
+        events.insert(event(1, 10, "a")).unwrap();
+        events.insert(event(2, 10, "b")).unwrap();
+        events.add_hashed_index(|e: &Event| e.at);
+    }
+
+    #[test]
+    fn prop_matches_maps() {
+        fn p(ops: Vec<(bool, u8, u8)>) -> bool {
+            let mut map = MultiIndexMap::new();
+            let by_id = map.add_hashed_index(|&(id, _): &(u8, u8)| id);
+            let by_value = map.add_ordered_index(|&(_, value): &(u8, u8)| value);
+            let mut model = BTreeMap::new();
+            for (insert, id, value) in ops {
+                let id = id % 16;
+                if insert {
+                    let inserted = map.insert((id, value)).is_ok();
+                    if inserted == model.contains_key(&id) {
+                        return false;
+                    }
+                    model.entry(id).or_insert(value);
+                } else if map.remove_by(by_id, &id).map(|(_, v)| v) != model.remove(&id) {
+                    return false;
+                }
+            }
+            let mut sorted = model.iter().map(|(&id, &v)| (v, id)).collect::<Vec<_>>();
+            sorted.sort_by_key(|&(v, _)| v);
+            let values = map.iter_by(by_value).map(|&(_, v)| v).collect::<Vec<_>>();
+            map.len() == model.len() && values == sorted.iter().map(|&(v, _)| v).collect::<Vec<_>>()
+        }
+        quickcheck(p as fn(Vec<(bool, u8, u8)>) -> bool);
+    }
+}
@@ -0,0 +1,222 @@
+use std::{
+    borrow::Borrow,
+    collections::{hash_map::RandomState, HashMap},
+    hash::{BuildHasher, Hash},
+};
+
+/// A hash map which associates each key with a bucket of values.
+/// Values under a key are kept in insertion order and may repeat.
+#[derive(Debug, Clone)]
+pub struct MultiMap<K, V, S = RandomState> {
+    buckets: HashMap<K, Vec<V>, S>,
+    len: usize,
+}
+
+impl<K, V> MultiMap<K, V> {
+    pub fn new() -> Self {
+        MultiMap::with_hasher(RandomState::new())
+    }
+}
+
+impl<K, V, S> MultiMap<K, V, S> {
+    pub fn with_hasher(hasher: S) -> Self {
+        MultiMap {
+            buckets: HashMap::with_hasher(hasher),
+            len: 0,
+        }
+    }
+
+    /// The total number of values across all keys.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The number of distinct keys.
+    pub fn key_count(&self) -> usize {
+        self.buckets.len()
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.buckets.keys()
+    }
+
+    /// Returns an iterator over every key and value pair, with each key repeated once per value.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.buckets
+            .iter()
+            .flat_map(|(k, values)| values.iter().map(move |v| (k, v)))
+    }
+
+    /// Returns an iterator over each key and its bucket of values.
+    pub fn buckets(&self) -> impl Iterator<Item = (&K, &[V])> {
+        self.buckets
+            .iter()
+            .map(|(k, values)| (k, values.as_slice()))
+    }
+
+    pub fn clear(&mut self) {
+        self.buckets.clear();
+        self.len = 0;
+    }
+}
+
+impl<K, V, S: Default> Default for MultiMap<K, V, S> {
+    fn default() -> Self {
+        MultiMap::with_hasher(S::default())
+    }
+}
+
+impl<K, V, S> MultiMap<K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{
+    pub fn insert(&mut self, k: K, v: V) {
+        self.buckets.entry(k).or_default().push(v);
+        self.len += 1;
+    }
+
+    /// Returns the values for the key, which is empty if the key is absent.
+    pub fn get<Q>(&self, k: &Q) -> &[V]
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.buckets.get(k).map_or(&[], |values| values.as_slice())
+    }
+
+    pub fn contains_key<Q>(&self, k: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.buckets.contains_key(k)
+    }
+
+    /// Removes the first occurrence of the value under the key, returning whether it was found.
+    /// The key is removed along with its last value.
+    pub fn remove<Q>(&mut self, k: &Q, v: &V) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+        V: PartialEq,
+    {
+        let values = match self.buckets.get_mut(k) {
+            Some(values) => values,
+            None => return false,
+        };
+        match values.iter().position(|x| x == v) {
+            Some(i) => {
+                values.remove(i);
+                if values.is_empty() {
+                    self.buckets.remove(k);
+                }
+                self.len -= 1;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Removes the key and returns all of its values.
+    pub fn remove_all<Q>(&mut self, k: &Q) -> Vec<V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let values = self.buckets.remove(k).unwrap_or_default();
+        self.len -= values.len();
+        values
+    }
+}
+
+impl<K, V, S> PartialEq for MultiMap<K, V, S>
+where
+    K: Eq + Hash,
+    V: PartialEq,
+    S: BuildHasher,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.buckets == other.buckets
+    }
+}
+
+impl<K, V, S> Eq for MultiMap<K, V, S>
+where
+    K: Eq + Hash,
+    V: Eq,
+    S: BuildHasher,
+{
+}
+
+impl<K, V, S> FromIterator<(K, V)> for MultiMap<K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher + Default,
+{
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut map = MultiMap::default();
+        map.extend(iter);
+        map
+    }
+}
+
+impl<K, V, S> Extend<(K, V)> for MultiMap<K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        for (k, v) in iter {
+            self.insert(k, v);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::MultiMap;
+
+    #[test]
+    fn insert_and_get() {
+        let mut map = MultiMap::new();
+        map.insert("a", 1);
+        map.insert("a", 2);
+        map.insert("b", 3);
+        assert_eq!(map.get("a"), &[1, 2]);
+        assert_eq!(map.get("z"), &[] as &[i32]);
+        assert_eq!(map.len(), 3);
+        assert_eq!(map.key_count(), 2);
+    }
+
+    #[test]
+    fn remove_value() {
+        let mut map = [("a", 1), ("a", 2), ("a", 1)]
+            .into_iter()
+            .collect::<MultiMap<_, _>>();
+        assert!(map.remove("a", &1));
+        assert_eq!(map.get("a"), &[2, 1]);
+        assert!(!map.remove("a", &3));
+        assert!(map.remove("a", &2));
+        assert!(map.remove("a", &1));
+        assert!(!map.contains_key("a"));
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn flattened_iter() {
+        let map = [(1, 'a'), (2, 'b'), (1, 'c')]
+            .into_iter()
+            .collect::<MultiMap<_, _>>();
+        let mut pairs = map.iter().map(|(k, v)| (*k, *v)).collect::<Vec<_>>();
+        pairs.sort();
+        assert_eq!(pairs, vec![(1, 'a'), (1, 'c'), (2, 'b')]);
+        let mut copy = map.clone();
+        assert_eq!(copy.remove_all(&1), vec!['a', 'c']);
+        assert_eq!(copy.len(), 1);
+    }
+}
@@ -0,0 +1,233 @@
+use std::{
+    borrow::Borrow,
+    collections::{hash_map::RandomState, HashMap},
+    hash::{BuildHasher, Hash},
+};
+
+/// A hash set which counts how many times each element was inserted.
+#[derive(Debug, Clone)]
+pub struct MultiSet<T, S = RandomState> {
+    counts: HashMap<T, usize, S>,
+    len: usize,
+}
+
+/// A multiset used to tally occurrences, e.g. with `most_common`.
+pub type Counter<T, S = RandomState> = MultiSet<T, S>;
+
+impl<T> MultiSet<T> {
+    pub fn new() -> Self {
+        MultiSet::with_hasher(RandomState::new())
+    }
+}
+
+impl<T, S> MultiSet<T, S> {
+    pub fn with_hasher(hasher: S) -> Self {
+        MultiSet {
+            counts: HashMap::with_hasher(hasher),
+            len: 0,
+        }
+    }
+
+    /// The total number of elements, counting repeats.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The number of distinct elements.
+    pub fn distinct_len(&self) -> usize {
+        self.counts.len()
+    }
+
+    /// Returns an iterator over each distinct element and its count.
+    pub fn iter(&self) -> impl Iterator<Item = (&T, usize)> {
+        self.counts.iter().map(|(t, c)| (t, *c))
+    }
+
+    /// Returns the `k` elements with the highest counts, from most to least common.
+    /// Ties are broken arbitrarily.
+    pub fn most_common(&self, k: usize) -> Vec<(&T, usize)> {
+        let mut counts = self.iter().collect::<Vec<_>>();
+        counts.sort_unstable_by_key(|&(_, c)| std::cmp::Reverse(c));
+        counts.truncate(k);
+        counts
+    }
+
+    pub fn clear(&mut self) {
+        self.counts.clear();
+        self.len = 0;
+    }
+}
+
+impl<T, S: Default> Default for MultiSet<T, S> {
+    fn default() -> Self {
+        MultiSet::with_hasher(S::default())
+    }
+}
+
+impl<T, S> MultiSet<T, S>
+where
+    T: Eq + Hash,
+    S: BuildHasher,
+{
+    /// Adds one occurrence of the element, returning its new count.
+    pub fn insert(&mut self, t: T) -> usize {
+        self.insert_many(t, 1)
+    }
+
+    /// Adds `n` occurrences of the element, returning its new count.
+    pub fn insert_many(&mut self, t: T, n: usize) -> usize {
+        if n == 0 {
+            return self.count(&t);
+        }
+        let count = self.counts.entry(t).or_insert(0);
+        *count += n;
+        self.len += n;
+        *count
+    }
+
+    pub fn count<Q>(&self, t: &Q) -> usize
+    where
+        T: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.counts.get(t).copied().unwrap_or(0)
+    }
+
+    pub fn contains<Q>(&self, t: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.counts.contains_key(t)
+    }
+
+    /// Removes one occurrence of the element, returning whether it was present.
+    pub fn remove<Q>(&mut self, t: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        match self.counts.get_mut(t) {
+            Some(count) => {
+                *count -= 1;
+                if *count == 0 {
+                    self.counts.remove(t);
+                }
+                self.len -= 1;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Removes every occurrence of the element, returning how many there were.
+    pub fn remove_all<Q>(&mut self, t: &Q) -> usize
+    where
+        T: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let count = self.counts.remove(t).unwrap_or(0);
+        self.len -= count;
+        count
+    }
+}
+
+impl<T, S> PartialEq for MultiSet<T, S>
+where
+    T: Eq + Hash,
+    S: BuildHasher,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.counts == other.counts
+    }
+}
+
+impl<T, S> Eq for MultiSet<T, S>
+where
+    T: Eq + Hash,
+    S: BuildHasher,
+{
+}
+
+impl<T, S> FromIterator<T> for MultiSet<T, S>
+where
+    T: Eq + Hash,
+    S: BuildHasher + Default,
+{
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut set = MultiSet::default();
+        set.extend(iter);
+        set
+    }
+}
+
+impl<T, S> Extend<T> for MultiSet<T, S>
+where
+    T: Eq + Hash,
+    S: BuildHasher,
+{
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for t in iter {
+            self.insert(t);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use quickcheck::quickcheck;
+
+    use super::{Counter, MultiSet};
+
+    #[test]
+    fn counts() {
+        let mut set = MultiSet::new();
+        assert_eq!(set.insert("a"), 1);
+        assert_eq!(set.insert("a"), 2);
+        assert_eq!(set.insert_many("b", 3), 3);
+        assert_eq!(set.count("a"), 2);
+        assert_eq!(set.count("z"), 0);
+        assert_eq!(set.len(), 5);
+        assert_eq!(set.distinct_len(), 2);
+        assert!(set.remove("a"));
+        assert_eq!(set.remove_all("b"), 3);
+        assert_eq!(set.len(), 1);
+    }
+
+    #[test]
+    fn most_common() {
+        let counter = "abcabaad".chars().collect::<Counter<_>>();
+        assert_eq!(counter.most_common(2), vec![(&'a', 4), (&'b', 2)]);
+        assert_eq!(counter.most_common(1), vec![(&'a', 4)]);
+        assert_eq!(counter.most_common(100).len(), 4);
+    }
+
+    #[test]
+    fn prop_matches_hash_map() {
+        fn p(input: Vec<u8>, removals: Vec<u8>) -> bool {
+            let mut set = input.iter().copied().collect::<MultiSet<_>>();
+            let mut model = HashMap::new();
+            for i in input.iter() {
+                *model.entry(*i).or_insert(0) += 1;
+            }
+            for r in removals.iter() {
+                let present = model.get(r).is_some_and(|c| *c > 0);
+                if present {
+                    *model.get_mut(r).unwrap() -= 1;
+                }
+                if set.remove(r) != present {
+                    return false;
+                }
+            }
+            model.iter().all(|(k, c)| set.count(k) == *c)
+                && set.len() == model.values().sum::<usize>()
+        }
+        quickcheck(p as fn(Vec<u8>, Vec<u8>) -> bool)
+    }
+}
@@ -0,0 +1,724 @@
+use std::{
+    cmp::Ordering,
+    fmt,
+    hash::{Hash, Hasher},
+    sync::Arc,
+};
+
+/// An immutable singly linked list. Prepending returns a new list which shares
+/// its tail with the original, so `cons`, `head`, `tail` and `clone` are all O(1).
+/// Lists are `Send` and `Sync` when their elements are, since tails are shared with `Arc`.
+pub struct List<T> {
+    head: Option<Arc<Node<T>>>,
+    len: usize,
+}
+
+struct Node<T> {
+    value: T,
+    next: Option<Arc<Node<T>>>,
+}
+
+impl<T> List<T> {
+    pub fn new() -> Self {
+        List { head: None, len: 0 }
+    }
+
+    /// Returns a new list with `value` in front of this one.
+    pub fn cons(&self, value: T) -> Self {
+        List {
+            head: Some(Arc::new(Node {
+                value,
+                next: self.head.clone(),
+            })),
+            len: self.len + 1,
+        }
+    }
+
+    pub fn head(&self) -> Option<&T> {
+        self.head.as_ref().map(|node| &node.value)
+    }
+
+    /// Returns the list without its first element, or None if it is empty.
+    pub fn tail(&self) -> Option<Self> {
+        self.head.as_ref().map(|node| List {
+            head: node.next.clone(),
+            len: self.len - 1,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            next: self.head.as_deref(),
+            len: self.len,
+        }
+    }
+
+    /// Whether both lists are the same physical list, without comparing elements.
+    pub fn ptr_eq(&self, other: &List<T>) -> bool {
+        match (&self.head, &other.head) {
+            (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+            (None, None) => true,
+            _ => false,
+        }
+    }
+}
+
+impl<T: Clone> List<T> {
+    pub fn reverse(&self) -> Self {
+        let mut reversed = List::new();
+        for value in self.iter() {
+            reversed = reversed.cons(value.clone());
+        }
+        reversed
+    }
+}
+
+impl<T> Default for List<T> {
+    fn default() -> Self {
+        List::new()
+    }
+}
+
+impl<T> Clone for List<T> {
+    fn clone(&self) -> Self {
+        List {
+            head: self.head.clone(),
+            len: self.len,
+        }
+    }
+}
+
+impl<T> Drop for List<T> {
+    fn drop(&mut self) {
+        // Unlink nodes iteratively so that dropping a long list doesn't overflow the stack.
+        // Stop at the first node which is still shared with another list.
+        let mut next = self.head.take();
+        while let Some(node) = next {
+            match Arc::try_unwrap(node) {
+                Ok(mut node) => next = node.next.take(),
+                Err(_) => break,
+            }
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for List<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<T: PartialEq> PartialEq for List<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len && self.iter().eq(other.iter())
+    }
+}
+
+impl<T: Eq> Eq for List<T> {}
+
+impl<T: PartialOrd> PartialOrd for List<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.iter().partial_cmp(other.iter())
+    }
+}
+
+impl<T: Ord> Ord for List<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.iter().cmp(other.iter())
+    }
+}
+
+impl<T: Hash> Hash for List<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.len.hash(state);
+        self.iter().for_each(|t| t.hash(state));
+    }
+}
+
+/// Builds a list whose head is the first element of the iterator.
+impl<T> FromIterator<T> for List<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let values = iter.into_iter().collect::<Vec<_>>();
+        let mut list = List::new();
+        for value in values.into_iter().rev() {
+            list = list.cons(value);
+        }
+        list
+    }
+}
+
+impl<'a, T> IntoIterator for &'a List<T> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// An iterator over the elements of a `List`, from head to end.
+pub struct Iter<'a, T> {
+    next: Option<&'a Node<T>>,
+    len: usize,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        let node = self.next?;
+        self.next = node.next.as_deref();
+        self.len -= 1;
+        Some(&node.value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<T> ExactSizeIterator for Iter<'_, T> {}
+
+/// An immutable ordered set, stored as an AVL tree whose subtrees are shared with `Arc`.
+/// Inserting and removing copy only the path to the changed node, so both are O(log n)
+/// and leave the original set intact.
+///
+/// Set operations split one tree by the keys of the other and join the results, stopping
+/// wherever both sides are the same subtree. Subtrees of the result which are unchanged
+/// from an input are reused rather than rebuilt. So comparing a snapshot against an edited
+/// copy of itself costs time in proportion to the edits rather than the size of the set.
+pub struct OrdSet<T> {
+    root: Tree<T>,
+}
+
+type Tree<T> = Option<Arc<TreeNode<T>>>;
+
+struct TreeNode<T> {
+    value: T,
+    left: Tree<T>,
+    right: Tree<T>,
+    height: usize,
+    size: usize,
+}
+
+impl<T> OrdSet<T> {
+    pub fn new() -> Self {
+        OrdSet { root: None }
+    }
+
+    pub fn len(&self) -> usize {
+        size(&self.root)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    pub fn first(&self) -> Option<&T> {
+        let mut node = self.root.as_deref()?;
+        while let Some(left) = node.left.as_deref() {
+            node = left;
+        }
+        Some(&node.value)
+    }
+
+    pub fn last(&self) -> Option<&T> {
+        let mut node = self.root.as_deref()?;
+        while let Some(right) = node.right.as_deref() {
+            node = right;
+        }
+        Some(&node.value)
+    }
+
+    /// Returns an iterator over the elements in ascending order.
+    pub fn iter(&self) -> OrdSetIter<'_, T> {
+        let mut iter = OrdSetIter {
+            stack: vec![],
+            len: self.len(),
+        };
+        iter.push_left_edge(&self.root);
+        iter
+    }
+
+    /// Whether both sets are the same physical tree, without comparing elements.
+    pub fn ptr_eq(&self, other: &OrdSet<T>) -> bool {
+        same(&self.root, &other.root)
+    }
+}
+
+impl<T: Ord + Clone> OrdSet<T> {
+    pub fn contains(&self, value: &T) -> bool {
+        let mut tree = &self.root;
+        while let Some(node) = tree {
+            tree = match value.cmp(&node.value) {
+                Ordering::Less => &node.left,
+                Ordering::Greater => &node.right,
+                Ordering::Equal => return true,
+            };
+        }
+        false
+    }
+
+    /// Returns a new set which also contains `value`. If it is already present, the new set
+    /// shares the whole tree with this one.
+    pub fn insert(&self, value: T) -> Self {
+        if self.contains(&value) {
+            return self.clone();
+        }
+        let (left, _, right) = split(&self.root, &value);
+        OrdSet {
+            root: join(left, value, right),
+        }
+    }
+
+    /// Returns a new set without `value`. If it is absent, the new set shares the whole tree
+    /// with this one.
+    pub fn remove(&self, value: &T) -> Self {
+        if !self.contains(value) {
+            return self.clone();
+        }
+        let (left, _, right) = split(&self.root, value);
+        OrdSet {
+            root: concat(left, right),
+        }
+    }
+
+    /// Returns the elements in either set.
+    pub fn union(&self, other: &Self) -> Self {
+        OrdSet {
+            root: union(&self.root, &other.root),
+        }
+    }
+
+    /// Returns the elements in both sets.
+    pub fn intersection(&self, other: &Self) -> Self {
+        OrdSet {
+            root: intersection(&self.root, &other.root),
+        }
+    }
+
+    /// Returns the elements in this set but not in `other`.
+    pub fn difference(&self, other: &Self) -> Self {
+        OrdSet {
+            root: difference(&self.root, &other.root),
+        }
+    }
+
+    /// Returns the elements in exactly one of the sets.
+    pub fn symmetric_difference(&self, other: &Self) -> Self {
+        self.difference(other).union(&other.difference(self))
+    }
+
+    /// Panics if the tree is out of order or unbalanced, or a cached height or size is wrong.
+    #[cfg(any(test, feature = "testing"))]
+    pub fn check_invariants(&self) {
+        fn check<T: Ord>(tree: &Tree<T>, low: Option<&T>, high: Option<&T>) {
+            let Some(node) = tree else {
+                return;
+            };
+            assert!(low.is_none_or(|low| *low < node.value), "out of order");
+            assert!(high.is_none_or(|high| node.value < *high), "out of order");
+            check(&node.left, low, Some(&node.value));
+            check(&node.right, Some(&node.value), high);
+            let (l, r) = (height(&node.left), height(&node.right));
+            assert!(l.abs_diff(r) <= 1, "unbalanced");
+            assert_eq!(node.height, l.max(r) + 1, "height is out of date");
+            assert_eq!(node.size, size(&node.left) + size(&node.right) + 1);
+        }
+        check(&self.root, None, None);
+    }
+}
+
+fn height<T>(tree: &Tree<T>) -> usize {
+    tree.as_ref().map_or(0, |node| node.height)
+}
+
+fn size<T>(tree: &Tree<T>) -> usize {
+    tree.as_ref().map_or(0, |node| node.size)
+}
+
+fn same<T>(a: &Tree<T>, b: &Tree<T>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+fn node<T>(left: Tree<T>, value: T, right: Tree<T>) -> Tree<T> {
+    Some(Arc::new(TreeNode {
+        height: height(&left).max(height(&right)) + 1,
+        size: size(&left) + size(&right) + 1,
+        value,
+        left,
+        right,
+    }))
+}
+
+/// Copies out the parts of a node, which must not be empty.
+fn expose<T: Clone>(tree: &Tree<T>) -> (Tree<T>, T, Tree<T>) {
+    let node = tree.as_ref().unwrap();
+    (node.left.clone(), node.value.clone(), node.right.clone())
+}
+
+/// Builds a node from subtrees whose heights differ by at most two, rotating if needed.
+fn balance<T: Clone>(left: Tree<T>, value: T, right: Tree<T>) -> Tree<T> {
+    if height(&left) > height(&right) + 1 {
+        let (ll, lv, lr) = expose(&left);
+        if height(&ll) >= height(&lr) {
+            node(ll, lv, node(lr, value, right))
+        } else {
+            let (lrl, lrv, lrr) = expose(&lr);
+            node(node(ll, lv, lrl), lrv, node(lrr, value, right))
+        }
+    } else if height(&right) > height(&left) + 1 {
+        let (rl, rv, rr) = expose(&right);
+        if height(&rr) >= height(&rl) {
+            node(node(left, value, rl), rv, rr)
+        } else {
+            let (rll, rlv, rlr) = expose(&rl);
+            node(node(left, value, rll), rlv, node(rlr, rv, rr))
+        }
+    } else {
+        node(left, value, right)
+    }
+}
+
+/// Joins two trees and a value which lies between them, descending the taller tree until
+/// the heights match. This takes time in proportion to the difference in heights.
+fn join<T: Clone>(left: Tree<T>, value: T, right: Tree<T>) -> Tree<T> {
+    if height(&left) > height(&right) + 1 {
+        let (ll, lv, lr) = expose(&left);
+        balance(ll, lv, join(lr, value, right))
+    } else if height(&right) > height(&left) + 1 {
+        let (rl, rv, rr) = expose(&right);
+        balance(join(left, value, rl), rv, rr)
+    } else {
+        node(left, value, right)
+    }
+}
+
+/// Joins two trees where every value in `left` is less than every value in `right`.
+fn concat<T: Clone>(left: Tree<T>, right: Tree<T>) -> Tree<T> {
+    match left {
+        None => right,
+        Some(_) => {
+            let (left, last) = split_last(&left);
+            join(left, last, right)
+        }
+    }
+}
+
+fn split_last<T: Clone>(tree: &Tree<T>) -> (Tree<T>, T) {
+    let (left, value, right) = expose(tree);
+    match right {
+        None => (left, value),
+        Some(_) => {
+            let (right, last) = split_last(&right);
+            (join(left, value, right), last)
+        }
+    }
+}
+
+/// Splits a tree into the values less than and greater than `key`, and whether it
+/// contained `key`. Subtrees entirely on one side are shared rather than copied.
+fn split<T: Ord + Clone>(tree: &Tree<T>, key: &T) -> (Tree<T>, bool, Tree<T>) {
+    let Some(node) = tree else {
+        return (None, false, None);
+    };
+    match key.cmp(&node.value) {
+        Ordering::Equal => (node.left.clone(), true, node.right.clone()),
+        Ordering::Less => {
+            let (left, found, right) = split(&node.left, key);
+            let right = join(right, node.value.clone(), node.right.clone());
+            (left, found, right)
+        }
+        Ordering::Greater => {
+            let (left, found, right) = split(&node.right, key);
+            let left = join(node.left.clone(), node.value.clone(), left);
+            (left, found, right)
+        }
+    }
+}
+
+/// Rebuilds `tree` around new subtrees, reusing it if they are the ones it already has.
+fn rejoin<T: Clone>(tree: &Tree<T>, left: Tree<T>, right: Tree<T>) -> Tree<T> {
+    let node = tree.as_ref().unwrap();
+    if same(&left, &node.left) && same(&right, &node.right) {
+        tree.clone()
+    } else {
+        join(left, node.value.clone(), right)
+    }
+}
+
+fn union<T: Ord + Clone>(a: &Tree<T>, b: &Tree<T>) -> Tree<T> {
+    let Some(node) = a else {
+        return b.clone();
+    };
+    if b.is_none() || same(a, b) {
+        return a.clone();
+    }
+    let (left, _, right) = split(b, &node.value);
+    let left = union(&node.left, &left);
+    let right = union(&node.right, &right);
+    rejoin(a, left, right)
+}
+
+fn intersection<T: Ord + Clone>(a: &Tree<T>, b: &Tree<T>) -> Tree<T> {
+    let Some(node) = a else {
+        return None;
+    };
+    if b.is_none() {
+        return None;
+    }
+    if same(a, b) {
+        return a.clone();
+    }
+    let (left, found, right) = split(b, &node.value);
+    let left = intersection(&node.left, &left);
+    let right = intersection(&node.right, &right);
+    if found {
+        rejoin(a, left, right)
+    } else {
+        concat(left, right)
+    }
+}
+
+fn difference<T: Ord + Clone>(a: &Tree<T>, b: &Tree<T>) -> Tree<T> {
+    let Some(node) = a else {
+        return None;
+    };
+    if b.is_none() {
+        return a.clone();
+    }
+    if same(a, b) {
+        return None;
+    }
+    let (left, found, right) = split(b, &node.value);
+    let left = difference(&node.left, &left);
+    let right = difference(&node.right, &right);
+    if found {
+        concat(left, right)
+    } else {
+        rejoin(a, left, right)
+    }
+}
+
+impl<T> Default for OrdSet<T> {
+    fn default() -> Self {
+        OrdSet::new()
+    }
+}
+
+impl<T> Clone for OrdSet<T> {
+    fn clone(&self) -> Self {
+        OrdSet {
+            root: self.root.clone(),
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for OrdSet<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+impl<T: PartialEq> PartialEq for OrdSet<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.ptr_eq(other) || (self.len() == other.len() && self.iter().eq(other.iter()))
+    }
+}
+
+impl<T: Eq> Eq for OrdSet<T> {}
+
+impl<T: Ord + Clone> FromIterator<T> for OrdSet<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut set = OrdSet::new();
+        for value in iter {
+            set = set.insert(value);
+        }
+        set
+    }
+}
+
+impl<'a, T> IntoIterator for &'a OrdSet<T> {
+    type Item = &'a T;
+    type IntoIter = OrdSetIter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// An iterator over the elements of an `OrdSet`, in ascending order.
+pub struct OrdSetIter<'a, T> {
+    stack: Vec<&'a TreeNode<T>>,
+    len: usize,
+}
+
+impl<'a, T> OrdSetIter<'a, T> {
+    fn push_left_edge(&mut self, mut tree: &'a Tree<T>) {
+        while let Some(node) = tree {
+            self.stack.push(node);
+            tree = &node.left;
+        }
+    }
+}
+
+impl<'a, T> Iterator for OrdSetIter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        let node = self.stack.pop()?;
+        self.push_left_edge(&node.right);
+        self.len -= 1;
+        Some(&node.value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<T> ExactSizeIterator for OrdSetIter<'_, T> {}
+
+#[cfg(test)]
+mod test {
+    use std::{collections::BTreeSet, thread};
+
+    use quickcheck::quickcheck;
+
+    use super::{List, OrdSet};
+
+    #[test]
+    fn cons_head_tail() {
+        let empty = List::new();
+        let a = empty.cons(1);
+        let b = a.cons(2);
+        assert_eq!(b.head(), Some(&2));
+        assert_eq!(b.tail(), Some(a.clone()));
+        assert!(b.tail().unwrap().ptr_eq(&a));
+        assert_eq!(a.len(), 1);
+        assert_eq!(empty.head(), None);
+        assert!(empty.tail().is_none());
+    }
+
+    #[test]
+    fn shared_tails() {
+        let base = [3, 4].into_iter().collect::<List<_>>();
+        let a = base.cons(1);
+        let b = base.cons(2);
+        assert_eq!(a.iter().copied().collect::<Vec<_>>(), vec![1, 3, 4]);
+        assert_eq!(b.iter().copied().collect::<Vec<_>>(), vec![2, 3, 4]);
+        drop(base);
+        assert!(a.tail().unwrap().ptr_eq(&b.tail().unwrap()));
+    }
+
+    #[test]
+    fn send_across_threads() {
+        let list = (0..10).collect::<List<_>>();
+        let other = list.clone();
+        let sum = thread::spawn(move || other.iter().sum::<i32>())
+            .join()
+            .unwrap();
+        assert_eq!(sum, 45);
+    }
+
+    #[test]
+    fn drop_long_list() {
+        let list = (0..1_000_000).collect::<List<_>>();
+        assert_eq!(list.len(), 1_000_000);
+    }
+
+    #[test]
+    fn prop_reverse() {
+        fn p(input: Vec<u32>) -> bool {
+            let list = input.iter().copied().collect::<List<_>>();
+            list.reverse()
+                .iter()
+                .copied()
+                .eq(input.iter().rev().copied())
+                && list.reverse().reverse() == list
+        }
+        quickcheck(p as fn(Vec<u32>) -> bool)
+    }
+
+    #[test]
+    fn ord_set_snapshots() {
+        let a = [5, 1, 3].into_iter().collect::<OrdSet<_>>();
+        let b = a.insert(4);
+        assert_eq!(a.iter().copied().collect::<Vec<_>>(), vec![1, 3, 5]);
+        assert_eq!(b.iter().copied().collect::<Vec<_>>(), vec![1, 3, 4, 5]);
+        assert!(b.contains(&4) && !a.contains(&4));
+        assert!(b.remove(&4) == a);
+        assert!(a.insert(3).ptr_eq(&a));
+        assert!(a.remove(&2).ptr_eq(&a));
+        assert_eq!((a.first(), a.last()), (Some(&1), Some(&5)));
+        assert_eq!(format!("{:?}", b), "{1, 3, 4, 5}");
+    }
+
+    #[test]
+    fn ord_set_operations_share_snapshots() {
+        let a = (0..1000).collect::<OrdSet<_>>();
+        let b = a.insert(1000).remove(&10);
+        // Results equal to an input are that input, found without visiting every element
+        assert!(a.intersection(&a.insert(2000)).ptr_eq(&a));
+        assert!(a.union(&a.remove(&500)).ptr_eq(&a));
+        assert!(a.difference(&OrdSet::new().insert(5000)).ptr_eq(&a));
+        let changed = a.symmetric_difference(&b);
+        assert_eq!(changed.iter().copied().collect::<Vec<_>>(), vec![10, 1000]);
+        assert!(a.difference(&a.clone()).is_empty());
+        changed.check_invariants();
+    }
+
+    #[test]
+    fn prop_ord_set_matches_btree_set() {
+        fn p(left: Vec<u8>, right: Vec<u8>, removed: Vec<u8>) -> bool {
+            let mut a = left.iter().copied().collect::<OrdSet<_>>();
+            // Share structure between the sets, as snapshots of one another would
+            let mut b = a.clone();
+            for &x in right.iter() {
+                b = b.insert(x);
+            }
+            for x in removed.iter() {
+                a = a.remove(x);
+            }
+            let mut left = left.into_iter().collect::<BTreeSet<_>>();
+            let right = left
+                .iter()
+                .chain(right.iter())
+                .copied()
+                .collect::<BTreeSet<_>>();
+            for x in removed.iter() {
+                left.remove(x);
+            }
+            let results = [
+                (a.union(&b), left.union(&right).copied().collect::<Vec<_>>()),
+                (
+                    a.intersection(&b),
+                    left.intersection(&right).copied().collect(),
+                ),
+                (a.difference(&b), left.difference(&right).copied().collect()),
+                (b.difference(&a), right.difference(&left).copied().collect()),
+                (
+                    a.symmetric_difference(&b),
+                    left.symmetric_difference(&right).copied().collect(),
+                ),
+            ];
+            results.into_iter().all(|(set, expected)| {
+                set.check_invariants();
+                set.len() == expected.len() && set.iter().copied().eq(expected)
+            })
+        }
+        quickcheck(p as fn(Vec<u8>, Vec<u8>, Vec<u8>) -> bool)
+    }
+}
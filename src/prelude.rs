@@ -0,0 +1,121 @@
+//! Re-exports the main collection types and traits, for use with `use rastd::prelude::*`.
+
+pub use crate::bounded_map::BoundedMap;
+pub use crate::capacity::Capacity;
+pub use crate::clock::{Clock, MockClock, SystemClock};
+pub use crate::footprint::MemoryFootprint;
+pub use crate::from_sorted::FromSorted;
+pub use crate::seek::Seekable;
+
+#[cfg(feature = "arena")]
+pub use crate::arena::Arena;
+#[cfg(feature = "avl_set")]
+pub use crate::avl_set::AVLSet;
+#[cfg(feature = "avl_tree")]
+pub use crate::avl_tree::AVLTree;
+#[cfg(feature = "bag")]
+pub use crate::bag::Bag;
+#[cfg(feature = "batch")]
+pub use crate::batch::BatchQueue;
+#[cfg(feature = "bimap")]
+pub use crate::bimap::BiMap;
+#[cfg(feature = "bitset")]
+pub use crate::bitset::BitSet;
+#[cfg(feature = "bs_tree")]
+pub use crate::bs_tree::BSTree;
+#[cfg(feature = "compact")]
+pub use crate::compact::{StaticSortedSet, StaticSortedSetView};
+#[cfg(feature = "cow")]
+pub use crate::cow::CowMap;
+#[cfg(feature = "cuckoo")]
+pub use crate::cuckoo::CuckooFilter;
+#[cfg(feature = "dedup")]
+pub use crate::dedup::RecentSet;
+#[cfg(feature = "disjoint_set")]
+pub use crate::disjoint_set::{KeyedUnionFind, UnionFind};
+#[cfg(feature = "expiry_map")]
+pub use crate::expiry_map::ExpiryMap;
+#[cfg(feature = "extsort")]
+pub use crate::extsort::{ExternalSorter, RunStore};
+#[cfg(feature = "graph")]
+pub use crate::graph::Graph;
+#[cfg(feature = "hash_map")]
+pub use crate::hash_map::FlatHashMap;
+#[cfg(feature = "hash_trie")]
+pub use crate::hash_trie::{FilteredHashTrie, HashTrie};
+#[cfg(feature = "heap")]
+pub use crate::heap::{BoundedHeap, Heap};
+#[cfg(feature = "index_list")]
+pub use crate::index_list::IndexList;
+#[cfg(feature = "index_map")]
+pub use crate::index_map::IndexMap;
+#[cfg(feature = "interval_tree")]
+pub use crate::interval_tree::IntervalTree;
+#[cfg(feature = "lfu_cache")]
+pub use crate::lfu_cache::LFUCache;
+#[cfg(feature = "linked_list")]
+pub use crate::linked_list::LinkedList;
+#[cfg(feature = "llrb")]
+pub use crate::llrb::LLRBSet;
+#[cfg(feature = "lru_cache")]
+pub use crate::lru_cache::LRUCache;
+#[cfg(feature = "merkle")]
+pub use crate::merkle::MerkleTree;
+#[cfg(feature = "min_stack")]
+pub use crate::min_stack::MinMaxStack;
+#[cfg(feature = "monotonic")]
+pub use crate::monotonic::MinMaxDeque;
+#[cfg(feature = "multi_index")]
+pub use crate::multi_index::MultiIndexMap;
+#[cfg(feature = "multi_map")]
+pub use crate::multi_map::MultiMap;
+#[cfg(feature = "multi_set")]
+pub use crate::multi_set::{Counter, MultiSet};
+#[cfg(feature = "persistent")]
+pub use crate::persistent::{List, OrdSet};
+#[cfg(feature = "priority_map")]
+pub use crate::priority_map::PriorityMap;
+#[cfg(feature = "range_map")]
+pub use crate::range_map::RangeMap;
+#[cfg(feature = "router")]
+pub use crate::router::PathRouter;
+#[cfg(feature = "sampling")]
+pub use crate::sampling::{Reservoir, WeightedReservoir};
+#[cfg(feature = "scheduler")]
+pub use crate::scheduler::PriorityBuckets;
+#[cfg(feature = "sketch")]
+pub use crate::sketch::{BloomFilter, CountMinSketch};
+#[cfg(feature = "slab")]
+pub use crate::slab::SlotMap;
+#[cfg(feature = "small_vec")]
+pub use crate::small_vec::SmallVec;
+#[cfg(feature = "sorted_list")]
+pub use crate::sorted_list::SortedList;
+#[cfg(feature = "spatial")]
+pub use crate::spatial::QuadTree;
+#[cfg(feature = "static_tree")]
+pub use crate::static_tree::{EytzingerSearch, EytzingerSearchView};
+#[cfg(feature = "static_trie")]
+pub use crate::static_trie::{StaticTrie, StaticTrieBuilder, StaticTrieView};
+#[cfg(feature = "stats")]
+pub use crate::stats::RunningMedian;
+#[cfg(feature = "suffix")]
+pub use crate::suffix::SuffixArray;
+#[cfg(feature = "tiered_map")]
+pub use crate::tiered_map::TieredMap;
+#[cfg(feature = "timer_wheel")]
+pub use crate::timer_wheel::TimerWheel;
+#[cfg(feature = "tinylfu")]
+pub use crate::tinylfu::Admission;
+#[cfg(feature = "txn")]
+pub use crate::txn::TxnMap;
+#[cfg(feature = "veb")]
+pub use crate::veb::VebTree;
+#[cfg(feature = "viz")]
+pub use crate::viz::Visualize;
+#[cfg(feature = "weak_map")]
+pub use crate::weak_map::WeakValueHashMap;
+#[cfg(feature = "weighted")]
+pub use crate::weighted::WeightedIndexTree;
+#[cfg(feature = "window")]
+pub use crate::window::SlidingWindow;
@@ -0,0 +1,261 @@
+use std::{
+    borrow::Borrow,
+    collections::{hash_map::RandomState, HashMap},
+    hash::{BuildHasher, Hash},
+};
+
+/// A priority queue of keys, each of which appears at most once and whose priority can be
+/// changed or removed while it is queued. Like `Heap`, the smallest priority comes first.
+/// The queue is a binary heap which records each key's position in a hash map, so
+/// `push`, `pop`, `change_priority` and `remove` are O(log n) and lookups are O(1).
+#[derive(Debug, Clone)]
+pub struct PriorityMap<K, P, S = RandomState> {
+    heap: Vec<(K, P)>,
+    positions: HashMap<K, usize, S>,
+}
+
+impl<K, P> PriorityMap<K, P> {
+    pub fn new() -> Self {
+        PriorityMap::with_hasher(RandomState::new())
+    }
+}
+
+impl<K, P, S> PriorityMap<K, P, S> {
+    pub fn with_hasher(hasher: S) -> Self {
+        PriorityMap {
+            heap: vec![],
+            positions: HashMap::with_hasher(hasher),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.heap.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.heap.is_empty()
+    }
+
+    /// Returns the key with the smallest priority without removing it.
+    pub fn peek(&self) -> Option<(&K, &P)> {
+        self.heap.first().map(|(k, p)| (k, p))
+    }
+
+    /// Returns an iterator over the keys and priorities in an arbitrary order.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &P)> {
+        self.heap.iter().map(|(k, p)| (k, p))
+    }
+}
+
+impl<K, P, S> PriorityMap<K, P, S>
+where
+    K: Eq + Hash + Clone,
+    P: Ord,
+    S: BuildHasher,
+{
+    /// Queues the key, or changes its priority if it is already queued, returning the
+    /// old priority.
+    pub fn push(&mut self, k: K, p: P) -> Option<P> {
+        if self.positions.contains_key(&k) {
+            return self.change_priority(&k, p);
+        }
+        self.positions.insert(k.clone(), self.heap.len());
+        self.heap.push((k, p));
+        self.sift_up(self.heap.len() - 1);
+        None
+    }
+
+    /// Changes the priority of a queued key, returning the old priority, or `None` without
+    /// queueing the key if it is absent.
+    pub fn change_priority<Q>(&mut self, k: &Q, p: P) -> Option<P>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let index = *self.positions.get(k)?;
+        let old = std::mem::replace(&mut self.heap[index].1, p);
+        if self.heap[index].1 < old {
+            self.sift_up(index);
+        } else {
+            self.sift_down(index);
+        }
+        Some(old)
+    }
+
+    pub fn get_priority<Q>(&self, k: &Q) -> Option<&P>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.positions.get(k).map(|&index| &self.heap[index].1)
+    }
+
+    pub fn contains_key<Q>(&self, k: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.positions.contains_key(k)
+    }
+
+    /// Removes and returns the key with the smallest priority.
+    pub fn pop(&mut self) -> Option<(K, P)> {
+        if self.heap.is_empty() {
+            return None;
+        }
+        Some(self.remove_at(0))
+    }
+
+    /// Removes the key from the queue, returning its priority.
+    pub fn remove<Q>(&mut self, k: &Q) -> Option<P>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let index = *self.positions.get(k)?;
+        Some(self.remove_at(index).1)
+    }
+
+    fn remove_at(&mut self, index: usize) -> (K, P) {
+        let last = self.heap.len() - 1;
+        self.swap(index, last);
+        let (k, p) = self.heap.pop().unwrap();
+        self.positions.remove(&k);
+        if index < self.heap.len() {
+            // The element moved into the gap may belong above or below it
+            self.sift_up(index);
+            self.sift_down(index);
+        }
+        (k, p)
+    }
+
+    fn sift_up(&mut self, mut index: usize) {
+        while index > 0 {
+            let parent = (index - 1) / 2;
+            if self.heap[index].1 >= self.heap[parent].1 {
+                break;
+            }
+            self.swap(index, parent);
+            index = parent;
+        }
+    }
+
+    fn sift_down(&mut self, mut index: usize) {
+        loop {
+            let mut lowest = index;
+            for child in [2 * index + 1, 2 * index + 2] {
+                if child < self.heap.len() && self.heap[child].1 < self.heap[lowest].1 {
+                    lowest = child;
+                }
+            }
+            if lowest == index {
+                break;
+            }
+            self.swap(index, lowest);
+            index = lowest;
+        }
+    }
+
+    /// Swaps two elements of the heap and updates their recorded positions.
+    fn swap(&mut self, a: usize, b: usize) {
+        self.heap.swap(a, b);
+        *self.positions.get_mut(&self.heap[a].0).unwrap() = a;
+        *self.positions.get_mut(&self.heap[b].0).unwrap() = b;
+    }
+
+    /// Panics if a child has a smaller priority than its parent or a position is out of date.
+    #[cfg(any(test, feature = "testing"))]
+    pub fn check_invariants(&self) {
+        for i in 1..self.heap.len() {
+            assert!(
+                self.heap[(i - 1) / 2].1 <= self.heap[i].1,
+                "heap property broken at {}",
+                i
+            );
+        }
+        assert_eq!(self.positions.len(), self.heap.len(), "stale positions");
+        for (i, (k, _)) in self.heap.iter().enumerate() {
+            assert_eq!(self.positions[k], i, "position is out of date");
+        }
+    }
+}
+
+impl<K, P, S: Default> Default for PriorityMap<K, P, S> {
+    fn default() -> Self {
+        PriorityMap::with_hasher(S::default())
+    }
+}
+
+impl<K, P> FromIterator<(K, P)> for PriorityMap<K, P>
+where
+    K: Eq + Hash + Clone,
+    P: Ord,
+{
+    fn from_iter<I: IntoIterator<Item = (K, P)>>(iter: I) -> Self {
+        let mut map = PriorityMap::new();
+        for (k, p) in iter {
+            map.push(k, p);
+        }
+        map
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use quickcheck::quickcheck;
+
+    use super::PriorityMap;
+
+    #[test]
+    fn pop_in_priority_order() {
+        let mut queue = [("b", 2), ("c", 3), ("a", 1)]
+            .into_iter()
+            .collect::<PriorityMap<_, _>>();
+        assert_eq!(queue.peek(), Some((&"a", &1)));
+        assert_eq!(queue.push("d", 0), None);
+        assert_eq!(queue.change_priority("c", -1), Some(3));
+        assert_eq!(queue.change_priority("e", 0), None);
+        assert_eq!(queue.push("a", 5), Some(1));
+        assert_eq!(queue.get_priority("a"), Some(&5));
+        assert_eq!(queue.remove("b"), Some(2));
+        queue.check_invariants();
+        assert_eq!(
+            std::iter::from_fn(|| queue.pop()).collect::<Vec<_>>(),
+            vec![("c", -1), ("d", 0), ("a", 5)]
+        );
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn prop_matches_model() {
+        fn p(ops: Vec<(u8, u8, i8)>) -> bool {
+            let mut queue = PriorityMap::new();
+            let mut model = HashMap::new();
+            for (op, k, p) in ops {
+                let k = k % 16;
+                let ok = match op % 4 {
+                    0 => queue.push(k, p) == model.insert(k, p),
+                    1 => queue.remove(&k) == model.remove(&k),
+                    2 => {
+                        let old = model.get_mut(&k).map(|old| std::mem::replace(old, p));
+                        queue.change_priority(&k, p) == old
+                    }
+                    _ => match queue.pop() {
+                        Some((k, p)) => {
+                            model.values().all(|&other| other >= p) && model.remove(&k) == Some(p)
+                        }
+                        None => model.is_empty(),
+                    },
+                };
+                queue.check_invariants();
+                if !ok {
+                    return false;
+                }
+            }
+            queue.len() == model.len()
+        }
+        quickcheck(p as fn(Vec<(u8, u8, i8)>) -> bool);
+    }
+}
@@ -0,0 +1,334 @@
+use std::{
+    collections::{btree_map, BTreeMap},
+    iter::FusedIterator,
+    ops::Range,
+};
+
+/// A map from non-overlapping half-open ranges to values. Setting a range overwrites the
+/// parts of any ranges it overlaps, splitting them if needed, and merges with neighbouring
+/// ranges which touch it and hold an equal value, so the map never holds two adjacent
+/// ranges with equal values.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RangeMap<K, V> {
+    // Keyed by the start of each range
+    entries: BTreeMap<K, Entry<K, V>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Entry<K, V> {
+    range: Range<K>,
+    value: V,
+}
+
+impl<K, V> RangeMap<K, V> {
+    pub fn new() -> Self {
+        RangeMap {
+            entries: BTreeMap::new(),
+        }
+    }
+
+    /// The number of distinct ranges.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the ranges and their values in ascending order.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = (&Range<K>, &V)> {
+        self.entries.values().map(|e| (&e.range, &e.value))
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+impl<K, V> RangeMap<K, V>
+where
+    K: Ord + Clone,
+    V: Eq + Clone,
+{
+    /// Panics if a range is empty, ranges overlap, or adjacent ranges hold equal values.
+    #[cfg(any(test, feature = "testing"))]
+    pub fn check_invariants(&self) {
+        let mut prev: Option<&Entry<K, V>> = None;
+        for (start, entry) in self.entries.iter() {
+            assert!(*start == entry.range.start, "keyed by the wrong start");
+            assert!(!entry.range.is_empty(), "empty range");
+            if let Some(prev) = prev {
+                assert!(prev.range.end <= entry.range.start, "ranges overlap");
+                assert!(
+                    prev.range.end < entry.range.start || prev.value != entry.value,
+                    "adjacent ranges with equal values"
+                );
+            }
+            prev = Some(entry);
+        }
+    }
+
+    /// Maps every key in the range to the value. Ranges it partly overlaps are split around
+    /// it, and it is merged with the ranges either side if they touch it and hold an equal
+    /// value, so setting part of a range back to the value around it leaves one range.
+    pub fn set_range(&mut self, range: Range<K>, value: V) {
+        if range.is_empty() {
+            return;
+        }
+        self.remove(range.clone());
+        let Range { mut start, mut end } = range;
+        if let Some((_, left)) = self.entries.range(..&start).next_back() {
+            if left.range.end == start && left.value == value {
+                start = left.range.start.clone();
+                self.entries.remove(&start);
+            }
+        }
+        if self.entries.get(&end).is_some_and(|e| e.value == value) {
+            end = self.entries.remove(&end).unwrap().range.end;
+        }
+        self.entries.insert(
+            start.clone(),
+            Entry {
+                range: start..end,
+                value,
+            },
+        );
+    }
+
+    /// Like `set_range`.
+    pub fn insert(&mut self, range: Range<K>, value: V) {
+        self.set_range(range, value);
+    }
+
+    /// Unmaps every key in the range, splitting ranges which extend past it.
+    pub fn remove(&mut self, range: Range<K>) {
+        if range.is_empty() {
+            return;
+        }
+        // Only the range starting before this one can overlap its start
+        if let Some((_, left)) = self.entries.range(..&range.start).next_back() {
+            if left.range.end > range.start {
+                let left = left.clone();
+                self.entries.get_mut(&left.range.start).unwrap().range.end = range.start.clone();
+                if left.range.end > range.end {
+                    self.insert_entry(range.end.clone()..left.range.end, left.value);
+                }
+            }
+        }
+        let inside = self
+            .entries
+            .range(range.clone())
+            .map(|(k, _)| k.clone())
+            .collect::<Vec<_>>();
+        for k in inside {
+            let entry = self.entries.remove(&k).unwrap();
+            if entry.range.end > range.end {
+                self.insert_entry(range.end.clone()..entry.range.end, entry.value);
+            }
+        }
+    }
+
+    pub fn get(&self, k: &K) -> Option<&V> {
+        self.get_key_value(k).map(|(_, v)| v)
+    }
+
+    /// Returns the range containing the key along with its value.
+    pub fn get_key_value(&self, k: &K) -> Option<(&Range<K>, &V)> {
+        let (_, entry) = self.entries.range(..=k).next_back()?;
+        entry
+            .range
+            .contains(k)
+            .then_some((&entry.range, &entry.value))
+    }
+
+    pub fn contains_key(&self, k: &K) -> bool {
+        self.get(k).is_some()
+    }
+
+    /// Returns the parts of the range which are not mapped, in ascending order.
+    pub fn gaps(&self, range: Range<K>) -> Vec<Range<K>> {
+        self.iter_gaps(range).collect()
+    }
+
+    /// Like `gaps`, but finds each gap as the iterator reaches it rather than all at once,
+    /// so a search for the first free range stops at the first one found.
+    pub fn iter_gaps(&self, range: Range<K>) -> Gaps<'_, K, V> {
+        if range.is_empty() {
+            return Gaps {
+                inside: self.entries.range(..),
+                cursor: range.end.clone(),
+                end: range.end,
+            };
+        }
+        // Only the range starting before this one can cover its start
+        let cursor = match self.entries.range(..&range.start).next_back() {
+            Some((_, left)) if left.range.end > range.start => left.range.end.clone(),
+            _ => range.start.clone(),
+        };
+        Gaps {
+            inside: self.entries.range(range.clone()),
+            cursor,
+            end: range.end,
+        }
+    }
+
+    fn insert_entry(&mut self, range: Range<K>, value: V) {
+        self.entries
+            .insert(range.start.clone(), Entry { range, value });
+    }
+}
+
+/// An iterator over the unmapped parts of a range of a `RangeMap`, as returned by
+/// `RangeMap::iter_gaps`.
+#[derive(Debug, Clone)]
+pub struct Gaps<'a, K, V> {
+    // The ranges starting within the searched range
+    inside: btree_map::Range<'a, K, Entry<K, V>>,
+    // Where the next gap could start
+    cursor: K,
+    end: K,
+}
+
+impl<K: Ord + Clone, V> Iterator for Gaps<'_, K, V> {
+    type Item = Range<K>;
+
+    fn next(&mut self) -> Option<Range<K>> {
+        while self.cursor < self.end {
+            let Some((_, entry)) = self.inside.next() else {
+                let start = std::mem::replace(&mut self.cursor, self.end.clone());
+                return Some(start..self.end.clone());
+            };
+            let start = std::mem::replace(&mut self.cursor, entry.range.end.clone());
+            if entry.range.start > start {
+                return Some(start..entry.range.start.clone());
+            }
+        }
+        None
+    }
+}
+
+impl<K: Ord + Clone, V> FusedIterator for Gaps<'_, K, V> {}
+
+impl<K, V> Default for RangeMap<K, V> {
+    fn default() -> Self {
+        RangeMap::new()
+    }
+}
+
+impl<K, V> FromIterator<(Range<K>, V)> for RangeMap<K, V>
+where
+    K: Ord + Clone,
+    V: Eq + Clone,
+{
+    /// Later ranges overwrite earlier ones where they overlap.
+    fn from_iter<I: IntoIterator<Item = (Range<K>, V)>>(iter: I) -> Self {
+        let mut map = RangeMap::new();
+        for (range, value) in iter {
+            map.insert(range, value);
+        }
+        map
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use quickcheck::quickcheck;
+
+    use super::RangeMap;
+
+    #[test]
+    fn split_and_merge() {
+        let mut map = RangeMap::new();
+        map.insert(0..10, 'a');
+        map.insert(4..6, 'b');
+        assert_eq!(
+            map.iter().collect::<Vec<_>>(),
+            vec![(&(0..4), &'a'), (&(4..6), &'b'), (&(6..10), &'a')]
+        );
+        map.insert(4..6, 'a');
+        assert_eq!(map.iter().collect::<Vec<_>>(), vec![(&(0..10), &'a')]);
+        map.insert(10..12, 'a');
+        assert_eq!(map.get_key_value(&11), Some((&(0..12), &'a')));
+        assert_eq!(map.get(&12), None);
+    }
+
+    #[test]
+    fn gaps() {
+        let mut map = RangeMap::new();
+        map.insert(0x1000..0x2000, "heap");
+        map.insert(0x3000..0x4000, "stack");
+        assert_eq!(
+            map.gaps(0x1800..0x5000),
+            vec![0x2000..0x3000, 0x4000..0x5000]
+        );
+        map.remove(0x1000..0x1800);
+        assert_eq!(map.gaps(0..0x2000), vec![0..0x1800]);
+        assert!(map.gaps(0x3100..0x3200).is_empty());
+    }
+
+    #[test]
+    fn set_range_coalesces() {
+        let mut map = RangeMap::new();
+        map.set_range(0..4, 1);
+        map.set_range(6..8, 1);
+        map.set_range(8..10, 2);
+        // Bridging the gap merges both neighbours holding the same value
+        map.set_range(4..6, 1);
+        assert_eq!(
+            map.iter().collect::<Vec<_>>(),
+            vec![(&(0..8), &1), (&(8..10), &2)]
+        );
+        // Overwriting across a boundary splits one side and merges with the other
+        map.set_range(6..9, 2);
+        assert_eq!(
+            map.iter().collect::<Vec<_>>(),
+            vec![(&(0..6), &1), (&(6..10), &2)]
+        );
+        map.check_invariants();
+    }
+
+    #[test]
+    fn iter_gaps() {
+        let map = [(2..4, ()), (6..8, ()), (10..12, ())]
+            .into_iter()
+            .collect::<RangeMap<_, _>>();
+        let mut gaps = map.iter_gaps(3..11);
+        assert_eq!(gaps.next(), Some(4..6));
+        assert_eq!(gaps.next(), Some(8..10));
+        assert_eq!(gaps.next(), None);
+        assert_eq!(gaps.next(), None);
+        assert_eq!(map.iter_gaps(0..20).last(), Some(12..20));
+        assert_eq!(map.iter_gaps(6..8).next(), None);
+        assert_eq!(map.iter_gaps(5..5).next(), None);
+        assert_eq!(map.iter_gaps(0..1).collect::<Vec<_>>(), vec![0..1]);
+    }
+
+    #[test]
+    fn prop_matches_array() {
+        // The model maps each key in 0..64 directly to its value
+        fn p(ops: Vec<(u8, u8, Option<bool>)>) -> bool {
+            let mut map = RangeMap::new();
+            let mut model = [None; 64];
+            for (a, b, value) in ops {
+                let (a, b) = ((a % 65) as usize, (b % 65) as usize);
+                let range = a.min(b)..a.max(b);
+                match value {
+                    Some(v) => map.set_range(range.clone(), v),
+                    None => map.remove(range.clone()),
+                }
+                model[range].fill(value);
+            }
+            let ranges = map.iter().collect::<Vec<_>>();
+            let coalesced = ranges
+                .windows(2)
+                .all(|w| w[0].0.end < w[1].0.start || w[0].1 != w[1].1);
+            let gaps = (0..64)
+                .filter(|&k| !map.gaps(0..64).iter().any(|g| g.contains(&k)))
+                .collect::<Vec<_>>();
+            let mapped = (0..64).filter(|&k| model[k].is_some()).collect::<Vec<_>>();
+            coalesced && gaps == mapped && (0..64).all(|k| map.get(&k).copied() == model[k])
+        }
+        quickcheck(p as fn(Vec<(u8, u8, Option<bool>)>) -> bool)
+    }
+}
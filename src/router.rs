@@ -0,0 +1,184 @@
+use crate::hash_trie::HashTrie;
+
+/// Maps URL path patterns to values. Patterns are split into `/`-separated segments, where
+/// `:name` captures any one segment and a final `*name` captures one or more trailing segments.
+/// When several patterns match a path, literal segments win over captures, and single
+/// segment captures win over wildcards, deciding segment by segment from the left.
+#[derive(Debug, Clone)]
+pub struct PathRouter<V> {
+    // Each route stores the names of its captures in order alongside its value
+    trie: HashTrie<Segment, (Vec<String>, V)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Segment {
+    Literal(String),
+    Param,
+    Wildcard,
+}
+
+/// The value of the route which matched a path, and the path segments it captured.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Match<'r, 'p, V> {
+    pub value: &'r V,
+    pub params: Vec<(&'r str, &'p str)>,
+}
+
+impl<'r, 'p, V> Match<'r, 'p, V> {
+    pub fn param(&self, name: &str) -> Option<&'p str> {
+        self.params
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, v)| *v)
+    }
+}
+
+impl<V> PathRouter<V> {
+    pub fn new() -> Self {
+        PathRouter {
+            trie: HashTrie::new(),
+        }
+    }
+
+    /// Adds a route, returning the value of an existing route with the same shape.
+    /// Routes which differ only in the names of their captures have the same shape.
+    pub fn insert(&mut self, pattern: &str, value: V) -> Option<V> {
+        let (key, names) = parse(pattern);
+        self.trie.insert(key, (names, value)).map(|(_, v)| v)
+    }
+
+    pub fn remove(&mut self, pattern: &str) -> Option<V> {
+        let (key, _) = parse(pattern);
+        self.trie.remove(key).map(|(_, v)| v)
+    }
+
+    /// Finds the route which best matches the path.
+    pub fn route<'r, 'p>(&'r self, path: &'p str) -> Option<Match<'r, 'p, V>> {
+        let segments = segments(path).collect::<Vec<_>>();
+        let mut captures = vec![];
+        let (names, value) = find(&self.trie, path, &segments, &mut captures)?;
+        let params = names.iter().map(String::as_str).zip(captures).collect();
+        Some(Match { value, params })
+    }
+}
+
+impl<V> Default for PathRouter<V> {
+    fn default() -> Self {
+        PathRouter::new()
+    }
+}
+
+/// The non-empty segments of the path with their byte offsets.
+fn segments(path: &str) -> impl Iterator<Item = (usize, &str)> {
+    path.split('/')
+        .scan(0, |offset, segment| {
+            let start = *offset;
+            *offset += segment.len() + 1;
+            Some((start, segment))
+        })
+        .filter(|(_, segment)| !segment.is_empty())
+}
+
+fn parse(pattern: &str) -> (Vec<Segment>, Vec<String>) {
+    let mut key = vec![];
+    let mut names = vec![];
+    let mut parts = segments(pattern).map(|(_, s)| s).peekable();
+    while let Some(part) = parts.next() {
+        if let Some(name) = part.strip_prefix(':') {
+            key.push(Segment::Param);
+            names.push(name.to_string());
+        } else if let Some(name) = part.strip_prefix('*') {
+            assert!(parts.peek().is_none(), "wildcard must be the last segment");
+            key.push(Segment::Wildcard);
+            names.push(name.to_string());
+        } else {
+            key.push(Segment::Literal(part.to_string()));
+        }
+    }
+    (key, names)
+}
+
+/// Matches the remaining segments against the subtrie, backtracking to less specific
+/// segments when a more specific one leads to a dead end.
+fn find<'r, 'p, V>(
+    node: &'r HashTrie<Segment, (Vec<String>, V)>,
+    path: &'p str,
+    segments: &[(usize, &'p str)],
+    captures: &mut Vec<&'p str>,
+) -> Option<&'r (Vec<String>, V)> {
+    let [(offset, segment), rest @ ..] = segments else {
+        return node.value();
+    };
+    if let Some(child) = node.child(&Segment::Literal(segment.to_string())) {
+        if let Some(found) = find(child, path, rest, captures) {
+            return Some(found);
+        }
+    }
+    if let Some(child) = node.child(&Segment::Param) {
+        captures.push(segment);
+        if let Some(found) = find(child, path, rest, captures) {
+            return Some(found);
+        }
+        captures.pop();
+    }
+    let found = node.child(&Segment::Wildcard)?.value()?;
+    captures.push(path[*offset..].trim_end_matches('/'));
+    Some(found)
+}
+
+#[cfg(test)]
+mod test {
+    use super::PathRouter;
+
+    #[test]
+    fn captures() {
+        let mut router = PathRouter::new();
+        router.insert("/users/:id", "user");
+        router.insert("/users/:id/posts/:post", "post");
+        router.insert("/static/*file", "static");
+
+        let m = router.route("/users/42/posts/7").unwrap();
+        assert_eq!(*m.value, "post");
+        assert_eq!(m.params, vec![("id", "42"), ("post", "7")]);
+        let m = router.route("/static/css/site.css").unwrap();
+        assert_eq!(m.param("file"), Some("css/site.css"));
+        assert!(router.route("/static").is_none());
+        assert!(router.route("/users").is_none());
+    }
+
+    #[test]
+    fn literals_take_priority() {
+        let mut router = PathRouter::new();
+        router.insert("/users/:id", 1);
+        router.insert("/users/me", 2);
+        router.insert("/users/me/settings/*rest", 3);
+        router.insert("/users/:id/settings/profile", 4);
+        router.insert("/*any", 5);
+        assert_eq!(router.route("/users/me").unwrap().value, &2);
+        assert_eq!(router.route("/users/you").unwrap().value, &1);
+        // The earlier literal segment decides, even though the other route is more literal overall
+        assert_eq!(
+            router.route("/users/me/settings/profile").unwrap().value,
+            &3
+        );
+        assert_eq!(
+            router.route("/users/you/settings/profile").unwrap().value,
+            &4
+        );
+        // Falls back all the way to the root wildcard
+        assert_eq!(
+            router.route("/users/you/other").unwrap().param("any"),
+            Some("users/you/other")
+        );
+    }
+
+    #[test]
+    fn replace_and_remove() {
+        let mut router = PathRouter::new();
+        assert_eq!(router.insert("/a/:x", 1), None);
+        assert_eq!(router.insert("a/:y/", 2), Some(1));
+        assert_eq!(router.route("/a/b").unwrap().param("y"), Some("b"));
+        assert_eq!(router.remove("/a/:z"), Some(2));
+        assert!(router.route("/a/b").is_none());
+    }
+}
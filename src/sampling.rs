@@ -0,0 +1,297 @@
+use std::cmp::Ordering;
+
+use crate::heap::Heap;
+
+/// A uniform random sample of up to `k` items from a stream of unknown length,
+/// using Vitter's Algorithm R. After `n` items have been observed, each one is in the
+/// sample with probability `k / n`.
+#[derive(Debug, Clone)]
+pub struct Reservoir<T> {
+    samples: Vec<T>,
+    k: usize,
+    seen: u64,
+    rng: Rng,
+}
+
+impl<T> Reservoir<T> {
+    pub fn new(k: usize) -> Self {
+        Reservoir {
+            samples: Vec::with_capacity(k),
+            k,
+            seen: 0,
+            rng: Rng::new(),
+        }
+    }
+
+    pub fn reseed(&mut self, seed: u64) {
+        self.rng.reseed(seed);
+    }
+
+    pub fn observe(&mut self, item: T) {
+        self.seen += 1;
+        if self.samples.len() < self.k {
+            self.samples.push(item);
+            return;
+        }
+        let j = self.rng.next_u64() % self.seen;
+        if let Some(slot) = self.samples.get_mut(j as usize) {
+            *slot = item;
+        }
+    }
+
+    pub fn samples(&self) -> &[T] {
+        &self.samples
+    }
+
+    pub fn into_samples(self) -> Vec<T> {
+        self.samples
+    }
+
+    /// The number of items observed so far.
+    pub fn seen(&self) -> u64 {
+        self.seen
+    }
+
+    /// The number of items in the sample, which is at most `k`.
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.samples.clear();
+        self.seen = 0;
+    }
+}
+
+impl<T> Extend<T> for Reservoir<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for item in iter {
+            self.observe(item);
+        }
+    }
+}
+
+/// A weighted random sample of up to `k` items from a stream, without replacement, using
+/// the Efraimidis-Spirakis A-Res algorithm. Each item draws a key of `u^(1 / weight)` for
+/// a uniform `u`, and the items with the `k` largest keys are kept in a heap.
+#[derive(Debug, Clone)]
+pub struct WeightedReservoir<T> {
+    samples: Heap<Keyed<T>>,
+    k: usize,
+    seen: u64,
+    rng: Rng,
+}
+
+#[derive(Debug, Clone)]
+struct Keyed<T> {
+    // The logarithm of the key, which orders the same way and doesn't underflow
+    key: f64,
+    item: T,
+}
+
+impl<T> PartialEq for Keyed<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<T> Eq for Keyed<T> {}
+
+impl<T> PartialOrd for Keyed<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for Keyed<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key.total_cmp(&other.key)
+    }
+}
+
+impl<T> WeightedReservoir<T> {
+    pub fn new(k: usize) -> Self {
+        WeightedReservoir {
+            samples: Heap::new(),
+            k,
+            seen: 0,
+            rng: Rng::new(),
+        }
+    }
+
+    pub fn reseed(&mut self, seed: u64) {
+        self.rng.reseed(seed);
+    }
+
+    /// Observes an item with the given weight. Items with zero weight are never sampled.
+    /// Panics if the weight is negative or not finite.
+    pub fn observe(&mut self, item: T, weight: f64) {
+        assert!(
+            weight.is_finite() && weight >= 0.0,
+            "weights must be finite and non-negative"
+        );
+        self.seen += 1;
+        if weight == 0.0 || self.k == 0 {
+            return;
+        }
+        let key = self.rng.next_f64().ln() / weight;
+        if self.samples.size() == self.k {
+            if self.samples.peek().is_some_and(|min| min.key >= key) {
+                return;
+            }
+            self.samples.pop();
+        }
+        self.samples.push(Keyed { key, item });
+    }
+
+    /// Returns the sampled items in an arbitrary order.
+    pub fn samples(&self) -> impl Iterator<Item = &T> {
+        self.samples.iter().map(|k| &k.item)
+    }
+
+    pub fn into_samples(self) -> Vec<T> {
+        let mut samples = self.samples;
+        std::iter::from_fn(|| samples.pop())
+            .map(|k| k.item)
+            .collect()
+    }
+
+    /// The number of items observed so far.
+    pub fn seen(&self) -> u64 {
+        self.seen
+    }
+
+    /// The number of items in the sample, which is at most `k`.
+    pub fn len(&self) -> usize {
+        self.samples.size()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.size() == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.samples = Heap::new();
+        self.seen = 0;
+    }
+}
+
+impl<T> Extend<(T, f64)> for WeightedReservoir<T> {
+    fn extend<I: IntoIterator<Item = (T, f64)>>(&mut self, iter: I) {
+        for (item, weight) in iter {
+            self.observe(item, weight);
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Rng(u64);
+
+impl Rng {
+    fn new() -> Self {
+        Rng(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn reseed(&mut self, seed: u64) {
+        // Scramble the seed with splitmix64, since xorshift's first outputs for small seeds
+        // are small too
+        let mut z = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        // xorshift gets stuck at zero
+        self.0 = (z ^ (z >> 31)).max(1);
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        // xorshift64
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// Returns a uniform float in `(0, 1]`.
+    fn next_f64(&mut self) -> f64 {
+        ((self.next_u64() >> 11) + 1) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use quickcheck::quickcheck;
+
+    use super::{Reservoir, WeightedReservoir};
+
+    #[test]
+    fn fills_then_replaces() {
+        let mut reservoir = Reservoir::new(3);
+        reservoir.extend([1, 2]);
+        assert_eq!(reservoir.samples(), &[1, 2]);
+        reservoir.extend(3..1000);
+        assert_eq!(reservoir.len(), 3);
+        assert_eq!(reservoir.seen(), 999);
+        assert_ne!(reservoir.samples(), &[1, 2, 3]);
+    }
+
+    #[test]
+    fn uniform() {
+        let mut counts = [0; 10];
+        let mut reservoir = Reservoir::new(2);
+        for seed in 1..=5000 {
+            reservoir.clear();
+            reservoir.reseed(seed);
+            reservoir.extend(0..10);
+            for &s in reservoir.samples() {
+                counts[s] += 1;
+            }
+        }
+        // Each item is expected 1000 times
+        assert!(
+            counts.iter().all(|&c| (800..1200).contains(&c)),
+            "{:?}",
+            counts
+        );
+    }
+
+    #[test]
+    fn weighted() {
+        let mut heavy = 0;
+        let mut reservoir = WeightedReservoir::new(1);
+        for seed in 1..=2000 {
+            reservoir.clear();
+            reservoir.reseed(seed);
+            reservoir.extend([("light", 1.0), ("heavy", 3.0), ("never", 0.0)]);
+            if reservoir.samples().next() == Some(&"heavy") {
+                heavy += 1;
+            }
+        }
+        // The heavy item is expected to be chosen three times in four
+        assert!((1350..1650).contains(&heavy), "{}", heavy);
+    }
+
+    #[test]
+    fn prop_samples_come_from_stream() {
+        fn p(items: Vec<u16>, k: u8) -> bool {
+            let k = k as usize % 8;
+            let mut reservoir = Reservoir::new(k);
+            let mut weighted = WeightedReservoir::new(k);
+            for &i in items.iter() {
+                reservoir.observe(i);
+                weighted.observe(i, i as f64);
+            }
+            let nonzero = items.iter().filter(|&&i| i != 0).count();
+            reservoir.len() == k.min(items.len())
+                && weighted.len() == k.min(nonzero)
+                && reservoir.samples().iter().all(|s| items.contains(s))
+                && weighted
+                    .into_samples()
+                    .iter()
+                    .all(|s| *s != 0 && items.contains(s))
+        }
+        quickcheck(p as fn(Vec<u16>, u8) -> bool)
+    }
+}
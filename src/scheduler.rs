@@ -0,0 +1,207 @@
+use std::collections::VecDeque;
+
+const MAX_LEVELS: usize = u64::BITS as usize;
+
+/// A priority queue with a fixed number of priority levels, up to 64, which is FIFO within
+/// each level. A bit mask of non-empty levels makes `push` and `pop` constant time.
+/// Time is measured in pops, and entries which have waited too long can be promoted
+/// with `age` or `age_with` to keep low priorities from starving.
+#[derive(Debug, Clone)]
+pub struct PriorityBuckets<T> {
+    levels: Vec<VecDeque<Queued<T>>>,
+    // Bit `i` is set when level `i` is non-empty
+    occupied: u64,
+    len: usize,
+    ticks: u64,
+}
+
+#[derive(Debug, Clone)]
+struct Queued<T> {
+    item: T,
+    // The number of pops before the item entered its level
+    since: u64,
+}
+
+impl<T> PriorityBuckets<T> {
+    /// Creates a queue with priorities `0..levels`, where higher numbers are popped first.
+    /// Panics if `levels` is zero or more than 64.
+    pub fn new(levels: usize) -> Self {
+        assert!(
+            (1..=MAX_LEVELS).contains(&levels),
+            "between 1 and 64 levels are supported"
+        );
+        PriorityBuckets {
+            levels: (0..levels).map(|_| VecDeque::new()).collect(),
+            occupied: 0,
+            len: 0,
+            ticks: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The number of priority levels.
+    pub fn levels(&self) -> usize {
+        self.levels.len()
+    }
+
+    /// The number of items waiting at the given priority.
+    pub fn len_at(&self, priority: usize) -> usize {
+        self.levels[priority].len()
+    }
+
+    /// Panics if the priority is not less than the number of levels.
+    pub fn push(&mut self, priority: usize, item: T) {
+        self.levels[priority].push_back(Queued {
+            item,
+            since: self.ticks,
+        });
+        self.occupied |= 1 << priority;
+        self.len += 1;
+    }
+
+    /// Removes the oldest item of the highest non-empty priority, along with its priority.
+    pub fn pop(&mut self) -> Option<(usize, T)> {
+        let priority = self.highest()?;
+        let queued = self.levels[priority].pop_front().unwrap();
+        if self.levels[priority].is_empty() {
+            self.occupied &= !(1 << priority);
+        }
+        self.len -= 1;
+        self.ticks += 1;
+        Some((priority, queued.item))
+    }
+
+    pub fn peek(&self) -> Option<(usize, &T)> {
+        let priority = self.highest()?;
+        Some((priority, &self.levels[priority].front()?.item))
+    }
+
+    /// Promotes items which have waited at least `max_wait` pops at their level by one level,
+    /// returning how many were promoted.
+    pub fn age(&mut self, max_wait: u64) -> usize {
+        self.age_with(|_, _, waited| waited >= max_wait)
+    }
+
+    /// Promotes items by one level while `f`, given each item, its priority and how many
+    /// pops it has waited at that level, returns true. Only the oldest items of each level
+    /// are considered, so `f` should return true for an item if it would for any older one.
+    /// A promoted item joins the back of its new level and its wait starts over.
+    pub fn age_with<F: FnMut(&T, usize, u64) -> bool>(&mut self, mut f: F) -> usize {
+        let mut promoted = 0;
+        // Going from the top down means an item is promoted at most once per call
+        for priority in (0..self.levels.len() - 1).rev() {
+            while let Some(front) = self.levels[priority].front() {
+                if !f(&front.item, priority, self.ticks - front.since) {
+                    break;
+                }
+                let mut queued = self.levels[priority].pop_front().unwrap();
+                queued.since = self.ticks;
+                self.levels[priority + 1].push_back(queued);
+                self.occupied |= 1 << (priority + 1);
+                promoted += 1;
+            }
+            if self.levels[priority].is_empty() {
+                self.occupied &= !(1 << priority);
+            }
+        }
+        promoted
+    }
+
+    pub fn clear(&mut self) {
+        self.levels.iter_mut().for_each(VecDeque::clear);
+        self.occupied = 0;
+        self.len = 0;
+    }
+
+    fn highest(&self) -> Option<usize> {
+        (self.occupied != 0).then(|| MAX_LEVELS - 1 - self.occupied.leading_zeros() as usize)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use quickcheck::quickcheck;
+
+    use super::PriorityBuckets;
+
+    #[test]
+    fn highest_first() {
+        let mut queue = PriorityBuckets::new(3);
+        queue.push(0, 'a');
+        queue.push(2, 'b');
+        queue.push(1, 'c');
+        queue.push(2, 'd');
+        assert_eq!(queue.peek(), Some((2, &'b')));
+        assert_eq!(queue.pop(), Some((2, 'b')));
+        assert_eq!(queue.pop(), Some((2, 'd')));
+        assert_eq!(queue.pop(), Some((1, 'c')));
+        assert_eq!(queue.pop(), Some((0, 'a')));
+        assert_eq!(queue.pop(), None);
+    }
+
+    #[test]
+    fn aging() {
+        let mut queue = PriorityBuckets::new(3);
+        queue.push(0, "old");
+        for _ in 0..2 {
+            queue.push(2, "busy");
+            queue.pop();
+        }
+        assert_eq!(queue.age(3), 0);
+        assert_eq!(queue.age(2), 1);
+        assert_eq!(queue.len_at(1), 1);
+        // The wait starts over at the new level
+        assert_eq!(queue.age(2), 0);
+        queue.push(2, "busy");
+        queue.push(2, "busy");
+        queue.pop();
+        queue.pop();
+        assert_eq!(queue.age(2), 1);
+        assert_eq!(queue.pop(), Some((2, "old")));
+    }
+
+    #[test]
+    #[should_panic]
+    fn too_many_levels() {
+        PriorityBuckets::<()>::new(65);
+    }
+
+    #[test]
+    fn prop_matches_sorted_model() {
+        fn p(ops: Vec<Option<u8>>) -> bool {
+            let mut queue = PriorityBuckets::new(64);
+            let mut model = vec![];
+            for (i, op) in ops.into_iter().enumerate() {
+                let agree = match op {
+                    Some(priority) => {
+                        let priority = priority as usize % 64;
+                        queue.push(priority, i);
+                        model.push((priority, i));
+                        true
+                    }
+                    None => {
+                        // The highest priority, breaking ties by insertion order
+                        let best = model
+                            .iter()
+                            .enumerate()
+                            .max_by_key(|(_, &(p, i))| (p, std::cmp::Reverse(i)))
+                            .map(|(j, _)| j);
+                        queue.pop() == best.map(|j| model.remove(j))
+                    }
+                };
+                if !agree || queue.len() != model.len() {
+                    return false;
+                }
+            }
+            true
+        }
+        quickcheck(p as fn(Vec<Option<u8>>) -> bool)
+    }
+}
@@ -0,0 +1,220 @@
+//! Sorted iterators which can skip ahead to a key, and a leapfrog join which uses them to
+//! intersect any number of sorted sources without visiting every element.
+
+use std::{
+    fmt::{self, Debug},
+    iter::FusedIterator,
+};
+
+/// An iterator over elements in ascending order of their keys, which can skip ahead to a
+/// key in sublinear time.
+pub trait Seekable: Iterator {
+    type Key: Ord + ?Sized;
+
+    /// The key which an element is ordered by.
+    fn key_of(item: &Self::Item) -> &Self::Key;
+
+    /// Skips ahead so that the next element is the first whose key is at least `key`.
+    /// The iterator never moves backwards, so seeking to a key behind it does nothing.
+    fn seek(&mut self, key: &Self::Key);
+}
+
+/// An iterator over the keys present in every input, which yields the element with that
+/// key from each input. The inputs take turns seeking to the largest key any of them is
+/// at, so runs of keys missing from one input are skipped in the others.
+pub struct LeapfrogJoin<I> {
+    iters: Vec<I>,
+    done: bool,
+}
+
+impl<I: Debug> Debug for LeapfrogJoin<I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LeapfrogJoin")
+            .field("iters", &self.iters)
+            .finish()
+    }
+}
+
+/// Intersects sorted inputs whose keys are distinct within each input.
+pub fn leapfrog_join<I: Seekable>(iters: impl IntoIterator<Item = I>) -> LeapfrogJoin<I> {
+    let iters = iters.into_iter().collect::<Vec<_>>();
+    LeapfrogJoin {
+        done: iters.is_empty(),
+        iters,
+    }
+}
+
+impl<I: Seekable> Iterator for LeapfrogJoin<I> {
+    type Item = Vec<I::Item>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let heads = self.iters.iter_mut().map(Iterator::next).collect();
+        let Some(mut heads) = heads else {
+            self.done = true;
+            return None;
+        };
+        let heads: &mut Vec<I::Item> = &mut heads;
+        let mut max = (0..heads.len())
+            .max_by(|&a, &b| I::key_of(&heads[a]).cmp(I::key_of(&heads[b])))
+            .unwrap();
+        // Bring every input up to the largest key, starting over whenever one overshoots
+        let mut i = 0;
+        while i < heads.len() {
+            if i == max || I::key_of(&heads[i]) == I::key_of(&heads[max]) {
+                i += 1;
+                continue;
+            }
+            let (head, target) = pair_mut(heads, i, max);
+            self.iters[i].seek(I::key_of(target));
+            match self.iters[i].next() {
+                Some(next) => *head = next,
+                None => {
+                    self.done = true;
+                    return None;
+                }
+            }
+            if I::key_of(&heads[i]) > I::key_of(&heads[max]) {
+                max = i;
+                i = 0;
+            } else {
+                i += 1;
+            }
+        }
+        Some(std::mem::take(heads))
+    }
+}
+
+impl<I: Seekable> FusedIterator for LeapfrogJoin<I> {}
+
+/// Borrows the `i`th element mutably and the `j`th immutably, where `i != j`.
+fn pair_mut<T>(items: &mut [T], i: usize, j: usize) -> (&mut T, &T) {
+    if i < j {
+        let (left, right) = items.split_at_mut(j);
+        (&mut left[i], &right[0])
+    } else {
+        let (left, right) = items.split_at_mut(i);
+        (&mut right[0], &left[j])
+    }
+}
+
+/// Checks that the iterator yields the same keys as a cursor over `expected`, where each op
+/// either seeks to a key or steps to the next element.
+#[cfg(test)]
+pub(crate) fn seeks_match<I>(mut iter: I, expected: &[I::Key], ops: &[Option<I::Key>]) -> bool
+where
+    I: Seekable,
+    I::Key: Sized,
+{
+    let mut pos = 0;
+    let steps_match = ops.iter().all(|op| match op {
+        Some(key) => {
+            iter.seek(key);
+            pos = pos.max(expected.partition_point(|k| k < key));
+            true
+        }
+        None => {
+            let next = iter.next();
+            let matches = next.as_ref().map(I::key_of) == expected.get(pos);
+            pos = (pos + 1).min(expected.len());
+            matches
+        }
+    });
+    let mut rest = expected[pos..].iter();
+    steps_match && iter.all(|item| Some(I::key_of(&item)) == rest.next()) && rest.next().is_none()
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeSet;
+
+    use quickcheck::quickcheck;
+
+    use super::{leapfrog_join, seeks_match, Seekable};
+
+    /// Seeks through a sorted slice by binary search, counting the elements it yields.
+    struct Sorted<'a> {
+        items: &'a [u8],
+        yielded: usize,
+    }
+
+    impl<'a> Iterator for Sorted<'a> {
+        type Item = &'a u8;
+
+        fn next(&mut self) -> Option<&'a u8> {
+            let (first, rest) = self.items.split_first()?;
+            self.items = rest;
+            self.yielded += 1;
+            Some(first)
+        }
+    }
+
+    impl Seekable for Sorted<'_> {
+        type Key = u8;
+
+        fn key_of<'i>(item: &'i &u8) -> &'i u8 {
+            item
+        }
+
+        fn seek(&mut self, key: &u8) {
+            let skip = self.items.partition_point(|x| x < key);
+            self.items = &self.items[skip..];
+        }
+    }
+
+    fn sorted(items: &[u8]) -> Sorted<'_> {
+        Sorted { items, yielded: 0 }
+    }
+
+    #[test]
+    fn skips_missing_runs() {
+        let dense = (0..=200).collect::<Vec<_>>();
+        let sparse = [3, 100, 150, 201];
+        let mut join = leapfrog_join([sorted(&dense), sorted(&sparse)]);
+        assert_eq!(join.next(), Some(vec![&3, &3]));
+        assert_eq!(join.next(), Some(vec![&100, &100]));
+        assert_eq!(join.next(), Some(vec![&150, &150]));
+        assert_eq!(join.next(), None);
+        assert_eq!(join.next(), None);
+        assert!(join.iters[0].yielded < 10);
+        assert_eq!(leapfrog_join(Vec::<Sorted>::new()).next(), None);
+    }
+
+    #[test]
+    fn prop_intersects() {
+        fn p(sets: Vec<BTreeSet<u8>>) -> bool {
+            let sets = sets.into_iter().take(4).collect::<Vec<_>>();
+            let items = sets
+                .iter()
+                .map(|s| s.iter().copied().collect::<Vec<_>>())
+                .collect::<Vec<_>>();
+            let joined = leapfrog_join(items.iter().map(|s| sorted(s)))
+                .map(|heads| {
+                    assert!(heads.windows(2).all(|w| w[0] == w[1]));
+                    *heads[0]
+                })
+                .collect::<Vec<_>>();
+            let expected = match sets.split_first() {
+                Some((first, rest)) => first
+                    .iter()
+                    .copied()
+                    .filter(|x| rest.iter().all(|s| s.contains(x)))
+                    .collect(),
+                None => vec![],
+            };
+            joined == expected
+        }
+        quickcheck(p as fn(Vec<BTreeSet<u8>>) -> bool);
+    }
+
+    #[test]
+    fn prop_seeks() {
+        fn p(items: BTreeSet<u8>, ops: Vec<Option<u8>>) -> bool {
+            let items = items.into_iter().collect::<Vec<_>>();
+            seeks_match(sorted(&items), &items, &ops)
+        }
+        quickcheck(p as fn(BTreeSet<u8>, Vec<Option<u8>>) -> bool);
+    }
+}
@@ -0,0 +1,336 @@
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+/// A Count-Min sketch estimates the frequency of items in a stream using sublinear space.
+/// Estimates never undercount, and with probability `1 - delta` they overcount by at most
+/// `epsilon` times the total count. Hashing is deterministic, so sketches with the same
+/// dimensions built on different shards can be merged.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CountMinSketch {
+    width: usize,
+    depth: usize,
+    counters: Vec<u64>,
+    total: u64,
+}
+
+impl CountMinSketch {
+    /// Creates a sketch whose estimates are within `epsilon * total` of the true
+    /// count with probability at least `1 - delta`.
+    pub fn new(epsilon: f64, delta: f64) -> Self {
+        assert!(epsilon > 0.0 && epsilon < 1.0, "epsilon must be in (0, 1)");
+        assert!(delta > 0.0 && delta < 1.0, "delta must be in (0, 1)");
+        let width = (std::f64::consts::E / epsilon).ceil() as usize;
+        let depth = (1.0 / delta).ln().ceil().max(1.0) as usize;
+        Self::with_dimensions(width, depth)
+    }
+
+    pub fn with_dimensions(width: usize, depth: usize) -> Self {
+        assert!(width > 0 && depth > 0, "dimensions must be positive");
+        CountMinSketch {
+            width,
+            depth,
+            counters: vec![0; width * depth],
+            total: 0,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// The sum of all counts added to the sketch.
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// The additive error bound relative to the total count.
+    pub fn epsilon(&self) -> f64 {
+        std::f64::consts::E / self.width as f64
+    }
+
+    /// The probability that an estimate exceeds the error bound.
+    pub fn delta(&self) -> f64 {
+        (-(self.depth as f64)).exp()
+    }
+
+    pub fn add<T: Hash + ?Sized>(&mut self, item: &T, count: u64) {
+        for i in indices(self.width, self.depth, item) {
+            self.counters[i] = self.counters[i].saturating_add(count);
+        }
+        self.total = self.total.saturating_add(count);
+    }
+
+    pub fn estimate<T: Hash + ?Sized>(&self, item: &T) -> u64 {
+        indices(self.width, self.depth, item)
+            .map(|i| self.counters[i])
+            .min()
+            .unwrap_or(0)
+    }
+
+    /// Adds the counts of another sketch into this one.
+    /// Both sketches must have the same dimensions.
+    pub fn merge(&mut self, other: &CountMinSketch) {
+        assert!(
+            self.width == other.width && self.depth == other.depth,
+            "cannot merge sketches with different dimensions"
+        );
+        for (a, b) in self.counters.iter_mut().zip(other.counters.iter()) {
+            *a = a.saturating_add(*b);
+        }
+        self.total = self.total.saturating_add(other.total);
+    }
+
+    /// Halves every count, so that items which are no longer seen fade out.
+    pub fn halve(&mut self) {
+        self.counters.iter_mut().for_each(|c| *c /= 2);
+        self.total /= 2;
+    }
+
+    pub fn clear(&mut self) {
+        self.counters.iter_mut().for_each(|c| *c = 0);
+        self.total = 0;
+    }
+}
+
+/// Returns the counter index for the item in each row, using double hashing.
+fn indices<T: Hash + ?Sized>(width: usize, depth: usize, item: &T) -> impl Iterator<Item = usize> {
+    let (h1, h2) = hash_pair(item);
+    let width = width as u64;
+    (0..depth as u64)
+        .map(move |row| (row * width + h1.wrapping_add(row.wrapping_mul(h2)) % width) as usize)
+}
+
+/// Splits the item's hash into the two hashes that double hashing combines.
+fn hash_pair<T: Hash + ?Sized>(item: &T) -> (u64, u64) {
+    let mut hasher = DefaultHasher::new();
+    item.hash(&mut hasher);
+    let hash = hasher.finish();
+    (hash as u32 as u64, (hash >> 32) | 1)
+}
+
+/// A Bloom filter is a probabilistic set which never reports a false negative, and reports
+/// a false positive at a rate set by its size. It cannot remove items. Like
+/// `CountMinSketch`, hashing is deterministic, so filters with the same dimensions can be
+/// merged.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BloomFilter {
+    bits: Vec<u64>,
+    num_bits: usize,
+    num_hashes: usize,
+    len: usize,
+}
+
+impl BloomFilter {
+    /// Creates a filter whose false positive rate stays below `false_positive_rate` until
+    /// `capacity` items have been added.
+    pub fn new(capacity: usize, false_positive_rate: f64) -> Self {
+        assert!(
+            false_positive_rate > 0.0 && false_positive_rate < 1.0,
+            "false positive rate must be in (0, 1)"
+        );
+        let ln2 = std::f64::consts::LN_2;
+        let capacity = capacity.max(1) as f64;
+        let num_bits = (-capacity * false_positive_rate.ln() / (ln2 * ln2)).ceil() as usize;
+        let num_hashes = (num_bits as f64 / capacity * ln2).round().max(1.0) as usize;
+        BloomFilter::with_dimensions(num_bits, num_hashes)
+    }
+
+    pub fn with_dimensions(num_bits: usize, num_hashes: usize) -> Self {
+        assert!(
+            num_bits > 0 && num_hashes > 0,
+            "dimensions must be positive"
+        );
+        BloomFilter {
+            bits: vec![0; num_bits.div_ceil(64)],
+            num_bits,
+            num_hashes,
+            len: 0,
+        }
+    }
+
+    pub fn num_bits(&self) -> usize {
+        self.num_bits
+    }
+
+    pub fn num_hashes(&self) -> usize {
+        self.num_hashes
+    }
+
+    /// The number of items added, counting repeats.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The expected false positive rate with the number of items added so far.
+    pub fn false_positive_rate(&self) -> f64 {
+        let k = self.num_hashes as f64;
+        (1.0 - (-k * self.len as f64 / self.num_bits as f64).exp()).powf(k)
+    }
+
+    pub fn insert<T: Hash + ?Sized>(&mut self, item: &T) {
+        for i in self.bit_indices(item) {
+            self.bits[i / 64] |= 1 << (i % 64);
+        }
+        self.len += 1;
+    }
+
+    /// Whether the item may have been added. `false` means it definitely was not.
+    pub fn contains<T: Hash + ?Sized>(&self, item: &T) -> bool {
+        self.bit_indices(item)
+            .all(|i| self.bits[i / 64] & (1 << (i % 64)) != 0)
+    }
+
+    /// Adds the items of another filter into this one.
+    /// Both filters must have the same dimensions.
+    pub fn merge(&mut self, other: &BloomFilter) {
+        assert!(
+            self.num_bits == other.num_bits && self.num_hashes == other.num_hashes,
+            "cannot merge filters with different dimensions"
+        );
+        for (a, b) in self.bits.iter_mut().zip(other.bits.iter()) {
+            *a |= b;
+        }
+        self.len += other.len;
+    }
+
+    pub fn clear(&mut self) {
+        self.bits.iter_mut().for_each(|w| *w = 0);
+        self.len = 0;
+    }
+
+    fn bit_indices<T: Hash + ?Sized>(&self, item: &T) -> impl Iterator<Item = usize> {
+        let (h1, h2) = hash_pair(item);
+        let num_bits = self.num_bits as u64;
+        (0..self.num_hashes as u64)
+            .map(move |i| (h1.wrapping_add(i.wrapping_mul(h2)) % num_bits) as usize)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use quickcheck::quickcheck;
+
+    use super::{BloomFilter, CountMinSketch};
+
+    #[test]
+    fn dimensions() {
+        let sketch = CountMinSketch::new(0.01, 0.01);
+        assert_eq!(sketch.width(), 272);
+        assert_eq!(sketch.depth(), 5);
+        assert!(sketch.epsilon() <= 0.01);
+        assert!(sketch.delta() <= 0.01);
+    }
+
+    #[test]
+    fn estimate_exact_when_sparse() {
+        let mut sketch = CountMinSketch::new(0.001, 0.01);
+        sketch.add("foo", 3);
+        sketch.add("bar", 1);
+        sketch.add("foo", 2);
+        assert_eq!(sketch.estimate("foo"), 5);
+        assert_eq!(sketch.estimate("bar"), 1);
+        assert_eq!(sketch.total(), 6);
+    }
+
+    #[test]
+    fn merge_shards() {
+        let mut a = CountMinSketch::with_dimensions(100, 4);
+        let mut b = CountMinSketch::with_dimensions(100, 4);
+        a.add(&1, 10);
+        b.add(&1, 5);
+        b.add(&2, 7);
+        a.merge(&b);
+        assert_eq!(a.estimate(&1), 15);
+        assert_eq!(a.estimate(&2), 7);
+        assert_eq!(a.total(), 22);
+    }
+
+    #[test]
+    fn halve() {
+        let mut sketch = CountMinSketch::with_dimensions(100, 4);
+        sketch.add("foo", 5);
+        sketch.add("bar", 1);
+        sketch.halve();
+        assert_eq!(sketch.estimate("foo"), 2);
+        assert_eq!(sketch.estimate("bar"), 0);
+        assert_eq!(sketch.total(), 3);
+    }
+
+    #[test]
+    #[should_panic]
+    fn merge_mismatched() {
+        let mut a = CountMinSketch::with_dimensions(100, 4);
+        a.merge(&CountMinSketch::with_dimensions(50, 4));
+    }
+
+    #[test]
+    fn prop_never_undercounts() {
+        fn p(input: Vec<u8>) -> bool {
+            let mut sketch = CountMinSketch::with_dimensions(16, 3);
+            let mut counts = HashMap::new();
+            for i in input.iter() {
+                sketch.add(i, 1);
+                *counts.entry(i).or_insert(0) += 1;
+            }
+            counts.iter().all(|(i, c)| sketch.estimate(*i) >= *c)
+        }
+        quickcheck(p as fn(Vec<u8>) -> bool)
+    }
+
+    #[test]
+    fn bloom_dimensions() {
+        let filter = BloomFilter::new(1000, 0.01);
+        assert_eq!(filter.num_bits(), 9586);
+        assert_eq!(filter.num_hashes(), 7);
+        assert_eq!(filter.false_positive_rate(), 0.0);
+    }
+
+    #[test]
+    fn bloom_false_positive_rate() {
+        let mut filter = BloomFilter::new(1000, 0.01);
+        for i in 0..1000 {
+            filter.insert(&i);
+        }
+        assert!(filter.false_positive_rate() < 0.011);
+        let false_positives = (1000..101_000).filter(|i| filter.contains(i)).count();
+        assert!(
+            false_positives < 1500,
+            "{} false positives",
+            false_positives
+        );
+        filter.clear();
+        assert!(!filter.contains(&0) && filter.is_empty());
+    }
+
+    #[test]
+    fn bloom_merge() {
+        let mut a = BloomFilter::with_dimensions(256, 3);
+        let mut b = BloomFilter::with_dimensions(256, 3);
+        a.insert("foo");
+        b.insert("bar");
+        a.merge(&b);
+        assert!(a.contains("foo") && a.contains("bar"));
+        assert_eq!(a.len(), 2);
+    }
+
+    #[test]
+    fn prop_bloom_no_false_negatives() {
+        fn p(input: Vec<u32>) -> bool {
+            let mut filter = BloomFilter::with_dimensions(64, 3);
+            for i in input.iter() {
+                filter.insert(i);
+            }
+            input.iter().all(|i| filter.contains(i))
+        }
+        quickcheck(p as fn(Vec<u32>) -> bool)
+    }
+}
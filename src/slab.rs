@@ -0,0 +1,270 @@
+use std::ops::{Index, IndexMut};
+
+/// A handle to a value in a `SlotMap`. Keys remember the generation of the slot they
+/// were issued for, so a key to a removed value never refers to a later value in the same slot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Key {
+    index: u32,
+    generation: u32,
+}
+
+/// A slot map stores values in a vector and hands out generational keys to them.
+/// Insertion, lookup and removal are O(1), and vacated slots are reused.
+#[derive(Debug, Clone)]
+pub struct SlotMap<T> {
+    slots: Vec<Slot<T>>,
+    // The head of the list of vacant slots, threaded through the slots themselves
+    free: Option<u32>,
+    len: usize,
+}
+
+#[derive(Debug, Clone)]
+struct Slot<T> {
+    generation: u32,
+    entry: Entry<T>,
+}
+
+#[derive(Debug, Clone)]
+enum Entry<T> {
+    Occupied(T),
+    Vacant(Option<u32>),
+}
+
+impl<T> SlotMap<T> {
+    pub fn new() -> Self {
+        SlotMap {
+            slots: vec![],
+            free: None,
+            len: 0,
+        }
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        SlotMap {
+            slots: Vec::with_capacity(capacity),
+            free: None,
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn insert(&mut self, value: T) -> Key {
+        self.len += 1;
+        match self.free {
+            Some(index) => {
+                let slot = &mut self.slots[index as usize];
+                if let Entry::Vacant(next) = slot.entry {
+                    self.free = next;
+                }
+                slot.entry = Entry::Occupied(value);
+                Key {
+                    index,
+                    generation: slot.generation,
+                }
+            }
+            None => {
+                let index = u32::try_from(self.slots.len()).expect("slot map is full");
+                self.slots.push(Slot {
+                    generation: 0,
+                    entry: Entry::Occupied(value),
+                });
+                Key {
+                    index,
+                    generation: 0,
+                }
+            }
+        }
+    }
+
+    pub fn get(&self, key: Key) -> Option<&T> {
+        match self.slots.get(key.index as usize) {
+            Some(Slot {
+                generation,
+                entry: Entry::Occupied(value),
+            }) if *generation == key.generation => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn get_mut(&mut self, key: Key) -> Option<&mut T> {
+        match self.slots.get_mut(key.index as usize) {
+            Some(Slot {
+                generation,
+                entry: Entry::Occupied(value),
+            }) if *generation == key.generation => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn contains_key(&self, key: Key) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Removes the value for the key. The key, and every copy of it, becomes stale.
+    pub fn remove(&mut self, key: Key) -> Option<T> {
+        let slot = self.slots.get_mut(key.index as usize)?;
+        if slot.generation != key.generation || matches!(slot.entry, Entry::Vacant(_)) {
+            return None;
+        }
+        let entry = std::mem::replace(&mut slot.entry, Entry::Vacant(self.free));
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free = Some(key.index);
+        self.len -= 1;
+        match entry {
+            Entry::Occupied(value) => Some(value),
+            Entry::Vacant(_) => unreachable!(),
+        }
+    }
+
+    /// Removes every value. All existing keys become stale.
+    pub fn clear(&mut self) {
+        for index in 0..self.slots.len() {
+            let slot = &self.slots[index];
+            if let Entry::Occupied(_) = slot.entry {
+                self.remove(Key {
+                    index: index as u32,
+                    generation: slot.generation,
+                });
+            }
+        }
+    }
+
+    /// Returns an iterator over the keys and values, in slot order.
+    pub fn iter(&self) -> impl Iterator<Item = (Key, &T)> {
+        self.slots
+            .iter()
+            .enumerate()
+            .filter_map(|(index, slot)| match &slot.entry {
+                Entry::Occupied(value) => Some((
+                    Key {
+                        index: index as u32,
+                        generation: slot.generation,
+                    },
+                    value,
+                )),
+                Entry::Vacant(_) => None,
+            })
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (Key, &mut T)> {
+        self.slots
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(index, slot)| match &mut slot.entry {
+                Entry::Occupied(value) => Some((
+                    Key {
+                        index: index as u32,
+                        generation: slot.generation,
+                    },
+                    value,
+                )),
+                Entry::Vacant(_) => None,
+            })
+    }
+}
+
+impl<T> Default for SlotMap<T> {
+    fn default() -> Self {
+        SlotMap::new()
+    }
+}
+
+impl<T> Index<Key> for SlotMap<T> {
+    type Output = T;
+
+    fn index(&self, key: Key) -> &T {
+        self.get(key).expect("invalid slot map key")
+    }
+}
+
+impl<T> IndexMut<Key> for SlotMap<T> {
+    fn index_mut(&mut self, key: Key) -> &mut T {
+        self.get_mut(key).expect("invalid slot map key")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use quickcheck::quickcheck;
+
+    use super::SlotMap;
+
+    #[test]
+    fn insert_get_remove() {
+        let mut map = SlotMap::new();
+        let a = map.insert("a");
+        let b = map.insert("b");
+        assert_eq!(map.get(a), Some(&"a"));
+        assert_eq!(map[b], "b");
+        assert_eq!(map.remove(a), Some("a"));
+        assert_eq!(map.remove(a), None);
+        assert_eq!(map.len(), 1);
+    }
+
+    #[test]
+    fn stale_keys() {
+        let mut map = SlotMap::new();
+        let a = map.insert(1);
+        map.remove(a);
+        let b = map.insert(2);
+        // The slot is reused, but the old key doesn't alias the new value
+        assert_ne!(a, b);
+        assert_eq!(map.get(a), None);
+        assert_eq!(map.get(b), Some(&2));
+        map.clear();
+        assert_eq!(map.get(b), None);
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn iterate() {
+        let mut map = SlotMap::new();
+        let keys = (0..5).map(|i| map.insert(i)).collect::<Vec<_>>();
+        map.remove(keys[2]);
+        for (_, v) in map.iter_mut() {
+            *v *= 10;
+        }
+        assert_eq!(
+            map.iter().map(|(_, v)| *v).collect::<Vec<_>>(),
+            vec![0, 10, 30, 40]
+        );
+        assert!(map.iter().all(|(k, _)| keys.contains(&k)));
+    }
+
+    #[test]
+    fn prop_matches_hash_map() {
+        // Each operation either inserts a value or removes the nth key ever issued
+        fn p(ops: Vec<Result<u32, u8>>) -> bool {
+            let mut map = SlotMap::new();
+            let mut model = HashMap::new();
+            let mut issued = vec![];
+            for op in ops {
+                match op {
+                    Ok(v) => {
+                        let key = map.insert(v);
+                        model.insert(key, v);
+                        issued.push(key);
+                    }
+                    Err(i) if !issued.is_empty() => {
+                        let key = issued[i as usize % issued.len()];
+                        if map.remove(key) != model.remove(&key) {
+                            return false;
+                        }
+                    }
+                    Err(_) => {}
+                }
+            }
+            map.len() == model.len() && issued.iter().all(|k| map.get(*k) == model.get(k))
+        }
+        quickcheck(p as fn(Vec<Result<u32, u8>>) -> bool)
+    }
+}
@@ -0,0 +1,355 @@
+use std::{
+    fmt,
+    mem::MaybeUninit,
+    ops::{Deref, DerefMut},
+    ptr,
+};
+
+/// A vector which stores up to `N` elements inline, only allocating once it grows past that.
+/// Short sequences avoid a heap allocation and a pointer indirection.
+pub struct SmallVec<T, const N: usize> {
+    data: Data<T, N>,
+}
+
+enum Data<T, const N: usize> {
+    // Only the first `len` elements of the buffer are initialized
+    Inline {
+        buf: [MaybeUninit<T>; N],
+        len: usize,
+    },
+    Heap(Vec<T>),
+}
+
+impl<T, const N: usize> SmallVec<T, N> {
+    pub fn new() -> Self {
+        SmallVec {
+            data: Data::Inline {
+                buf: [const { MaybeUninit::uninit() }; N],
+                len: 0,
+            },
+        }
+    }
+
+    /// Creates a vector with room for `capacity` elements, allocating if that is more than `N`.
+    pub fn with_capacity(capacity: usize) -> Self {
+        if capacity <= N {
+            SmallVec::new()
+        } else {
+            SmallVec {
+                data: Data::Heap(Vec::with_capacity(capacity)),
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match &self.data {
+            Data::Inline { len, .. } => *len,
+            Data::Heap(v) => v.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        match &self.data {
+            Data::Inline { .. } => N,
+            Data::Heap(v) => v.capacity(),
+        }
+    }
+
+    /// Whether the elements have moved to the heap.
+    pub fn spilled(&self) -> bool {
+        matches!(self.data, Data::Heap(_))
+    }
+
+    pub fn push(&mut self, value: T) {
+        match &mut self.data {
+            Data::Inline { buf, len } if *len < N => {
+                buf[*len].write(value);
+                *len += 1;
+            }
+            Data::Inline { .. } => {
+                self.spill(N.max(1) * 2);
+                self.push(value);
+            }
+            Data::Heap(v) => v.push(value),
+        }
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        match &mut self.data {
+            Data::Inline { buf, len } => {
+                if *len == 0 {
+                    return None;
+                }
+                *len -= 1;
+                // The element is now past the end, so it won't be read or dropped again
+                Some(unsafe { buf[*len].assume_init_read() })
+            }
+            Data::Heap(v) => v.pop(),
+        }
+    }
+
+    /// Inserts an element at `index`, shifting later elements to the right.
+    pub fn insert(&mut self, index: usize, value: T) {
+        let length = self.len();
+        assert!(index <= length, "insertion index out of bounds");
+        match &mut self.data {
+            Data::Inline { buf, len } if *len < N => unsafe {
+                let p = buf.as_mut_ptr().add(index);
+                ptr::copy(p, p.add(1), length - index);
+                (*p).write(value);
+                *len += 1;
+            },
+            Data::Inline { .. } => {
+                self.spill(N.max(1) * 2);
+                self.insert(index, value);
+            }
+            Data::Heap(v) => v.insert(index, value),
+        }
+    }
+
+    /// Removes the element at `index`, shifting later elements to the left.
+    pub fn remove(&mut self, index: usize) -> T {
+        let length = self.len();
+        assert!(index < length, "removal index out of bounds");
+        match &mut self.data {
+            Data::Inline { buf, len } => unsafe {
+                let p = buf.as_mut_ptr().add(index);
+                let value = (*p).assume_init_read();
+                ptr::copy(p.add(1), p, length - index - 1);
+                *len -= 1;
+                value
+            },
+            Data::Heap(v) => v.remove(index),
+        }
+    }
+
+    /// Shortens the vector to `new_len` elements, dropping the rest.
+    pub fn truncate(&mut self, new_len: usize) {
+        while self.len() > new_len {
+            self.pop();
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.truncate(0);
+    }
+
+    pub fn as_slice(&self) -> &[T] {
+        match &self.data {
+            Data::Inline { buf, len } => unsafe {
+                std::slice::from_raw_parts(buf.as_ptr() as *const T, *len)
+            },
+            Data::Heap(v) => v,
+        }
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        match &mut self.data {
+            Data::Inline { buf, len } => unsafe {
+                std::slice::from_raw_parts_mut(buf.as_mut_ptr() as *mut T, *len)
+            },
+            Data::Heap(v) => v,
+        }
+    }
+
+    pub fn into_vec(mut self) -> Vec<T> {
+        if !self.spilled() {
+            self.spill(self.len());
+        }
+        match std::mem::replace(&mut self.data, Data::Heap(vec![])) {
+            Data::Heap(v) => v,
+            Data::Inline { .. } => unreachable!(),
+        }
+    }
+
+    /// Moves the inline elements into a heap allocation with the given capacity.
+    fn spill(&mut self, capacity: usize) {
+        let mut heap = Vec::with_capacity(capacity);
+        if let Data::Inline { buf, len } = &mut self.data {
+            for slot in &buf[..*len] {
+                heap.push(unsafe { slot.assume_init_read() });
+            }
+            // Ownership of the elements moved to the vector
+            *len = 0;
+        }
+        self.data = Data::Heap(heap);
+    }
+}
+
+impl<T, const N: usize> Drop for SmallVec<T, N> {
+    fn drop(&mut self) {
+        if let Data::Inline { buf, len } = &mut self.data {
+            for slot in &mut buf[..*len] {
+                unsafe { slot.assume_init_drop() };
+            }
+        }
+    }
+}
+
+impl<T, const N: usize> Default for SmallVec<T, N> {
+    fn default() -> Self {
+        SmallVec::new()
+    }
+}
+
+impl<T, const N: usize> Deref for SmallVec<T, N> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        self.as_slice()
+    }
+}
+
+impl<T, const N: usize> DerefMut for SmallVec<T, N> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        self.as_mut_slice()
+    }
+}
+
+impl<T: Clone, const N: usize> Clone for SmallVec<T, N> {
+    fn clone(&self) -> Self {
+        self.iter().cloned().collect()
+    }
+}
+
+impl<T: fmt::Debug, const N: usize> fmt::Debug for SmallVec<T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<T: PartialEq, const N: usize> PartialEq for SmallVec<T, N> {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_slice() == other.as_slice()
+    }
+}
+
+impl<T: Eq, const N: usize> Eq for SmallVec<T, N> {}
+
+impl<T, const N: usize> FromIterator<T> for SmallVec<T, N> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut v = SmallVec::new();
+        v.extend(iter);
+        v
+    }
+}
+
+impl<T, const N: usize> Extend<T> for SmallVec<T, N> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for value in iter {
+            self.push(value);
+        }
+    }
+}
+
+impl<'a, T, const N: usize> IntoIterator for &'a SmallVec<T, N> {
+    type Item = &'a T;
+    type IntoIter = std::slice::Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, T, const N: usize> IntoIterator for &'a mut SmallVec<T, N> {
+    type Item = &'a mut T;
+    type IntoIter = std::slice::IterMut<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+
+    use quickcheck::quickcheck;
+
+    use super::SmallVec;
+
+    #[test]
+    fn stays_inline() {
+        let mut v = SmallVec::<i32, 4>::new();
+        v.extend([1, 2, 3, 4]);
+        assert!(!v.spilled());
+        assert_eq!(&v[..], &[1, 2, 3, 4]);
+        v.push(5);
+        assert!(v.spilled());
+        assert_eq!(v.pop(), Some(5));
+        assert_eq!(v.len(), 4);
+    }
+
+    #[test]
+    fn insert_remove() {
+        let mut v = [1, 3].into_iter().collect::<SmallVec<_, 4>>();
+        v.insert(1, 2);
+        v.insert(0, 0);
+        assert_eq!(&v[..], &[0, 1, 2, 3]);
+        assert_eq!(v.remove(1), 1);
+        assert_eq!(&v[..], &[0, 2, 3]);
+        v.sort_by(|a, b| b.cmp(a));
+        assert_eq!(v.into_vec(), vec![3, 2, 0]);
+    }
+
+    #[test]
+    fn drops_elements() {
+        let rc = Rc::new(());
+        {
+            let mut v = SmallVec::<_, 2>::new();
+            v.push(rc.clone());
+            v.push(rc.clone());
+            assert_eq!(Rc::strong_count(&rc), 3);
+            v.truncate(1);
+            assert_eq!(Rc::strong_count(&rc), 2);
+        }
+        assert_eq!(Rc::strong_count(&rc), 1);
+    }
+
+    #[test]
+    fn zero_inline() {
+        let mut v = SmallVec::<i32, 0>::new();
+        v.push(1);
+        assert!(v.spilled());
+        assert_eq!(&v[..], &[1]);
+    }
+
+    #[test]
+    fn prop_matches_vec() {
+        // 0 and 2 push or insert, 1 and 3 pop or remove
+        fn p(ops: Vec<(u8, u8)>) -> bool {
+            let mut v = SmallVec::<u8, 3>::new();
+            let mut model = vec![];
+            for (op, x) in ops {
+                let agree = match op % 4 {
+                    0 => {
+                        v.push(x);
+                        model.push(x);
+                        true
+                    }
+                    1 => v.pop() == model.pop(),
+                    2 => {
+                        let i = x as usize % (model.len() + 1);
+                        v.insert(i, x);
+                        model.insert(i, x);
+                        true
+                    }
+                    _ if !model.is_empty() => {
+                        let i = x as usize % model.len();
+                        v.remove(i) == model.remove(i)
+                    }
+                    _ => true,
+                };
+                if !agree {
+                    return false;
+                }
+            }
+            v[..] == model[..]
+        }
+        quickcheck(p as fn(Vec<(u8, u8)>) -> bool)
+    }
+}
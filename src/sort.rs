@@ -0,0 +1,507 @@
+//! In-place slice sorts which never allocate and run in O(n log n) time even in the worst
+//! case, for callers that can't afford the allocations of the standard library's stable
+//! sort or the quadratic worst case of a naive quicksort. Neither sort is stable.
+//!
+//! With the `parallel` feature, `par_sort_by` is a stable sort spread across threads. Unlike
+//! the others it isn't in place, and takes O(n) scratch space.
+
+use std::cmp::Ordering;
+#[cfg(feature = "parallel")]
+use std::{num::NonZeroUsize, thread};
+
+use crate::heap::sift_down_by;
+
+// The shortest run worth handing to a thread of its own in `par_sort_by`
+#[cfg(feature = "parallel")]
+const MIN_PARALLEL_RUN: usize = 1 << 14;
+
+/// Sorts the slice in ascending order with a bottom-up heapsort, which makes at most about
+/// `1.5 n log2 n` comparisons, and close to `n log2 n` on average, using O(1) extra space.
+pub fn heapsort<T: Ord>(v: &mut [T]) {
+    heapsort_by(v, T::cmp);
+}
+
+/// Like `heapsort`, but orders the elements with `compare`.
+pub fn heapsort_by<T>(v: &mut [T], mut compare: impl FnMut(&T, &T) -> Ordering) {
+    let is_less = &mut |a: &T, b: &T| compare(a, b) == Ordering::Less;
+    for root in (0..v.len() / 2).rev() {
+        sift_down_by(v, root, is_less);
+    }
+    // Move the largest remaining element to the end of the shrinking heap
+    for end in (1..v.len()).rev() {
+        v.swap(0, end);
+        sift_down_by(&mut v[..end], 0, is_less);
+    }
+}
+
+/// Sorts the slice in ascending order with Dijkstra's smoothsort, which takes O(n log n)
+/// time in the worst case but approaches O(n) as the input approaches sorted order. It
+/// uses O(1) extra space.
+pub fn smoothsort<T: Ord>(v: &mut [T]) {
+    smoothsort_by(v, T::cmp);
+}
+
+/// Like `smoothsort`, but orders the elements with `compare`.
+pub fn smoothsort_by<T>(v: &mut [T], mut compare: impl FnMut(&T, &T) -> Ordering) {
+    let is_less = &mut |a: &T, b: &T| compare(a, b) == Ordering::Less;
+    let n = v.len();
+    // The prefix built so far is a forest of max-heaps shaped as Leonardo trees, whose
+    // orders strictly decrease from left to right, so a bit per order describes it
+    let mut orders = 0u128;
+    for end in 0..n {
+        let order = match orders.trailing_zeros() {
+            low if orders.checked_shr(low).unwrap_or(0) & 0b11 == 0b11 => {
+                // The two rightmost trees become the children of the new element
+                orders &= !(0b11 << low);
+                low + 2
+            }
+            1 => 0,
+            _ => 1,
+        };
+        orders |= 1 << order;
+        // A tree that will end up as the child of a later element only needs to be a heap;
+        // the roots are put in order once each tree is known to be final
+        let prev = previous_order(orders, order);
+        let right_child = prev == Some(order + 1) && end + 1 < n;
+        let left_child = order >= 1 && n - 1 - end > leonardo(order - 1);
+        if right_child || left_child {
+            sift(v, end, order, is_less);
+        } else {
+            trinkle(v, end, order, orders, is_less);
+        }
+    }
+    // The largest root is always rightmost, so each tree in turn gives up its root and
+    // exposes its children as trees of their own
+    for end in (0..n).rev() {
+        let order = orders.trailing_zeros();
+        orders &= !(1 << order);
+        if order >= 2 {
+            let right = end - 1;
+            let left = right - leonardo(order - 2);
+            orders |= 0b11 << (order - 2);
+            trinkle(v, left, order - 1, orders, is_less);
+            trinkle(v, right, order - 2, orders, is_less);
+        }
+    }
+}
+
+/// Returns the number of nodes in a Leonardo tree of the given order, saturating at
+/// `usize::MAX` for orders too large to fit in memory.
+fn leonardo(order: u32) -> usize {
+    const LEONARDO: [usize; 96] = {
+        let mut table = [1usize; 96];
+        let mut i = 2;
+        while i < table.len() {
+            table[i] = table[i - 1].saturating_add(table[i - 2]).saturating_add(1);
+            i += 1;
+        }
+        table
+    };
+    LEONARDO[order as usize]
+}
+
+/// Returns the order of the tree to the left of the one with the given order.
+fn previous_order(orders: u128, order: u32) -> Option<u32> {
+    let above = orders.checked_shr(order + 1).unwrap_or(0);
+    (above != 0).then(|| above.trailing_zeros() + order + 1)
+}
+
+/// Restores the max-heap property to the Leonardo tree of the given order rooted at
+/// `root`, whose subtrees must already be heaps.
+fn sift<T>(v: &mut [T], mut root: usize, mut order: u32, is_less: &mut impl FnMut(&T, &T) -> bool) {
+    while order >= 2 {
+        let right = root - 1;
+        let left = right - leonardo(order - 2);
+        let (child, child_order) = if is_less(&v[left], &v[right]) {
+            (right, order - 2)
+        } else {
+            (left, order - 1)
+        };
+        if !is_less(&v[root], &v[child]) {
+            break;
+        }
+        v.swap(root, child);
+        root = child;
+        order = child_order;
+    }
+}
+
+/// Moves the root of the tree at `root` leftwards past every root larger than it, so the
+/// roots ascend from left to right, and then restores the tree it ends up in. `orders`
+/// describes the forest the tree belongs to, and the trees to its left must already be in
+/// order.
+fn trinkle<T>(
+    v: &mut [T],
+    mut root: usize,
+    mut order: u32,
+    orders: u128,
+    is_less: &mut impl FnMut(&T, &T) -> bool,
+) {
+    while let Some(prev_order) = previous_order(orders, order) {
+        let prev = root - leonardo(order);
+        if !is_less(&v[root], &v[prev]) {
+            break;
+        }
+        // Swapping is pointless if a child would rise above the previous root anyway
+        if order >= 2 {
+            let right = root - 1;
+            let left = right - leonardo(order - 2);
+            if !is_less(&v[left], &v[prev]) || !is_less(&v[right], &v[prev]) {
+                break;
+            }
+        }
+        v.swap(root, prev);
+        root = prev;
+        order = prev_order;
+    }
+    sift(v, root, order, is_less);
+}
+
+/// Sorts the vector stably with `compare`, spread across the available cores. The vector is
+/// split into a run per core, the runs are sorted on scoped threads, and then pairs of runs
+/// are merged in parallel until one is left. It takes O(n) scratch space. If `compare`
+/// panics, the panic is propagated and the vector is left empty.
+#[cfg(feature = "parallel")]
+pub fn par_sort_by<T, F>(v: &mut Vec<T>, compare: F)
+where
+    T: Send,
+    F: Fn(&T, &T) -> Ordering + Sync,
+{
+    let threads = thread::available_parallelism().map_or(1, NonZeroUsize::get);
+    let run_len = v.len().div_ceil(threads).max(MIN_PARALLEL_RUN);
+    if v.len() <= run_len {
+        v.sort_by(compare);
+        return;
+    }
+    let compare = &compare;
+    // Splitting from the back moves each element only once
+    let mut rest = std::mem::take(v);
+    let mut runs = vec![];
+    while rest.len() > run_len {
+        runs.push(rest.split_off(rest.len() - run_len));
+    }
+    runs.push(rest);
+    runs.reverse();
+    thread::scope(|scope| {
+        for run in &mut runs {
+            scope.spawn(move || run.sort_by(compare));
+        }
+    });
+    while runs.len() > 1 {
+        runs = thread::scope(|scope| {
+            let mut runs = runs.into_iter();
+            let mut merges = vec![];
+            while let Some(left) = runs.next() {
+                let right = runs.next().unwrap_or_default();
+                merges.push(scope.spawn(move || merge_runs(left, right, compare)));
+            }
+            merges
+                .into_iter()
+                .map(|merge| merge.join().unwrap())
+                .collect()
+        });
+    }
+    *v = runs.pop().unwrap();
+}
+
+/// Merges two sorted runs, taking from `left` first when elements are equal.
+#[cfg(feature = "parallel")]
+fn merge_runs<T>(left: Vec<T>, right: Vec<T>, compare: impl Fn(&T, &T) -> Ordering) -> Vec<T> {
+    let mut merged = Vec::with_capacity(left.len() + right.len());
+    let mut right = right.into_iter().peekable();
+    for l in left {
+        while let Some(r) = right.next_if(|r| compare(r, &l) == Ordering::Less) {
+            merged.push(r);
+        }
+        merged.push(l);
+    }
+    merged.extend(right);
+    merged
+}
+
+/// An unsigned integer key for `radix_sort_by_key`.
+pub trait RadixKey: Copy {
+    const BYTES: usize;
+
+    /// Returns the byte at `index`, counting from the least significant.
+    fn byte(self, index: usize) -> u8;
+}
+
+impl RadixKey for u32 {
+    const BYTES: usize = 4;
+
+    fn byte(self, index: usize) -> u8 {
+        self.to_le_bytes()[index]
+    }
+}
+
+impl RadixKey for u64 {
+    const BYTES: usize = 8;
+
+    fn byte(self, index: usize) -> u8 {
+        self.to_le_bytes()[index]
+    }
+}
+
+impl RadixKey for usize {
+    const BYTES: usize = std::mem::size_of::<usize>();
+
+    fn byte(self, index: usize) -> u8 {
+        self.to_le_bytes()[index]
+    }
+}
+
+/// Sorts the slice by the keys `key` extracts, with a stable least-significant-digit radix
+/// sort. `key` is called once per element, and there is a pass over the keys for each of
+/// their bytes, skipping bytes every key shares, so sorting takes O(n) time for a given key
+/// width. Unlike the comparison sorts, it allocates O(n) scratch space.
+pub fn radix_sort_by_key<T, K: RadixKey>(v: &mut [T], mut key: impl FnMut(&T) -> K) {
+    let mut keys = v
+        .iter()
+        .enumerate()
+        .map(|(i, x)| (key(x), i))
+        .collect::<Vec<_>>();
+    // Count every byte in a single read of the keys
+    let mut counts = vec![[0; 256]; K::BYTES];
+    for &(k, _) in &keys {
+        for (index, counts) in counts.iter_mut().enumerate() {
+            counts[k.byte(index) as usize] += 1;
+        }
+    }
+    let mut scratch = keys.clone();
+    for (index, counts) in counts.iter_mut().enumerate() {
+        if counts.contains(&keys.len()) {
+            continue;
+        }
+        distribute(&keys, &mut scratch, counts, |(k, _)| k.byte(index) as usize);
+        std::mem::swap(&mut keys, &mut scratch);
+    }
+    permute(v, keys.into_iter().map(|(_, i)| i).collect());
+}
+
+/// Sorts the slice by the keys `key` extracts, which must be less than `buckets`, with a
+/// stable counting sort. It calls `key` once per element and takes O(n + buckets) time and
+/// space. Panics if a key is out of range.
+pub fn counting_sort_by_key<T>(v: &mut [T], buckets: usize, mut key: impl FnMut(&T) -> usize) {
+    let mut counts = vec![0; buckets];
+    let keys = v
+        .iter()
+        .enumerate()
+        .map(|(i, x)| {
+            let k = key(x);
+            assert!(k < buckets, "key must be less than the number of buckets");
+            counts[k] += 1;
+            (k, i)
+        })
+        .collect::<Vec<_>>();
+    let mut sorted = keys.clone();
+    distribute(&keys, &mut sorted, &mut counts, |(k, _)| k);
+    permute(v, sorted.into_iter().map(|(_, i)| i).collect());
+}
+
+/// Copies the items from `src` into `dst` grouped by bucket, keeping their order within
+/// each bucket, where `counts` holds the number of items in each bucket.
+fn distribute<E: Copy>(
+    src: &[E],
+    dst: &mut [E],
+    counts: &mut [usize],
+    bucket: impl Fn(E) -> usize,
+) {
+    // Turn the counts into the position of each bucket's next item
+    let mut start = 0;
+    for count in counts.iter_mut() {
+        start += std::mem::replace(count, start);
+    }
+    for &item in src {
+        let position = &mut counts[bucket(item)];
+        dst[*position] = item;
+        *position += 1;
+    }
+}
+
+/// Rearranges the slice so that position `i` holds the element that was at `order[i]`,
+/// following each cycle of the permutation with swaps.
+fn permute<T>(v: &mut [T], mut order: Vec<usize>) {
+    for start in 0..v.len() {
+        let mut current = start;
+        // Positions already in place point to themselves
+        while order[current] != current {
+            let next = std::mem::replace(&mut order[current], current);
+            if next == start {
+                break;
+            }
+            v.swap(current, next);
+            current = next;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cmp::Reverse;
+
+    use quickcheck::quickcheck;
+
+    use super::{
+        counting_sort_by_key, heapsort, heapsort_by, radix_sort_by_key, smoothsort, smoothsort_by,
+    };
+    #[cfg(feature = "parallel")]
+    use super::{par_sort_by, MIN_PARALLEL_RUN};
+
+    /// Calls `f` with every permutation of `v`, by Heap's algorithm.
+    fn permutations(v: &mut [u8], k: usize, f: &mut impl FnMut(&[u8])) {
+        if k <= 1 {
+            return f(v);
+        }
+        for i in 0..k - 1 {
+            permutations(v, k - 1, f);
+            v.swap(if k.is_multiple_of(2) { i } else { 0 }, k - 1);
+        }
+        permutations(v, k - 1, f);
+    }
+
+    #[test]
+    fn sorts_every_permutation() {
+        for n in 0..=7 {
+            let mut input = (0..n).map(|i| i / 2).collect::<Vec<u8>>();
+            let expected = input.clone();
+            permutations(&mut input, n as usize, &mut |v| {
+                let mut heap = v.to_vec();
+                heapsort(&mut heap);
+                assert_eq!(heap, expected, "heapsort of {:?}", v);
+                let mut smooth = v.to_vec();
+                smoothsort(&mut smooth);
+                assert_eq!(smooth, expected, "smoothsort of {:?}", v);
+            });
+        }
+    }
+
+    #[test]
+    fn smoothsort_adapts_to_sorted_input() {
+        let count = |v: &mut [u32]| {
+            let mut comparisons = 0;
+            smoothsort_by(v, |a, b| {
+                comparisons += 1;
+                a.cmp(b)
+            });
+            comparisons
+        };
+        let n = 10_000;
+        let mut sorted = (0..n).collect::<Vec<_>>();
+        assert!(count(&mut sorted) < 4 * n);
+        let mut reversed = (0..n).rev().collect::<Vec<_>>();
+        assert!(count(&mut reversed) > 4 * n);
+        assert!(reversed.iter().copied().eq(0..n));
+    }
+
+    #[test]
+    fn prop_heapsort_matches_sort() {
+        fn p(mut input: Vec<i32>) -> bool {
+            let mut expected = input.clone();
+            expected.sort();
+            heapsort(&mut input);
+            input == expected
+        }
+        quickcheck(p as fn(Vec<i32>) -> bool);
+    }
+
+    #[test]
+    fn prop_smoothsort_matches_sort() {
+        fn p(mut input: Vec<i32>) -> bool {
+            let mut expected = input.clone();
+            expected.sort();
+            smoothsort(&mut input);
+            input == expected
+        }
+        quickcheck(p as fn(Vec<i32>) -> bool);
+    }
+
+    #[test]
+    fn prop_sort_by_key() {
+        fn p(input: Vec<(u8, u8)>) -> bool {
+            let mut expected = input.iter().map(|&(k, _)| Reverse(k)).collect::<Vec<_>>();
+            expected.sort();
+            let mut heap = input.clone();
+            heapsort_by(&mut heap, |a, b| b.0.cmp(&a.0));
+            let mut smooth = input;
+            smoothsort_by(&mut smooth, |a, b| b.0.cmp(&a.0));
+            heap.iter()
+                .map(|&(k, _)| Reverse(k))
+                .eq(expected.iter().copied())
+                && smooth.iter().map(|&(k, _)| Reverse(k)).eq(expected)
+        }
+        quickcheck(p as fn(Vec<(u8, u8)>) -> bool);
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn par_sort_is_stable() {
+        // Enough elements for several runs, with many equal keys in each
+        let mut state = 1u64;
+        let mut input = (0..MIN_PARALLEL_RUN * 5 + 7)
+            .map(|i| {
+                state = state.wrapping_mul(6364136223846793005).wrapping_add(1);
+                ((state >> 56) as u8, i)
+            })
+            .collect::<Vec<_>>();
+        let mut expected = input.clone();
+        expected.sort_by_key(|&(k, _)| k);
+        par_sort_by(&mut input, |a, b| a.0.cmp(&b.0));
+        assert!(input == expected);
+        let mut small = vec![3, 1, 2];
+        par_sort_by(&mut small, |a, b| b.cmp(a));
+        assert_eq!(small, [3, 2, 1]);
+    }
+
+    #[test]
+    fn radix_sort_is_stable() {
+        let mut rows = vec![
+            (3u64 << 40, 'a'),
+            (7, 'b'),
+            (3u64 << 40, 'c'),
+            (0, 'd'),
+            (7, 'e'),
+        ];
+        radix_sort_by_key(&mut rows, |&(k, _)| k);
+        assert_eq!(rows.iter().map(|&(_, c)| c).collect::<String>(), "dbeac");
+    }
+
+    #[test]
+    #[should_panic(expected = "key must be less than the number of buckets")]
+    fn counting_sort_out_of_range() {
+        counting_sort_by_key(&mut [1, 2, 3], 3, |&k| k);
+    }
+
+    #[test]
+    fn prop_radix_sort_matches_stable_sort() {
+        fn p(input: Vec<(u64, u8)>, shift: u8) -> bool {
+            // Shifting exercises passes over the high bytes and skipping the low ones
+            let input = input
+                .into_iter()
+                .map(|(k, x)| (k << (shift % 64), x))
+                .collect::<Vec<_>>();
+            let mut expected = input.clone();
+            expected.sort_by_key(|&(k, _)| k);
+            let mut wide = input.clone();
+            radix_sort_by_key(&mut wide, |&(k, _)| k);
+            let mut narrow = input.clone();
+            narrow.sort_by_key(|&(k, _)| k as u32);
+            let mut narrow_radix = input;
+            radix_sort_by_key(&mut narrow_radix, |&(k, _)| k as u32);
+            wide == expected && narrow_radix == narrow
+        }
+        quickcheck(p as fn(Vec<(u64, u8)>, u8) -> bool);
+    }
+
+    #[test]
+    fn prop_counting_sort_matches_stable_sort() {
+        fn p(input: Vec<(u8, u8)>) -> bool {
+            let mut expected = input.clone();
+            expected.sort_by_key(|&(k, _)| k % 10);
+            let mut sorted = input;
+            counting_sort_by_key(&mut sorted, 10, |&(k, _)| (k % 10) as usize);
+            sorted == expected
+        }
+        quickcheck(p as fn(Vec<(u8, u8)>) -> bool);
+    }
+}
@@ -0,0 +1,363 @@
+use std::{
+    cmp::Ordering,
+    hash::{Hash, Hasher},
+    ops::Index,
+};
+
+use crate::from_sorted::{collect_sorted, FromSorted};
+
+const DEFAULT_LOAD: usize = 256;
+
+/// A sorted sequence which allows duplicates and supports positional access.
+/// Elements are kept in a list of sorted chunks of roughly `load` elements each, so
+/// insertion and removal only shift one small chunk, and indexing skips over whole chunks.
+/// With the default load, most operations take O(sqrt n) time or better in practice.
+#[derive(Debug, Clone)]
+pub struct SortedList<T> {
+    chunks: Vec<Vec<T>>,
+    len: usize,
+    load: usize,
+}
+
+impl<T> SortedList<T> {
+    pub fn new() -> Self {
+        SortedList::with_load(DEFAULT_LOAD)
+    }
+
+    /// Creates a list whose chunks are split once they grow past twice `load`.
+    pub fn with_load(load: usize) -> Self {
+        assert!(load > 0, "load must be positive");
+        SortedList {
+            chunks: vec![],
+            len: 0,
+            load,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn get(&self, index: usize) -> Option<&T> {
+        let (ci, i) = self.position(index)?;
+        Some(&self.chunks[ci][i])
+    }
+
+    pub fn first(&self) -> Option<&T> {
+        self.chunks.first().and_then(|c| c.first())
+    }
+
+    pub fn last(&self) -> Option<&T> {
+        self.chunks.last().and_then(|c| c.last())
+    }
+
+    /// Removes and returns the element at `index`.
+    pub fn remove_index(&mut self, index: usize) -> Option<T> {
+        let (ci, i) = self.position(index)?;
+        Some(self.remove_at(ci, i))
+    }
+
+    pub fn pop_first(&mut self) -> Option<T> {
+        self.remove_index(0)
+    }
+
+    pub fn pop_last(&mut self) -> Option<T> {
+        self.remove_index(self.len.checked_sub(1)?)
+    }
+
+    /// Returns an iterator over the elements in ascending order.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &T> {
+        self.chunks.iter().flatten()
+    }
+
+    pub fn clear(&mut self) {
+        self.chunks.clear();
+        self.len = 0;
+    }
+
+    /// Converts a global index into a chunk and an index within it.
+    fn position(&self, mut index: usize) -> Option<(usize, usize)> {
+        if index >= self.len {
+            return None;
+        }
+        for (ci, chunk) in self.chunks.iter().enumerate() {
+            if index < chunk.len() {
+                return Some((ci, index));
+            }
+            index -= chunk.len();
+        }
+        unreachable!()
+    }
+
+    /// The number of elements in the chunks before chunk `ci`.
+    fn offset(&self, ci: usize) -> usize {
+        self.chunks[..ci].iter().map(|c| c.len()).sum()
+    }
+
+    fn remove_at(&mut self, ci: usize, i: usize) -> T {
+        let t = self.chunks[ci].remove(i);
+        if self.chunks[ci].is_empty() {
+            self.chunks.remove(ci);
+        }
+        self.len -= 1;
+        t
+    }
+}
+
+impl<T> Default for SortedList<T> {
+    fn default() -> Self {
+        SortedList::new()
+    }
+}
+
+impl<T> SortedList<T>
+where
+    T: Ord,
+{
+    /// Panics if the elements are out of order or the chunks are malformed.
+    #[cfg(any(test, feature = "testing"))]
+    pub fn check_invariants(&self) {
+        assert!(self.chunks.iter().all(|c| !c.is_empty()), "empty chunk");
+        assert!(
+            self.chunks.iter().all(|c| c.len() <= 2 * self.load),
+            "chunk larger than twice the load"
+        );
+        let mut iter = self.iter();
+        if let Some(mut prev) = iter.next() {
+            for t in iter {
+                assert!(prev <= t, "elements are out of order");
+                prev = t;
+            }
+        }
+        assert_eq!(
+            self.offset(self.chunks.len()),
+            self.len,
+            "length is out of date"
+        );
+    }
+
+    /// Inserts the element after any equal elements.
+    pub fn insert(&mut self, t: T) {
+        if self.chunks.is_empty() {
+            self.chunks.push(vec![t]);
+            self.len = 1;
+            return;
+        }
+        // The first chunk with an element greater than t, or the last chunk
+        let ci = self
+            .chunks
+            .partition_point(|c| c.last().unwrap() <= &t)
+            .min(self.chunks.len() - 1);
+        let chunk = &mut self.chunks[ci];
+        let i = chunk.partition_point(|x| x <= &t);
+        chunk.insert(i, t);
+        self.len += 1;
+        if chunk.len() > 2 * self.load {
+            let tail = chunk.split_off(self.load);
+            self.chunks.insert(ci + 1, tail);
+        }
+    }
+
+    /// Removes one element equal to `t`, returning whether it was found.
+    pub fn remove(&mut self, t: &T) -> bool {
+        let ci = self.chunks.partition_point(|c| c.last().unwrap() < t);
+        if ci == self.chunks.len() {
+            return false;
+        }
+        let i = self.chunks[ci].partition_point(|x| x < t);
+        if self.chunks[ci][i] != *t {
+            return false;
+        }
+        self.remove_at(ci, i);
+        true
+    }
+
+    /// The index of the first element not less than `t`, i.e. where `t` would be inserted
+    /// before any equal elements. This is also the number of elements less than `t`.
+    pub fn bisect_left(&self, t: &T) -> usize {
+        let ci = self.chunks.partition_point(|c| c.last().unwrap() < t);
+        if ci == self.chunks.len() {
+            return self.len;
+        }
+        self.offset(ci) + self.chunks[ci].partition_point(|x| x < t)
+    }
+
+    /// The index of the first element greater than `t`, i.e. where `t` would be inserted
+    /// after any equal elements.
+    pub fn bisect_right(&self, t: &T) -> usize {
+        let ci = self.chunks.partition_point(|c| c.last().unwrap() <= t);
+        if ci == self.chunks.len() {
+            return self.len;
+        }
+        self.offset(ci) + self.chunks[ci].partition_point(|x| x <= t)
+    }
+
+    /// The number of elements less than `t`.
+    pub fn rank(&self, t: &T) -> usize {
+        self.bisect_left(t)
+    }
+
+    pub fn count(&self, t: &T) -> usize {
+        self.bisect_right(t) - self.bisect_left(t)
+    }
+
+    pub fn contains(&self, t: &T) -> bool {
+        self.get(self.bisect_left(t)) == Some(t)
+    }
+}
+
+impl<T> Index<usize> for SortedList<T> {
+    type Output = T;
+
+    fn index(&self, index: usize) -> &T {
+        self.get(index).expect("index out of bounds")
+    }
+}
+
+/// Lists are compared by their elements, regardless of how they are chunked.
+impl<T: PartialEq> PartialEq for SortedList<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len && self.iter().eq(other.iter())
+    }
+}
+
+impl<T: Eq> Eq for SortedList<T> {}
+
+impl<T: Hash> Hash for SortedList<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.len.hash(state);
+        self.iter().for_each(|t| t.hash(state));
+    }
+}
+
+impl<T: PartialOrd> PartialOrd for SortedList<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.iter().partial_cmp(other.iter())
+    }
+}
+
+impl<T: Ord> Ord for SortedList<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.iter().cmp(other.iter())
+    }
+}
+
+impl<T: Ord> FromIterator<T> for SortedList<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut values = iter.into_iter().collect::<Vec<_>>();
+        values.sort();
+        SortedList::from_sorted_vec(values)
+    }
+}
+
+/// Duplicates are allowed, so the elements only need to be non-decreasing.
+impl<T: Ord> FromSorted<T> for SortedList<T> {
+    fn from_sorted_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        SortedList::from_sorted_vec(collect_sorted(iter, false, |t| t))
+    }
+}
+
+impl<T> SortedList<T> {
+    fn from_sorted_vec(mut values: Vec<T>) -> Self {
+        let mut list = SortedList::new();
+        list.len = values.len();
+        while !values.is_empty() {
+            let rest = values.split_off(values.len().min(list.load));
+            list.chunks.push(values);
+            values = rest;
+        }
+        list
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use quickcheck::quickcheck;
+
+    use super::SortedList;
+    use crate::from_sorted::FromSorted;
+
+    #[test]
+    fn insert_sorted() {
+        let mut list = SortedList::new();
+        for i in [5, 1, 4, 1, 3] {
+            list.insert(i);
+        }
+        assert_eq!(
+            list.iter().copied().collect::<Vec<_>>(),
+            vec![1, 1, 3, 4, 5]
+        );
+        assert_eq!(list[2], 3);
+        assert_eq!(list.first(), Some(&1));
+        assert_eq!(list.last(), Some(&5));
+    }
+
+    #[test]
+    fn from_sorted() {
+        let list = SortedList::from_sorted_iter((0..1000).map(|i| i / 2));
+        list.check_invariants();
+        assert_eq!(list.len(), 1000);
+        assert_eq!(list.count(&7), 2);
+    }
+
+    #[test]
+    #[should_panic]
+    fn from_unsorted() {
+        SortedList::from_sorted_iter([2, 1]);
+    }
+
+    #[test]
+    fn bisect_and_rank() {
+        let list = [1, 2, 2, 2, 3].into_iter().collect::<SortedList<_>>();
+        assert_eq!(list.bisect_left(&2), 1);
+        assert_eq!(list.bisect_right(&2), 4);
+        assert_eq!(list.rank(&3), 4);
+        assert_eq!(list.count(&2), 3);
+        assert_eq!(list.bisect_left(&10), 5);
+        assert!(!list.contains(&0));
+    }
+
+    #[test]
+    fn remove() {
+        let mut list = [1, 2, 2, 3].into_iter().collect::<SortedList<_>>();
+        assert!(list.remove(&2));
+        assert!(!list.remove(&7));
+        assert_eq!(list.remove_index(0), Some(1));
+        assert_eq!(list.pop_last(), Some(3));
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), vec![2]);
+    }
+
+    #[test]
+    fn prop_matches_sorted_vec() {
+        fn p(inserts: Vec<u8>, removals: Vec<u8>) -> bool {
+            // A small load forces many chunk splits
+            let mut list = SortedList::with_load(2);
+            let mut model = vec![];
+            for i in inserts {
+                list.insert(i);
+                let pos = model.partition_point(|x| *x <= i);
+                model.insert(pos, i);
+            }
+            for r in removals {
+                let found = model.binary_search(&r).ok();
+                if let Some(pos) = found {
+                    model.remove(pos);
+                }
+                if list.remove(&r) != found.is_some() {
+                    return false;
+                }
+            }
+            list.iter().eq(model.iter())
+                && (0..=255u8).all(|t| {
+                    list.bisect_left(&t) == model.partition_point(|x| *x < t)
+                        && list.bisect_right(&t) == model.partition_point(|x| *x <= t)
+                })
+                && (0..model.len()).all(|i| list.get(i) == model.get(i))
+        }
+        quickcheck(p as fn(Vec<u8>, Vec<u8>) -> bool)
+    }
+}
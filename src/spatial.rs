@@ -0,0 +1,362 @@
+use crate::slab::{Key, SlotMap};
+
+const MAX_ITEMS: usize = 8;
+const MAX_DEPTH: usize = 8;
+
+/// An axis-aligned rectangle. Rectangles are closed, so ones which only touch intersect.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rect {
+    pub min_x: f64,
+    pub min_y: f64,
+    pub max_x: f64,
+    pub max_y: f64,
+}
+
+impl Rect {
+    pub fn new(min_x: f64, min_y: f64, max_x: f64, max_y: f64) -> Self {
+        assert!(
+            min_x <= max_x && min_y <= max_y,
+            "rect min must not exceed max"
+        );
+        Rect {
+            min_x,
+            min_y,
+            max_x,
+            max_y,
+        }
+    }
+
+    /// A rectangle with zero area at the point.
+    pub fn point(x: f64, y: f64) -> Self {
+        Rect::new(x, y, x, y)
+    }
+
+    pub fn intersects(&self, other: &Rect) -> bool {
+        self.min_x <= other.max_x
+            && other.min_x <= self.max_x
+            && self.min_y <= other.max_y
+            && other.min_y <= self.max_y
+    }
+
+    pub fn contains(&self, other: &Rect) -> bool {
+        self.min_x <= other.min_x
+            && other.max_x <= self.max_x
+            && self.min_y <= other.min_y
+            && other.max_y <= self.max_y
+    }
+
+    /// Splits the rectangle into four equal quadrants, ordered by row and then column.
+    fn quadrants(&self) -> [Rect; 4] {
+        let mid_x = (self.min_x + self.max_x) / 2.0;
+        let mid_y = (self.min_y + self.max_y) / 2.0;
+        [
+            Rect::new(self.min_x, self.min_y, mid_x, mid_y),
+            Rect::new(mid_x, self.min_y, self.max_x, mid_y),
+            Rect::new(self.min_x, mid_y, mid_x, self.max_y),
+            Rect::new(mid_x, mid_y, self.max_x, self.max_y),
+        ]
+    }
+}
+
+/// A handle to an item stored in a `QuadTree`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ItemId(Key);
+
+/// A region quadtree indexing rectangles by location, for finding the items near a point
+/// or region without testing every item. Each item is stored in the deepest node whose
+/// bounds fully contain it, and nodes split into quadrants once they hold too many items.
+/// Items outside the bounds of the tree are allowed but are always tested by queries.
+#[derive(Debug, Clone)]
+pub struct QuadTree<T> {
+    root: Node,
+    items: SlotMap<(Rect, T)>,
+}
+
+#[derive(Debug, Clone)]
+struct Node {
+    bounds: Rect,
+    depth: usize,
+    items: Vec<Key>,
+    children: Option<Box<[Node; 4]>>,
+}
+
+impl Node {
+    fn new(bounds: Rect, depth: usize) -> Self {
+        Node {
+            bounds,
+            depth,
+            items: vec![],
+            children: None,
+        }
+    }
+
+    /// The index of the child quadrant which contains the rectangle without touching
+    /// the lines between quadrants, if any. Rectangles on a dividing line stay in this
+    /// node so that touching rectangles in neighbouring quadrants are never missed.
+    fn child_for(&self, rect: &Rect) -> Option<usize> {
+        if self.children.is_none() || !self.bounds.contains(rect) {
+            return None;
+        }
+        let mid_x = (self.bounds.min_x + self.bounds.max_x) / 2.0;
+        let mid_y = (self.bounds.min_y + self.bounds.max_y) / 2.0;
+        let column = if rect.max_x < mid_x {
+            0
+        } else if rect.min_x > mid_x {
+            1
+        } else {
+            return None;
+        };
+        let row = if rect.max_y < mid_y {
+            0
+        } else if rect.min_y > mid_y {
+            1
+        } else {
+            return None;
+        };
+        Some(row * 2 + column)
+    }
+}
+
+impl<T> QuadTree<T> {
+    pub fn new(bounds: Rect) -> Self {
+        QuadTree {
+            root: Node::new(bounds, 0),
+            items: SlotMap::new(),
+        }
+    }
+
+    pub fn bounds(&self) -> Rect {
+        self.root.bounds
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn get(&self, id: ItemId) -> Option<(&Rect, &T)> {
+        self.items.get(id.0).map(|(rect, t)| (rect, t))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (ItemId, &Rect, &T)> {
+        self.items
+            .iter()
+            .map(|(key, (rect, t))| (ItemId(key), rect, t))
+    }
+
+    pub fn insert(&mut self, rect: Rect, t: T) -> ItemId {
+        let key = self.items.insert((rect, t));
+        let items = &self.items;
+        let mut node = &mut self.root;
+        while let Some(i) = node.child_for(&rect) {
+            node = &mut node.children.as_mut().unwrap()[i];
+        }
+        node.items.push(key);
+
+        if node.children.is_none() && node.items.len() > MAX_ITEMS && node.depth < MAX_DEPTH {
+            // Split the node and push down every item which fits in a quadrant
+            let children = node
+                .bounds
+                .quadrants()
+                .map(|q| Node::new(q, node.depth + 1));
+            node.children = Some(Box::new(children));
+            let keys = std::mem::take(&mut node.items);
+            for key in keys {
+                let rect = &items[key].0;
+                match node.child_for(rect) {
+                    Some(i) => node.children.as_mut().unwrap()[i].items.push(key),
+                    None => node.items.push(key),
+                }
+            }
+        }
+        ItemId(key)
+    }
+
+    /// Removes the item. Nodes are not merged back together afterwards.
+    pub fn remove(&mut self, id: ItemId) -> Option<T> {
+        let (rect, t) = self.items.remove(id.0)?;
+        let mut node = &mut self.root;
+        loop {
+            if let Some(i) = node.items.iter().position(|key| *key == id.0) {
+                node.items.swap_remove(i);
+                return Some(t);
+            }
+            let i = node.child_for(&rect).expect("item missing from quadtree");
+            node = &mut node.children.as_mut().unwrap()[i];
+        }
+    }
+
+    /// Returns every item whose rectangle intersects the region.
+    pub fn query_region(&self, region: Rect) -> Query<'_, T> {
+        Query {
+            tree: self,
+            stack: vec![&self.root],
+            keys: [].iter(),
+            region,
+        }
+    }
+
+    /// Returns every item whose rectangle contains the point.
+    pub fn query_point(&self, x: f64, y: f64) -> Query<'_, T> {
+        self.query_region(Rect::point(x, y))
+    }
+
+    /// Returns every pair of distinct items whose rectangles intersect.
+    /// Each pair is reported once.
+    pub fn intersecting_pairs(&self) -> Vec<(ItemId, ItemId)> {
+        let mut pairs = vec![];
+        self.collect_pairs(&self.root, &mut vec![], &mut pairs);
+        pairs
+    }
+
+    /// Tests the items of `node` against each other and against the items of its ancestors.
+    fn collect_pairs(
+        &self,
+        node: &Node,
+        ancestors: &mut Vec<Key>,
+        pairs: &mut Vec<(ItemId, ItemId)>,
+    ) {
+        for (i, a) in node.items.iter().enumerate() {
+            let rect = &self.items[*a].0;
+            for b in ancestors.iter().chain(node.items[i + 1..].iter()) {
+                if rect.intersects(&self.items[*b].0) {
+                    pairs.push((ItemId(*b), ItemId(*a)));
+                }
+            }
+        }
+        if let Some(children) = &node.children {
+            let len = ancestors.len();
+            ancestors.extend(node.items.iter().copied());
+            for child in children.iter() {
+                self.collect_pairs(child, ancestors, pairs);
+            }
+            ancestors.truncate(len);
+        }
+    }
+}
+
+/// An iterator over the items of a `QuadTree` which intersect a region.
+pub struct Query<'a, T> {
+    tree: &'a QuadTree<T>,
+    stack: Vec<&'a Node>,
+    keys: std::slice::Iter<'a, Key>,
+    region: Rect,
+}
+
+impl<'a, T> Iterator for Query<'a, T> {
+    type Item = (ItemId, &'a Rect, &'a T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            for key in self.keys.by_ref() {
+                let (rect, t) = &self.tree.items[*key];
+                if rect.intersects(&self.region) {
+                    return Some((ItemId(*key), rect, t));
+                }
+            }
+            let node = self.stack.pop()?;
+            if let Some(children) = &node.children {
+                self.stack.extend(
+                    children
+                        .iter()
+                        .filter(|child| child.bounds.intersects(&self.region)),
+                );
+            }
+            self.keys = node.items.iter();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashSet;
+
+    use quickcheck::quickcheck;
+
+    use super::{QuadTree, Rect};
+
+    #[test]
+    fn point_and_region_queries() {
+        let mut tree = QuadTree::new(Rect::new(0.0, 0.0, 100.0, 100.0));
+        let a = tree.insert(Rect::new(10.0, 10.0, 20.0, 20.0), "a");
+        let b = tree.insert(Rect::new(15.0, 15.0, 60.0, 60.0), "b");
+        tree.insert(Rect::new(80.0, 80.0, 90.0, 90.0), "c");
+        let hits = tree
+            .query_point(16.0, 16.0)
+            .map(|(id, _, _)| id)
+            .collect::<HashSet<_>>();
+        assert_eq!(hits, HashSet::from([a, b]));
+        let hits = tree
+            .query_region(Rect::new(70.0, 70.0, 100.0, 100.0))
+            .map(|(_, _, t)| *t)
+            .collect::<Vec<_>>();
+        assert_eq!(hits, vec!["c"]);
+    }
+
+    #[test]
+    fn remove() {
+        let mut tree = QuadTree::new(Rect::new(0.0, 0.0, 100.0, 100.0));
+        let ids = (0..50)
+            .map(|i| tree.insert(Rect::point(i as f64, i as f64), i))
+            .collect::<Vec<_>>();
+        assert_eq!(tree.remove(ids[10]), Some(10));
+        assert_eq!(tree.remove(ids[10]), None);
+        assert_eq!(tree.query_point(10.0, 10.0).count(), 0);
+        assert_eq!(tree.query_point(11.0, 11.0).count(), 1);
+        assert_eq!(tree.len(), 49);
+    }
+
+    #[test]
+    fn outside_bounds() {
+        let mut tree = QuadTree::new(Rect::new(0.0, 0.0, 10.0, 10.0));
+        tree.insert(Rect::new(-5.0, -5.0, -1.0, -1.0), ());
+        assert_eq!(tree.query_point(-2.0, -2.0).count(), 1);
+    }
+
+    #[test]
+    fn prop_matches_brute_force() {
+        fn p(rects: Vec<(u8, u8, u8, u8)>) -> bool {
+            let mut tree = QuadTree::new(Rect::new(0.0, 0.0, 256.0, 256.0));
+            let items = rects
+                .into_iter()
+                .map(|(x, y, w, h)| {
+                    let rect = Rect::new(
+                        x as f64,
+                        y as f64,
+                        (x as f64) + (w % 32) as f64,
+                        (y as f64) + (h % 32) as f64,
+                    );
+                    (tree.insert(rect, ()), rect)
+                })
+                .collect::<Vec<_>>();
+            let mut expected = HashSet::new();
+            for (i, (a, ra)) in items.iter().enumerate() {
+                for (b, rb) in items[i + 1..].iter() {
+                    if ra.intersects(rb) {
+                        expected.insert((*a.min(b), *a.max(b)));
+                    }
+                }
+            }
+            let pairs = tree.intersecting_pairs();
+            let actual = pairs
+                .iter()
+                .map(|(a, b)| (*a.min(b), *a.max(b)))
+                .collect::<HashSet<_>>();
+            let region = Rect::new(64.0, 64.0, 128.0, 128.0);
+            let queried = tree
+                .query_region(region)
+                .map(|(id, _, _)| id)
+                .collect::<HashSet<_>>();
+            let scanned = items
+                .iter()
+                .filter(|(_, r)| r.intersects(&region))
+                .map(|(id, _)| *id)
+                .collect::<HashSet<_>>();
+            pairs.len() == expected.len() && actual == expected && queried == scanned
+        }
+        quickcheck(p as fn(Vec<(u8, u8, u8, u8)>) -> bool)
+    }
+}
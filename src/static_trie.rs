@@ -0,0 +1,503 @@
+#[cfg(feature = "viz")]
+use std::fmt::Write;
+use std::io;
+
+use crate::layout::{Buf, LoadError, Plain, Reader, Writer};
+#[cfg(feature = "viz")]
+use crate::viz::{self, Visualize};
+
+/// An immutable radix trie over byte strings, stored in a few flat arrays.
+/// Chains of nodes with a single child and no value are collapsed into one edge with a
+/// multi-byte label, and each node's edges are sorted so lookups binary search them.
+/// It is built in one pass from sorted keys with a `StaticTrieBuilder`. A trie of plain
+/// values can be written with `write_to` and loaded back with `load`, which borrows the
+/// arrays from the buffer for the lifetime `'a`.
+#[derive(Debug, Clone)]
+pub struct StaticTrieView<'a, V> {
+    nodes: Buf<'a, Node>,
+    edges: Buf<'a, Edge>,
+    labels: Buf<'a, u8>,
+    values: Buf<'a, V>,
+    root: u32,
+}
+
+/// A `StaticTrieView` which owns its arrays, as every trie does unless it was loaded.
+pub type StaticTrie<V> = StaticTrieView<'static, V>;
+
+// Marks a node without a value
+const NO_VALUE: u32 = u32::MAX;
+
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+struct Node {
+    edges_start: u32,
+    edges_len: u32,
+    // An index into the values, or `NO_VALUE`
+    value: u32,
+}
+
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+struct Edge {
+    label_start: u32,
+    label_len: u32,
+    target: u32,
+}
+
+// Both are three `u32`s without padding
+unsafe impl Plain for Node {
+    fn swap_le(self) -> Self {
+        Node {
+            edges_start: self.edges_start.to_le(),
+            edges_len: self.edges_len.to_le(),
+            value: self.value.to_le(),
+        }
+    }
+}
+
+unsafe impl Plain for Edge {
+    fn swap_le(self) -> Self {
+        Edge {
+            label_start: self.label_start.to_le(),
+            label_len: self.label_len.to_le(),
+            target: self.target.to_le(),
+        }
+    }
+}
+
+/// Builds a `StaticTrie` from keys inserted in strictly ascending order. Only the nodes on
+/// the path of the last key are kept in an uncompressed form; every other node has already
+/// been written out, since no later key can reach it.
+#[derive(Debug)]
+pub struct StaticTrieBuilder<V> {
+    nodes: Vec<Node>,
+    edges: Vec<Edge>,
+    labels: Vec<u8>,
+    values: Vec<V>,
+    // The nodes along the last key, where `pending[i]` is reached by its first `i` bytes
+    pending: Vec<Pending<V>>,
+    last_key: Vec<u8>,
+    started: bool,
+}
+
+#[derive(Debug)]
+struct Pending<V> {
+    value: Option<V>,
+    // Edges to nodes which have been written, as a label and target node
+    edges: Vec<(Vec<u8>, u32)>,
+}
+
+impl<V> StaticTrieView<'_, V> {
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// The number of nodes after collapsing chains.
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn get(&self, key: &[u8]) -> Option<&V> {
+        let mut node = &self.nodes[self.root as usize];
+        let mut rest = key;
+        while let Some(&first) = rest.first() {
+            let edges = self.edges_of(node);
+            let i = edges
+                .binary_search_by_key(&first, |e| self.labels[e.label_start as usize])
+                .ok()?;
+            let label = self.label(&edges[i]);
+            rest = rest.strip_prefix(label)?;
+            node = &self.nodes[edges[i].target as usize];
+        }
+        self.value_of(node)
+    }
+
+    pub fn contains_key(&self, key: &[u8]) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Returns the keys and values in ascending key order.
+    pub fn iter(&self) -> Iter<'_, V> {
+        Iter {
+            trie: self,
+            key: vec![],
+            stack: vec![Frame {
+                node: self.root,
+                next_edge: 0,
+                key_len: 0,
+                visited: false,
+            }],
+        }
+    }
+
+    fn value_of(&self, node: &Node) -> Option<&V> {
+        (node.value != NO_VALUE).then(|| &self.values[node.value as usize])
+    }
+
+    fn edges_of(&self, node: &Node) -> &[Edge] {
+        let start = node.edges_start as usize;
+        &self.edges[start..start + node.edges_len as usize]
+    }
+
+    fn label(&self, edge: &Edge) -> &[u8] {
+        let start = edge.label_start as usize;
+        &self.labels[start..start + edge.label_len as usize]
+    }
+}
+
+impl<'a, V: Plain> StaticTrieView<'a, V> {
+    /// Writes the trie in the format described in `layout`.
+    pub fn write_to<W: io::Write>(&self, out: W) -> io::Result<()> {
+        let mut writer = Writer::new(out, *b"TRIE")?;
+        writer.word(self.root as u64)?;
+        writer.array(&self.nodes)?;
+        writer.array(&self.edges)?;
+        writer.array(&self.labels)?;
+        writer.array(&self.values)
+    }
+
+    /// Loads a trie written by `write_to`, borrowing its arrays from `bytes` where possible.
+    /// Every index is checked, so a corrupt trie can't send lookups out of bounds or round
+    /// in a cycle.
+    pub fn load(bytes: &'a [u8]) -> Result<Self, LoadError> {
+        let mut reader = Reader::new(bytes, *b"TRIE")?;
+        let root = reader.word()?;
+        let trie = StaticTrieView {
+            nodes: reader.array()?,
+            edges: reader.array()?,
+            labels: reader.array()?,
+            values: reader.array()?,
+            root: u32::try_from(root).map_err(|_| LoadError::Corrupt("root out of bounds"))?,
+        };
+        let within = |start: u32, len: u32, bound: usize| {
+            (start as usize)
+                .checked_add(len as usize)
+                .is_some_and(|end| end <= bound)
+        };
+        if trie.root as usize >= trie.nodes.len() {
+            return Err(LoadError::Corrupt("root out of bounds"));
+        }
+        for (i, node) in trie.nodes.iter().enumerate() {
+            if !within(node.edges_start, node.edges_len, trie.edges.len())
+                || (node.value != NO_VALUE && node.value as usize >= trie.values.len())
+            {
+                return Err(LoadError::Corrupt("node out of bounds"));
+            }
+            // Children are written before their parents, which also rules out cycles
+            for edge in trie.edges_of(node) {
+                if edge.label_len == 0
+                    || !within(edge.label_start, edge.label_len, trie.labels.len())
+                    || edge.target as usize >= i
+                {
+                    return Err(LoadError::Corrupt("edge out of bounds"));
+                }
+            }
+        }
+        Ok(trie)
+    }
+
+    /// Copies any arrays borrowed from a loaded buffer, so the trie can outlive it.
+    pub fn into_owned(self) -> StaticTrie<V> {
+        StaticTrieView {
+            nodes: self.nodes.into_owned(),
+            edges: self.edges.into_owned(),
+            labels: self.labels.into_owned(),
+            values: self.values.into_owned(),
+            root: self.root,
+        }
+    }
+}
+
+impl<V> StaticTrieBuilder<V> {
+    pub fn new() -> Self {
+        StaticTrieBuilder {
+            nodes: vec![],
+            edges: vec![],
+            labels: vec![],
+            values: vec![],
+            pending: vec![Pending {
+                value: None,
+                edges: vec![],
+            }],
+            last_key: vec![],
+            started: false,
+        }
+    }
+
+    /// Adds a key, which must be greater than every key added before it.
+    /// Returns the value back if the key is out of order.
+    pub fn insert(&mut self, key: &[u8], value: V) -> Result<(), V> {
+        if self.started && key <= self.last_key.as_slice() {
+            return Err(value);
+        }
+        self.started = true;
+        let common = key
+            .iter()
+            .zip(&self.last_key)
+            .take_while(|(a, b)| a == b)
+            .count();
+        // Nodes past the common prefix can't gain any more children
+        self.freeze_to(common);
+        for _ in common..key.len() {
+            self.pending.push(Pending {
+                value: None,
+                edges: vec![],
+            });
+        }
+        self.pending.last_mut().unwrap().value = Some(value);
+        self.last_key = key.to_vec();
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> StaticTrie<V> {
+        self.freeze_to(0);
+        let root = self.pending.pop().unwrap();
+        let root = self.write(root);
+        StaticTrieView {
+            nodes: self.nodes.into(),
+            edges: self.edges.into(),
+            labels: self.labels.into(),
+            values: self.values.into(),
+            root,
+        }
+    }
+
+    /// Writes out the pending nodes deeper than `depth`, attaching each to its parent.
+    fn freeze_to(&mut self, depth: usize) {
+        while self.pending.len() > depth + 1 {
+            let node = self.pending.pop().unwrap();
+            let byte = self.last_key[self.pending.len() - 1];
+            let (mut label, target) = if node.value.is_none() && node.edges.len() == 1 {
+                // Collapse the node into the edge leading to it
+                node.edges.into_iter().next().unwrap()
+            } else {
+                (vec![], self.write(node))
+            };
+            label.insert(0, byte);
+            self.pending.last_mut().unwrap().edges.push((label, target));
+        }
+    }
+
+    fn write(&mut self, node: Pending<V>) -> u32 {
+        let value = match node.value {
+            Some(v) => {
+                self.values.push(v);
+                self.values.len() as u32 - 1
+            }
+            None => NO_VALUE,
+        };
+        let edges_start = self.edges.len() as u32;
+        let edges_len = node.edges.len() as u32;
+        for (label, target) in node.edges {
+            self.edges.push(Edge {
+                label_start: self.labels.len() as u32,
+                label_len: label.len() as u32,
+                target,
+            });
+            self.labels.extend(label);
+        }
+        self.nodes.push(Node {
+            edges_start,
+            edges_len,
+            value,
+        });
+        self.nodes.len() as u32 - 1
+    }
+}
+
+impl<V> Default for StaticTrieBuilder<V> {
+    fn default() -> Self {
+        StaticTrieBuilder::new()
+    }
+}
+
+/// An iterator over the entries of a `StaticTrie` in ascending key order.
+#[derive(Debug)]
+pub struct Iter<'a, V> {
+    trie: &'a StaticTrieView<'a, V>,
+    key: Vec<u8>,
+    stack: Vec<Frame>,
+}
+
+#[derive(Debug)]
+struct Frame {
+    node: u32,
+    next_edge: u32,
+    // The length of the key leading to this node
+    key_len: usize,
+    visited: bool,
+}
+
+impl<'a, V> Iter<'a, V> {
+    /// Like `next`, but lends the key from the iterator's buffer instead of copying it.
+    pub fn next_ref(&mut self) -> Option<(&[u8], &'a V)> {
+        let trie = self.trie;
+        loop {
+            let frame = self.stack.last_mut()?;
+            let node = &trie.nodes[frame.node as usize];
+            self.key.truncate(frame.key_len);
+            if !frame.visited {
+                frame.visited = true;
+                if let Some(v) = trie.value_of(node) {
+                    return Some((&self.key, v));
+                }
+            }
+            if frame.next_edge < node.edges_len {
+                let edge = &trie.edges_of(node)[frame.next_edge as usize];
+                frame.next_edge += 1;
+                self.key.extend_from_slice(trie.label(edge));
+                self.stack.push(Frame {
+                    node: edge.target,
+                    next_edge: 0,
+                    key_len: self.key.len(),
+                    visited: false,
+                });
+            } else {
+                self.stack.pop();
+            }
+        }
+    }
+}
+
+impl<'a, V> Iterator for Iter<'a, V> {
+    type Item = (Vec<u8>, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_ref().map(|(k, v)| (k.to_vec(), v))
+    }
+}
+
+/// Labels each node with the bytes of the edge leading to it and any value stored there.
+#[cfg(feature = "viz")]
+impl<V: std::fmt::Debug> Visualize for StaticTrieView<'_, V> {
+    type Key = [u8];
+
+    fn view(&self, search: Option<&[u8]>) -> Option<viz::Node> {
+        if self.is_empty() {
+            return None;
+        }
+        Some(self.view_node(self.root, "(root)".to_string(), search))
+    }
+}
+
+#[cfg(feature = "viz")]
+impl<V: std::fmt::Debug> StaticTrieView<'_, V> {
+    fn view_node(&self, index: u32, mut label: String, search: Option<&[u8]>) -> viz::Node {
+        let node = &self.nodes[index as usize];
+        if let Some(v) = self.value_of(node) {
+            write!(label, " = {:?}", v).unwrap();
+        }
+        let children = self
+            .edges_of(node)
+            .iter()
+            .map(|edge| {
+                let edge_label = self.label(edge);
+                let search = search.and_then(|s| s.strip_prefix(edge_label));
+                let label = format!("{:?}", String::from_utf8_lossy(edge_label));
+                Some(self.view_node(edge.target, label, search))
+            })
+            .collect();
+        viz::Node {
+            label,
+            highlighted: search.is_some(),
+            children,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use quickcheck::quickcheck;
+
+    use super::{StaticTrie, StaticTrieBuilder, StaticTrieView};
+    use crate::layout::LoadError;
+
+    #[test]
+    fn build_and_lookup() {
+        let words = ["", "car", "card", "care", "cart", "dog"];
+        let mut builder = StaticTrieBuilder::new();
+        for (i, w) in words.iter().enumerate() {
+            builder.insert(w.as_bytes(), i).unwrap();
+        }
+        assert_eq!(builder.insert(b"cat", 9), Err(9));
+        let trie = builder.finish();
+        assert_eq!(trie.len(), 6);
+        assert_eq!(trie.get(b"card"), Some(&2));
+        assert_eq!(trie.get(b""), Some(&0));
+        assert_eq!(trie.get(b"ca"), None);
+        assert_eq!(trie.get(b"dogs"), None);
+        // The root, "car" and its four children and "dog"
+        assert_eq!(trie.node_count(), 6);
+        let mut iter = trie.iter();
+        let mut keys = vec![];
+        while let Some((k, _)) = iter.next_ref() {
+            keys.push(String::from_utf8(k.to_vec()).unwrap());
+        }
+        assert_eq!(keys, words);
+    }
+
+    #[test]
+    fn rejects_duplicates() {
+        let mut builder = StaticTrieBuilder::new();
+        builder.insert(b"a", 1).unwrap();
+        assert_eq!(builder.insert(b"a", 2), Err(2));
+        assert!(StaticTrieBuilder::<()>::new().finish().is_empty());
+    }
+
+    #[test]
+    fn write_and_load() {
+        let mut builder = StaticTrieBuilder::new();
+        for (i, w) in ["", "car", "card", "care", "dog"].iter().enumerate() {
+            builder.insert(w.as_bytes(), i as u16).unwrap();
+        }
+        let trie = builder.finish();
+        let mut bytes = vec![];
+        trie.write_to(&mut bytes).unwrap();
+        let loaded = StaticTrieView::<u16>::load(&bytes).unwrap();
+        assert_eq!(loaded.node_count(), trie.node_count());
+        assert!(loaded.iter().eq(trie.iter()));
+        assert_eq!(loaded.get(b"care"), Some(&3));
+        assert_eq!(loaded.get(b"ca"), None);
+        let owned: StaticTrie<_> = loaded.into_owned();
+        assert!(owned.iter().eq(trie.iter()));
+        // Point the root past the last node
+        let mut corrupt = bytes.clone();
+        corrupt[16..24].copy_from_slice(&100u64.to_le_bytes());
+        assert_eq!(
+            StaticTrieView::<u16>::load(&corrupt).err(),
+            Some(LoadError::Corrupt("root out of bounds"))
+        );
+    }
+
+    #[test]
+    fn prop_matches_btree_map() {
+        fn p(keys: Vec<Vec<u8>>, probes: Vec<Vec<u8>>) -> bool {
+            // A small alphabet produces more shared prefixes
+            let small = |k: Vec<u8>| k.into_iter().map(|b| b % 3).collect::<Vec<_>>();
+            let model = keys
+                .into_iter()
+                .map(small)
+                .enumerate()
+                .map(|(i, k)| (k, i))
+                .collect::<BTreeMap<_, _>>();
+            let mut builder = StaticTrieBuilder::new();
+            for (k, v) in &model {
+                builder.insert(k, *v).unwrap();
+            }
+            let trie = builder.finish();
+            trie.len() == model.len()
+                && trie.iter().eq(model.iter().map(|(k, v)| (k.clone(), v)))
+                && probes
+                    .into_iter()
+                    .map(small)
+                    .all(|k| trie.get(&k) == model.get(&k))
+        }
+        quickcheck(p as fn(Vec<Vec<u8>>, Vec<Vec<u8>>) -> bool)
+    }
+}
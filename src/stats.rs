@@ -0,0 +1,270 @@
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, VecDeque},
+};
+
+use crate::heap::Heap;
+
+/// Tracks the median of a stream of values, optionally over a sliding window of the most
+/// recent ones. The smaller half of the values is kept in a max-heap and the larger half in
+/// a min-heap, so the median is always at the top of one of them and inserting is
+/// O(log n). Values which leave the window are deleted lazily once they reach the top of
+/// their heap.
+#[derive(Debug, Clone)]
+pub struct RunningMedian<T> {
+    low: Heap<Reverse<T>>,
+    high: Heap<T>,
+    // The number of live values in each heap. `low` holds as many as `high`, or one more
+    low_len: usize,
+    high_len: usize,
+    // Values which have left the window but are still in a heap
+    pending: BTreeMap<T, usize>,
+    pending_len: usize,
+    window: VecDeque<T>,
+    window_size: Option<usize>,
+}
+
+impl<T: Ord + Clone> RunningMedian<T> {
+    pub fn new() -> Self {
+        RunningMedian {
+            low: Heap::new(),
+            high: Heap::new(),
+            low_len: 0,
+            high_len: 0,
+            pending: BTreeMap::new(),
+            pending_len: 0,
+            window: VecDeque::new(),
+            window_size: None,
+        }
+    }
+
+    /// Creates a tracker for the median of the last `size` values. Panics if `size` is zero.
+    pub fn with_window(size: usize) -> Self {
+        assert!(size > 0, "window must be positive");
+        RunningMedian {
+            window_size: Some(size),
+            ..RunningMedian::new()
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.low_len + self.high_len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn window_size(&self) -> Option<usize> {
+        self.window_size
+    }
+
+    pub fn insert(&mut self, x: T) {
+        if let Some(size) = self.window_size {
+            self.window.push_back(x.clone());
+            if self.window.len() > size {
+                let oldest = self.window.pop_front().unwrap();
+                self.evict(oldest);
+            }
+        }
+        // `low` can be empty while `high` isn't once the window has evicted its top
+        let above = match (self.low.peek(), self.high.peek()) {
+            (Some(Reverse(top)), _) => x > *top,
+            (None, Some(top)) => x >= *top,
+            (None, None) => false,
+        };
+        if above {
+            self.high.push(x);
+            self.high_len += 1;
+        } else {
+            self.low.push(Reverse(x));
+            self.low_len += 1;
+        }
+        self.rebalance();
+    }
+
+    /// Returns the median, or the lower of the two middle values when there is an even
+    /// number of them.
+    pub fn median(&self) -> Option<&T> {
+        self.low.peek().map(|Reverse(x)| x)
+    }
+
+    /// Returns the two middle values, which are the same value when there is an odd number
+    /// of them.
+    pub fn medians(&self) -> Option<(&T, &T)> {
+        let low = self.median()?;
+        if self.low_len > self.high_len {
+            Some((low, low))
+        } else {
+            Some((low, self.high.peek().unwrap()))
+        }
+    }
+
+    /// Marks a value which left the window for deletion, taking it off the top of its heap
+    /// right away if it is there.
+    fn evict(&mut self, x: T) {
+        // Both heaps are pruned after every change, so their tops are live values
+        let in_low = matches!(self.low.peek(), Some(Reverse(top)) if x <= *top);
+        if in_low {
+            self.low_len -= 1;
+        } else {
+            self.high_len -= 1;
+        }
+        *self.pending.entry(x).or_insert(0) += 1;
+        self.pending_len += 1;
+        if in_low {
+            self.prune_low();
+        } else {
+            self.prune_high();
+        }
+    }
+
+    /// Moves the top of one heap to the other until their sizes are balanced.
+    fn rebalance(&mut self) {
+        if self.low_len > self.high_len + 1 {
+            let Reverse(x) = self.low.pop().unwrap();
+            self.high.push(x);
+            self.low_len -= 1;
+            self.high_len += 1;
+            self.prune_low();
+        } else if self.high_len > self.low_len {
+            let x = self.high.pop().unwrap();
+            self.low.push(Reverse(x));
+            self.high_len -= 1;
+            self.low_len += 1;
+            self.prune_high();
+        }
+        // Deleted values which never reach the top are dropped by rebuilding the heaps
+        if self.pending_len > self.len() {
+            self.rebuild();
+        }
+    }
+
+    fn prune_low(&mut self) {
+        while let Some(Reverse(top)) = self.low.peek() {
+            if !take_pending(&mut self.pending, top) {
+                break;
+            }
+            self.pending_len -= 1;
+            self.low.pop();
+        }
+    }
+
+    fn prune_high(&mut self) {
+        while let Some(top) = self.high.peek() {
+            if !take_pending(&mut self.pending, top) {
+                break;
+            }
+            self.pending_len -= 1;
+            self.high.pop();
+        }
+    }
+
+    fn rebuild(&mut self) {
+        let mut values = vec![];
+        while let Some(Reverse(x)) = self.low.pop() {
+            values.push(x);
+        }
+        while let Some(x) = self.high.pop() {
+            values.push(x);
+        }
+        values.retain(|x| !take_pending(&mut self.pending, x));
+        self.pending_len = 0;
+        values.sort();
+        let high = values.split_off(values.len().div_ceil(2));
+        self.low_len = values.len();
+        self.high_len = high.len();
+        for x in values {
+            self.low.push(Reverse(x));
+        }
+        for x in high {
+            self.high.push(x);
+        }
+    }
+}
+
+/// Consumes one pending deletion of `x`, if there is one.
+fn take_pending<T: Ord>(pending: &mut BTreeMap<T, usize>, x: &T) -> bool {
+    let Some(count) = pending.get_mut(x) else {
+        return false;
+    };
+    *count -= 1;
+    if *count == 0 {
+        pending.remove(x);
+    }
+    true
+}
+
+impl<T: Ord + Clone> Default for RunningMedian<T> {
+    fn default() -> Self {
+        RunningMedian::new()
+    }
+}
+
+impl<T: Ord + Clone> Extend<T> for RunningMedian<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for x in iter {
+            self.insert(x);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use quickcheck::quickcheck;
+
+    use super::RunningMedian;
+
+    #[test]
+    fn median_of_stream() {
+        let mut median = RunningMedian::new();
+        assert_eq!(median.median(), None);
+        median.insert(5);
+        assert_eq!(median.medians(), Some((&5, &5)));
+        median.insert(1);
+        assert_eq!(median.medians(), Some((&1, &5)));
+        median.extend([9, 3]);
+        assert_eq!(median.medians(), Some((&3, &5)));
+        median.insert(7);
+        assert_eq!(median.median(), Some(&5));
+        assert_eq!(median.len(), 5);
+    }
+
+    #[test]
+    fn sliding_window() {
+        let mut median = RunningMedian::with_window(3);
+        median.extend([1, 2, 3, 100, 200]);
+        assert_eq!(median.len(), 3);
+        assert_eq!(median.median(), Some(&100));
+        median.extend([0, 0]);
+        assert_eq!(median.median(), Some(&0));
+    }
+
+    #[test]
+    fn rebuild_drops_buried_values() {
+        let mut median = RunningMedian::with_window(4);
+        // Evicted values sit at the bottom of the max-heap and never reach its top
+        median.extend(0..1000);
+        assert!(median.low.size() + median.high.size() <= 2 * 4 + 1);
+        assert_eq!(median.medians(), Some((&997, &998)));
+    }
+
+    #[test]
+    fn prop_matches_sorted_window() {
+        fn p(values: Vec<i8>, size: u8) -> bool {
+            let size = size as usize % 8 + 1;
+            let mut median = RunningMedian::with_window(size);
+            for (i, &x) in values.iter().enumerate() {
+                median.insert(x);
+                let mut window = values[(i + 1).saturating_sub(size)..=i].to_vec();
+                window.sort();
+                let expected = (&window[(window.len() - 1) / 2], &window[window.len() / 2]);
+                if median.medians() != Some(expected) {
+                    return false;
+                }
+            }
+            true
+        }
+        quickcheck(p as fn(Vec<i8>, u8) -> bool);
+    }
+}
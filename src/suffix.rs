@@ -0,0 +1,220 @@
+use std::cmp::Ordering;
+
+/// A suffix array lists the starting positions of every suffix of a text in sorted order,
+/// so all occurrences of a pattern form one contiguous run found by binary search.
+/// It is built in O(n log n) by prefix doubling with counting sorts, and is accompanied
+/// by the LCP array, where `lcp()[i]` is the length of the longest common prefix of the
+/// suffixes at `suffixes()[i - 1]` and `suffixes()[i]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SuffixArray {
+    text: Vec<u8>,
+    suffixes: Vec<usize>,
+    lcp: Vec<usize>,
+}
+
+impl SuffixArray {
+    pub fn new(text: &[u8]) -> Self {
+        let suffixes = build(text);
+        let lcp = kasai(text, &suffixes);
+        SuffixArray {
+            text: text.to_vec(),
+            suffixes,
+            lcp,
+        }
+    }
+
+    pub fn text(&self) -> &[u8] {
+        &self.text
+    }
+
+    pub fn len(&self) -> usize {
+        self.text.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.text.is_empty()
+    }
+
+    pub fn suffixes(&self) -> &[usize] {
+        &self.suffixes
+    }
+
+    pub fn lcp(&self) -> &[usize] {
+        &self.lcp
+    }
+
+    /// Returns the position of every occurrence of the pattern in ascending order.
+    /// An empty pattern occurs at every position.
+    pub fn find(&self, pattern: &[u8]) -> Vec<usize> {
+        let mut positions = self.suffixes[self.matching(pattern)].to_vec();
+        positions.sort_unstable();
+        positions
+    }
+
+    pub fn count(&self, pattern: &[u8]) -> usize {
+        self.matching(pattern).len()
+    }
+
+    pub fn contains(&self, pattern: &[u8]) -> bool {
+        self.count(pattern) > 0
+    }
+
+    /// The longest substring which occurs at least twice, or None if no byte repeats.
+    pub fn longest_repeated_substring(&self) -> Option<&[u8]> {
+        let (i, len) = self
+            .lcp
+            .iter()
+            .copied()
+            .enumerate()
+            .max_by_key(|(_, len)| *len)?;
+        (len > 0).then(|| &self.text[self.suffixes[i]..self.suffixes[i] + len])
+    }
+
+    /// The range of suffixes which start with the pattern.
+    fn matching(&self, pattern: &[u8]) -> std::ops::Range<usize> {
+        // Compares a suffix to the pattern, treating suffixes which start with it as equal
+        let compare = |&start: &usize| {
+            let suffix = &self.text[start..];
+            let prefix = &suffix[..suffix.len().min(pattern.len())];
+            prefix.cmp(pattern)
+        };
+        let lo = self
+            .suffixes
+            .partition_point(|s| compare(s) == Ordering::Less);
+        let hi = self
+            .suffixes
+            .partition_point(|s| compare(s) != Ordering::Greater);
+        lo..hi
+    }
+}
+
+fn build(text: &[u8]) -> Vec<usize> {
+    let n = text.len();
+    if n == 0 {
+        return vec![];
+    }
+    let buckets = n.max(256);
+    let mut rank = text.iter().map(|&b| b as usize).collect::<Vec<_>>();
+    let mut suffixes = counting_sort(&(0..n).collect::<Vec<_>>(), &rank, buckets);
+    let mut next_rank = vec![0; n];
+    let mut k = 1;
+    loop {
+        // Each step sorts the suffixes by their first 2k bytes, as a pair of ranks of k bytes.
+        // Suffixes with no second half sort first, then the rest in order of their second half.
+        let by_second = (n.saturating_sub(k)..n)
+            .chain(suffixes.iter().filter(|&&s| s >= k).map(|&s| s - k))
+            .collect::<Vec<_>>();
+        suffixes = counting_sort(&by_second, &rank, buckets);
+
+        let key = |i: usize| (rank[i], rank.get(i + k).map_or(0, |r| r + 1));
+        next_rank[suffixes[0]] = 0;
+        for w in 1..n {
+            let (prev, curr) = (suffixes[w - 1], suffixes[w]);
+            next_rank[curr] = next_rank[prev] + (key(prev) != key(curr)) as usize;
+        }
+        std::mem::swap(&mut rank, &mut next_rank);
+        if rank[suffixes[n - 1]] == n - 1 || k >= n {
+            return suffixes;
+        }
+        k *= 2;
+    }
+}
+
+/// Stably sorts the positions by their rank.
+fn counting_sort(positions: &[usize], rank: &[usize], buckets: usize) -> Vec<usize> {
+    let mut starts = vec![0; buckets + 1];
+    for &p in positions {
+        starts[rank[p] + 1] += 1;
+    }
+    for i in 1..starts.len() {
+        starts[i] += starts[i - 1];
+    }
+    let mut sorted = vec![0; positions.len()];
+    for &p in positions {
+        sorted[starts[rank[p]]] = p;
+        starts[rank[p]] += 1;
+    }
+    sorted
+}
+
+/// Computes the LCP array in O(n) with Kasai's algorithm.
+fn kasai(text: &[u8], suffixes: &[usize]) -> Vec<usize> {
+    let n = text.len();
+    let mut position = vec![0; n];
+    for (i, &s) in suffixes.iter().enumerate() {
+        position[s] = i;
+    }
+    let mut lcp = vec![0; n];
+    let mut h = 0;
+    for start in 0..n {
+        if position[start] == 0 {
+            h = 0;
+            continue;
+        }
+        let prev = suffixes[position[start] - 1];
+        while start + h < n && prev + h < n && text[start + h] == text[prev + h] {
+            h += 1;
+        }
+        lcp[position[start]] = h;
+        // The next suffix shares all but the first of these bytes with its predecessor
+        h = h.saturating_sub(1);
+    }
+    lcp
+}
+
+#[cfg(test)]
+mod test {
+    use quickcheck::quickcheck;
+
+    use super::SuffixArray;
+
+    #[test]
+    fn banana() {
+        let sa = SuffixArray::new(b"banana");
+        assert_eq!(sa.suffixes(), &[5, 3, 1, 0, 4, 2]);
+        assert_eq!(sa.lcp(), &[0, 1, 3, 0, 0, 2]);
+        assert_eq!(sa.find(b"ana"), vec![1, 3]);
+        assert_eq!(sa.find(b"nab"), Vec::<usize>::new());
+        assert_eq!(sa.count(b"a"), 3);
+        assert_eq!(sa.longest_repeated_substring(), Some(&b"ana"[..]));
+    }
+
+    #[test]
+    fn empty() {
+        let sa = SuffixArray::new(b"");
+        assert!(sa.suffixes().is_empty());
+        assert!(!sa.contains(b"a"));
+        assert_eq!(sa.longest_repeated_substring(), None);
+    }
+
+    #[test]
+    fn prop_sorted_suffixes() {
+        fn p(text: Vec<u8>) -> bool {
+            // A small alphabet produces more repeats
+            let text = text.into_iter().map(|b| b % 4).collect::<Vec<_>>();
+            let sa = SuffixArray::new(&text);
+            let mut expected = (0..text.len()).collect::<Vec<_>>();
+            expected.sort_by_key(|&i| &text[i..]);
+            sa.suffixes() == expected
+        }
+        quickcheck(p as fn(Vec<u8>) -> bool)
+    }
+
+    #[test]
+    fn prop_find_matches_scan() {
+        fn p(text: Vec<u8>, pattern: Vec<u8>) -> bool {
+            let text = text.into_iter().map(|b| b % 3).collect::<Vec<_>>();
+            let pattern = pattern
+                .into_iter()
+                .take(3)
+                .map(|b| b % 3)
+                .collect::<Vec<_>>();
+            let sa = SuffixArray::new(&text);
+            let expected = (0..text.len())
+                .filter(|&i| text[i..].starts_with(&pattern))
+                .collect::<Vec<_>>();
+            sa.find(&pattern) == expected
+        }
+        quickcheck(p as fn(Vec<u8>, Vec<u8>) -> bool)
+    }
+}
@@ -0,0 +1,243 @@
+use std::{
+    sync::{Condvar, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    batch::BatchQueue,
+    clock::{Clock, SystemClock},
+};
+
+/// A `BatchQueue` shared between threads, whose consumers block until a batch is ready.
+/// Once the queue is closed, consumers drain whatever is left in batches and then get
+/// `None`. With a `MockClock`, a batch's age only grows as the clock is advanced, and
+/// consumers notice it the next time they check rather than being woken.
+#[derive(Debug)]
+pub struct BlockingBatchQueue<T, C = SystemClock> {
+    state: (Mutex<State<T, C>>, Condvar),
+}
+
+#[derive(Debug)]
+struct State<T, C> {
+    queue: BatchQueue<T, C>,
+    // Items pushed before the last flush which have yet to be taken
+    flushed: usize,
+    closed: bool,
+}
+
+impl<T> BlockingBatchQueue<T> {
+    pub fn new(max_size: usize) -> Self {
+        BlockingBatchQueue::from_queue(BatchQueue::new(max_size))
+    }
+
+    pub fn with_max_age(max_size: usize, max_age: Duration) -> Self {
+        BlockingBatchQueue::from_queue(BatchQueue::with_max_age(max_size, max_age))
+    }
+}
+
+impl<T, C: Clock> BlockingBatchQueue<T, C> {
+    pub fn with_clock(max_size: usize, clock: C) -> Self {
+        BlockingBatchQueue::from_queue(BatchQueue::with_clock(max_size, clock))
+    }
+
+    pub fn with_max_age_and_clock(max_size: usize, max_age: Duration, clock: C) -> Self {
+        BlockingBatchQueue::from_queue(BatchQueue::with_max_age_and_clock(max_size, max_age, clock))
+    }
+
+    fn from_queue(queue: BatchQueue<T, C>) -> Self {
+        BlockingBatchQueue {
+            state: (
+                Mutex::new(State {
+                    queue,
+                    flushed: 0,
+                    closed: false,
+                }),
+                Condvar::new(),
+            ),
+        }
+    }
+
+    /// Adds an item, or hands it back if the queue has been closed.
+    pub fn push(&self, item: T) -> Result<(), T> {
+        let (lock, cvar) = &self.state;
+        let mut state = lock.lock().unwrap();
+        if state.closed {
+            return Err(item);
+        }
+        state.queue.push(item);
+        // Later items don't move the deadline set by the first, so consumers only need
+        // waking to learn of that deadline or once the batch fills
+        if state.queue.len() >= state.queue.max_size() || state.queue.len() == 1 {
+            cvar.notify_one();
+        }
+        Ok(())
+    }
+
+    /// Releases every item pushed so far, in batches, without waiting for them to fill.
+    pub fn flush(&self) {
+        let (lock, cvar) = &self.state;
+        let mut state = lock.lock().unwrap();
+        state.flushed = state.queue.len();
+        cvar.notify_all();
+    }
+
+    /// Stops accepting items. The items already pushed are still released in batches.
+    pub fn close(&self) {
+        let (lock, cvar) = &self.state;
+        lock.lock().unwrap().closed = true;
+        cvar.notify_all();
+    }
+
+    pub fn is_closed(&self) -> bool {
+        let (lock, _) = &self.state;
+        lock.lock().unwrap().closed
+    }
+
+    /// Takes the next batch if one is ready, without blocking.
+    pub fn try_next_batch(&self) -> Option<Vec<T>> {
+        let (lock, _) = &self.state;
+        lock.lock().unwrap().ready_batch()
+    }
+
+    /// Blocks until a batch is ready and takes it, or returns `None` once the queue is
+    /// closed and empty.
+    pub fn next_batch(&self) -> Option<Vec<T>> {
+        self.next_batch_until(None)
+    }
+
+    /// Like `next_batch`, but gives up if no batch is ready within `timeout`.
+    pub fn next_batch_timeout(&self, timeout: Duration) -> Option<Vec<T>> {
+        let (lock, _) = &self.state;
+        let now = lock.lock().unwrap().queue.now();
+        self.next_batch_until(Some(now + timeout))
+    }
+
+    fn next_batch_until(&self, timeout: Option<Instant>) -> Option<Vec<T>> {
+        let (lock, cvar) = &self.state;
+        let mut state = lock.lock().unwrap();
+        loop {
+            if let Some(batch) = state.ready_batch() {
+                return Some(batch);
+            }
+            if state.closed {
+                return None;
+            }
+            // Wake for whichever comes first of the batch's age and the caller's timeout
+            let wake = match (state.queue.deadline(), timeout) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, b) => a.or(b),
+            };
+            state = match wake {
+                Some(wake) => {
+                    let now = state.queue.now();
+                    if timeout.is_some_and(|timeout| timeout <= now) {
+                        return None;
+                    }
+                    cvar.wait_timeout(state, wake.saturating_duration_since(now))
+                        .unwrap()
+                        .0
+                }
+                None => cvar.wait(state).unwrap(),
+            };
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        let (lock, _) = &self.state;
+        lock.lock().unwrap().queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T, C: Clock> State<T, C> {
+    fn ready_batch(&mut self) -> Option<Vec<T>> {
+        let batch = if self.flushed > 0 || self.closed {
+            self.queue.flush()
+        } else {
+            self.queue.pop_batch()
+        }?;
+        self.flushed = self.flushed.saturating_sub(batch.len());
+        Some(batch)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{sync::Arc, thread, time::Duration};
+
+    use super::BlockingBatchQueue;
+    use crate::clock::MockClock;
+
+    #[test]
+    fn flush_and_close() {
+        let queue = BlockingBatchQueue::new(2);
+        for i in 0..5 {
+            queue.push(i).unwrap();
+        }
+        assert_eq!(queue.next_batch(), Some(vec![0, 1]));
+        assert_eq!(queue.next_batch(), Some(vec![2, 3]));
+        assert_eq!(queue.try_next_batch(), None);
+        queue.flush();
+        assert_eq!(queue.next_batch(), Some(vec![4]));
+        queue.push(5).unwrap();
+        queue.close();
+        assert_eq!(queue.push(6), Err(6));
+        assert_eq!(queue.next_batch(), Some(vec![5]));
+        assert_eq!(queue.next_batch(), None);
+    }
+
+    #[test]
+    fn timeout_expires() {
+        let queue = BlockingBatchQueue::new(2);
+        queue.push(1).unwrap();
+        assert_eq!(queue.next_batch_timeout(Duration::from_millis(10)), None);
+        assert_eq!(queue.len(), 1);
+    }
+
+    #[test]
+    fn releases_by_age() {
+        let clock = MockClock::new();
+        let queue =
+            BlockingBatchQueue::with_max_age_and_clock(100, Duration::from_millis(10), &clock);
+        queue.push(1).unwrap();
+        assert_eq!(queue.try_next_batch(), None);
+        clock.advance(Duration::from_millis(10));
+        assert_eq!(queue.next_batch(), Some(vec![1]));
+    }
+
+    #[test]
+    fn consumer_drains_producers() {
+        let queue = Arc::new(BlockingBatchQueue::new(8));
+        let producers = (0..4)
+            .map(|t| {
+                let queue = queue.clone();
+                thread::spawn(move || {
+                    for i in 0..100 {
+                        queue.push(t * 100 + i).unwrap();
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        let consumer = thread::spawn({
+            let queue = queue.clone();
+            move || {
+                let mut items = vec![];
+                while let Some(batch) = queue.next_batch() {
+                    assert!(batch.len() <= 8);
+                    items.extend(batch);
+                }
+                items
+            }
+        });
+        for producer in producers {
+            producer.join().unwrap();
+        }
+        queue.close();
+        let mut items = consumer.join().unwrap();
+        items.sort();
+        assert_eq!(items, (0..400).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,640 @@
+use std::{
+    fmt,
+    mem::MaybeUninit,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    capacity::Capacity,
+    sync::primitives::{fence, Arc, AtomicBool, AtomicUsize, Condvar, Mutex, Ordering, UnsafeCell},
+};
+
+/// Creates a channel which holds at most `capacity` messages, returning its sending and
+/// receiving halves. Both halves can be cloned to add producers and consumers. Once every
+/// sender or every receiver has been dropped the channel is disconnected: sends fail, and
+/// receives drain the messages already sent before failing. Panics if `capacity` is zero.
+pub fn bounded<T>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+    assert!(capacity > 0, "capacity must be positive");
+    let channel = Arc::new(Channel {
+        slots: (0..capacity)
+            .map(|i| Slot {
+                stamp: AtomicUsize::new(free_stamp(i)),
+                value: UnsafeCell::new(MaybeUninit::uninit()),
+            })
+            .collect(),
+        head: AtomicUsize::new(0),
+        tail: AtomicUsize::new(0),
+        senders: AtomicUsize::new(1),
+        receivers: AtomicUsize::new(1),
+        disconnected: AtomicBool::new(false),
+        not_empty: Signal::new(),
+        not_full: Signal::new(),
+    });
+    (
+        Sender {
+            channel: channel.clone(),
+        },
+        Receiver { channel },
+    )
+}
+
+/// A bounded multi-producer multi-consumer queue.
+/// Messages pass through a ring of slots, each stamped with the position it will next be
+/// written or read at, so senders and receivers claim positions with a compare-and-swap
+/// and never take a lock unless they have to block.
+#[derive(Debug)]
+struct Channel<T> {
+    slots: Box<[Slot<T>]>,
+    // The next position to receive from
+    head: AtomicUsize,
+    // The next position to send to
+    tail: AtomicUsize,
+    senders: AtomicUsize,
+    receivers: AtomicUsize,
+    disconnected: AtomicBool,
+    not_empty: Signal,
+    not_full: Signal,
+}
+
+#[derive(Debug)]
+struct Slot<T> {
+    // `free_stamp(p)` once the slot is ready to be sent to at position `p`, and one more once
+    // the message sent at `p` is ready to be received
+    stamp: AtomicUsize,
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+
+fn free_stamp(position: usize) -> usize {
+    position.wrapping_mul(2)
+}
+
+/// How far a slot's stamp is ahead of the one expected, which is negative while the slot
+/// is still a lap behind.
+fn stamp_lead(stamp: usize, expected: usize) -> isize {
+    stamp.wrapping_sub(expected) as isize
+}
+
+unsafe impl<T: Send> Send for Channel<T> {}
+unsafe impl<T: Send> Sync for Channel<T> {}
+
+impl<T> Channel<T> {
+    fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    fn len(&self) -> usize {
+        let head = self.head.load(Ordering::Acquire);
+        let tail = self.tail.load(Ordering::Acquire);
+        // The positions are read at different times, so clamp what they imply
+        (tail.wrapping_sub(head) as isize).clamp(0, self.capacity() as isize) as usize
+    }
+
+    fn is_disconnected(&self) -> bool {
+        self.disconnected.load(Ordering::SeqCst)
+    }
+
+    /// Whether the slot at the tail is free, or has been claimed since, so a send could
+    /// make progress. A slot which a receiver has claimed but not yet freed isn't, however
+    /// `len` counts it.
+    fn can_send(&self) -> bool {
+        let tail = self.tail.load(Ordering::Acquire);
+        let slot = &self.slots[tail % self.capacity()];
+        stamp_lead(slot.stamp.load(Ordering::Acquire), free_stamp(tail)) >= 0
+    }
+
+    /// Whether the message at the head has been written, or received since, so a receive
+    /// could make progress. Like `can_send`, this looks at the slot rather than at `len`.
+    fn can_recv(&self) -> bool {
+        let head = self.head.load(Ordering::Acquire);
+        let slot = &self.slots[head % self.capacity()];
+        let expected = free_stamp(head).wrapping_add(1);
+        stamp_lead(slot.stamp.load(Ordering::Acquire), expected) >= 0
+    }
+
+    /// Hands the value back if the channel is full.
+    fn try_send(&self, value: T) -> Result<(), T> {
+        let mut tail = self.tail.load(Ordering::Relaxed);
+        loop {
+            let slot = &self.slots[tail % self.capacity()];
+            let lead = stamp_lead(slot.stamp.load(Ordering::Acquire), free_stamp(tail));
+            if lead == 0 {
+                let next = tail.wrapping_add(1);
+                match self.tail.compare_exchange_weak(
+                    tail,
+                    next,
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => {
+                        // Claiming the position gives us the slot until we stamp it
+                        slot.value
+                            .with_mut(|v| unsafe { v.write(MaybeUninit::new(value)) });
+                        slot.stamp
+                            .store(free_stamp(tail).wrapping_add(1), Ordering::Release);
+                        self.not_empty.notify();
+                        return Ok(());
+                    }
+                    Err(current) => tail = current,
+                }
+            } else if lead < 0 {
+                // The message from a lap ago hasn't been received yet
+                return Err(value);
+            } else {
+                // Another sender claimed this position
+                tail = self.tail.load(Ordering::Relaxed);
+            }
+        }
+    }
+
+    fn try_recv(&self) -> Option<T> {
+        let mut head = self.head.load(Ordering::Relaxed);
+        loop {
+            let slot = &self.slots[head % self.capacity()];
+            let expected = free_stamp(head).wrapping_add(1);
+            let lead = stamp_lead(slot.stamp.load(Ordering::Acquire), expected);
+            if lead == 0 {
+                let next = head.wrapping_add(1);
+                match self.head.compare_exchange_weak(
+                    head,
+                    next,
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => {
+                        let value = slot.value.with(|v| unsafe { v.read().assume_init() });
+                        let lap = head.wrapping_add(self.capacity());
+                        slot.stamp.store(free_stamp(lap), Ordering::Release);
+                        self.not_full.notify();
+                        return Some(value);
+                    }
+                    Err(current) => head = current,
+                }
+            } else if lead < 0 {
+                // Nothing has been sent to this position yet
+                return None;
+            } else {
+                head = self.head.load(Ordering::Relaxed);
+            }
+        }
+    }
+
+    fn send_until(&self, mut value: T, deadline: Option<Instant>) -> Result<(), SendError<T>> {
+        loop {
+            if self.is_disconnected() {
+                return Err(SendError::Disconnected(value));
+            }
+            value = match self.try_send(value) {
+                Ok(()) => return Ok(()),
+                Err(value) => value,
+            };
+            let ready = || self.can_send() || self.is_disconnected();
+            if !self.not_full.wait_until(ready, deadline) {
+                return Err(SendError::Timeout(value));
+            }
+        }
+    }
+
+    fn recv_until(&self, deadline: Option<Instant>) -> Result<T, RecvError> {
+        loop {
+            // Anything sent before the disconnect is visible once it's observed
+            let disconnected = self.is_disconnected();
+            if let Some(value) = self.try_recv() {
+                return Ok(value);
+            }
+            if disconnected {
+                return Err(RecvError::Disconnected);
+            }
+            let ready = || self.can_recv() || self.is_disconnected();
+            if !self.not_empty.wait_until(ready, deadline) {
+                return Err(RecvError::Timeout);
+            }
+        }
+    }
+
+    fn disconnect(&self) {
+        self.disconnected.store(true, Ordering::SeqCst);
+        self.not_empty.notify_all();
+        self.not_full.notify_all();
+    }
+}
+
+impl<T> Drop for Channel<T> {
+    fn drop(&mut self) {
+        while self.try_recv().is_some() {}
+    }
+}
+
+/// The sending half of a channel created by `bounded`.
+#[derive(Debug)]
+pub struct Sender<T> {
+    channel: Arc<Channel<T>>,
+}
+
+impl<T> Sender<T> {
+    /// Blocks until there is room in the channel, handing the value back as `Disconnected`
+    /// if every receiver has been dropped.
+    pub fn send(&self, value: T) -> Result<(), SendError<T>> {
+        self.channel.send_until(value, None)
+    }
+
+    /// Like `send`, but hands the value back as `Timeout` if there's still no room after
+    /// `timeout`.
+    pub fn send_timeout(&self, value: T, timeout: Duration) -> Result<(), SendError<T>> {
+        self.channel
+            .send_until(value, Some(Instant::now() + timeout))
+    }
+
+    /// Like `send`, but hands the value back as `Full` rather than blocking.
+    pub fn try_send(&self, value: T) -> Result<(), SendError<T>> {
+        if self.channel.is_disconnected() {
+            return Err(SendError::Disconnected(value));
+        }
+        self.channel.try_send(value).map_err(SendError::Full)
+    }
+
+    /// Whether every receiver has been dropped.
+    pub fn is_disconnected(&self) -> bool {
+        self.channel.is_disconnected()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.channel.capacity()
+    }
+
+    /// How many more messages can be sent before senders block.
+    pub fn remaining_capacity(&self) -> usize {
+        Capacity::Entries(self.capacity()).remaining(self.len())
+    }
+
+    pub fn len(&self) -> usize {
+        self.channel.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.channel.senders.fetch_add(1, Ordering::Relaxed);
+        Sender {
+            channel: self.channel.clone(),
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        if self.channel.senders.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.channel.disconnect();
+        }
+    }
+}
+
+/// The receiving half of a channel created by `bounded`.
+#[derive(Debug)]
+pub struct Receiver<T> {
+    channel: Arc<Channel<T>>,
+}
+
+impl<T> Receiver<T> {
+    /// Blocks until a message arrives, or fails with `Disconnected` once every sender has
+    /// been dropped and the channel is empty.
+    pub fn recv(&self) -> Result<T, RecvError> {
+        self.channel.recv_until(None)
+    }
+
+    /// Like `recv`, but fails with `Timeout` if no message arrives within `timeout`.
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<T, RecvError> {
+        self.channel.recv_until(Some(Instant::now() + timeout))
+    }
+
+    /// Like `recv`, but fails with `Empty` rather than blocking.
+    pub fn try_recv(&self) -> Result<T, RecvError> {
+        // Anything sent before the disconnect is visible once it's observed
+        let disconnected = self.channel.is_disconnected();
+        match self.channel.try_recv() {
+            Some(value) => Ok(value),
+            None if disconnected => Err(RecvError::Disconnected),
+            None => Err(RecvError::Empty),
+        }
+    }
+
+    /// Returns an iterator which blocks for each message, and ends once every sender has
+    /// been dropped and the channel is empty.
+    pub fn iter(&self) -> impl Iterator<Item = T> + '_ {
+        std::iter::from_fn(|| self.recv().ok())
+    }
+
+    /// Whether every sender has been dropped. Messages sent before then can still be
+    /// received.
+    pub fn is_disconnected(&self) -> bool {
+        self.channel.is_disconnected()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.channel.capacity()
+    }
+
+    pub fn len(&self) -> usize {
+        self.channel.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T> Clone for Receiver<T> {
+    fn clone(&self) -> Self {
+        self.channel.receivers.fetch_add(1, Ordering::Relaxed);
+        Receiver {
+            channel: self.channel.clone(),
+        }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        if self.channel.receivers.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.channel.disconnect();
+        }
+    }
+}
+
+/// Why a send failed. The message is handed back so it isn't lost.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SendError<T> {
+    /// The channel is full, and `try_send` doesn't wait.
+    Full(T),
+    /// The channel was still full when `send_timeout` gave up.
+    Timeout(T),
+    /// Every receiver has been dropped.
+    Disconnected(T),
+}
+
+impl<T> SendError<T> {
+    /// Returns the message which wasn't sent.
+    pub fn into_inner(self) -> T {
+        match self {
+            SendError::Full(t) | SendError::Timeout(t) | SendError::Disconnected(t) => t,
+        }
+    }
+
+    pub fn is_disconnected(&self) -> bool {
+        matches!(self, SendError::Disconnected(_))
+    }
+}
+
+impl<T> fmt::Display for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SendError::Full(_) => write!(f, "channel is full"),
+            SendError::Timeout(_) => write!(f, "timed out waiting for room in the channel"),
+            SendError::Disconnected(_) => write!(f, "channel is disconnected"),
+        }
+    }
+}
+
+impl<T: fmt::Debug> std::error::Error for SendError<T> {}
+
+/// Why a receive failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecvError {
+    /// The channel is empty, and `try_recv` doesn't wait.
+    Empty,
+    /// No message arrived before `recv_timeout` gave up.
+    Timeout,
+    /// Every sender has been dropped and the channel is empty.
+    Disconnected,
+}
+
+impl fmt::Display for RecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecvError::Empty => write!(f, "channel is empty"),
+            RecvError::Timeout => write!(f, "timed out waiting for a message"),
+            RecvError::Disconnected => write!(f, "channel is empty and disconnected"),
+        }
+    }
+}
+
+impl std::error::Error for RecvError {}
+
+/// Blocks threads until the other side of the channel makes progress.
+/// Waiters are counted so that the lock is only taken to notify when someone is waiting.
+#[derive(Debug)]
+struct Signal {
+    lock: Mutex<()>,
+    cvar: Condvar,
+    waiters: AtomicUsize,
+}
+
+impl Signal {
+    fn new() -> Self {
+        Signal {
+            lock: Mutex::new(()),
+            cvar: Condvar::new(),
+            waiters: AtomicUsize::new(0),
+        }
+    }
+
+    /// Blocks until `ready` holds, returning false if the deadline passes first.
+    fn wait_until(&self, ready: impl Fn() -> bool, deadline: Option<Instant>) -> bool {
+        let mut guard = self.lock.lock().unwrap();
+        self.waiters.fetch_add(1, Ordering::SeqCst);
+        // Check again after registering, in case we raced with a notification
+        fence(Ordering::SeqCst);
+        let ready = loop {
+            if ready() {
+                break true;
+            }
+            match deadline {
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        break false;
+                    }
+                    guard = self.cvar.wait_timeout(guard, deadline - now).unwrap().0;
+                }
+                None => guard = self.cvar.wait(guard).unwrap(),
+            }
+        };
+        self.waiters.fetch_sub(1, Ordering::SeqCst);
+        ready
+    }
+
+    fn notify(&self) {
+        fence(Ordering::SeqCst);
+        if self.waiters.load(Ordering::SeqCst) > 0 {
+            // Taking the lock orders this after a waiter's check of `ready`
+            let _guard = self.lock.lock().unwrap();
+            self.cvar.notify_one();
+        }
+    }
+
+    fn notify_all(&self) {
+        let _guard = self.lock.lock().unwrap();
+        self.cvar.notify_all();
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod test {
+    use std::{sync::Arc, thread, time::Duration};
+
+    use super::{bounded, RecvError, SendError};
+
+    #[test]
+    fn try_send_and_recv() {
+        let (tx, rx) = bounded(2);
+        assert_eq!(tx.try_send(1), Ok(()));
+        assert_eq!(tx.try_send(2), Ok(()));
+        assert_eq!(tx.try_send(3), Err(SendError::Full(3)));
+        assert_eq!(tx.remaining_capacity(), 0);
+        assert_eq!(rx.try_recv(), Ok(1));
+        assert_eq!(tx.try_send(3), Ok(()));
+        assert_eq!(rx.len(), 2);
+        assert_eq!(rx.try_recv(), Ok(2));
+        assert_eq!(rx.try_recv(), Ok(3));
+        assert_eq!(rx.try_recv(), Err(RecvError::Empty));
+    }
+
+    #[test]
+    fn single_slot() {
+        let (tx, rx) = bounded(1);
+        for i in 0..3 {
+            assert_eq!(tx.try_send(i), Ok(()));
+            assert_eq!(tx.try_send(i), Err(SendError::Full(i)));
+            assert_eq!(rx.try_recv(), Ok(i));
+            assert_eq!(rx.try_recv(), Err(RecvError::Empty));
+        }
+    }
+
+    #[test]
+    fn disconnect() {
+        let (tx, rx) = bounded(4);
+        tx.send(1).unwrap();
+        tx.clone().send(2).unwrap();
+        assert!(!rx.is_disconnected());
+        drop(tx);
+        // Messages sent before the disconnect are still delivered
+        assert!(rx.is_disconnected());
+        assert_eq!(rx.iter().collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(rx.recv(), Err(RecvError::Disconnected));
+        assert_eq!(rx.try_recv(), Err(RecvError::Disconnected));
+        assert_eq!(
+            rx.recv_timeout(Duration::from_millis(10)),
+            Err(RecvError::Disconnected)
+        );
+
+        let (tx, rx) = bounded(4);
+        drop(rx);
+        assert_eq!(tx.send(1), Err(SendError::Disconnected(1)));
+        assert_eq!(tx.try_send(2), Err(SendError::Disconnected(2)));
+    }
+
+    #[test]
+    fn timeouts() {
+        let (tx, rx) = bounded(1);
+        assert_eq!(
+            rx.recv_timeout(Duration::from_millis(10)),
+            Err(RecvError::Timeout)
+        );
+        tx.send(1).unwrap();
+        assert_eq!(
+            tx.send_timeout(2, Duration::from_millis(10)),
+            Err(SendError::Timeout(2))
+        );
+        assert_eq!(rx.recv_timeout(Duration::from_secs(5)), Ok(1));
+    }
+
+    #[test]
+    fn blocked_sender_wakes_on_disconnect() {
+        let (tx, rx) = bounded(1);
+        tx.send(1).unwrap();
+        let handle = thread::spawn(move || tx.send(2));
+        thread::sleep(Duration::from_millis(10));
+        drop(rx);
+        assert_eq!(handle.join().unwrap(), Err(SendError::Disconnected(2)));
+    }
+
+    #[test]
+    fn drop_undelivered() {
+        let counter = Arc::new(());
+        let (tx, rx) = bounded(4);
+        tx.send(counter.clone()).unwrap();
+        tx.send(counter.clone()).unwrap();
+        drop(rx);
+        drop(tx);
+        assert_eq!(Arc::strong_count(&counter), 1);
+    }
+
+    #[test]
+    fn many_producers_and_consumers() {
+        const PER_PRODUCER: usize = if cfg!(miri) { 50 } else { 5_000 };
+        let (tx, rx) = bounded(8);
+        let producers = (0..4)
+            .map(|p| {
+                let tx = tx.clone();
+                thread::spawn(move || {
+                    for i in 0..PER_PRODUCER {
+                        tx.send(p * PER_PRODUCER + i).unwrap();
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        drop(tx);
+        let consumers = (0..3)
+            .map(|_| {
+                let rx = rx.clone();
+                thread::spawn(move || rx.iter().collect::<Vec<_>>())
+            })
+            .collect::<Vec<_>>();
+        drop(rx);
+        for producer in producers {
+            producer.join().unwrap();
+        }
+        let mut received = consumers
+            .into_iter()
+            .flat_map(|c| c.join().unwrap())
+            .collect::<Vec<_>>();
+        received.sort();
+        assert_eq!(received, (0..4 * PER_PRODUCER).collect::<Vec<_>>());
+    }
+}
+
+#[cfg(all(test, loom))]
+mod loom_test {
+    use loom::thread;
+
+    use super::{bounded, RecvError};
+
+    #[test]
+    fn send_and_recv() {
+        loom::model(|| {
+            let (tx, rx) = bounded(2);
+            let tx2 = tx.clone();
+            let handle = thread::spawn(move || tx2.send(1).unwrap());
+            tx.send(2).unwrap();
+            drop(tx);
+            let mut received = vec![rx.recv().unwrap(), rx.recv().unwrap()];
+            received.sort();
+            assert_eq!(received, vec![1, 2]);
+            handle.join().unwrap();
+            assert_eq!(rx.recv(), Err(RecvError::Disconnected));
+        });
+    }
+
+    #[test]
+    fn send_waits_for_recv() {
+        loom::model(|| {
+            let (tx, rx) = bounded(1);
+            let handle = thread::spawn(move || (rx.recv().unwrap(), rx.recv().unwrap()));
+            tx.send(1).unwrap();
+            tx.send(2).unwrap();
+            assert_eq!(handle.join().unwrap(), (1, 2));
+        });
+    }
+}
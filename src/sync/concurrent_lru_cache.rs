@@ -0,0 +1,178 @@
+use std::{
+    collections::hash_map::RandomState,
+    fmt::{self, Debug},
+    hash::{BuildHasher, Hash},
+    sync::Mutex,
+};
+
+use crate::{lru_cache::LRUCache, Error};
+
+const DEFAULT_SHARDS: usize = 16;
+
+/// A thread-safe LRU cache. Keys are spread across independently locked
+/// shards, each of which maintains its own recency list, so eviction is
+/// least-recently-used within a shard rather than across the whole cache.
+pub struct ConcurrentLruCache<K, V> {
+    shards: Vec<Mutex<LRUCache<K, V>>>,
+    hasher: RandomState,
+}
+
+impl<K, V> ConcurrentLruCache<K, V>
+where
+    K: Clone,
+{
+    pub fn new(capacity: usize) -> Self {
+        Self::with_shards(capacity, DEFAULT_SHARDS)
+    }
+
+    /// Creates a cache whose capacity is divided as evenly as possible between `shards`
+    /// shards, so that the shard capacities add up to `capacity`. There are never more
+    /// shards than entries, since a shard which can hold nothing would reject its keys.
+    pub fn with_shards(capacity: usize, shards: usize) -> Self {
+        assert!(shards > 0, "shard count must be positive");
+        let shards = shards.min(capacity).max(1);
+        let (base, extra) = (capacity / shards, capacity % shards);
+        ConcurrentLruCache {
+            shards: (0..shards)
+                .map(|i| Mutex::new(LRUCache::new(base + usize::from(i < extra))))
+                .collect(),
+            hasher: RandomState::new(),
+        }
+    }
+}
+
+impl<K, V> ConcurrentLruCache<K, V> {
+    /// The total capacity of the shards.
+    pub fn capacity(&self) -> usize {
+        self.shards
+            .iter()
+            .map(|shard| shard.lock().unwrap().capacity())
+            .sum()
+    }
+}
+
+impl<K, V> ConcurrentLruCache<K, V>
+where
+    K: Eq + Hash + Clone,
+{
+    fn shard(&self, k: &K) -> &Mutex<LRUCache<K, V>> {
+        let index = self.hasher.hash_one(k) as usize % self.shards.len();
+        &self.shards[index]
+    }
+
+    pub fn insert(&self, k: K, v: V) {
+        self.shard(&k).lock().unwrap().insert(k, v);
+    }
+
+    /// Inserts the entry only if the key is absent and its shard has room.
+    pub fn try_insert(&self, k: K, v: V) -> Result<(), Error<(K, V)>> {
+        self.shard(&k).lock().unwrap().try_insert(k, v)
+    }
+
+    pub fn get(&self, k: &K) -> Option<V>
+    where
+        V: Clone,
+    {
+        self.shard(k).lock().unwrap().get(k).cloned()
+    }
+
+    /// Returns the cached value for `k`, computing and inserting it with `f` if absent.
+    /// The shard lock is held while `f` runs, so concurrent callers for the same key
+    /// compute the value only once.
+    pub fn get_or_insert_with<F>(&self, k: K, f: F) -> V
+    where
+        V: Clone,
+        F: FnOnce() -> V,
+    {
+        let mut shard = self.shard(&k).lock().unwrap();
+        if let Some(v) = shard.get(&k) {
+            return v.clone();
+        }
+        let v = f();
+        shard.insert(k, v.clone());
+        v
+    }
+}
+
+impl<K, V> Debug for ConcurrentLruCache<K, V>
+where
+    K: Eq + Hash + Debug,
+    V: Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConcurrentLruCache")
+            .field("shards", &self.shards)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        thread,
+    };
+
+    use super::ConcurrentLruCache;
+
+    #[test]
+    fn insert_and_get() {
+        let cache = ConcurrentLruCache::new(4);
+        cache.insert(1, 101);
+        assert_eq!(cache.get(&1), Some(101));
+        assert_eq!(cache.get(&2), None);
+    }
+
+    #[test]
+    fn shard_capacities_add_up() {
+        assert_eq!(ConcurrentLruCache::<i32, i32>::new(4).capacity(), 4);
+        assert_eq!(ConcurrentLruCache::<i32, i32>::new(0).capacity(), 0);
+        assert_eq!(ConcurrentLruCache::<i32, i32>::new(100).capacity(), 100);
+        assert_eq!(
+            ConcurrentLruCache::<i32, i32>::with_shards(10, 3).capacity(),
+            10
+        );
+        let cache = ConcurrentLruCache::new(3);
+        for i in 0..100 {
+            cache.insert(i, i);
+        }
+        assert!((0..100).filter(|i| cache.get(i).is_some()).count() <= 3);
+    }
+
+    #[test]
+    fn evict_within_shard() {
+        let cache = ConcurrentLruCache::with_shards(2, 1);
+        cache.insert(1, 101);
+        cache.insert(2, 102);
+        cache.get(&1);
+        cache.insert(3, 103);
+        assert_eq!(cache.get(&1), Some(101));
+        assert_eq!(cache.get(&2), None);
+        assert_eq!(cache.get(&3), Some(103));
+    }
+
+    #[test]
+    fn get_or_insert_with_computes_once() {
+        let cache = Arc::new(ConcurrentLruCache::new(16));
+        let calls = Arc::new(AtomicUsize::new(0));
+        let handles = (0..8)
+            .map(|_| {
+                let cache = cache.clone();
+                let calls = calls.clone();
+                thread::spawn(move || {
+                    cache.get_or_insert_with(7, || {
+                        calls.fetch_add(1, Ordering::SeqCst);
+                        700
+                    })
+                })
+            })
+            .collect::<Vec<_>>();
+        for handle in handles {
+            assert_eq!(handle.join().unwrap(), 700);
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+}
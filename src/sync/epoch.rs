@@ -0,0 +1,417 @@
+//! Epoch-based memory reclamation for lock-free structures.
+//!
+//! A thread pins itself before reading shared pointers, and objects unlinked from a
+//! structure are handed to `Guard::defer_destroy` rather than freed. The collector keeps a
+//! global epoch which only advances once every pinned thread has seen the current one, so
+//! once it has moved twice past the epoch an object was retired in, no thread can still be
+//! reading that object and it is destroyed.
+
+use std::{
+    cell::{Cell, RefCell},
+    fmt::{self, Debug},
+    mem,
+};
+
+use crate::sync::primitives::{fence, Arc, AtomicUsize, Mutex, Ordering};
+
+// How much garbage a thread buffers before trying to collect it
+const COLLECT_THRESHOLD: usize = 64;
+
+/// The shared state which threads register with to reclaim memory together.
+/// Structures sharing a collector must only retire objects through its handles.
+#[derive(Clone)]
+pub struct Collector {
+    global: Arc<Global>,
+}
+
+struct Global {
+    epoch: AtomicUsize,
+    participants: Mutex<Vec<Arc<Participant>>>,
+    // Garbage left behind by handles which were dropped before it could be destroyed
+    orphans: Mutex<Vec<Deferred>>,
+}
+
+struct Participant {
+    // The epoch shifted left by one, with the low bit set while the thread is pinned
+    state: AtomicUsize,
+}
+
+/// A thread's registration with a `Collector`.
+pub struct Handle {
+    global: Arc<Global>,
+    participant: Arc<Participant>,
+    pins: Cell<usize>,
+    garbage: RefCell<Vec<Deferred>>,
+}
+
+/// Keeps the thread pinned, so that objects retired after it was created are not destroyed
+/// while it is alive.
+pub struct Guard<'a> {
+    handle: &'a Handle,
+}
+
+/// A destructor to run once the epoch it was retired in has passed.
+struct Deferred {
+    epoch: usize,
+    f: Box<dyn FnOnce() + Send>,
+}
+
+/// Sends a pointer to whichever thread ends up destroying it.
+struct SendPtr<T>(*mut T);
+
+unsafe impl<T: Send> Send for SendPtr<T> {}
+
+impl Collector {
+    pub fn new() -> Self {
+        Collector {
+            global: Arc::new(Global {
+                epoch: AtomicUsize::new(0),
+                participants: Mutex::new(vec![]),
+                orphans: Mutex::new(vec![]),
+            }),
+        }
+    }
+
+    /// Registers a thread with the collector.
+    pub fn register(&self) -> Handle {
+        let participant = Arc::new(Participant {
+            state: AtomicUsize::new(0),
+        });
+        self.global
+            .participants
+            .lock()
+            .unwrap()
+            .push(participant.clone());
+        Handle {
+            global: self.global.clone(),
+            participant,
+            pins: Cell::new(0),
+            garbage: RefCell::new(vec![]),
+        }
+    }
+}
+
+impl Default for Collector {
+    fn default() -> Self {
+        Collector::new()
+    }
+}
+
+impl Debug for Collector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Collector")
+            .field("epoch", &self.global.epoch.load(Ordering::Relaxed))
+            .finish_non_exhaustive()
+    }
+}
+
+impl Global {
+    /// Moves the epoch forward if every pinned thread has seen it, returning the epoch.
+    fn try_advance(&self) -> usize {
+        let epoch = self.epoch.load(Ordering::Relaxed);
+        fence(Ordering::SeqCst);
+        let participants = self.participants.lock().unwrap();
+        let lagging = participants.iter().any(|p| {
+            let state = p.state.load(Ordering::Relaxed);
+            state & 1 == 1 && state >> 1 != epoch
+        });
+        drop(participants);
+        if lagging {
+            return epoch;
+        }
+        fence(Ordering::Acquire);
+        // Another thread may have advanced it first, which is just as good
+        let _ = self
+            .epoch
+            .compare_exchange(epoch, epoch + 1, Ordering::Release, Ordering::Relaxed);
+        self.epoch.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for Global {
+    fn drop(&mut self) {
+        // Every handle is gone, so nothing can still be reading the garbage
+        for deferred in mem::take(self.orphans.get_mut().unwrap()) {
+            (deferred.f)();
+        }
+    }
+}
+
+impl Handle {
+    /// Pins the thread until the guard is dropped. Pinning again while pinned is cheap.
+    pub fn pin(&self) -> Guard<'_> {
+        let pins = self.pins.get();
+        self.pins.set(pins + 1);
+        if pins == 0 {
+            let epoch = self.global.epoch.load(Ordering::Relaxed);
+            self.participant
+                .state
+                .store(epoch << 1 | 1, Ordering::Relaxed);
+            // Order the announcement before every read of shared pointers under the guard
+            fence(Ordering::SeqCst);
+        }
+        Guard { handle: self }
+    }
+
+    pub fn is_pinned(&self) -> bool {
+        self.pins.get() > 0
+    }
+
+    fn defer(&self, f: Box<dyn FnOnce() + Send>) {
+        // The object must be unlinked before the epoch it is tagged with is read
+        fence(Ordering::SeqCst);
+        let epoch = self.global.epoch.load(Ordering::Relaxed);
+        let len = {
+            let mut garbage = self.garbage.borrow_mut();
+            garbage.push(Deferred { epoch, f });
+            garbage.len()
+        };
+        if len >= COLLECT_THRESHOLD {
+            self.collect();
+        }
+    }
+
+    /// Destroys the garbage retired at least two epochs ago, after trying to advance.
+    fn collect(&self) {
+        let epoch = self.global.try_advance();
+        let expired = |deferred: &Deferred| deferred.epoch + 2 <= epoch;
+        let mut ready = vec![];
+        {
+            let mut garbage = self.garbage.borrow_mut();
+            let (done, pending): (Vec<_>, _) =
+                mem::take(&mut *garbage).into_iter().partition(expired);
+            *garbage = pending;
+            ready.extend(done);
+        }
+        {
+            let mut orphans = self.global.orphans.lock().unwrap();
+            let (done, pending): (Vec<_>, _) =
+                mem::take(&mut *orphans).into_iter().partition(expired);
+            *orphans = pending;
+            ready.extend(done);
+        }
+        // Destructors run outside of the borrows, since they may retire more garbage
+        for deferred in ready {
+            (deferred.f)();
+        }
+    }
+}
+
+impl Drop for Handle {
+    fn drop(&mut self) {
+        let participant = Arc::as_ptr(&self.participant);
+        self.global
+            .participants
+            .lock()
+            .unwrap()
+            .retain(|p| Arc::as_ptr(p) != participant);
+        let garbage = mem::take(self.garbage.get_mut());
+        self.global.orphans.lock().unwrap().extend(garbage);
+    }
+}
+
+impl Debug for Handle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Handle")
+            .field("pinned", &self.is_pinned())
+            .field("garbage", &self.garbage.borrow().len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Guard<'_> {
+    /// Runs `f` once every thread which is pinned now has been unpinned.
+    pub fn defer<F: FnOnce() + Send + 'static>(&self, f: F) {
+        self.handle.defer(Box::new(f));
+    }
+
+    /// Drops the boxed object once no thread which is pinned now can still be reading it.
+    ///
+    /// # Safety
+    /// `ptr` must come from `Box::into_raw`, and must already be unreachable from the
+    /// structure so that threads which pin later cannot find it. It must not be retired
+    /// twice.
+    pub unsafe fn defer_destroy<T: Send + 'static>(&self, ptr: *mut T) {
+        let ptr = SendPtr(ptr);
+        self.defer(move || {
+            let ptr = ptr;
+            drop(unsafe { Box::from_raw(ptr.0) });
+        });
+    }
+
+    /// Tries to advance the epoch and destroys whatever garbage has expired, without waiting
+    /// for the thread's buffer to fill.
+    pub fn flush(&self) {
+        self.handle.collect();
+    }
+}
+
+impl Drop for Guard<'_> {
+    fn drop(&mut self) {
+        let pins = self.handle.pins.get() - 1;
+        self.handle.pins.set(pins);
+        if pins == 0 {
+            self.handle.participant.state.store(0, Ordering::Release);
+        }
+    }
+}
+
+impl Debug for Guard<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Guard").finish_non_exhaustive()
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod test {
+    use std::{
+        sync::{
+            atomic::{AtomicPtr, AtomicUsize, Ordering},
+            Arc,
+        },
+        thread,
+    };
+
+    use super::Collector;
+
+    /// Counts how many times it has been dropped.
+    struct Tracked(Arc<AtomicUsize>);
+
+    impl Drop for Tracked {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn pinned_thread_delays_destruction() {
+        let collector = Collector::new();
+        let (reader, writer) = (collector.register(), collector.register());
+        let drops = Arc::new(AtomicUsize::new(0));
+        let read_guard = reader.pin();
+        {
+            let guard = writer.pin();
+            let ptr = Box::into_raw(Box::new(Tracked(drops.clone())));
+            unsafe { guard.defer_destroy(ptr) };
+            for _ in 0..4 {
+                guard.flush();
+            }
+        }
+        assert_eq!(drops.load(Ordering::SeqCst), 0);
+        drop(read_guard);
+        for _ in 0..3 {
+            writer.pin().flush();
+        }
+        assert_eq!(drops.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn nested_pins() {
+        let collector = Collector::new();
+        let handle = collector.register();
+        let outer = handle.pin();
+        let inner = handle.pin();
+        drop(inner);
+        assert!(handle.is_pinned());
+        drop(outer);
+        assert!(!handle.is_pinned());
+    }
+
+    #[test]
+    fn dropped_handles_leave_garbage_behind() {
+        let collector = Collector::new();
+        let drops = Arc::new(AtomicUsize::new(0));
+        let handle = collector.register();
+        handle.pin().defer({
+            let drops = drops.clone();
+            move || {
+                drops.fetch_add(1, Ordering::SeqCst);
+            }
+        });
+        drop(handle);
+        assert_eq!(drops.load(Ordering::SeqCst), 0);
+        // Another handle picks it up, or else the collector destroys it when dropped
+        let other = collector.register();
+        for _ in 0..3 {
+            other.pin().flush();
+        }
+        assert_eq!(drops.load(Ordering::SeqCst), 1);
+        drop(other);
+        let handle = collector.register();
+        let ptr = Box::into_raw(Box::new(Tracked(drops.clone())));
+        unsafe { handle.pin().defer_destroy(ptr) };
+        drop((handle, collector));
+        assert_eq!(drops.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn concurrent_swaps() {
+        const SWAPS: usize = if cfg!(miri) { 50 } else { 5_000 };
+        let collector = Collector::new();
+        let shared = Arc::new(AtomicPtr::new(Box::into_raw(Box::new(0usize))));
+        let threads = (0..4)
+            .map(|t| {
+                let (collector, shared) = (collector.clone(), shared.clone());
+                thread::spawn(move || {
+                    let handle = collector.register();
+                    for i in 0..SWAPS {
+                        let guard = handle.pin();
+                        let current = unsafe { *shared.load(Ordering::Acquire) };
+                        assert!(current < 4 * SWAPS);
+                        if i % 2 == t % 2 {
+                            let new = Box::into_raw(Box::new(t * SWAPS + i));
+                            let old = shared.swap(new, Ordering::AcqRel);
+                            unsafe { guard.defer_destroy(old) };
+                        }
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        // Whatever is still deferred is destroyed along with the collector
+        drop(unsafe { Box::from_raw(shared.load(Ordering::Relaxed)) });
+    }
+}
+
+#[cfg(all(test, loom))]
+mod loom_test {
+    use loom::{
+        sync::{
+            atomic::{AtomicPtr, Ordering},
+            Arc,
+        },
+        thread,
+    };
+
+    use super::Collector;
+
+    #[test]
+    fn reader_never_sees_freed_memory() {
+        loom::model(|| {
+            let collector = Collector::new();
+            let shared = Arc::new(AtomicPtr::new(Box::into_raw(Box::new(1))));
+            let reader = thread::spawn({
+                let (collector, shared) = (collector.clone(), shared.clone());
+                move || {
+                    let handle = collector.register();
+                    let _guard = handle.pin();
+                    let value = unsafe { *shared.load(Ordering::Acquire) };
+                    assert!(value == 1 || value == 2);
+                }
+            });
+            let handle = collector.register();
+            {
+                let guard = handle.pin();
+                let old = shared.swap(Box::into_raw(Box::new(2)), Ordering::AcqRel);
+                unsafe { guard.defer_destroy(old) };
+            }
+            for _ in 0..2 {
+                handle.pin().flush();
+            }
+            reader.join().unwrap();
+            drop(unsafe { Box::from_raw(shared.load(Ordering::Relaxed)) });
+        });
+    }
+}
@@ -1,42 +1,191 @@
-use std::sync::{Arc, Condvar, Mutex};
+use std::{
+    ptr::NonNull,
+    sync::{Arc, Mutex},
+};
+
+#[cfg(feature = "async")]
+use std::{
+    cell::UnsafeCell,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+#[cfg(feature = "async")]
+use crate::sync::waiter_list::Waiter;
+use crate::sync::waiter_list::{Parker, WaiterList};
 
 /// A blocking countdown latch.
+/// Threads and futures wait in one queue, and each is woken through its own waker when the
+/// count reaches zero, so none of them has to take the lock again to find out.
 #[derive(Debug, Clone)]
 pub struct Latch {
-    state: Arc<(Mutex<usize>, Condvar)>,
+    state: Arc<Mutex<State>>,
+}
+
+#[derive(Debug)]
+struct State {
+    count: usize,
+    waiters: WaiterList,
 }
 
 impl Latch {
     pub fn new(count: usize) -> Self {
+        let state = State {
+            count,
+            waiters: WaiterList::new(),
+        };
         Latch {
-            state: Arc::new((Mutex::new(count), Condvar::new())),
+            state: Arc::new(Mutex::new(state)),
         }
     }
 
     pub fn remaining(&self) -> usize {
-        let (lock, _) = &*self.state;
-        *lock.lock().unwrap()
+        self.state.lock().unwrap().count
     }
 
     pub fn count_down(&self) {
-        let (lock, cvar) = &*self.state;
-        let mut count = lock.lock().unwrap();
-        if *count > 0 {
-            *count -= 1;
-            if *count == 0 {
-                cvar.notify_all();
+        let mut state = self.state.lock().unwrap();
+        if state.count > 0 {
+            state.count -= 1;
+            if state.count == 0 {
+                let wakers = state.waiters.notify_all();
+                drop(state);
+                wakers.into_iter().for_each(|w| w.wake());
             }
         }
     }
 
     pub fn wait(&self) {
-        let (lock, cvar) = &*self.state;
-        let mut count = lock.lock().unwrap();
-        while *count > 0 {
-            count = cvar.wait(count).unwrap();
+        let mut state = self.state.lock().unwrap();
+        if state.count == 0 {
+            return;
+        }
+        let parker = Parker::new();
+        let mut waiter = parker.waiter(0);
+        // The waiter stays on this stack frame until the parker is woken, by which point
+        // it has been removed from the list
+        unsafe { state.waiters.push_back(NonNull::from(&mut waiter)) };
+        drop(state);
+        parker.park();
+    }
+
+    /// Returns a future which completes once the count reaches zero.
+    #[cfg(feature = "async")]
+    pub fn wait_async(&self) -> LatchWait<'_> {
+        LatchWait {
+            latch: self,
+            waiter: UnsafeCell::new(Waiter::new(0)),
+        }
+    }
+}
+
+/// Future returned by `Latch::wait_async`.
+#[cfg(feature = "async")]
+#[derive(Debug)]
+pub struct LatchWait<'a> {
+    latch: &'a Latch,
+    waiter: UnsafeCell<Waiter>,
+}
+
+#[cfg(feature = "async")]
+unsafe impl Send for LatchWait<'_> {}
+
+#[cfg(feature = "async")]
+impl Future for LatchWait<'_> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let mut state = self.latch.state.lock().unwrap();
+        if state.count == 0 {
+            return Poll::Ready(());
+        }
+        // The waiter is only accessed under the lock, and it is pinned along with the future
+        unsafe {
+            let node = NonNull::new_unchecked(self.waiter.get());
+            let waiter = &mut *node.as_ptr();
+            waiter.waker = Some(cx.waker().clone());
+            if !waiter.queued {
+                state.waiters.push_back(node);
+            }
+        }
+        Poll::Pending
+    }
+}
+
+#[cfg(feature = "async")]
+impl Drop for LatchWait<'_> {
+    fn drop(&mut self) {
+        let mut state = self.latch.state.lock().unwrap();
+        unsafe {
+            let node = NonNull::new_unchecked(self.waiter.get());
+            if node.as_ref().queued {
+                state.waiters.remove(node);
+            }
         }
     }
 }
 
 #[cfg(test)]
-mod test {}
+mod test {
+    use std::thread;
+
+    use super::Latch;
+
+    #[test]
+    fn count_down() {
+        let latch = Latch::new(2);
+        latch.count_down();
+        assert_eq!(latch.remaining(), 1);
+        latch.count_down();
+        latch.count_down();
+        assert_eq!(latch.remaining(), 0);
+        latch.wait();
+    }
+
+    #[test]
+    fn wait_for_other_threads() {
+        let latch = Latch::new(3);
+        let handles = (0..3)
+            .map(|_| {
+                let latch = latch.clone();
+                thread::spawn(move || latch.count_down())
+            })
+            .collect::<Vec<_>>();
+        latch.wait();
+        assert_eq!(latch.remaining(), 0);
+        for handle in handles {
+            handle.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn many_waiters() {
+        let latch = Latch::new(1);
+        let handles = (0..64)
+            .map(|_| {
+                let latch = latch.clone();
+                thread::spawn(move || latch.wait())
+            })
+            .collect::<Vec<_>>();
+        latch.count_down();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        // Waiting after the count reaches zero returns at once
+        latch.wait();
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn wait_async() {
+        use crate::sync::test_util::block_on;
+
+        let latch = Latch::new(1);
+        let counter = latch.clone();
+        let handle = thread::spawn(move || counter.count_down());
+        block_on(latch.wait_async());
+        assert_eq!(latch.remaining(), 0);
+        handle.join().unwrap();
+    }
+}
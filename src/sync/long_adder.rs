@@ -0,0 +1,124 @@
+use std::{
+    sync::atomic::{AtomicI64, AtomicUsize, Ordering},
+    thread,
+};
+
+/// A counter which spreads updates across several cache-line-padded cells,
+/// so that threads incrementing concurrently rarely contend on the same line.
+/// Reads must visit every cell, so this favors update-heavy workloads.
+#[derive(Debug)]
+pub struct LongAdder {
+    cells: Box<[PaddedCell]>,
+}
+
+#[derive(Debug, Default)]
+#[repr(align(128))]
+struct PaddedCell(AtomicI64);
+
+static NEXT_STRIPE: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    // Threads are assigned stripes round robin on first use
+    static STRIPE: usize = NEXT_STRIPE.fetch_add(1, Ordering::Relaxed);
+}
+
+impl LongAdder {
+    pub fn new() -> Self {
+        let stripes = thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1);
+        Self::with_stripes(stripes)
+    }
+
+    pub fn with_stripes(stripes: usize) -> Self {
+        assert!(stripes > 0, "stripe count must be positive");
+        LongAdder {
+            cells: (0..stripes).map(|_| PaddedCell::default()).collect(),
+        }
+    }
+
+    pub fn add(&self, n: i64) {
+        let stripe = STRIPE.with(|stripe| *stripe) % self.cells.len();
+        self.cells[stripe].0.fetch_add(n, Ordering::Relaxed);
+    }
+
+    pub fn increment(&self) {
+        self.add(1);
+    }
+
+    pub fn decrement(&self) {
+        self.add(-1);
+    }
+
+    /// Returns the current total. Updates which happen concurrently
+    /// with this call may or may not be reflected in the result.
+    pub fn sum(&self) -> i64 {
+        self.cells
+            .iter()
+            .map(|cell| cell.0.load(Ordering::Relaxed))
+            .sum()
+    }
+
+    /// Resets the counter to zero, returning the total beforehand.
+    pub fn sum_then_reset(&self) -> i64 {
+        self.cells
+            .iter()
+            .map(|cell| cell.0.swap(0, Ordering::Relaxed))
+            .sum()
+    }
+}
+
+impl Default for LongAdder {
+    fn default() -> Self {
+        LongAdder::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{mem::align_of, sync::Arc, thread};
+
+    use super::{LongAdder, PaddedCell};
+
+    #[test]
+    fn add_and_sum() {
+        let adder = LongAdder::new();
+        adder.add(5);
+        adder.increment();
+        adder.decrement();
+        adder.add(-2);
+        assert_eq!(adder.sum(), 3);
+    }
+
+    #[test]
+    fn sum_then_reset() {
+        let adder = LongAdder::with_stripes(4);
+        adder.add(7);
+        assert_eq!(adder.sum_then_reset(), 7);
+        assert_eq!(adder.sum(), 0);
+    }
+
+    #[test]
+    fn cells_padded() {
+        assert!(align_of::<PaddedCell>() >= 64);
+    }
+
+    #[test]
+    fn concurrent_add() {
+        let adder = Arc::new(LongAdder::with_stripes(4));
+        let handles = (0..8)
+            .map(|_| {
+                let adder = adder.clone();
+                thread::spawn(move || {
+                    for _ in 0..1000 {
+                        adder.increment();
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert_eq!(adder.sum(), 8000);
+    }
+}
@@ -1 +1,18 @@
+pub mod batch_queue;
+pub mod channel;
+pub mod concurrent_lru_cache;
+pub mod epoch;
 pub mod latch;
+pub mod long_adder;
+mod primitives;
+pub mod priority_blocking_queue;
+pub mod rw_map;
+pub mod semaphore;
+pub mod set_once;
+pub mod snapshot_map;
+pub mod spsc_ring_buffer;
+#[cfg(all(test, feature = "async"))]
+mod test_util;
+pub mod token_bucket;
+mod waiter_list;
+pub mod work_stealing_deque;
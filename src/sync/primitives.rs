@@ -0,0 +1,41 @@
+//! The atomics, locks and threads used by the lock-free structures.
+//! Building with `RUSTFLAGS="--cfg loom"` swaps in loom's model-checked versions, so that
+//! the loom tests explore every interleaving the memory model allows.
+
+#[cfg(loom)]
+pub(crate) use loom::{
+    cell::UnsafeCell,
+    sync::{
+        atomic::{fence, AtomicBool, AtomicIsize, AtomicPtr, AtomicUsize, Ordering},
+        Arc, Condvar, Mutex,
+    },
+    thread::{self, Thread},
+};
+#[cfg(not(loom))]
+pub(crate) use std::{
+    sync::{
+        atomic::{fence, AtomicBool, AtomicIsize, AtomicPtr, AtomicUsize, Ordering},
+        Arc, Condvar, Mutex,
+    },
+    thread::{self, Thread},
+};
+
+/// std's `UnsafeCell` behind loom's closure-based API, so that loom can track every access.
+#[cfg(not(loom))]
+#[derive(Debug)]
+pub(crate) struct UnsafeCell<T>(std::cell::UnsafeCell<T>);
+
+#[cfg(not(loom))]
+impl<T> UnsafeCell<T> {
+    pub(crate) fn new(value: T) -> Self {
+        UnsafeCell(std::cell::UnsafeCell::new(value))
+    }
+
+    pub(crate) fn with<R>(&self, f: impl FnOnce(*const T) -> R) -> R {
+        f(self.0.get())
+    }
+
+    pub(crate) fn with_mut<R>(&self, f: impl FnOnce(*mut T) -> R) -> R {
+        f(self.0.get())
+    }
+}
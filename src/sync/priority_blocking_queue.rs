@@ -0,0 +1,189 @@
+use std::{
+    sync::{Condvar, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::{capacity::Capacity, heap::Heap, Error};
+
+/// A priority queue whose consumers block until an element is available, and whose
+/// producers block until there is room if it was created `bounded`.
+/// Like `Heap`, the smallest element has the highest priority and is popped first;
+/// wrap elements in `std::cmp::Reverse` to pop the largest first instead.
+#[derive(Debug)]
+pub struct PriorityBlockingQueue<T> {
+    state: (Mutex<Heap<T>>, Condvar),
+    // Signalled when an element is popped, for producers waiting for room
+    room: Condvar,
+    capacity: Capacity,
+}
+
+impl<T> PriorityBlockingQueue<T> {
+    /// Creates an unbounded queue.
+    pub fn new() -> Self {
+        PriorityBlockingQueue::with_budget(Capacity::UNBOUNDED)
+    }
+
+    /// Creates a queue holding at most `capacity` elements.
+    /// Panics if `capacity` is zero, as nothing could ever be pushed.
+    pub fn bounded(capacity: usize) -> Self {
+        assert!(capacity > 0, "capacity must be positive");
+        PriorityBlockingQueue::with_budget(Capacity::Entries(capacity))
+    }
+
+    fn with_budget(capacity: Capacity) -> Self {
+        PriorityBlockingQueue {
+            state: (Mutex::new(Heap::new()), Condvar::new()),
+            room: Condvar::new(),
+            capacity,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity.limit()
+    }
+}
+
+impl<T> PriorityBlockingQueue<T>
+where
+    T: Ord,
+{
+    /// Blocks until there is room in the queue.
+    pub fn push(&self, value: T) {
+        let (lock, cvar) = &self.state;
+        let mut heap = lock.lock().unwrap();
+        while !self.capacity.fits(heap.size(), 1) {
+            heap = self.room.wait(heap).unwrap();
+        }
+        heap.push(value);
+        cvar.notify_one();
+    }
+
+    /// Like `push`, but hands the value back if the queue is full rather than blocking.
+    pub fn try_push(&self, value: T) -> Result<(), Error<T>> {
+        let (lock, cvar) = &self.state;
+        let mut heap = lock.lock().unwrap();
+        if !self.capacity.fits(heap.size(), 1) {
+            return Err(Error::Full(value));
+        }
+        heap.push(value);
+        cvar.notify_one();
+        Ok(())
+    }
+
+    pub fn try_pop(&self) -> Option<T> {
+        let (lock, _) = &self.state;
+        let value = lock.lock().unwrap().pop();
+        if value.is_some() {
+            self.room.notify_one();
+        }
+        value
+    }
+
+    /// Blocks until an element is available and removes the highest-priority one.
+    pub fn pop(&self) -> T {
+        let (lock, cvar) = &self.state;
+        let mut heap = lock.lock().unwrap();
+        loop {
+            if let Some(value) = heap.pop() {
+                self.room.notify_one();
+                return value;
+            }
+            heap = cvar.wait(heap).unwrap();
+        }
+    }
+
+    /// Like `pop`, but gives up if no element arrives within `timeout`.
+    pub fn pop_timeout(&self, timeout: Duration) -> Option<T> {
+        let deadline = Instant::now() + timeout;
+        let (lock, cvar) = &self.state;
+        let mut heap = lock.lock().unwrap();
+        loop {
+            if let Some(value) = heap.pop() {
+                self.room.notify_one();
+                return Some(value);
+            }
+            let remaining = deadline.checked_duration_since(Instant::now())?;
+            heap = cvar.wait_timeout(heap, remaining).unwrap().0;
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        let (lock, _) = &self.state;
+        lock.lock().unwrap().size()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// How many more elements can be pushed before producers block.
+    pub fn remaining_capacity(&self) -> usize {
+        self.capacity.remaining(self.len())
+    }
+}
+
+impl<T> Default for PriorityBlockingQueue<T> {
+    fn default() -> Self {
+        PriorityBlockingQueue::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{sync::Arc, thread, time::Duration};
+
+    use super::PriorityBlockingQueue;
+    use crate::Error;
+
+    #[test]
+    fn pop_in_priority_order() {
+        let queue = PriorityBlockingQueue::new();
+        queue.push(3);
+        queue.push(1);
+        queue.push(2);
+        assert_eq!(queue.len(), 3);
+        assert_eq!(queue.pop(), 1);
+        assert_eq!(queue.pop(), 2);
+        assert_eq!(queue.pop(), 3);
+        assert_eq!(queue.try_pop(), None);
+    }
+
+    #[test]
+    fn bounded_push_blocks_for_consumer() {
+        let queue = Arc::new(PriorityBlockingQueue::bounded(1));
+        queue.push(2);
+        assert_eq!(queue.remaining_capacity(), 0);
+        assert_eq!(queue.try_push(1), Err(Error::Full(1)));
+        let producer = queue.clone();
+        let handle = thread::spawn(move || producer.push(1));
+        thread::sleep(Duration::from_millis(10));
+        assert_eq!(queue.pop(), 2);
+        handle.join().unwrap();
+        assert_eq!(queue.pop(), 1);
+        assert_eq!(queue.remaining_capacity(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "capacity must be positive")]
+    fn bounded_rejects_zero() {
+        PriorityBlockingQueue::<i32>::bounded(0);
+    }
+
+    #[test]
+    fn pop_timeout_expires() {
+        let queue = PriorityBlockingQueue::<i32>::new();
+        assert_eq!(queue.pop_timeout(Duration::from_millis(10)), None);
+    }
+
+    #[test]
+    fn pop_blocks_for_producer() {
+        let queue = Arc::new(PriorityBlockingQueue::new());
+        let producer = queue.clone();
+        let handle = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(10));
+            producer.push(7);
+        });
+        assert_eq!(queue.pop_timeout(Duration::from_secs(5)), Some(7));
+        handle.join().unwrap();
+    }
+}
@@ -0,0 +1,149 @@
+use std::{
+    ops::{Deref, DerefMut, RangeBounds},
+    sync::{RwLock, RwLockReadGuard, RwLockWriteGuard},
+};
+
+use crate::avl_tree::{AVLTree, Iter};
+
+/// An ordered map guarded by a readers-writer lock.
+/// `read` and `write` return guards which expose the whole `AVLTree` API
+/// for as long as the lock is held.
+#[derive(Debug, Default)]
+pub struct RwMap<K, V> {
+    tree: RwLock<AVLTree<K, V>>,
+}
+
+/// Shared access to the map of a `RwMap`.
+#[derive(Debug)]
+pub struct ReadGuard<'a, K, V> {
+    guard: RwLockReadGuard<'a, AVLTree<K, V>>,
+}
+
+/// Exclusive access to the map of a `RwMap`.
+#[derive(Debug)]
+pub struct WriteGuard<'a, K, V> {
+    guard: RwLockWriteGuard<'a, AVLTree<K, V>>,
+}
+
+impl<K, V> RwMap<K, V> {
+    pub fn new() -> Self {
+        RwMap {
+            tree: RwLock::new(AVLTree::new()),
+        }
+    }
+
+    /// Blocks until no writer holds the lock.
+    pub fn read(&self) -> ReadGuard<'_, K, V> {
+        ReadGuard {
+            guard: self.tree.read().unwrap(),
+        }
+    }
+
+    /// Blocks until no other reader or writer holds the lock.
+    pub fn write(&self) -> WriteGuard<'_, K, V> {
+        WriteGuard {
+            guard: self.tree.write().unwrap(),
+        }
+    }
+
+    pub fn into_inner(self) -> AVLTree<K, V> {
+        self.tree.into_inner().unwrap()
+    }
+}
+
+impl<K, V> RwMap<K, V>
+where
+    K: Ord,
+{
+    pub fn get_cloned(&self, k: &K) -> Option<V>
+    where
+        V: Clone,
+    {
+        self.read().get(k).cloned()
+    }
+
+    pub fn insert(&self, k: K, v: V) {
+        self.write().insert(k, v)
+    }
+
+    pub fn remove(&self, k: &K) -> Option<V> {
+        self.write().remove(k)
+    }
+}
+
+impl<K, V> ReadGuard<'_, K, V>
+where
+    K: Ord,
+{
+    /// Returns an iterator over the entries within `range`, borrowed from the guard.
+    pub fn range<R: RangeBounds<K>>(&self, range: R) -> Iter<'_, K, V> {
+        self.guard.range(range)
+    }
+}
+
+impl<K, V> Deref for ReadGuard<'_, K, V> {
+    type Target = AVLTree<K, V>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.guard
+    }
+}
+
+impl<K, V> Deref for WriteGuard<'_, K, V> {
+    type Target = AVLTree<K, V>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.guard
+    }
+}
+
+impl<K, V> DerefMut for WriteGuard<'_, K, V> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.guard
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{sync::Arc, thread};
+
+    use super::RwMap;
+
+    #[test]
+    fn write_then_read() {
+        let map = RwMap::new();
+        {
+            let mut guard = map.write();
+            guard.insert(1, 10);
+            guard.insert(2, 20);
+            assert_eq!(guard.remove(&1), Some(10));
+        }
+        let guard = map.read();
+        assert_eq!(guard.get(&1), None);
+        assert_eq!(guard.get(&2), Some(&20));
+    }
+
+    #[test]
+    fn read_range() {
+        let map = RwMap::new();
+        for i in 0..10 {
+            map.insert(i, i);
+        }
+        let guard = map.read();
+        let keys = guard.range(4..7).map(|e| *e.0).collect::<Vec<_>>();
+        assert_eq!(keys, vec![4, 5, 6]);
+    }
+
+    #[test]
+    fn concurrent_readers() {
+        let map = Arc::new(RwMap::new());
+        map.insert(1, 1);
+        let first = map.read();
+        let handle = {
+            let map = map.clone();
+            thread::spawn(move || map.get_cloned(&1))
+        };
+        assert_eq!(handle.join().unwrap(), Some(1));
+        drop(first);
+    }
+}
@@ -0,0 +1,385 @@
+use std::{ptr::NonNull, sync::Mutex};
+
+#[cfg(feature = "async")]
+use std::{
+    cell::UnsafeCell,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+#[cfg(feature = "async")]
+use crate::sync::waiter_list::Waiter;
+use crate::sync::waiter_list::{Parker, WaiterList};
+
+/// A counting semaphore, acquired by blocking the thread or, with the `async` feature, by
+/// awaiting a future. Threads and futures wait in one queue and each is woken on its own
+/// once its permits have been handed to it, so releasing permits never wakes waiters which
+/// can't be served.
+#[derive(Debug)]
+pub struct Semaphore {
+    state: Mutex<State>,
+    fairness: Fairness,
+}
+
+/// The order in which a `Semaphore` serves waiters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Fairness {
+    /// Waiters are served in FIFO order, and new arrivals queue behind them, so a large
+    /// request at the front of the queue is never starved by a stream of smaller ones.
+    #[default]
+    Fair,
+    /// New arrivals take permits whenever enough are free, and released permits go to any
+    /// queued waiter they satisfy, in queue order. Throughput is higher, but large requests
+    /// can starve.
+    Barging,
+}
+
+#[derive(Debug)]
+struct State {
+    permits: usize,
+    waiters: WaiterList,
+}
+
+impl Semaphore {
+    pub fn new(permits: usize) -> Self {
+        Semaphore::with_fairness(permits, Fairness::Fair)
+    }
+
+    pub fn with_fairness(permits: usize, fairness: Fairness) -> Self {
+        Semaphore {
+            state: Mutex::new(State {
+                permits,
+                waiters: WaiterList::new(),
+            }),
+            fairness,
+        }
+    }
+
+    pub fn fairness(&self) -> Fairness {
+        self.fairness
+    }
+
+    pub fn available_permits(&self) -> usize {
+        self.state.lock().unwrap().permits
+    }
+
+    pub fn try_acquire(&self) -> Option<Permit<'_>> {
+        self.try_acquire_many(1)
+    }
+
+    pub fn try_acquire_many(&self, n: usize) -> Option<Permit<'_>> {
+        let mut state = self.state.lock().unwrap();
+        if self.can_take(&state, n) {
+            state.permits -= n;
+            Some(Permit {
+                semaphore: self,
+                permits: n,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Blocks the thread until a permit is handed to it.
+    pub fn acquire_blocking(&self) -> Permit<'_> {
+        self.acquire_many_blocking(1)
+    }
+
+    /// Blocks the thread until `n` permits are handed to it.
+    pub fn acquire_many_blocking(&self, n: usize) -> Permit<'_> {
+        let mut state = self.state.lock().unwrap();
+        if !self.can_take(&state, n) {
+            let parker = Parker::new();
+            let mut waiter = parker.waiter(n);
+            // The waiter stays on this stack frame until the parker is woken, by which
+            // point it has been removed from the list and given its permits
+            unsafe { state.waiters.push_back(NonNull::from(&mut waiter)) };
+            drop(state);
+            parker.park();
+        } else {
+            state.permits -= n;
+        }
+        Permit {
+            semaphore: self,
+            permits: n,
+        }
+    }
+
+    #[cfg(feature = "async")]
+    pub fn acquire(&self) -> Acquire<'_> {
+        self.acquire_many(1)
+    }
+
+    #[cfg(feature = "async")]
+    pub fn acquire_many(&self, n: usize) -> Acquire<'_> {
+        Acquire {
+            semaphore: self,
+            waiter: UnsafeCell::new(Waiter::new(n)),
+        }
+    }
+
+    /// Returns permits to the semaphore, handing them to queued waiters as the fairness
+    /// policy allows.
+    pub fn add_permits(&self, n: usize) {
+        let mut state = self.state.lock().unwrap();
+        state.permits += n;
+        let mut wakers = vec![];
+        let mut next = state.waiters.front();
+        while let Some(node) = next {
+            unsafe {
+                next = state.waiters.next(node);
+                let wanted = node.as_ref().wanted;
+                if wanted <= state.permits {
+                    state.permits -= wanted;
+                    wakers.extend(state.waiters.notify(node));
+                } else if self.fairness == Fairness::Fair {
+                    break;
+                }
+            }
+        }
+        drop(state);
+        wakers.into_iter().for_each(|w| w.wake());
+    }
+
+    /// Whether a new arrival asking for `n` permits may take them without queueing.
+    fn can_take(&self, state: &State, n: usize) -> bool {
+        // Under the fair policy, queued waiters take precedence over new arrivals
+        (self.fairness == Fairness::Barging || state.waiters.is_empty()) && state.permits >= n
+    }
+}
+
+/// Permits acquired from a `Semaphore`, which are released when dropped.
+#[derive(Debug)]
+pub struct Permit<'a> {
+    semaphore: &'a Semaphore,
+    permits: usize,
+}
+
+impl Permit<'_> {
+    /// Drops the guard without returning the permits to the semaphore.
+    pub fn forget(mut self) {
+        self.permits = 0;
+    }
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        if self.permits > 0 {
+            self.semaphore.add_permits(self.permits);
+        }
+    }
+}
+
+/// Future returned by `Semaphore::acquire`.
+#[cfg(feature = "async")]
+#[derive(Debug)]
+pub struct Acquire<'a> {
+    semaphore: &'a Semaphore,
+    waiter: UnsafeCell<Waiter>,
+}
+
+#[cfg(feature = "async")]
+unsafe impl Send for Acquire<'_> {}
+
+#[cfg(feature = "async")]
+impl<'a> Future for Acquire<'a> {
+    type Output = Permit<'a>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Permit<'a>> {
+        let semaphore = self.semaphore;
+        let mut state = semaphore.state.lock().unwrap();
+        // The waiter is only accessed under the lock, and it is pinned along with the future
+        unsafe {
+            let node = NonNull::new_unchecked(self.waiter.get());
+            let waiter = &mut *node.as_ptr();
+            let ready = if waiter.notified {
+                // Permits were handed to us by `add_permits`
+                true
+            } else if !waiter.queued && semaphore.can_take(&state, waiter.wanted) {
+                state.permits -= waiter.wanted;
+                true
+            } else {
+                false
+            };
+
+            if ready {
+                // Ownership of the permits moves from the future to the guard
+                let permits = std::mem::take(&mut waiter.wanted);
+                waiter.notified = false;
+                return Poll::Ready(Permit { semaphore, permits });
+            }
+
+            waiter.waker = Some(cx.waker().clone());
+            if !waiter.queued {
+                state.waiters.push_back(node);
+            }
+        }
+        Poll::Pending
+    }
+}
+
+#[cfg(feature = "async")]
+impl Drop for Acquire<'_> {
+    fn drop(&mut self) {
+        let mut state = self.semaphore.state.lock().unwrap();
+        unsafe {
+            let node = NonNull::new_unchecked(self.waiter.get());
+            let waiter = node.as_ref();
+            let returned = if waiter.queued {
+                state.waiters.remove(node);
+                0
+            } else if waiter.notified {
+                // We were handed permits but never observed them
+                waiter.wanted
+            } else {
+                return;
+            };
+            drop(state);
+            // The waiters which were queued behind this one may be served now
+            self.semaphore.add_permits(returned);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #[cfg(feature = "async")]
+    use std::{
+        future::Future,
+        pin::pin,
+        task::{Context, Poll, Waker},
+    };
+    use std::{sync::Arc, thread};
+
+    use super::{Fairness, Semaphore};
+    #[cfg(feature = "async")]
+    use crate::sync::test_util::block_on;
+
+    #[test]
+    fn try_acquire() {
+        let semaphore = Semaphore::new(2);
+        let a = semaphore.try_acquire().unwrap();
+        let _b = semaphore.try_acquire().unwrap();
+        assert!(semaphore.try_acquire().is_none());
+        drop(a);
+        assert_eq!(semaphore.available_permits(), 1);
+    }
+
+    #[test]
+    fn forget() {
+        let semaphore = Semaphore::new(1);
+        semaphore.try_acquire().unwrap().forget();
+        assert_eq!(semaphore.available_permits(), 0);
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn fifo_order() {
+        let semaphore = Semaphore::new(0);
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut big = pin!(semaphore.acquire_many(2));
+        let mut small = pin!(semaphore.acquire());
+        assert!(big.as_mut().poll(&mut cx).is_pending());
+        assert!(small.as_mut().poll(&mut cx).is_pending());
+
+        // The large request at the front blocks the small one
+        semaphore.add_permits(1);
+        assert!(small.as_mut().poll(&mut cx).is_pending());
+        semaphore.add_permits(1);
+        let Poll::Ready(permit) = big.as_mut().poll(&mut cx) else {
+            panic!("expected the large request to complete");
+        };
+        assert_eq!(semaphore.available_permits(), 0);
+        assert!(small.as_mut().poll(&mut cx).is_pending());
+        drop(permit);
+        assert!(small.as_mut().poll(&mut cx).is_ready());
+        assert_eq!(semaphore.available_permits(), 2);
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn cancelled_waiter_returns_permits() {
+        let semaphore = Semaphore::new(0);
+        let mut cx = Context::from_waker(Waker::noop());
+        {
+            let mut acquire = pin!(semaphore.acquire());
+            assert!(acquire.as_mut().poll(&mut cx).is_pending());
+            semaphore.add_permits(1);
+        }
+        assert_eq!(semaphore.available_permits(), 1);
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn cancelled_head_unblocks_queue() {
+        let semaphore = Semaphore::new(1);
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut small = pin!(semaphore.acquire());
+        {
+            let mut big = pin!(semaphore.acquire_many(2));
+            assert!(big.as_mut().poll(&mut cx).is_pending());
+            assert!(small.as_mut().poll(&mut cx).is_pending());
+        }
+        // The free permit goes to the waiter which was stuck behind the cancelled one
+        assert!(small.as_mut().poll(&mut cx).is_ready());
+        assert_eq!(semaphore.available_permits(), 1);
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn acquire_across_threads() {
+        let semaphore = Arc::new(Semaphore::new(0));
+        let releaser = semaphore.clone();
+        let handle = thread::spawn(move || releaser.add_permits(1));
+        let permit = block_on(semaphore.acquire());
+        drop(permit);
+        handle.join().unwrap();
+        assert_eq!(semaphore.available_permits(), 1);
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn barging() {
+        let semaphore = Semaphore::with_fairness(0, Fairness::Barging);
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut big = pin!(semaphore.acquire_many(2));
+        let mut small = pin!(semaphore.acquire());
+        assert!(big.as_mut().poll(&mut cx).is_pending());
+        assert!(small.as_mut().poll(&mut cx).is_pending());
+
+        // The small request behind the large one is served first
+        semaphore.add_permits(1);
+        let Poll::Ready(served) = small.as_mut().poll(&mut cx) else {
+            panic!("expected the small request to complete");
+        };
+        // And a new arrival can take permits ahead of the queued request
+        semaphore.add_permits(1);
+        let barged = semaphore.try_acquire().unwrap();
+        assert!(big.as_mut().poll(&mut cx).is_pending());
+        drop(served);
+        drop(barged);
+        assert!(big.as_mut().poll(&mut cx).is_ready());
+    }
+
+    #[test]
+    fn blocking_acquire() {
+        let semaphore = Arc::new(Semaphore::new(0));
+        let handles = (0..8)
+            .map(|_| {
+                let semaphore = semaphore.clone();
+                thread::spawn(move || semaphore.acquire_many_blocking(2).forget())
+            })
+            .collect::<Vec<_>>();
+        semaphore.add_permits(16);
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert_eq!(semaphore.available_permits(), 0);
+        assert_eq!(semaphore.fairness(), Fairness::Fair);
+        // Released permits go back to the semaphore
+        semaphore.add_permits(1);
+        drop(semaphore.acquire_blocking());
+        assert_eq!(semaphore.available_permits(), 1);
+    }
+}
@@ -0,0 +1,92 @@
+use std::sync::{Arc, Condvar, Mutex, OnceLock};
+
+/// A single-assignment cell. The value may be set at most once,
+/// after which it can be read by any number of threads.
+#[derive(Debug)]
+pub struct SetOnce<T> {
+    state: Arc<(OnceLock<T>, Mutex<()>, Condvar)>,
+}
+
+impl<T> SetOnce<T> {
+    pub fn new() -> Self {
+        SetOnce {
+            state: Arc::new((OnceLock::new(), Mutex::new(()), Condvar::new())),
+        }
+    }
+
+    /// Publishes the value, waking up any waiting threads.
+    /// If the cell has already been set, the value is handed back.
+    pub fn set(&self, value: T) -> Result<(), T> {
+        let (cell, lock, cvar) = &*self.state;
+        let _guard = lock.lock().unwrap();
+        cell.set(value)?;
+        cvar.notify_all();
+        Ok(())
+    }
+
+    pub fn get(&self) -> Option<&T> {
+        let (cell, _, _) = &*self.state;
+        cell.get()
+    }
+
+    pub fn is_set(&self) -> bool {
+        self.get().is_some()
+    }
+
+    /// Blocks until the value has been set.
+    pub fn wait(&self) -> &T {
+        let (cell, lock, cvar) = &*self.state;
+        let mut guard = lock.lock().unwrap();
+        loop {
+            if let Some(value) = cell.get() {
+                return value;
+            }
+            guard = cvar.wait(guard).unwrap();
+        }
+    }
+}
+
+impl<T> Clone for SetOnce<T> {
+    fn clone(&self) -> Self {
+        SetOnce {
+            state: self.state.clone(),
+        }
+    }
+}
+
+impl<T> Default for SetOnce<T> {
+    fn default() -> Self {
+        SetOnce::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::thread;
+
+    use super::SetOnce;
+
+    #[test]
+    fn get_unset() {
+        let cell = SetOnce::<i32>::new();
+        assert_eq!(cell.get(), None);
+        assert!(!cell.is_set());
+    }
+
+    #[test]
+    fn set_once() {
+        let cell = SetOnce::new();
+        assert_eq!(cell.set(1), Ok(()));
+        assert_eq!(cell.set(2), Err(2));
+        assert_eq!(cell.get(), Some(&1));
+    }
+
+    #[test]
+    fn wait_for_other_thread() {
+        let cell = SetOnce::new();
+        let writer = cell.clone();
+        let handle = thread::spawn(move || writer.set(5).unwrap());
+        assert_eq!(cell.wait(), &5);
+        handle.join().unwrap();
+    }
+}
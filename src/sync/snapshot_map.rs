@@ -0,0 +1,230 @@
+use std::{
+    hint,
+    marker::PhantomData,
+    ptr,
+    sync::{
+        atomic::{AtomicPtr, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+};
+
+use crate::avl_tree::AVLTree;
+
+/// An ordered map optimized for many readers and rare writers.
+/// Readers take an `Arc` snapshot of the current tree without acquiring any locks,
+/// while writers copy the tree, mutate the copy, and publish it atomically.
+#[derive(Debug)]
+pub struct SnapshotMap<K, V> {
+    current: AtomicPtr<AVLTree<K, V>>,
+    // Each reader announces the version it is about to take a reference to in a slot
+    // of its own, so a writer only waits for readers of the version it replaced.
+    slots: Box<[PaddedSlot<K, V>]>,
+    writer: Mutex<()>,
+    _marker: PhantomData<Arc<AVLTree<K, V>>>,
+}
+
+#[derive(Debug)]
+#[repr(align(128))]
+struct PaddedSlot<K, V>(AtomicPtr<AVLTree<K, V>>);
+
+static NEXT_SLOT: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    // Threads start looking for a free slot at one assigned round robin on first use
+    static SLOT: usize = NEXT_SLOT.fetch_add(1, Ordering::Relaxed);
+}
+
+impl<K, V> SnapshotMap<K, V> {
+    pub fn new() -> Self {
+        let slots = thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1);
+        SnapshotMap {
+            current: AtomicPtr::new(Arc::into_raw(Arc::new(AVLTree::<K, V>::new())) as *mut _),
+            slots: (0..slots)
+                .map(|_| PaddedSlot(AtomicPtr::new(ptr::null_mut())))
+                .collect(),
+            writer: Mutex::new(()),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns the most recently published version of the map.
+    pub fn snapshot(&self) -> Arc<AVLTree<K, V>> {
+        let start = SLOT.with(|slot| *slot);
+        loop {
+            for i in 0..self.slots.len() {
+                let slot = &self.slots[(start + i) % self.slots.len()].0;
+                let ptr = self.current.load(Ordering::SeqCst);
+                if slot
+                    .compare_exchange(ptr::null_mut(), ptr, Ordering::SeqCst, Ordering::Relaxed)
+                    .is_err()
+                {
+                    continue;
+                }
+                // If the version is still current, the writer which replaces it will find
+                // it announced and wait for us before releasing its reference
+                let valid = self.current.load(Ordering::SeqCst) == ptr;
+                let snapshot = valid.then(|| unsafe {
+                    Arc::increment_strong_count(ptr);
+                    Arc::from_raw(ptr)
+                });
+                slot.store(ptr::null_mut(), Ordering::Release);
+                if let Some(snapshot) = snapshot {
+                    return snapshot;
+                }
+            }
+            // Every slot is taken by other readers
+            hint::spin_loop();
+        }
+    }
+
+    fn publish(&self, tree: AVLTree<K, V>) {
+        let old = self
+            .current
+            .swap(Arc::into_raw(Arc::new(tree)) as *mut _, Ordering::SeqCst);
+        // Readers which announce the old version from here on fail to validate it, so
+        // this only waits on those which were already taking their reference.
+        for slot in self.slots.iter() {
+            while slot.0.load(Ordering::SeqCst) == old {
+                hint::spin_loop();
+            }
+        }
+        unsafe { drop(Arc::from_raw(old)) };
+    }
+}
+
+impl<K, V> SnapshotMap<K, V>
+where
+    K: Ord + Clone,
+    V: Clone,
+{
+    pub fn get(&self, k: &K) -> Option<V> {
+        self.snapshot().get(k).cloned()
+    }
+
+    /// Applies `f` to a private copy of the map and publishes the result.
+    /// Use this to batch several mutations into a single version.
+    pub fn update<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&mut AVLTree<K, V>) -> R,
+    {
+        let _guard = self.writer.lock().unwrap();
+        let mut tree = AVLTree::clone(&self.snapshot());
+        let out = f(&mut tree);
+        self.publish(tree);
+        out
+    }
+
+    pub fn insert(&self, k: K, v: V) -> Option<V> {
+        self.update(|tree| {
+            let old = tree.remove(&k);
+            tree.insert(k, v);
+            old
+        })
+    }
+
+    pub fn remove(&self, k: &K) -> Option<V> {
+        self.update(|tree| tree.remove(k))
+    }
+}
+
+impl<K, V> Default for SnapshotMap<K, V> {
+    fn default() -> Self {
+        SnapshotMap::new()
+    }
+}
+
+impl<K, V> Drop for SnapshotMap<K, V> {
+    fn drop(&mut self) {
+        unsafe { drop(Arc::from_raw(*self.current.get_mut())) };
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+        thread,
+    };
+
+    use super::SnapshotMap;
+
+    #[test]
+    fn insert_and_get() {
+        let map = SnapshotMap::new();
+        assert_eq!(map.insert(1, 10), None);
+        assert_eq!(map.insert(1, 11), Some(10));
+        assert_eq!(map.get(&1), Some(11));
+        assert_eq!(map.remove(&1), Some(11));
+        assert_eq!(map.get(&1), None);
+    }
+
+    #[test]
+    fn snapshot_is_stable() {
+        let map = SnapshotMap::new();
+        map.insert(1, 10);
+        let snapshot = map.snapshot();
+        map.insert(2, 20);
+        map.remove(&1);
+        assert_eq!(snapshot.get(&1), Some(&10));
+        assert_eq!(snapshot.get(&2), None);
+        assert_eq!(map.get(&2), Some(20));
+    }
+
+    #[test]
+    fn concurrent_readers() {
+        let map = Arc::new(SnapshotMap::new());
+        let readers = (0..4)
+            .map(|_| {
+                let map = map.clone();
+                thread::spawn(move || {
+                    for _ in 0..1000 {
+                        let snapshot = map.snapshot();
+                        // Writers always insert keys in order, so every
+                        // snapshot must contain a prefix of them
+                        if let Some(last) = snapshot.last() {
+                            for i in 0..=*last {
+                                assert_eq!(snapshot.get(&i), Some(&i));
+                            }
+                        }
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for i in 0..100 {
+            map.insert(i, i);
+        }
+        for reader in readers {
+            reader.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn writers_progress_under_constant_reads() {
+        let map = Arc::new(SnapshotMap::new());
+        let done = Arc::new(AtomicBool::new(false));
+        let readers = (0..8)
+            .map(|_| {
+                let (map, done) = (map.clone(), done.clone());
+                thread::spawn(move || {
+                    while !done.load(Ordering::Relaxed) {
+                        let _ = map.get(&0);
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for i in 0..100 {
+            map.insert(i, i);
+        }
+        done.store(true, Ordering::Relaxed);
+        for reader in readers {
+            reader.join().unwrap();
+        }
+        assert_eq!(map.get(&99), Some(99));
+    }
+}
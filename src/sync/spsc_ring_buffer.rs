@@ -0,0 +1,314 @@
+use std::mem::MaybeUninit;
+
+use crate::{
+    capacity::Capacity,
+    sync::primitives::{
+        fence, thread, Arc, AtomicBool, AtomicUsize, Mutex, Ordering, Thread, UnsafeCell,
+    },
+    Error,
+};
+
+/// A fixed-capacity ring buffer shared between exactly one producer and one consumer.
+/// `try_push` and `try_pop` are wait-free, while `push` and `pop` park the calling thread
+/// until the buffer has room or an element respectively.
+#[derive(Debug)]
+pub struct SpscRingBuffer<T> {
+    slots: Box<[UnsafeCell<MaybeUninit<T>>]>,
+    // Read index, only advanced by the consumer
+    head: AtomicUsize,
+    // Write index, only advanced by the producer
+    tail: AtomicUsize,
+    producer: Waiter,
+    consumer: Waiter,
+}
+
+/// The writing half of a `SpscRingBuffer`.
+#[derive(Debug)]
+pub struct Producer<T> {
+    buffer: Arc<SpscRingBuffer<T>>,
+}
+
+/// The reading half of a `SpscRingBuffer`.
+#[derive(Debug)]
+pub struct Consumer<T> {
+    buffer: Arc<SpscRingBuffer<T>>,
+}
+
+unsafe impl<T: Send> Send for SpscRingBuffer<T> {}
+unsafe impl<T: Send> Sync for SpscRingBuffer<T> {}
+
+impl<T> SpscRingBuffer<T> {
+    /// Creates a ring buffer, returning its producer and consumer halves.
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(capacity: usize) -> (Producer<T>, Consumer<T>) {
+        assert!(capacity > 0, "capacity must be positive");
+        let buffer = Arc::new(SpscRingBuffer {
+            slots: (0..capacity)
+                .map(|_| UnsafeCell::new(MaybeUninit::uninit()))
+                .collect(),
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+            producer: Waiter::new(),
+            consumer: Waiter::new(),
+        });
+        (
+            Producer {
+                buffer: buffer.clone(),
+            },
+            Consumer { buffer },
+        )
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn len(&self) -> usize {
+        let tail = self.tail.load(Ordering::Acquire);
+        let head = self.head.load(Ordering::Acquire);
+        tail.wrapping_sub(head)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len() == self.capacity()
+    }
+
+    /// How many more elements can be pushed before the producer blocks.
+    pub fn remaining_capacity(&self) -> usize {
+        Capacity::Entries(self.capacity()).remaining(self.len())
+    }
+
+    fn slot(&self, index: usize) -> &UnsafeCell<MaybeUninit<T>> {
+        &self.slots[index % self.capacity()]
+    }
+
+    fn try_push(&self, value: T) -> Result<(), Error<T>> {
+        let tail = self.tail.load(Ordering::Relaxed);
+        let head = self.head.load(Ordering::Acquire);
+        if tail.wrapping_sub(head) == self.capacity() {
+            return Err(Error::Full(value));
+        }
+        self.slot(tail)
+            .with_mut(|slot| unsafe { slot.write(MaybeUninit::new(value)) });
+        self.tail.store(tail.wrapping_add(1), Ordering::Release);
+        self.consumer.notify();
+        Ok(())
+    }
+
+    fn try_pop(&self) -> Option<T> {
+        let head = self.head.load(Ordering::Relaxed);
+        let tail = self.tail.load(Ordering::Acquire);
+        if head == tail {
+            return None;
+        }
+        let value = self
+            .slot(head)
+            .with(|slot| unsafe { slot.read().assume_init() });
+        self.head.store(head.wrapping_add(1), Ordering::Release);
+        self.producer.notify();
+        Some(value)
+    }
+}
+
+impl<T> Drop for SpscRingBuffer<T> {
+    fn drop(&mut self) {
+        while self.try_pop().is_some() {}
+    }
+}
+
+impl<T> Producer<T> {
+    pub fn try_push(&mut self, value: T) -> Result<(), Error<T>> {
+        self.buffer.try_push(value)
+    }
+
+    /// Blocks until there is room in the buffer.
+    pub fn push(&mut self, value: T) {
+        let mut value = value;
+        loop {
+            match self.buffer.try_push(value) {
+                Ok(()) => return,
+                Err(e) => value = e.into_inner(),
+            }
+            self.buffer.producer.wait_until(|| !self.buffer.is_full());
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.buffer.capacity()
+    }
+
+    pub fn remaining_capacity(&self) -> usize {
+        self.buffer.remaining_capacity()
+    }
+
+    pub fn len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buffer.is_empty()
+    }
+}
+
+impl<T> Consumer<T> {
+    pub fn try_pop(&mut self) -> Option<T> {
+        self.buffer.try_pop()
+    }
+
+    /// Blocks until an element is available.
+    pub fn pop(&mut self) -> T {
+        loop {
+            if let Some(value) = self.buffer.try_pop() {
+                return value;
+            }
+            self.buffer.consumer.wait_until(|| !self.buffer.is_empty());
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.buffer.capacity()
+    }
+
+    pub fn remaining_capacity(&self) -> usize {
+        self.buffer.remaining_capacity()
+    }
+
+    pub fn len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buffer.is_empty()
+    }
+}
+
+/// Parks a single thread until the opposite side makes progress.
+/// The lock is only taken on the slow path, when a thread is actually parked.
+#[derive(Debug)]
+struct Waiter {
+    waiting: AtomicBool,
+    thread: Mutex<Option<Thread>>,
+}
+
+impl Waiter {
+    fn new() -> Self {
+        Waiter {
+            waiting: AtomicBool::new(false),
+            thread: Mutex::new(None),
+        }
+    }
+
+    fn wait_until<F: Fn() -> bool>(&self, ready: F) {
+        *self.thread.lock().unwrap() = Some(thread::current());
+        self.waiting.store(true, Ordering::SeqCst);
+        fence(Ordering::SeqCst);
+        // Check again after registering, in case we raced with a notification
+        if !ready() {
+            thread::park();
+        }
+        self.waiting.store(false, Ordering::SeqCst);
+    }
+
+    fn notify(&self) {
+        fence(Ordering::SeqCst);
+        if self.waiting.load(Ordering::SeqCst) {
+            if let Some(thread) = self.thread.lock().unwrap().as_ref() {
+                thread.unpark();
+            }
+        }
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod test {
+    use std::{sync::Arc, thread};
+
+    use super::SpscRingBuffer;
+    use crate::Error;
+
+    #[test]
+    fn push_and_pop() {
+        let (mut producer, mut consumer) = SpscRingBuffer::new(2);
+        assert_eq!(producer.try_push(1), Ok(()));
+        assert_eq!(producer.try_push(2), Ok(()));
+        assert_eq!(producer.try_push(3), Err(Error::Full(3)));
+        assert_eq!(consumer.try_pop(), Some(1));
+        assert_eq!(producer.try_push(3), Ok(()));
+        assert_eq!(consumer.try_pop(), Some(2));
+        assert_eq!(consumer.try_pop(), Some(3));
+        assert_eq!(consumer.try_pop(), None);
+    }
+
+    #[test]
+    fn len() {
+        let (mut producer, consumer) = SpscRingBuffer::new(4);
+        assert!(consumer.is_empty());
+        producer.push(1);
+        producer.push(2);
+        assert_eq!(consumer.len(), 2);
+        assert_eq!(producer.capacity(), 4);
+        assert_eq!(producer.remaining_capacity(), 2);
+    }
+
+    #[test]
+    fn drop_remaining() {
+        let counter = Arc::new(());
+        let (mut producer, consumer) = SpscRingBuffer::new(4);
+        producer.push(counter.clone());
+        producer.push(counter.clone());
+        drop(producer);
+        drop(consumer);
+        assert_eq!(Arc::strong_count(&counter), 1);
+    }
+
+    #[test]
+    fn blocking_transfer() {
+        // Miri is slow enough that a few hundred operations already explore plenty of schedules
+        const COUNT: usize = if cfg!(miri) { 200 } else { 10_000 };
+        let (mut producer, mut consumer) = SpscRingBuffer::new(8);
+        let handle = thread::spawn(move || {
+            for i in 0..COUNT {
+                producer.push(i);
+            }
+        });
+        for i in 0..COUNT {
+            assert_eq!(consumer.pop(), i);
+        }
+        handle.join().unwrap();
+    }
+}
+
+#[cfg(all(test, loom))]
+mod loom_test {
+    use loom::thread;
+
+    use super::SpscRingBuffer;
+
+    #[test]
+    fn push_and_pop() {
+        loom::model(|| {
+            let (mut producer, mut consumer) = SpscRingBuffer::new(1);
+            let handle = thread::spawn(move || {
+                producer.push(1);
+                producer.push(2);
+            });
+            assert_eq!(consumer.pop(), 1);
+            assert_eq!(consumer.pop(), 2);
+            handle.join().unwrap();
+        });
+    }
+
+    #[test]
+    fn drop_unconsumed() {
+        loom::model(|| {
+            let (mut producer, consumer) = SpscRingBuffer::new(2);
+            let handle = thread::spawn(move || producer.try_push(String::from("a")).is_ok());
+            drop(consumer);
+            assert!(handle.join().unwrap());
+        });
+    }
+}
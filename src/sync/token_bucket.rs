@@ -0,0 +1,142 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crate::clock::{Clock, SystemClock};
+
+/// A token bucket rate limiter.
+/// The bucket holds up to `capacity` tokens and is refilled continuously at
+/// `rate` tokens per second. Refills are computed lazily from the elapsed time
+/// whenever the bucket is accessed, so no background thread is needed.
+/// Time is read from a `Clock`, and `acquire` waits by sleeping on it, so a bucket given a
+/// `MockClock` never blocks.
+#[derive(Debug)]
+pub struct TokenBucket<C = SystemClock> {
+    capacity: u64,
+    rate: f64,
+    state: Mutex<State>,
+    clock: C,
+}
+
+#[derive(Debug)]
+struct State {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    /// Creates a full bucket.
+    pub fn new(capacity: u64, rate: f64) -> Self {
+        TokenBucket::with_clock(capacity, rate, SystemClock)
+    }
+}
+
+impl<C: Clock> TokenBucket<C> {
+    /// Creates a full bucket which measures time with `clock`.
+    pub fn with_clock(capacity: u64, rate: f64, clock: C) -> Self {
+        assert!(rate > 0.0, "refill rate must be positive");
+        TokenBucket {
+            capacity,
+            rate,
+            state: Mutex::new(State {
+                tokens: capacity as f64,
+                last_refill: clock.now(),
+            }),
+            clock,
+        }
+    }
+
+    pub fn capacity(&self) -> u64 {
+        self.capacity
+    }
+
+    /// Returns the number of whole tokens currently available.
+    pub fn available(&self) -> u64 {
+        let mut state = self.state.lock().unwrap();
+        self.refill(&mut state);
+        state.tokens as u64
+    }
+
+    /// Takes `n` tokens if they are all available, without blocking.
+    pub fn try_acquire(&self, n: u64) -> bool {
+        self.try_acquire_or_wait_time(n).is_ok()
+    }
+
+    /// Blocks until `n` tokens are available and takes them.
+    pub fn acquire(&self, n: u64) {
+        assert!(
+            n <= self.capacity,
+            "cannot acquire more tokens than the capacity"
+        );
+        while let Err(wait) = self.try_acquire_or_wait_time(n) {
+            self.clock.sleep(wait);
+        }
+    }
+
+    /// On failure, returns how long it will take for `n` tokens to become available.
+    fn try_acquire_or_wait_time(&self, n: u64) -> Result<(), Duration> {
+        let mut state = self.state.lock().unwrap();
+        self.refill(&mut state);
+        let n = n as f64;
+        if state.tokens >= n {
+            state.tokens -= n;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((n - state.tokens) / self.rate))
+        }
+    }
+
+    fn refill(&self, state: &mut State) {
+        let now = self.clock.now();
+        let elapsed = now.duration_since(state.last_refill).as_secs_f64();
+        state.tokens = (state.tokens + elapsed * self.rate).min(self.capacity as f64);
+        state.last_refill = now;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::TokenBucket;
+    use crate::clock::{Clock, MockClock};
+
+    #[test]
+    fn starts_full() {
+        let bucket = TokenBucket::new(10, 1.0);
+        assert_eq!(bucket.available(), 10);
+        assert!(bucket.try_acquire(10));
+        assert!(!bucket.try_acquire(1));
+    }
+
+    #[test]
+    fn try_acquire_all_or_nothing() {
+        let bucket = TokenBucket::new(5, 1.0);
+        assert!(bucket.try_acquire(3));
+        assert!(!bucket.try_acquire(3));
+        assert!(bucket.try_acquire(2));
+    }
+
+    #[test]
+    fn refill_over_time() {
+        let clock = MockClock::new();
+        let bucket = TokenBucket::with_clock(10, 1000.0, clock.clone());
+        assert!(bucket.try_acquire(10));
+        clock.advance(Duration::from_millis(5));
+        assert_eq!(bucket.available(), 5);
+        clock.advance(Duration::from_millis(20));
+        assert_eq!(bucket.available(), 10);
+    }
+
+    #[test]
+    fn acquire_blocks_until_refilled() {
+        let clock = MockClock::new();
+        let bucket = TokenBucket::with_clock(10, 200.0, clock.clone());
+        bucket.acquire(10);
+        let start = clock.now();
+        bucket.acquire(4);
+        assert!(clock.now() - start >= Duration::from_millis(20));
+        assert_eq!(bucket.available(), 0);
+    }
+}
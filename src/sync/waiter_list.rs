@@ -0,0 +1,178 @@
+use std::{
+    marker::PhantomPinned,
+    ptr::NonNull,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    task::{Wake, Waker},
+    thread::{self, Thread},
+};
+
+/// An intrusive doubly linked list of waiting tasks and threads.
+/// Each node lives inside the (pinned) future that is waiting, or on the stack of the
+/// blocked thread, so enqueueing a waiter never allocates. The list and every node in it
+/// must only be accessed while holding the lock of the owning primitive.
+/// Waiters are woken individually through their own wakers, so releasing one never wakes
+/// the others to contend for the lock.
+#[derive(Debug)]
+pub(crate) struct WaiterList {
+    head: Option<NonNull<Waiter>>,
+    tail: Option<NonNull<Waiter>>,
+}
+
+#[derive(Debug)]
+pub(crate) struct Waiter {
+    pub(crate) waker: Option<Waker>,
+    // How many units of the resource this waiter is asking for
+    pub(crate) wanted: usize,
+    // Set once the waiter has been removed from the list and handed what it asked for
+    pub(crate) notified: bool,
+    pub(crate) queued: bool,
+    prev: Option<NonNull<Waiter>>,
+    next: Option<NonNull<Waiter>>,
+    _pin: PhantomPinned,
+}
+
+// The raw pointers are only dereferenced under the owner's lock.
+unsafe impl Send for WaiterList {}
+
+impl WaiterList {
+    pub(crate) fn new() -> Self {
+        WaiterList {
+            head: None,
+            tail: None,
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.head.is_none()
+    }
+
+    pub(crate) fn front(&self) -> Option<NonNull<Waiter>> {
+        self.head
+    }
+
+    /// The node must stay pinned until it has been removed from the list.
+    pub(crate) unsafe fn push_back(&mut self, mut node: NonNull<Waiter>) {
+        let waiter = node.as_mut();
+        waiter.prev = self.tail;
+        waiter.next = None;
+        waiter.queued = true;
+        match self.tail {
+            Some(mut tail) => tail.as_mut().next = Some(node),
+            None => self.head = Some(node),
+        }
+        self.tail = Some(node);
+    }
+
+    /// The node must currently be in this list.
+    pub(crate) unsafe fn remove(&mut self, mut node: NonNull<Waiter>) {
+        let waiter = node.as_mut();
+        match waiter.prev {
+            Some(mut prev) => prev.as_mut().next = waiter.next,
+            None => self.head = waiter.next,
+        }
+        match waiter.next {
+            Some(mut next) => next.as_mut().prev = waiter.prev,
+            None => self.tail = waiter.prev,
+        }
+        waiter.prev = None;
+        waiter.next = None;
+        waiter.queued = false;
+    }
+
+    /// The waiter queued after `node`, which must currently be in this list.
+    pub(crate) unsafe fn next(&self, node: NonNull<Waiter>) -> Option<NonNull<Waiter>> {
+        node.as_ref().next
+    }
+
+    /// Removes the waiter, marks it as notified, and returns its waker.
+    /// The node must currently be in this list.
+    pub(crate) unsafe fn notify(&mut self, mut node: NonNull<Waiter>) -> Option<Waker> {
+        self.remove(node);
+        let waiter = node.as_mut();
+        waiter.notified = true;
+        waiter.waker.take()
+    }
+
+    /// Removes the first waiter, marks it as notified, and returns its waker.
+    pub(crate) fn notify_front(&mut self) -> Option<Waker> {
+        let node = self.head?;
+        unsafe { self.notify(node) }
+    }
+
+    /// Notifies every waiter, returning their wakers so they can be woken outside of the lock.
+    pub(crate) fn notify_all(&mut self) -> Vec<Waker> {
+        let mut wakers = vec![];
+        while !self.is_empty() {
+            wakers.extend(self.notify_front());
+        }
+        wakers
+    }
+}
+
+impl Waiter {
+    pub(crate) fn new(wanted: usize) -> Self {
+        Waiter {
+            waker: None,
+            wanted,
+            notified: false,
+            queued: false,
+            prev: None,
+            next: None,
+            _pin: PhantomPinned,
+        }
+    }
+}
+
+/// Blocks a thread until its waiter is notified, so that threads can wait in the same queue
+/// as futures. The thread is only unparked by its own waker, and it learns it was notified
+/// from a flag set by that waker, without taking the primitive's lock again.
+#[derive(Debug)]
+pub(crate) struct Parker {
+    unparker: Arc<Unparker>,
+}
+
+#[derive(Debug)]
+struct Unparker {
+    thread: Thread,
+    woken: AtomicBool,
+}
+
+impl Wake for Unparker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.woken.store(true, Ordering::Release);
+        self.thread.unpark();
+    }
+}
+
+impl Parker {
+    pub(crate) fn new() -> Self {
+        Parker {
+            unparker: Arc::new(Unparker {
+                thread: thread::current(),
+                woken: AtomicBool::new(false),
+            }),
+        }
+    }
+
+    /// A waiter which unparks the current thread when notified.
+    pub(crate) fn waiter(&self, wanted: usize) -> Waiter {
+        let mut waiter = Waiter::new(wanted);
+        waiter.waker = Some(self.unparker.clone().into());
+        waiter
+    }
+
+    /// Parks until the waker is woken, which only happens once the waiter has been removed
+    /// from its list, so the waiter can be dropped afterwards.
+    pub(crate) fn park(&self) {
+        while !self.unparker.woken.load(Ordering::Acquire) {
+            thread::park();
+        }
+    }
+}
@@ -0,0 +1,373 @@
+use std::mem::MaybeUninit;
+
+use crate::sync::primitives::{fence, Arc, AtomicIsize, AtomicPtr, Mutex, Ordering, UnsafeCell};
+
+const MIN_CAPACITY: usize = 16;
+
+/// A Chase-Lev work-stealing deque.
+/// The owner pushes and pops at the bottom of the deque in LIFO order, while
+/// any number of `Stealer`s take from the top in FIFO order.
+#[derive(Debug)]
+pub struct WorkStealingDeque<T> {
+    inner: Arc<Inner<T>>,
+}
+
+/// A handle which steals from the top of a `WorkStealingDeque`.
+#[derive(Debug)]
+pub struct Stealer<T> {
+    inner: Arc<Inner<T>>,
+}
+
+#[derive(Debug)]
+struct Inner<T> {
+    top: AtomicIsize,
+    bottom: AtomicIsize,
+    buffer: AtomicPtr<Buffer<T>>,
+    // Buffers which have been replaced by a resize. Thieves may still be reading
+    // from them, so they are only freed once every handle has been dropped.
+    retired: Mutex<Vec<*mut Buffer<T>>>,
+}
+
+#[derive(Debug)]
+struct Buffer<T> {
+    slots: Box<[UnsafeCell<MaybeUninit<T>>]>,
+}
+
+unsafe impl<T: Send> Send for WorkStealingDeque<T> {}
+unsafe impl<T: Send> Send for Stealer<T> {}
+unsafe impl<T: Send> Sync for Stealer<T> {}
+
+impl<T> Buffer<T> {
+    fn alloc(capacity: usize) -> *mut Buffer<T> {
+        let slots = (0..capacity)
+            .map(|_| UnsafeCell::new(MaybeUninit::uninit()))
+            .collect();
+        Box::into_raw(Box::new(Buffer { slots }))
+    }
+
+    fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    fn slot(&self, index: isize) -> &UnsafeCell<MaybeUninit<T>> {
+        &self.slots[(index as usize) & (self.capacity() - 1)]
+    }
+
+    unsafe fn write(&self, index: isize, value: T) {
+        self.write_uninit(index, MaybeUninit::new(value));
+    }
+
+    unsafe fn write_uninit(&self, index: isize, value: MaybeUninit<T>) {
+        self.slot(index).with_mut(|slot| slot.write(value));
+    }
+
+    unsafe fn read(&self, index: isize) -> MaybeUninit<T> {
+        self.slot(index).with(|slot| slot.read())
+    }
+}
+
+impl<T> WorkStealingDeque<T> {
+    pub fn new() -> Self {
+        WorkStealingDeque {
+            inner: Arc::new(Inner {
+                top: AtomicIsize::new(0),
+                bottom: AtomicIsize::new(0),
+                buffer: AtomicPtr::new(Buffer::alloc(MIN_CAPACITY)),
+                retired: Mutex::new(vec![]),
+            }),
+        }
+    }
+
+    pub fn stealer(&self) -> Stealer<T> {
+        Stealer {
+            inner: self.inner.clone(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn push(&mut self, value: T) {
+        let inner = &*self.inner;
+        let bottom = inner.bottom.load(Ordering::Relaxed);
+        let top = inner.top.load(Ordering::Acquire);
+        let mut buffer = inner.buffer.load(Ordering::Relaxed);
+        unsafe {
+            if bottom - top >= (*buffer).capacity() as isize {
+                buffer = inner.grow(top, bottom);
+            }
+            (*buffer).write(bottom, value);
+        }
+        fence(Ordering::Release);
+        inner.bottom.store(bottom + 1, Ordering::Relaxed);
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        let inner = &*self.inner;
+        let bottom = inner.bottom.load(Ordering::Relaxed) - 1;
+        let buffer = inner.buffer.load(Ordering::Relaxed);
+        inner.bottom.store(bottom, Ordering::Relaxed);
+        fence(Ordering::SeqCst);
+        let top = inner.top.load(Ordering::Relaxed);
+
+        if top > bottom {
+            // The deque was already empty
+            inner.bottom.store(bottom + 1, Ordering::Relaxed);
+            return None;
+        }
+
+        let value = unsafe { (*buffer).read(bottom) };
+        if top == bottom {
+            // This is the last element, so we race with thieves for it
+            let won = inner
+                .top
+                .compare_exchange(top, top + 1, Ordering::SeqCst, Ordering::Relaxed)
+                .is_ok();
+            inner.bottom.store(bottom + 1, Ordering::Relaxed);
+            if !won {
+                return None;
+            }
+        }
+        Some(unsafe { value.assume_init() })
+    }
+}
+
+impl<T> Default for WorkStealingDeque<T> {
+    fn default() -> Self {
+        WorkStealingDeque::new()
+    }
+}
+
+impl<T> Stealer<T> {
+    /// Takes the oldest element from the deque, retrying if
+    /// another thread wins the race for the same element.
+    pub fn steal(&self) -> Option<T> {
+        let inner = &*self.inner;
+        loop {
+            let top = inner.top.load(Ordering::Acquire);
+            fence(Ordering::SeqCst);
+            let bottom = inner.bottom.load(Ordering::Acquire);
+            if top >= bottom {
+                return None;
+            }
+
+            let buffer = inner.buffer.load(Ordering::Acquire);
+            let value = unsafe { (*buffer).read(top) };
+            if inner
+                .top
+                .compare_exchange(top, top + 1, Ordering::SeqCst, Ordering::Relaxed)
+                .is_ok()
+            {
+                return Some(unsafe { value.assume_init() });
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T> Clone for Stealer<T> {
+    fn clone(&self) -> Self {
+        Stealer {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<T> Inner<T> {
+    fn len(&self) -> usize {
+        let bottom = self.bottom.load(Ordering::Relaxed);
+        let top = self.top.load(Ordering::Relaxed);
+        (bottom - top).max(0) as usize
+    }
+
+    /// Moves the live elements into a buffer of twice the capacity.
+    /// This must only be called by the owner.
+    unsafe fn grow(&self, top: isize, bottom: isize) -> *mut Buffer<T> {
+        let old = self.buffer.load(Ordering::Relaxed);
+        let new = Buffer::alloc((*old).capacity() * 2);
+        for i in top..bottom {
+            (*new).write_uninit(i, (*old).read(i));
+        }
+        self.buffer.store(new, Ordering::Release);
+        self.retired.lock().unwrap().push(old);
+        new
+    }
+}
+
+impl<T> Drop for Inner<T> {
+    fn drop(&mut self) {
+        // Every handle is gone, so there are no concurrent accesses left to order against
+        let top = self.top.load(Ordering::Relaxed);
+        let bottom = self.bottom.load(Ordering::Relaxed);
+        unsafe {
+            let buffer = Box::from_raw(self.buffer.load(Ordering::Relaxed));
+            for i in top..bottom {
+                buffer.read(i).assume_init_drop();
+            }
+            for retired in self.retired.get_mut().unwrap().drain(..) {
+                drop(Box::from_raw(retired));
+            }
+        }
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod test {
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        thread,
+    };
+
+    use super::WorkStealingDeque;
+
+    #[test]
+    fn pop_lifo() {
+        let mut deque = WorkStealingDeque::new();
+        deque.push(1);
+        deque.push(2);
+        deque.push(3);
+        assert_eq!(deque.pop(), Some(3));
+        assert_eq!(deque.pop(), Some(2));
+        assert_eq!(deque.pop(), Some(1));
+        assert_eq!(deque.pop(), None);
+    }
+
+    #[test]
+    fn steal_fifo() {
+        let mut deque = WorkStealingDeque::new();
+        let stealer = deque.stealer();
+        deque.push(1);
+        deque.push(2);
+        assert_eq!(stealer.steal(), Some(1));
+        assert_eq!(deque.pop(), Some(2));
+        assert_eq!(stealer.steal(), None);
+    }
+
+    #[test]
+    fn grow() {
+        let mut deque = WorkStealingDeque::new();
+        let stealer = deque.stealer();
+        for i in 0..100 {
+            deque.push(i);
+        }
+        assert_eq!(deque.len(), 100);
+        assert_eq!(stealer.steal(), Some(0));
+        assert_eq!(deque.pop(), Some(99));
+        assert_eq!(deque.len(), 98);
+    }
+
+    #[test]
+    fn drop_remaining() {
+        let counter = Arc::new(());
+        let mut deque = WorkStealingDeque::new();
+        for _ in 0..40 {
+            deque.push(counter.clone());
+        }
+        deque.pop();
+        drop(deque);
+        assert_eq!(Arc::strong_count(&counter), 1);
+    }
+
+    #[test]
+    fn concurrent_steal() {
+        // Miri is slow enough that a few hundred operations already explore plenty of schedules
+        const COUNT: usize = if cfg!(miri) { 200 } else { 10_000 };
+        let mut deque = WorkStealingDeque::new();
+        let sum = Arc::new(AtomicUsize::new(0));
+        let taken = Arc::new(AtomicUsize::new(0));
+
+        let thieves = (0..4)
+            .map(|_| {
+                let stealer = deque.stealer();
+                let sum = sum.clone();
+                let taken = taken.clone();
+                thread::spawn(move || {
+                    while taken.load(Ordering::SeqCst) < COUNT {
+                        if let Some(i) = stealer.steal() {
+                            sum.fetch_add(i, Ordering::SeqCst);
+                            taken.fetch_add(1, Ordering::SeqCst);
+                        }
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+
+        for i in 0..COUNT {
+            deque.push(i);
+            if i % 3 == 0 {
+                if let Some(i) = deque.pop() {
+                    sum.fetch_add(i, Ordering::SeqCst);
+                    taken.fetch_add(1, Ordering::SeqCst);
+                }
+            }
+        }
+        while let Some(i) = deque.pop() {
+            sum.fetch_add(i, Ordering::SeqCst);
+            taken.fetch_add(1, Ordering::SeqCst);
+        }
+
+        for thief in thieves {
+            thief.join().unwrap();
+        }
+        assert_eq!(sum.load(Ordering::SeqCst), COUNT * (COUNT - 1) / 2);
+    }
+}
+
+#[cfg(all(test, loom))]
+mod loom_test {
+    use loom::thread;
+
+    use super::WorkStealingDeque;
+
+    #[test]
+    fn pop_races_steal() {
+        loom::model(|| {
+            let mut deque = WorkStealingDeque::new();
+            let stealer = deque.stealer();
+            deque.push(1);
+            deque.push(2);
+            let thief = thread::spawn(move || stealer.steal());
+            let mut taken = vec![deque.pop(), deque.pop(), thief.join().unwrap()]
+                .into_iter()
+                .flatten()
+                .collect::<Vec<_>>();
+            taken.sort();
+            assert_eq!(taken, vec![1, 2]);
+        });
+    }
+
+    #[test]
+    fn steal_during_grow() {
+        loom::model(|| {
+            let mut deque = WorkStealingDeque::new();
+            let stealer = deque.stealer();
+            deque.push(0);
+            let thief = thread::spawn(move || stealer.steal());
+            // Filling past the initial capacity moves the elements into a new buffer
+            for i in 1..=16 {
+                deque.push(i);
+            }
+            let stolen = thief.join().unwrap();
+            let mut taken = std::iter::from_fn(|| deque.pop())
+                .chain(stolen)
+                .collect::<Vec<_>>();
+            taken.sort();
+            assert_eq!(taken, (0..=16).collect::<Vec<_>>());
+        });
+    }
+}
@@ -0,0 +1,971 @@
+//! `quickcheck::Arbitrary` implementations and `proptest` strategies for the collections,
+//! so code which embeds them can be property tested with either. Instances are built
+//! through the public API, so they are always structurally valid, and shrink by shrinking
+//! their contents.
+//! Structures with internal invariants also expose a `check_invariants` method with this
+//! feature, which panics when an invariant is broken.
+
+// Which implementations exist depends on the enabled features
+#![allow(unused_imports, dead_code)]
+
+use std::hash::Hash;
+
+use quickcheck::{Arbitrary, Gen};
+
+/// Shrinks a collection by shrinking its contents and rebuilding it.
+fn shrink_via<T, C>(contents: Vec<T>, build: fn(Vec<T>) -> C) -> Box<dyn Iterator<Item = C>>
+where
+    T: Arbitrary,
+    C: 'static,
+{
+    Box::new(contents.shrink().map(build))
+}
+
+#[cfg(feature = "avl_set")]
+impl<T: Arbitrary + Ord> Arbitrary for crate::avl_set::AVLSet<T> {
+    fn arbitrary(g: &mut Gen) -> Self {
+        Vec::<T>::arbitrary(g).into_iter().collect()
+    }
+
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+        shrink_via(self.iter().cloned().collect(), |v| v.into_iter().collect())
+    }
+}
+
+#[cfg(feature = "avl_tree")]
+impl<K, V> Arbitrary for crate::avl_tree::AVLTree<K, V>
+where
+    K: Arbitrary + Ord,
+    V: Arbitrary,
+{
+    fn arbitrary(g: &mut Gen) -> Self {
+        avl_tree(Vec::arbitrary(g))
+    }
+
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+        let entries = self.iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+        shrink_via(entries, avl_tree)
+    }
+}
+
+#[cfg(feature = "avl_tree")]
+fn avl_tree<K: Ord, V>(entries: Vec<(K, V)>) -> crate::avl_tree::AVLTree<K, V> {
+    let mut tree = crate::avl_tree::AVLTree::new();
+    for (k, v) in entries {
+        tree.insert(k, v);
+    }
+    tree
+}
+
+#[cfg(feature = "bag")]
+impl<T: Arbitrary + Eq + Hash> Arbitrary for crate::bag::Bag<T> {
+    fn arbitrary(g: &mut Gen) -> Self {
+        Vec::<T>::arbitrary(g).into_iter().collect()
+    }
+
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+        shrink_via(self.iter().cloned().collect(), |v| v.into_iter().collect())
+    }
+}
+
+#[cfg(feature = "bimap")]
+impl<L, R> Arbitrary for crate::bimap::BiMap<L, R>
+where
+    L: Arbitrary + Eq + Hash,
+    R: Arbitrary + Eq + Hash,
+{
+    fn arbitrary(g: &mut Gen) -> Self {
+        Vec::<(L, R)>::arbitrary(g).into_iter().collect()
+    }
+
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+        let pairs = self.iter().map(|(l, r)| (l.clone(), r.clone())).collect();
+        shrink_via(pairs, |v| v.into_iter().collect())
+    }
+}
+
+#[cfg(feature = "bitset")]
+impl Arbitrary for crate::bitset::BitSet {
+    fn arbitrary(g: &mut Gen) -> Self {
+        // Small indices keep the sets from allocating huge bit vectors
+        Vec::<u16>::arbitrary(g)
+            .into_iter()
+            .map(usize::from)
+            .collect()
+    }
+
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+        shrink_via(self.iter().collect(), |v| v.into_iter().collect())
+    }
+}
+
+#[cfg(feature = "compact")]
+impl Arbitrary for crate::compact::StaticSortedSet {
+    fn arbitrary(g: &mut Gen) -> Self {
+        Vec::<u64>::arbitrary(g).into_iter().collect()
+    }
+
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+        shrink_via(self.iter().collect(), |v| v.into_iter().collect())
+    }
+}
+
+#[cfg(feature = "hash_map")]
+impl<K, V> Arbitrary for crate::hash_map::FlatHashMap<K, V>
+where
+    K: Arbitrary + Eq + Hash,
+    V: Arbitrary,
+{
+    fn arbitrary(g: &mut Gen) -> Self {
+        Vec::<(K, V)>::arbitrary(g).into_iter().collect()
+    }
+
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+        let entries = self.iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+        shrink_via(entries, |v| v.into_iter().collect())
+    }
+}
+
+#[cfg(feature = "hash_trie")]
+impl<K, V> Arbitrary for crate::hash_trie::HashTrie<K, V>
+where
+    K: Arbitrary + Eq + Hash,
+    V: Arbitrary,
+{
+    fn arbitrary(g: &mut Gen) -> Self {
+        hash_trie(Arbitrary::arbitrary(g))
+    }
+
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+        let entries = self.iter().map(|(k, v)| (k.to_vec(), v.clone())).collect();
+        Box::new(
+            (self.keeps_insertion_order(), entries)
+                .shrink()
+                .map(hash_trie),
+        )
+    }
+}
+
+/// Builds a trie from its entries, remembering the insertion order if the flag is set.
+#[cfg(feature = "hash_trie")]
+fn hash_trie<K, V>((ordered, entries): (bool, Vec<(Vec<K>, V)>)) -> crate::hash_trie::HashTrie<K, V>
+where
+    K: Eq + Hash + Clone,
+{
+    let mut trie = if ordered {
+        crate::hash_trie::HashTrie::with_insertion_order()
+    } else {
+        crate::hash_trie::HashTrie::new()
+    };
+    for (k, v) in entries {
+        trie.insert(k, v);
+    }
+    trie
+}
+
+#[cfg(feature = "heap")]
+impl<A: Arbitrary + Ord> Arbitrary for crate::heap::Heap<A> {
+    fn arbitrary(g: &mut Gen) -> Self {
+        heap(Vec::arbitrary(g))
+    }
+
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+        shrink_via(self.iter().cloned().collect(), heap)
+    }
+}
+
+#[cfg(feature = "heap")]
+fn heap<A: Ord>(elements: Vec<A>) -> crate::heap::Heap<A> {
+    let mut heap = crate::heap::Heap::new();
+    for a in elements {
+        heap.push(a);
+    }
+    heap
+}
+
+#[cfg(feature = "index_list")]
+impl<A: Arbitrary> Arbitrary for crate::index_list::IndexList<A> {
+    fn arbitrary(g: &mut Gen) -> Self {
+        index_list(Vec::arbitrary(g))
+    }
+
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+        shrink_via(self.iter().cloned().map(Some).collect(), index_list)
+    }
+}
+
+/// Pushes each element onto the tail and pops the head for each `None`, so that the list
+/// reuses the slots it frees.
+#[cfg(feature = "index_list")]
+fn index_list<A>(ops: Vec<Option<A>>) -> crate::index_list::IndexList<A> {
+    let mut list = crate::index_list::IndexList::new();
+    for op in ops {
+        match op {
+            Some(a) => {
+                list.push_tail(a);
+            }
+            None => {
+                list.pop_head();
+            }
+        }
+    }
+    list
+}
+
+#[cfg(feature = "index_map")]
+impl<K, V> Arbitrary for crate::index_map::IndexMap<K, V>
+where
+    K: Arbitrary + Eq + Hash,
+    V: Arbitrary,
+{
+    fn arbitrary(g: &mut Gen) -> Self {
+        Vec::<(K, V)>::arbitrary(g).into_iter().collect()
+    }
+
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+        let entries = self.iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+        shrink_via(entries, |v| v.into_iter().collect())
+    }
+}
+
+#[cfg(feature = "lfu_cache")]
+impl<K, V> Arbitrary for crate::lfu_cache::LFUCache<K, V>
+where
+    K: Arbitrary + Eq + Hash,
+    V: Arbitrary,
+{
+    fn arbitrary(g: &mut Gen) -> Self {
+        lfu_cache(Arbitrary::arbitrary(g))
+    }
+
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+        let uses = self
+            .iter()
+            .flat_map(|(k, v)| {
+                let frequency = self.frequency(k).unwrap();
+                std::iter::repeat_n((k.clone(), v.clone()), frequency)
+            })
+            .collect();
+        let capacity = self.capacity().min(u8::MAX.into()) as u8;
+        Box::new((capacity, uses).shrink().map(lfu_cache))
+    }
+}
+
+/// Builds a cache by inserting each entry, or using its key again if it's already present.
+#[cfg(feature = "lfu_cache")]
+fn lfu_cache<K, V>((capacity, uses): (u8, Vec<(K, V)>)) -> crate::lfu_cache::LFUCache<K, V>
+where
+    K: Eq + Hash + Clone,
+{
+    let mut cache = crate::lfu_cache::LFUCache::new(capacity.into());
+    for (k, v) in uses {
+        if cache.get(&k).is_none() {
+            cache.insert(k, v);
+        }
+    }
+    cache
+}
+
+#[cfg(feature = "linked_list")]
+impl<A: Arbitrary> Arbitrary for crate::linked_list::LinkedList<A> {
+    fn arbitrary(g: &mut Gen) -> Self {
+        linked_list(Vec::arbitrary(g))
+    }
+
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+        shrink_via(self.iter().cloned().collect(), linked_list)
+    }
+}
+
+/// Builds a list with the elements from head to tail.
+#[cfg(feature = "linked_list")]
+fn linked_list<A>(elements: Vec<A>) -> crate::linked_list::LinkedList<A> {
+    let mut list = crate::linked_list::LinkedList::new();
+    for a in elements.into_iter().rev() {
+        list.push_head(a);
+    }
+    list
+}
+
+#[cfg(feature = "llrb")]
+impl<T: Arbitrary + Ord> Arbitrary for crate::llrb::LLRBSet<T> {
+    fn arbitrary(g: &mut Gen) -> Self {
+        Vec::<T>::arbitrary(g).into_iter().collect()
+    }
+
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+        shrink_via(self.iter().cloned().collect(), |v| v.into_iter().collect())
+    }
+}
+
+#[cfg(feature = "lru_cache")]
+impl<K, V> Arbitrary for crate::lru_cache::LRUCache<K, V>
+where
+    K: Arbitrary + Eq + Hash,
+    V: Arbitrary,
+{
+    fn arbitrary(g: &mut Gen) -> Self {
+        lru_cache(Arbitrary::arbitrary(g))
+    }
+
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+        // Reinserting from least to most recently used restores the recency order
+        let entries = self.clone().into_iter().collect();
+        let capacity = self.capacity().min(u8::MAX.into()) as u8;
+        Box::new((capacity, entries).shrink().map(lru_cache))
+    }
+}
+
+#[cfg(feature = "lru_cache")]
+fn lru_cache<K, V>((capacity, entries): (u8, Vec<(K, V)>)) -> crate::lru_cache::LRUCache<K, V>
+where
+    K: Eq + Hash + Clone,
+{
+    let mut cache = crate::lru_cache::LRUCache::new(capacity.into());
+    for (k, v) in entries {
+        cache.insert(k, v);
+    }
+    cache
+}
+
+#[cfg(feature = "min_stack")]
+impl<T: Arbitrary + Ord> Arbitrary for crate::min_stack::MinMaxStack<T> {
+    fn arbitrary(g: &mut Gen) -> Self {
+        Vec::<T>::arbitrary(g).into_iter().collect()
+    }
+
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+        shrink_via(self.iter().cloned().collect(), |v| v.into_iter().collect())
+    }
+}
+
+#[cfg(feature = "monotonic")]
+impl<T: Arbitrary + Ord> Arbitrary for crate::monotonic::MinMaxDeque<T> {
+    fn arbitrary(g: &mut Gen) -> Self {
+        Vec::<T>::arbitrary(g).into_iter().collect()
+    }
+
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+        shrink_via(self.iter().cloned().collect(), |v| v.into_iter().collect())
+    }
+}
+
+#[cfg(feature = "multi_map")]
+impl<K, V> Arbitrary for crate::multi_map::MultiMap<K, V>
+where
+    K: Arbitrary + Eq + Hash,
+    V: Arbitrary,
+{
+    fn arbitrary(g: &mut Gen) -> Self {
+        Vec::<(K, V)>::arbitrary(g).into_iter().collect()
+    }
+
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+        let entries = self.iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+        shrink_via(entries, |v| v.into_iter().collect())
+    }
+}
+
+#[cfg(feature = "multi_set")]
+impl<T: Arbitrary + Eq + Hash> Arbitrary for crate::multi_set::MultiSet<T> {
+    fn arbitrary(g: &mut Gen) -> Self {
+        Vec::<T>::arbitrary(g).into_iter().collect()
+    }
+
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+        let elements = self
+            .iter()
+            .flat_map(|(t, count)| std::iter::repeat_n(t.clone(), count))
+            .collect();
+        shrink_via(elements, |v| v.into_iter().collect())
+    }
+}
+
+#[cfg(feature = "persistent")]
+impl<T: Arbitrary> Arbitrary for crate::persistent::List<T> {
+    fn arbitrary(g: &mut Gen) -> Self {
+        Vec::<T>::arbitrary(g).into_iter().collect()
+    }
+
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+        shrink_via(self.iter().cloned().collect(), |v| v.into_iter().collect())
+    }
+}
+
+#[cfg(feature = "persistent")]
+impl<T: Arbitrary + Ord> Arbitrary for crate::persistent::OrdSet<T> {
+    fn arbitrary(g: &mut Gen) -> Self {
+        Vec::<T>::arbitrary(g).into_iter().collect()
+    }
+
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+        shrink_via(self.iter().cloned().collect(), |v| v.into_iter().collect())
+    }
+}
+
+#[cfg(feature = "range_map")]
+impl<K, V> Arbitrary for crate::range_map::RangeMap<K, V>
+where
+    K: Arbitrary + Ord,
+    V: Arbitrary + Eq,
+{
+    fn arbitrary(g: &mut Gen) -> Self {
+        range_map(Vec::arbitrary(g))
+    }
+
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+        let entries = self
+            .iter()
+            .map(|(r, v)| (r.start.clone(), r.end.clone(), v.clone()))
+            .collect();
+        shrink_via(entries, range_map)
+    }
+}
+
+#[cfg(feature = "range_map")]
+fn range_map<K, V>(entries: Vec<(K, K, V)>) -> crate::range_map::RangeMap<K, V>
+where
+    K: Ord + Clone,
+    V: Eq + Clone,
+{
+    let mut map = crate::range_map::RangeMap::new();
+    for (a, b, v) in entries {
+        if a <= b {
+            map.insert(a..b, v);
+        } else {
+            map.insert(b..a, v);
+        }
+    }
+    map
+}
+
+#[cfg(feature = "slab")]
+impl<T: Arbitrary> Arbitrary for crate::slab::SlotMap<T> {
+    fn arbitrary(g: &mut Gen) -> Self {
+        slot_map(Vec::arbitrary(g))
+    }
+
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+        shrink_via(
+            self.iter().map(|(_, t)| Some(t.clone())).collect(),
+            slot_map,
+        )
+    }
+}
+
+/// Inserts each value and removes the oldest remaining one for each `None`, so that the
+/// map reuses the slots it frees and bumps their generations.
+#[cfg(feature = "slab")]
+fn slot_map<T>(ops: Vec<Option<T>>) -> crate::slab::SlotMap<T> {
+    let mut map = crate::slab::SlotMap::new();
+    let mut keys = std::collections::VecDeque::new();
+    for op in ops {
+        match op {
+            Some(t) => keys.push_back(map.insert(t)),
+            None => {
+                if let Some(key) = keys.pop_front() {
+                    map.remove(key);
+                }
+            }
+        }
+    }
+    map
+}
+
+#[cfg(feature = "small_vec")]
+impl<T: Arbitrary, const N: usize> Arbitrary for crate::small_vec::SmallVec<T, N> {
+    fn arbitrary(g: &mut Gen) -> Self {
+        Vec::<T>::arbitrary(g).into_iter().collect()
+    }
+
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+        shrink_via(self.to_vec(), |v| v.into_iter().collect())
+    }
+}
+
+#[cfg(feature = "sorted_list")]
+impl<T: Arbitrary + Ord> Arbitrary for crate::sorted_list::SortedList<T> {
+    fn arbitrary(g: &mut Gen) -> Self {
+        Vec::<T>::arbitrary(g).into_iter().collect()
+    }
+
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+        shrink_via(self.iter().cloned().collect(), |v| v.into_iter().collect())
+    }
+}
+
+#[cfg(feature = "static_tree")]
+impl<K, V> Arbitrary for crate::static_tree::EytzingerSearch<K, V>
+where
+    K: Arbitrary + Ord,
+    V: Arbitrary,
+{
+    fn arbitrary(g: &mut Gen) -> Self {
+        Vec::<(K, V)>::arbitrary(g).into_iter().collect()
+    }
+
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+        let entries = self.iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+        shrink_via(entries, |v| v.into_iter().collect())
+    }
+}
+
+#[cfg(feature = "veb")]
+impl Arbitrary for crate::veb::VebTree {
+    fn arbitrary(g: &mut Gen) -> Self {
+        Vec::<u32>::arbitrary(g).into_iter().collect()
+    }
+
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+        shrink_via(self.iter().collect(), |v| v.into_iter().collect())
+    }
+}
+
+/// `proptest` strategies for the same collections, as implementations of its own
+/// `Arbitrary`, so that `any::<AVLTree<u8, u8>>()` generates trees. Each one generates the
+/// collection's contents and builds it from them like the `quickcheck` implementation, so
+/// the instances are just as valid, and they shrink by shrinking the contents.
+mod strategies {
+    use std::{fmt::Debug, hash::Hash};
+
+    use proptest::{
+        arbitrary::{any, Arbitrary},
+        strategy::{BoxedStrategy, Strategy},
+    };
+
+    /// Generates a collection's contents and builds the collection from them.
+    fn via<T, C>(build: fn(T) -> C) -> BoxedStrategy<C>
+    where
+        T: Arbitrary + 'static,
+        C: Debug + 'static,
+    {
+        any::<T>().prop_map(build).boxed()
+    }
+
+    #[cfg(feature = "avl_set")]
+    impl<T: Arbitrary + Ord + 'static> Arbitrary for crate::avl_set::AVLSet<T> {
+        type Parameters = ();
+        type Strategy = BoxedStrategy<Self>;
+
+        fn arbitrary_with(_: ()) -> Self::Strategy {
+            via::<Vec<T>, _>(|v| v.into_iter().collect())
+        }
+    }
+
+    #[cfg(feature = "avl_tree")]
+    impl<K, V> Arbitrary for crate::avl_tree::AVLTree<K, V>
+    where
+        K: Arbitrary + Ord + 'static,
+        V: Arbitrary + 'static,
+    {
+        type Parameters = ();
+        type Strategy = BoxedStrategy<Self>;
+
+        fn arbitrary_with(_: ()) -> Self::Strategy {
+            via::<Vec<(K, V)>, _>(super::avl_tree)
+        }
+    }
+
+    #[cfg(feature = "bag")]
+    impl<T: Arbitrary + Eq + Hash + Clone + 'static> Arbitrary for crate::bag::Bag<T> {
+        type Parameters = ();
+        type Strategy = BoxedStrategy<Self>;
+
+        fn arbitrary_with(_: ()) -> Self::Strategy {
+            via::<Vec<T>, _>(|v| v.into_iter().collect())
+        }
+    }
+
+    #[cfg(feature = "bimap")]
+    impl<L, R> Arbitrary for crate::bimap::BiMap<L, R>
+    where
+        L: Arbitrary + Eq + Hash + Clone + 'static,
+        R: Arbitrary + Eq + Hash + Clone + 'static,
+    {
+        type Parameters = ();
+        type Strategy = BoxedStrategy<Self>;
+
+        fn arbitrary_with(_: ()) -> Self::Strategy {
+            via::<Vec<(L, R)>, _>(|v| v.into_iter().collect())
+        }
+    }
+
+    #[cfg(feature = "bitset")]
+    impl Arbitrary for crate::bitset::BitSet {
+        type Parameters = ();
+        type Strategy = BoxedStrategy<Self>;
+
+        fn arbitrary_with(_: ()) -> Self::Strategy {
+            via::<Vec<u16>, _>(|v| v.into_iter().map(usize::from).collect())
+        }
+    }
+
+    #[cfg(feature = "compact")]
+    impl Arbitrary for crate::compact::StaticSortedSet {
+        type Parameters = ();
+        type Strategy = BoxedStrategy<Self>;
+
+        fn arbitrary_with(_: ()) -> Self::Strategy {
+            via::<Vec<u64>, _>(|v| v.into_iter().collect())
+        }
+    }
+
+    #[cfg(feature = "hash_map")]
+    impl<K, V> Arbitrary for crate::hash_map::FlatHashMap<K, V>
+    where
+        K: Arbitrary + Eq + Hash + 'static,
+        V: Arbitrary + 'static,
+    {
+        type Parameters = ();
+        type Strategy = BoxedStrategy<Self>;
+
+        fn arbitrary_with(_: ()) -> Self::Strategy {
+            via::<Vec<(K, V)>, _>(|v| v.into_iter().collect())
+        }
+    }
+
+    #[cfg(feature = "hash_trie")]
+    impl<K, V> Arbitrary for crate::hash_trie::HashTrie<K, V>
+    where
+        K: Arbitrary + Eq + Hash + Clone + 'static,
+        V: Arbitrary + 'static,
+    {
+        type Parameters = ();
+        type Strategy = BoxedStrategy<Self>;
+
+        fn arbitrary_with(_: ()) -> Self::Strategy {
+            via::<(bool, Vec<(Vec<K>, V)>), _>(super::hash_trie)
+        }
+    }
+
+    #[cfg(feature = "heap")]
+    impl<A: Arbitrary + Ord + 'static> Arbitrary for crate::heap::Heap<A> {
+        type Parameters = ();
+        type Strategy = BoxedStrategy<Self>;
+
+        fn arbitrary_with(_: ()) -> Self::Strategy {
+            via::<Vec<A>, _>(super::heap)
+        }
+    }
+
+    #[cfg(feature = "index_list")]
+    impl<A: Arbitrary + 'static> Arbitrary for crate::index_list::IndexList<A> {
+        type Parameters = ();
+        type Strategy = BoxedStrategy<Self>;
+
+        fn arbitrary_with(_: ()) -> Self::Strategy {
+            via::<Vec<Option<A>>, _>(super::index_list)
+        }
+    }
+
+    #[cfg(feature = "index_map")]
+    impl<K, V> Arbitrary for crate::index_map::IndexMap<K, V>
+    where
+        K: Arbitrary + Eq + Hash + Clone + 'static,
+        V: Arbitrary + 'static,
+    {
+        type Parameters = ();
+        type Strategy = BoxedStrategy<Self>;
+
+        fn arbitrary_with(_: ()) -> Self::Strategy {
+            via::<Vec<(K, V)>, _>(|v| v.into_iter().collect())
+        }
+    }
+
+    #[cfg(feature = "lfu_cache")]
+    impl<K, V> Arbitrary for crate::lfu_cache::LFUCache<K, V>
+    where
+        K: Arbitrary + Eq + Hash + Clone + 'static,
+        V: Arbitrary + 'static,
+    {
+        type Parameters = ();
+        type Strategy = BoxedStrategy<Self>;
+
+        fn arbitrary_with(_: ()) -> Self::Strategy {
+            via::<(u8, Vec<(K, V)>), _>(super::lfu_cache)
+        }
+    }
+
+    #[cfg(feature = "linked_list")]
+    impl<A: Arbitrary + 'static> Arbitrary for crate::linked_list::LinkedList<A> {
+        type Parameters = ();
+        type Strategy = BoxedStrategy<Self>;
+
+        fn arbitrary_with(_: ()) -> Self::Strategy {
+            via::<Vec<A>, _>(super::linked_list)
+        }
+    }
+
+    #[cfg(feature = "llrb")]
+    impl<T: Arbitrary + Ord + 'static> Arbitrary for crate::llrb::LLRBSet<T> {
+        type Parameters = ();
+        type Strategy = BoxedStrategy<Self>;
+
+        fn arbitrary_with(_: ()) -> Self::Strategy {
+            via::<Vec<T>, _>(|v| v.into_iter().collect())
+        }
+    }
+
+    #[cfg(feature = "lru_cache")]
+    impl<K, V> Arbitrary for crate::lru_cache::LRUCache<K, V>
+    where
+        K: Arbitrary + Eq + Hash + Clone + 'static,
+        V: Arbitrary + 'static,
+    {
+        type Parameters = ();
+        type Strategy = BoxedStrategy<Self>;
+
+        fn arbitrary_with(_: ()) -> Self::Strategy {
+            via::<(u8, Vec<(K, V)>), _>(super::lru_cache)
+        }
+    }
+
+    #[cfg(feature = "min_stack")]
+    impl<T: Arbitrary + Ord + 'static> Arbitrary for crate::min_stack::MinMaxStack<T> {
+        type Parameters = ();
+        type Strategy = BoxedStrategy<Self>;
+
+        fn arbitrary_with(_: ()) -> Self::Strategy {
+            via::<Vec<T>, _>(|v| v.into_iter().collect())
+        }
+    }
+
+    #[cfg(feature = "monotonic")]
+    impl<T: Arbitrary + Ord + 'static> Arbitrary for crate::monotonic::MinMaxDeque<T> {
+        type Parameters = ();
+        type Strategy = BoxedStrategy<Self>;
+
+        fn arbitrary_with(_: ()) -> Self::Strategy {
+            via::<Vec<T>, _>(|v| v.into_iter().collect())
+        }
+    }
+
+    #[cfg(feature = "multi_map")]
+    impl<K, V> Arbitrary for crate::multi_map::MultiMap<K, V>
+    where
+        K: Arbitrary + Eq + Hash + 'static,
+        V: Arbitrary + 'static,
+    {
+        type Parameters = ();
+        type Strategy = BoxedStrategy<Self>;
+
+        fn arbitrary_with(_: ()) -> Self::Strategy {
+            via::<Vec<(K, V)>, _>(|v| v.into_iter().collect())
+        }
+    }
+
+    #[cfg(feature = "multi_set")]
+    impl<T: Arbitrary + Eq + Hash + 'static> Arbitrary for crate::multi_set::MultiSet<T> {
+        type Parameters = ();
+        type Strategy = BoxedStrategy<Self>;
+
+        fn arbitrary_with(_: ()) -> Self::Strategy {
+            via::<Vec<T>, _>(|v| v.into_iter().collect())
+        }
+    }
+
+    #[cfg(feature = "persistent")]
+    impl<T: Arbitrary + 'static> Arbitrary for crate::persistent::List<T> {
+        type Parameters = ();
+        type Strategy = BoxedStrategy<Self>;
+
+        fn arbitrary_with(_: ()) -> Self::Strategy {
+            via::<Vec<T>, _>(|v| v.into_iter().collect())
+        }
+    }
+
+    #[cfg(feature = "persistent")]
+    impl<T: Arbitrary + Ord + Clone + 'static> Arbitrary for crate::persistent::OrdSet<T> {
+        type Parameters = ();
+        type Strategy = BoxedStrategy<Self>;
+
+        fn arbitrary_with(_: ()) -> Self::Strategy {
+            via::<Vec<T>, _>(|v| v.into_iter().collect())
+        }
+    }
+
+    #[cfg(feature = "range_map")]
+    impl<K, V> Arbitrary for crate::range_map::RangeMap<K, V>
+    where
+        K: Arbitrary + Ord + Clone + 'static,
+        V: Arbitrary + Eq + Clone + 'static,
+    {
+        type Parameters = ();
+        type Strategy = BoxedStrategy<Self>;
+
+        fn arbitrary_with(_: ()) -> Self::Strategy {
+            via::<Vec<(K, K, V)>, _>(super::range_map)
+        }
+    }
+
+    #[cfg(feature = "slab")]
+    impl<T: Arbitrary + 'static> Arbitrary for crate::slab::SlotMap<T> {
+        type Parameters = ();
+        type Strategy = BoxedStrategy<Self>;
+
+        fn arbitrary_with(_: ()) -> Self::Strategy {
+            via::<Vec<Option<T>>, _>(super::slot_map)
+        }
+    }
+
+    #[cfg(feature = "small_vec")]
+    impl<T, const N: usize> Arbitrary for crate::small_vec::SmallVec<T, N>
+    where
+        T: Arbitrary + 'static,
+    {
+        type Parameters = ();
+        type Strategy = BoxedStrategy<Self>;
+
+        fn arbitrary_with(_: ()) -> Self::Strategy {
+            via::<Vec<T>, _>(|v| v.into_iter().collect())
+        }
+    }
+
+    #[cfg(feature = "sorted_list")]
+    impl<T: Arbitrary + Ord + 'static> Arbitrary for crate::sorted_list::SortedList<T> {
+        type Parameters = ();
+        type Strategy = BoxedStrategy<Self>;
+
+        fn arbitrary_with(_: ()) -> Self::Strategy {
+            via::<Vec<T>, _>(|v| v.into_iter().collect())
+        }
+    }
+
+    #[cfg(feature = "static_tree")]
+    impl<K, V> Arbitrary for crate::static_tree::EytzingerSearch<K, V>
+    where
+        K: Arbitrary + Ord + 'static,
+        V: Arbitrary + 'static,
+    {
+        type Parameters = ();
+        type Strategy = BoxedStrategy<Self>;
+
+        fn arbitrary_with(_: ()) -> Self::Strategy {
+            via::<Vec<(K, V)>, _>(|v| v.into_iter().collect())
+        }
+    }
+
+    #[cfg(feature = "veb")]
+    impl Arbitrary for crate::veb::VebTree {
+        type Parameters = ();
+        type Strategy = BoxedStrategy<Self>;
+
+        fn arbitrary_with(_: ()) -> Self::Strategy {
+            via::<Vec<u32>, _>(|v| v.into_iter().collect())
+        }
+    }
+}
+
+#[cfg(all(test, feature = "full"))]
+mod test {
+    use proptest::{arbitrary::any, test_runner::TestRunner};
+    use quickcheck::quickcheck;
+
+    use crate::{
+        avl_set::AVLSet,
+        avl_tree::AVLTree,
+        bimap::BiMap,
+        bitset::BitSet,
+        hash_trie::HashTrie,
+        heap::Heap,
+        index_list::IndexList,
+        index_map::IndexMap,
+        lfu_cache::LFUCache,
+        linked_list::LinkedList,
+        llrb::LLRBSet,
+        lru_cache::LRUCache,
+        persistent::{List, OrdSet},
+        range_map::RangeMap,
+        slab::SlotMap,
+        sorted_list::SortedList,
+    };
+
+    #[test]
+    fn prop_generated_instances_are_valid() {
+        fn p(
+            tree: AVLTree<u8, u8>,
+            set: LLRBSet<u8>,
+            heap: Heap<u8>,
+            bits: BitSet,
+            list: SortedList<u8>,
+            map: (IndexMap<u8, u8>, BiMap<u8, u8>, RangeMap<u8, bool>),
+        ) -> bool {
+            tree.check_invariants();
+            set.check_invariants();
+            heap.check_invariants();
+            bits.check_invariants();
+            list.check_invariants();
+            map.0.check_invariants();
+            map.1.check_invariants();
+            map.2.check_invariants();
+            true
+        }
+        quickcheck(
+            p as fn(
+                AVLTree<u8, u8>,
+                LLRBSet<u8>,
+                Heap<u8>,
+                BitSet,
+                SortedList<u8>,
+                (IndexMap<u8, u8>, BiMap<u8, u8>, RangeMap<u8, bool>),
+            ) -> bool,
+        )
+    }
+
+    #[test]
+    fn prop_generated_lists_and_caches_are_valid() {
+        fn p(
+            trie: HashTrie<u8, u8>,
+            lists: (LinkedList<u8>, IndexList<u8>, List<u8>),
+            sets: (AVLSet<u8>, OrdSet<u8>),
+            caches: (LRUCache<u8, u8>, LFUCache<u8, u8>),
+            slots: SlotMap<u8>,
+        ) -> bool {
+            trie.check_invariants();
+            lists.0.check_invariants();
+            lists.1.check_invariants();
+            sets.0.check_invariants();
+            sets.1.check_invariants();
+            lists.2.len() == lists.2.iter().count()
+                && caches.0.len() <= caches.0.capacity()
+                && caches.1.len() <= caches.1.capacity()
+                && slots.len() == slots.iter().count()
+        }
+        quickcheck(
+            p as fn(
+                HashTrie<u8, u8>,
+                (LinkedList<u8>, IndexList<u8>, List<u8>),
+                (AVLSet<u8>, OrdSet<u8>),
+                (LRUCache<u8, u8>, LFUCache<u8, u8>),
+                SlotMap<u8>,
+            ) -> bool,
+        )
+    }
+
+    #[test]
+    fn strategies_generate_valid_instances() {
+        let instances = any::<(
+            AVLTree<u8, u8>,
+            HashTrie<u8, u8>,
+            (LinkedList<u8>, IndexList<u8>),
+            (AVLSet<u8>, OrdSet<u8>),
+            (IndexMap<u8, u8>, RangeMap<u8, bool>),
+            (LRUCache<u8, u8>, SlotMap<u8>),
+        )>();
+        TestRunner::default()
+            .run(&instances, |(tree, trie, lists, sets, maps, others)| {
+                tree.check_invariants();
+                trie.check_invariants();
+                lists.0.check_invariants();
+                lists.1.check_invariants();
+                sets.0.check_invariants();
+                sets.1.check_invariants();
+                maps.0.check_invariants();
+                maps.1.check_invariants();
+                assert!(others.0.len() <= others.0.capacity());
+                assert_eq!(others.1.len(), others.1.iter().count());
+                Ok(())
+            })
+            .unwrap();
+    }
+}
@@ -0,0 +1,338 @@
+use std::ops::{Bound, RangeBounds};
+
+use crate::avl_tree::AVLTree;
+
+const DEFAULT_MEMTABLE_CAPACITY: usize = 1024;
+const DEFAULT_MAX_RUNS: usize = 8;
+
+/// An ordered map split into tiers, like a log-structured merge tree.
+/// Writes go to a small mutable AVL tree, the memtable, which is frozen into an immutable
+/// sorted run once it fills up. When there are too many runs they are compacted into one.
+/// Removals are recorded as tombstones, which shadow older tiers until compaction.
+/// Reads merge every tier, with newer tiers taking precedence.
+#[derive(Debug)]
+pub struct TieredMap<K, V> {
+    memtable: AVLTree<K, Option<V>>,
+    memtable_len: usize,
+    // Sorted runs from oldest to newest
+    runs: Vec<Vec<(K, Option<V>)>>,
+    memtable_capacity: usize,
+    max_runs: usize,
+    len: usize,
+}
+
+impl<K, V> TieredMap<K, V> {
+    pub fn new() -> Self {
+        TieredMap::with_limits(DEFAULT_MEMTABLE_CAPACITY, DEFAULT_MAX_RUNS)
+    }
+
+    /// Creates a map which freezes the memtable once it holds `memtable_capacity` entries
+    /// and compacts once there are more than `max_runs` runs.
+    pub fn with_limits(memtable_capacity: usize, max_runs: usize) -> Self {
+        TieredMap {
+            memtable: AVLTree::new(),
+            memtable_len: 0,
+            runs: vec![],
+            memtable_capacity: memtable_capacity.max(1),
+            max_runs,
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The number of immutable sorted runs.
+    pub fn runs(&self) -> usize {
+        self.runs.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.memtable = AVLTree::new();
+        self.memtable_len = 0;
+        self.runs.clear();
+        self.len = 0;
+    }
+}
+
+impl<K, V> Default for TieredMap<K, V> {
+    fn default() -> Self {
+        TieredMap::new()
+    }
+}
+
+impl<K, V> TieredMap<K, V>
+where
+    K: Ord + Clone,
+{
+    pub fn insert(&mut self, k: K, v: V) {
+        if !self.contains_key(&k) {
+            self.len += 1;
+        }
+        self.write(k, Some(v));
+    }
+
+    /// Removes the key, returning whether it was present.
+    pub fn remove(&mut self, k: &K) -> bool {
+        if !self.contains_key(k) {
+            return false;
+        }
+        self.len -= 1;
+        if self.runs.is_empty() {
+            // Nothing older to shadow
+            self.memtable.remove(k);
+            self.memtable_len -= 1;
+        } else {
+            self.write(k.clone(), None);
+        }
+        true
+    }
+
+    pub fn get(&self, k: &K) -> Option<&V> {
+        if let Some(v) = self.memtable.get(k) {
+            return v.as_ref();
+        }
+        for run in self.runs.iter().rev() {
+            if let Ok(i) = run.binary_search_by(|(key, _)| key.cmp(k)) {
+                return run[i].1.as_ref();
+            }
+        }
+        None
+    }
+
+    pub fn contains_key(&self, k: &K) -> bool {
+        self.get(k).is_some()
+    }
+
+    /// Returns the entries in ascending key order.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.range(..)
+    }
+
+    /// Returns the entries with keys in the range in ascending order.
+    pub fn range<R: RangeBounds<K>>(&self, range: R) -> impl Iterator<Item = (&K, &V)> {
+        let mut sources = vec![];
+        for run in self.runs.iter() {
+            let start = match range.start_bound() {
+                Bound::Included(s) => run.partition_point(|(k, _)| k < s),
+                Bound::Excluded(s) => run.partition_point(|(k, _)| k <= s),
+                Bound::Unbounded => 0,
+            };
+            let end = match range.end_bound() {
+                Bound::Included(e) => run.partition_point(|(k, _)| k <= e),
+                Bound::Excluded(e) => run.partition_point(|(k, _)| k < e),
+                Bound::Unbounded => run.len(),
+            };
+            let entries = run[start..end.max(start)].iter().map(|(k, v)| (k, v));
+            sources.push(Box::new(entries) as Box<dyn Iterator<Item = _>>);
+        }
+        let bounds = (range.start_bound(), range.end_bound());
+        sources.push(Box::new(self.memtable.range(bounds)));
+        Merge::new(sources).filter_map(|(k, v)| Some((k, v.as_ref()?)))
+    }
+
+    /// Clones the entries into a vector in ascending key order, allocating it once.
+    pub fn to_sorted_vec(&self) -> Vec<(K, V)>
+    where
+        V: Clone,
+    {
+        let mut entries = Vec::with_capacity(self.len);
+        entries.extend(self.iter().map(|(k, v)| (k.clone(), v.clone())));
+        entries
+    }
+
+    /// Like `to_sorted_vec`.
+    pub fn to_vec(&self) -> Vec<(K, V)>
+    where
+        V: Clone,
+    {
+        self.to_sorted_vec()
+    }
+
+    /// Moves the entries into a vector in ascending key order. The map is compacted into
+    /// a single run first, so the entries are moved rather than merged.
+    pub fn into_sorted_vec(mut self) -> Vec<(K, V)> {
+        self.compact();
+        let mut entries = Vec::with_capacity(self.len);
+        let run = self.runs.pop().unwrap_or_default();
+        entries.extend(run.into_iter().filter_map(|(k, v)| Some((k, v?))));
+        entries
+    }
+
+    /// Moves the memtable into a new sorted run.
+    pub fn freeze(&mut self) {
+        if self.memtable_len == 0 {
+            return;
+        }
+        let mut run = Vec::with_capacity(self.memtable_len);
+        while let Some(k) = self.memtable.first().cloned() {
+            let v = self.memtable.remove(&k).unwrap();
+            // Tombstones in the oldest tier have nothing left to shadow
+            if v.is_some() || !self.runs.is_empty() {
+                run.push((k, v));
+            }
+        }
+        self.memtable_len = 0;
+        self.runs.push(run);
+    }
+
+    /// Freezes the memtable and merges every run into one, dropping tombstones.
+    pub fn compact(&mut self) {
+        self.freeze();
+        if self.runs.len() <= 1 {
+            return;
+        }
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("compact", runs = self.runs.len()).entered();
+        let sources = std::mem::take(&mut self.runs)
+            .into_iter()
+            .map(|run| run.into_iter())
+            .collect();
+        let run = Merge::new(sources)
+            .filter(|(_, v)| v.is_some())
+            .collect::<Vec<_>>();
+        self.runs.push(run);
+    }
+
+    fn write(&mut self, k: K, v: Option<V>) {
+        // The tree keeps the existing value for a duplicate key
+        if self.memtable.remove(&k).is_none() {
+            self.memtable_len += 1;
+        }
+        self.memtable.insert(k, v);
+        if self.memtable_len >= self.memtable_capacity {
+            self.freeze();
+            if self.runs.len() > self.max_runs {
+                self.compact();
+            }
+        }
+    }
+}
+
+impl<K: Ord + Clone, V> FromIterator<(K, V)> for TieredMap<K, V> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut map = TieredMap::new();
+        map.extend(iter);
+        map
+    }
+}
+
+impl<K: Ord + Clone, V> Extend<(K, V)> for TieredMap<K, V> {
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        for (k, v) in iter {
+            self.insert(k, v);
+        }
+    }
+}
+
+/// Merges sorted sources, ordered from oldest to newest, into one sorted sequence.
+/// When several sources hold the same key, only the newest entry is kept.
+struct Merge<I: Iterator> {
+    sources: Vec<I>,
+    // The next entry of each source
+    heads: Vec<Option<I::Item>>,
+}
+
+impl<I: Iterator> Merge<I> {
+    fn new(mut sources: Vec<I>) -> Self {
+        let heads = sources.iter_mut().map(Iterator::next).collect();
+        Merge { sources, heads }
+    }
+}
+
+impl<K: Ord, V, I: Iterator<Item = (K, V)>> Iterator for Merge<I> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<(K, V)> {
+        let mut newest: Option<usize> = None;
+        for (i, head) in self.heads.iter().enumerate() {
+            let Some((k, _)) = head else {
+                continue;
+            };
+            if newest.is_none_or(|j| self.heads[j].as_ref().is_some_and(|(min, _)| k <= min)) {
+                newest = Some(i);
+            }
+        }
+        let newest = newest?;
+        let (k, v) = std::mem::replace(&mut self.heads[newest], self.sources[newest].next())?;
+        // Skip the older entries for the same key
+        for (head, source) in self.heads.iter_mut().zip(self.sources.iter_mut()) {
+            while head.as_ref().is_some_and(|(key, _)| *key == k) {
+                *head = source.next();
+            }
+        }
+        Some((k, v))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use quickcheck::quickcheck;
+
+    use super::TieredMap;
+
+    #[test]
+    fn reads_across_tiers() {
+        let mut map = TieredMap::with_limits(2, 8);
+        map.insert(1, 'a');
+        map.insert(2, 'b');
+        assert_eq!(map.runs(), 1);
+        map.insert(1, 'c');
+        assert!(map.remove(&2));
+        assert_eq!(map.runs(), 2);
+        map.insert(3, 'd');
+        assert_eq!(map.get(&1), Some(&'c'));
+        assert_eq!(map.get(&2), None);
+        assert_eq!(map.len(), 2);
+        let entries = map.iter().map(|(k, v)| (*k, *v)).collect::<Vec<_>>();
+        assert_eq!(entries, vec![(1, 'c'), (3, 'd')]);
+        map.compact();
+        assert_eq!(map.runs(), 1);
+        assert_eq!(map.runs[0], vec![(1, Some('c')), (3, Some('d'))]);
+    }
+
+    #[test]
+    fn compacts_automatically() {
+        let map = (0..100).map(|i| (i % 30, i)).collect::<TieredMap<_, _>>();
+        assert_eq!(map.len(), 30);
+        let mut small = TieredMap::with_limits(4, 2);
+        small.extend((0..100).map(|i| (i, i)));
+        assert!(small.runs() <= 3);
+        assert_eq!(small.range(10..15).count(), 5);
+        assert_eq!(map.get(&29), Some(&89));
+    }
+
+    #[test]
+    fn prop_matches_btree_map() {
+        fn p(ops: Vec<(u8, Option<u8>)>, capacity: u8, max_runs: u8) -> bool {
+            let mut map = TieredMap::with_limits(capacity as usize % 8, max_runs as usize % 4);
+            let mut model = BTreeMap::new();
+            for (k, v) in ops {
+                let agree = match v {
+                    Some(v) => {
+                        map.insert(k, v);
+                        model.insert(k, v);
+                        true
+                    }
+                    None => map.remove(&k) == model.remove(&k).is_some(),
+                };
+                if !agree || map.len() != model.len() {
+                    return false;
+                }
+            }
+            let reads_agree = map.iter().eq(model.iter())
+                && map.range(50..=200).eq(model.range(50..=200))
+                && model.keys().all(|k| map.get(k) == model.get(k));
+            let sorted = model.into_iter().collect::<Vec<_>>();
+            reads_agree && map.to_sorted_vec() == sorted && map.into_sorted_vec() == sorted
+        }
+        quickcheck(p as fn(Vec<(u8, Option<u8>)>, u8, u8) -> bool)
+    }
+}
@@ -0,0 +1,340 @@
+use std::time::{Duration, Instant};
+
+use crate::{
+    clock::{Clock, SystemClock},
+    slab::{Key, SlotMap},
+};
+
+// Each level divides time into 64 slots, each spanning a whole rotation of the level below
+const SLOT_BITS: usize = 6;
+const SLOTS: usize = 1 << SLOT_BITS;
+// Enough levels to place any u64 tick
+const LEVELS: usize = (u64::BITS as usize).div_ceil(SLOT_BITS);
+
+/// A handle to a scheduled timer, which can be used to cancel it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TimerHandle(Key);
+
+/// A hierarchical timing wheel, which fires items once their delay has passed.
+/// Time is divided into ticks, and each level of the wheel has 64 slots covering one tick,
+/// 64 ticks, 64^2 ticks and so on. A timer is placed in the slot of the lowest level which
+/// can tell its deadline apart from the current time, and is moved down a level whenever
+/// its slot comes up, so scheduling and cancelling are O(1) regardless of how many timers
+/// are pending. Deadlines are rounded up to whole ticks.
+/// `advance_now` reads the time from a `Clock`, which tests can replace with a `MockClock`.
+#[derive(Debug)]
+pub struct TimerWheel<T, C = SystemClock> {
+    timers: SlotMap<Timer<T>>,
+    levels: Vec<Level>,
+    start: Instant,
+    tick: Duration,
+    // The number of ticks since `start` which have been processed
+    elapsed: u64,
+    // Keys of cancelled timers which are still in a slot
+    stale: usize,
+    clock: C,
+}
+
+#[derive(Debug)]
+struct Timer<T> {
+    deadline: u64,
+    item: T,
+}
+
+#[derive(Debug)]
+struct Level {
+    slots: Vec<Vec<Key>>,
+    // Bit `i` is set when slot `i` is non-empty
+    occupied: u64,
+}
+
+impl<T> TimerWheel<T> {
+    /// Creates a wheel which starts now and measures time in units of `tick`.
+    pub fn new(tick: Duration) -> Self {
+        TimerWheel::with_clock(tick, SystemClock)
+    }
+
+    /// Creates a wheel whose time starts at `start`. Panics if `tick` is zero.
+    pub fn with_start(tick: Duration, start: Instant) -> Self {
+        TimerWheel::with_start_and_clock(tick, start, SystemClock)
+    }
+}
+
+impl<T, C: Clock> TimerWheel<T, C> {
+    /// Creates a wheel which starts at the clock's current time.
+    pub fn with_clock(tick: Duration, clock: C) -> Self {
+        let start = clock.now();
+        TimerWheel::with_start_and_clock(tick, start, clock)
+    }
+
+    /// Advances the wheel to the clock's current time, returning the items which are due.
+    pub fn advance_now(&mut self) -> Vec<T> {
+        self.advance(self.clock.now())
+    }
+}
+
+impl<T, C> TimerWheel<T, C> {
+    fn with_start_and_clock(tick: Duration, start: Instant, clock: C) -> Self {
+        assert!(!tick.is_zero(), "tick must be positive");
+        TimerWheel {
+            timers: SlotMap::new(),
+            levels: (0..LEVELS)
+                .map(|_| Level {
+                    slots: (0..SLOTS).map(|_| vec![]).collect(),
+                    occupied: 0,
+                })
+                .collect(),
+            start,
+            tick,
+            elapsed: 0,
+            stale: 0,
+            clock,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.timers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.timers.is_empty()
+    }
+
+    /// Schedules `item` to fire once `after` has passed since the last call to `advance`.
+    pub fn schedule(&mut self, after: Duration, item: T) -> TimerHandle {
+        let deadline = self.elapsed.saturating_add(self.ticks(after, true));
+        self.insert(deadline, item)
+    }
+
+    /// Schedules `item` to fire at `deadline`. Deadlines in the past fire on the next `advance`.
+    pub fn schedule_at(&mut self, deadline: Instant, item: T) -> TimerHandle {
+        let after = deadline.saturating_duration_since(self.start);
+        let deadline = self.ticks(after, true).max(self.elapsed);
+        self.insert(deadline, item)
+    }
+
+    /// Cancels the timer, returning its item if it hadn't fired yet.
+    pub fn cancel(&mut self, handle: TimerHandle) -> Option<T> {
+        let timer = self.timers.remove(handle.0)?;
+        // The key is left in its slot and skipped when the slot comes up, unless enough
+        // of them build up to be worth sweeping
+        self.stale += 1;
+        if self.stale > 2 * self.timers.len() + SLOTS {
+            self.sweep();
+        }
+        Some(timer.item)
+    }
+
+    /// Advances the wheel to `now`, returning the items of every timer which is due, in
+    /// order of deadline. Timers due on the same tick are returned in no particular order.
+    pub fn advance(&mut self, now: Instant) -> Vec<T> {
+        let target = self.ticks(now.saturating_duration_since(self.start), false);
+        let mut fired = vec![];
+        while let Some((level, slot, at)) = self.next_slot() {
+            if at > target {
+                break;
+            }
+            self.elapsed = at;
+            let keys = std::mem::take(&mut self.levels[level].slots[slot]);
+            self.levels[level].occupied &= !(1 << slot);
+            for key in keys {
+                let Some(timer) = self.timers.get(key) else {
+                    self.stale -= 1;
+                    continue;
+                };
+                if timer.deadline <= self.elapsed {
+                    fired.push(self.timers.remove(key).unwrap().item);
+                } else {
+                    // Move the timer down to a level which tells its deadline apart
+                    let deadline = timer.deadline;
+                    self.place(key, deadline);
+                }
+            }
+        }
+        self.elapsed = self.elapsed.max(target);
+        fired
+    }
+
+    /// A time at or before the earliest deadline, after which `advance` should next be called.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        let (_, _, at) = self.next_slot()?;
+        let nanos = self.tick.as_nanos().saturating_mul(at as u128);
+        self.start
+            .checked_add(Duration::from_nanos(nanos.min(u64::MAX as u128) as u64))
+    }
+
+    fn insert(&mut self, deadline: u64, item: T) -> TimerHandle {
+        let key = self.timers.insert(Timer { deadline, item });
+        self.place(key, deadline);
+        TimerHandle(key)
+    }
+
+    /// Puts the key in the slot for its deadline on the lowest level where the deadline
+    /// differs from the current time.
+    fn place(&mut self, key: Key, deadline: u64) {
+        let differing = (self.elapsed ^ deadline) | (SLOTS as u64 - 1);
+        let level = (u64::BITS - 1 - differing.leading_zeros()) as usize / SLOT_BITS;
+        let slot = (deadline >> (level * SLOT_BITS)) as usize % SLOTS;
+        self.levels[level].slots[slot].push(key);
+        self.levels[level].occupied |= 1 << slot;
+    }
+
+    /// Finds the next occupied slot and the tick at which it starts. The lowest occupied level
+    /// always comes first, since higher levels only hold timers beyond its current rotation.
+    fn next_slot(&self) -> Option<(usize, usize, u64)> {
+        self.levels.iter().enumerate().find_map(|(i, level)| {
+            let shift = i * SLOT_BITS;
+            let position = (self.elapsed >> shift) as usize % SLOTS;
+            // Slots behind the current position have already been emptied
+            let ahead = level.occupied & (u64::MAX << position);
+            if ahead == 0 {
+                return None;
+            }
+            let slot = ahead.trailing_zeros() as usize;
+            let rotation = self.elapsed
+                & u64::MAX
+                    .checked_shl((shift + SLOT_BITS) as u32)
+                    .unwrap_or(0);
+            Some((i, slot, rotation | ((slot as u64) << shift)))
+        })
+    }
+
+    /// Removes the keys of cancelled timers from every slot.
+    fn sweep(&mut self) {
+        for level in self.levels.iter_mut() {
+            for (i, slot) in level.slots.iter_mut().enumerate() {
+                slot.retain(|&key| self.timers.contains_key(key));
+                if slot.is_empty() {
+                    level.occupied &= !(1 << i);
+                }
+            }
+        }
+        self.stale = 0;
+    }
+
+    /// Converts a duration into ticks, rounding up or down.
+    fn ticks(&self, duration: Duration, round_up: bool) -> u64 {
+        let tick = self.tick.as_nanos();
+        let nanos = duration.as_nanos();
+        let ticks = if round_up {
+            nanos.div_ceil(tick)
+        } else {
+            nanos / tick
+        };
+        ticks.min(u64::MAX as u128) as u64
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use quickcheck::quickcheck;
+
+    use super::TimerWheel;
+    use crate::clock::MockClock;
+
+    const MS: Duration = Duration::from_millis(1);
+
+    #[test]
+    fn fires_in_order() {
+        let start = Instant::now();
+        let mut wheel = TimerWheel::with_start(MS, start);
+        wheel.schedule(MS * 5000, 'c');
+        wheel.schedule(MS * 3, 'a');
+        wheel.schedule(MS * 70, 'b');
+        assert_eq!(wheel.len(), 3);
+        assert_eq!(wheel.advance(start + MS * 2), vec![]);
+        assert_eq!(wheel.advance(start + MS * 100), vec!['a', 'b']);
+        assert!(wheel.next_deadline().unwrap() <= start + MS * 5000);
+        assert_eq!(wheel.advance(start + MS * 4999), vec![]);
+        assert_eq!(wheel.advance(start + MS * 5000), vec!['c']);
+        assert!(wheel.is_empty());
+        assert_eq!(wheel.next_deadline(), None);
+    }
+
+    #[test]
+    fn schedule_relative_to_advance() {
+        let start = Instant::now();
+        let mut wheel = TimerWheel::with_start(MS, start);
+        wheel.advance(start + MS * 10);
+        wheel.schedule(MS * 5, 1);
+        wheel.schedule(Duration::ZERO, 0);
+        wheel.schedule_at(start, 0);
+        assert_eq!(wheel.advance(start + MS * 10), vec![0, 0]);
+        assert_eq!(wheel.advance(start + MS * 14), vec![]);
+        assert_eq!(wheel.advance(start + MS * 15), vec![1]);
+    }
+
+    #[test]
+    fn mock_clock() {
+        let clock = MockClock::new();
+        let mut wheel = TimerWheel::with_clock(MS, clock.clone());
+        wheel.schedule(MS * 20, 'a');
+        clock.advance(MS * 19);
+        assert_eq!(wheel.advance_now(), vec![]);
+        clock.advance(MS);
+        assert_eq!(wheel.advance_now(), vec!['a']);
+    }
+
+    #[test]
+    fn cancel() {
+        let start = Instant::now();
+        let mut wheel = TimerWheel::with_start(MS, start);
+        let a = wheel.schedule(MS * 10, 'a');
+        wheel.schedule(MS * 10, 'b');
+        assert_eq!(wheel.cancel(a), Some('a'));
+        assert_eq!(wheel.cancel(a), None);
+        assert_eq!(wheel.advance(start + MS * 10), vec!['b']);
+        // Cancelling far-off timers sweeps their keys from the slots
+        for _ in 0..1000 {
+            let handle = wheel.schedule(Duration::from_secs(3600), 'c');
+            wheel.cancel(handle);
+        }
+        assert!(wheel.stale <= super::SLOTS);
+        assert!(wheel.is_empty());
+    }
+
+    #[test]
+    fn far_deadlines() {
+        let start = Instant::now();
+        let mut wheel = TimerWheel::with_start(Duration::from_nanos(1), start);
+        let far = Duration::from_secs(100 * 365 * 24 * 3600);
+        wheel.schedule(far, 'a');
+        assert_eq!(wheel.advance(start + far - Duration::from_nanos(1)), vec![]);
+        assert_eq!(wheel.advance(start + far), vec!['a']);
+    }
+
+    #[test]
+    fn prop_fires_when_due() {
+        fn p(delays: Vec<u16>, steps: Vec<u16>) -> bool {
+            let start = Instant::now();
+            let mut wheel = TimerWheel::with_start(MS, start);
+            for (i, &delay) in delays.iter().enumerate() {
+                wheel.schedule(MS * delay as u32, (delay, i));
+            }
+            let mut now = 0;
+            let mut fired = vec![];
+            for step in steps.into_iter().chain([u16::MAX]) {
+                now += step as u64;
+                let batch = wheel.advance(start + MS * now as u32);
+                if batch.iter().any(|&(delay, _)| delay as u64 > now)
+                    || !batch.windows(2).all(|w| w[0].0 <= w[1].0)
+                {
+                    return false;
+                }
+                fired.extend(batch);
+                if fired
+                    .iter()
+                    .filter(|(delay, _)| *delay as u64 <= now)
+                    .count()
+                    != delays.iter().filter(|&&delay| delay as u64 <= now).count()
+                {
+                    return false;
+                }
+            }
+            wheel.is_empty()
+        }
+        quickcheck(p as fn(Vec<u16>, Vec<u16>) -> bool);
+    }
+}
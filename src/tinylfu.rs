@@ -0,0 +1,179 @@
+use std::hash::Hash;
+
+use crate::{bounded_map::BoundedMap, sketch::CountMinSketch};
+
+const DEFAULT_DEPTH: usize = 4;
+const MIN_WIDTH: usize = 64;
+
+/// A TinyLFU admission policy in front of a bounded cache. Every access is counted in a
+/// Count-Min sketch, and once the cache is full a new key is only admitted if it has been
+/// seen more often than the entry the cache would evict for it. This keeps one-off keys,
+/// such as those of a scan, from pushing out popular entries. The counts are halved after
+/// every `sample_size` accesses so that the sketch follows changes in popularity.
+#[derive(Debug, Clone)]
+pub struct Admission<M> {
+    cache: M,
+    sketch: CountMinSketch,
+    sample_size: usize,
+    // Accesses counted since the sketch was last halved
+    samples: usize,
+}
+
+impl<M> Admission<M> {
+    /// Wraps the cache with a sketch sized for its capacity, halving counts after ten
+    /// accesses per entry.
+    pub fn new<K, V>(cache: M) -> Self
+    where
+        M: BoundedMap<K, V>,
+    {
+        let capacity = cache.capacity();
+        let sketch = CountMinSketch::with_dimensions((capacity * 4).max(MIN_WIDTH), DEFAULT_DEPTH);
+        Admission::with_sketch(cache, sketch, capacity.saturating_mul(10))
+    }
+
+    /// Wraps the cache with the given sketch, halving its counts after every `sample_size`
+    /// accesses. Panics if `sample_size` is zero.
+    pub fn with_sketch(cache: M, sketch: CountMinSketch, sample_size: usize) -> Self {
+        assert!(sample_size > 0, "sample size must be positive");
+        Admission {
+            cache,
+            sketch,
+            sample_size,
+            samples: 0,
+        }
+    }
+
+    pub fn cache(&self) -> &M {
+        &self.cache
+    }
+
+    pub fn into_inner(self) -> M {
+        self.cache
+    }
+
+    pub fn sketch(&self) -> &CountMinSketch {
+        &self.sketch
+    }
+
+    pub fn sample_size(&self) -> usize {
+        self.sample_size
+    }
+
+    /// Counts an access to the key, halving every count once the sample is full.
+    fn record<K: Hash>(&mut self, k: &K) {
+        self.sketch.add(k, 1);
+        self.samples += 1;
+        if self.samples >= self.sample_size {
+            self.sketch.halve();
+            self.samples = 0;
+        }
+    }
+}
+
+impl<K, V, M> BoundedMap<K, V> for Admission<M>
+where
+    K: Hash,
+    M: BoundedMap<K, V>,
+{
+    fn capacity(&self) -> usize {
+        self.cache.capacity()
+    }
+
+    fn len(&self) -> usize {
+        self.cache.len()
+    }
+
+    fn contains_key(&self, k: &K) -> bool {
+        self.cache.contains_key(k)
+    }
+
+    fn get(&mut self, k: &K) -> Option<&V> {
+        self.record(k);
+        self.cache.get(k)
+    }
+
+    /// Inserts the entry if the key is present, there is room, or the key is more frequent
+    /// than the cache's victim. Otherwise the entry is rejected and handed back.
+    fn insert_evicting(&mut self, k: K, v: V) -> Option<(K, V)> {
+        self.record(&k);
+        if !self.cache.contains_key(&k) && self.cache.is_full() {
+            let admit = match self.cache.victim() {
+                Some(victim) => self.sketch.estimate(&k) > self.sketch.estimate(victim),
+                None => false,
+            };
+            if !admit {
+                return Some((k, v));
+            }
+        }
+        self.cache.insert_evicting(k, v)
+    }
+
+    fn remove(&mut self, k: &K) -> Option<V> {
+        self.cache.remove(k)
+    }
+
+    fn victim(&self) -> Option<&K> {
+        self.cache.victim()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #[cfg(feature = "lru_cache")]
+    #[test]
+    fn rejects_scans() {
+        use super::Admission;
+        use crate::{bounded_map::BoundedMap, lru_cache::LRUCache};
+
+        let mut cache = Admission::new(LRUCache::new(2));
+        for _ in 0..3 {
+            cache.insert_evicting("a", 1);
+            cache.insert_evicting("b", 2);
+            cache.get(&"a");
+        }
+        // One-off keys are not seen more often than the least recently used entry
+        for k in ["x", "y", "z"] {
+            assert_eq!(cache.insert_evicting(k, 0), Some((k, 0)));
+        }
+        assert!(cache.contains_key(&"a") && cache.contains_key(&"b"));
+        // A key which becomes popular is eventually admitted
+        let mut evicted = None;
+        for _ in 0..10 {
+            evicted = evicted.or(cache.insert_evicting("c", 3).filter(|(k, _)| *k != "c"));
+        }
+        assert_eq!(evicted, Some(("b", 2)));
+        assert!(cache.contains_key(&"c"));
+    }
+
+    #[cfg(feature = "lru_cache")]
+    #[test]
+    fn halves_after_sample() {
+        use super::Admission;
+        use crate::{bounded_map::BoundedMap, lru_cache::LRUCache, sketch::CountMinSketch};
+
+        let sketch = CountMinSketch::with_dimensions(64, 4);
+        let mut cache = Admission::with_sketch(LRUCache::new(4), sketch, 4);
+        for _ in 0..3 {
+            cache.insert_evicting(1, ());
+        }
+        assert_eq!(cache.sketch().estimate(&1), 3);
+        cache.get(&1);
+        assert_eq!(cache.sketch().estimate(&1), 2);
+        assert_eq!(cache.sample_size(), 4);
+    }
+
+    #[cfg(feature = "lfu_cache")]
+    #[test]
+    fn bounded_map_contract() {
+        use super::Admission;
+        use crate::{bounded_map::BoundedMap, lfu_cache::LFUCache};
+
+        let mut cache = Admission::new(LFUCache::new(1));
+        assert_eq!(cache.try_insert(1, 1), Ok(()));
+        assert!(cache.is_full());
+        assert_eq!(cache.insert_evicting(1, 2), None);
+        assert_eq!(cache.get(&1), Some(&2));
+        assert_eq!(cache.remove(&1), Some(2));
+        assert!(cache.into_inner().is_empty());
+    }
+}
@@ -0,0 +1,76 @@
+//! An explicit stack for walking structures whose depth isn't bounded, such as unbalanced
+//! trees and tries, so that traversing, cloning or dropping them never recurses once per
+//! level and can't overflow the call stack.
+
+// Entries per chunk. A deep walk grows by allocating another chunk rather than by copying
+// everything pushed so far into a bigger buffer.
+const CHUNK_LEN: usize = 256;
+
+/// A LIFO stack kept on the heap in fixed-size chunks.
+#[derive(Debug)]
+pub(crate) struct TraversalStack<T> {
+    top: Vec<T>,
+    // Full chunks below the top, the oldest first
+    below: Vec<Vec<T>>,
+    // An emptied chunk kept so that pushing and popping across a chunk boundary doesn't
+    // allocate every time
+    spare: Option<Vec<T>>,
+}
+
+impl<T> TraversalStack<T> {
+    pub(crate) fn new() -> Self {
+        TraversalStack {
+            top: vec![],
+            below: vec![],
+            spare: None,
+        }
+    }
+
+    pub(crate) fn push(&mut self, item: T) {
+        if self.top.len() == CHUNK_LEN {
+            let chunk = self
+                .spare
+                .take()
+                .unwrap_or_else(|| Vec::with_capacity(CHUNK_LEN));
+            self.below.push(std::mem::replace(&mut self.top, chunk));
+        } else if self.top.capacity() == 0 {
+            self.top.reserve_exact(CHUNK_LEN);
+        }
+        self.top.push(item);
+    }
+
+    pub(crate) fn pop(&mut self) -> Option<T> {
+        if self.top.is_empty() {
+            let chunk = self.below.pop()?;
+            self.spare = Some(std::mem::replace(&mut self.top, chunk));
+        }
+        self.top.pop()
+    }
+}
+
+impl<T> Extend<T> for TraversalStack<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for item in iter {
+            self.push(item);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{TraversalStack, CHUNK_LEN};
+
+    #[test]
+    fn lifo_across_chunks() {
+        let mut stack = TraversalStack::new();
+        stack.extend(0..3 * CHUNK_LEN + 1);
+        for i in (CHUNK_LEN..3 * CHUNK_LEN + 1).rev() {
+            assert_eq!(stack.pop(), Some(i));
+        }
+        // Pushing back over the boundary reuses the spare chunk
+        stack.extend(0..2);
+        assert_eq!(stack.pop(), Some(1));
+        assert_eq!(stack.pop(), Some(0));
+        assert!((0..CHUNK_LEN).rev().eq(std::iter::from_fn(|| stack.pop())));
+    }
+}
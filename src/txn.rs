@@ -0,0 +1,217 @@
+use std::ops::RangeBounds;
+
+use crate::avl_tree::{self, AVLTree};
+
+/// An ordered map whose updates can be made speculatively and then committed or rolled back.
+/// While a transaction is open, every mutation records the key's previous value in an undo
+/// log, so rolling back costs time proportional to the changes made rather than the size of
+/// the map. Transactions nest: committing an inner transaction folds its changes into the
+/// enclosing one, which can still roll them back.
+#[derive(Debug)]
+pub struct TxnMap<K, V> {
+    tree: AVLTree<K, V>,
+    len: usize,
+    // The previous value of each key changed in an open transaction, oldest first
+    undo: Vec<(K, Option<V>)>,
+    // The length of the undo log when each open transaction began
+    savepoints: Vec<usize>,
+}
+
+impl<K, V> TxnMap<K, V> {
+    pub fn new() -> Self {
+        TxnMap {
+            tree: AVLTree::new(),
+            len: 0,
+            undo: vec![],
+            savepoints: vec![],
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The number of open transactions.
+    pub fn depth(&self) -> usize {
+        self.savepoints.len()
+    }
+
+    /// Opens a transaction, nested inside any which is already open.
+    pub fn begin(&mut self) {
+        self.savepoints.push(self.undo.len());
+    }
+
+    /// Commits the innermost transaction. Panics if none is open.
+    pub fn commit(&mut self) {
+        self.savepoints.pop().expect("no transaction in progress");
+        if self.savepoints.is_empty() {
+            self.undo.clear();
+        }
+    }
+}
+
+impl<K: Ord, V> TxnMap<K, V> {
+    pub fn get(&self, k: &K) -> Option<&V> {
+        self.tree.get(k)
+    }
+
+    pub fn contains_key(&self, k: &K) -> bool {
+        self.tree.get(k).is_some()
+    }
+
+    pub fn first(&self) -> Option<&K> {
+        self.tree.first()
+    }
+
+    pub fn last(&self) -> Option<&K> {
+        self.tree.last()
+    }
+
+    pub fn iter(&self) -> avl_tree::Iter<'_, K, V> {
+        self.tree.iter()
+    }
+
+    pub fn range<R: RangeBounds<K>>(&self, range: R) -> avl_tree::Iter<'_, K, V> {
+        self.tree.range(range)
+    }
+
+    /// Rolls back the innermost transaction, restoring every key it changed.
+    /// Panics if none is open.
+    pub fn rollback(&mut self) {
+        let savepoint = self.savepoints.pop().expect("no transaction in progress");
+        for (k, old) in self.undo.drain(savepoint..).rev() {
+            if self.tree.remove(&k).is_some() {
+                self.len -= 1;
+            }
+            if let Some(v) = old {
+                self.tree.insert(k, v);
+                self.len += 1;
+            }
+        }
+    }
+}
+
+// Mutations hand the previous value back to the caller and keep a copy in the undo log
+impl<K: Ord + Clone, V: Clone> TxnMap<K, V> {
+    pub fn insert(&mut self, k: K, v: V) -> Option<V> {
+        // The tree keeps the existing value for a duplicate key
+        let old = self.tree.remove(&k);
+        if old.is_none() {
+            self.len += 1;
+        }
+        self.tree.insert(k.clone(), v);
+        self.record(k, &old);
+        old
+    }
+
+    pub fn remove(&mut self, k: &K) -> Option<V> {
+        let old = self.tree.remove(k);
+        if old.is_some() {
+            self.len -= 1;
+            self.record(k.clone(), &old);
+        }
+        old
+    }
+
+    fn record(&mut self, k: K, old: &Option<V>) {
+        if !self.savepoints.is_empty() {
+            self.undo.push((k, old.clone()));
+        }
+    }
+}
+
+impl<K, V> Default for TxnMap<K, V> {
+    fn default() -> Self {
+        TxnMap::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use quickcheck::quickcheck;
+
+    use super::TxnMap;
+
+    #[test]
+    fn rollback_restores() {
+        let mut map = TxnMap::new();
+        map.insert(1, 'a');
+        map.insert(2, 'b');
+        map.begin();
+        assert_eq!(map.insert(1, 'x'), Some('a'));
+        assert_eq!(map.insert(3, 'c'), None);
+        assert_eq!(map.remove(&2), Some('b'));
+        assert_eq!(map.len(), 2);
+        map.rollback();
+        assert_eq!(map.iter().collect::<Vec<_>>(), vec![(&1, &'a'), (&2, &'b')]);
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.depth(), 0);
+    }
+
+    #[test]
+    fn nested_commit_folds_into_parent() {
+        let mut map = TxnMap::new();
+        map.begin();
+        map.insert(1, 'a');
+        map.begin();
+        map.insert(2, 'b');
+        map.commit();
+        map.begin();
+        map.insert(3, 'c');
+        map.rollback();
+        assert_eq!(map.iter().map(|(k, _)| *k).collect::<Vec<_>>(), vec![1, 2]);
+        map.rollback();
+        assert!(map.is_empty());
+        map.begin();
+        map.insert(1, 'a');
+        map.commit();
+        assert!(map.undo.is_empty());
+        assert_eq!(map.get(&1), Some(&'a'));
+    }
+
+    #[test]
+    #[should_panic(expected = "no transaction in progress")]
+    fn rollback_without_begin() {
+        TxnMap::<u8, u8>::new().rollback();
+    }
+
+    #[test]
+    fn prop_matches_snapshots() {
+        fn p(ops: Vec<(u8, u8, u8)>) -> bool {
+            let mut map = TxnMap::new();
+            let mut model = BTreeMap::new();
+            let mut snapshots = vec![];
+            for (op, k, v) in ops {
+                let k = k % 16;
+                match op % 6 {
+                    0 | 1 => assert_eq!(map.insert(k, v), model.insert(k, v)),
+                    2 => assert_eq!(map.remove(&k), model.remove(&k)),
+                    3 => {
+                        map.begin();
+                        snapshots.push(model.clone());
+                    }
+                    4 if !snapshots.is_empty() => {
+                        map.commit();
+                        snapshots.pop();
+                    }
+                    5 if !snapshots.is_empty() => {
+                        map.rollback();
+                        model = snapshots.pop().unwrap();
+                    }
+                    _ => {}
+                }
+                if map.len() != model.len() || !map.iter().eq(model.iter()) {
+                    return false;
+                }
+            }
+            map.depth() == snapshots.len()
+        }
+        quickcheck(p as fn(Vec<(u8, u8, u8)>) -> bool);
+    }
+}
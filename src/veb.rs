@@ -0,0 +1,339 @@
+use std::collections::HashMap;
+
+/// A van Emde Boas tree over `u32` keys, answering successor and predecessor queries in
+/// O(log log U) rather than the O(log n) of a balanced tree. Each level splits a key into a
+/// high half picking a cluster and a low half within it, and the minimum of every level is
+/// kept out of its clusters. Clusters are allocated lazily, so space is O(n).
+#[derive(Debug, Clone)]
+pub struct VebTree {
+    root: Node,
+    len: usize,
+}
+
+#[derive(Debug, Clone)]
+struct Node {
+    // The number of bits in the keys of this level
+    bits: u32,
+    // `max` is only meaningful while `min` is present
+    min: Option<u32>,
+    max: u32,
+    summary: Option<Box<Node>>,
+    clusters: HashMap<u32, Node>,
+}
+
+impl Node {
+    fn new(bits: u32) -> Self {
+        Node {
+            bits,
+            min: None,
+            max: 0,
+            summary: None,
+            clusters: HashMap::new(),
+        }
+    }
+
+    fn low_bits(&self) -> u32 {
+        self.bits / 2
+    }
+
+    fn split(&self, x: u32) -> (u32, u32) {
+        (x >> self.low_bits(), x & ((1 << self.low_bits()) - 1))
+    }
+
+    fn join(&self, high: u32, low: u32) -> u32 {
+        (high << self.low_bits()) | low
+    }
+
+    fn contains(&self, x: u32) -> bool {
+        match self.min {
+            None => false,
+            Some(min) if x == min || x == self.max => true,
+            Some(_) if self.bits == 1 => false,
+            Some(_) => {
+                let (high, low) = self.split(x);
+                self.clusters.get(&high).is_some_and(|c| c.contains(low))
+            }
+        }
+    }
+
+    fn insert(&mut self, mut x: u32) -> bool {
+        let min = match self.min {
+            None => {
+                self.min = Some(x);
+                self.max = x;
+                return true;
+            }
+            Some(min) if min == x => return false,
+            Some(min) => min,
+        };
+        let displaced = x < min;
+        if displaced {
+            // The new key becomes the minimum and the old one moves into the clusters
+            self.min = Some(x);
+            x = min;
+        }
+        let inserted = if self.bits == 1 {
+            displaced || x != self.max
+        } else {
+            let (high, low) = self.split(x);
+            let (low_bits, high_bits) = (self.low_bits(), self.bits - self.low_bits());
+            let cluster = self
+                .clusters
+                .entry(high)
+                .or_insert_with(|| Node::new(low_bits));
+            if cluster.min.is_none() {
+                self.summary
+                    .get_or_insert_with(|| Box::new(Node::new(high_bits)))
+                    .insert(high);
+            }
+            cluster.insert(low)
+        };
+        if x > self.max {
+            self.max = x;
+        }
+        inserted
+    }
+
+    fn remove(&mut self, mut x: u32) -> bool {
+        let Some(min) = self.min else {
+            return false;
+        };
+        if min == self.max {
+            if x != min {
+                return false;
+            }
+            self.min = None;
+            return true;
+        }
+        if self.bits == 1 {
+            // Both 0 and 1 are present
+            self.min = Some(1 - x);
+            self.max = 1 - x;
+            return true;
+        }
+        if x == min {
+            // Pull the smallest clustered key up to replace the minimum
+            let high = self.summary.as_ref().unwrap().min.unwrap();
+            x = self.join(high, self.clusters[&high].min.unwrap());
+            self.min = Some(x);
+        }
+        let (high, low) = self.split(x);
+        let Some(cluster) = self.clusters.get_mut(&high) else {
+            return false;
+        };
+        if !cluster.remove(low) {
+            return false;
+        }
+        if cluster.min.is_none() {
+            self.clusters.remove(&high);
+            let summary = self.summary.as_mut().unwrap();
+            summary.remove(high);
+            if summary.min.is_none() {
+                self.summary = None;
+            }
+        }
+        if x == self.max {
+            self.max = match &self.summary {
+                Some(summary) => self.join(summary.max, self.clusters[&summary.max].max),
+                None => self.min.unwrap(),
+            };
+        }
+        true
+    }
+
+    fn successor(&self, x: u32) -> Option<u32> {
+        let min = self.min?;
+        if x < min {
+            return Some(min);
+        }
+        if x >= self.max {
+            return None;
+        }
+        if self.bits == 1 {
+            return Some(self.max);
+        }
+        let (high, low) = self.split(x);
+        if let Some(cluster) = self.clusters.get(&high) {
+            if low < cluster.max {
+                return Some(self.join(high, cluster.successor(low)?));
+            }
+        }
+        let next = self.summary.as_ref()?.successor(high)?;
+        Some(self.join(next, self.clusters[&next].min?))
+    }
+
+    fn predecessor(&self, x: u32) -> Option<u32> {
+        let min = self.min?;
+        if x > self.max {
+            return Some(self.max);
+        }
+        if x <= min {
+            return None;
+        }
+        if self.bits == 1 {
+            return Some(min);
+        }
+        let (high, low) = self.split(x);
+        if let Some(cluster) = self.clusters.get(&high) {
+            if cluster.min.is_some_and(|m| m < low) {
+                return Some(self.join(high, cluster.predecessor(low)?));
+            }
+        }
+        // The minimum isn't in any cluster, so it is the last resort
+        match self.summary.as_ref().and_then(|s| s.predecessor(high)) {
+            Some(prev) => Some(self.join(prev, self.clusters[&prev].max)),
+            None => Some(min),
+        }
+    }
+}
+
+impl VebTree {
+    pub fn new() -> Self {
+        VebTree {
+            root: Node::new(32),
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn contains(&self, x: u32) -> bool {
+        self.root.contains(x)
+    }
+
+    /// Inserts the key, returning whether it was absent.
+    pub fn insert(&mut self, x: u32) -> bool {
+        let inserted = self.root.insert(x);
+        self.len += inserted as usize;
+        inserted
+    }
+
+    /// Removes the key, returning whether it was present.
+    pub fn remove(&mut self, x: u32) -> bool {
+        let removed = self.root.remove(x);
+        self.len -= removed as usize;
+        removed
+    }
+
+    pub fn min(&self) -> Option<u32> {
+        self.root.min
+    }
+
+    pub fn max(&self) -> Option<u32> {
+        self.root.min.map(|_| self.root.max)
+    }
+
+    /// The smallest key strictly greater than `x`.
+    pub fn successor(&self, x: u32) -> Option<u32> {
+        self.root.successor(x)
+    }
+
+    /// The largest key strictly less than `x`.
+    pub fn predecessor(&self, x: u32) -> Option<u32> {
+        self.root.predecessor(x)
+    }
+
+    /// Returns the keys in ascending order.
+    pub fn iter(&self) -> impl Iterator<Item = u32> + '_ {
+        std::iter::successors(self.min(), |&x| self.successor(x))
+    }
+
+    pub fn clear(&mut self) {
+        *self = VebTree::new();
+    }
+}
+
+impl Default for VebTree {
+    fn default() -> Self {
+        VebTree::new()
+    }
+}
+
+impl FromIterator<u32> for VebTree {
+    fn from_iter<I: IntoIterator<Item = u32>>(iter: I) -> Self {
+        let mut tree = VebTree::new();
+        tree.extend(iter);
+        tree
+    }
+}
+
+impl Extend<u32> for VebTree {
+    fn extend<I: IntoIterator<Item = u32>>(&mut self, iter: I) {
+        for x in iter {
+            self.insert(x);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeSet;
+
+    use quickcheck::quickcheck;
+
+    use super::VebTree;
+
+    #[test]
+    fn successor_predecessor() {
+        let tree = [3, 10, 1 << 20, u32::MAX].into_iter().collect::<VebTree>();
+        assert_eq!(tree.successor(0), Some(3));
+        assert_eq!(tree.successor(3), Some(10));
+        assert_eq!(tree.successor(11), Some(1 << 20));
+        assert_eq!(tree.successor(u32::MAX), None);
+        assert_eq!(tree.predecessor(u32::MAX), Some(1 << 20));
+        assert_eq!(tree.predecessor(3), None);
+        assert_eq!(
+            tree.iter().collect::<Vec<_>>(),
+            vec![3, 10, 1 << 20, u32::MAX]
+        );
+    }
+
+    #[test]
+    fn insert_remove() {
+        let mut tree = VebTree::new();
+        assert!(tree.insert(5));
+        assert!(!tree.insert(5));
+        assert!(tree.insert(0));
+        assert!(tree.remove(0));
+        assert!(!tree.remove(0));
+        assert_eq!(tree.min(), Some(5));
+        assert_eq!(tree.max(), Some(5));
+        assert!(tree.remove(5));
+        assert!(tree.is_empty());
+        assert_eq!(tree.max(), None);
+    }
+
+    #[test]
+    fn prop_matches_btree_set() {
+        // Keys are spread over a few small regions so that clusters fill up
+        fn p(ops: Vec<(bool, u8, u16)>, queries: Vec<u32>) -> bool {
+            let mut tree = VebTree::new();
+            let mut model = BTreeSet::new();
+            for (insert, region, low) in ops {
+                let x = ((region % 4) as u32) << 30 | (low % 64) as u32;
+                let agree = if insert {
+                    tree.insert(x) == model.insert(x)
+                } else {
+                    tree.remove(x) == model.remove(&x)
+                };
+                if !agree || tree.min() != model.first().copied() {
+                    return false;
+                }
+            }
+            tree.len() == model.len()
+                && tree.iter().eq(model.iter().copied())
+                && model.iter().chain(queries.iter()).all(|&q| {
+                    tree.contains(q) == model.contains(&q)
+                        && tree.successor(q) == model.range(q.saturating_add(1)..).next().copied()
+                        && tree.predecessor(q) == model.range(..q).next_back().copied()
+                })
+        }
+        quickcheck(p as fn(Vec<(bool, u8, u16)>, Vec<u32>) -> bool)
+    }
+}
@@ -0,0 +1,259 @@
+//! Renders tree-shaped structures as Graphviz `dot` source or as indented ASCII art,
+//! optionally highlighting the nodes visited while searching for a key.
+
+use std::{cmp::Ordering, fmt::Write};
+
+/// One node of a rendered tree. A `None` child marks an empty slot, such as the missing left
+/// child of a binary tree node, so that the remaining child is still drawn on its own side.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Node {
+    pub label: String,
+    pub highlighted: bool,
+    pub children: Vec<Option<Node>>,
+}
+
+/// A structure which can be drawn as a tree.
+pub trait Visualize {
+    type Key: ?Sized;
+
+    /// Returns the root of the tree, or `None` if the structure is empty.
+    /// When `search` is given, the nodes visited while looking it up are highlighted.
+    fn view(&self, search: Option<&Self::Key>) -> Option<Node>;
+
+    fn to_dot(&self) -> String {
+        dot(self.view(None).as_ref())
+    }
+
+    fn to_ascii(&self) -> String {
+        ascii(self.view(None).as_ref())
+    }
+
+    /// Renders the tree with the search path for `key` highlighted.
+    fn to_dot_with_path(&self, key: &Self::Key) -> String {
+        dot(self.view(Some(key)).as_ref())
+    }
+
+    /// Renders the tree with the search path for `key` marked by a `*`.
+    fn to_ascii_with_path(&self, key: &Self::Key) -> String {
+        ascii(self.view(Some(key)).as_ref())
+    }
+}
+
+/// Renders the tree as a Graphviz digraph. Empty slots are drawn as points.
+pub fn dot(root: Option<&Node>) -> String {
+    let mut out = String::from("digraph {\n");
+    if let Some(root) = root {
+        dot_node(root, &mut 0, &mut out);
+    }
+    out.push_str("}\n");
+    out
+}
+
+fn dot_node(node: &Node, next_id: &mut usize, out: &mut String) -> usize {
+    let id = *next_id;
+    *next_id += 1;
+    let label = node.label.replace('\\', "\\\\").replace('"', "\\\"");
+    let style = if node.highlighted {
+        ", style=filled, fillcolor=lightblue"
+    } else {
+        ""
+    };
+    writeln!(out, "    n{} [label=\"{}\"{}];", id, label, style).unwrap();
+    for child in node.children.iter() {
+        match child {
+            Some(child) => {
+                let child_id = dot_node(child, next_id, out);
+                let style = if node.highlighted && child.highlighted {
+                    " [color=blue, penwidth=2]"
+                } else {
+                    ""
+                };
+                writeln!(out, "    n{} -> n{}{};", id, child_id, style).unwrap();
+            }
+            None => {
+                let child_id = *next_id;
+                *next_id += 1;
+                writeln!(out, "    n{} [shape=point];", child_id).unwrap();
+                writeln!(out, "    n{} -> n{};", id, child_id).unwrap();
+            }
+        }
+    }
+    id
+}
+
+/// Renders the tree with one node per line, indented beneath its parent.
+/// Empty slots are drawn as `·` unless all of a node's children are empty.
+pub fn ascii(root: Option<&Node>) -> String {
+    let mut out = String::new();
+    if let Some(root) = root {
+        ascii_node(root, "", "", &mut out);
+    }
+    out
+}
+
+fn ascii_node(node: &Node, first_prefix: &str, prefix: &str, out: &mut String) {
+    let marker = if node.highlighted { " *" } else { "" };
+    writeln!(out, "{}{}{}", first_prefix, node.label, marker).unwrap();
+    if node.children.iter().all(Option::is_none) {
+        return;
+    }
+    for (i, child) in node.children.iter().enumerate() {
+        let last = i + 1 == node.children.len();
+        let (branch, indent) = if last {
+            ("└── ", "    ")
+        } else {
+            ("├── ", "│   ")
+        };
+        match child {
+            Some(child) => ascii_node(
+                child,
+                &format!("{}{}", prefix, branch),
+                &format!("{}{}", prefix, indent),
+                out,
+            ),
+            None => writeln!(out, "{}{}·", prefix, branch).unwrap(),
+        }
+    }
+}
+
+/// Decides which child of a binary search tree node the search continues into,
+/// returning the search key for the left and right subtrees respectively.
+#[cfg_attr(not(any(feature = "avl_tree", feature = "bs_tree")), allow(dead_code))]
+pub(crate) fn descend<'a, K: Ord>(
+    search: Option<&'a K>,
+    key: &K,
+) -> (Option<&'a K>, Option<&'a K>) {
+    match search.map(|s| s.cmp(key)) {
+        Some(Ordering::Less) => (search, None),
+        Some(Ordering::Greater) => (None, search),
+        _ => (None, None),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ascii, dot, Node};
+
+    fn leaf(label: &str, highlighted: bool) -> Option<Node> {
+        Some(Node {
+            label: label.to_string(),
+            highlighted,
+            children: vec![],
+        })
+    }
+
+    #[test]
+    fn render() {
+        let root = Node {
+            label: "2".to_string(),
+            highlighted: true,
+            children: vec![
+                Some(Node {
+                    label: "1".to_string(),
+                    highlighted: true,
+                    children: vec![None, leaf("\"x\"", true)],
+                }),
+                leaf("3", false),
+            ],
+        };
+        assert_eq!(
+            ascii(Some(&root)),
+            "2 *\n├── 1 *\n│   ├── ·\n│   └── \"x\" *\n└── 3\n"
+        );
+        assert_eq!(
+            dot(Some(&root)),
+            "digraph {\n\
+             \x20   n0 [label=\"2\", style=filled, fillcolor=lightblue];\n\
+             \x20   n1 [label=\"1\", style=filled, fillcolor=lightblue];\n\
+             \x20   n2 [shape=point];\n\
+             \x20   n1 -> n2;\n\
+             \x20   n3 [label=\"\\\"x\\\"\", style=filled, fillcolor=lightblue];\n\
+             \x20   n1 -> n3 [color=blue, penwidth=2];\n\
+             \x20   n0 -> n1 [color=blue, penwidth=2];\n\
+             \x20   n4 [label=\"3\"];\n\
+             \x20   n0 -> n4;\n\
+             }\n"
+        );
+        assert_eq!(dot(None), "digraph {\n}\n");
+        assert_eq!(ascii(None), "");
+    }
+
+    #[cfg(feature = "avl_tree")]
+    #[test]
+    fn avl_tree() {
+        use super::Visualize;
+        use crate::avl_tree::AVLTree;
+
+        let mut tree = AVLTree::new();
+        for i in [2, 1, 3, 4] {
+            tree.insert(i, ());
+        }
+        assert_eq!(
+            tree.to_ascii_with_path(&4),
+            "2 (h=3) *\n├── 1 (h=1)\n└── 3 (h=2) *\n    ├── ·\n    └── 4 (h=1) *\n"
+        );
+    }
+
+    #[cfg(feature = "bs_tree")]
+    #[test]
+    fn bs_tree() {
+        use super::Visualize;
+        use crate::bs_tree::BSTree;
+
+        let mut tree = BSTree::new();
+        for i in [2, 1, 3] {
+            tree.insert(i);
+        }
+        assert_eq!(tree.to_ascii_with_path(&0), "2 *\n├── 1 *\n└── 3\n");
+        assert!(tree.to_dot().contains("n0 -> n1;"));
+    }
+
+    #[cfg(feature = "heap")]
+    #[test]
+    fn heap() {
+        use super::Visualize;
+        use crate::heap::Heap;
+
+        let mut heap = Heap::new();
+        for i in [1, 2, 3, 4, 5] {
+            heap.push(i);
+        }
+        assert_eq!(
+            heap.to_ascii_with_path(&5),
+            "1 *\n├── 2 *\n│   ├── 4\n│   └── 5 *\n└── 3\n"
+        );
+        assert_eq!(heap.to_ascii_with_path(&6), heap.to_ascii());
+    }
+
+    #[cfg(feature = "hash_trie")]
+    #[test]
+    fn hash_trie() {
+        use super::Visualize;
+        use crate::hash_trie::HashTrie;
+
+        let mut trie = HashTrie::new();
+        trie.insert(['t', 'o'], 1);
+        trie.insert(['t', 'e'], 2);
+        trie.insert(['a'], 3);
+        assert_eq!(
+            trie.to_ascii_with_path(&['t', 'e']),
+            "(root) *\n├── 'a' = 3\n└── 't' *\n    ├── 'e' = 2 *\n    └── 'o' = 1\n"
+        );
+    }
+
+    #[cfg(feature = "static_trie")]
+    #[test]
+    fn static_trie() {
+        use super::Visualize;
+        use crate::static_trie::StaticTrieBuilder;
+
+        let mut builder = StaticTrieBuilder::new();
+        builder.insert(b"tea", 1).unwrap();
+        builder.insert(b"ten", 2).unwrap();
+        let trie = builder.finish();
+        assert_eq!(
+            trie.to_ascii_with_path(b"ten"),
+            "(root) *\n└── \"te\" *\n    ├── \"a\" = 1\n    └── \"n\" = 2 *\n"
+        );
+    }
+}
@@ -0,0 +1,145 @@
+use std::{
+    borrow::Borrow,
+    collections::{hash_map::RandomState, HashMap},
+    hash::{BuildHasher, Hash},
+    sync::{Arc, Weak},
+};
+
+/// A hash map which holds its values weakly, so it never keeps a value alive on its own.
+/// Entries whose value has been dropped read as absent. They are purged in bulk whenever the
+/// map has doubled in size since the last purge, keeping purging amortized O(1) per insert,
+/// or on demand with `purge`.
+#[derive(Debug)]
+pub struct WeakValueHashMap<K, V, S = RandomState> {
+    entries: HashMap<K, Weak<V>, S>,
+    // The number of entries after the last purge
+    purged_len: usize,
+}
+
+impl<K, V> WeakValueHashMap<K, V> {
+    pub fn new() -> Self {
+        WeakValueHashMap::with_hasher(RandomState::new())
+    }
+}
+
+impl<K, V, S> WeakValueHashMap<K, V, S> {
+    pub fn with_hasher(hasher: S) -> Self {
+        WeakValueHashMap {
+            entries: HashMap::with_hasher(hasher),
+            purged_len: 0,
+        }
+    }
+
+    /// The number of entries, including ones whose value has been dropped but not yet purged.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Removes every entry whose value has been dropped.
+    pub fn purge(&mut self) {
+        self.entries.retain(|_, v| v.strong_count() > 0);
+        self.purged_len = self.entries.len();
+    }
+
+    /// Returns the entries whose values are still alive.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, Arc<V>)> {
+        self.entries
+            .iter()
+            .filter_map(|(k, v)| v.upgrade().map(|v| (k, v)))
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.purged_len = 0;
+    }
+}
+
+impl<K: Eq + Hash, V, S: BuildHasher> WeakValueHashMap<K, V, S> {
+    /// Inserts a weak reference to the value, returning the previous value if it was still alive.
+    pub fn insert(&mut self, k: K, v: &Arc<V>) -> Option<Arc<V>> {
+        let previous = self.entries.insert(k, Arc::downgrade(v));
+        if self.entries.len() > 2 * self.purged_len.max(8) {
+            self.purge();
+        }
+        previous?.upgrade()
+    }
+
+    pub fn get<Q>(&self, k: &Q) -> Option<Arc<V>>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.entries.get(k)?.upgrade()
+    }
+
+    pub fn contains_key<Q>(&self, k: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.entries.get(k).is_some_and(|v| v.strong_count() > 0)
+    }
+
+    pub fn remove<Q>(&mut self, k: &Q) -> Option<Arc<V>>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.entries.remove(k)?.upgrade()
+    }
+}
+
+impl<K, V, S: Default> Default for WeakValueHashMap<K, V, S> {
+    fn default() -> Self {
+        WeakValueHashMap::with_hasher(S::default())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use super::WeakValueHashMap;
+
+    #[test]
+    fn does_not_keep_values_alive() {
+        let mut map = WeakValueHashMap::new();
+        let doc = Arc::new(String::from("parsed"));
+        map.insert("a", &doc);
+        assert_eq!(map.get("a").as_deref(), Some(&String::from("parsed")));
+        drop(doc);
+        assert_eq!(map.get("a"), None);
+        assert!(!map.contains_key("a"));
+        assert_eq!(map.len(), 1);
+        map.purge();
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn insert_replaces() {
+        let mut map = WeakValueHashMap::new();
+        let a = Arc::new(1);
+        let b = Arc::new(2);
+        assert_eq!(map.insert("k", &a), None);
+        assert_eq!(map.insert("k", &b), Some(a));
+        assert_eq!(map.remove("k"), Some(b));
+        assert_eq!(map.remove("k"), None);
+    }
+
+    #[test]
+    fn purges_incrementally() {
+        let mut map = WeakValueHashMap::new();
+        let live = Arc::new(0);
+        map.insert(0, &live);
+        for i in 1..1000 {
+            map.insert(i, &Arc::new(i));
+        }
+        assert!(map.len() <= 20);
+        assert_eq!(map.iter().count(), 1);
+        assert_eq!(map.get(&0), Some(live));
+    }
+}
@@ -0,0 +1,286 @@
+/// A set of items with integer weights which can be sampled in proportion to their weights.
+/// The weights are kept in a Fenwick tree over the item indices, so inserting, reweighting,
+/// removing and sampling all take O(log n) time. Indices of removed items are reused.
+#[derive(Debug, Clone)]
+pub struct WeightedIndexTree<T> {
+    // The Fenwick tree, counting from one, where `tree[i]` holds the weights of the
+    // `i & i.wrapping_neg()` slots ending at slot `i - 1`
+    tree: Vec<u64>,
+    slots: Vec<Option<(T, u64)>>,
+    free: Vec<usize>,
+    total: u64,
+    len: usize,
+}
+
+impl<T> WeightedIndexTree<T> {
+    pub fn new() -> Self {
+        WeightedIndexTree {
+            tree: vec![0],
+            slots: vec![],
+            free: vec![],
+            total: 0,
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The sum of every item's weight.
+    pub fn total_weight(&self) -> u64 {
+        self.total
+    }
+
+    /// Adds the item, returning the index which refers to it until it is removed.
+    /// Panics if the total weight would overflow.
+    pub fn insert(&mut self, item: T, weight: u64) -> usize {
+        self.total = self
+            .total
+            .checked_add(weight)
+            .expect("total weight overflows");
+        self.len += 1;
+        if let Some(index) = self.free.pop() {
+            self.slots[index] = Some((item, weight));
+            self.add(index, weight as i128);
+            return index;
+        }
+        let index = self.slots.len();
+        self.slots.push(Some((item, weight)));
+        // The new node covers its own slot plus the slots just before it
+        let node = index + 1;
+        let covered = self.prefix(index) - self.prefix(node - lowest_bit(node));
+        self.tree.push(weight + covered);
+        index
+    }
+
+    pub fn get(&self, index: usize) -> Option<&T> {
+        self.slots.get(index)?.as_ref().map(|(item, _)| item)
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        self.slots.get_mut(index)?.as_mut().map(|(item, _)| item)
+    }
+
+    pub fn weight(&self, index: usize) -> Option<u64> {
+        self.slots.get(index)?.as_ref().map(|&(_, weight)| weight)
+    }
+
+    /// Sets the weight of the item at `index`, returning its old weight, or `None` if there
+    /// is no such item. Panics if the total weight would overflow.
+    pub fn update_weight(&mut self, index: usize, weight: u64) -> Option<u64> {
+        let (_, old) = self.slots.get_mut(index)?.as_mut()?;
+        let old = std::mem::replace(old, weight);
+        self.total = (self.total - old)
+            .checked_add(weight)
+            .expect("total weight overflows");
+        self.add(index, weight as i128 - old as i128);
+        Some(old)
+    }
+
+    pub fn remove(&mut self, index: usize) -> Option<T> {
+        let (item, weight) = self.slots.get_mut(index)?.take()?;
+        self.total -= weight;
+        self.len -= 1;
+        self.add(index, -(weight as i128));
+        self.free.push(index);
+        Some(item)
+    }
+
+    /// Picks an item with probability proportional to its weight, drawing from `rng`, which
+    /// should return uniformly random `u64`s. Returns `None` if the total weight is zero.
+    pub fn sample(&self, mut rng: impl FnMut() -> u64) -> Option<(usize, &T)> {
+        if self.total == 0 {
+            return None;
+        }
+        // Scale to [0, total) by taking the high half of the product, which avoids the bias
+        // of a remainder
+        let target = ((rng() as u128 * self.total as u128) >> 64) as u64;
+        let index = self.find(target);
+        self.get(index).map(|item| (index, item))
+    }
+
+    /// Returns an iterator over the items and their indices and weights, in index order.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &T, u64)> {
+        self.slots
+            .iter()
+            .enumerate()
+            .filter_map(|(index, slot)| slot.as_ref().map(|(item, w)| (index, item, *w)))
+    }
+
+    pub fn clear(&mut self) {
+        *self = WeightedIndexTree::new();
+    }
+
+    /// The total weight of the slots before `index`.
+    fn prefix(&self, index: usize) -> u64 {
+        let mut node = index;
+        let mut sum = 0;
+        while node > 0 {
+            sum += self.tree[node];
+            node -= lowest_bit(node);
+        }
+        sum
+    }
+
+    fn add(&mut self, index: usize, delta: i128) {
+        let mut node = index + 1;
+        while node < self.tree.len() {
+            self.tree[node] = (self.tree[node] as i128 + delta) as u64;
+            node += lowest_bit(node);
+        }
+    }
+
+    /// Returns the slot whose range of cumulative weights contains `target`, which must be
+    /// less than the total weight.
+    fn find(&self, mut target: u64) -> usize {
+        let n = self.tree.len() - 1;
+        let mut node = 0;
+        let mut step = if n == 0 { 0 } else { 1 << n.ilog2() };
+        while step > 0 {
+            if node + step <= n && self.tree[node + step] <= target {
+                node += step;
+                target -= self.tree[node];
+            }
+            step >>= 1;
+        }
+        node
+    }
+
+    /// Panics if a node of the tree disagrees with the weights it covers.
+    #[cfg(any(test, feature = "testing"))]
+    pub fn check_invariants(&self) {
+        assert_eq!(self.tree.len(), self.slots.len() + 1);
+        let weights = self
+            .slots
+            .iter()
+            .map(|slot| slot.as_ref().map_or(0, |&(_, w)| w))
+            .collect::<Vec<_>>();
+        for node in 1..self.tree.len() {
+            let covered = weights[node - lowest_bit(node)..node].iter().sum::<u64>();
+            assert_eq!(self.tree[node], covered, "node {} is out of date", node);
+        }
+        assert_eq!(
+            weights.iter().sum::<u64>(),
+            self.total,
+            "total is out of date"
+        );
+        assert_eq!(self.iter().count(), self.len, "length is out of date");
+        assert_eq!(self.free.len() + self.len, self.slots.len());
+    }
+}
+
+fn lowest_bit(node: usize) -> usize {
+    node & node.wrapping_neg()
+}
+
+impl<T> Default for WeightedIndexTree<T> {
+    fn default() -> Self {
+        WeightedIndexTree::new()
+    }
+}
+
+impl<T> FromIterator<(T, u64)> for WeightedIndexTree<T> {
+    fn from_iter<I: IntoIterator<Item = (T, u64)>>(iter: I) -> Self {
+        let mut tree = WeightedIndexTree::new();
+        for (item, weight) in iter {
+            tree.insert(item, weight);
+        }
+        tree
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use quickcheck::quickcheck;
+
+    use super::WeightedIndexTree;
+
+    /// An rng which lands at the given fraction of the total weight.
+    fn at(fraction: f64) -> impl FnMut() -> u64 {
+        move || (fraction * u64::MAX as f64) as u64
+    }
+
+    #[test]
+    fn samples_by_weight() {
+        let mut backends = WeightedIndexTree::new();
+        let a = backends.insert("a", 1);
+        let b = backends.insert("b", 3);
+        assert_eq!(backends.total_weight(), 4);
+        assert_eq!(backends.sample(at(0.2)), Some((a, &"a")));
+        assert_eq!(backends.sample(at(0.3)), Some((b, &"b")));
+        assert_eq!(backends.update_weight(a, 0), Some(1));
+        assert_eq!(backends.sample(at(0.0)), Some((b, &"b")));
+        assert_eq!(backends.remove(b), Some("b"));
+        assert_eq!(backends.sample(at(0.5)), None);
+        // The removed index is reused
+        assert_eq!(backends.insert("c", 2), b);
+        assert_eq!(backends.sample(at(0.9)), Some((b, &"c")));
+        assert_eq!(backends.len(), 2);
+        backends.check_invariants();
+    }
+
+    #[test]
+    fn missing_indices() {
+        let mut tree = WeightedIndexTree::<()>::new();
+        assert_eq!(tree.sample(at(0.5)), None);
+        assert_eq!(tree.update_weight(0, 1), None);
+        assert_eq!(tree.remove(0), None);
+        let index = tree.insert((), 1);
+        tree.remove(index);
+        assert_eq!(tree.remove(index), None);
+        assert_eq!(tree.weight(index), None);
+    }
+
+    #[test]
+    fn prop_matches_prefix_sums() {
+        fn p(ops: Vec<(u8, u16)>, draws: Vec<u64>) -> bool {
+            let mut tree = WeightedIndexTree::new();
+            let mut model: Vec<Option<u64>> = vec![];
+            for (i, (op, weight)) in ops.into_iter().enumerate() {
+                let weight = weight as u64;
+                let index = op as usize % (model.len() + 1);
+                match op % 3 {
+                    0 => {
+                        let index = tree.insert(i, weight);
+                        if index == model.len() {
+                            model.push(None);
+                        }
+                        model[index] = Some(weight);
+                    }
+                    1 => {
+                        let old = model.get(index).copied().flatten();
+                        if tree.update_weight(index, weight) != old {
+                            return false;
+                        }
+                        if old.is_some() {
+                            model[index] = Some(weight);
+                        }
+                    }
+                    _ => {
+                        let removed = model.get_mut(index).and_then(Option::take);
+                        if tree.remove(index).is_some() != removed.is_some() {
+                            return false;
+                        }
+                    }
+                }
+            }
+            tree.check_invariants();
+            let total = model.iter().flatten().sum::<u64>();
+            draws.into_iter().all(|r| {
+                let target = ((r as u128 * total as u128) >> 64) as u64;
+                let mut sum = 0;
+                let expected = model.iter().position(|w| {
+                    sum += w.unwrap_or(0);
+                    sum > target
+                });
+                tree.sample(|| r).map(|(index, _)| index) == expected
+            })
+        }
+        quickcheck(p as fn(Vec<(u8, u16)>, Vec<u64>) -> bool);
+    }
+}
@@ -0,0 +1,266 @@
+use std::{
+    collections::VecDeque,
+    ops::{AddAssign, SubAssign},
+};
+
+use crate::monotonic::MinMaxDeque;
+
+/// A statistic maintained over the values in a `SlidingWindow`.
+/// Values leave the window in the same order they entered it.
+pub trait Aggregator<T> {
+    type Output;
+
+    fn push(&mut self, value: &T);
+
+    /// Called with the oldest value when it leaves the window.
+    fn evict(&mut self, value: &T);
+
+    fn value(&self) -> Self::Output;
+}
+
+/// A fixed-capacity window over the most recent values of a stream. Pushing onto a full
+/// window overwrites its oldest value, and the aggregator is kept up to date as values
+/// enter and leave so that it can be queried in O(1).
+#[derive(Debug, Clone)]
+pub struct SlidingWindow<T, A> {
+    // Used as a circular buffer which never grows past `capacity`
+    values: VecDeque<T>,
+    capacity: usize,
+    aggregator: A,
+}
+
+impl<T, A: Aggregator<T> + Default> SlidingWindow<T, A> {
+    pub fn new(capacity: usize) -> Self {
+        SlidingWindow::with_aggregator(capacity, A::default())
+    }
+}
+
+impl<T, A: Aggregator<T>> SlidingWindow<T, A> {
+    pub fn with_aggregator(capacity: usize, aggregator: A) -> Self {
+        assert!(capacity > 0, "window capacity must be positive");
+        SlidingWindow {
+            values: VecDeque::with_capacity(capacity),
+            capacity,
+            aggregator,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.values.len() == self.capacity
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Adds a value, returning the oldest value if it was pushed out of a full window.
+    pub fn push(&mut self, value: T) -> Option<T> {
+        let evicted = if self.is_full() { self.pop() } else { None };
+        self.aggregator.push(&value);
+        self.values.push_back(value);
+        evicted
+    }
+
+    /// Removes the oldest value.
+    pub fn pop(&mut self) -> Option<T> {
+        let value = self.values.pop_front()?;
+        self.aggregator.evict(&value);
+        Some(value)
+    }
+
+    /// Returns the values from oldest to newest.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &T> {
+        self.values.iter()
+    }
+
+    pub fn aggregate(&self) -> A::Output {
+        self.aggregator.value()
+    }
+
+    pub fn aggregator(&self) -> &A {
+        &self.aggregator
+    }
+
+    pub fn clear(&mut self) {
+        while self.pop().is_some() {}
+    }
+}
+
+/// The sum of the values in the window.
+#[derive(Debug, Clone, Default)]
+pub struct Sum<T> {
+    total: T,
+}
+
+impl<T: Copy + AddAssign + SubAssign> Aggregator<T> for Sum<T> {
+    type Output = T;
+
+    fn push(&mut self, value: &T) {
+        self.total += *value;
+    }
+
+    fn evict(&mut self, value: &T) {
+        self.total -= *value;
+    }
+
+    fn value(&self) -> T {
+        self.total
+    }
+}
+
+/// The arithmetic mean of the values in the window, or None if it is empty.
+#[derive(Debug, Clone, Default)]
+pub struct Mean {
+    total: f64,
+    count: usize,
+}
+
+impl<T: Copy + Into<f64>> Aggregator<T> for Mean {
+    type Output = Option<f64>;
+
+    fn push(&mut self, value: &T) {
+        self.total += (*value).into();
+        self.count += 1;
+    }
+
+    fn evict(&mut self, value: &T) {
+        self.total -= (*value).into();
+        self.count -= 1;
+    }
+
+    fn value(&self) -> Option<f64> {
+        (self.count > 0).then(|| self.total / self.count as f64)
+    }
+}
+
+/// The smallest value in the window.
+#[derive(Debug, Clone)]
+pub struct Min<T> {
+    values: MinMaxDeque<T>,
+}
+
+/// The largest value in the window.
+#[derive(Debug, Clone)]
+pub struct Max<T> {
+    values: MinMaxDeque<T>,
+}
+
+impl<T> Default for Min<T> {
+    fn default() -> Self {
+        Min {
+            values: MinMaxDeque::new(),
+        }
+    }
+}
+
+impl<T> Default for Max<T> {
+    fn default() -> Self {
+        Max {
+            values: MinMaxDeque::new(),
+        }
+    }
+}
+
+impl<T: Ord + Clone> Aggregator<T> for Min<T> {
+    type Output = Option<T>;
+
+    fn push(&mut self, value: &T) {
+        self.values.push_back(value.clone());
+    }
+
+    fn evict(&mut self, _: &T) {
+        self.values.pop_front();
+    }
+
+    fn value(&self) -> Option<T> {
+        self.values.min().cloned()
+    }
+}
+
+impl<T: Ord + Clone> Aggregator<T> for Max<T> {
+    type Output = Option<T>;
+
+    fn push(&mut self, value: &T) {
+        self.values.push_back(value.clone());
+    }
+
+    fn evict(&mut self, _: &T) {
+        self.values.pop_front();
+    }
+
+    fn value(&self) -> Option<T> {
+        self.values.max().cloned()
+    }
+}
+
+/// Maintains two aggregates at once. Nest pairs to combine more.
+impl<T, A: Aggregator<T>, B: Aggregator<T>> Aggregator<T> for (A, B) {
+    type Output = (A::Output, B::Output);
+
+    fn push(&mut self, value: &T) {
+        self.0.push(value);
+        self.1.push(value);
+    }
+
+    fn evict(&mut self, value: &T) {
+        self.0.evict(value);
+        self.1.evict(value);
+    }
+
+    fn value(&self) -> Self::Output {
+        (self.0.value(), self.1.value())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use quickcheck::quickcheck;
+
+    use super::{Max, Mean, Min, SlidingWindow, Sum};
+
+    #[test]
+    fn overwrites_oldest() {
+        let mut window = SlidingWindow::<i32, Sum<i32>>::new(3);
+        assert_eq!(window.push(1), None);
+        window.push(2);
+        window.push(3);
+        assert!(window.is_full());
+        assert_eq!(window.push(4), Some(1));
+        assert_eq!(window.iter().copied().collect::<Vec<_>>(), vec![2, 3, 4]);
+        assert_eq!(window.aggregate(), 9);
+        window.clear();
+        assert_eq!(window.aggregate(), 0);
+    }
+
+    #[test]
+    fn mean() {
+        let mut window = SlidingWindow::<u32, Mean>::new(2);
+        assert_eq!(window.aggregate(), None);
+        for x in [1, 2, 6] {
+            window.push(x);
+        }
+        assert_eq!(window.aggregate(), Some(4.0));
+    }
+
+    #[test]
+    fn prop_min_max_match_scan() {
+        fn p(values: Vec<i32>, capacity: u8) -> bool {
+            let capacity = capacity as usize % 8 + 1;
+            let mut window = SlidingWindow::<_, (Min<_>, Max<_>)>::new(capacity);
+            values.iter().enumerate().all(|(i, &x)| {
+                window.push(x);
+                let recent = &values[(i + 1).saturating_sub(capacity)..=i];
+                window.aggregate() == (recent.iter().min().copied(), recent.iter().max().copied())
+            })
+        }
+        quickcheck(p as fn(Vec<i32>, u8) -> bool)
+    }
+}